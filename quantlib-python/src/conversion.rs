@@ -0,0 +1,122 @@
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDateTime, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple};
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+create_exception!(quantlib_python, QuantlibError, PyException, "Error from quantlib with the chain of its contexts.");
+
+/// anyhow errors are raised as QuantlibError with all the contexts ("{:#}")
+pub fn to_py_err(error: anyhow::Error) -> PyErr {
+    QuantlibError::new_err(format!("{:#}", error))
+}
+
+pub trait IntoPyResult<T> {
+    fn into_py_result(self) -> PyResult<T>;
+}
+
+impl<T> IntoPyResult<T> for anyhow::Result<T> {
+    fn into_py_result(self) -> PyResult<T> {
+        self.map_err(to_py_err)
+    }
+}
+
+/// Python objects to JSON to be deserialized by serde as in the trade and configuration files.
+/// datetime (with tzinfo) becomes the serde form of OffsetDateTime, and objects having tolist (e.g., NumPy arrays) are lists.
+pub fn py_to_json(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if obj.is_none() {
+        return Ok(Value::Null);
+    }
+    // bool is a subclass of int in Python
+    if obj.is_instance_of::<PyBool>() {
+        return Ok(Value::Bool(obj.extract::<bool>()?));
+    }
+    if obj.is_instance_of::<PyInt>() {
+        return Ok(Value::Number(Number::from(obj.extract::<i64>()?)));
+    }
+    if obj.is_instance_of::<PyFloat>() {
+        let value = obj.extract::<f64>()?;
+        return Number::from_f64(value)
+            .map(Value::Number)
+            .ok_or_else(|| PyTypeError::new_err(format!("{} can not be in JSON", value)));
+    }
+    if obj.is_instance_of::<PyString>() {
+        return Ok(Value::String(obj.extract::<String>()?));
+    }
+    if obj.is_instance_of::<PyDateTime>() {
+        let datetime = obj.extract::<OffsetDateTime>()?;
+        return serde_json::to_value(datetime).map_err(|e| PyTypeError::new_err(e.to_string()));
+    }
+    if let Ok(dict) = obj.cast::<PyDict>() {
+        let mut map = Map::new();
+        for (key, value) in dict.iter() {
+            let key = key.extract::<String>()
+                .map_err(|_| PyTypeError::new_err(format!("dict keys must be str, got {}", key)))?;
+            map.insert(key, py_to_json(&value)?);
+        }
+        return Ok(Value::Object(map));
+    }
+    if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
+        let values = obj.try_iter()?
+            .map(|item| py_to_json(&item?))
+            .collect::<PyResult<Vec<_>>>()?;
+        return Ok(Value::Array(values));
+    }
+    if obj.hasattr("tolist")? {
+        return py_to_json(&obj.call_method0("tolist")?);
+    }
+    Err(PyTypeError::new_err(format!("{} can not be converted to JSON", obj.get_type().name()?)))
+}
+
+pub fn json_to_py<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    let res = match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(b) => PyBool::new(py, *b).to_owned().into_any(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into_pyobject(py)?.into_any(),
+            None => n.as_f64().unwrap_or(f64::NAN).into_pyobject(py)?.into_any(),
+        },
+        Value::String(s) => PyString::new(py, s).into_any(),
+        Value::Array(values) => {
+            let list = PyList::empty(py);
+            for value in values {
+                list.append(json_to_py(py, value)?)?;
+            }
+            list.into_any()
+        },
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, value) in map {
+                dict.set_item(key, json_to_py(py, value)?)?;
+            }
+            dict.into_any()
+        },
+    };
+    Ok(res)
+}
+
+/// dict (or keyword arguments) deserialized by serde
+pub fn from_py_dict<T: serde::de::DeserializeOwned>(dict: Option<&Bound<'_, PyDict>>, what: &str) -> PyResult<T> {
+    let value = match dict {
+        Some(dict) => py_to_json(dict.as_any())?,
+        None => Value::Object(Map::new()),
+    };
+    serde_json::from_value(value)
+        .map_err(|e| QuantlibError::new_err(format!("invalid {}: {}", what, e)))
+}
+
+pub fn to_py_dict<'py, T: serde::Serialize>(py: Python<'py>, value: &T) -> PyResult<Bound<'py, PyAny>> {
+    let value = serde_json::to_value(value)
+        .map_err(|e| QuantlibError::new_err(e.to_string()))?;
+    json_to_py(py, &value)
+}
+
+pub fn real_map_to_py<'py, K: ToString>(py: Python<'py>, map: &HashMap<K, f32>) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    for (key, value) in map {
+        dict.set_item(key.to_string(), *value as f64)?;
+    }
+    Ok(dict)
+}
@@ -0,0 +1,100 @@
+use crate::conversion::{IntoPyResult, QuantlibError};
+use numpy::{PyArray1, ToPyArray};
+use pyo3::prelude::*;
+use quantlib::currency::Currency;
+use quantlib::data::vector_data::VectorData;
+use quantlib::enums::Compounding;
+use quantlib::evaluation_date::EvaluationDate;
+use quantlib::parameters::zero_curve::ZeroCurve;
+use quantlib::definitions::Real;
+use ndarray::Array1;
+use std::cell::RefCell;
+use std::rc::Rc;
+use time::OffsetDateTime;
+
+pub fn parse_currency(currency: &str) -> PyResult<Currency> {
+    serde_json::from_value(serde_json::Value::String(currency.to_string()))
+        .map_err(|_| QuantlibError::new_err(format!("unknown currency {}", currency)))
+}
+
+fn parse_compounding(compounding: &str) -> PyResult<Compounding> {
+    match compounding.to_ascii_lowercase().as_str() {
+        "continuous" => Ok(Compounding::Continuous),
+        "simple" => Ok(Compounding::Simple),
+        _ => Err(QuantlibError::new_err(format!("unknown compounding {} (continuous or simple)", compounding))),
+    }
+}
+
+/// ZeroCurve(evaluation_datetime, dates, rates, code, currency="KRW"): zero rates (continuous) at the dates
+#[pyclass(name = "ZeroCurve", unsendable)]
+pub struct PyZeroCurve {
+    curve: ZeroCurve,
+    evaluation_datetime: OffsetDateTime,
+}
+
+#[pymethods]
+impl PyZeroCurve {
+    #[new]
+    #[pyo3(signature = (evaluation_datetime, dates, rates, code, currency = "KRW"))]
+    fn new(
+        evaluation_datetime: OffsetDateTime,
+        dates: Vec<OffsetDateTime>,
+        rates: Vec<f64>,
+        code: String,
+        currency: &str,
+    ) -> PyResult<PyZeroCurve> {
+        let data = VectorData::new(
+            Array1::from_iter(rates.iter().map(|r| *r as Real)),
+            Some(dates),
+            None,
+            Some(evaluation_datetime),
+            parse_currency(currency)?,
+            code.clone(),
+            code.clone(),
+        ).into_py_result()?;
+        let evaluation_date = Rc::new(RefCell::new(EvaluationDate::new(evaluation_datetime)));
+        let curve = ZeroCurve::new(evaluation_date, &data, code.clone(), code).into_py_result()?;
+        Ok(PyZeroCurve { curve, evaluation_datetime })
+    }
+
+    #[getter]
+    fn code(&self) -> String {
+        self.curve.get_code().clone()
+    }
+
+    #[getter]
+    fn evaluation_datetime(&self) -> OffsetDateTime {
+        self.evaluation_datetime
+    }
+
+    fn discount_factor(&self, date: OffsetDateTime) -> PyResult<f64> {
+        Ok(self.curve.get_discount_factor_at_date(&date).into_py_result()? as f64)
+    }
+
+    /// discount factors of the dates (in ascending order) as a NumPy array
+    fn discount_factors<'py>(&self, py: Python<'py>, dates: Vec<OffsetDateTime>) -> PyResult<Bound<'py, PyArray1<Real>>> {
+        if dates.windows(2).any(|w| w[0] > w[1]) {
+            return Err(QuantlibError::new_err("dates must be in ascending order"));
+        }
+        let res = self.curve.get_vectorized_discount_factor_for_sorted_dates(&dates).into_py_result()?;
+        Ok(res.to_pyarray(py))
+    }
+
+    /// forward rate between the dates, compounding = "continuous" or "simple"
+    #[pyo3(signature = (date1, date2, compounding = "continuous"))]
+    fn forward_rate(&self, date1: OffsetDateTime, date2: OffsetDateTime, compounding: &str) -> PyResult<f64> {
+        let compounding = parse_compounding(compounding)?;
+        Ok(self.curve.get_forward_rate_between_dates(&date1, &date2, compounding).into_py_result()? as f64)
+    }
+
+    /// zero rate from the evaluation date to the date
+    #[pyo3(signature = (date, compounding = "continuous"))]
+    fn zero_rate(&self, date: OffsetDateTime, compounding: &str) -> PyResult<f64> {
+        let compounding = parse_compounding(compounding)?;
+        Ok(self.curve.get_forward_rate_from_evaluation_date(&date, compounding).into_py_result()? as f64)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ZeroCurve(code={}, evaluation_datetime={})", self.curve.get_code(), self.evaluation_datetime)
+    }
+}
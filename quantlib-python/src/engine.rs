@@ -0,0 +1,173 @@
+use crate::conversion::{from_py_dict, py_to_json, to_py_dict, IntoPyResult, QuantlibError};
+use crate::instrument::PyInstrument;
+use crate::result::PyCalculationResult;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use quantlib::data::{
+    market_data_snapshot::MarketDataSnapshot,
+    market_data_store::MarketDataStore,
+};
+use quantlib::instrument::Instruments;
+use quantlib::pricing_engines::{
+    calculation_configuration::CalculationConfiguration,
+    engine_generator::{EngineGenerator, InstrumentCategory},
+    match_parameter::MatchParameter,
+};
+use std::collections::HashMap;
+use std::rc::Rc;
+use time::OffsetDateTime;
+
+/// CalculationConfiguration(npv=True, delta=True, ...): the fields not given are those of the default
+#[pyclass(name = "CalculationConfiguration", unsendable)]
+#[derive(Clone)]
+pub struct PyCalculationConfiguration {
+    pub configuration: CalculationConfiguration,
+}
+
+#[pymethods]
+impl PyCalculationConfiguration {
+    #[new]
+    #[pyo3(signature = (**fields))]
+    fn new(fields: Option<&Bound<'_, PyDict>>) -> PyResult<PyCalculationConfiguration> {
+        let configuration = from_py_dict(fields, "calculation configuration")?;
+        Ok(PyCalculationConfiguration { configuration })
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_py_dict(py, &self.configuration)
+    }
+}
+
+/// MatchParameter(collateral_curve_map={...}, borrowing_curve_map={...}, ...) as in the run configuration files
+#[pyclass(name = "MatchParameter", unsendable)]
+#[derive(Clone)]
+pub struct PyMatchParameter {
+    pub match_parameter: MatchParameter,
+}
+
+#[pymethods]
+impl PyMatchParameter {
+    #[new]
+    #[pyo3(signature = (**fields))]
+    fn new(fields: Option<&Bound<'_, PyDict>>) -> PyResult<PyMatchParameter> {
+        let match_parameter = from_py_dict(fields, "match parameter")?;
+        Ok(PyMatchParameter { match_parameter })
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_py_dict(py, &self.match_parameter)
+    }
+}
+
+#[pyclass(name = "MarketDataSnapshot", unsendable)]
+#[derive(Clone)]
+pub struct PyMarketDataSnapshot {
+    pub snapshot: MarketDataSnapshot,
+}
+
+#[pymethods]
+impl PyMarketDataSnapshot {
+    /// a snapshot as saved by MarketDataStore
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<PyMarketDataSnapshot> {
+        let snapshot = serde_json::from_str(json)
+            .map_err(|e| QuantlibError::new_err(format!("invalid snapshot: {}", e)))?;
+        Ok(PyMarketDataSnapshot { snapshot })
+    }
+
+    #[staticmethod]
+    fn from_dict(data: &Bound<'_, PyDict>) -> PyResult<PyMarketDataSnapshot> {
+        let snapshot = serde_json::from_value(py_to_json(data.as_any())?)
+            .map_err(|e| QuantlibError::new_err(format!("invalid snapshot: {}", e)))?;
+        Ok(PyMarketDataSnapshot { snapshot })
+    }
+
+    /// the snapshot as of the datetime in the MarketDataStore directory
+    #[staticmethod]
+    fn from_store(root: &str, as_of: OffsetDateTime) -> PyResult<PyMarketDataSnapshot> {
+        let snapshot = MarketDataStore::new(root).load_as_of(as_of).into_py_result()?;
+        Ok(PyMarketDataSnapshot { snapshot })
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(&self.snapshot).map_err(|e| QuantlibError::new_err(e.to_string()))
+    }
+
+    #[getter]
+    fn as_of(&self) -> OffsetDateTime {
+        *self.snapshot.get_as_of()
+    }
+}
+
+/// EngineGenerator(calculation_configuration, evaluation_datetime, match_parameter=None).
+/// set_instruments and set_market_data, then calculate gives {code: CalculationResult}.
+#[pyclass(name = "EngineGenerator", unsendable)]
+pub struct PyEngineGenerator {
+    calculation_configuration: CalculationConfiguration,
+    evaluation_datetime: OffsetDateTime,
+    match_parameter: MatchParameter,
+    instruments: Instruments,
+    instrument_categories: Vec<InstrumentCategory>,
+    snapshot: Option<MarketDataSnapshot>,
+}
+
+#[pymethods]
+impl PyEngineGenerator {
+    #[new]
+    #[pyo3(signature = (calculation_configuration, evaluation_datetime, match_parameter = None))]
+    fn new(
+        calculation_configuration: &PyCalculationConfiguration,
+        evaluation_datetime: OffsetDateTime,
+        match_parameter: Option<&PyMatchParameter>,
+    ) -> PyEngineGenerator {
+        PyEngineGenerator {
+            calculation_configuration: calculation_configuration.configuration.clone(),
+            evaluation_datetime,
+            match_parameter: match_parameter.map(|m| m.match_parameter.clone()).unwrap_or_default(),
+            instruments: Instruments::default(),
+            instrument_categories: vec![],
+            snapshot: None,
+        }
+    }
+
+    fn set_instruments(&mut self, instruments: Vec<PyRef<'_, PyInstrument>>) {
+        let instruments = instruments.iter().map(|i| Rc::clone(&i.instrument)).collect();
+        self.instruments = Instruments::new(instruments);
+    }
+
+    /// [{"type_names": [...], "currency": [...], "underlying_codes": [...]}, ...]; empty means one category
+    fn set_instrument_categories(&mut self, categories: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.instrument_categories = serde_json::from_value(py_to_json(categories)?)
+            .map_err(|e| QuantlibError::new_err(format!("invalid instrument categories: {}", e)))?;
+        Ok(())
+    }
+
+    fn set_market_data(&mut self, snapshot: &PyMarketDataSnapshot) {
+        self.snapshot = Some(snapshot.snapshot.clone());
+    }
+
+    fn calculate(&self) -> PyResult<HashMap<String, PyCalculationResult>> {
+        let snapshot = self.snapshot.clone()
+            .ok_or_else(|| QuantlibError::new_err("no market data (set_market_data)"))?;
+        let instrument_categories = match self.instrument_categories.is_empty() {
+            true => vec![InstrumentCategory::default()],
+            false => self.instrument_categories.clone(),
+        };
+        let mut engine_generator = EngineGenerator::builder();
+        engine_generator
+            .with_configuration(
+                self.calculation_configuration.clone(),
+                self.evaluation_datetime,
+                self.match_parameter.clone(),
+            ).into_py_result()?
+            .with_instruments(self.instruments.clone()).into_py_result()?
+            .with_instrument_categories(instrument_categories).into_py_result()?
+            .with_market_data_snapshot(snapshot).into_py_result()?;
+        engine_generator.distribute_instruments().into_py_result()?;
+        engine_generator.calculate().into_py_result()?;
+        let results = engine_generator.get_calculation_results().iter()
+            .map(|(code, result)| (code.clone(), PyCalculationResult::new(result.clone(), &self.calculation_configuration)))
+            .collect();
+        Ok(results)
+    }
+}
@@ -0,0 +1,122 @@
+use crate::conversion::{py_to_json, IntoPyResult, QuantlibError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use quantlib::instrument::{Instrument, InstrumentTrait, Instruments};
+use quantlib::instruments::trade_loader::{
+    load_trades, load_trades_from_json_value, load_trades_from_str, write_trades_to_string, TradeFileFormat,
+};
+use serde_json::Value;
+use std::rc::Rc;
+use time::OffsetDateTime;
+
+/// An instrument made from the fields of a trade file (see quantlib::instruments::trade_loader),
+/// so the fields are validated as in the files.
+#[pyclass(name = "Instrument", unsendable)]
+#[derive(Clone)]
+pub struct PyInstrument {
+    pub instrument: Rc<Instrument>,
+}
+
+impl PyInstrument {
+    fn from_fields(type_name: Option<&str>, fields: Option<&Bound<'_, PyDict>>) -> PyResult<PyInstrument> {
+        let mut value = match fields {
+            Some(fields) => py_to_json(fields.as_any())?,
+            None => Value::Object(Default::default()),
+        };
+        if let (Some(type_name), Value::Object(map)) = (type_name, &mut value) {
+            map.insert("type".to_string(), Value::String(type_name.to_string()));
+        }
+        single(load_trades_from_json_value(value).into_py_result()?)
+    }
+}
+
+fn single(instruments: Instruments) -> PyResult<PyInstrument> {
+    match instruments.len() {
+        1 => Ok(PyInstrument { instrument: instruments.iter().next().expect("one instrument").clone() }),
+        n => Err(QuantlibError::new_err(format!("one trade is expected, got {}", n))),
+    }
+}
+
+pub fn to_py_instruments(instruments: &Instruments) -> Vec<PyInstrument> {
+    instruments.iter().map(|instrument| PyInstrument { instrument: instrument.clone() }).collect()
+}
+
+#[pymethods]
+impl PyInstrument {
+    /// a trade with "type" (e.g., {"type": "Futures", "code": ..., ...})
+    #[staticmethod]
+    fn from_dict(fields: &Bound<'_, PyDict>) -> PyResult<PyInstrument> {
+        PyInstrument::from_fields(None, Some(fields))
+    }
+
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<PyInstrument> {
+        single(load_trades_from_str(json, TradeFileFormat::Json).into_py_result()?)
+    }
+
+    /// Instrument.vanilla_option(code=..., strike=..., ...) with the fields of VanillaOption
+    #[staticmethod]
+    #[pyo3(signature = (**fields))]
+    fn vanilla_option(fields: Option<&Bound<'_, PyDict>>) -> PyResult<PyInstrument> {
+        PyInstrument::from_fields(Some("VanillaOption"), fields)
+    }
+
+    /// Instrument.bond(code=..., ...) with the fields of Bond
+    #[staticmethod]
+    #[pyo3(signature = (**fields))]
+    fn bond(fields: Option<&Bound<'_, PyDict>>) -> PyResult<PyInstrument> {
+        PyInstrument::from_fields(Some("Bond"), fields)
+    }
+
+    /// Instrument.plain_swap(code=..., ...) with the fields of PlainSwap (IRS, CRS, FX swap, etc.)
+    #[staticmethod]
+    #[pyo3(signature = (**fields))]
+    fn plain_swap(fields: Option<&Bound<'_, PyDict>>) -> PyResult<PyInstrument> {
+        PyInstrument::from_fields(Some("PlainSwap"), fields)
+    }
+
+    #[getter]
+    fn code(&self) -> String {
+        self.instrument.get_code().clone()
+    }
+
+    #[getter]
+    fn name(&self) -> String {
+        self.instrument.get_name().clone()
+    }
+
+    #[getter]
+    fn type_name(&self) -> &'static str {
+        self.instrument.get_type_name()
+    }
+
+    #[getter]
+    fn currency(&self) -> &'static str {
+        self.instrument.get_currency().as_str()
+    }
+
+    #[getter]
+    fn unit_notional(&self) -> f64 {
+        self.instrument.get_unit_notional() as f64
+    }
+
+    #[getter]
+    fn maturity(&self) -> Option<OffsetDateTime> {
+        self.instrument.get_maturity().copied()
+    }
+
+    /// the trade in the format of the trade files, which from_json reads back
+    fn to_json(&self) -> PyResult<String> {
+        write_trades_to_string(&Instruments::new(vec![self.instrument.clone()]), TradeFileFormat::Json).into_py_result()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Instrument(type={}, code={})", self.instrument.get_type_name(), self.instrument.get_code())
+    }
+}
+
+/// trades of a json, yaml or csv file
+#[pyfunction(name = "load_trades")]
+pub fn py_load_trades(path: &str) -> PyResult<Vec<PyInstrument>> {
+    Ok(to_py_instruments(&load_trades(path).into_py_result()?))
+}
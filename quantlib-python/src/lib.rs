@@ -0,0 +1,137 @@
+//! Python bindings of quantlib: zero curves, instruments (as in the trade files),
+//! market data snapshots, the engine generator and its calculation results.
+//! Errors of quantlib are raised as quantlib_python.QuantlibError.
+pub mod conversion;
+pub mod curve;
+pub mod engine;
+pub mod instrument;
+pub mod result;
+
+use pyo3::prelude::*;
+
+#[pymodule]
+fn quantlib_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("QuantlibError", m.py().get_type::<conversion::QuantlibError>())?;
+    m.add_class::<curve::PyZeroCurve>()?;
+    m.add_class::<instrument::PyInstrument>()?;
+    m.add_class::<engine::PyCalculationConfiguration>()?;
+    m.add_class::<engine::PyMatchParameter>()?;
+    m.add_class::<engine::PyMarketDataSnapshot>()?;
+    m.add_class::<engine::PyEngineGenerator>()?;
+    m.add_class::<result::PyCalculationResult>()?;
+    m.add_function(wrap_pyfunction!(instrument::py_load_trades, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use ndarray::Array1;
+    use pyo3::types::PyDict;
+    use quantlib::currency::Currency;
+    use quantlib::data::{market_data_snapshot::MarketDataSnapshot, value_data::ValueData, vector_data::VectorData};
+    use quantlib::instrument::{Instrument, Instruments};
+    use quantlib::instruments::{futures::Futures, trade_loader::{write_trades_to_string, TradeFileFormat}};
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use time::macros::datetime;
+
+    fn snapshot_json() -> Result<String> {
+        let as_of = datetime!(2024-01-02 15:40:00 +09:00);
+        let stock = ValueData::new(350.0, Some(as_of), Currency::KRW, "KOSPI2".to_string(), "KOSPI2".to_string())?;
+        let mut curves = HashMap::new();
+        for code in ["KSD", "KOSPI2"] {
+            let data = VectorData::new(
+                Array1::from(vec![0.0345, 0.0345]),
+                Some(vec![datetime!(2025-01-02 16:30:00 +09:00), datetime!(2026-01-02 16:30:00 +09:00)]),
+                None,
+                Some(as_of),
+                Currency::KRW,
+                code.to_string(),
+                code.to_string(),
+            )?;
+            curves.insert(code.to_string(), data);
+        }
+        let snapshot = MarketDataSnapshot::new(as_of)
+            .with_stock_data(HashMap::from([("KOSPI2".to_string(), stock)]))
+            .with_curve_data(curves);
+        Ok(serde_json::to_string(&snapshot)?)
+    }
+
+    fn trades_json() -> Result<String> {
+        let futures = Futures::new(
+            320.0,
+            datetime!(2023-09-15 09:00:00 +09:00),
+            datetime!(2024-03-14 15:20:00 +09:00),
+            datetime!(2024-03-14 15:20:00 +09:00),
+            datetime!(2024-03-14 15:20:00 +09:00),
+            250_000.0,
+            Currency::KRW,
+            Currency::KRW,
+            "KOSPI2".to_string(),
+            "KOSPI2 Fut Mar24".to_string(),
+            "165XXX".to_string(),
+        );
+        write_trades_to_string(&Instruments::new(vec![Rc::new(Instrument::Futures(futures))]), TradeFileFormat::Json)
+    }
+
+    #[test]
+    fn test_python_pricing() -> Result<()> {
+        let (snapshot_json, trades_json) = (snapshot_json()?, trades_json()?);
+        Python::attach(|py| -> PyResult<()> {
+            let globals = PyDict::new(py);
+            globals.set_item("ql", pyo3::wrap_pymodule!(quantlib_python)(py))?;
+            globals.set_item("snapshot_json", snapshot_json)?;
+            globals.set_item("trades_json", trades_json)?;
+            py.run(c"
+import datetime, json
+kst = datetime.timezone(datetime.timedelta(hours=9))
+evaluation_datetime = datetime.datetime(2024, 1, 2, 16, 30, tzinfo=kst)
+
+curve = ql.ZeroCurve(
+    evaluation_datetime,
+    [datetime.datetime(2025, 1, 2, 16, 30, tzinfo=kst), datetime.datetime(2026, 1, 2, 16, 30, tzinfo=kst)],
+    [0.0345, 0.0345],
+    'KSD',
+)
+df = curve.discount_factor(datetime.datetime(2025, 1, 2, 16, 30, tzinfo=kst))
+assert abs(df - 0.966) < 1e-3, df
+assert abs(curve.zero_rate(datetime.datetime(2025, 7, 2, 16, 30, tzinfo=kst)) - 0.0345) < 1e-4
+
+futures = ql.Instrument.from_json(trades_json)
+assert futures.code == '165XXX' and futures.type_name == 'Futures', futures
+# the dict of a trade goes through the trade loader as the files do
+trade = json.loads(trades_json)[0]
+assert ql.Instrument.from_dict(trade).code == '165XXX'
+
+snapshot = ql.MarketDataSnapshot.from_json(snapshot_json)
+config = ql.CalculationConfiguration(npv=True, delta=True)
+match_parameter = ql.MatchParameter(
+    collateral_curve_map={'KOSPI2': 'KSD'},
+    borrowing_curve_map={'KOSPI2': 'KOSPI2'},
+)
+generator = ql.EngineGenerator(config, evaluation_datetime, match_parameter)
+generator.set_instruments([futures])
+generator.set_market_data(snapshot)
+results = generator.calculate()
+result = results['165XXX']
+assert result.npv is not None and abs(result.npv - 350.0) < 5.0, result
+result_dict = result.to_dict()
+assert result_dict['evaluation_date'] == evaluation_datetime
+assert result_dict['instrument_info']['code'] == '165XXX'
+assert 'delta' in result_dict
+assert any(row['risk_type'] == 'delta' for row in result.to_rows())
+
+bad_trade = dict(trade)
+del bad_trade['maturity']
+try:
+    ql.Instrument.from_dict(bad_trade)
+    raise AssertionError('the trade without maturity is loaded')
+except ql.QuantlibError:
+    pass
+", Some(&globals), None)
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use crate::conversion::{real_map_to_py, to_py_dict, IntoPyResult};
+use numpy::{PyArray1, ToPyArray};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use quantlib::definitions::Real;
+use quantlib::pricing_engines::{
+    calculation_configuration::CalculationConfiguration,
+    calculation_result::CalculationResult,
+    result_export::flatten_results,
+};
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+/// A CalculationResult with the configuration for the tenors of the structures and the moneyness of vega_matrix.
+#[pyclass(name = "CalculationResult", unsendable)]
+#[derive(Clone)]
+pub struct PyCalculationResult {
+    result: CalculationResult,
+    configuration: CalculationConfiguration,
+}
+
+impl PyCalculationResult {
+    pub fn new(result: CalculationResult, configuration: &CalculationConfiguration) -> PyCalculationResult {
+        PyCalculationResult { result, configuration: configuration.clone() }
+    }
+}
+
+/// {key: list or NumPy array}
+fn structure_to_py<'py>(py: Python<'py>, map: &HashMap<String, Vec<Real>>, numpy: bool) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    for (key, values) in map {
+        match numpy {
+            true => dict.set_item(key, PyArray1::from_slice(py, values))?,
+            false => dict.set_item(key, values.iter().map(|v| *v as f64).collect::<Vec<_>>())?,
+        }
+    }
+    Ok(dict)
+}
+
+fn nested_to_py<'py>(py: Python<'py>, map: &HashMap<String, HashMap<String, Real>>) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    for (key, inner) in map {
+        dict.set_item(key, real_map_to_py(py, inner)?)?;
+    }
+    Ok(dict)
+}
+
+#[pymethods]
+impl PyCalculationResult {
+    #[getter]
+    fn code(&self) -> Option<String> {
+        self.result.get_instrument_info().map(|info| info.get_code().clone())
+    }
+
+    #[getter]
+    fn evaluation_date(&self) -> Option<OffsetDateTime> {
+        self.result.get_evaluation_date().copied()
+    }
+
+    #[getter]
+    fn npv(&self) -> Option<f64> {
+        self.result.get_npv_result().map(|npv| npv.get_npv() as f64)
+    }
+
+    #[getter]
+    fn value(&self) -> Option<f64> {
+        self.result.get_value().map(|v| v as f64)
+    }
+
+    /// vega_matrix of the underlying as a NumPy array (tenors x moneyness)
+    fn vega_matrix<'py>(&self, py: Python<'py>, und_code: &str) -> Option<Bound<'py, numpy::PyArray2<Real>>> {
+        self.result.get_vega_matrix()
+            .and_then(|matrix| matrix.get(und_code))
+            .map(|matrix| matrix.to_pyarray(py))
+    }
+
+    /// The calculated measures as a dict with the keys of the fields, where the datetimes are datetime.
+    /// With numpy=True, the structures (e.g., rho_structure) and vega_matrix are NumPy arrays, otherwise lists.
+    #[pyo3(signature = (numpy = false))]
+    fn to_dict<'py>(&self, py: Python<'py>, numpy: bool) -> PyResult<Bound<'py, PyDict>> {
+        let result = &self.result;
+        let dict = PyDict::new(py);
+        if let Some(info) = result.get_instrument_info() {
+            dict.set_item("instrument_info", to_py_dict(py, info)?)?;
+        }
+        dict.set_item("evaluation_date", result.get_evaluation_date().copied())?;
+        for (key, value) in [
+            ("npv", result.get_npv_result().map(|npv| npv.get_npv())),
+            ("value", result.get_value()),
+            ("theta", result.get_theta()),
+            ("implied_volatility", result.get_implied_volatility()),
+        ] {
+            if let Some(value) = value {
+                dict.set_item(key, value as f64)?;
+            }
+        }
+        if let Some(fx_exposure) = result.get_fx_exposure() {
+            let fx_exposure = fx_exposure.iter()
+                .map(|(currency, v)| (currency.as_str().to_string(), *v))
+                .collect::<HashMap<_, _>>();
+            dict.set_item("fx_exposure", real_map_to_py(py, &fx_exposure)?)?;
+        }
+        for (key, map) in [
+            ("delta", result.get_delta()),
+            ("gamma", result.get_gamma()),
+            ("vega", result.get_vega()),
+            ("rho", result.get_rho()),
+            ("div_delta", result.get_div_delta()),
+            ("volga", result.get_volga()),
+            ("fx_delta", result.get_fx_delta()),
+            ("fx_gamma", result.get_fx_gamma()),
+            ("fx_vega", result.get_fx_vega()),
+            ("scenario_pnl", result.get_scenario_pnl()),
+            ("curvature_up_pnl", result.get_curvature_up_pnl()),
+            ("curvature_down_pnl", result.get_curvature_down_pnl()),
+        ] {
+            if let Some(map) = map {
+                dict.set_item(key, real_map_to_py(py, map)?)?;
+            }
+        }
+        for (key, map) in [
+            ("vega_structure", result.get_vega_structure()),
+            ("rho_structure", result.get_rho_structure()),
+            ("div_structure", result.get_div_structure()),
+        ] {
+            if let Some(map) = map {
+                dict.set_item(key, structure_to_py(py, map, numpy)?)?;
+            }
+        }
+        for (key, map) in [
+            ("cross_gamma", result.get_cross_gamma()),
+            ("vanna", result.get_vanna()),
+            ("quanto_correlation", result.get_quanto_correlation()),
+        ] {
+            if let Some(map) = map {
+                dict.set_item(key, nested_to_py(py, map)?)?;
+            }
+        }
+        if let Some(vega_matrix) = result.get_vega_matrix() {
+            let matrices = PyDict::new(py);
+            for (und_code, matrix) in vega_matrix {
+                match numpy {
+                    true => matrices.set_item(und_code, matrix.to_pyarray(py))?,
+                    false => {
+                        let rows = matrix.rows().into_iter()
+                            .map(|row| row.iter().map(|v| *v as f64).collect::<Vec<_>>())
+                            .collect::<Vec<_>>();
+                        matrices.set_item(und_code, rows)?
+                    },
+                }
+            }
+            dict.set_item("vega_matrix", matrices)?;
+        }
+        if let Some(cashflows) = result.get_cashflows() {
+            let cashflow_dict = PyDict::new(py);
+            for (date, amount) in cashflows {
+                cashflow_dict.set_item(*date, *amount as f64)?;
+            }
+            dict.set_item("cashflows", cashflow_dict)?;
+        }
+        if let Some(bond_analytics) = result.get_bond_analytics() {
+            let analytics_dict = PyDict::new(py);
+            for (bond_code, analytics) in bond_analytics {
+                let fields = PyDict::new(py);
+                fields.set_item("settlement_date", *analytics.get_settlement_date())?;
+                for (field, value) in [
+                    ("dirty_price", analytics.get_dirty_price()),
+                    ("clean_price", analytics.get_clean_price()),
+                    ("accrued_interest", analytics.get_accrued_interest()),
+                    ("yield_to_maturity", analytics.get_yield_to_maturity()),
+                    ("macaulay_duration", analytics.get_macaulay_duration()),
+                    ("modified_duration", analytics.get_modified_duration()),
+                    ("convexity", analytics.get_convexity()),
+                    ("dv01", analytics.get_dv01()),
+                    ("z_spread", analytics.get_z_spread()),
+                    ("i_spread", analytics.get_i_spread()),
+                ] {
+                    fields.set_item(field, value as f64)?;
+                }
+                analytics_dict.set_item(bond_code, fields)?;
+            }
+            dict.set_item("bond_analytics", analytics_dict)?;
+        }
+        Ok(dict)
+    }
+
+    /// rows of the long format table (see quantlib::pricing_engines::result_export), e.g., for pandas.DataFrame
+    fn to_rows<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let code = self.code().unwrap_or_default();
+        let results = HashMap::from([(code, self.result.clone())]);
+        let rows = flatten_results(&results, &self.configuration).into_py_result()?;
+        let list = PyList::empty(py);
+        for row in rows.iter() {
+            list.append(to_py_dict(py, row)?)?;
+        }
+        Ok(list)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("CalculationResult(code={}, npv={:?})", self.code().unwrap_or_default(), self.npv())
+    }
+}
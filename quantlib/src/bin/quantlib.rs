@@ -0,0 +1,73 @@
+use quantlib::pricing_engines::run_configuration::RunConfiguration;
+use quantlib::utils::tracing_timer::CustomOffsetTime;
+use anyhow::{anyhow, bail, Result};
+use tracing::Level;
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: quantlib <run-config.json> [--json <path>] [--csv <path>] [--arrow <path>] [--parquet <path>]";
+
+/// run configuration with the outputs of the command line overriding those of the file
+fn parse_args(args: &[String]) -> Result<RunConfiguration> {
+    let mut config_path = None;
+    let mut outputs = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--json" | "--csv" | "--arrow" | "--parquet" => {
+                let path = iter.next()
+                    .ok_or_else(|| anyhow!("{} needs a path\n{}", arg, USAGE))?;
+                outputs.push((arg.as_str(), path.clone()));
+            },
+            "-h" | "--help" => bail!("{}", USAGE),
+            _ if config_path.is_none() && !arg.starts_with("--") => config_path = Some(arg.clone()),
+            _ => bail!("unexpected argument {}\n{}", arg, USAGE),
+        }
+    }
+    let config_path = config_path.ok_or_else(|| anyhow!("{}", USAGE))?;
+    let mut configuration = RunConfiguration::from_json_file(&config_path)?;
+    // paths given on the command line are relative to the working directory
+    let current_directory = std::env::current_dir()?;
+    for (flag, path) in outputs {
+        let path = Some(current_directory.join(path).display().to_string());
+        match flag {
+            "--json" => configuration.output.json = path,
+            "--csv" => configuration.output.csv = path,
+            "--arrow" => configuration.output.arrow = path,
+            _ => configuration.output.parquet = path,
+        }
+    }
+    Ok(configuration)
+}
+
+fn main() -> ExitCode {
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(std::io::stderr.with_max_level(Level::INFO))
+        .with_timer(CustomOffsetTime::new(9, 0, 0))
+        .finish();
+    tracing::subscriber::set_global_default(subscriber)
+        .expect("Setting default subscriber failed");
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let summary = match parse_args(&args).and_then(|configuration| configuration.run()) {
+        Ok(summary) => summary,
+        Err(e) => {
+            eprintln!("quantlib failed: {:#}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!("trades: {}", summary.trades);
+    println!("results: {}", summary.results.len());
+    for output in summary.outputs.iter() {
+        println!("written: {}", output);
+    }
+    for error in summary.error_report.instrument_errors.iter() {
+        eprintln!("{} ({:?}): {}", error.instrument_code, error.kind, error.message);
+    }
+    if !summary.is_success() {
+        eprintln!("no results for {} trades: {}", summary.missing.len(), summary.missing.join(", "));
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,46 @@
+use quantlib::data::market_data_store::MarketDataStore;
+use quantlib::server::{serve, PricingServerState, DEFAULT_STORE};
+use quantlib::utils::tracing_timer::CustomOffsetTime;
+use anyhow::{anyhow, bail, Result};
+use std::sync::Arc;
+use time::OffsetDateTime;
+use tracing::{info, Level};
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+
+const USAGE: &str = "usage: quantlib-server [--bind <address>] [--store <directory>]";
+
+/// With --store, the latest snapshot of the store is loaded as "default" at start,
+/// and POST /snapshots/{name}/load reads the store.
+#[tokio::main]
+async fn main() -> Result<()> {
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(std::io::stderr.with_max_level(Level::INFO))
+        .with_timer(CustomOffsetTime::new(9, 0, 0))
+        .finish();
+    tracing::subscriber::set_global_default(subscriber)
+        .expect("Setting default subscriber failed");
+
+    let mut bind = "127.0.0.1:8080".to_string();
+    let mut store = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bind" => bind = args.next().ok_or_else(|| anyhow!("--bind needs an address\n{}", USAGE))?,
+            "--store" => store = Some(args.next().ok_or_else(|| anyhow!("--store needs a directory\n{}", USAGE))?),
+            _ => bail!("unexpected argument {}\n{}", arg, USAGE),
+        }
+    }
+
+    let mut state = PricingServerState::new();
+    if let Some(store) = store {
+        state = state.with_store(DEFAULT_STORE, MarketDataStore::new(&store));
+    }
+    if let Some(store) = state.get_store(DEFAULT_STORE) {
+        let snapshot = store.load_as_of(OffsetDateTime::now_utc())?;
+        info!("snapshot default as of {} is loaded from {}", snapshot.get_as_of(), store.get_root().display());
+        state.insert_snapshot("default", snapshot);
+    }
+    let state = Arc::new(state);
+    let listener = tokio::net::TcpListener::bind(&bind).await?;
+    serve(listener, state).await
+}
@@ -1,6 +1,12 @@
 use std::hash::Hash;
 use std::fmt::Display;
-use serde::{Deserialize, Serialize, Serializer};
+use serde::{
+    de::{Error, IntoDeserializer},
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Currency {
@@ -176,7 +182,7 @@ impl Currency {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, PartialEq, Eq, Hash, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
 pub struct FxCode {
     currency1: Currency,
     currency2: Currency,
@@ -192,6 +198,34 @@ impl Serialize for FxCode {
     }
 }
 
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FxCodeRepr {
+    Code(String),
+    Fields { currency1: Currency, currency2: Currency },
+}
+
+/// "USD_KRW" as serialized, "USDKRW", or {"currency1": "USD", "currency2": "KRW"}
+impl<'de> Deserialize<'de> for FxCode {
+    fn deserialize<D>(deserializer: D) -> Result<FxCode, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match FxCodeRepr::deserialize(deserializer)? {
+            FxCodeRepr::Fields { currency1, currency2 } => Ok(FxCode { currency1, currency2 }),
+            FxCodeRepr::Code(code) => {
+                let letters = code.replace('_', "");
+                if letters.len() != 6 || !letters.is_ascii() {
+                    return Err(D::Error::custom(format!("invalid fx code {} (e.g., USD_KRW or USDKRW)", code)));
+                }
+                let currency1 = Currency::deserialize(letters[0..3].into_deserializer())?;
+                let currency2 = Currency::deserialize(letters[3..6].into_deserializer())?;
+                Ok(FxCode { currency1, currency2 })
+            },
+        }
+    }
+}
+
 impl FxCode {
     pub fn new(currency1: Currency, currency2: Currency) -> FxCode {
         FxCode {
@@ -271,6 +305,17 @@ mod tests {
         assert_eq!(deserialized, currency);
     }
 
+    #[test]
+    fn test_fxcode_serialization() {
+        let fx_code = FxCode::new(Currency::USD, Currency::KRW);
+        let serialized = to_string(&fx_code).unwrap();
+        assert_eq!(serialized, "\"USD_KRW\"");
+        assert_eq!(from_str::<FxCode>(&serialized).unwrap(), fx_code);
+        assert_eq!(from_str::<FxCode>("\"USDKRW\"").unwrap(), fx_code);
+        assert_eq!(from_str::<FxCode>(r#"{"currency1": "USD", "currency2": "KRW"}"#).unwrap(), fx_code);
+        assert!(from_str::<FxCode>("\"USD_XXX\"").is_err());
+    }
+
     #[test] // test for as_str
     fn test_currency_as_str() {
         let currency = Currency::KRW;
@@ -1,4 +1,4 @@
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
 use std::collections::HashMap;
 use time::{Date, Time, UtcOffset};
 use crate::definitions::Real;
@@ -8,8 +8,10 @@ use crate::time::calendars::southkorea::{
     SouthKoreaType,
 };
 
+/// The values are serialized as (date, value) pairs in date order since dates can not be keys of JSON objects.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DailyValueData {
+    #[serde(serialize_with = "serialize_daily_values", deserialize_with = "deserialize_daily_values")]
     value: HashMap<Date, Real>,
     close_time: Time,
     utc_offset: UtcOffset,
@@ -18,6 +20,17 @@ pub struct DailyValueData {
     code: String,
 }
 
+fn serialize_daily_values<S: Serializer>(value: &HashMap<Date, Real>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut pairs = value.iter().collect::<Vec<_>>();
+    pairs.sort_by(|a, b| a.0.cmp(b.0));
+    pairs.serialize(serializer)
+}
+
+fn deserialize_daily_values<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<Date, Real>, D::Error> {
+    let pairs = Vec::<(Date, Real)>::deserialize(deserializer)?;
+    Ok(pairs.into_iter().collect())
+}
+
 impl Default for DailyValueData {
     fn default() -> Self {
         DailyValueData {
@@ -126,4 +139,15 @@ mod tests {
         assert_eq!(ordered_datetime, vec![date1, date2, date3]);
         assert_eq!(ordered_value, vec![100.0, 200.0, 300.0]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_daily_value_data_serde() -> anyhow::Result<()> {
+        let mut data = DailyValueData::default();
+        data.insert(date!(2021-01-04), 200.0);
+        data.insert(date!(2021-01-01), 100.0);
+        let json = serde_json::to_string(&data)?;
+        let res: DailyValueData = serde_json::from_str(&json)?;
+        assert_eq!(res.get_ordered_data_by_date(), data.get_ordered_data_by_date());
+        Ok(())
+    }
+}
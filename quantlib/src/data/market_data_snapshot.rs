@@ -0,0 +1,280 @@
+use crate::currency::FxCode;
+use crate::data::{
+    daily_value_data::DailyValueData,
+    surface_data::SurfaceData,
+    value_data::ValueData,
+    vector_data::VectorData,
+};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt::Display;
+use time::OffsetDateTime;
+
+type QuantoCorrelationData = HashMap<(String, FxCode), ValueData>;
+
+/// (underlying code, fx code) can not be a key of a JSON object, so the quanto correlations are entries
+fn serialize_quanto_correlation_data<S: Serializer>(data: &QuantoCorrelationData, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut entries = data.iter().collect::<Vec<_>>();
+    entries.sort_by(|a, b| (&a.0.0, a.0.1.to_string()).cmp(&(&b.0.0, b.0.1.to_string())));
+    entries.serialize(serializer)
+}
+
+fn deserialize_quanto_correlation_data<'de, D: Deserializer<'de>>(deserializer: D) -> Result<QuantoCorrelationData, D::Error> {
+    let entries = Vec::<((String, FxCode), ValueData)>::deserialize(deserializer)?;
+    Ok(entries.into_iter().collect())
+}
+
+/// All the market data of EngineGenerator::with_data observed at as_of.
+/// A snapshot is what MarketDataStore saves and loads.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketDataSnapshot {
+    as_of: OffsetDateTime,
+    #[serde(default)]
+    fx_data: HashMap<FxCode, ValueData>,
+    #[serde(default)]
+    stock_data: HashMap<String, ValueData>,
+    #[serde(default)]
+    curve_data: HashMap<String, VectorData>,
+    #[serde(default)]
+    dividend_data: HashMap<String, VectorData>,
+    #[serde(default)]
+    equity_constant_volatility_data: HashMap<String, ValueData>,
+    #[serde(default)]
+    equity_volatility_surface_data: HashMap<String, SurfaceData>,
+    #[serde(default)]
+    fx_constant_volatility_data: HashMap<FxCode, ValueData>,
+    #[serde(
+        default,
+        serialize_with = "serialize_quanto_correlation_data",
+        deserialize_with = "deserialize_quanto_correlation_data"
+    )]
+    quanto_correlation_data: QuantoCorrelationData,
+    #[serde(default)]
+    past_daily_value_data: HashMap<String, DailyValueData>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum MarketDataCategory {
+    Fx,
+    Stock,
+    Curve,
+    Dividend,
+    EquityConstantVolatility,
+    EquityVolatilitySurface,
+    FxConstantVolatility,
+    QuantoCorrelation,
+    PastDailyValue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MarketDataChangeType {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// a data of the category and key which differs between two snapshots
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketDataChange {
+    pub category: MarketDataCategory,
+    pub key: String,
+    pub change_type: MarketDataChangeType,
+}
+
+/// changes to a snapshot (from) which make another snapshot (to), sorted by category and key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketDataDiff {
+    pub from_as_of: OffsetDateTime,
+    pub to_as_of: OffsetDateTime,
+    pub changes: Vec<MarketDataChange>,
+}
+
+impl MarketDataDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn get_changes_of(&self, category: MarketDataCategory) -> Vec<&MarketDataChange> {
+        self.changes.iter().filter(|change| change.category == category).collect()
+    }
+}
+
+/// The data are compared in their serialized forms as the data types do not implement PartialEq.
+fn diff_maps<K: Display, V: Serialize>(
+    category: MarketDataCategory,
+    from: &HashMap<K, V>,
+    to: &HashMap<K, V>,
+) -> Result<Vec<MarketDataChange>> {
+    let to_values = |map: &HashMap<K, V>| -> Result<HashMap<String, serde_json::Value>> {
+        map.iter()
+            .map(|(key, data)| {
+                let value = serde_json::to_value(data)
+                    .with_context(|| anyhow!("({}:{}) failed to serialize {:?} {}", file!(), line!(), category, key))?;
+                Ok((key.to_string(), value))
+            })
+            .collect()
+    };
+    let from = to_values(from)?;
+    let to = to_values(to)?;
+    let mut res = Vec::new();
+    for (key, value) in from.iter() {
+        let change_type = match to.get(key) {
+            None => MarketDataChangeType::Removed,
+            Some(to_value) if to_value != value => MarketDataChangeType::Modified,
+            Some(_) => continue,
+        };
+        res.push(MarketDataChange { category, key: key.clone(), change_type });
+    }
+    for key in to.keys().filter(|key| !from.contains_key(*key)) {
+        res.push(MarketDataChange { category, key: key.clone(), change_type: MarketDataChangeType::Added });
+    }
+    Ok(res)
+}
+
+impl MarketDataSnapshot {
+    pub fn new(as_of: OffsetDateTime) -> MarketDataSnapshot {
+        MarketDataSnapshot {
+            as_of,
+            fx_data: HashMap::new(),
+            stock_data: HashMap::new(),
+            curve_data: HashMap::new(),
+            dividend_data: HashMap::new(),
+            equity_constant_volatility_data: HashMap::new(),
+            equity_volatility_surface_data: HashMap::new(),
+            fx_constant_volatility_data: HashMap::new(),
+            quanto_correlation_data: HashMap::new(),
+            past_daily_value_data: HashMap::new(),
+        }
+    }
+
+    pub fn with_fx_data(mut self, fx_data: HashMap<FxCode, ValueData>) -> MarketDataSnapshot {
+        self.fx_data = fx_data;
+        self
+    }
+
+    pub fn with_stock_data(mut self, stock_data: HashMap<String, ValueData>) -> MarketDataSnapshot {
+        self.stock_data = stock_data;
+        self
+    }
+
+    pub fn with_curve_data(mut self, curve_data: HashMap<String, VectorData>) -> MarketDataSnapshot {
+        self.curve_data = curve_data;
+        self
+    }
+
+    pub fn with_dividend_data(mut self, dividend_data: HashMap<String, VectorData>) -> MarketDataSnapshot {
+        self.dividend_data = dividend_data;
+        self
+    }
+
+    pub fn with_equity_constant_volatility_data(mut self, data: HashMap<String, ValueData>) -> MarketDataSnapshot {
+        self.equity_constant_volatility_data = data;
+        self
+    }
+
+    pub fn with_equity_volatility_surface_data(mut self, data: HashMap<String, SurfaceData>) -> MarketDataSnapshot {
+        self.equity_volatility_surface_data = data;
+        self
+    }
+
+    pub fn with_fx_constant_volatility_data(mut self, data: HashMap<FxCode, ValueData>) -> MarketDataSnapshot {
+        self.fx_constant_volatility_data = data;
+        self
+    }
+
+    pub fn with_quanto_correlation_data(mut self, data: QuantoCorrelationData) -> MarketDataSnapshot {
+        self.quanto_correlation_data = data;
+        self
+    }
+
+    pub fn with_past_daily_value_data(mut self, data: HashMap<String, DailyValueData>) -> MarketDataSnapshot {
+        self.past_daily_value_data = data;
+        self
+    }
+
+    pub fn get_as_of(&self) -> &OffsetDateTime {
+        &self.as_of
+    }
+
+    pub fn get_fx_data(&self) -> &HashMap<FxCode, ValueData> {
+        &self.fx_data
+    }
+
+    pub fn get_stock_data(&self) -> &HashMap<String, ValueData> {
+        &self.stock_data
+    }
+
+    pub fn get_curve_data(&self) -> &HashMap<String, VectorData> {
+        &self.curve_data
+    }
+
+    pub fn get_dividend_data(&self) -> &HashMap<String, VectorData> {
+        &self.dividend_data
+    }
+
+    pub fn get_equity_constant_volatility_data(&self) -> &HashMap<String, ValueData> {
+        &self.equity_constant_volatility_data
+    }
+
+    pub fn get_equity_volatility_surface_data(&self) -> &HashMap<String, SurfaceData> {
+        &self.equity_volatility_surface_data
+    }
+
+    pub fn get_fx_constant_volatility_data(&self) -> &HashMap<FxCode, ValueData> {
+        &self.fx_constant_volatility_data
+    }
+
+    pub fn get_quanto_correlation_data(&self) -> &QuantoCorrelationData {
+        &self.quanto_correlation_data
+    }
+
+    pub fn get_past_daily_value_data(&self) -> &HashMap<String, DailyValueData> {
+        &self.past_daily_value_data
+    }
+
+    pub fn get_past_daily_value_data_mut(&mut self) -> &mut HashMap<String, DailyValueData> {
+        &mut self.past_daily_value_data
+    }
+
+    /// changes from self to other. Quanto correlations are keyed by "und_code/fx_code".
+    pub fn diff(&self, other: &MarketDataSnapshot) -> Result<MarketDataDiff> {
+        let quanto_keys = |data: &QuantoCorrelationData| -> HashMap<String, ValueData> {
+            data.iter()
+                .map(|((und_code, fx_code), value)| (format!("{}/{}", und_code, fx_code), value.clone()))
+                .collect()
+        };
+        let mut changes = Vec::new();
+        changes.extend(diff_maps(MarketDataCategory::Fx, &self.fx_data, &other.fx_data)?);
+        changes.extend(diff_maps(MarketDataCategory::Stock, &self.stock_data, &other.stock_data)?);
+        changes.extend(diff_maps(MarketDataCategory::Curve, &self.curve_data, &other.curve_data)?);
+        changes.extend(diff_maps(MarketDataCategory::Dividend, &self.dividend_data, &other.dividend_data)?);
+        changes.extend(diff_maps(
+            MarketDataCategory::EquityConstantVolatility,
+            &self.equity_constant_volatility_data,
+            &other.equity_constant_volatility_data,
+        )?);
+        changes.extend(diff_maps(
+            MarketDataCategory::EquityVolatilitySurface,
+            &self.equity_volatility_surface_data,
+            &other.equity_volatility_surface_data,
+        )?);
+        changes.extend(diff_maps(
+            MarketDataCategory::FxConstantVolatility,
+            &self.fx_constant_volatility_data,
+            &other.fx_constant_volatility_data,
+        )?);
+        changes.extend(diff_maps(
+            MarketDataCategory::QuantoCorrelation,
+            &quanto_keys(&self.quanto_correlation_data),
+            &quanto_keys(&other.quanto_correlation_data),
+        )?);
+        changes.extend(diff_maps(
+            MarketDataCategory::PastDailyValue,
+            &self.past_daily_value_data,
+            &other.past_daily_value_data,
+        )?);
+        changes.sort_by(|a, b| (a.category, &a.key).cmp(&(b.category, &b.key)));
+        Ok(MarketDataDiff { from_as_of: self.as_of, to_as_of: other.as_of, changes })
+    }
+}
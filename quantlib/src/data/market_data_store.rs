@@ -0,0 +1,290 @@
+use crate::data::{
+    daily_value_data::DailyValueData,
+    market_data_snapshot::MarketDataSnapshot,
+};
+use crate::definitions::Real;
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use time::{macros::format_description, Date, OffsetDateTime};
+
+/// Snapshots saved in a directory as root/YYYY-MM-DD/vNNNN.json where the date is that of the as_of of the snapshot.
+/// Saving a snapshot of a date which already has snapshots adds the next version, so the earlier ones are kept.
+#[derive(Debug, Clone)]
+pub struct MarketDataStore {
+    root: PathBuf,
+}
+
+fn date_to_directory_name(date: Date) -> Result<String> {
+    date.format(format_description!("[year]-[month]-[day]"))
+        .with_context(|| anyhow!("({}:{}) failed to format {}", file!(), line!(), date))
+}
+
+fn version_file_name(version: u32) -> String {
+    format!("v{:04}.json", version)
+}
+
+impl MarketDataStore {
+    pub fn new<P: Into<PathBuf>>(root: P) -> MarketDataStore {
+        MarketDataStore { root: root.into() }
+    }
+
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    fn date_directory(&self, date: Date) -> Result<PathBuf> {
+        Ok(self.root.join(date_to_directory_name(date)?))
+    }
+
+    /// dates having snapshots in ascending order
+    pub fn get_dates(&self) -> Result<Vec<Date>> {
+        if !self.root.exists() {
+            return Ok(vec![]);
+        }
+        let entries = std::fs::read_dir(&self.root)
+            .with_context(|| anyhow!("({}:{}) failed to read {}", file!(), line!(), self.root.display()))?;
+        let mut res = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            // other directories in the root are not snapshots
+            if let Ok(date) = Date::parse(&name, format_description!("[year]-[month]-[day]")) {
+                res.push(date);
+            }
+        }
+        res.sort();
+        Ok(res)
+    }
+
+    /// versions of the date in ascending order
+    pub fn get_versions(&self, date: Date) -> Result<Vec<u32>> {
+        let directory = self.date_directory(date)?;
+        if !directory.exists() {
+            return Ok(vec![]);
+        }
+        let entries = std::fs::read_dir(&directory)
+            .with_context(|| anyhow!("({}:{}) failed to read {}", file!(), line!(), directory.display()))?;
+        let mut res = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            let version = name.strip_prefix('v')
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|version| version.parse::<u32>().ok());
+            if let Some(version) = version {
+                res.push(version);
+            }
+        }
+        res.sort();
+        Ok(res)
+    }
+
+    /// saves the snapshot as the next version of its date and returns the version (starting from 1)
+    pub fn save(&self, snapshot: &MarketDataSnapshot) -> Result<u32> {
+        let date = snapshot.get_as_of().date();
+        let directory = self.date_directory(date)?;
+        std::fs::create_dir_all(&directory)
+            .with_context(|| anyhow!("({}:{}) failed to create {}", file!(), line!(), directory.display()))?;
+        let version = self.get_versions(date)?.last().map_or(1, |v| v + 1);
+        let json = serde_json::to_string(snapshot)
+            .with_context(|| anyhow!("({}:{}) failed to serialize the snapshot as of {}", file!(), line!(), snapshot.get_as_of()))?;
+        // a partially written file must not be taken as a version
+        let temporary = directory.join(format!("{}.tmp", version_file_name(version)));
+        let path = directory.join(version_file_name(version));
+        std::fs::write(&temporary, json)
+            .with_context(|| anyhow!("({}:{}) failed to write {}", file!(), line!(), temporary.display()))?;
+        std::fs::rename(&temporary, &path)
+            .with_context(|| anyhow!("({}:{}) failed to move {} to {}", file!(), line!(), temporary.display(), path.display()))?;
+        Ok(version)
+    }
+
+    /// the version of the date, or the latest version if version is None
+    pub fn load(&self, date: Date, version: Option<u32>) -> Result<MarketDataSnapshot> {
+        let version = match version {
+            Some(version) => version,
+            None => *self.get_versions(date)?.last()
+                .ok_or_else(|| anyhow!("({}:{}) no snapshot on {} in {}", file!(), line!(), date, self.root.display()))?,
+        };
+        let path = self.date_directory(date)?.join(version_file_name(version));
+        let json = std::fs::read_to_string(&path)
+            .with_context(|| anyhow!("({}:{}) failed to read {}", file!(), line!(), path.display()))?;
+        serde_json::from_str(&json)
+            .with_context(|| anyhow!("({}:{}) failed to deserialize {}", file!(), line!(), path.display()))
+    }
+
+    /// The snapshot which was in effect at datetime: the latest version of the latest date
+    /// among the snapshots whose as_of is not after datetime.
+    pub fn load_as_of(&self, datetime: OffsetDateTime) -> Result<MarketDataSnapshot> {
+        let dates = self.get_dates()?;
+        for date in dates.iter().rev().filter(|date| **date <= datetime.date()) {
+            for version in self.get_versions(*date)?.iter().rev() {
+                let snapshot = self.load(*date, Some(*version))?;
+                if *snapshot.get_as_of() <= datetime {
+                    return Ok(snapshot);
+                }
+            }
+        }
+        bail!("({}:{}) no snapshot as of {} in {}", file!(), line!(), datetime, self.root.display())
+    }
+
+    /// Daily values from the latest snapshot of each date in [start_date, end_date] taken by value,
+    /// e.g., |snapshot| snapshot.get_stock_data().get("KOSPI2").map(|data| data.get_value()).
+    /// The dates where value gives None are skipped. The calendar and close time are those of DailyValueData::default().
+    pub fn fill_time_series<F>(
+        &self,
+        name: &str,
+        code: &str,
+        start_date: Date,
+        end_date: Date,
+        value: F,
+    ) -> Result<DailyValueData>
+    where
+        F: Fn(&MarketDataSnapshot) -> Option<Real>,
+    {
+        let series = self.fill_time_series_map(&[code.to_string()], start_date, end_date, |snapshot, _| value(snapshot))?;
+        let (values, _) = series.into_values().next().unwrap_or_default();
+        let default = DailyValueData::default();
+        Ok(DailyValueData::new(
+            values,
+            *default.get_close_time(),
+            *default.get_utc_offset(),
+            default.get_calendar().clone(),
+            name.to_string(),
+            code.to_string(),
+        ))
+    }
+
+    /// Close prices of the stock data for the codes in [start_date, end_date],
+    /// in the form of past_daily_value_data of EngineGenerator::with_data
+    pub fn fill_daily_value_data(
+        &self,
+        codes: &[String],
+        start_date: Date,
+        end_date: Date,
+    ) -> Result<HashMap<String, DailyValueData>> {
+        let series = self.fill_time_series_map(codes, start_date, end_date, |snapshot, code| {
+            snapshot.get_stock_data().get(code).map(|data| data.get_value())
+        })?;
+        let default = DailyValueData::default();
+        let mut res = HashMap::new();
+        for (code, (values, name)) in series {
+            let data = DailyValueData::new(
+                values,
+                *default.get_close_time(),
+                *default.get_utc_offset(),
+                default.get_calendar().clone(),
+                name.unwrap_or_else(|| code.clone()),
+                code.clone(),
+            );
+            res.insert(code, data);
+        }
+        Ok(res)
+    }
+
+    /// code -> (date -> value, name of the stock data if any). Each snapshot is loaded once for all the codes.
+    fn fill_time_series_map<F>(
+        &self,
+        codes: &[String],
+        start_date: Date,
+        end_date: Date,
+        value: F,
+    ) -> Result<HashMap<String, DailySeries>>
+    where
+        F: Fn(&MarketDataSnapshot, &str) -> Option<Real>,
+    {
+        if start_date > end_date {
+            bail!("({}:{}) start_date {} is after end_date {}", file!(), line!(), start_date, end_date);
+        }
+        let mut res: HashMap<String, DailySeries> = codes.iter()
+            .map(|code| (code.clone(), (HashMap::new(), None)))
+            .collect();
+        for date in self.get_dates()?.into_iter().filter(|date| *date >= start_date && *date <= end_date) {
+            let snapshot = self.load(date, None)?;
+            for code in codes.iter() {
+                if let Some(v) = value(&snapshot, code) {
+                    let entry = res.get_mut(code).expect("codes are in res");
+                    entry.0.insert(date, v);
+                    if entry.1.is_none() {
+                        entry.1 = snapshot.get_stock_data().get(code).map(|data| data.get_name().clone());
+                    }
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+type DailySeries = (HashMap<Date, Real>, Option<String>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::{Currency, FxCode};
+    use crate::data::{
+        market_data_snapshot::{MarketDataCategory, MarketDataChangeType},
+        value_data::ValueData,
+    };
+    use time::macros::{date, datetime};
+
+    fn snapshot(as_of: OffsetDateTime, kospi2: Real, usdkrw: Option<Real>) -> Result<MarketDataSnapshot> {
+        let stock = ValueData::new(kospi2, Some(as_of), Currency::KRW, "KOSPI200".to_string(), "KOSPI2".to_string())?;
+        let mut fx = HashMap::new();
+        if let Some(usdkrw) = usdkrw {
+            fx.insert(FxCode::from("USDKRW"), ValueData::new(usdkrw, Some(as_of), Currency::KRW, "USDKRW".to_string(), "USDKRW".to_string())?);
+        }
+        Ok(MarketDataSnapshot::new(as_of)
+            .with_stock_data(HashMap::from([("KOSPI2".to_string(), stock)]))
+            .with_fx_data(fx))
+    }
+
+    #[test]
+    fn test_market_data_store() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("quantlib-market-data-store-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let store = MarketDataStore::new(&root);
+
+        assert_eq!(store.save(&snapshot(datetime!(2024-01-02 15:40:00 +09:00), 350.0, Some(1300.0))?)?, 1);
+        assert_eq!(store.save(&snapshot(datetime!(2024-01-03 09:00:00 +09:00), 351.0, Some(1300.0))?)?, 1);
+        // intraday correction of 2024-01-03
+        assert_eq!(store.save(&snapshot(datetime!(2024-01-03 15:40:00 +09:00), 352.0, None)?)?, 2);
+        assert_eq!(store.save(&snapshot(datetime!(2024-01-05 15:40:00 +09:00), 355.0, Some(1310.0))?)?, 1);
+
+        assert_eq!(store.get_dates()?, vec![date!(2024-01-02), date!(2024-01-03), date!(2024-01-05)]);
+        assert_eq!(store.get_versions(date!(2024-01-03))?, vec![1, 2]);
+
+        let latest = store.load(date!(2024-01-03), None)?;
+        assert_eq!(latest.get_stock_data()["KOSPI2"].get_value(), 352.0);
+        let first = store.load(date!(2024-01-03), Some(1))?;
+        assert_eq!(first.get_fx_data()[&FxCode::from("USDKRW")].get_value(), 1300.0);
+
+        // as of noon, the second version of 2024-01-03 was not there yet
+        let noon = store.load_as_of(datetime!(2024-01-03 12:00:00 +09:00))?;
+        assert_eq!(*noon.get_as_of(), datetime!(2024-01-03 09:00:00 +09:00));
+        let holiday = store.load_as_of(datetime!(2024-01-04 12:00:00 +09:00))?;
+        assert_eq!(*holiday.get_as_of(), datetime!(2024-01-03 15:40:00 +09:00));
+        assert!(store.load_as_of(datetime!(2024-01-01 12:00:00 +09:00)).is_err());
+
+        let diff = first.diff(&latest)?;
+        assert_eq!(diff.get_changes_of(MarketDataCategory::Stock).len(), 1);
+        assert_eq!(diff.get_changes_of(MarketDataCategory::Stock)[0].change_type, MarketDataChangeType::Modified);
+        assert_eq!(diff.get_changes_of(MarketDataCategory::Fx)[0].change_type, MarketDataChangeType::Removed);
+        assert!(latest.diff(&latest)?.is_empty());
+
+        let past = store.fill_daily_value_data(&["KOSPI2".to_string()], date!(2024-01-01), date!(2024-01-04))?;
+        let (dates, values) = past["KOSPI2"].get_ordered_data_by_date();
+        assert_eq!(dates, vec![date!(2024-01-02), date!(2024-01-03)]);
+        assert_eq!(values, vec![350.0, 352.0]);
+        assert_eq!(past["KOSPI2"].get_name(), "KOSPI200");
+
+        let usdkrw = store.fill_time_series("USDKRW", "USDKRW", date!(2024-01-01), date!(2024-01-31), |snapshot| {
+            snapshot.get_fx_data().get(&FxCode::from("USDKRW")).map(|data| data.get_value())
+        })?;
+        assert_eq!(usdkrw.get_ordered_data_by_date().1, vec![1300.0, 1310.0]);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
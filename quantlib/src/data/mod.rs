@@ -2,4 +2,6 @@ pub mod value_data;
 pub mod surface_data;
 pub mod vector_data;
 //pub mod observable;
-pub mod daily_value_data;
\ No newline at end of file
+pub mod daily_value_data;
+pub mod market_data_snapshot;
+pub mod market_data_store;
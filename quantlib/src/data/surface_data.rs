@@ -3,6 +3,7 @@ use crate::currency::Currency;
 use time::OffsetDateTime;
 use serde::{Serialize, Deserialize};
 use ndarray::{Array1, Array2};
+use anyhow::{anyhow, Result};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SurfaceData {
@@ -66,4 +67,14 @@ impl SurfaceData {
     pub fn get_code(&self) -> &str {
         &self.code
     }
+
+    /// sets the value on (date, strike) which must be a point of the surface
+    pub fn set_value_on(&mut self, date: &OffsetDateTime, strike: Real, value: Real) -> Result<()> {
+        let i = self.dates.iter().position(|d| d == date)
+            .ok_or_else(|| anyhow!("({}:{}) {} is not a date of {}", file!(), line!(), date, self.code))?;
+        let j = self.strikes.iter().position(|k| (k - strike).abs() < 1.0e-10)
+            .ok_or_else(|| anyhow!("({}:{}) {} is not a strike of {}", file!(), line!(), strike, self.code))?;
+        self.value[[i, j]] = value;
+        Ok(())
+    }
 }
@@ -58,6 +58,10 @@ impl ValueData {
         self.value
     }
 
+    pub fn set_value(&mut self, value: Real) {
+        self.value = value;
+    }
+
     pub fn get_market_datetime(&self) -> &Option<OffsetDateTime> {
         &self.market_datetime
     }
@@ -66,6 +70,10 @@ impl ValueData {
         &self.name
     }
 
+    pub fn get_code(&self) -> &String {
+        &self.code
+    }
+
     pub fn get_currency(&self) -> &Currency {
         &self.currency
     }
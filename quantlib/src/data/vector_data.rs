@@ -125,6 +125,10 @@ impl VectorData {
         self.name.clone()
     }
 
+    pub fn get_code(&self) -> &String {
+        &self.code
+    }
+
     pub fn get_value_clone(&self) -> Array1<Real> {
         self.value.clone()
     }
@@ -136,6 +140,22 @@ impl VectorData {
     pub fn get_dates_clone(&self) -> Option<Vec<OffsetDateTime>> {
         self.dates.clone()
     }
+
+    pub fn get_currency(&self) -> &Currency {
+        &self.currency
+    }
+
+    /// sets the value on the date which must be one of the dates of the data
+    pub fn set_value_on_date(&mut self, date: &OffsetDateTime, value: Real) -> Result<()> {
+        let index = self.dates.as_ref()
+            .and_then(|dates| dates.iter().position(|d| d == date))
+            .ok_or_else(|| anyhow!(
+                "({}:{}) {} is not a date of {} ({})",
+                file!(), line!(), date, self.name, self.code
+            ))?;
+        self.value[index] = value;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -30,5 +30,6 @@ pub const VEGA_PNL_UNIT: Real = 0.01;
 pub const RHO_PNL_UNIT: Real = 0.0001;
 pub const DIV_PNL_UNIT: Real = 0.0001;
 pub const THETA_PNL_UNIT: Real = 1.0;
+pub const CORRELATION_PNL_UNIT: Real = 0.01;
 
 
@@ -40,6 +40,19 @@ pub enum Compounding {
     Continuous = 1,
 }
 
+/// Yield quotation convention used in bond analytics.
+/// Krx: 금융투자회사의 영업 및 업무에 관한 규정 별표 14 (see KrxYieldPricer)
+/// Compounded: compounded by the coupon frequency of the bond
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Hash, Default)]
+pub enum YieldConvention {
+    Krx = 0,
+    #[default]
+    Compounded = 1,
+    Annual = 2,
+    Continuous = 3,
+    Simple = 4,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Hash)]
 pub enum CreditRating {
     None = 0,
@@ -130,6 +143,29 @@ impl Default for StickynessType {
     }
 }
 
+/// How an implied volatility SurfaceData is turned into a Volatility.
+/// Interpolated goes through LocalVolatilitySurface (bilinear, flat extrapolation).
+/// RawSvi fits a raw SVI slice per expiry, Ssvi fits one SSVI surface across expiries.
+/// Heston calibrates the Heston model to the surface.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Copy, Default)]
+pub enum VolatilitySurfaceType {
+    #[default]
+    Interpolated = 0,
+    RawSvi = 1,
+    Ssvi = 2,
+    Heston = 3,
+}
+
+/// discretization of the Heston variance in HestonPathGenerator
+/// QuadraticExponential: Andersen (2008) with the martingale correction
+/// FullTruncationEuler: Euler with the negative variance truncated at zero (Lord et al. 2010)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum MonteCarloScheme {
+    #[default]
+    QuadraticExponential = 0,
+    FullTruncationEuler = 1,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Copy)]
 pub enum VanillaOptionCalculationMethod {
     MonteCarlo = 0,
@@ -145,4 +181,160 @@ pub enum StockRankType {
     Warrant = 2,
     Convertible = 3,
     Undefined = 4,
-}
\ No newline at end of file
+}
+/// Absolute shocks are added to the market data, e.g., 0.01 for +1% vol point.
+/// Relative shocks multiply the market data by (1 + shock), e.g., -0.3 for a 30% crash.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Copy, Default)]
+pub enum ShockType {
+    Absolute = 0,
+    #[default]
+    Relative = 1,
+}
+
+/// risk classes of the market risk factors
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Copy)]
+pub enum RiskClass {
+    Equity = 0,
+    Rates = 1,
+    Fx = 2,
+    Volatility = 3,
+    Dividend = 4,
+}
+
+/// approximation of the quantile of the delta-gamma pnl in ParametricVar
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Copy, Default)]
+pub enum DeltaGammaMethod {
+    #[default]
+    CornishFisher = 0,
+    MonteCarlo = 1,
+}
+
+/// dimensions along which PortfolioAggregator groups the results
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Copy)]
+pub enum PortfolioGrouping {
+    Total = 0,
+    Book = 1,
+    InstrumentType = 2,
+    Issuer = 3,
+    Underlying = 4,
+}
+
+/// Sensitivity: greeks of the previous date times the market moves.
+/// Waterfall: revaluation replacing the market data of the previous date step by step.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Copy, Default)]
+pub enum PnlExplainMethod {
+    #[default]
+    Sensitivity = 0,
+    Waterfall = 1,
+}
+
+/// product classes of ISDA SIMM (ProductClass in CRIF)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Copy)]
+pub enum SimmProductClass {
+    #[serde(rename = "RatesFX")]
+    RatesFx = 0,
+    Credit = 1,
+    Equity = 2,
+    Commodity = 3,
+}
+
+/// risk classes of ISDA SIMM in the order of the risk class correlations
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Copy)]
+pub enum SimmRiskClass {
+    InterestRate = 0,
+    CreditQualifying = 1,
+    CreditNonQualifying = 2,
+    Equity = 3,
+    Commodity = 4,
+    #[serde(rename = "FX")]
+    Fx = 5,
+}
+
+/// risk types of ISDA SIMM (RiskType in CRIF)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Copy)]
+pub enum SimmRiskType {
+    #[serde(rename = "Risk_IRCurve")]
+    IrCurve = 0,
+    #[serde(rename = "Risk_Inflation")]
+    Inflation = 1,
+    #[serde(rename = "Risk_XCcyBasis")]
+    XccyBasis = 2,
+    #[serde(rename = "Risk_IRVol")]
+    IrVol = 3,
+    #[serde(rename = "Risk_InflationVol")]
+    InflationVol = 4,
+    #[serde(rename = "Risk_CreditQ")]
+    CreditQ = 5,
+    #[serde(rename = "Risk_CreditVol")]
+    CreditVol = 6,
+    #[serde(rename = "Risk_CreditNonQ")]
+    CreditNonQ = 7,
+    #[serde(rename = "Risk_CreditVolNonQ")]
+    CreditVolNonQ = 8,
+    #[serde(rename = "Risk_Equity")]
+    Equity = 9,
+    #[serde(rename = "Risk_EquityVol")]
+    EquityVol = 10,
+    #[serde(rename = "Risk_Commodity")]
+    Commodity = 11,
+    #[serde(rename = "Risk_CommodityVol")]
+    CommodityVol = 12,
+    #[serde(rename = "Risk_FX")]
+    Fx = 13,
+    #[serde(rename = "Risk_FXVol")]
+    FxVol = 14,
+}
+
+impl SimmRiskType {
+    pub fn get_risk_class(&self) -> SimmRiskClass {
+        match self {
+            SimmRiskType::IrCurve
+            | SimmRiskType::Inflation
+            | SimmRiskType::XccyBasis
+            | SimmRiskType::IrVol
+            | SimmRiskType::InflationVol => SimmRiskClass::InterestRate,
+            SimmRiskType::CreditQ | SimmRiskType::CreditVol => SimmRiskClass::CreditQualifying,
+            SimmRiskType::CreditNonQ | SimmRiskType::CreditVolNonQ => SimmRiskClass::CreditNonQualifying,
+            SimmRiskType::Equity | SimmRiskType::EquityVol => SimmRiskClass::Equity,
+            SimmRiskType::Commodity | SimmRiskType::CommodityVol => SimmRiskClass::Commodity,
+            SimmRiskType::Fx | SimmRiskType::FxVol => SimmRiskClass::Fx,
+        }
+    }
+}
+
+/// risk classes of the FRTB sensitivities-based method.
+/// CSR on securitisations and commodity are not covered
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Copy)]
+pub enum FrtbRiskClass {
+    #[serde(rename = "GIRR")]
+    Girr = 0,
+    #[serde(rename = "CSR_NonSec")]
+    CsrNonSec = 1,
+    #[serde(rename = "EQ")]
+    Equity = 2,
+    #[serde(rename = "FX")]
+    Fx = 3,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Copy)]
+pub enum FrtbRiskMeasure {
+    Delta = 0,
+    Vega = 1,
+    Curvature = 2,
+}
+
+/// Medium uses the prescribed correlations, High min(1.25 rho, 1) and Low max(2 rho - 1, 0.75 rho)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Copy)]
+pub enum FrtbCorrelationScenario {
+    Low = 0,
+    Medium = 1,
+    High = 2,
+}
+
+/// buckets of the default risk charge for non-securitisations
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Copy)]
+pub enum FrtbDrcBucket {
+    Corporates = 0,
+    Sovereigns = 1,
+    LocalGovernments = 2,
+}
@@ -33,6 +33,7 @@ use crate::time::{
 // 
 use anyhow::{anyhow, Context, Result};
 use enum_dispatch::enum_dispatch;
+use serde::{Deserialize, Serialize, Serializer};
 use std::{
     rc::Rc,
     cell::RefCell,
@@ -146,6 +147,11 @@ pub trait InstrumentTrait{
         Err(anyhow!("not supported instrument type on get_schedule"))
     }
 
+    // the coupon periods of the rate index, e.g., for the past fixings
+    fn get_floating_schedule(&self) -> Result<&Schedule> {
+        Err(anyhow!("not supported instrument type on get_floating_schedule"))
+    }
+
     fn get_fixed_leg_currency(&self) -> Result<&Currency> {
         Err(anyhow!("not supported instrument type on get_fixed_leg_currency"))
     }
@@ -183,26 +189,59 @@ pub trait InstrumentTrait{
     }
 }
 
+/// Serialized as the fields of the instrument with "type" given by get_type_name, e.g.,
+/// {"type": "IRS", "fixed_legs": ..}. The type names of the variants are also accepted on deserialization.
 #[enum_dispatch(InstrumentTrait)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type")]
 pub enum Instrument {
     Futures(Futures),
     Bond(Bond),
     BondFutures(BondFutures),
     KTBF(KTBF),
+    #[serde(alias = "IRS", alias = "CRS", alias = "FxSwap", alias = "FxForward", alias = "FxSpot")]
     PlainSwap(PlainSwap),
     FxFutures(FxFutures),
+    #[serde(alias = "VanillaCall", alias = "VanillaPut")]
     VanillaOption(VanillaOption),   
     Stock(Stock),
     Cash(Cash),
 }
 
+#[derive(Serialize)]
+struct TypedInstrument<'a, T: Serialize> {
+    #[serde(rename = "type")]
+    type_name: &'static str,
+    #[serde(flatten)]
+    instrument: &'a T,
+}
+
+impl Serialize for Instrument {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let type_name = self.get_type_name();
+        match self {
+            Instrument::Futures(instrument) => TypedInstrument { type_name, instrument }.serialize(serializer),
+            Instrument::Bond(instrument) => TypedInstrument { type_name, instrument }.serialize(serializer),
+            Instrument::BondFutures(instrument) => TypedInstrument { type_name, instrument }.serialize(serializer),
+            Instrument::KTBF(instrument) => TypedInstrument { type_name, instrument }.serialize(serializer),
+            Instrument::PlainSwap(instrument) => TypedInstrument { type_name, instrument }.serialize(serializer),
+            Instrument::FxFutures(instrument) => TypedInstrument { type_name, instrument }.serialize(serializer),
+            Instrument::VanillaOption(instrument) => TypedInstrument { type_name, instrument }.serialize(serializer),
+            Instrument::Stock(instrument) => TypedInstrument { type_name, instrument }.serialize(serializer),
+            Instrument::Cash(instrument) => TypedInstrument { type_name, instrument }.serialize(serializer),
+        }
+    }
+}
+
 /// calculation groups for calculation optimization, 
 /// On the group, again select calculation sets based on currency and underlying assets (not sub|superset, exact the same assets)
 /// currency and underlying_assets categorization
 /// GROUP1: Vec<&'static str> = vec!["StockFutures"]; 
 /// GROUP2: Vec<&'static str> = vec!["FixedCouponBond", "BondFutures", "KTBF"]; 
 /// GROUP3: Vec<&'static str> = vec!["StructuredProduct"]; 
+/// Serialized as a list of instruments. See instruments::trade_loader for loading them from files.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Instruments {
     instruments: Vec<Rc<Instrument>>,
 }
@@ -227,6 +227,14 @@ impl Bond {
     pub fn set_pricing_date(&mut self, pricing_date: OffsetDateTime) {
         self.pricing_date = Some(pricing_date);
     }
+
+    pub fn get_fixed_coupon_rate(&self) -> Option<Real> {
+        self.fixed_coupon_rate
+    }
+
+    pub fn get_daycounter(&self) -> &DayCountConvention {
+        &self.daycounter
+    }
 }
 
 impl InstrumentTrait for Bond {
@@ -242,6 +250,10 @@ impl InstrumentTrait for Bond {
         Ok(&self.schedule)
     }
 
+    fn get_floating_schedule(&self) -> Result<&Schedule> {
+        Ok(&self.schedule)
+    }
+
     fn get_calendar(&self) -> Result<&JointCalendar> {
         Ok(&self.calendar)
     }
@@ -266,6 +278,10 @@ impl InstrumentTrait for Bond {
         Ok(&self.issuer_type)
     }
 
+    fn get_rank_type(&self) -> Result<&RankType> {
+        Ok(&self.rank)
+    }
+
     fn get_issuer_name(&self) -> Result<&String> {
         Ok(&self.issuer_name)
     }
@@ -8,4 +8,5 @@ pub mod bond_futures;
 pub mod fx_futures;
 pub mod vanilla_option;
 pub mod cash;
-pub mod stock;
\ No newline at end of file
+pub mod stock;
+pub mod trade_loader;
@@ -551,6 +551,10 @@ impl InstrumentTrait for PlainSwap {
         Ok(self.rate_index.as_ref())
     }
 
+    fn get_floating_schedule(&self) -> Result<&Schedule> {
+        Ok(&self.floating_legs)
+    }
+
     fn get_type_name(&self) -> &'static str {
         //"PlainSwap"
         self.specific_type.as_str()
@@ -0,0 +1,375 @@
+use crate::instrument::{Instrument, InstrumentTrait, Instruments};
+use crate::instruments::{
+    bond::Bond,
+    bond_futures::BondFutures,
+    cash::Cash,
+    futures::Futures,
+    fx_futures::FxFutures,
+    ktbf::KTBF,
+    plain_swap::PlainSwap,
+    stock::Stock,
+    vanilla_option::VanillaOption,
+};
+//
+use anyhow::{anyhow, bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::{collections::HashSet, path::Path, rc::Rc};
+
+/// type names accepted in the "type" field of a trade
+pub const TRADE_TYPES: [&str; 16] = [
+    "Futures", "Bond", "BondFutures", "KTBF", "PlainSwap", "IRS", "CRS", "FxSwap", "FxForward", "FxSpot",
+    "FxFutures", "VanillaOption", "VanillaCall", "VanillaPut", "Stock", "Cash",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TradeFileFormat {
+    Json,
+    Yaml,
+    Csv,
+}
+
+impl TradeFileFormat {
+    /// format from the extension of the path (json, yaml, yml or csv)
+    pub fn from_path(path: &str) -> Result<TradeFileFormat> {
+        let extension = Path::new(path).extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("json") => Ok(TradeFileFormat::Json),
+            Some("yaml") | Some("yml") => Ok(TradeFileFormat::Yaml),
+            Some("csv") => Ok(TradeFileFormat::Csv),
+            _ => bail!("({}:{}) unknown trade file format of {} (json, yaml, yml or csv)", file!(), line!(), path),
+        }
+    }
+}
+
+/// A trade read from a file before deserialization, with its position in the file for error messages
+struct RawTrade {
+    location: String,
+    value: Value,
+}
+
+impl RawTrade {
+    fn describe(&self) -> String {
+        let field = |name: &str| self.value.get(name).and_then(|v| v.as_str());
+        match (field("code"), field("type")) {
+            (Some(code), Some(type_name)) => format!("{} (code: {}, type: {})", self.location, code, type_name),
+            (Some(code), None) => format!("{} (code: {})", self.location, code),
+            _ => self.location.clone(),
+        }
+    }
+}
+
+fn split_trades(value: Value, location: &str) -> Vec<RawTrade> {
+    match value {
+        Value::Array(values) => values.into_iter().enumerate()
+            .map(|(i, value)| RawTrade { location: format!("{} #{}", location, i), value })
+            .collect(),
+        value => vec![RawTrade { location: format!("{} #0", location), value }],
+    }
+}
+
+/// YAML tags (enum variants written by serde_yaml, e.g., !SouthKorea Settlement) become {"SouthKorea": "Settlement"}
+/// as in JSON
+fn yaml_to_json(value: serde_yaml::Value) -> Result<Value> {
+    let res = match value {
+        serde_yaml::Value::Null => Value::Null,
+        serde_yaml::Value::Bool(b) => Value::Bool(b),
+        serde_yaml::Value::Number(n) => serde_json::to_value(&n)
+            .with_context(|| anyhow!("({}:{}) invalid number {} in yaml", file!(), line!(), n))?,
+        serde_yaml::Value::String(s) => Value::String(s),
+        serde_yaml::Value::Sequence(values) => Value::Array(
+            values.into_iter().map(yaml_to_json).collect::<Result<Vec<Value>>>()?
+        ),
+        serde_yaml::Value::Mapping(mapping) => {
+            let mut object = Map::new();
+            for (key, value) in mapping {
+                let key = match key {
+                    serde_yaml::Value::String(key) => key,
+                    key => serde_yaml::to_string(&key)
+                        .with_context(|| anyhow!("({}:{}) invalid key in yaml", file!(), line!()))?
+                        .trim().to_string(),
+                };
+                object.insert(key, yaml_to_json(value)?);
+            }
+            Value::Object(object)
+        },
+        serde_yaml::Value::Tagged(tagged) => {
+            let mut object = Map::new();
+            object.insert(tagged.tag.to_string().trim_start_matches('!').to_string(), yaml_to_json(tagged.value)?);
+            Value::Object(object)
+        },
+    };
+    Ok(res)
+}
+
+/// A cell is a JSON value if it parses as JSON (numbers, booleans, lists, objects and quoted strings),
+/// and a string otherwise. Empty cells are omitted, i.e., None for Option fields.
+fn parse_csv(text: &str) -> Result<Vec<RawTrade>> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(text.as_bytes());
+    let headers = reader.headers()
+        .with_context(|| anyhow!("({}:{}) failed to read the csv header", file!(), line!()))?
+        .clone();
+    if !headers.iter().any(|header| header == "type") {
+        bail!("({}:{}) the csv header must have a type column", file!(), line!());
+    }
+    let mut res = Vec::new();
+    for record in reader.records() {
+        let record = record.with_context(|| anyhow!("({}:{}) failed to read a csv record", file!(), line!()))?;
+        let line = record.position().map(|position| position.line()).unwrap_or(0);
+        let mut object = Map::new();
+        for (header, cell) in headers.iter().zip(record.iter()) {
+            if cell.is_empty() {
+                continue;
+            }
+            let value = serde_json::from_str(cell).unwrap_or_else(|_| Value::String(cell.to_string()));
+            object.insert(header.to_string(), value);
+        }
+        res.push(RawTrade { location: format!("trade at line {}", line), value: Value::Object(object) });
+    }
+    Ok(res)
+}
+
+/// Every field in the input must be a field of the instrument, so that a misspelled optional field is not ignored.
+/// canonical is the instrument serialized back.
+fn check_unknown_fields(input: &Value, canonical: &Value, path: &str) -> Result<()> {
+    match (input, canonical) {
+        (Value::Object(input), Value::Object(canonical)) => {
+            for (key, value) in input.iter() {
+                let field = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                match canonical.get(key) {
+                    Some(canonical) => check_unknown_fields(value, canonical, &field)?,
+                    None => bail!("({}:{}) unknown field {}", file!(), line!(), field),
+                }
+            }
+        },
+        (Value::Array(input), Value::Array(canonical)) if input.len() == canonical.len() => {
+            for (i, (value, canonical)) in input.iter().zip(canonical.iter()).enumerate() {
+                check_unknown_fields(value, canonical, &format!("{}[{}]", path, i))?;
+            }
+        },
+        _ => {},
+    }
+    Ok(())
+}
+
+fn validate_trade(instrument: &Instrument, input: &Value) -> Result<()> {
+    if instrument.get_code().is_empty() {
+        bail!("({}:{}) field code must not be empty", file!(), line!());
+    }
+    let unit_notional = instrument.get_unit_notional();
+    if !unit_notional.is_finite() || unit_notional <= 0.0 {
+        bail!("({}:{}) field unit_notional must be positive, got {}", file!(), line!(), unit_notional);
+    }
+    if let (Ok(issue_date), Some(maturity)) = (instrument.get_issue_date(), instrument.get_maturity()) {
+        if maturity < issue_date {
+            bail!("({}:{}) field maturity ({}) is before issue_date ({})", file!(), line!(), maturity, issue_date);
+        }
+    }
+    let canonical = serde_json::to_value(instrument)
+        .with_context(|| anyhow!("({}:{}) failed to serialize the trade", file!(), line!()))?;
+    // e.g., type VanillaCall with option_type Put
+    let type_name = input.get("type").and_then(|v| v.as_str()).unwrap_or_default();
+    let variant_name = match instrument {
+        Instrument::PlainSwap(_) => "PlainSwap",
+        Instrument::VanillaOption(_) => "VanillaOption",
+        _ => instrument.get_type_name(),
+    };
+    if type_name != instrument.get_type_name() && type_name != variant_name {
+        bail!(
+            "({}:{}) field type is {} but the fields make {}",
+            file!(), line!(), type_name, instrument.get_type_name()
+        );
+    }
+    check_unknown_fields(input, &canonical, "")
+}
+
+/// deserialize the instrument struct directly, not through the tagged Instrument, to keep the path of the field
+fn deserialize_fields<T: DeserializeOwned>(value: &Value) -> Result<T> {
+    serde_path_to_error::deserialize(value).map_err(|error| {
+        let path = error.path().to_string();
+        match path.as_str() {
+            "." | "" => anyhow!("({}:{}) {}", file!(), line!(), error.into_inner()),
+            _ => anyhow!("({}:{}) field {}: {}", file!(), line!(), path, error.into_inner()),
+        }
+    })
+}
+
+fn deserialize_trade(trade: &RawTrade) -> Result<Instrument> {
+    let value = &trade.value;
+    let instrument = match value.get("type") {
+        Some(Value::String(type_name)) => match type_name.as_str() {
+            "Futures" => Instrument::Futures(deserialize_fields::<Futures>(value)?),
+            "Bond" => Instrument::Bond(deserialize_fields::<Bond>(value)?),
+            "BondFutures" => Instrument::BondFutures(deserialize_fields::<BondFutures>(value)?),
+            "KTBF" => Instrument::KTBF(deserialize_fields::<KTBF>(value)?),
+            "PlainSwap" | "IRS" | "CRS" | "FxSwap" | "FxForward" | "FxSpot" => {
+                Instrument::PlainSwap(deserialize_fields::<PlainSwap>(value)?)
+            },
+            "FxFutures" => Instrument::FxFutures(deserialize_fields::<FxFutures>(value)?),
+            "VanillaOption" | "VanillaCall" | "VanillaPut" => {
+                Instrument::VanillaOption(deserialize_fields::<VanillaOption>(value)?)
+            },
+            "Stock" => Instrument::Stock(deserialize_fields::<Stock>(value)?),
+            "Cash" => Instrument::Cash(deserialize_fields::<Cash>(value)?),
+            _ => bail!("({}:{}) unknown type {} (expected one of {:?})", file!(), line!(), type_name, TRADE_TYPES),
+        },
+        Some(_) => bail!("({}:{}) field type must be a string", file!(), line!()),
+        None => bail!("({}:{}) missing field type", file!(), line!()),
+    };
+    validate_trade(&instrument, &trade.value)?;
+    Ok(instrument)
+}
+
+fn load_raw_trades(trades: Vec<RawTrade>) -> Result<Instruments> {
+    let mut codes = HashSet::new();
+    let mut instruments = Vec::with_capacity(trades.len());
+    for trade in trades.iter() {
+        let instrument = deserialize_trade(trade)
+            .with_context(|| anyhow!("({}:{}) invalid {}", file!(), line!(), trade.describe()))?;
+        if !codes.insert(instrument.get_code().clone()) {
+            bail!("({}:{}) duplicated code {} in {}", file!(), line!(), instrument.get_code(), trade.describe());
+        }
+        instruments.push(Rc::new(instrument));
+    }
+    Ok(Instruments::new(instruments))
+}
+
+/// load a list of trades (or a single trade). Each trade has the fields of the instrument with "type"
+/// given by get_type_name or the variant name of Instrument (e.g., "IRS" or "PlainSwap").
+/// In csv, a row is a trade and the columns are the fields, where the nested fields are JSON in the cells.
+/// Unknown fields, an empty code, a non-positive unit_notional, the maturity before the issue date and
+/// duplicated codes are errors, with the position, the code and the field of the trade.
+pub fn load_trades_from_str(text: &str, format: TradeFileFormat) -> Result<Instruments> {
+    let trades = match format {
+        TradeFileFormat::Json => {
+            let value: Value = serde_json::from_str(text)
+                .with_context(|| anyhow!("({}:{}) failed to parse trade json", file!(), line!()))?;
+            split_trades(value, "trade")
+        },
+        TradeFileFormat::Yaml => {
+            let value: serde_yaml::Value = serde_yaml::from_str(text)
+                .with_context(|| anyhow!("({}:{}) failed to parse trade yaml", file!(), line!()))?;
+            split_trades(yaml_to_json(value)?, "trade")
+        },
+        TradeFileFormat::Csv => parse_csv(text)?,
+    };
+    load_raw_trades(trades)
+}
+
+/// load trades of a JSON value (e.g., a part of a request) as load_trades_from_str
+pub fn load_trades_from_json_value(value: Value) -> Result<Instruments> {
+    load_raw_trades(split_trades(value, "trade"))
+}
+
+/// load trades from a file whose format is given by the extension
+pub fn load_trades(path: &str) -> Result<Instruments> {
+    let format = TradeFileFormat::from_path(path)?;
+    let text = std::fs::read_to_string(path)
+        .with_context(|| anyhow!("({}:{}) failed to read trade file {}", file!(), line!(), path))?;
+    load_trades_from_str(&text, format)
+        .with_context(|| anyhow!("({}:{}) failed to load trades from {}", file!(), line!(), path))
+}
+
+/// write trades in the format read by load_trades_from_str (csv is not supported for writing)
+pub fn write_trades_to_string(instruments: &Instruments, format: TradeFileFormat) -> Result<String> {
+    match format {
+        TradeFileFormat::Json => serde_json::to_string_pretty(instruments)
+            .with_context(|| anyhow!("({}:{}) failed to write trades in json", file!(), line!())),
+        TradeFileFormat::Yaml => serde_yaml::to_string(instruments)
+            .with_context(|| anyhow!("({}:{}) failed to write trades in yaml", file!(), line!())),
+        TradeFileFormat::Csv => bail!("({}:{}) writing trades in csv is not supported", file!(), line!()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::Currency;
+    use crate::instruments::{futures::Futures, stock::Stock};
+    use time::macros::datetime;
+
+    fn sample_instruments() -> Instruments {
+        let futures = Futures::new(
+            350.0,
+            datetime!(2021-01-17 09:00:00 +09:00),
+            datetime!(2022-01-17 15:40:00 +09:00),
+            datetime!(2022-01-17 15:40:00 +09:00),
+            datetime!(2022-01-17 15:40:00 +09:00),
+            250_000.0,
+            Currency::KRW,
+            Currency::KRW,
+            "KOSPI2".to_string(),
+            "KOSPI2 Fut Jan22".to_string(),
+            "165AAA".to_string(),
+        );
+        let stock = Stock::new(
+            "Samsung".to_string(),
+            "005930".to_string(),
+            vec!["005930".to_string()],
+            Currency::KRW,
+            None,
+        );
+        Instruments::new(vec![Rc::new(Instrument::Futures(futures)), Rc::new(Instrument::Stock(stock))])
+    }
+
+    #[test]
+    fn test_trade_round_trip() -> Result<()> {
+        let instruments = sample_instruments();
+        for format in [TradeFileFormat::Json, TradeFileFormat::Yaml] {
+            let text = write_trades_to_string(&instruments, format)?;
+            assert!(text.contains("Futures") && text.contains("Stock"));
+            let loaded = load_trades_from_str(&text, format)?;
+            assert_eq!(loaded.len(), 2);
+            assert_eq!(write_trades_to_string(&loaded, format)?, text);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_trade_errors() -> Result<()> {
+        let json = write_trades_to_string(&sample_instruments(), TradeFileFormat::Json)?;
+        let mut value: Value = serde_json::from_str(&json)?;
+
+        value[0]["unit_notional"] = Value::String("large".to_string());
+        let error = format!("{:#}", load_trades_from_str(&value.to_string(), TradeFileFormat::Json).unwrap_err());
+        assert!(error.contains("trade #0 (code: 165AAA, type: Futures)"), "{}", error);
+        assert!(error.contains("field unit_notional"), "{}", error);
+
+        value[0]["unit_notional"] = Value::from(-250_000.0);
+        let error = format!("{:#}", load_trades_from_str(&value.to_string(), TradeFileFormat::Json).unwrap_err());
+        assert!(error.contains("unit_notional must be positive"), "{}", error);
+
+        value[0]["unit_notional"] = Value::from(250_000.0);
+        value[1]["unit_notinal"] = Value::from(1.0);
+        let error = format!("{:#}", load_trades_from_str(&value.to_string(), TradeFileFormat::Json).unwrap_err());
+        assert!(error.contains("trade #1 (code: 005930, type: Stock)") && error.contains("unknown field unit_notinal"), "{}", error);
+
+        value[1] = value[0].clone();
+        let error = format!("{:#}", load_trades_from_str(&value.to_string(), TradeFileFormat::Json).unwrap_err());
+        assert!(error.contains("duplicated code 165AAA"), "{}", error);
+
+        value[1]["type"] = Value::String("Swaption".to_string());
+        let error = format!("{:#}", load_trades_from_str(&value.to_string(), TradeFileFormat::Json).unwrap_err());
+        assert!(error.contains("unknown type Swaption"), "{}", error);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trade_csv() -> Result<()> {
+        let csv = "type,code,name,currency,underlying_codes,rank_type\n\
+            Stock,\"\"\"005930\"\"\",Samsung,KRW,\"[\"\"005930\"\"]\",Common\n\
+            Stock,000660,SK Hynix,KRW,\"[\"\"000660\"\"]\",Undefined\n";
+        let instruments = load_trades_from_str(csv, TradeFileFormat::Csv)?;
+        assert_eq!(instruments.len(), 2);
+        assert_eq!(instruments[0].get_code(), "005930");
+        assert_eq!(instruments[1].get_underlying_codes(), vec!["000660"]);
+
+        let csv = "type,code,name,currency,underlying_codes,rank_type\nStock,000660,SK Hynix,KRW,\"[\"\"000660\"\"]\",Senior\n";
+        let error = format!("{:#}", load_trades_from_str(csv, TradeFileFormat::Csv).unwrap_err());
+        assert!(error.contains("trade at line 2 (code: 000660, type: Stock)"), "{}", error);
+        assert!(error.contains("field rank_type"), "{}", error);
+        Ok(())
+    }
+}
@@ -10,8 +10,11 @@ pub mod parameters;
 pub mod data;
 pub mod evaluation_date;
 pub mod pricing_engines;
+pub mod risk;
 pub mod currency;
 pub mod enums;
+#[cfg(feature = "server")]
+pub mod server;
 #[macro_use]
 pub mod macros;
 
@@ -4,4 +4,6 @@ pub mod interpolators {
     pub mod stepwise_interpolatior;
     pub mod bilinear_interpolator;
 }
-pub mod cholescky_factorization;
\ No newline at end of file
+pub mod cholescky_factorization;
+pub mod solvers;
+pub mod optimizers;
@@ -0,0 +1,297 @@
+use crate::definitions::Real;
+use anyhow::{bail, Result};
+
+/// Result of a minimization
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptimizerReport {
+    params: Vec<Real>,
+    value: Real,
+    iterations: usize,
+}
+
+impl OptimizerReport {
+    pub fn get_params(&self) -> &Vec<Real> {
+        &self.params
+    }
+
+    pub fn get_value(&self) -> Real {
+        self.value
+    }
+
+    pub fn get_iterations(&self) -> usize {
+        self.iterations
+    }
+}
+
+/// Nelder-Mead simplex minimization.
+/// The initial simplex is init and init + steps[i] * e_i.
+/// It stops when the spread of the function values in the simplex is below f_tolerance
+/// or after max_iterations (the best point found is returned in both cases).
+/// Constraints can be imposed by returning a large value from f.
+pub fn nelder_mead<F>(
+    mut f: F,
+    init: &[Real],
+    steps: &[Real],
+    f_tolerance: Real,
+    max_iterations: usize,
+) -> Result<OptimizerReport>
+where F: FnMut(&[Real]) -> Real
+{
+    let n = init.len();
+    if n == 0 || steps.len() != n {
+        bail!(
+            "({}:{}) init ({}) and steps ({}) must have the same positive length",
+            file!(), line!(), n, steps.len()
+        );
+    }
+    let (alpha, gamma, rho, sigma) = (1.0, 2.0, 0.5, 0.5);
+
+    let mut simplex: Vec<Vec<Real>> = Vec::with_capacity(n + 1);
+    simplex.push(init.to_vec());
+    for i in 0..n {
+        let mut point = init.to_vec();
+        point[i] += steps[i];
+        simplex.push(point);
+    }
+    let mut values: Vec<Real> = simplex.iter().map(|point| f(point)).collect();
+
+    let mut iterations = 0;
+    while iterations < max_iterations {
+        // order the simplex
+        let mut order: Vec<usize> = (0..=n).collect();
+        order.sort_by(|&i, &j| values[i].partial_cmp(&values[j]).unwrap_or(std::cmp::Ordering::Equal));
+        simplex = order.iter().map(|&i| simplex[i].clone()).collect();
+        values = order.iter().map(|&i| values[i]).collect();
+
+        if (values[n] - values[0]).abs() <= f_tolerance * (1.0 + values[0].abs()) {
+            break;
+        }
+        iterations += 1;
+
+        let mut centroid = vec![0.0; n];
+        for point in simplex.iter().take(n) {
+            for (c, x) in centroid.iter_mut().zip(point.iter()) {
+                *c += x / n as Real;
+            }
+        }
+        let along = |coef: Real, worst: &[Real]| -> Vec<Real> {
+            centroid.iter().zip(worst.iter()).map(|(c, w)| c + coef * (c - w)).collect()
+        };
+
+        let reflected = along(alpha, &simplex[n]);
+        let f_reflected = f(&reflected);
+        if f_reflected < values[0] {
+            let expanded = along(gamma, &simplex[n]);
+            let f_expanded = f(&expanded);
+            if f_expanded < f_reflected {
+                simplex[n] = expanded;
+                values[n] = f_expanded;
+            } else {
+                simplex[n] = reflected;
+                values[n] = f_reflected;
+            }
+            continue;
+        }
+        if f_reflected < values[n - 1] {
+            simplex[n] = reflected;
+            values[n] = f_reflected;
+            continue;
+        }
+        let contracted = along(-rho, &simplex[n]);
+        let f_contracted = f(&contracted);
+        if f_contracted < values[n] {
+            simplex[n] = contracted;
+            values[n] = f_contracted;
+            continue;
+        }
+        // shrink toward the best point
+        for i in 1..=n {
+            let shrunk: Vec<Real> = simplex[0].iter().zip(simplex[i].iter())
+                .map(|(b, x)| b + sigma * (x - b))
+                .collect();
+            values[i] = f(&shrunk);
+            simplex[i] = shrunk;
+        }
+    }
+
+    let best = (0..=n)
+        .min_by(|&i, &j| values[i].partial_cmp(&values[j]).unwrap_or(std::cmp::Ordering::Equal))
+        .unwrap();
+    Ok(OptimizerReport {
+        params: simplex[best].clone(),
+        value: values[best],
+        iterations,
+    })
+}
+
+/// Solve the square linear system a x = b by Gaussian elimination with partial pivoting.
+/// It fails if an entry is not finite or the system is singular,
+/// i.e., a pivot is below 1.0e-14 times the largest absolute entry of a.
+pub fn solve_linear_system(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Result<Vec<f64>> {
+    let n = b.len();
+    if a.len() != n || a.iter().any(|row| row.len() != n) {
+        bail!("({}:{}) the matrix is not {} x {}", file!(), line!(), n, n);
+    }
+    if a.iter().flatten().chain(b.iter()).any(|v| !v.is_finite()) {
+        bail!("({}:{}) the linear system has a non-finite entry", file!(), line!());
+    }
+    let scale = a.iter().flatten().fold(0.0_f64, |max, v| max.max(v.abs()));
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+            .unwrap_or(col);
+        if a[pivot][col].abs() <= 1.0e-14 * scale {
+            bail!("({}:{}) the linear system is singular", file!(), line!());
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in (col + 1)..n {
+            let factor = a[row][col] / a[col][col];
+            let pivot_row = a[col].clone();
+            for (x, p) in a[row].iter_mut().zip(pivot_row.iter()).skip(col) {
+                *x -= factor * p;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum = b[row] - ((row + 1)..n).map(|c| a[row][c] * x[c]).sum::<f64>();
+        x[row] = sum / a[row][row];
+    }
+    Ok(x)
+}
+
+/// Levenberg-Marquardt minimization of the sum of squared residuals.
+/// The Jacobian is taken by forward differences and the normal equations are solved in f64.
+/// It stops when the relative decrease of the sum of squares is below f_tolerance,
+/// when the damping can not be increased further or after max_iterations.
+/// The reported value is the sum of squared residuals at the returned point.
+pub fn levenberg_marquardt<F>(
+    mut residuals: F,
+    init: &[Real],
+    f_tolerance: Real,
+    max_iterations: usize,
+) -> Result<OptimizerReport>
+where F: FnMut(&[Real]) -> Result<Vec<Real>>
+{
+    let n = init.len();
+    if n == 0 {
+        bail!("({}:{}) init must not be empty", file!(), line!());
+    }
+    let sum_of_squares = |r: &[Real]| r.iter().map(|x| (*x as f64) * (*x as f64)).sum::<f64>();
+
+    let mut x = init.to_vec();
+    let mut r = residuals(&x)?;
+    let m = r.len();
+    let mut cost = sum_of_squares(&r);
+    let mut lambda = 1.0e-3;
+    let mut iterations = 0;
+
+    while iterations < max_iterations {
+        iterations += 1;
+        // jacobian (m x n) by forward differences
+        let mut jacobian = vec![vec![0.0_f64; n]; m];
+        for j in 0..n {
+            let h = 1.0e-3 * x[j].abs().max(1.0e-2);
+            let mut shifted = x.clone();
+            shifted[j] += h;
+            let r_shifted = residuals(&shifted)?;
+            for i in 0..m {
+                jacobian[i][j] = (r_shifted[i] as f64 - r[i] as f64) / h as f64;
+            }
+        }
+        let mut jtj = vec![vec![0.0_f64; n]; n];
+        let mut jtr = vec![0.0_f64; n];
+        for (row, res) in jacobian.iter().zip(r.iter()) {
+            for a in 0..n {
+                jtr[a] += row[a] * *res as f64;
+                for b in 0..n {
+                    jtj[a][b] += row[a] * row[b];
+                }
+            }
+        }
+
+        let mut improved = false;
+        while lambda < 1.0e10 {
+            let mut damped = jtj.clone();
+            for (a, row) in damped.iter_mut().enumerate() {
+                row[a] += lambda * jtj[a][a].max(1.0e-12);
+            }
+            let step = match solve_linear_system(damped, jtr.iter().map(|g| -g).collect()) {
+                Ok(step) => step,
+                Err(_) => {
+                    lambda *= 10.0;
+                    continue;
+                }
+            };
+            let candidate: Vec<Real> = x.iter().zip(step.iter()).map(|(x, d)| x + *d as Real).collect();
+            let r_candidate = match residuals(&candidate) {
+                Ok(r) if r.iter().all(|v| v.is_finite()) => r,
+                _ => {
+                    lambda *= 10.0;
+                    continue;
+                }
+            };
+            let cost_candidate = sum_of_squares(&r_candidate);
+            if cost_candidate < cost {
+                let decrease = cost - cost_candidate;
+                x = candidate;
+                r = r_candidate;
+                cost = cost_candidate;
+                lambda = (lambda / 10.0).max(1.0e-12);
+                improved = decrease > f_tolerance as f64 * cost.max(1.0e-300);
+                break;
+            }
+            lambda *= 10.0;
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    Ok(OptimizerReport {
+        params: x,
+        value: cost as Real,
+        iterations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nelder_mead_rosenbrock() -> Result<()> {
+        let rosenbrock = |x: &[Real]| (1.0 - x[0]).powi(2) + 100.0 * (x[1] - x[0] * x[0]).powi(2);
+        let res = nelder_mead(rosenbrock, &[-1.2, 1.0], &[0.1, 0.1], 1.0e-12, 2000)?;
+        let params = res.get_params();
+        assert!((params[0] - 1.0).abs() < 1.0e-2, "{:?}", res);
+        assert!((params[1] - 1.0).abs() < 2.0e-2, "{:?}", res);
+        Ok(())
+    }
+
+    #[test]
+    fn test_levenberg_marquardt_curve_fit() -> Result<()> {
+        // y = a * exp(-b * x)
+        let xs: Vec<Real> = (0..20).map(|i| i as Real * 0.25).collect();
+        let ys: Vec<Real> = xs.iter().map(|x| 2.0 * (-0.7 * x).exp()).collect();
+        let residuals = |p: &[Real]| -> Result<Vec<Real>> {
+            Ok(xs.iter().zip(ys.iter()).map(|(x, y)| p[0] * (-p[1] * x).exp() - y).collect())
+        };
+        let res = levenberg_marquardt(residuals, &[1.0, 0.2], 1.0e-10, 100)?;
+        let params = res.get_params();
+        assert!((params[0] - 2.0).abs() < 1.0e-3, "{:?}", res);
+        assert!((params[1] - 0.7).abs() < 1.0e-3, "{:?}", res);
+        Ok(())
+    }
+
+    #[test]
+    fn test_solve_linear_system() -> Result<()> {
+        let x = solve_linear_system(vec![vec![0.0, 2.0], vec![1.0, 1.0]], vec![4.0, 3.0])?;
+        assert!((x[0] - 1.0).abs() < 1.0e-12 && (x[1] - 2.0).abs() < 1.0e-12, "{:?}", x);
+        assert!(solve_linear_system(vec![vec![1.0, 2.0], vec![2.0, 4.0]], vec![1.0, 2.0]).is_err());
+        assert!(solve_linear_system(vec![vec![f64::NAN, 0.0], vec![0.0, 1.0]], vec![1.0, 1.0]).is_err());
+        Ok(())
+    }
+}
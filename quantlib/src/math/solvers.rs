@@ -0,0 +1,384 @@
+use crate::definitions::Real;
+use anyhow::{anyhow, bail, Result};
+use serde::{Serialize, Deserialize};
+
+/// One dimensional root finders (bisection, Brent, safeguarded Newton).
+/// x_tolerance: the root is accepted if the bracket (or the Newton step) is smaller than this
+/// f_tolerance: the root is accepted if |f(x)| is smaller than this
+/// max_iterations: the solver fails if the root is not found within this number of iterations
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SolverConfig {
+    x_tolerance: Real,
+    f_tolerance: Real,
+    max_iterations: usize,
+}
+
+impl Default for SolverConfig {
+    fn default() -> SolverConfig {
+        SolverConfig {
+            x_tolerance: 1.0e-7,
+            f_tolerance: 1.0e-7,
+            max_iterations: 100,
+        }
+    }
+}
+
+impl SolverConfig {
+    pub fn new(x_tolerance: Real, f_tolerance: Real, max_iterations: usize) -> Result<SolverConfig> {
+        if x_tolerance <= 0.0 {
+            bail!("({}:{}) x_tolerance must be > 0.0, got {}", file!(), line!(), x_tolerance);
+        }
+        if f_tolerance <= 0.0 {
+            bail!("({}:{}) f_tolerance must be > 0.0, got {}", file!(), line!(), f_tolerance);
+        }
+        if max_iterations == 0 {
+            bail!("({}:{}) max_iterations must be > 0", file!(), line!());
+        }
+        Ok(SolverConfig { x_tolerance, f_tolerance, max_iterations })
+    }
+
+    pub fn with_x_tolerance(mut self, x_tolerance: Real) -> SolverConfig {
+        self.x_tolerance = x_tolerance;
+        self
+    }
+
+    pub fn with_f_tolerance(mut self, f_tolerance: Real) -> SolverConfig {
+        self.f_tolerance = f_tolerance;
+        self
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> SolverConfig {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn get_x_tolerance(&self) -> Real {
+        self.x_tolerance
+    }
+
+    pub fn get_f_tolerance(&self) -> Real {
+        self.f_tolerance
+    }
+
+    pub fn get_max_iterations(&self) -> usize {
+        self.max_iterations
+    }
+}
+
+/// Result of a root search
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SolverReport {
+    root: Real,
+    function_value: Real,
+    iterations: usize,
+    function_evaluations: usize,
+}
+
+impl SolverReport {
+    pub fn get_root(&self) -> Real {
+        self.root
+    }
+
+    pub fn get_function_value(&self) -> Real {
+        self.function_value
+    }
+
+    pub fn get_iterations(&self) -> usize {
+        self.iterations
+    }
+
+    pub fn get_function_evaluations(&self) -> usize {
+        self.function_evaluations
+    }
+}
+
+/// Widen [lower, upper] geometrically until f(lower) and f(upper) have opposite signs.
+/// Returns the bracket and the function values on it: (lower, upper, f(lower), f(upper))
+pub fn find_bracket<F>(
+    mut f: F,
+    mut lower: Real,
+    mut upper: Real,
+    max_expansions: usize,
+) -> Result<(Real, Real, Real, Real)>
+where F: FnMut(Real) -> Result<Real>
+{
+    if lower >= upper {
+        bail!("({}:{}) lower ({}) must be less than upper ({})", file!(), line!(), lower, upper);
+    }
+    let mut f_lower = f(lower)?;
+    let mut f_upper = f(upper)?;
+    for _ in 0..max_expansions {
+        if f_lower * f_upper <= 0.0 {
+            return Ok((lower, upper, f_lower, f_upper));
+        }
+        let width = upper - lower;
+        // expand toward the side whose function value is smaller in magnitude
+        if f_lower.abs() < f_upper.abs() {
+            lower -= 1.6 * width;
+            f_lower = f(lower)?;
+        } else {
+            upper += 1.6 * width;
+            f_upper = f(upper)?;
+        }
+    }
+    if f_lower * f_upper <= 0.0 {
+        return Ok((lower, upper, f_lower, f_upper));
+    }
+    Err(anyhow!(
+        "({}:{}) failed to bracket a root after {} expansions: f({}) = {}, f({}) = {}",
+        file!(), line!(), max_expansions, lower, f_lower, upper, f_upper
+    ))
+}
+
+fn check_bracket(lower: Real, upper: Real, f_lower: Real, f_upper: Real) -> Result<()> {
+    if lower >= upper {
+        bail!("({}:{}) lower ({}) must be less than upper ({})", file!(), line!(), lower, upper);
+    }
+    if f_lower * f_upper > 0.0 {
+        bail!(
+            "({}:{}) root is not bracketed: f({}) = {}, f({}) = {}",
+            file!(), line!(), lower, f_lower, upper, f_upper
+        );
+    }
+    Ok(())
+}
+
+/// Bisection on [lower, upper] where f(lower) and f(upper) have opposite signs
+pub fn bisection<F>(
+    mut f: F,
+    lower: Real,
+    upper: Real,
+    config: &SolverConfig,
+) -> Result<SolverReport>
+where F: FnMut(Real) -> Result<Real>
+{
+    let (mut a, mut b) = (lower, upper);
+    let mut f_a = f(a)?;
+    let f_b = f(b)?;
+    check_bracket(a, b, f_a, f_b)?;
+    if f_a == 0.0 {
+        return Ok(SolverReport { root: a, function_value: f_a, iterations: 0, function_evaluations: 2 });
+    }
+    if f_b == 0.0 {
+        return Ok(SolverReport { root: b, function_value: f_b, iterations: 0, function_evaluations: 2 });
+    }
+
+    for iteration in 1..=config.max_iterations {
+        let mid = 0.5 * (a + b);
+        let f_mid = f(mid)?;
+        let tol = 2.0 * Real::EPSILON * mid.abs() + config.x_tolerance;
+        if f_mid.abs() < config.f_tolerance || 0.5 * (b - a) < tol {
+            return Ok(SolverReport {
+                root: mid,
+                function_value: f_mid,
+                iterations: iteration,
+                function_evaluations: iteration + 2,
+            });
+        }
+        if f_a * f_mid < 0.0 {
+            b = mid;
+        } else {
+            a = mid;
+            f_a = f_mid;
+        }
+    }
+    Err(anyhow!(
+        "({}:{}) bisection did not converge in {} iterations (bracket = [{}, {}])",
+        file!(), line!(), config.max_iterations, a, b
+    ))
+}
+
+/// Brent's method (inverse quadratic interpolation, secant and bisection)
+/// on [lower, upper] where f(lower) and f(upper) have opposite signs
+pub fn brent<F>(
+    mut f: F,
+    lower: Real,
+    upper: Real,
+    config: &SolverConfig,
+) -> Result<SolverReport>
+where F: FnMut(Real) -> Result<Real>
+{
+    let mut a = lower;
+    let mut b = upper;
+    let mut f_a = f(a)?;
+    let mut f_b = f(b)?;
+    check_bracket(a, b, f_a, f_b)?;
+
+    let mut c = a;
+    let mut f_c = f_a;
+    let mut d = b - a;
+    let mut e = d;
+
+    for iteration in 1..=config.max_iterations {
+        if f_b * f_c > 0.0 {
+            c = a;
+            f_c = f_a;
+            d = b - a;
+            e = d;
+        }
+        if f_c.abs() < f_b.abs() {
+            a = b;
+            b = c;
+            c = a;
+            f_a = f_b;
+            f_b = f_c;
+            f_c = f_a;
+        }
+
+        let tol = 2.0 * Real::EPSILON * b.abs() + 0.5 * config.x_tolerance;
+        let m = 0.5 * (c - b);
+        if f_b.abs() < config.f_tolerance || m.abs() <= tol {
+            return Ok(SolverReport {
+                root: b,
+                function_value: f_b,
+                iterations: iteration,
+                function_evaluations: iteration + 1,
+            });
+        }
+
+        if e.abs() >= tol && f_a.abs() > f_b.abs() {
+            let s = f_b / f_a;
+            let (mut p, mut q);
+            if a == c {
+                // secant
+                p = 2.0 * m * s;
+                q = 1.0 - s;
+            } else {
+                // inverse quadratic interpolation
+                let q_ac = f_a / f_c;
+                let r = f_b / f_c;
+                p = s * (2.0 * m * q_ac * (q_ac - r) - (b - a) * (r - 1.0));
+                q = (q_ac - 1.0) * (r - 1.0) * (s - 1.0);
+            }
+            if p > 0.0 {
+                q = -q;
+            } else {
+                p = -p;
+            }
+            if 2.0 * p < (3.0 * m * q - (tol * q).abs()).min((e * q).abs()) {
+                e = d;
+                d = p / q;
+            } else {
+                d = m;
+                e = m;
+            }
+        } else {
+            d = m;
+            e = m;
+        }
+
+        a = b;
+        f_a = f_b;
+        b += if d.abs() > tol { d } else { tol.copysign(m) };
+        f_b = f(b)?;
+    }
+    Err(anyhow!(
+        "({}:{}) brent did not converge in {} iterations (x = {}, f(x) = {})",
+        file!(), line!(), config.max_iterations, b, f_b
+    ))
+}
+
+/// Newton's method safeguarded by the bracket [lower, upper]:
+/// if a Newton step leaves the bracket or the derivative vanishes, a bisection step is taken.
+/// f_df returns (f(x), f'(x))
+pub fn newton<F>(
+    mut f_df: F,
+    init_guess: Real,
+    lower: Real,
+    upper: Real,
+    config: &SolverConfig,
+) -> Result<SolverReport>
+where F: FnMut(Real) -> Result<(Real, Real)>
+{
+    let (f_lower, _) = f_df(lower)?;
+    let (f_upper, _) = f_df(upper)?;
+    let mut evaluations = 2;
+    check_bracket(lower, upper, f_lower, f_upper)?;
+
+    // orient the bracket so that f(neg) < 0 < f(pos)
+    let (mut neg, mut pos) = match f_lower < 0.0 {
+        true => (lower, upper),
+        false => (upper, lower),
+    };
+    let mut x = init_guess.clamp(lower, upper);
+
+    for iteration in 1..=config.max_iterations {
+        let (fx, dfx) = f_df(x)?;
+        evaluations += 1;
+        if fx.abs() < config.f_tolerance {
+            return Ok(SolverReport { root: x, function_value: fx, iterations: iteration, function_evaluations: evaluations });
+        }
+        if fx < 0.0 {
+            neg = x;
+        } else {
+            pos = x;
+        }
+
+        let newton_x = x - fx / dfx;
+        let in_bracket = dfx != 0.0
+            && newton_x.is_finite()
+            && (newton_x - neg) * (newton_x - pos) < 0.0;
+        let next_x = match in_bracket {
+            true => newton_x,
+            false => 0.5 * (neg + pos),
+        };
+
+        let tol = 2.0 * Real::EPSILON * x.abs() + config.x_tolerance;
+        if (next_x - x).abs() < tol || (pos - neg).abs() < tol {
+            let (f_next, _) = f_df(next_x)?;
+            evaluations += 1;
+            return Ok(SolverReport { root: next_x, function_value: f_next, iterations: iteration, function_evaluations: evaluations });
+        }
+        x = next_x;
+    }
+    Err(anyhow!(
+        "({}:{}) newton did not converge in {} iterations (x = {})",
+        file!(), line!(), config.max_iterations, x
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cubic(x: Real) -> Result<Real> {
+        Ok(x * x * x - 2.0 * x - 5.0)
+    }
+
+    #[test]
+    fn test_solvers_cubic() -> Result<()> {
+        let expected = 2.094_551_5;
+        let config = SolverConfig::default();
+
+        let res = bisection(cubic, 2.0, 3.0, &config)?;
+        assert!((res.get_root() - expected).abs() < 1.0e-5, "bisection: {:?}", res);
+
+        let res = brent(cubic, 2.0, 3.0, &config)?;
+        assert!((res.get_root() - expected).abs() < 1.0e-5, "brent: {:?}", res);
+        assert!(res.get_iterations() < 15, "brent: {:?}", res);
+
+        let res = newton(|x| Ok((cubic(x)?, 3.0 * x * x - 2.0)), 3.0, 2.0, 3.0, &config)?;
+        assert!((res.get_root() - expected).abs() < 1.0e-5, "newton: {:?}", res);
+        Ok(())
+    }
+
+    #[test]
+    fn test_newton_safeguard() -> Result<()> {
+        // f'(0) = 0 so that the first step must fall back to bisection
+        let config = SolverConfig::default();
+        let res = newton(|x| Ok((x * x * x - 1.0, 3.0 * x * x)), 0.0, -1.0, 2.0, &config)?;
+        assert!((res.get_root() - 1.0).abs() < 1.0e-5, "newton: {:?}", res);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_bracket() -> Result<()> {
+        let (lower, upper, f_lower, f_upper) = find_bracket(|x| Ok(x - 10.0), 0.0, 1.0, 10)?;
+        assert!(lower <= 10.0 && 10.0 <= upper);
+        assert!(f_lower * f_upper <= 0.0);
+
+        assert!(find_bracket(|x| Ok(x * x + 1.0), 0.0, 1.0, 5).is_err());
+        assert!(brent(|x| Ok(x * x + 1.0), 0.0, 1.0, &SolverConfig::default()).is_err());
+        Ok(())
+    }
+}
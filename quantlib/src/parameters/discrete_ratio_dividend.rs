@@ -210,6 +210,44 @@ impl DiscreteRatioDividend {
 
         // update self.dividend_amounts
         self.dividend_amounts = &self.dividend_amounts + bump_mask * bump_val;
+        self.reset_deduction_interpolator()
+    }
+
+    /// multiply dividend amount by (1 + ratio) where the dividend in the interval: date1 < div_date <= date2
+    /// update dividend_yields and deduction_interpolator
+    pub fn scale_date_interval(
+        &mut self,
+        date1: Option<&OffsetDateTime>,
+        date2: Option<&OffsetDateTime>,
+        ratio: Real
+    ) -> Result<()> {
+        let d1 = match date1 {
+            None => -99999999,
+            Some(date1) => to_yyyymmdd_int(date1),
+        };
+
+        let d2 = match date2 {
+            None => 99999999,
+            Some(date2) => to_yyyymmdd_int(date2),
+        };
+
+        if d1 >= d2 {
+            return Err(anyhow!(
+                "DiscreteRatioDividend::scale_date_interval: {} >= {}", 
+                d1, d2
+            ));
+        }
+
+        let scale = self.date_integers.mapv(
+            |x| if (d1 < x) & (x <= d2) {1.0 + ratio} else {1.0}
+        );
+
+        self.dividend_amounts = &self.dividend_amounts * scale;
+        self.reset_deduction_interpolator()
+    }
+
+    /// update self.dividend_yields and deduction_interpolator from self.dividend_amounts
+    fn reset_deduction_interpolator(&mut self) -> Result<()> {
         // update self.dividend_yields and remake a incremental_deduction_ratio
         self.dividend_yields = &self.dividend_amounts / self.spot;
         let mut incremental_deduction_ratio = Array1::zeros(self.dividend_yields.len());
@@ -43,6 +43,19 @@ impl Quanto {
         self.fx_volatility.borrow().get_value(t, forward_moneyness) * self.correlation
     }
 
+    pub fn get_correlation(&self) -> Real {
+        self.correlation
+    }
+
+    /// parallel shift of the correlation, e.g., for the correlation sensitivity
+    pub fn bump_correlation(&mut self, bump: Real) {
+        self.correlation += bump;
+    }
+
+    pub fn get_fx_volatility(&self) -> &Rc<RefCell<Volatility>> {
+        &self.fx_volatility
+    }
+
     pub fn get_underlying_code(&self) -> &String {
         &self.underlying_code
     }
@@ -0,0 +1,496 @@
+use crate::definitions::{Real, Time};
+use crate::data::surface_data::SurfaceData;
+use crate::enums::OptionType;
+use crate::evaluation_date::EvaluationDate;
+use crate::math::optimizers::levenberg_marquardt;
+use crate::parameters::{
+    market_price::MarketPrice,
+    volatility::VolatilityTrait,
+    volatilities::volatility_bump_grid::VolatilityBumpGrid,
+    zero_curve::ZeroCurve,
+};
+use crate::pricing_engines::implied_volatility::{black_price, implied_black_volatility};
+use crate::time::calendar_trait::CalendarTrait;
+use crate::time::calendars::nullcalendar::NullCalendar;
+//
+use anyhow::{anyhow, bail, Context, Result};
+use log::warn;
+use ndarray::Array1;
+use num_complex::Complex64;
+use serde::{Deserialize, Serialize};
+use statrs::distribution::{Continuous, Normal};
+use std::{
+    cell::RefCell,
+    f64::consts::PI,
+    rc::Rc,
+};
+use time::OffsetDateTime;
+
+const MIN_TIME: Time = 1.0e-4;
+const CALIBRATION_MAX_ITERATIONS: usize = 100;
+const CALIBRATION_F_TOLERANCE: Real = 1.0e-8;
+
+/// Heston (1993) stochastic volatility model under the forward measure:
+/// dS/S = sqrt(v) dW1, dv = kappa * (theta - v) dt + xi * sqrt(v) dW2, dW1 dW2 = rho dt
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HestonParameters {
+    v0: Real,
+    kappa: Real,
+    theta: Real,
+    xi: Real,
+    rho: Real,
+}
+
+/// initial guess of the calibration
+impl Default for HestonParameters {
+    fn default() -> HestonParameters {
+        HestonParameters {
+            v0: 0.04,
+            kappa: 1.5,
+            theta: 0.04,
+            xi: 0.5,
+            rho: -0.5,
+        }
+    }
+}
+
+impl HestonParameters {
+    pub fn new(v0: Real, kappa: Real, theta: Real, xi: Real, rho: Real) -> Result<HestonParameters> {
+        if v0 <= 0.0 || kappa <= 0.0 || theta <= 0.0 || xi <= 0.0 || rho.abs() >= 1.0 {
+            bail!(
+                "({}:{}) invalid Heston parameters: v0 = {}, kappa = {}, theta = {}, xi = {}, rho = {} \
+                (v0, kappa, theta, xi > 0 and |rho| < 1)",
+                file!(), line!(), v0, kappa, theta, xi, rho
+            );
+        }
+        Ok(HestonParameters { v0, kappa, theta, xi, rho })
+    }
+
+    pub fn get_v0(&self) -> Real {
+        self.v0
+    }
+
+    pub fn get_kappa(&self) -> Real {
+        self.kappa
+    }
+
+    pub fn get_theta(&self) -> Real {
+        self.theta
+    }
+
+    pub fn get_xi(&self) -> Real {
+        self.xi
+    }
+
+    pub fn get_rho(&self) -> Real {
+        self.rho
+    }
+
+    /// 2 * kappa * theta >= xi^2, i.e., the variance process does not reach zero
+    pub fn satisfies_feller_condition(&self) -> bool {
+        2.0 * self.kappa * self.theta >= self.xi * self.xi
+    }
+
+    /// E[exp(i u ln(S_t / F_t))] in the "little Heston trap" form (Albrecher et al. 2007)
+    pub fn characteristic_function(&self, u: Complex64, t: Time) -> Complex64 {
+        let i = Complex64::i();
+        let (v0, kappa, theta, xi, rho) = (
+            self.v0 as f64, self.kappa as f64, self.theta as f64, self.xi as f64, self.rho as f64,
+        );
+        let t = t as f64;
+        let xi2 = xi * xi;
+        let beta = kappa - rho * xi * i * u;
+        let d = (beta * beta + xi2 * (i * u + u * u)).sqrt();
+        let g = (beta - d) / (beta + d);
+        let e = (-d * t).exp();
+        let c = kappa * theta / xi2 * ((beta - d) * t - 2.0 * ((1.0 - g * e) / (1.0 - g)).ln());
+        let dd = (beta - d) / xi2 * (1.0 - e) / (1.0 - g * e);
+        (c + dd * v0).exp()
+    }
+
+    /// European option price by the Lewis (2001) single integral:
+    /// C = D * (F - sqrt(F K) / pi * int_0^inf Re[exp(-i u k) phi(u - i / 2)] / (u^2 + 1/4) du), k = ln(K / F)
+    pub fn price(
+        &self,
+        option_type: OptionType,
+        forward: Real,
+        strike: Real,
+        t: Time,
+        discount: Real,
+    ) -> Real {
+        if t <= 0.0 {
+            return match option_type {
+                OptionType::Call => discount * (forward - strike).max(0.0),
+                OptionType::Put => discount * (strike - forward).max(0.0),
+            };
+        }
+        let (f, k) = (forward as f64, strike as f64);
+        let log_moneyness = (k / f).ln();
+        let integrand = |u: f64| -> f64 {
+            let phi = self.characteristic_function(Complex64::new(u, -0.5), t);
+            let kernel = Complex64::new(0.0, -u * log_moneyness).exp();
+            (kernel * phi).re / (u * u + 0.25)
+        };
+
+        // composite Simpson on panels growing geometrically (the integrand is smooth on the scale of u)
+        // until two consecutive panels become negligible
+        let steps = 16;
+        let mut integral = 0.0;
+        let mut lower = 0.0;
+        let mut width: f64 = 0.5;
+        let mut negligible_panels = 0;
+        while negligible_panels < 2 && lower < 1.0e4 {
+            let h = width / steps as f64;
+            let mut panel = integrand(lower) + integrand(lower + width);
+            for s in 1..steps {
+                let weight = if s % 2 == 1 { 4.0 } else { 2.0 };
+                panel += weight * integrand(lower + s as f64 * h);
+            }
+            panel *= h / 3.0;
+            integral += panel;
+            lower += width;
+            width = (width * 1.5).min(10.0);
+            if panel.abs() < 1.0e-12 {
+                negligible_panels += 1;
+            } else {
+                negligible_panels = 0;
+            }
+        }
+        let call = f - (f * k).sqrt() / PI * integral;
+        let price = match option_type {
+            OptionType::Call => call,
+            OptionType::Put => call - (f - k),
+        };
+        (discount as f64 * price.max(0.0)) as Real
+    }
+
+    /// Black volatility of the Heston price. Out-of-the-money options are used for accuracy.
+    pub fn implied_volatility(&self, forward_moneyness: Real, t: Time) -> Result<Real> {
+        let t = t.max(MIN_TIME);
+        let option_type = if forward_moneyness >= 1.0 { OptionType::Call } else { OptionType::Put };
+        let price = self.price(option_type, 1.0, forward_moneyness, t, 1.0);
+        implied_black_volatility(option_type, 1.0, forward_moneyness, t, 1.0, price, 0.0)
+    }
+
+    /// sqrt of the expected average variance over [0, t]
+    pub fn average_volatility(&self, t: Time) -> Real {
+        let t = t.max(MIN_TIME);
+        let decay = (1.0 - (-self.kappa * t).exp()) / (self.kappa * t);
+        (self.theta + (self.v0 - self.theta) * decay).sqrt()
+    }
+
+    /// v0 and theta shifted so that their square roots move by bump
+    pub fn with_volatility_shift(&self, bump: Real) -> HestonParameters {
+        let shift = |v: Real| (v.sqrt() + bump).max(1.0e-4).powi(2);
+        HestonParameters {
+            v0: shift(self.v0),
+            theta: shift(self.theta),
+            ..*self
+        }
+    }
+}
+
+/// An implied volatility observed at (t, forward, strike)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HestonCalibrationQuote {
+    pub t: Time,
+    pub forward: Real,
+    pub strike: Real,
+    pub volatility: Real,
+}
+
+/// Least squares calibration (Levenberg-Marquardt) of the Heston parameters.
+/// Residuals are out-of-the-money price errors divided by the Black vega of the quote,
+/// which approximate the implied volatility errors without inverting the model prices.
+/// The parameters are optimized on (ln v0, ln kappa, ln theta, ln xi, atanh rho).
+pub fn calibrate_heston(quotes: &[HestonCalibrationQuote], init: &HestonParameters) -> Result<HestonParameters> {
+    if quotes.len() < 5 {
+        bail!("({}:{}) Heston calibration needs at least 5 quotes, got {}", file!(), line!(), quotes.len());
+    }
+    let normal = Normal::new(0.0, 1.0).unwrap();
+    // (option type, forward moneyness, t, market price, vega) with forward = 1 and no discounting
+    let targets: Vec<(OptionType, Real, Time, Real, Real)> = quotes.iter()
+        .map(|quote| {
+            let x = quote.strike / quote.forward;
+            let option_type = if x >= 1.0 { OptionType::Call } else { OptionType::Put };
+            let price = black_price(option_type, 1.0, x, quote.t, 1.0, quote.volatility, 0.0);
+            let deviation = quote.volatility * quote.t.sqrt();
+            let d1 = (-x.ln() + deviation * deviation / 2.0) / deviation;
+            let vega = (normal.pdf(d1 as f64) as Real * quote.t.sqrt()).max(1.0e-4);
+            (option_type, x, quote.t, price, vega)
+        })
+        .collect();
+
+    let to_parameters = |y: &[Real]| HestonParameters {
+        v0: y[0].exp(),
+        kappa: y[1].exp(),
+        theta: y[2].exp(),
+        xi: y[3].exp(),
+        rho: y[4].tanh().clamp(-0.999, 0.999),
+    };
+    let residuals = |y: &[Real]| -> Result<Vec<Real>> {
+        let parameters = to_parameters(y);
+        Ok(targets.iter()
+            .map(|(option_type, x, t, price, vega)| (parameters.price(*option_type, 1.0, *x, *t, 1.0) - price) / vega)
+            .collect())
+    };
+    let init = [
+        init.v0.ln(), init.kappa.ln(), init.theta.ln(), init.xi.ln(), init.rho.clamp(-0.99, 0.99).atanh(),
+    ];
+    let report = levenberg_marquardt(residuals, &init, CALIBRATION_F_TOLERANCE, CALIBRATION_MAX_ITERATIONS)?;
+    let calibrated = to_parameters(report.get_params());
+    HestonParameters::new(calibrated.v0, calibrated.kappa, calibrated.theta, calibrated.xi, calibrated.rho)
+}
+
+/// Heston model calibrated to an implied volatility SurfaceData used as a Volatility.
+/// get_value is the Black volatility of the semi-analytic Heston price, so the analytic pricer prices under Heston.
+/// Exotic (Monte Carlo) pricers take the model from get_parameters or get_bumped_parameters.
+#[derive(Debug, Clone)]
+pub struct HestonVolatility {
+    parameters: HestonParameters,
+    forward_times: Vec<Time>,
+    forward_ratios: Vec<Real>, // forward / spot at each expiry of the market surface
+    bump_grid: VolatilityBumpGrid,
+    //
+    evaluation_date: Rc<RefCell<EvaluationDate>>,
+    market_price: Rc<RefCell<MarketPrice>>,
+    collateral_curve: Rc<RefCell<ZeroCurve>>,
+    borrowing_curve: Rc<RefCell<ZeroCurve>>,
+    //
+    name: String,
+    code: String,
+}
+
+impl HestonVolatility {
+    pub fn initialize(
+        parameters: HestonParameters,
+        evaluation_date: Rc<RefCell<EvaluationDate>>,
+        market_price: Rc<RefCell<MarketPrice>>,
+        collateral_curve: Rc<RefCell<ZeroCurve>>,
+        borrowing_curve: Rc<RefCell<ZeroCurve>>,
+        name: String,
+        code: String,
+    ) -> HestonVolatility {
+        HestonVolatility {
+            parameters,
+            forward_times: Vec::new(),
+            forward_ratios: Vec::new(),
+            bump_grid: VolatilityBumpGrid::default(),
+            //
+            evaluation_date,
+            market_price,
+            collateral_curve,
+            borrowing_curve,
+            //
+            name,
+            code,
+        }
+    }
+
+    /// calibrate the parameters to the surface starting from the current parameters
+    pub fn with_market_surface(
+        mut self,
+        market_implied_volatility_surface: &SurfaceData,
+        vega_structure_tenors: Vec<String>,
+        vega_matrix_spot_moneyness: Array1<Real>,
+    ) -> Result<HestonVolatility> {
+        let eval_date = self.evaluation_date.borrow().get_date_clone();
+        self.bump_grid = VolatilityBumpGrid::from_tenors(&eval_date, &vega_structure_tenors, vega_matrix_spot_moneyness)?;
+
+        let spot = market_implied_volatility_surface.get_spot()
+            .ok_or_else(|| anyhow!(
+                "({}:{}) Error getting spot from market_implied_volatility_surface of {}",
+                file!(), line!(), market_implied_volatility_surface.get_name()
+            ))?;
+        let strikes = market_implied_volatility_surface.get_strike();
+        let values = market_implied_volatility_surface.get_value();
+        let time_calculator = NullCalendar::new();
+
+        let mut quotes = Vec::new();
+        for (i, date) in market_implied_volatility_surface.get_dates().iter().enumerate() {
+            if date <= &eval_date {
+                warn!(
+                    "({}:{}) {} ({}): expiry {} is not after the evaluation date and is ignored",
+                    file!(), line!(), self.name, self.code, date
+                );
+                continue;
+            }
+            let t = time_calculator.get_time_difference(&eval_date, date);
+            let forward = self.get_forward(spot, date)?;
+            self.forward_times.push(t);
+            self.forward_ratios.push(forward / spot);
+            for (j, strike) in strikes.iter().enumerate() {
+                let volatility = values[[i, j]];
+                if volatility.is_finite() && volatility > 0.0 && *strike > 0.0 {
+                    quotes.push(HestonCalibrationQuote { t, forward, strike: *strike, volatility });
+                }
+            }
+        }
+        if self.forward_times.windows(2).any(|w| w[0] >= w[1]) {
+            bail!(
+                "({}:{}) Maturity dates of {} ({}) are not strictly increasing",
+                file!(), line!(), self.name, self.code
+            );
+        }
+        self.parameters = calibrate_heston(&quotes, &self.parameters)
+            .with_context(|| anyhow!(
+                "({}:{}) failed to calibrate Heston parameters of {} ({})",
+                file!(), line!(), self.name, self.code
+            ))?;
+        if !self.parameters.satisfies_feller_condition() {
+            warn!(
+                "({}:{}) calibrated Heston parameters of {} ({}) violate the Feller condition: {:?}",
+                file!(), line!(), self.name, self.code, self.parameters
+            );
+        }
+        Ok(self)
+    }
+
+    fn get_forward(&self, spot: Real, maturity: &OffsetDateTime) -> Result<Real> {
+        let collateral_discount = self.collateral_curve
+            .borrow()
+            .get_discount_factor_at_date(maturity)
+            .with_context(|| anyhow!(
+                "({}:{}) failed to get collateral discount factor\n\
+                maturity: {}, name: {}, code: {}",
+                file!(), line!(),
+                maturity, self.name, self.code
+            ))?;
+
+        let borrowing_discount = self.borrowing_curve
+            .borrow()
+            .get_discount_factor_at_date(maturity)
+            .with_context(|| anyhow!(
+                "({}:{}) failed to get borrowing discount factor\n\
+                maturity: {}, name: {}, code: {}",
+                file!(), line!(),
+                maturity, self.name, self.code
+            ))?;
+
+        let dividend_deduction_ratio = self.market_price
+            .borrow()
+            .get_dividend_deduction_ratio(maturity)
+            .with_context(|| anyhow!(
+                "({}:{}) failed to get dividend deduction ratio\n\
+                maturity: {}, name: {}, code: {}",
+                file!(), line!(),
+                maturity, self.name, self.code
+            ))?;
+
+        Ok(spot * borrowing_discount / collateral_discount * dividend_deduction_ratio)
+    }
+
+    /// forward / spot at t, log-linear in t between expiries with flat rates outside
+    fn forward_ratio(&self, t: Time) -> Real {
+        let n = self.forward_times.len();
+        if n == 0 {
+            return 1.0;
+        }
+        let rate = |i: usize| self.forward_ratios[i].ln() / self.forward_times[i];
+        let r = if t <= self.forward_times[0] {
+            rate(0)
+        } else if t >= self.forward_times[n - 1] {
+            rate(n - 1)
+        } else {
+            let i = self.forward_times.partition_point(|&x| x <= t) - 1;
+            let weight = (t - self.forward_times[i]) / (self.forward_times[i + 1] - self.forward_times[i]);
+            rate(i) * (1.0 - weight) + rate(i + 1) * weight
+        };
+        (r * t).exp()
+    }
+
+    pub fn get_parameters(&self) -> HestonParameters {
+        self.parameters
+    }
+
+    /// the parameters with the volatility bump at (t, spot moneyness) as a shift of sqrt(v0) and sqrt(theta)
+    /// so that Monte Carlo pricers see the engine's vega bumps
+    pub fn get_bumped_parameters(&self, t: Time, spot_moneyness: Real) -> HestonParameters {
+        if self.bump_grid.is_zero() {
+            return self.parameters;
+        }
+        self.parameters.with_volatility_shift(self.bump_grid.get_value(t, spot_moneyness))
+    }
+}
+
+impl VolatilityTrait for HestonVolatility {
+    fn get_value(&self, t: Time, forward_moneyness: Real) -> Real {
+        let forward_moneyness = forward_moneyness.max(1.0e-6);
+        let base = self.parameters.implied_volatility(forward_moneyness, t)
+            .unwrap_or_else(|_| self.parameters.average_volatility(t));
+        if self.bump_grid.is_zero() {
+            return base;
+        }
+        base + self.bump_grid.get_value(t, forward_moneyness * self.forward_ratio(t))
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    fn get_code(&self) -> &String {
+        &self.code
+    }
+
+    fn total_variance(&self, t: Time, forward_moneyness: Real) -> Result<Real> {
+        let vol = self.get_value(t, forward_moneyness);
+        Ok(vol * vol * t)
+    }
+
+    fn total_deviation(&self, t: Time, forward_moneyness: Real) -> Result<Real> {
+        let vol = self.get_value(t, forward_moneyness);
+        Ok(vol * t.sqrt())
+    }
+
+    /// bump the nodes of the bump grid in time1 < t <= time2, left_spot_moneyness < x <= right_spot_moneyness
+    /// as in LocalVolatilitySurface. The calibrated parameters are kept.
+    fn bump_volatility(
+        &mut self,
+        time1: Option<Time>,
+        time2: Option<Time>,
+        left_spot_moneyness: Option<Real>,
+        right_spot_moneyness: Option<Real>,
+        bump: Real
+    ) -> Result<()> {
+        self.bump_grid.bump(time1, time2, left_spot_moneyness, right_spot_moneyness, bump);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heston_price() -> Result<()> {
+        // with (almost) no vol of vol and v0 = theta, Heston is Black with volatility sqrt(v0)
+        let flat = HestonParameters::new(0.04, 1.0, 0.04, 1.0e-2, 0.0)?;
+        for strike in [70.0, 100.0, 130.0] {
+            for option_type in [OptionType::Call, OptionType::Put] {
+                let heston = flat.price(option_type, 100.0, strike, 1.0, 0.97);
+                let black = black_price(option_type, 100.0, strike, 1.0, 0.97, 0.2, 0.0);
+                assert!((heston - black).abs() < 2.0e-3, "{:?} {}: {} vs {}", option_type, strike, heston, black);
+            }
+        }
+
+        // calibration recovers the parameters of a Heston surface
+        let target = HestonParameters::new(0.03, 2.0, 0.05, 0.6, -0.6)?;
+        let mut quotes = Vec::new();
+        for t in [0.25, 0.5, 1.0, 2.0] {
+            for strike in [80.0, 90.0, 100.0, 110.0, 120.0] {
+                let volatility = target.implied_volatility(strike / 100.0, t)?;
+                quotes.push(HestonCalibrationQuote { t, forward: 100.0, strike, volatility });
+            }
+        }
+        let init = HestonParameters::new(0.04, 1.0, 0.04, 0.4, -0.3)?;
+        let calibrated = calibrate_heston(&quotes, &init)?;
+        for quote in quotes.iter() {
+            let volatility = calibrated.implied_volatility(quote.strike / quote.forward, quote.t)?;
+            assert!(
+                (volatility - quote.volatility).abs() < 1.0e-3,
+                "t: {}, strike: {}, calibrated: {:?}", quote.t, quote.strike, calibrated
+            );
+        }
+        Ok(())
+    }
+}
@@ -1,3 +1,6 @@
 pub mod constant_volatility;
 pub mod local_volatility_surface;
+pub mod svi_volatility_surface;
+pub mod heston_volatility;
+pub mod volatility_bump_grid;
 pub mod volatiltiy_interpolator;
@@ -0,0 +1,791 @@
+use crate::definitions::{Real, Time};
+use crate::data::surface_data::SurfaceData;
+use crate::enums::VolatilitySurfaceType;
+use crate::evaluation_date::EvaluationDate;
+use crate::math::optimizers::{nelder_mead, solve_linear_system};
+use crate::parameters::{
+    market_price::MarketPrice,
+    volatility::VolatilityTrait,
+    volatilities::volatility_bump_grid::VolatilityBumpGrid,
+    zero_curve::ZeroCurve,
+};
+use crate::time::calendar_trait::CalendarTrait;
+use crate::time::calendars::nullcalendar::NullCalendar;
+//
+use anyhow::{anyhow, bail, Context, Result};
+use log::warn;
+use ndarray::Array1;
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+};
+use time::OffsetDateTime;
+
+const PENALTY: Real = 1.0e10;
+const ARBITRAGE_TOLERANCE: Real = 1.0e-6;
+const CALIBRATION_MAX_ITERATIONS: usize = 500;
+const CALIBRATION_F_TOLERANCE: Real = 1.0e-12;
+
+/// Raw SVI slice (Gatheral 2004) in log forward moneyness k = ln(K/F):
+/// w(k) = a + b * (rho * (k - m) + sqrt((k - m)^2 + sigma^2))
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RawSviParameters {
+    a: Real,
+    b: Real,
+    rho: Real,
+    m: Real,
+    sigma: Real,
+}
+
+impl RawSviParameters {
+    pub fn new(a: Real, b: Real, rho: Real, m: Real, sigma: Real) -> Result<RawSviParameters> {
+        if b < 0.0 || rho.abs() >= 1.0 || sigma <= 0.0 {
+            bail!(
+                "({}:{}) invalid raw SVI parameters: b = {} (>= 0), rho = {} (|rho| < 1), sigma = {} (> 0)",
+                file!(), line!(), b, rho, sigma
+            );
+        }
+        if a + b * sigma * (1.0 - rho * rho).sqrt() < 0.0 {
+            bail!(
+                "({}:{}) raw SVI parameters (a = {}, b = {}, rho = {}, sigma = {}) give a negative minimum total variance",
+                file!(), line!(), a, b, rho, sigma
+            );
+        }
+        Ok(RawSviParameters { a, b, rho, m, sigma })
+    }
+
+    pub fn get_a(&self) -> Real {
+        self.a
+    }
+
+    pub fn get_b(&self) -> Real {
+        self.b
+    }
+
+    pub fn get_rho(&self) -> Real {
+        self.rho
+    }
+
+    pub fn get_m(&self) -> Real {
+        self.m
+    }
+
+    pub fn get_sigma(&self) -> Real {
+        self.sigma
+    }
+
+    pub fn total_variance(&self, k: Real) -> Real {
+        let x = k - self.m;
+        self.a + self.b * (self.rho * x + (x * x + self.sigma * self.sigma).sqrt())
+    }
+
+    /// dw/dk
+    pub fn first_derivative(&self, k: Real) -> Real {
+        let x = k - self.m;
+        self.b * (self.rho + x / (x * x + self.sigma * self.sigma).sqrt())
+    }
+
+    /// d^2w/dk^2
+    pub fn second_derivative(&self, k: Real) -> Real {
+        let x = k - self.m;
+        let s2 = self.sigma * self.sigma;
+        self.b * s2 / (x * x + s2).powf(1.5)
+    }
+
+    /// g(k) in Gatheral & Jacquier (2014). The risk neutral density is
+    /// non-negative (no butterfly arbitrage) iff g(k) >= 0.
+    pub fn butterfly_density(&self, k: Real) -> Real {
+        let w = self.total_variance(k);
+        if w <= 0.0 {
+            return Real::NEG_INFINITY;
+        }
+        let w1 = self.first_derivative(k);
+        let w2 = self.second_derivative(k);
+        let term = 1.0 - k * w1 / (2.0 * w);
+        term * term - w1 * w1 / 4.0 * (1.0 / w + 0.25) + w2 / 2.0
+    }
+}
+
+/// SSVI (Gatheral & Jacquier 2014) with the power-law function phi(theta) = eta * theta^(-gamma):
+/// w(theta, k) = theta / 2 * (1 + rho * phi * k + sqrt((phi * k + rho)^2 + 1 - rho^2))
+/// where theta is the ATM total variance of the expiry.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SsviParameters {
+    rho: Real,
+    eta: Real,
+    gamma: Real,
+}
+
+impl SsviParameters {
+    /// The constraints are the usual power-law ones: |rho| < 1, eta > 0, 0 < gamma <= 0.5
+    /// and eta * (1 + |rho|) <= 2.
+    pub fn new(rho: Real, eta: Real, gamma: Real) -> Result<SsviParameters> {
+        if !SsviParameters::is_admissible(rho, eta, gamma) {
+            bail!(
+                "({}:{}) invalid SSVI parameters: rho = {}, eta = {}, gamma = {}",
+                file!(), line!(), rho, eta, gamma
+            );
+        }
+        Ok(SsviParameters { rho, eta, gamma })
+    }
+
+    fn is_admissible(rho: Real, eta: Real, gamma: Real) -> bool {
+        rho.abs() < 1.0 && eta > 0.0 && gamma > 0.0 && gamma <= 0.5 && eta * (1.0 + rho.abs()) <= 2.0
+    }
+
+    pub fn get_rho(&self) -> Real {
+        self.rho
+    }
+
+    pub fn get_eta(&self) -> Real {
+        self.eta
+    }
+
+    pub fn get_gamma(&self) -> Real {
+        self.gamma
+    }
+
+    pub fn phi(&self, theta: Real) -> Real {
+        self.eta * theta.powf(-self.gamma)
+    }
+
+    pub fn total_variance(&self, theta: Real, k: Real) -> Real {
+        let phi = self.phi(theta);
+        let x = phi * k + self.rho;
+        theta / 2.0 * (1.0 + self.rho * phi * k + (x * x + 1.0 - self.rho * self.rho).sqrt())
+    }
+
+    /// The SSVI slice at theta as a raw SVI slice (Gatheral & Jacquier 2014, Lemma 3.3)
+    pub fn to_raw(&self, theta: Real) -> RawSviParameters {
+        let phi = self.phi(theta);
+        let rho2 = 1.0 - self.rho * self.rho;
+        RawSviParameters {
+            a: theta / 2.0 * rho2,
+            b: theta * phi / 2.0,
+            rho: self.rho,
+            m: -self.rho / phi,
+            sigma: rho2.sqrt() / phi,
+        }
+    }
+}
+
+/// For fixed (m, sigma), w = a + d * y + c * sqrt(y^2 + 1) with y = (k - m) / sigma is linear in (a, d, c).
+/// Residuals are relative (w_fit / w - 1) so that the small total variances near the money are not
+/// dominated by the wings. The weighted least squares solution is projected onto c >= 0, |d| <= c
+/// and a non-negative minimum variance. Returns the slice and its weighted sum of squared errors.
+fn fit_raw_svi_given_m_sigma(
+    log_moneyness: &[Real],
+    total_variance: &[Real],
+    m: Real,
+    sigma: Real,
+) -> Option<(RawSviParameters, Real)> {
+    let (m64, sigma64) = (m as f64, sigma as f64);
+    let features: Vec<(f64, f64)> = log_moneyness.iter()
+        .map(|&k| {
+            let y = (k as f64 - m64) / sigma64;
+            (y, (y * y + 1.0).sqrt())
+        })
+        .collect();
+
+    let mut ata = [[0.0; 3]; 3];
+    let mut atw = [0.0; 3];
+    for (&(y, z), &w) in features.iter().zip(total_variance.iter()) {
+        let weight = 1.0 / (w as f64 * w as f64);
+        let row = [1.0, y, z];
+        for i in 0..3 {
+            for j in 0..3 {
+                ata[i][j] += weight * row[i] * row[j];
+            }
+            atw[i] += weight * row[i] * w as f64;
+        }
+    }
+    let solution = solve_linear_system(ata.iter().map(|row| row.to_vec()).collect(), atw.to_vec()).ok()?;
+    let (mut a, mut d, mut c) = (solution[0], solution[1], solution[2]);
+
+    if c < 0.0 || d.abs() > c {
+        c = c.max(1.0e-12);
+        d = d.clamp(-c, c);
+        let weight_sum = total_variance.iter().map(|&w| 1.0 / (w as f64 * w as f64)).sum::<f64>();
+        a = features.iter().zip(total_variance.iter())
+            .map(|(&(y, z), &w)| (w as f64 - d * y - c * z) / (w as f64 * w as f64))
+            .sum::<f64>() / weight_sum;
+    }
+    let rho = (d / c).clamp(-0.999, 0.999);
+    a = a.max(-c * (1.0 - rho * rho).sqrt());
+
+    let params = RawSviParameters {
+        a: a as Real,
+        b: (c / sigma64) as Real,
+        rho: rho as Real,
+        m,
+        sigma,
+    };
+    let sse = log_moneyness.iter().zip(total_variance.iter())
+        .map(|(&k, &w)| {
+            let e = params.total_variance(k) as f64 / w as f64 - 1.0;
+            e * e
+        })
+        .sum::<f64>();
+    Some((params, sse as Real))
+}
+
+/// Quasi-explicit raw SVI calibration (Zeliade 2009): Nelder-Mead over (m, ln sigma)
+/// with the remaining parameters solved by linear least squares. Total variances must be positive.
+pub fn calibrate_raw_svi(log_moneyness: &[Real], total_variance: &[Real]) -> Result<RawSviParameters> {
+    if log_moneyness.len() != total_variance.len() || log_moneyness.len() < 3 {
+        bail!(
+            "({}:{}) raw SVI calibration needs at least 3 points of the same length (k: {}, w: {})",
+            file!(), line!(), log_moneyness.len(), total_variance.len()
+        );
+    }
+    if total_variance.iter().any(|w| *w <= 0.0) {
+        bail!("({}:{}) total variances must be positive: {:?}", file!(), line!(), total_variance);
+    }
+    let objective = |x: &[Real]| -> Real {
+        let sigma = x[1].exp();
+        if !(1.0e-4..=10.0).contains(&sigma) {
+            return PENALTY;
+        }
+        fit_raw_svi_given_m_sigma(log_moneyness, total_variance, x[0], sigma)
+            .map(|(_, sse)| sse)
+            .unwrap_or(PENALTY)
+    };
+
+    // coarse grid search for the initial point as the objective has local minima
+    let k_min = log_moneyness.iter().fold(Real::MAX, |acc, k| acc.min(*k));
+    let k_max = log_moneyness.iter().fold(Real::MIN, |acc, k| acc.max(*k));
+    let mut init = [0.0, 0.0];
+    let mut best = Real::MAX;
+    for i in 0..=10 {
+        let m = k_min + (k_max - k_min) * i as Real / 10.0;
+        for sigma in [0.01, 0.03, 0.1, 0.3, 1.0] {
+            let x = [m, (sigma as Real).ln()];
+            let value = objective(&x);
+            if value < best {
+                best = value;
+                init = x;
+            }
+        }
+    }
+    let report = nelder_mead(objective, &init, &[0.05, 0.5], CALIBRATION_F_TOLERANCE, CALIBRATION_MAX_ITERATIONS)?;
+    let params = report.get_params();
+    let (fitted, _) = fit_raw_svi_given_m_sigma(log_moneyness, total_variance, params[0], params[1].exp())
+        .ok_or_else(|| anyhow!("({}:{}) raw SVI least squares is singular", file!(), line!()))?;
+    Ok(fitted)
+}
+
+/// SSVI calibration by Nelder-Mead over (rho, eta, gamma) for given ATM total variances
+/// minimizing the relative total variance errors.
+/// slices[i] = (log moneyness, total variance) observed at thetas[i].
+pub fn calibrate_ssvi(thetas: &[Real], slices: &[(Vec<Real>, Vec<Real>)], init_rho: Real) -> Result<SsviParameters> {
+    if thetas.len() != slices.len() || thetas.is_empty() {
+        bail!(
+            "({}:{}) SSVI calibration needs one ATM total variance per slice (thetas: {}, slices: {})",
+            file!(), line!(), thetas.len(), slices.len()
+        );
+    }
+    if thetas.iter().any(|theta| *theta <= 0.0) {
+        bail!("({}:{}) ATM total variances must be positive: {:?}", file!(), line!(), thetas);
+    }
+    let objective = |x: &[Real]| -> Real {
+        if !SsviParameters::is_admissible(x[0], x[1], x[2]) {
+            return PENALTY;
+        }
+        let params = SsviParameters { rho: x[0], eta: x[1], gamma: x[2] };
+        let mut sse = 0.0_f64;
+        for (theta, (ks, ws)) in thetas.iter().zip(slices.iter()) {
+            for (k, w) in ks.iter().zip(ws.iter()) {
+                let e = params.total_variance(*theta, *k) as f64 / *w as f64 - 1.0;
+                sse += e * e;
+            }
+        }
+        sse as Real
+    };
+    let init = [init_rho.clamp(-0.9, 0.9), 0.5, 0.3];
+    let report = nelder_mead(objective, &init, &[0.1, 0.2, 0.1], CALIBRATION_F_TOLERANCE, CALIBRATION_MAX_ITERATIONS)?;
+    let params = report.get_params();
+    SsviParameters::new(params[0], params[1], params[2])
+        .context(anyhow!("({}:{}) SSVI calibration ended at an inadmissible point", file!(), line!()))
+}
+
+/// Total variance decreases in maturity at log moneyness k: w(time2, k) < w(time1, k)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CalendarSpreadViolation {
+    pub time1: Time,
+    pub time2: Time,
+    pub log_moneyness: Real,
+    pub total_variance1: Real,
+    pub total_variance2: Real,
+}
+
+/// Negative risk neutral density at (time, log moneyness): g < 0
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ButterflyViolation {
+    pub time: Time,
+    pub log_moneyness: Real,
+    pub density: Real,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArbitrageReport {
+    calendar_violations: Vec<CalendarSpreadViolation>,
+    butterfly_violations: Vec<ButterflyViolation>,
+}
+
+impl ArbitrageReport {
+    pub fn get_calendar_violations(&self) -> &Vec<CalendarSpreadViolation> {
+        &self.calendar_violations
+    }
+
+    pub fn get_butterfly_violations(&self) -> &Vec<ButterflyViolation> {
+        &self.butterfly_violations
+    }
+
+    pub fn is_arbitrage_free(&self) -> bool {
+        self.calendar_violations.is_empty() && self.butterfly_violations.is_empty()
+    }
+}
+
+/// Implied volatility surface fitted by raw SVI per expiry or by SSVI across expiries.
+/// Slices are in log forward moneyness where the forwards are computed from the spot of the SurfaceData.
+/// Between expiries, raw SVI is linear in total variance and SSVI is linear in the ATM total variance.
+/// Before the first (after the last) expiry, total variance is proportional to time.
+#[derive(Debug, Clone)]
+pub struct SviVolatilitySurface {
+    surface_type: VolatilitySurfaceType,
+    expiry_dates: Vec<OffsetDateTime>,
+    expiry_times: Vec<Time>,
+    forward_ratios: Vec<Real>, // forward / spot at each expiry
+    slices: Vec<RawSviParameters>,
+    ssvi: Option<SsviParameters>,
+    thetas: Vec<Real>,
+    log_moneyness_range: (Real, Real),
+    arbitrage_report: ArbitrageReport,
+    bump_grid: VolatilityBumpGrid,
+    //
+    evaluation_date: Rc<RefCell<EvaluationDate>>,
+    market_price: Rc<RefCell<MarketPrice>>,
+    collateral_curve: Rc<RefCell<ZeroCurve>>,
+    borrowing_curve: Rc<RefCell<ZeroCurve>>,
+    //
+    name: String,
+    code: String,
+}
+
+impl SviVolatilitySurface {
+    pub fn initialize(
+        evaluation_date: Rc<RefCell<EvaluationDate>>,
+        market_price: Rc<RefCell<MarketPrice>>,
+        collateral_curve: Rc<RefCell<ZeroCurve>>,
+        borrowing_curve: Rc<RefCell<ZeroCurve>>,
+        surface_type: VolatilitySurfaceType,
+        name: String,
+        code: String,
+    ) -> SviVolatilitySurface {
+        SviVolatilitySurface {
+            surface_type,
+            expiry_dates: Vec::new(),
+            expiry_times: Vec::new(),
+            forward_ratios: Vec::new(),
+            slices: Vec::new(),
+            ssvi: None,
+            thetas: Vec::new(),
+            log_moneyness_range: (0.0, 0.0),
+            arbitrage_report: ArbitrageReport::default(),
+            bump_grid: VolatilityBumpGrid::default(),
+            //
+            evaluation_date,
+            market_price,
+            collateral_curve,
+            borrowing_curve,
+            //
+            name,
+            code,
+        }
+    }
+
+    pub fn with_market_surface(
+        mut self,
+        market_implied_volatility_surface: &SurfaceData,
+        vega_structure_tenors: Vec<String>,
+        vega_matrix_spot_moneyness: Array1<Real>,
+    ) -> Result<SviVolatilitySurface> {
+        if self.surface_type == VolatilitySurfaceType::Interpolated {
+            bail!(
+                "({}:{}) {} ({}) is an SVI surface but the surface type is {:?}",
+                file!(), line!(), self.name, self.code, self.surface_type
+            );
+        }
+        let given_dates = market_implied_volatility_surface.get_dates();
+        let eval_date = self.evaluation_date.borrow().get_date_clone();
+        self.bump_grid = VolatilityBumpGrid::from_tenors(&eval_date, &vega_structure_tenors, vega_matrix_spot_moneyness)?;
+        if given_dates.windows(2).any(|w| w[0] >= w[1]) {
+            bail!(
+                "({}:{}) Maturity dates of {} ({}) are not strictly increasing: {:?}",
+                file!(), line!(), self.name, self.code, given_dates
+            );
+        }
+
+        let spot = market_implied_volatility_surface.get_spot()
+            .ok_or_else(|| anyhow!(
+                "({}:{}) Error getting spot from market_implied_volatility_surface of {}",
+                file!(), line!(), market_implied_volatility_surface.get_name()
+            ))?;
+        let strikes = market_implied_volatility_surface.get_strike();
+        let values = market_implied_volatility_surface.get_value();
+
+        let time_calculator = NullCalendar::new();
+        let mut market_slices: Vec<(Vec<Real>, Vec<Real>)> = Vec::new();
+        let (mut k_min, mut k_max) = (Real::MAX, Real::MIN);
+        for (i, date) in given_dates.iter().enumerate() {
+            if date <= &eval_date {
+                warn!(
+                    "({}:{}) {} ({}): expiry {} is not after the evaluation date and is ignored",
+                    file!(), line!(), self.name, self.code, date
+                );
+                continue;
+            }
+            let t = time_calculator.get_time_difference(&eval_date, date);
+            let forward = self.get_forward(spot, date)?;
+            let mut ks = Vec::with_capacity(strikes.len());
+            let mut ws = Vec::with_capacity(strikes.len());
+            for (j, strike) in strikes.iter().enumerate() {
+                let vol = values[[i, j]];
+                if !vol.is_finite() || vol <= 0.0 || *strike <= 0.0 {
+                    continue;
+                }
+                let k = (strike / forward).ln();
+                k_min = k_min.min(k);
+                k_max = k_max.max(k);
+                ks.push(k);
+                ws.push(vol * vol * t);
+            }
+            let slice = calibrate_raw_svi(&ks, &ws)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to calibrate raw SVI of {} ({}) at {}",
+                    file!(), line!(), self.name, self.code, date
+                ))?;
+
+            self.expiry_dates.push(*date);
+            self.expiry_times.push(t);
+            self.forward_ratios.push(forward / spot);
+            self.slices.push(slice);
+            market_slices.push((ks, ws));
+        }
+        if self.slices.is_empty() {
+            bail!("({}:{}) no expiry of {} ({}) is after the evaluation date", file!(), line!(), self.name, self.code);
+        }
+        self.log_moneyness_range = (k_min, k_max);
+
+        if self.surface_type == VolatilitySurfaceType::Ssvi {
+            let mut thetas: Vec<Real> = Vec::with_capacity(self.slices.len());
+            for slice in self.slices.iter() {
+                let theta = slice.total_variance(0.0).max(thetas.last().copied().unwrap_or(0.0));
+                thetas.push(theta);
+            }
+            let init_rho = self.slices.iter().map(|slice| slice.get_rho()).sum::<Real>() / self.slices.len() as Real;
+            let ssvi = calibrate_ssvi(&thetas, &market_slices, init_rho)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to calibrate SSVI of {} ({})",
+                    file!(), line!(), self.name, self.code
+                ))?;
+            self.slices = thetas.iter().map(|theta| ssvi.to_raw(*theta)).collect();
+            self.thetas = thetas;
+            self.ssvi = Some(ssvi);
+        }
+
+        let grid: Vec<Real> = (0..=50)
+            .map(|i| k_min + (k_max - k_min) * i as Real / 50.0)
+            .collect();
+        self.arbitrage_report = self.check_static_arbitrage(&grid);
+        if !self.arbitrage_report.is_arbitrage_free() {
+            warn!(
+                "({}:{}) fitted {:?} surface of {} ({}) has {} calendar spread and {} butterfly violations",
+                file!(), line!(), self.surface_type, self.name, self.code,
+                self.arbitrage_report.calendar_violations.len(),
+                self.arbitrage_report.butterfly_violations.len(),
+            );
+        }
+        Ok(self)
+    }
+
+    /// Calendar spread (total variance non-decreasing between consecutive expiries) and
+    /// butterfly (g >= 0 on each expiry) checks of the fitted surface on the given log forward moneyness.
+    /// Bumps are not included.
+    pub fn check_static_arbitrage(&self, log_moneyness_grid: &[Real]) -> ArbitrageReport {
+        let mut report = ArbitrageReport::default();
+        for (i, slice) in self.slices.iter().enumerate() {
+            for k in log_moneyness_grid.iter() {
+                let density = slice.butterfly_density(*k);
+                if density < -ARBITRAGE_TOLERANCE {
+                    report.butterfly_violations.push(ButterflyViolation {
+                        time: self.expiry_times[i],
+                        log_moneyness: *k,
+                        density,
+                    });
+                }
+            }
+        }
+        for i in 1..self.slices.len() {
+            for k in log_moneyness_grid.iter() {
+                let total_variance1 = self.slices[i - 1].total_variance(*k);
+                let total_variance2 = self.slices[i].total_variance(*k);
+                if total_variance2 < total_variance1 - ARBITRAGE_TOLERANCE {
+                    report.calendar_violations.push(CalendarSpreadViolation {
+                        time1: self.expiry_times[i - 1],
+                        time2: self.expiry_times[i],
+                        log_moneyness: *k,
+                        total_variance1,
+                        total_variance2,
+                    });
+                }
+            }
+        }
+        report
+    }
+
+    fn get_forward(&self, spot: Real, maturity: &OffsetDateTime) -> Result<Real> {
+        let collateral_discount = self.collateral_curve
+            .borrow()
+            .get_discount_factor_at_date(maturity)
+            .with_context(|| anyhow!(
+                "({}:{}) failed to get collateral discount factor\n\
+                maturity: {}, name: {}, code: {}",
+                file!(), line!(),
+                maturity, self.name, self.code
+            ))?;
+
+        let borrowing_discount = self.borrowing_curve
+            .borrow()
+            .get_discount_factor_at_date(maturity)
+            .with_context(|| anyhow!(
+                "({}:{}) failed to get borrowing discount factor\n\
+                maturity: {}, name: {}, code: {}",
+                file!(), line!(),
+                maturity, self.name, self.code
+            ))?;
+
+        let dividend_deduction_ratio = self.market_price
+            .borrow()
+            .get_dividend_deduction_ratio(maturity)
+            .with_context(|| anyhow!(
+                "({}:{}) failed to get dividend deduction ratio\n\
+                maturity: {}, name: {}, code: {}",
+                file!(), line!(),
+                maturity, self.name, self.code
+            ))?;
+
+        Ok(spot * borrowing_discount / collateral_discount * dividend_deduction_ratio)
+    }
+
+    /// (lower index, upper index, weight of the upper index) of t among the expiries
+    fn bracket(&self, t: Time) -> (usize, usize, Real) {
+        let n = self.expiry_times.len();
+        if t <= self.expiry_times[0] {
+            return (0, 0, 0.0);
+        }
+        if t >= self.expiry_times[n - 1] {
+            return (n - 1, n - 1, 0.0);
+        }
+        let i = self.expiry_times.partition_point(|&x| x <= t) - 1;
+        let weight = (t - self.expiry_times[i]) / (self.expiry_times[i + 1] - self.expiry_times[i]);
+        (i, i + 1, weight)
+    }
+
+    /// fitted total variance without bumps
+    fn base_total_variance(&self, t: Time, k: Real) -> Real {
+        let n = self.expiry_times.len();
+        if t <= self.expiry_times[0] {
+            return self.slices[0].total_variance(k) * t / self.expiry_times[0];
+        }
+        if t >= self.expiry_times[n - 1] {
+            return self.slices[n - 1].total_variance(k) * t / self.expiry_times[n - 1];
+        }
+        let (i, j, weight) = self.bracket(t);
+        match self.ssvi {
+            Some(ssvi) => {
+                let theta = self.thetas[i] * (1.0 - weight) + self.thetas[j] * weight;
+                ssvi.total_variance(theta, k)
+            },
+            None => self.slices[i].total_variance(k) * (1.0 - weight) + self.slices[j].total_variance(k) * weight,
+        }
+    }
+
+    /// forward / spot at t, log-linear in t between expiries with flat rates outside
+    fn forward_ratio(&self, t: Time) -> Real {
+        let rate = |i: usize| self.forward_ratios[i].ln() / self.expiry_times[i];
+        let (i, j, weight) = self.bracket(t);
+        ((rate(i) * (1.0 - weight) + rate(j) * weight) * t).exp()
+    }
+
+    pub fn get_surface_type(&self) -> VolatilitySurfaceType {
+        self.surface_type
+    }
+
+    pub fn get_expiry_dates(&self) -> &Vec<OffsetDateTime> {
+        &self.expiry_dates
+    }
+
+    pub fn get_expiry_times(&self) -> &Vec<Time> {
+        &self.expiry_times
+    }
+
+    /// raw SVI parameters of each expiry (SSVI slices are converted to raw SVI)
+    pub fn get_slices(&self) -> &Vec<RawSviParameters> {
+        &self.slices
+    }
+
+    pub fn get_ssvi_parameters(&self) -> Option<SsviParameters> {
+        self.ssvi
+    }
+
+    /// static arbitrage report on the range of the market strikes at calibration
+    pub fn get_arbitrage_report(&self) -> &ArbitrageReport {
+        &self.arbitrage_report
+    }
+}
+
+impl VolatilityTrait for SviVolatilitySurface {
+    fn get_value(&self, t: Time, forward_moneyness: Real) -> Real {
+        let k = forward_moneyness.max(1.0e-6).ln();
+        let t0 = self.expiry_times[0];
+        let base = if t <= t0 {
+            (self.slices[0].total_variance(k).max(0.0) / t0).sqrt()
+        } else {
+            (self.base_total_variance(t, k).max(0.0) / t).sqrt()
+        };
+        if self.bump_grid.is_zero() {
+            return base;
+        }
+        base + self.bump_grid.get_value(t, forward_moneyness * self.forward_ratio(t))
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    fn get_code(&self) -> &String {
+        &self.code
+    }
+
+    fn total_variance(&self, t: Time, forward_moneyness: Real) -> Result<Real> {
+        let vol = self.get_value(t, forward_moneyness);
+        Ok(vol * vol * t)
+    }
+
+    fn total_deviation(&self, t: Time, forward_moneyness: Real) -> Result<Real> {
+        let vol = self.get_value(t, forward_moneyness);
+        Ok(vol * t.sqrt())
+    }
+
+    /// bump the nodes of the bump grid in time1 < t <= time2, left_spot_moneyness < x <= right_spot_moneyness
+    /// as in LocalVolatilitySurface. The fitted slices are kept.
+    fn bump_volatility(
+        &mut self,
+        time1: Option<Time>,
+        time2: Option<Time>,
+        left_spot_moneyness: Option<Real>,
+        right_spot_moneyness: Option<Real>,
+        bump: Real
+    ) -> Result<()> {
+        self.bump_grid.bump(time1, time2, left_spot_moneyness, right_spot_moneyness, bump);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::{self, Currency};
+    use crate::data::{self, vector_data::VectorData};
+    use crate::{surfacedatasample, vectordatasample};
+    use crate::utils;
+    use ndarray::Array2;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_raw_svi_calibration() -> Result<()> {
+        let target = RawSviParameters::new(0.02, 0.1, -0.4, 0.05, 0.2)?;
+        let ks: Vec<Real> = (0..21).map(|i| -0.5 + 0.05 * i as Real).collect();
+        let ws: Vec<Real> = ks.iter().map(|k| target.total_variance(*k)).collect();
+        let fitted = calibrate_raw_svi(&ks, &ws)?;
+        for (k, w) in ks.iter().zip(ws.iter()) {
+            assert!((fitted.total_variance(*k) - w).abs() < 1.0e-5, "k: {}, fitted: {:?}", k, fitted);
+        }
+        assert!(ks.iter().all(|k| target.butterfly_density(*k) >= 0.0));
+
+        // SSVI slices are raw SVI slices
+        let ssvi = SsviParameters::new(-0.3, 0.8, 0.4)?;
+        let raw = ssvi.to_raw(0.04);
+        for k in ks.iter() {
+            assert!((raw.total_variance(*k) - ssvi.total_variance(0.04, *k)).abs() < 1.0e-6);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_svi_volatility_surface() -> Result<()> {
+        let eval_date = datetime!(2024-01-02 00:00:00 +09:00);
+        let spot = 350.0;
+        let equity = Rc::new(RefCell::new(MarketPrice::new(
+            spot, eval_date, None, Currency::KRW, "KOSPI2".to_string(), "KOSPI2".to_string(),
+        )));
+        let evaluation_date = Rc::new(RefCell::new(EvaluationDate::new(eval_date)));
+        let dummy_data = vectordatasample!(0.00, Currency::KRW, "mock curve data")?;
+        let zero_curve = Rc::new(RefCell::new(ZeroCurve::new(
+            evaluation_date.clone(), &dummy_data, "KRWGOV".to_string(), "zero curve".to_string(),
+        )?));
+        let surface_data = surfacedatasample!(&eval_date, spot);
+        let tenors: Vec<String> = ["1M", "3M", "6M", "1Y", "2Y", "3Y"].iter().map(|t| t.to_string()).collect();
+        let spot_moneyness = Array1::linspace(0.6, 1.4, 17);
+
+        for surface_type in [VolatilitySurfaceType::RawSvi, VolatilitySurfaceType::Ssvi] {
+            let mut surface = SviVolatilitySurface::initialize(
+                evaluation_date.clone(), equity.clone(), zero_curve.clone(), zero_curve.clone(),
+                surface_type, "svi".to_string(), "svi".to_string(),
+            ).with_market_surface(&surface_data, tenors.clone(), spot_moneyness.clone())?;
+
+            // ATM fit and calendar spread freedom at the expiries
+            let times = surface.get_expiry_times().clone();
+            let value = surface_data.get_value();
+            let atm = surface_data.get_strike().iter()
+                .position(|strike| (strike - spot).abs() < 1.0e-3)
+                .unwrap();
+            for (i, t) in times.iter().enumerate() {
+                let fitted = surface.get_value(*t, 1.0);
+                assert!(
+                    (fitted - value[[i, atm]]).abs() < 0.02,
+                    "{:?} t: {}, fitted: {}, market: {}", surface_type, t, fitted, value[[i, atm]]
+                );
+            }
+            if surface_type == VolatilitySurfaceType::Ssvi {
+                assert!(surface.get_arbitrage_report().get_calendar_violations().is_empty());
+            }
+
+            let before = surface.get_value(1.0, 1.0);
+            surface.bump_volatility(None, None, None, None, 0.01)?;
+            assert!((surface.get_value(1.0, 1.0) - before - 0.01).abs() < 1.0e-6);
+            surface.bump_volatility(Some(2.0), None, None, None, 0.01)?;
+            assert!((surface.get_value(1.0, 1.0) - before - 0.01).abs() < 1.0e-6);
+        }
+
+        // total variance decreasing from the first to the second expiry
+        let dates = vec![datetime!(2024-04-02 00:00:00 +09:00), datetime!(2024-07-02 00:00:00 +09:00)];
+        let strikes = Array1::linspace(250.0, 450.0, 9);
+        let mut value = Array2::from_elem((2, 9), 0.4);
+        value.row_mut(1).fill(0.15);
+        let inverted = SurfaceData::new(
+            Some(spot), value, dates, strikes, None, Currency::KRW, "inverted".to_string(), "inverted".to_string(),
+        );
+        let surface = SviVolatilitySurface::initialize(
+            evaluation_date.clone(), equity.clone(), zero_curve.clone(), zero_curve.clone(),
+            VolatilitySurfaceType::RawSvi, "inverted".to_string(), "inverted".to_string(),
+        ).with_market_surface(&inverted, tenors.clone(), spot_moneyness.clone())?;
+        let report = surface.get_arbitrage_report();
+        assert!(!report.is_arbitrage_free());
+        assert!(report.get_calendar_violations().iter()
+            .all(|v| v.total_variance2 < v.total_variance1 && v.time1 < v.time2));
+        Ok(())
+    }
+}
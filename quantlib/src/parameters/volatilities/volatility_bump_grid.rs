@@ -0,0 +1,138 @@
+use crate::definitions::{Real, Time};
+use crate::time::calendar_trait::CalendarTrait;
+use crate::time::calendars::nullcalendar::NullCalendar;
+use crate::utils::string_arithmetic::add_period;
+//
+use anyhow::{bail, Result};
+use ndarray::{Array1, Array2};
+use time::OffsetDateTime;
+
+/// Additive volatility bumps on (vega structure tenors) x (vega matrix spot moneyness) nodes
+/// for fitted surfaces that keep their own parameters.
+/// Nodes are bumped by the same rule as LocalVolatilitySurface::bump_volatility and
+/// the bump at (t, spot moneyness) is bilinear between the nodes with flat extrapolation,
+/// so that bumping every bucket and putting back with one parallel bump leaves no residual.
+#[derive(Debug, Clone, Default)]
+pub struct VolatilityBumpGrid {
+    times: Array1<Time>,
+    spot_moneyness: Array1<Real>,
+    bumps: Array2<Real>,
+}
+
+impl VolatilityBumpGrid {
+    pub fn new(times: Array1<Time>, spot_moneyness: Array1<Real>) -> Result<VolatilityBumpGrid> {
+        if times.is_empty() || spot_moneyness.is_empty() {
+            bail!(
+                "({}:{}) bump grid needs at least one time ({}) and one spot moneyness ({})",
+                file!(), line!(), times.len(), spot_moneyness.len()
+            );
+        }
+        if times.windows(2).into_iter().any(|w| w[0] > w[1])
+            || spot_moneyness.windows(2).into_iter().any(|w| w[0] > w[1]) {
+            bail!(
+                "({}:{}) bump grid nodes are not sorted: times: {:?}, spot moneyness: {:?}",
+                file!(), line!(), times, spot_moneyness
+            );
+        }
+        let bumps = Array2::zeros((times.len(), spot_moneyness.len()));
+        Ok(VolatilityBumpGrid { times, spot_moneyness, bumps })
+    }
+
+    pub fn from_tenors(
+        evaluation_date: &OffsetDateTime,
+        vega_structure_tenors: &[String],
+        vega_matrix_spot_moneyness: Array1<Real>,
+    ) -> Result<VolatilityBumpGrid> {
+        let time_calculator = NullCalendar::new();
+        let times: Array1<Time> = vega_structure_tenors.iter()
+            .map(|tenor| time_calculator.get_time_difference(evaluation_date, &add_period(evaluation_date, tenor)))
+            .collect();
+        VolatilityBumpGrid::new(times, vega_matrix_spot_moneyness)
+    }
+
+    /// time1 < t <= time2, left_spot_moneyness < x <= right_spot_moneyness (None is unbounded)
+    pub fn bump(
+        &mut self,
+        time1: Option<Time>,
+        time2: Option<Time>,
+        left_spot_moneyness: Option<Real>,
+        right_spot_moneyness: Option<Real>,
+        bump: Real,
+    ) {
+        let time1 = time1.unwrap_or(Time::MIN + 10.0);
+        let time2 = time2.unwrap_or(Time::MAX - 10.0);
+        let left_spot_moneyness = left_spot_moneyness.unwrap_or(Real::MIN + 10.0);
+        let right_spot_moneyness = right_spot_moneyness.unwrap_or(Real::MAX - 10.0);
+
+        let eps = 1.0e-4;
+        for (i, t) in self.times.iter().enumerate() {
+            if !(time1 + eps < *t && *t <= time2 + eps) {
+                continue;
+            }
+            for (j, x) in self.spot_moneyness.iter().enumerate() {
+                if !(left_spot_moneyness + eps < *x && *x <= right_spot_moneyness + eps) {
+                    continue;
+                }
+                self.bumps[[i, j]] += bump;
+            }
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.bumps.iter().all(|bump| *bump == 0.0)
+    }
+
+    /// (lower index, upper index, weight of the upper index) with flat extrapolation.
+    /// x = NaN is put on the first node.
+    fn locate(domain: &Array1<Real>, x: Real) -> (usize, usize, Real) {
+        // the number of nodes up to x (0 for NaN) as the domain is increasing
+        let upper = domain.iter().take_while(|d| **d <= x).count();
+        if upper == 0 {
+            return (0, 0, 0.0);
+        }
+        if upper == domain.len() {
+            return (upper - 1, upper - 1, 0.0);
+        }
+        let i = upper - 1;
+        (i, upper, (x - domain[i]) / (domain[upper] - domain[i]))
+    }
+
+    pub fn get_value(&self, t: Time, spot_moneyness: Real) -> Real {
+        if self.bumps.is_empty() {
+            return 0.0;
+        }
+        let (i0, i1, wt) = VolatilityBumpGrid::locate(&self.times, t);
+        let (j0, j1, wx) = VolatilityBumpGrid::locate(&self.spot_moneyness, spot_moneyness);
+        let at = |i: usize| self.bumps[[i, j0]] * (1.0 - wx) + self.bumps[[i, j1]] * wx;
+        at(i0) * (1.0 - wt) + at(i1) * wt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_bumps_put_back() -> Result<()> {
+        let times = Array1::from_vec(vec![0.25, 0.5, 1.0, 2.0]);
+        let mut grid = VolatilityBumpGrid::new(times.clone(), Array1::linspace(0.8, 1.2, 5))?;
+
+        // bump from the tail as in Engine::set_vega_structure and put back in parallel
+        for i in (0..times.len()).rev() {
+            let start = if i == 0 { None } else { Some(times[i - 1]) };
+            grid.bump(start, Some(times[i]), None, None, 0.01);
+        }
+        assert!((grid.get_value(1.5, 1.0) - 0.01).abs() < 1.0e-7);
+        assert!((grid.get_value(5.0, 0.5) - 0.01).abs() < 1.0e-7);
+        assert!((grid.get_value(0.1, 1.5) - 0.01).abs() < 1.0e-7);
+        assert!((grid.get_value(Time::NAN, Real::NAN) - 0.01).abs() < 1.0e-7);
+        grid.bump(None, None, None, None, -0.01);
+        assert!(grid.is_zero());
+
+        grid.bump(Some(0.5), Some(1.0), Some(0.9), Some(1.0), 0.01);
+        assert!((grid.get_value(1.0, 1.0) - 0.01).abs() < 1.0e-7);
+        assert!((grid.get_value(0.75, 0.95) - 0.0025).abs() < 1.0e-7);
+        assert_eq!(grid.get_value(0.25, 1.0), 0.0);
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
 use crate::parameters::volatilities::{
     constant_volatility::ConstantVolatility,
     local_volatility_surface::LocalVolatilitySurface,
+    svi_volatility_surface::SviVolatilitySurface,
+    heston_volatility::HestonVolatility,
 };
 use crate::definitions::{Real, Time};
 use anyhow::Result;
@@ -10,6 +12,8 @@ use serde::{Serialize, Deserialize};
 pub enum VolatilityType {
     ConstantVolatility,
     LocalVolatilitySurface,
+    SviVolatilitySurface,
+    HestonVolatility,
 }
 
 pub trait VolatilityTrait {
@@ -36,6 +40,8 @@ pub trait VolatilityTrait {
 pub enum Volatility {
     ConstantVolatility(ConstantVolatility),
     LocalVolatilitySurface(LocalVolatilitySurface),
+    SviVolatilitySurface(SviVolatilitySurface),
+    HestonVolatility(HestonVolatility),
 }
 
 impl Volatility {
@@ -43,6 +49,8 @@ impl Volatility {
         match self {
             Volatility::ConstantVolatility(volatility) => volatility.get_name(),
             Volatility::LocalVolatilitySurface(volatility) => volatility.get_name(),
+            Volatility::SviVolatilitySurface(volatility) => volatility.get_name(),
+            Volatility::HestonVolatility(volatility) => volatility.get_name(),
         }
     }
 
@@ -50,6 +58,8 @@ impl Volatility {
         match self {
             Volatility::ConstantVolatility(volatility) => volatility.get_code(),
             Volatility::LocalVolatilitySurface(volatility) => volatility.get_code(),
+            Volatility::SviVolatilitySurface(volatility) => volatility.get_code(),
+            Volatility::HestonVolatility(volatility) => volatility.get_code(),
         }
     }
 
@@ -57,6 +67,8 @@ impl Volatility {
         match self {
             Volatility::ConstantVolatility(volatility) => volatility.get_value(t, forward_moneyness),
             Volatility::LocalVolatilitySurface(volatility) => volatility.get_value(t, forward_moneyness),
+            Volatility::SviVolatilitySurface(volatility) => volatility.get_value(t, forward_moneyness),
+            Volatility::HestonVolatility(volatility) => volatility.get_value(t, forward_moneyness),
         }
     }
 
@@ -64,6 +76,8 @@ impl Volatility {
         match self {
             Volatility::ConstantVolatility(volatility) => volatility.total_variance(t, forward_moneyness),
             Volatility::LocalVolatilitySurface(volatility) => volatility.total_variance(t, forward_moneyness),
+            Volatility::SviVolatilitySurface(volatility) => volatility.total_variance(t, forward_moneyness),
+            Volatility::HestonVolatility(volatility) => volatility.total_variance(t, forward_moneyness),
         }
     }
 
@@ -71,6 +85,8 @@ impl Volatility {
         match self {
             Volatility::ConstantVolatility(volatility) => volatility.total_deviation(t, forward_moneyness),
             Volatility::LocalVolatilitySurface(volatility) => volatility.total_deviation(t, forward_moneyness),
+            Volatility::SviVolatilitySurface(volatility) => volatility.total_deviation(t, forward_moneyness),
+            Volatility::HestonVolatility(volatility) => volatility.total_deviation(t, forward_moneyness),
         }
     }
 
@@ -83,6 +99,12 @@ impl Volatility {
                 volatility.build()?;
                 Ok(())
             }
+            Volatility::SviVolatilitySurface(_volatility) => {
+                Ok(())
+            }
+            Volatility::HestonVolatility(_volatility) => {
+                Ok(())
+            }
         }
     }
     pub fn bump_volatility(
@@ -96,6 +118,8 @@ impl Volatility {
         match self {
             Volatility::ConstantVolatility(volatility) => volatility.bump_volatility(time1, time2, left_spot_moneyness, right_spot_moneyness, bump),
             Volatility::LocalVolatilitySurface(volatility) => volatility.bump_volatility(time1, time2, left_spot_moneyness, right_spot_moneyness, bump),
+            Volatility::SviVolatilitySurface(volatility) => volatility.bump_volatility(time1, time2, left_spot_moneyness, right_spot_moneyness, bump),
+            Volatility::HestonVolatility(volatility) => volatility.bump_volatility(time1, time2, left_spot_moneyness, right_spot_moneyness, bump),
         }
     }
 
@@ -103,6 +127,8 @@ impl Volatility {
         match self {
             Volatility::ConstantVolatility(_) => VolatilityType::ConstantVolatility,
             Volatility::LocalVolatilitySurface(_) => VolatilityType::LocalVolatilitySurface,
+            Volatility::SviVolatilitySurface(_) => VolatilityType::SviVolatilitySurface,
+            Volatility::HestonVolatility(_) => VolatilityType::HestonVolatility,
         }
     }
 }
\ No newline at end of file
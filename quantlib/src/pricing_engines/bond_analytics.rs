@@ -0,0 +1,723 @@
+use crate::definitions::{Real, Time};
+use crate::enums::YieldConvention;
+use crate::evaluation_date::EvaluationDate;
+use crate::math::solvers::{brent, find_bracket, SolverConfig};
+use crate::instrument::{Instrument, InstrumentTrait};
+use crate::instruments::bond::Bond;
+use crate::parameters::{
+    zero_curve::ZeroCurve,
+    past_price::DailyClosePrice,
+};
+use crate::pricing_engines::{
+    pricer::PricerTrait,
+    krx_yield_pricer::KrxYieldPricer,
+};
+use crate::time::{
+    calendar_trait::CalendarTrait,
+    calendars::nullcalendar::NullCalendar,
+};
+//
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Serialize, Deserialize};
+use time::OffsetDateTime;
+use std::{
+    rc::Rc,
+    cell::RefCell,
+};
+
+/// Price and risk measures of a bond at its settlement date.
+/// All prices are per unit face value (the same unit as the npv of BondPricer).
+/// dv01: price change for 1bp move of the yield (per unit face value)
+/// z_spread: continuous spread over the discount curve
+/// i_spread: yield minus the curve rate at maturity expressed in the same yield convention
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct BondAnalytics {
+    settlement_date: OffsetDateTime,
+    yield_convention: YieldConvention,
+    dirty_price: Real,
+    clean_price: Real,
+    accrued_interest: Real,
+    yield_to_maturity: Real,
+    macaulay_duration: Real,
+    modified_duration: Real,
+    convexity: Real,
+    dv01: Real,
+    z_spread: Real,
+    i_spread: Real,
+}
+
+impl BondAnalytics {
+    pub fn get_settlement_date(&self) -> &OffsetDateTime {
+        &self.settlement_date
+    }
+
+    pub fn get_yield_convention(&self) -> YieldConvention {
+        self.yield_convention
+    }
+
+    pub fn get_dirty_price(&self) -> Real {
+        self.dirty_price
+    }
+
+    pub fn get_clean_price(&self) -> Real {
+        self.clean_price
+    }
+
+    pub fn get_accrued_interest(&self) -> Real {
+        self.accrued_interest
+    }
+
+    pub fn get_yield_to_maturity(&self) -> Real {
+        self.yield_to_maturity
+    }
+
+    pub fn get_macaulay_duration(&self) -> Real {
+        self.macaulay_duration
+    }
+
+    pub fn get_modified_duration(&self) -> Real {
+        self.modified_duration
+    }
+
+    pub fn get_convexity(&self) -> Real {
+        self.convexity
+    }
+
+    pub fn get_dv01(&self) -> Real {
+        self.dv01
+    }
+
+    pub fn get_z_spread(&self) -> Real {
+        self.z_spread
+    }
+
+    pub fn get_i_spread(&self) -> Real {
+        self.i_spread
+    }
+}
+
+/// compounding frequency of the yield: the coupon frequency, or annual for zero-coupon bonds (PaymentFrequency::None)
+fn compounding_frequency(bond: &Bond) -> Result<Real> {
+    let freq = bond.get_coupon_frequency()?.as_real();
+    match freq > 0.0 {
+        true => Ok(freq),
+        false => Ok(1.0),
+    }
+}
+
+/// discount factor of the yield y for the time t under the convention
+/// The frequency is used only for YieldConvention::Compounded and YieldConvention::Krx
+fn yield_discount_factor(convention: YieldConvention, y: Real, t: Time, freq: Real) -> Real {
+    match convention {
+        YieldConvention::Compounded | YieldConvention::Krx => (1.0 + y / freq).powf(-freq * t),
+        YieldConvention::Annual => (1.0 + y).powf(-t),
+        YieldConvention::Continuous => (-y * t).exp(),
+        YieldConvention::Simple => 1.0 / (1.0 + y * t),
+    }
+}
+
+/// (first, second) derivatives of yield_discount_factor with respect to the yield
+fn yield_discount_factor_derivatives(convention: YieldConvention, y: Real, t: Time, freq: Real) -> (Real, Real) {
+    match convention {
+        YieldConvention::Compounded | YieldConvention::Krx => {
+            let base = 1.0 + y / freq;
+            let d1 = -t * base.powf(-freq * t - 1.0);
+            let d2 = t * (t + 1.0 / freq) * base.powf(-freq * t - 2.0);
+            (d1, d2)
+        },
+        YieldConvention::Annual => {
+            let base = 1.0 + y;
+            (-t * base.powf(-t - 1.0), t * (t + 1.0) * base.powf(-t - 2.0))
+        },
+        YieldConvention::Continuous => {
+            let df = (-y * t).exp();
+            (-t * df, t * t * df)
+        },
+        YieldConvention::Simple => {
+            let base = 1.0 + y * t;
+            (-t / base.powi(2), 2.0 * t * t / base.powi(3))
+        },
+    }
+}
+
+/// inverse of yield_discount_factor
+fn rate_from_discount_factor(convention: YieldConvention, df: Real, t: Time, freq: Real) -> Result<Real> {
+    if t <= 0.0 || df <= 0.0 {
+        bail!(
+            "({}:{}) rate is not defined for t = {} and discount factor = {}",
+            file!(), line!(), t, df
+        );
+    }
+    let res = match convention {
+        YieldConvention::Compounded | YieldConvention::Krx => freq * (df.powf(-1.0 / (freq * t)) - 1.0),
+        YieldConvention::Annual => df.powf(-1.0 / t) - 1.0,
+        YieldConvention::Continuous => -df.ln() / t,
+        YieldConvention::Simple => (1.0 / df - 1.0) / t,
+    };
+    Ok(res)
+}
+
+/// Find x such that f(x) = 0 where f is monotone.
+/// [lower, upper] is widened until the root is bracketed, and then Brent's method is applied.
+fn solve_monotone<F>(mut f: F, lower: Real, upper: Real, tolerance: Real) -> Result<Real>
+where F: FnMut(Real) -> Result<Real>
+{
+    let (lower, upper, _, _) = find_bracket(&mut f, lower, upper, 10)?;
+    let config = SolverConfig::default().with_f_tolerance(tolerance);
+    Ok(brent(&mut f, lower, upper, &config)?.get_root())
+}
+
+/// BondAnalyticsCalculator calculates clean/dirty price, accrued interest,
+/// yield to maturity, durations, convexity, dv01, z-spread and i-spread.
+/// Every measure can be converted back to the dirty price so that the measures round-trip.
+/// The settlement date is the pricing date of the bond if given, otherwise the evaluation date.
+pub struct BondAnalyticsCalculator {
+    evaluation_date: Rc<RefCell<EvaluationDate>>,
+    discount_curve: Rc<RefCell<ZeroCurve>>,
+    forward_curve: Option<Rc<RefCell<ZeroCurve>>>,
+    past_fixing_data: Option<Rc<DailyClosePrice>>,
+    time_calculator: NullCalendar,
+    tolerance: Real,
+}
+
+impl BondAnalyticsCalculator {
+    pub fn new(
+        evaluation_date: Rc<RefCell<EvaluationDate>>,
+        discount_curve: Rc<RefCell<ZeroCurve>>,
+        forward_curve: Option<Rc<RefCell<ZeroCurve>>>,
+        past_fixing_data: Option<Rc<DailyClosePrice>>,
+    ) -> BondAnalyticsCalculator {
+        BondAnalyticsCalculator {
+            evaluation_date,
+            discount_curve,
+            forward_curve,
+            past_fixing_data,
+            time_calculator: NullCalendar::default(),
+            tolerance: 1.0e-7,
+        }
+    }
+
+    pub fn with_tolerance(mut self, tolerance: Real) -> BondAnalyticsCalculator {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn get_settlement_date(&self, bond: &Bond) -> Result<OffsetDateTime> {
+        let eval_dt = self.evaluation_date.borrow().get_date_clone();
+        Ok(*bond.get_pricing_date()?.unwrap_or(&eval_dt))
+    }
+
+    /// cashflows paid after the settlement date sorted by payment date
+    fn future_cashflows(&self, bond: &Bond, settlement_date: &OffsetDateTime) -> Result<Vec<(OffsetDateTime, Real)>> {
+        let cashflows = bond.get_cashflows(
+            settlement_date,
+            self.forward_curve.clone(),
+            self.past_fixing_data.clone(),
+        ).with_context(|| anyhow!(
+            "({}:{}) failed to get cashflows of {} ({}) in bond analytics",
+            file!(), line!(), bond.get_name(), bond.get_code()
+        ))?;
+
+        let mut res: Vec<(OffsetDateTime, Real)> = cashflows
+            .into_iter()
+            .filter(|(date, _)| date.date() > settlement_date.date())
+            .collect();
+        res.sort_by_key(|(date, _)| *date);
+        Ok(res)
+    }
+
+    /// year fractions from the settlement date measured by the day count convention of the bond
+    fn yield_times(&self, bond: &Bond, settlement_date: &OffsetDateTime, dates: &[OffsetDateTime]) -> Result<Vec<Time>> {
+        let calendar = bond.get_calendar()?;
+        let daycounter = bond.get_daycounter();
+        dates.iter()
+            .map(|date| calendar.year_fraction(settlement_date, date, daycounter))
+            .collect()
+    }
+
+    fn krx_yield_pricer(&self, bond_yield: Real) -> KrxYieldPricer {
+        KrxYieldPricer::new(
+            self.evaluation_date.clone(),
+            bond_yield,
+            self.forward_curve.clone(),
+            self.past_fixing_data.clone(),
+        )
+    }
+
+    /// Accrued interest from the start of the current coupon period to the settlement date
+    pub fn accrued_interest(&self, bond: &Bond) -> Result<Real> {
+        if bond.is_coupon_strip()? {
+            return Ok(0.0);
+        }
+        let settlement_date = self.get_settlement_date(bond)?;
+        let calendar = bond.get_calendar()?;
+        let daycounter = bond.get_daycounter();
+
+        let current_period = bond.get_schedule()?
+            .iter()
+            .find(|base_schedule| {
+                base_schedule.get_calc_start_date().date() <= settlement_date.date()
+                    && settlement_date.date() < base_schedule.get_calc_end_date().date()
+            });
+
+        let base_schedule = match current_period {
+            Some(base_schedule) => base_schedule,
+            None => return Ok(0.0),
+        };
+
+        let start_date = base_schedule.get_calc_start_date();
+        let period_fraction = calendar.year_fraction(start_date, base_schedule.get_calc_end_date(), daycounter)?;
+        if period_fraction <= 0.0 {
+            return Ok(0.0);
+        }
+        let accrued_fraction = calendar.year_fraction(start_date, &settlement_date, daycounter)?;
+
+        let coupon_amount = match (base_schedule.get_amount(), bond.get_fixed_coupon_rate()) {
+            (Some(amount), _) => amount,
+            (None, Some(rate)) => rate * period_fraction,
+            (None, None) => {
+                // floating rate note: the coupon is projected in the cashflows
+                let payment_date = base_schedule.get_payment_date();
+                let cashflows = bond.get_cashflows(
+                    &settlement_date,
+                    self.forward_curve.clone(),
+                    self.past_fixing_data.clone(),
+                )?;
+                let amount = cashflows.get(payment_date).copied().unwrap_or(0.0);
+                let maturity = bond.get_maturity()
+                    .ok_or_else(|| anyhow!("({}:{}) no maturity in {}", file!(), line!(), bond.get_code()))?;
+                match payment_date == maturity {
+                    true => amount - 1.0,
+                    false => amount,
+                }
+            },
+        };
+
+        Ok(coupon_amount * accrued_fraction / period_fraction)
+    }
+
+    pub fn clean_price_from_dirty_price(&self, bond: &Bond, dirty_price: Real) -> Result<Real> {
+        Ok(dirty_price - self.accrued_interest(bond)?)
+    }
+
+    pub fn dirty_price_from_clean_price(&self, bond: &Bond, clean_price: Real) -> Result<Real> {
+        Ok(clean_price + self.accrued_interest(bond)?)
+    }
+
+    /// dirty price of the bond at the settlement date discounted by the yield
+    pub fn dirty_price_from_yield(
+        &self,
+        bond: &Bond,
+        bond_yield: Real,
+        convention: YieldConvention,
+    ) -> Result<Real> {
+        if convention == YieldConvention::Krx {
+            return self.krx_yield_pricer(bond_yield).npv(&Instrument::Bond(bond.clone()));
+        }
+        let settlement_date = self.get_settlement_date(bond)?;
+        let freq = compounding_frequency(bond)?;
+        let cashflows = self.future_cashflows(bond, &settlement_date)?;
+        let dates: Vec<OffsetDateTime> = cashflows.iter().map(|(date, _)| *date).collect();
+        let times = self.yield_times(bond, &settlement_date, &dates)?;
+
+        let mut res: Real = 0.0;
+        for ((_, amount), t) in cashflows.iter().zip(times.iter()) {
+            res += amount * yield_discount_factor(convention, bond_yield, *t, freq);
+        }
+        Ok(res)
+    }
+
+    pub fn yield_from_dirty_price(
+        &self,
+        bond: &Bond,
+        dirty_price: Real,
+        convention: YieldConvention,
+    ) -> Result<Real> {
+        let freq = compounding_frequency(bond)?;
+        let lower = match convention {
+            YieldConvention::Compounded | YieldConvention::Krx => -0.5 * freq,
+            YieldConvention::Annual => -0.5,
+            YieldConvention::Continuous => -0.5,
+            YieldConvention::Simple => {
+                let settlement_date = self.get_settlement_date(bond)?;
+                let maturity = bond.get_maturity()
+                    .ok_or_else(|| anyhow!("({}:{}) no maturity in {}", file!(), line!(), bond.get_code()))?;
+                let t = self.yield_times(bond, &settlement_date, &[*maturity])?[0].max(1.0);
+                -0.5 / t
+            },
+        };
+        solve_monotone(
+            |y| Ok(self.dirty_price_from_yield(bond, y, convention)? - dirty_price),
+            lower.max(-0.5),
+            1.0,
+            self.tolerance,
+        ).with_context(|| anyhow!(
+            "({}:{}) failed to find the yield of {} ({}) for the dirty price {}",
+            file!(), line!(), bond.get_name(), bond.get_code(), dirty_price
+        ))
+    }
+
+    /// (modified duration, convexity) of the yield
+    pub fn modified_duration_and_convexity(
+        &self,
+        bond: &Bond,
+        bond_yield: Real,
+        convention: YieldConvention,
+    ) -> Result<(Real, Real)> {
+        if convention == YieldConvention::Krx {
+            let h = 1.0e-3;
+            let price = self.dirty_price_from_yield(bond, bond_yield, convention)?;
+            let price_up = self.dirty_price_from_yield(bond, bond_yield + h, convention)?;
+            let price_down = self.dirty_price_from_yield(bond, bond_yield - h, convention)?;
+            let duration = -(price_up - price_down) / (2.0 * h) / price;
+            let convexity = (price_up - 2.0 * price + price_down) / (h * h) / price;
+            return Ok((duration, convexity));
+        }
+        let settlement_date = self.get_settlement_date(bond)?;
+        let freq = compounding_frequency(bond)?;
+        let cashflows = self.future_cashflows(bond, &settlement_date)?;
+        let dates: Vec<OffsetDateTime> = cashflows.iter().map(|(date, _)| *date).collect();
+        let times = self.yield_times(bond, &settlement_date, &dates)?;
+
+        let mut price: Real = 0.0;
+        let mut first: Real = 0.0;
+        let mut second: Real = 0.0;
+        for ((_, amount), t) in cashflows.iter().zip(times.iter()) {
+            price += amount * yield_discount_factor(convention, bond_yield, *t, freq);
+            let (d1, d2) = yield_discount_factor_derivatives(convention, bond_yield, *t, freq);
+            first += amount * d1;
+            second += amount * d2;
+        }
+        if price <= 0.0 {
+            bail!("({}:{}) non-positive price {} of {}", file!(), line!(), price, bond.get_code());
+        }
+        Ok((-first / price, second / price))
+    }
+
+    pub fn macaulay_duration(
+        &self,
+        bond: &Bond,
+        bond_yield: Real,
+        convention: YieldConvention,
+    ) -> Result<Real> {
+        let freq = compounding_frequency(bond)?;
+        match convention {
+            YieldConvention::Krx => {
+                let (duration, _) = self.modified_duration_and_convexity(bond, bond_yield, convention)?;
+                Ok(duration * (1.0 + bond_yield / freq))
+            },
+            _ => {
+                let settlement_date = self.get_settlement_date(bond)?;
+                let cashflows = self.future_cashflows(bond, &settlement_date)?;
+                let dates: Vec<OffsetDateTime> = cashflows.iter().map(|(date, _)| *date).collect();
+                let times = self.yield_times(bond, &settlement_date, &dates)?;
+                let mut price: Real = 0.0;
+                let mut weighted: Real = 0.0;
+                for ((_, amount), t) in cashflows.iter().zip(times.iter()) {
+                    let pv = amount * yield_discount_factor(convention, bond_yield, *t, freq);
+                    price += pv;
+                    weighted += t * pv;
+                }
+                if price <= 0.0 {
+                    bail!("({}:{}) non-positive price {} of {}", file!(), line!(), price, bond.get_code());
+                }
+                Ok(weighted / price)
+            },
+        }
+    }
+
+    /// dirty price at the settlement date discounted by the discount curve shifted by z_spread (continuous)
+    /// If z_spread = 0, this is the same as the npv of BondPricer
+    pub fn dirty_price_from_z_spread(&self, bond: &Bond, z_spread: Real) -> Result<Real> {
+        let settlement_date = self.get_settlement_date(bond)?;
+        let eval_dt = self.evaluation_date.borrow().get_date_clone();
+        let cashflows = self.future_cashflows(bond, &settlement_date)?;
+        let curve = self.discount_curve.borrow();
+        let settlement_time = self.time_calculator.get_time_difference(&eval_dt, &settlement_date);
+
+        let mut res: Real = 0.0;
+        for (date, amount) in cashflows.iter() {
+            let tau = self.time_calculator.get_time_difference(&eval_dt, date) - settlement_time;
+            res += amount * curve.get_discount_factor_at_date(date)? * (-z_spread * tau).exp();
+        }
+        res /= curve.get_discount_factor_at_date(&settlement_date)?;
+        Ok(res)
+    }
+
+    pub fn z_spread_from_dirty_price(&self, bond: &Bond, dirty_price: Real) -> Result<Real> {
+        solve_monotone(
+            |z| Ok(self.dirty_price_from_z_spread(bond, z)? - dirty_price),
+            -0.5,
+            1.0,
+            self.tolerance,
+        ).with_context(|| anyhow!(
+            "({}:{}) failed to find the z-spread of {} ({}) for the dirty price {}",
+            file!(), line!(), bond.get_name(), bond.get_code(), dirty_price
+        ))
+    }
+
+    /// curve rate from the settlement date to the maturity expressed in the yield convention
+    pub fn curve_rate_at_maturity(&self, bond: &Bond, convention: YieldConvention) -> Result<Real> {
+        let settlement_date = self.get_settlement_date(bond)?;
+        let maturity = bond.get_maturity()
+            .ok_or_else(|| anyhow!("({}:{}) no maturity in {}", file!(), line!(), bond.get_code()))?;
+        let freq = compounding_frequency(bond)?;
+        let curve = self.discount_curve.borrow();
+        let df = curve.get_discount_factor_at_date(maturity)? / curve.get_discount_factor_at_date(&settlement_date)?;
+        let t = self.yield_times(bond, &settlement_date, &[*maturity])?[0];
+        rate_from_discount_factor(convention, df, t, freq)
+    }
+
+    pub fn i_spread_from_yield(&self, bond: &Bond, bond_yield: Real, convention: YieldConvention) -> Result<Real> {
+        Ok(bond_yield - self.curve_rate_at_maturity(bond, convention)?)
+    }
+
+    pub fn yield_from_i_spread(&self, bond: &Bond, i_spread: Real, convention: YieldConvention) -> Result<Real> {
+        Ok(i_spread + self.curve_rate_at_maturity(bond, convention)?)
+    }
+
+    pub fn i_spread_from_dirty_price(&self, bond: &Bond, dirty_price: Real, convention: YieldConvention) -> Result<Real> {
+        let bond_yield = self.yield_from_dirty_price(bond, dirty_price, convention)?;
+        self.i_spread_from_yield(bond, bond_yield, convention)
+    }
+
+    pub fn dirty_price_from_i_spread(&self, bond: &Bond, i_spread: Real, convention: YieldConvention) -> Result<Real> {
+        let bond_yield = self.yield_from_i_spread(bond, i_spread, convention)?;
+        self.dirty_price_from_yield(bond, bond_yield, convention)
+    }
+
+    /// all measures for the given dirty price
+    pub fn analyze(
+        &self,
+        bond: &Bond,
+        dirty_price: Real,
+        convention: YieldConvention,
+    ) -> Result<BondAnalytics> {
+        let settlement_date = self.get_settlement_date(bond)?;
+        let accrued_interest = self.accrued_interest(bond)?;
+        let yield_to_maturity = self.yield_from_dirty_price(bond, dirty_price, convention)?;
+        let (modified_duration, convexity) = self.modified_duration_and_convexity(
+            bond, yield_to_maturity, convention)?;
+        let macaulay_duration = self.macaulay_duration(bond, yield_to_maturity, convention)?;
+        let z_spread = self.z_spread_from_dirty_price(bond, dirty_price)?;
+        let i_spread = self.i_spread_from_yield(bond, yield_to_maturity, convention)?;
+
+        Ok(BondAnalytics {
+            settlement_date,
+            yield_convention: convention,
+            dirty_price,
+            clean_price: dirty_price - accrued_interest,
+            accrued_interest,
+            yield_to_maturity,
+            macaulay_duration,
+            modified_duration,
+            convexity,
+            dv01: modified_duration * dirty_price * 1.0e-4,
+            z_spread,
+            i_spread,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::vector_data::VectorData;
+    use crate::currency::Currency;
+    use crate::enums::{CreditRating, IssuerType, RankType};
+    use crate::pricing_engines::bond_pricer::BondPricer;
+    use crate::instruments::schedule::build_schedule;
+    use crate::time::conventions::{BusinessDayConvention, DayCountConvention, PaymentFrequency};
+    use crate::time::{
+        calendar::Calendar,
+        calendars::southkorea::{SouthKorea, SouthKoreaType},
+        jointcalendar::JointCalendar,
+    };
+    use ndarray::array;
+    use time::macros::datetime;
+
+    fn setup() -> Result<(Rc<RefCell<EvaluationDate>>, Rc<RefCell<ZeroCurve>>, Bond)> {
+        let dt = datetime!(2024-03-18 16:30:00 +09:00);
+        let evaluation_date = Rc::new(RefCell::new(EvaluationDate::new(dt)));
+        let curve_data = VectorData::new(
+            array![0.032, 0.034, 0.035],
+            None,
+            Some(array![0.5, 2.0, 5.0]),
+            None,
+            Currency::KRW,
+            "KRWGOV".to_string(),
+            "KRWGOV".to_string(),
+        )?;
+        let discount_curve = Rc::new(RefCell::new(ZeroCurve::new(
+            evaluation_date.clone(),
+            &curve_data,
+            "KRWGOV".to_string(),
+            "KRWGOV".to_string(),
+        )?));
+
+        let issue_date = datetime!(2022-12-10 16:30:00 +09:00);
+        let maturity = datetime!(2025-12-10 16:30:00 +09:00);
+        let sk = Calendar::SouthKorea(SouthKorea::new(SouthKoreaType::Settlement));
+        let bond = Bond::new_from_conventions(
+            IssuerType::Government,
+            CreditRating::None,
+            "Korea Gov".to_string(),
+            RankType::Senior,
+            Currency::KRW,
+            10_000.0,
+            false,
+            issue_date,
+            issue_date,
+            None,
+            maturity,
+            Some(0.0425),
+            None,
+            None,
+            None,
+            JointCalendar::new(vec![sk])?,
+            true,
+            DayCountConvention::StreetConvention,
+            BusinessDayConvention::Unadjusted,
+            PaymentFrequency::SemiAnnually,
+            0,
+            0,
+            "국고채권 04250-2512(22-13)".to_string(),
+            "KR103501GCC0".to_string(),
+        )?;
+        Ok((evaluation_date, discount_curve, bond))
+    }
+
+    #[test]
+    fn test_bond_analytics_round_trip() -> Result<()> {
+        let (evaluation_date, discount_curve, bond) = setup()?;
+        let pricer = BondPricer::new(evaluation_date.clone(), discount_curve.clone(), None, None);
+        let npv = pricer.npv(&Instrument::Bond(bond.clone()))?;
+        let calculator = BondAnalyticsCalculator::new(evaluation_date, discount_curve, None, None);
+
+        // accrued interest: 2023-12-10 ~ 2024-03-18 (98 days in 30/360) of 4.25% coupon
+        let accrued = calculator.accrued_interest(&bond)?;
+        let expected_accrued = 0.0425 * 98.0 / 360.0;
+        assert!(
+            (accrued - expected_accrued).abs() < 1.0e-6,
+            "accrued: {}, expected: {}", accrued, expected_accrued
+        );
+        let clean = calculator.clean_price_from_dirty_price(&bond, npv)?;
+        assert!((calculator.dirty_price_from_clean_price(&bond, clean)? - npv).abs() < 1.0e-6);
+
+        for convention in [
+            YieldConvention::Krx,
+            YieldConvention::Compounded,
+            YieldConvention::Annual,
+            YieldConvention::Continuous,
+            YieldConvention::Simple,
+        ] {
+            let bond_yield = calculator.yield_from_dirty_price(&bond, npv, convention)?;
+            let price = calculator.dirty_price_from_yield(&bond, bond_yield, convention)?;
+            assert!(
+                (price - npv).abs() < 1.0e-5,
+                "{:?}: yield = {}, price = {}, npv = {}", convention, bond_yield, price, npv
+            );
+            let i_spread = calculator.i_spread_from_dirty_price(&bond, npv, convention)?;
+            let price = calculator.dirty_price_from_i_spread(&bond, i_spread, convention)?;
+            assert!(
+                (price - npv).abs() < 1.0e-5,
+                "{:?}: i_spread = {}, price = {}, npv = {}", convention, i_spread, price, npv
+            );
+        }
+
+        // the model price has no spread over the discount curve
+        let z_spread = calculator.z_spread_from_dirty_price(&bond, npv)?;
+        assert!(z_spread.abs() < 1.0e-5, "z_spread: {}", z_spread);
+
+        let z_spread = calculator.z_spread_from_dirty_price(&bond, npv - 0.01)?;
+        let price = calculator.dirty_price_from_z_spread(&bond, z_spread)?;
+        assert!(z_spread > 0.0);
+        assert!((price - npv + 0.01).abs() < 1.0e-5, "price: {}, target: {}", price, npv - 0.01);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bond_analytics_duration() -> Result<()> {
+        let (evaluation_date, discount_curve, bond) = setup()?;
+        let calculator = BondAnalyticsCalculator::new(evaluation_date, discount_curve, None, None);
+        let dirty_price = 1.02;
+        let analytics = calculator.analyze(&bond, dirty_price, YieldConvention::Compounded)?;
+
+        let y = analytics.get_yield_to_maturity();
+        let h = 1.0e-3;
+        let up = calculator.dirty_price_from_yield(&bond, y + h, YieldConvention::Compounded)?;
+        let down = calculator.dirty_price_from_yield(&bond, y - h, YieldConvention::Compounded)?;
+        let numerical_duration = -(up - down) / (2.0 * h) / dirty_price;
+        assert!(
+            (analytics.get_modified_duration() - numerical_duration).abs() < 1.0e-2,
+            "modified duration: {}, numerical: {}", analytics.get_modified_duration(), numerical_duration
+        );
+        // Macaulay duration = modified duration * (1 + y / f)
+        let macaulay = analytics.get_modified_duration() * (1.0 + y / 2.0);
+        assert!((analytics.get_macaulay_duration() - macaulay).abs() < 1.0e-4);
+        assert!(analytics.get_convexity() > 0.0);
+        assert!((analytics.get_dv01() - analytics.get_modified_duration() * dirty_price * 1.0e-4).abs() < 1.0e-8);
+        assert!((analytics.get_clean_price() + analytics.get_accrued_interest() - dirty_price).abs() < 1.0e-6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bond_analytics_zero_coupon() -> Result<()> {
+        let (evaluation_date, discount_curve, _) = setup()?;
+        let issue_date = datetime!(2023-12-10 16:30:00 +09:00);
+        let maturity = datetime!(2025-12-10 16:30:00 +09:00);
+        let calendar = JointCalendar::new(vec![Calendar::SouthKorea(SouthKorea::new(SouthKoreaType::Settlement))])?;
+        let schedule = build_schedule(
+            true,
+            &issue_date,
+            &maturity,
+            &calendar,
+            &BusinessDayConvention::Unadjusted,
+            &PaymentFrequency::Annually,
+            0,
+            0,
+        )?;
+        let bond = Bond::new(
+            IssuerType::Government,
+            CreditRating::None,
+            "Korea Gov".to_string(),
+            RankType::Senior,
+            Currency::KRW,
+            10_000.0,
+            false,
+            schedule,
+            Some(0.0),
+            None,
+            None,
+            None,
+            issue_date,
+            issue_date,
+            None,
+            maturity,
+            calendar,
+            DayCountConvention::StreetConvention,
+            BusinessDayConvention::Unadjusted,
+            PaymentFrequency::None,
+            0,
+            0,
+            "Zero Coupon".to_string(),
+            "ZERO".to_string(),
+        )?;
+        let calculator = BondAnalyticsCalculator::new(evaluation_date, discount_curve, None, None);
+        let dirty_price = 0.95;
+        // the yield of a zero-coupon bond is compounded annually
+        let analytics = calculator.analyze(&bond, dirty_price, YieldConvention::Compounded)?;
+        let y = analytics.get_yield_to_maturity();
+        let annual_yield = calculator.yield_from_dirty_price(&bond, dirty_price, YieldConvention::Annual)?;
+        assert!((y - annual_yield).abs() < 1.0e-8, "yield: {}, annual: {}", y, annual_yield);
+        assert!(y > 0.0);
+        assert!(
+            (analytics.get_modified_duration() - analytics.get_macaulay_duration() / (1.0 + y)).abs() < 1.0e-6,
+            "modified duration: {}, macaulay: {}", analytics.get_modified_duration(), analytics.get_macaulay_duration()
+        );
+        assert!(analytics.get_convexity().is_finite() && analytics.get_convexity() > 0.0);
+        assert!(analytics.get_i_spread().is_finite());
+        assert_eq!(analytics.get_accrued_interest(), 0.0);
+        Ok(())
+    }
+}
@@ -4,17 +4,19 @@ use crate::parameters::zero_curve::ZeroCurve;
 use crate::evaluation_date::EvaluationDate;
 use crate::pricing_engines::{
     npv_result::NpvResult, 
-    pricer::PricerTrait
+    pricer::PricerTrait,
+    bond_analytics::{BondAnalytics, BondAnalyticsCalculator},
 };
 use crate::instrument::Instrument;
 use crate::definitions::Real;
+use crate::enums::YieldConvention;
 //
 use std::{
     rc::Rc, 
     cell::RefCell,
     collections::HashMap,
 };
-use anyhow::{Result, Context};
+use anyhow::{Result, Context, anyhow};
 use time::OffsetDateTime;
 
 /// forward_curve (Optional<Rc<RefCell<ZeroCurve>>>): forward curve for floating rate bond, so it is optional
@@ -104,7 +106,33 @@ impl PricerTrait for BondPricer {
         );
 
         Ok(res)
-    }       
+    }
+
+    /// the npv of the bond is used as the dirty price
+    fn bond_analytics(
+        &self,
+        instrument: &Instrument,
+        yield_convention: YieldConvention,
+    ) -> Result<HashMap<String, BondAnalytics>> {
+        let bond = match instrument {
+            Instrument::Bond(bond) => bond,
+            _ => return Err(anyhow!(
+                "({}:{}) BondPricer::bond_analytics requires a bond, but got {} ({})",
+                file!(), line!(), instrument.get_name(), instrument.get_code()
+            )),
+        };
+        let dirty_price = self.npv(instrument)?;
+        let calculator = BondAnalyticsCalculator::new(
+            self.evaluation_date.clone(),
+            self.discount_curve.clone(),
+            self.forward_curve.clone(),
+            self.past_fixing_data.clone(),
+        );
+        let analytics = calculator.analyze(bond, dirty_price, yield_convention)?;
+        let mut res = HashMap::new();
+        res.insert(instrument.get_code().clone(), analytics);
+        Ok(res)
+    }
 }
 
 // please make a pricer test by refering crate::instruments::schedule, 
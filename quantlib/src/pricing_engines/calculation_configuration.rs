@@ -1,15 +1,79 @@
-use crate::definitions::{Real, Integer};
-use crate::enums::{StickynessType, VanillaOptionCalculationMethod};
+use crate::definitions::{Real, Integer, Time};
+use crate::enums::{MonteCarloScheme, StickynessType, VanillaOptionCalculationMethod, VolatilitySurfaceType, YieldConvention};
 use crate::parameters::volatilities::volatiltiy_interpolator::VolatilityInterplator;
+use crate::pricing_engines::scenario::{CurvatureScenario, Scenario};
 use serde::{Serialize, Deserialize};
 use anyhow::{anyhow, Result};
 use ndarray::Array1;
+
+/// Monte Carlo settings of the pricers simulating the underlying (see OptionMonteCarloPricer).
+/// The seed is fixed, so that the greeks by bumping use common random numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MonteCarloConfiguration {
+    number_of_paths: usize,
+    /// the time steps between the observation dates are at most max_time_step
+    max_time_step: Time,
+    seed: u64,
+    scheme: MonteCarloScheme,
+}
+
+impl Default for MonteCarloConfiguration {
+    fn default() -> MonteCarloConfiguration {
+        MonteCarloConfiguration {
+            number_of_paths: 10_000,
+            max_time_step: 1.0 / 52.0,
+            seed: 0,
+            scheme: MonteCarloScheme::default(),
+        }
+    }
+}
+
+impl MonteCarloConfiguration {
+    pub fn with_number_of_paths(mut self, number_of_paths: usize) -> MonteCarloConfiguration {
+        self.number_of_paths = number_of_paths;
+        self
+    }
+
+    pub fn with_max_time_step(mut self, max_time_step: Time) -> MonteCarloConfiguration {
+        self.max_time_step = max_time_step;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> MonteCarloConfiguration {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_scheme(mut self, scheme: MonteCarloScheme) -> MonteCarloConfiguration {
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn get_number_of_paths(&self) -> usize {
+        self.number_of_paths
+    }
+
+    pub fn get_max_time_step(&self) -> Time {
+        self.max_time_step
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_scheme(&self) -> MonteCarloScheme {
+        self.scheme
+    }
+}
 /// CalculationConfiguration is a struct that holds the configuration of the calculation.
 /// stickyness_type: StickynessType
 /// StickynessType is an enum that represents the stickyness of the calculation.
 /// If the stickyness_type is StickyToMoneyness, the delta will be calculated with respect to moneyness:
 /// In other words, delta = dV/dS + dvol/dS * dV/dvols
+/// In files, the missing fields are those of CalculationConfiguration::default().
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CalculationConfiguration {
     npv: bool,
     fx_exposure: bool,
@@ -23,9 +87,33 @@ pub struct CalculationConfiguration {
     rho_structure: bool,
     div_structure: bool,
     vega_matrix: bool,
+    #[serde(default)]
+    bond_analytics: bool,
+    #[serde(default)]
+    implied_volatility: bool,
+    #[serde(default)]
+    scenario: bool,
+    #[serde(default)]
+    cross_gamma: bool,
+    #[serde(default)]
+    vanna: bool,
+    #[serde(default)]
+    volga: bool,
+    #[serde(default)]
+    fx_delta: bool,
+    #[serde(default)]
+    fx_gamma: bool,
+    #[serde(default)]
+    fx_vega: bool,
+    #[serde(default)]
+    quanto_correlation: bool,
+    #[serde(default)]
+    curvature: bool,
     //
     stickyness_type: StickynessType,
     lv_interpolator: VolatilityInterplator,
+    #[serde(default)]
+    volatility_surface_type: VolatilitySurfaceType,
     //
     delta_bump_ratio: Real,
     gamma_bump_ratio: Real,
@@ -34,6 +122,8 @@ pub struct CalculationConfiguration {
     vega_matrix_bump_value: Real,
     rho_bump_value: Real,
     div_bump_value: Real,
+    #[serde(default = "default_correlation_bump_value")]
+    correlation_bump_value: Real,
     theta_day: Integer,
     // 
     rho_structure_tenors: Vec<String>,
@@ -42,7 +132,21 @@ pub struct CalculationConfiguration {
     vega_matrix_spot_moneyness: Array1<Real>,
     // 
     vanilla_option_calculation_method: VanillaOptionCalculationMethod,
+    #[serde(default)]
+    monte_carlo: MonteCarloConfiguration,
+    #[serde(default)]
+    yield_convention: YieldConvention,
     //
+    #[serde(default)]
+    scenarios: Vec<Scenario>,
+    #[serde(default)]
+    curvature_scenarios: Vec<CurvatureScenario>,
+    #[serde(default)]
+    parallel_bumps: bool,
+}
+
+fn default_correlation_bump_value() -> Real {
+    0.01
 }
 
 impl Default for CalculationConfiguration {
@@ -99,8 +203,20 @@ impl Default for CalculationConfiguration {
             rho_structure: false,
             div_structure: false,
             vega_matrix: false,
+            bond_analytics: false,
+            implied_volatility: false,
+            scenario: false,
+            cross_gamma: false,
+            vanna: false,
+            volga: false,
+            fx_delta: false,
+            fx_gamma: false,
+            fx_vega: false,
+            quanto_correlation: false,
+            curvature: false,
             stickyness_type: StickynessType::StickyToMoneyness,
             lv_interpolator: VolatilityInterplator::default(),
+            volatility_surface_type: VolatilitySurfaceType::default(),
             delta_bump_ratio: 0.01,
             gamma_bump_ratio: 0.01,
             vega_bump_value: 0.01,
@@ -108,12 +224,18 @@ impl Default for CalculationConfiguration {
             vega_matrix_bump_value: 0.001,
             rho_bump_value: 0.0001,
             div_bump_value: 0.0001,
+            correlation_bump_value: default_correlation_bump_value(),
             theta_day: 1,
             rho_structure_tenors: rho_tenors,
             vega_structure_tenors: vega_tenors,
             div_structure_tenors: div_tenors,
             vega_matrix_spot_moneyness,
             vanilla_option_calculation_method: VanillaOptionCalculationMethod::Analytic,
+            monte_carlo: MonteCarloConfiguration::default(),
+            yield_convention: YieldConvention::default(),
+            scenarios: vec![],
+            curvature_scenarios: vec![],
+            parallel_bumps: false,
         }
     }
 }
@@ -195,9 +317,21 @@ impl CalculationConfiguration {
             div_structure,
             rho_structure,
             vega_matrix,
+            bond_analytics: false,
+            implied_volatility: false,
+            scenario: false,
+            cross_gamma: false,
+            vanna: false,
+            volga: false,
+            fx_delta: false,
+            fx_gamma: false,
+            fx_vega: false,
+            quanto_correlation: false,
+            curvature: false,
             //
             stickyness_type,
             lv_interpolator,
+            volatility_surface_type: VolatilitySurfaceType::default(),
             //
             delta_bump_ratio,
             gamma_bump_ratio,
@@ -206,6 +340,7 @@ impl CalculationConfiguration {
             vega_matrix_bump_value,
             rho_bump_value,
             div_bump_value,
+            correlation_bump_value: default_correlation_bump_value(),
             theta_day,
             rho_structure_tenors,
             vega_structure_tenors,
@@ -213,6 +348,11 @@ impl CalculationConfiguration {
             vega_matrix_spot_moneyness,
             //
             vanilla_option_calculation_method,
+            monte_carlo: MonteCarloConfiguration::default(),
+            yield_convention: YieldConvention::default(),
+            scenarios: vec![],
+            curvature_scenarios: vec![],
+            parallel_bumps: false,
         })
     }
 
@@ -221,6 +361,11 @@ impl CalculationConfiguration {
         self
     }
 
+    pub fn with_fx_exposure_calculation(mut self, fx_exposure: bool) -> CalculationConfiguration {
+        self.fx_exposure = fx_exposure;
+        self
+    }
+
     pub fn with_delta_calculation(mut self, delta: bool) -> CalculationConfiguration {
         self.delta = delta;
         self
@@ -334,11 +479,146 @@ impl CalculationConfiguration {
         self
     }
 
+    pub fn with_monte_carlo_configuration(mut self, monte_carlo: MonteCarloConfiguration) -> CalculationConfiguration {
+        self.monte_carlo = monte_carlo;
+        self
+    }
+
+    pub fn with_bond_analytics_calculation(mut self, bond_analytics: bool) -> CalculationConfiguration {
+        self.bond_analytics = bond_analytics;
+        self
+    }
+
+    /// implied volatilities of vanilla options from the option prices given to the engine
+    pub fn with_implied_volatility_calculation(mut self, implied_volatility: bool) -> CalculationConfiguration {
+        self.implied_volatility = implied_volatility;
+        self
+    }
+
+    pub fn with_scenario_calculation(mut self, scenario: bool) -> CalculationConfiguration {
+        self.scenario = scenario;
+        self
+    }
+
+    /// scenarios for Engine::set_scenario_pnls, e.g., loaded by Scenario::from_json_file
+    pub fn with_scenarios(mut self, scenarios: Vec<Scenario>) -> CalculationConfiguration {
+        self.scenarios = scenarios;
+        self
+    }
+
+    /// cross gamma between the underlyings (and the quanto fx rates) of each instrument
+    pub fn with_cross_gamma_calculation(mut self, cross_gamma: bool) -> CalculationConfiguration {
+        self.cross_gamma = cross_gamma;
+        self
+    }
+
+    /// dDelta/dVol between the underlyings and the volatilities of each instrument
+    pub fn with_vanna_calculation(mut self, vanna: bool) -> CalculationConfiguration {
+        self.vanna = vanna;
+        self
+    }
+
+    /// dVega/dVol
+    pub fn with_volga_calculation(mut self, volga: bool) -> CalculationConfiguration {
+        self.volga = volga;
+        self
+    }
+
+    /// delta per fx rate (e.g., "USDKRW") used in pricing, such as FxFutures and CRS
+    pub fn with_fx_delta_calculation(mut self, fx_delta: bool) -> CalculationConfiguration {
+        self.fx_delta = fx_delta;
+        self
+    }
+
+    /// gamma per fx rate used in pricing
+    pub fn with_fx_gamma_calculation(mut self, fx_gamma: bool) -> CalculationConfiguration {
+        self.fx_gamma = fx_gamma;
+        self
+    }
+
+    /// vega of the fx volatilities in the quanto adjustments
+    pub fn with_fx_vega_calculation(mut self, fx_vega: bool) -> CalculationConfiguration {
+        self.fx_vega = fx_vega;
+        self
+    }
+
+    /// sensitivity to the correlations between the underlyings and the fx rates in the quanto adjustments
+    pub fn with_quanto_correlation_calculation(mut self, quanto_correlation: bool) -> CalculationConfiguration {
+        self.quanto_correlation = quanto_correlation;
+        self
+    }
+
+    /// up and down revaluations of the risk factors in curvature_scenarios
+    pub fn with_curvature_calculation(mut self, curvature: bool) -> CalculationConfiguration {
+        self.curvature = curvature;
+        self
+    }
+
+    /// curvature scenarios for Engine::set_curvature_pnls, e.g., made by FrtbSensitivityMapper::get_curvature_scenarios
+    pub fn with_curvature_scenarios(mut self, curvature_scenarios: Vec<CurvatureScenario>) -> CalculationConfiguration {
+        self.curvature_scenarios = curvature_scenarios;
+        self
+    }
+
+    pub fn with_correlation_bump_value(mut self, correlation_bump_value: Real) -> CalculationConfiguration {
+        self.correlation_bump_value = correlation_bump_value;
+        self
+    }
+
+    pub fn with_yield_convention(mut self, yield_convention: YieldConvention) -> CalculationConfiguration {
+        self.yield_convention = yield_convention;
+        self
+    }
+
+    /// Interpolated (default) keeps the market surface as it is,
+    /// RawSvi and Ssvi fit the surface and check it for static arbitrage
+    pub fn with_volatility_surface_type(mut self, volatility_surface_type: VolatilitySurfaceType) -> CalculationConfiguration {
+        self.volatility_surface_type = volatility_surface_type;
+        self
+    }
+
     pub fn with_lv_interpolator(mut self, lv_interpolator: VolatilityInterplator) -> CalculationConfiguration {
         self.lv_interpolator = lv_interpolator;
         self
     }
 
+    /// If true, the bump revaluations of the greeks (delta, vega, rho, div and the structures)
+    /// run in parallel on replicas of the engine (see Engine::get_npvs_of_bump_jobs)
+    pub fn with_parallel_bumps(mut self, parallel_bumps: bool) -> CalculationConfiguration {
+        self.parallel_bumps = parallel_bumps;
+        self
+    }
+
+    /// the same configuration where only npv (and the cashflows) is calculated
+    pub fn npv_only(mut self) -> CalculationConfiguration {
+        self.npv = true;
+        self.fx_exposure = false;
+        self.delta = false;
+        self.gamma = false;
+        self.vega = false;
+        self.rho = false;
+        self.div_delta = false;
+        self.theta = false;
+        self.vega_strucure = false;
+        self.rho_structure = false;
+        self.div_structure = false;
+        self.vega_matrix = false;
+        self.bond_analytics = false;
+        self.implied_volatility = false;
+        self.scenario = false;
+        self.scenarios = vec![];
+        self.cross_gamma = false;
+        self.vanna = false;
+        self.volga = false;
+        self.fx_delta = false;
+        self.fx_gamma = false;
+        self.fx_vega = false;
+        self.quanto_correlation = false;
+        self.curvature = false;
+        self.curvature_scenarios = vec![];
+        self
+    }
+
 
     pub fn get_vanilla_option_calculation_method(&self) -> VanillaOptionCalculationMethod {
         self.vanilla_option_calculation_method
@@ -444,6 +724,78 @@ impl CalculationConfiguration {
         self.lv_interpolator.clone()
     }
 
+    pub fn get_volatility_surface_type(&self) -> VolatilitySurfaceType {
+        self.volatility_surface_type
+    }
+
+    pub fn get_monte_carlo_configuration(&self) -> MonteCarloConfiguration {
+        self.monte_carlo
+    }
+
+    pub fn get_bond_analytics_calculation(&self) -> bool {
+        self.bond_analytics
+    }
+
+    pub fn get_implied_volatility_calculation(&self) -> bool {
+        self.implied_volatility
+    }
+
+    pub fn get_scenario_calculation(&self) -> bool {
+        self.scenario
+    }
+
+    pub fn get_cross_gamma_calculation(&self) -> bool {
+        self.cross_gamma
+    }
+
+    pub fn get_vanna_calculation(&self) -> bool {
+        self.vanna
+    }
+
+    pub fn get_volga_calculation(&self) -> bool {
+        self.volga
+    }
+
+    pub fn get_fx_delta_calculation(&self) -> bool {
+        self.fx_delta
+    }
+
+    pub fn get_fx_gamma_calculation(&self) -> bool {
+        self.fx_gamma
+    }
+
+    pub fn get_fx_vega_calculation(&self) -> bool {
+        self.fx_vega
+    }
+
+    pub fn get_quanto_correlation_calculation(&self) -> bool {
+        self.quanto_correlation
+    }
+
+    pub fn get_correlation_bump_value(&self) -> Real {
+        self.correlation_bump_value
+    }
+
+    pub fn get_scenarios(&self) -> &Vec<Scenario> {
+        &self.scenarios
+    }
+
+    pub fn get_curvature_calculation(&self) -> bool {
+        self.curvature
+    }
+
+    pub fn get_curvature_scenarios(&self) -> &Vec<CurvatureScenario> {
+        &self.curvature_scenarios
+    }
+
+    pub fn get_yield_convention(&self) -> YieldConvention {
+        self.yield_convention
+    }
+
+    pub fn get_parallel_bumps(&self) -> bool {
+        self.parallel_bumps
+    }
+
     
 }
 
@@ -2,6 +2,7 @@ use crate::instruments::instrument_info::InstrumentInfo;
 use crate::currency::Currency;
 use crate::definitions::{Real, Integer};
 use crate::pricing_engines::npv_result::NpvResult;
+use crate::pricing_engines::bond_analytics::BondAnalytics;
 use crate::utils::number_format::{write_number_with_commas, formatted_number};
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
@@ -42,6 +43,27 @@ pub struct CalculationResult {
     rho: Option<HashMap<String, Real>>, // Curve Code -> rho
     rho_structure: Option<HashMap<String, Vec<Real>>>, // curve code -> Vec::<Real> on rho_tenor in CalculationConfig
     theta_day: Option<Integer>,
+    bond_analytics: Option<HashMap<String, BondAnalytics>>, // bond code -> analytics per unit face value
+    implied_volatility: Option<Real>, // volatility implied by the market price of the option
+    scenario_pnl: Option<HashMap<String, Real>>, // scenario name -> (npv under the scenario - npv) * unit_notional
+    #[serde(default)]
+    cross_gamma: Option<HashMap<String, HashMap<String, Real>>>, // code1 -> code2 -> cross gamma (symmetric)
+    #[serde(default)]
+    vanna: Option<HashMap<String, HashMap<String, Real>>>, // underlying code -> volatility code -> vanna
+    #[serde(default)]
+    volga: Option<HashMap<String, Real>>, // volatility code -> volga
+    #[serde(default)]
+    fx_delta: Option<HashMap<String, Real>>, // fx code (e.g., "USDKRW") -> fx delta
+    #[serde(default)]
+    fx_gamma: Option<HashMap<String, Real>>, // fx code -> fx gamma
+    #[serde(default)]
+    fx_vega: Option<HashMap<String, Real>>, // fx code -> vega of the fx volatility
+    #[serde(default)]
+    quanto_correlation: Option<HashMap<String, HashMap<String, Real>>>, // underlying code -> fx code -> sensitivity
+    #[serde(default)]
+    curvature_up_pnl: Option<HashMap<String, Real>>, // risk factor -> (npv under the up scenario - npv) * unit_notional
+    #[serde(default)]
+    curvature_down_pnl: Option<HashMap<String, Real>>, // risk factor -> (npv under the down scenario - npv) * unit_notional
     #[serde(skip)]
     cashflows: Option<HashMap<OffsetDateTime, Real>>, //expected cashflow inbetween
     representation_currency: Option<Currency>,
@@ -66,6 +88,18 @@ impl Default for CalculationResult {
             rho: None,
             rho_structure: None,
             theta_day: None,
+            bond_analytics: None,
+            implied_volatility: None,
+            scenario_pnl: None,
+            cross_gamma: None,
+            vanna: None,
+            volga: None,
+            fx_delta: None,
+            fx_gamma: None,
+            fx_vega: None,
+            quanto_correlation: None,
+            curvature_up_pnl: None,
+            curvature_down_pnl: None,
             cashflows: None,
             representation_currency: None,
         }
@@ -75,7 +109,7 @@ impl Default for CalculationResult {
 
 impl std::fmt::Debug for CalculationResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f,"")?;
+        writeln!(f)?;
         if let Some(ref info) = self.instrument_info {
             writeln!(f, " * instrument  {:?}", info)?;
         }
@@ -95,18 +129,18 @@ impl std::fmt::Debug for CalculationResult {
             for (currency, value) in exposure {
                 write!(f, "        {}: ", currency)?;
                 write_number_with_commas(f, *value)?;
-                writeln!(f, "")?;
+                writeln!(f)?;
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
         if let Some(ref delta) = self.delta {
             writeln!(f, " * delta: ")?;
             for (key, value) in delta {
                 write!(f, "        {}: ", key)?;
                 write_number_with_commas(f, *value)?;
-                writeln!(f, "")?;
+                writeln!(f)?;
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
         // Similar formatting for gamma, vega, vega_structure, theta, div_delta, div_structure, rho, rho_structure
         if let Some(ref gamma) = self.gamma {
@@ -114,26 +148,26 @@ impl std::fmt::Debug for CalculationResult {
             for (key, value) in gamma {
                 write!(f, "        {}: ", key)?;
                 write_number_with_commas(f, *value)?;
-                writeln!(f, "")?;
+                writeln!(f)?;
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
 
         if let Some(ref theta) = self.theta {
             write!(f, " * theta: ")?;
             write_number_with_commas(f, *theta)?;
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
-        writeln!(f, "")?;
+        writeln!(f)?;
 
         if let Some(ref vega) = self.vega {
             writeln!(f, " * vega: ")?;
             for (key, value) in vega {
                 write!(f, "        {}: ", key)?;
                 write_number_with_commas(f, *value)?;
-                writeln!(f, "")?;
+                writeln!(f)?;
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
 
         if let Some(ref vega_structure) = self.vega_strucure {
@@ -148,9 +182,9 @@ impl std::fmt::Debug for CalculationResult {
                     write_number_with_commas(f, *v)?;
                     write!(f, " | ")?;
                 }
-                writeln!(f, "")?;
+                writeln!(f)?;
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
 
         if let Some(ref rho) = self.rho {
@@ -158,9 +192,9 @@ impl std::fmt::Debug for CalculationResult {
             for (key, value) in rho {
                 write!(f, "        {}: ", key)?;
                 write_number_with_commas(f, *value)?;
-                writeln!(f, "")?;
+                writeln!(f)?;
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
 
         if let Some(ref rho_structure) = self.rho_structure {
@@ -175,9 +209,9 @@ impl std::fmt::Debug for CalculationResult {
                     write_number_with_commas(f, *v)?;
                     write!(f, " | ")?;
                 }
-                writeln!(f, "")?;
+                writeln!(f)?;
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
 
         if let Some(div_delta) = self.div_delta.as_ref() {
@@ -185,9 +219,9 @@ impl std::fmt::Debug for CalculationResult {
             for (key, value) in div_delta {
                 write!(f, "        {}: ", key)?;
                 write_number_with_commas(f, *value)?;
-                writeln!(f, "")?;
+                writeln!(f)?;
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
 
         if let Some(div_structure) = self.div_structure.as_ref() {
@@ -202,9 +236,9 @@ impl std::fmt::Debug for CalculationResult {
                     write_number_with_commas(f, *v)?;
                     write!(f, " | ")?;
                 }
-                writeln!(f, "")?;
+                writeln!(f)?;
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
 
         if let Some(vega_matrix) = self.vega_matrix.as_ref() {
@@ -224,11 +258,65 @@ impl std::fmt::Debug for CalculationResult {
                         let formatted_number = format!("{:9}", formatted_number(*element));
                         write!(f, "{} | ", formatted_number)?;
                     }       
-                    writeln!(f, "")?;
+                    writeln!(f)?;
                     writeln!(f, "{}", under_line)?;
                 }
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
+        }
+        if let Some(implied_volatility) = self.implied_volatility {
+            writeln!(f, " * implied_volatility: {:.6}\n", implied_volatility)?;
+        }
+        if let Some(scenario_pnl) = self.scenario_pnl.as_ref() {
+            writeln!(f, " * scenario_pnl: ")?;
+            for (key, value) in scenario_pnl {
+                write!(f, "        {}: ", key)?;
+                write_number_with_commas(f, *value)?;
+                writeln!(f)?;
+            }
+            writeln!(f)?;
+        }
+        for (name, matrix) in [
+            ("cross_gamma", self.cross_gamma.as_ref()),
+            ("vanna", self.vanna.as_ref()),
+            ("quanto_correlation", self.quanto_correlation.as_ref()),
+        ] {
+            if let Some(matrix) = matrix {
+                writeln!(f, " * {}: ", name)?;
+                for (key1, row) in matrix {
+                    for (key2, value) in row {
+                        write!(f, "        ({}, {}): ", key1, key2)?;
+                        write_number_with_commas(f, *value)?;
+                        writeln!(f)?;
+                    }
+                }
+                writeln!(f)?;
+            }
+        }
+        for (name, sensitivity) in [
+            ("volga", self.volga.as_ref()),
+            ("fx_delta", self.fx_delta.as_ref()),
+            ("fx_gamma", self.fx_gamma.as_ref()),
+            ("fx_vega", self.fx_vega.as_ref()),
+            ("curvature_up_pnl", self.curvature_up_pnl.as_ref()),
+            ("curvature_down_pnl", self.curvature_down_pnl.as_ref()),
+        ] {
+            if let Some(sensitivity) = sensitivity {
+                writeln!(f, " * {}: ", name)?;
+                for (key, value) in sensitivity {
+                    write!(f, "        {}: ", key)?;
+                    write_number_with_commas(f, *value)?;
+                    writeln!(f)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        if let Some(bond_analytics) = self.bond_analytics.as_ref() {
+            writeln!(f, " * bond_analytics: ")?;
+            for (key, value) in bond_analytics {
+                writeln!(f, "        {}: {:?}", key, value)?;
+            }
+            writeln!(f)?;
         }
         if let Some(ref currency) = self.representation_currency {
             writeln!(f, " * representation_currency: {:?}", currency)?;
@@ -261,6 +349,18 @@ impl CalculationResult {
             rho: None,
             rho_structure: None,
             theta_day: None,
+            bond_analytics: None,
+            implied_volatility: None,
+            scenario_pnl: None,
+            cross_gamma: None,
+            vanna: None,
+            volga: None,
+            fx_delta: None,
+            fx_gamma: None,
+            fx_vega: None,
+            quanto_correlation: None,
+            curvature_up_pnl: None,
+            curvature_down_pnl: None,
             cashflows: None,
             representation_currency: Some(representation_currency),
         }
@@ -421,6 +521,76 @@ impl CalculationResult {
         self.theta = Some(theta);
     }
 
+    pub fn set_single_bond_analytics(&mut self, bond_code: &str, analytics: BondAnalytics) {
+        match &mut self.bond_analytics {
+            None => {
+                let mut bond_analytics = HashMap::new();
+                bond_analytics.insert(bond_code.to_string(), analytics);
+                self.bond_analytics = Some(bond_analytics);
+            },
+            Some(bond_analytics) => {
+                bond_analytics.insert(bond_code.to_string(), analytics);
+            },
+        }
+    }
+
+    pub fn set_implied_volatility(&mut self, implied_volatility: Real) {
+        self.implied_volatility = Some(implied_volatility);
+    }
+
+    /// set the cross gamma of the pair (code1, code2) and (code2, code1)
+    pub fn set_single_cross_gamma(&mut self, code1: &str, code2: &str, v: Real) {
+        let cross_gamma = self.cross_gamma.get_or_insert_with(HashMap::new);
+        cross_gamma.entry(code1.to_string()).or_default().insert(code2.to_string(), v);
+        cross_gamma.entry(code2.to_string()).or_default().insert(code1.to_string(), v);
+    }
+
+    pub fn set_single_vanna(&mut self, und_code: &str, vol_code: &str, v: Real) {
+        self.vanna.get_or_insert_with(HashMap::new)
+            .entry(und_code.to_string()).or_default()
+            .insert(vol_code.to_string(), v);
+    }
+
+    pub fn set_single_volga(&mut self, vol_code: &str, v: Real) {
+        self.volga.get_or_insert_with(HashMap::new).insert(vol_code.to_string(), v);
+    }
+
+    pub fn set_single_fx_delta(&mut self, fx_code: &str, v: Real) {
+        self.fx_delta.get_or_insert_with(HashMap::new).insert(fx_code.to_string(), v);
+    }
+
+    pub fn set_single_fx_gamma(&mut self, fx_code: &str, v: Real) {
+        self.fx_gamma.get_or_insert_with(HashMap::new).insert(fx_code.to_string(), v);
+    }
+
+    pub fn set_single_fx_vega(&mut self, fx_code: &str, v: Real) {
+        self.fx_vega.get_or_insert_with(HashMap::new).insert(fx_code.to_string(), v);
+    }
+
+    pub fn set_single_quanto_correlation(&mut self, und_code: &str, fx_code: &str, v: Real) {
+        self.quanto_correlation.get_or_insert_with(HashMap::new)
+            .entry(und_code.to_string()).or_default()
+            .insert(fx_code.to_string(), v);
+    }
+
+    pub fn set_single_curvature_pnl(&mut self, risk_factor: &str, up: Real, down: Real) {
+        self.curvature_up_pnl.get_or_insert_with(HashMap::new).insert(risk_factor.to_string(), up);
+        self.curvature_down_pnl.get_or_insert_with(HashMap::new).insert(risk_factor.to_string(), down);
+    }
+
+    pub fn set_single_scenario_pnl(&mut self, scenario_name: &str, v: Real) {
+        match &mut self.scenario_pnl {
+            None => {
+                let mut scenario_pnl = HashMap::new();
+                scenario_pnl.insert(scenario_name.to_string(), v);
+                self.scenario_pnl = Some(scenario_pnl);
+            },
+            Some(scenario_pnl) => {
+                scenario_pnl.insert(scenario_name.to_string(), v);
+            },
+        }
+    }
+
     pub fn set_cashflows(&mut self, cashflows: HashMap<OffsetDateTime, Real>) {
         self.cashflows = Some(cashflows);
     }
@@ -481,6 +651,65 @@ impl CalculationResult {
         self.div_structure.as_ref()
     }
 
+    pub fn get_bond_analytics(&self) -> Option<&HashMap<String, BondAnalytics>> {
+        self.bond_analytics.as_ref()
+    }
+
+    pub fn get_implied_volatility(&self) -> Option<Real> {
+        self.implied_volatility
+    }
+
+    pub fn get_scenario_pnl(&self) -> Option<&HashMap<String, Real>> {
+        self.scenario_pnl.as_ref()
+    }
+
+    /// d^2 value / (dS1 dS2) * (S1 * DELTA_PNL_UNIT) * (S2 * DELTA_PNL_UNIT), i.e.,
+    /// the change of the delta on code1 for a DELTA_PNL_UNIT relative move of code2.
+    /// The codes are underlying codes or fx codes (e.g., "USDKRW")
+    pub fn get_cross_gamma(&self) -> Option<&HashMap<String, HashMap<String, Real>>> {
+        self.cross_gamma.as_ref()
+    }
+
+    /// change of the delta on the underlying for a VEGA_PNL_UNIT shift of the volatility
+    pub fn get_vanna(&self) -> Option<&HashMap<String, HashMap<String, Real>>> {
+        self.vanna.as_ref()
+    }
+
+    /// change of the vega for a VEGA_PNL_UNIT shift of the volatility
+    pub fn get_volga(&self) -> Option<&HashMap<String, Real>> {
+        self.volga.as_ref()
+    }
+
+    /// dV/dFX * FX * DELTA_PNL_UNIT for the fx rates used in pricing (e.g., FxFutures, CRS)
+    pub fn get_fx_delta(&self) -> Option<&HashMap<String, Real>> {
+        self.fx_delta.as_ref()
+    }
+
+    /// 0.5 * d^2V/dFX^2 * (FX * DELTA_PNL_UNIT)^2, the same convention as gamma
+    pub fn get_fx_gamma(&self) -> Option<&HashMap<String, Real>> {
+        self.fx_gamma.as_ref()
+    }
+
+    /// change of the value for a VEGA_PNL_UNIT shift of the fx volatility in the quanto adjustment
+    pub fn get_fx_vega(&self) -> Option<&HashMap<String, Real>> {
+        self.fx_vega.as_ref()
+    }
+
+    /// change of the value for a CORRELATION_PNL_UNIT shift of the quanto correlation
+    pub fn get_quanto_correlation(&self) -> Option<&HashMap<String, HashMap<String, Real>>> {
+        self.quanto_correlation.as_ref()
+    }
+
+    /// value change under the up scenario of each curvature risk factor
+    pub fn get_curvature_up_pnl(&self) -> Option<&HashMap<String, Real>> {
+        self.curvature_up_pnl.as_ref()
+    }
+
+    /// value change under the down scenario of each curvature risk factor
+    pub fn get_curvature_down_pnl(&self) -> Option<&HashMap<String, Real>> {
+        self.curvature_down_pnl.as_ref()
+    }
+
     pub fn set_representation_currency(&mut self, currency: Currency) {
         self.representation_currency = Some(currency);
     }
@@ -627,6 +856,28 @@ impl CalculationResult {
             None => None,
         };
         let theta_day: Option<Integer> = self.theta_day.clone();
+        // bond analytics are quoted per unit face value, so they do not depend on the currency
+        let bond_analytics: Option<HashMap<String, BondAnalytics>> = self.bond_analytics.clone();
+        let implied_volatility: Option<Real> = self.implied_volatility;
+        let scenario_pnl: Option<HashMap<String, Real>> = self.scenario_pnl.as_ref()
+            .map(|pnl| pnl.iter().map(|(name, v)| (name.clone(), v * fx_rate)).collect());
+        let convert_matrix = |matrix: &HashMap<String, HashMap<String, Real>>| -> HashMap<String, HashMap<String, Real>> {
+            matrix.iter()
+                .map(|(key, row)| (key.clone(), row.iter().map(|(k, v)| (k.clone(), v * fx_rate)).collect()))
+                .collect()
+        };
+        let cross_gamma = self.cross_gamma.as_ref().map(convert_matrix);
+        let vanna = self.vanna.as_ref().map(convert_matrix);
+        let convert_map = |map: &HashMap<String, Real>| -> HashMap<String, Real> {
+            map.iter().map(|(code, v)| (code.clone(), v * fx_rate)).collect()
+        };
+        let volga = self.volga.as_ref().map(convert_map);
+        let fx_delta = self.fx_delta.as_ref().map(convert_map);
+        let fx_gamma = self.fx_gamma.as_ref().map(convert_map);
+        let fx_vega = self.fx_vega.as_ref().map(convert_map);
+        let quanto_correlation = self.quanto_correlation.as_ref().map(convert_matrix);
+        let curvature_up_pnl = self.curvature_up_pnl.as_ref().map(convert_map);
+        let curvature_down_pnl = self.curvature_down_pnl.as_ref().map(convert_map);
         let cashflows: Option<HashMap<OffsetDateTime, Real>> = self.cashflows.clone();
         let representation_currency: Option<Currency> = Some(currency);
 
@@ -647,6 +898,18 @@ impl CalculationResult {
             rho,
             rho_structure,
             theta_day,
+            bond_analytics,
+            implied_volatility,
+            scenario_pnl,
+            cross_gamma,
+            vanna,
+            volga,
+            fx_delta,
+            fx_gamma,
+            fx_vega,
+            quanto_correlation,
+            curvature_up_pnl,
+            curvature_down_pnl,
             cashflows,
             representation_currency,
         };
@@ -717,6 +980,12 @@ mod tests {
         result.set_npv(NpvResult::new_from_npv(100.0));
         
         result.set_single_delta(&"KOSPI200".to_string(), 0.1);
+        result.set_single_cross_gamma("KOSPI200", "USDKRW", 0.02);
+        result.set_single_vanna("KOSPI200", "KOSPI200", -0.3);
+        result.set_single_volga("KOSPI200", 0.05);
+        assert_eq!(result.get_cross_gamma().unwrap()["USDKRW"]["KOSPI200"], 0.02);
+        result.set_single_fx_delta("USDKRW", 1.5);
+        result.set_single_quanto_correlation("KOSPI200", "USDKRW", -0.1);
 
 
         let serialized = serde_json::to_string_pretty(&result).unwrap();
@@ -1,6 +1,8 @@
 use tracing::{info, Level, span, warn, debug};
 use crate::instruments::instrument_info::InstrumentInfo;
 use crate::parameters::volatilities::local_volatility_surface::LocalVolatilitySurface;
+use crate::parameters::volatilities::svi_volatility_surface::SviVolatilitySurface;
+use crate::parameters::volatilities::heston_volatility::{HestonParameters, HestonVolatility};
 use crate::parameters::{
     discrete_ratio_dividend::DiscreteRatioDividend,
     zero_curve::ZeroCurve,
@@ -15,6 +17,7 @@ use crate::instrument::{Instrument, Instruments, InstrumentTrait};
 use crate::definitions::{
     Real, Time, 
     DELTA_PNL_UNIT, VEGA_PNL_UNIT, DIV_PNL_UNIT, RHO_PNL_UNIT, THETA_PNL_UNIT,
+    CORRELATION_PNL_UNIT,
 };
 use crate::currency::{Currency, FxCode};
 
@@ -25,6 +28,7 @@ use crate::data::{
     daily_value_data::DailyValueData,
 };
 use crate::util::format_duration;
+use crate::enums::{ShockType, VolatilitySurfaceType};
 use crate::utils::string_arithmetic::add_period;
 use crate::pricing_engines::{
     pricer::{Pricer, PricerTrait},
@@ -33,6 +37,9 @@ use crate::pricing_engines::{
     calculation_configuration::CalculationConfiguration,
     npv_result::NpvResult,
     pricer_factory::PricerFactory,
+    scenario::Scenario,
+    engine_generator::resolve_fx_rate,
+    market_data_update::{fx_rate_depends_on, MarketDataUpdate},
 };
 use crate::time::{
     calendar_trait::CalendarTrait,
@@ -41,15 +48,143 @@ use crate::time::{
 
 use std::{
     time::Instant,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     rc::Rc,
     cell::RefCell,
 };
 use std::sync::Arc;
+use rayon::prelude::*;
 use anyhow::{Result, Context, anyhow, bail};
 use ndarray::Array2;
 use time::{OffsetDateTime, Duration};
 
+/// market data overwritten by Engine::apply_scenario which is put back by Engine::restore_scenario
+struct ScenarioBackup {
+    name: String,
+    fxs: HashMap<FxCode, Real>,
+    equities: HashMap<String, Real>,
+    zero_curves: HashMap<String, ZeroCurve>,
+    volatilities: HashMap<String, Volatility>,
+    dividends: HashMap<String, DiscreteRatioDividend>,
+    fx_volatilities: HashMap<FxCode, Volatility>,
+    quantos: HashMap<(String, FxCode), Quanto>,
+}
+
+impl ScenarioBackup {
+    fn new(name: &str) -> ScenarioBackup {
+        ScenarioBackup {
+            name: name.to_string(),
+            fxs: HashMap::new(),
+            equities: HashMap::new(),
+            zero_curves: HashMap::new(),
+            volatilities: HashMap::new(),
+            dividends: HashMap::new(),
+            fx_volatilities: HashMap::new(),
+            quantos: HashMap::new(),
+        }
+    }
+}
+
+/// a bump of the market data held by the engine in the revaluations for the greeks
+#[derive(Debug, Clone)]
+enum Bump {
+    /// equity price * (1 + ratio)
+    Equity { code: String, ratio: Real },
+    /// fx rate (e.g., "USDKRW") * (1 + ratio) where the reciprocal rate is moved together
+    Fx { code: String, ratio: Real },
+    /// zero rates on (start, end] (times from the evaluation date) + value
+    ZeroCurve { code: String, start: Option<Time>, end: Option<Time>, value: Real },
+    /// volatilities on (start, end] x (left_moneyness, right_moneyness] + value
+    Volatility {
+        code: String,
+        start: Option<Time>,
+        end: Option<Time>,
+        left_moneyness: Option<Real>,
+        right_moneyness: Option<Real>,
+        value: Real,
+    },
+    /// dividends on (start, end] + value
+    Dividend { code: String, start: Option<OffsetDateTime>, end: Option<OffsetDateTime>, value: Real },
+    /// fx volatility of the quantos (e.g., "USDKRW") + value
+    FxVolatility { code: String, value: Real },
+    /// correlation of the quanto of (und_code, fx_code) + value
+    QuantoCorrelation { und_code: String, fx_code: String, value: Real },
+}
+
+/// npvs of the instruments where the bumps are applied to the unbumped market data.
+/// The jobs do not depend on each other, so that they can be evaluated on replicas of the engine in parallel.
+#[derive(Debug, Clone)]
+struct BumpJob {
+    inst_codes: Vec<String>,
+    bumps: Vec<Bump>,
+}
+
+impl BumpJob {
+    fn new(inst_codes: Vec<String>, bumps: Vec<Bump>) -> BumpJob {
+        BumpJob { inst_codes, bumps }
+    }
+}
+
+/// the data given to Engine::with_parameter_data, kept for the replicas of the engine
+#[derive(Clone)]
+struct ParameterData {
+    fx_data: Arc<HashMap<FxCode, ValueData>>,
+    stock_data: Arc<HashMap<String, ValueData>>,
+    curve_data: Arc<HashMap<String, VectorData>>,
+    dividend_data: Arc<HashMap<String, VectorData>>,
+    equity_constant_volatility_data: Arc<HashMap<String, ValueData>>,
+    equity_volatility_surface_data: Arc<HashMap<String, SurfaceData>>,
+    fx_constant_volatility_data: Arc<HashMap<FxCode, ValueData>>,
+    quanto_correlation_data: Arc<HashMap<(String, FxCode), ValueData>>,
+    past_daily_value_data: Arc<HashMap<String, DailyValueData>>,
+}
+
+/// The inputs of an engine and the scenario applied to it, shared by Arc with the replicas of the engine
+/// in the parallel bumps. Engine is not Send since the parameters are shared with the pricers by Rc<RefCell<..>>,
+/// so a replica builds its own parameters from the market data and applies the scenario again,
+/// i.e., it is the engine as it is when the snapshot is taken. The snapshot is never changed:
+/// it is taken from the engine for each set of bump jobs, and the jobs bump the parameters of the replicas.
+struct EngineSnapshot {
+    engine_id: usize,
+    calculation_configuration: CalculationConfiguration,
+    evaluation_datetime: OffsetDateTime,
+    match_parameter: MatchParameter,
+    instruments: Vec<Instrument>,
+    option_prices: Arc<HashMap<String, Real>>,
+    parameter_data: ParameterData,
+    scenario: Option<Scenario>,
+}
+
+impl EngineSnapshot {
+    fn build(&self) -> Result<Engine> {
+        let data = self.parameter_data.clone();
+        let mut engine = Engine::builder(
+            self.engine_id,
+            self.calculation_configuration.clone(),
+            self.evaluation_datetime,
+            self.match_parameter.clone(),
+        )
+            .with_instruments(self.instruments.clone())?
+            .with_option_prices(self.option_prices.clone())
+            .with_parameter_data(
+                data.fx_data,
+                data.stock_data,
+                data.curve_data,
+                data.dividend_data,
+                data.equity_constant_volatility_data,
+                data.equity_volatility_surface_data,
+                data.fx_constant_volatility_data,
+                data.quanto_correlation_data,
+                data.past_daily_value_data,
+            )?;
+        engine.initialize_pricers()?;
+        if let Some(scenario) = self.scenario.as_ref() {
+            engine.apply_scenario(scenario)?;
+        }
+        Ok(engine)
+    }
+}
+
 /// Engine typically handles a bunch of instruments and calculate the pricing of the instruments.
 /// Therefore, the result of calculations is a hashmap with the key being the code of the instrument
 /// Engine is a struct that holds the calculation results of the instruments
@@ -68,6 +203,8 @@ pub struct Engine {
     volatilities: HashMap<String, Rc<RefCell<Volatility>>>,
     quantos: HashMap<(String, FxCode), Rc<RefCell<Quanto>>>,
     past_daily_close_prices: HashMap<String, Rc<DailyClosePrice>>,
+    // market prices of instruments (code -> price), e.g., option prices for implied volatilities
+    option_prices: Arc<HashMap<String, Real>>,
     // instruments
     instruments: Instruments, // all instruments
     pricers: HashMap<String, Pricer>, // pricers for each instrument
@@ -75,6 +212,12 @@ pub struct Engine {
     // e.g., if we calcualte a delta of a single stock, we do not need calculate all instruments
     instruments_in_action: Vec<Rc<Instrument>>, 
     match_parameter: Rc<MatchParameter>, // this must be cloned 
+    // market data before the scenario currently applied
+    scenario_backup: Option<ScenarioBackup>,
+    // data of the parameters for the replicas of the engine in the parallel bumps
+    parameter_data: Option<ParameterData>,
+    // the scenario currently applied
+    applied_scenario: Option<Scenario>,
 }
 
 impl Engine {
@@ -97,13 +240,23 @@ impl Engine {
             volatilities: HashMap::new(),
             quantos: HashMap::new(),
             past_daily_close_prices: HashMap::new(),
+            option_prices: Arc::new(HashMap::new()),
             instruments: Instruments::default(),
             instruments_in_action: vec![],
             pricers: HashMap::new(),
             match_parameter: Rc::new(match_parameter),
+            scenario_backup: None,
+            parameter_data: None,
+            applied_scenario: None,
         }
     }
 
+    /// option code -> market price (npv, not considering unit_notional) used in implied volatility calculation
+    pub fn with_option_prices(mut self, option_prices: Arc<HashMap<String, Real>>) -> Engine {
+        self.option_prices = option_prices;
+        self
+    }
+
     pub fn with_parameter_data(
         mut self,
         fx_data: Arc<HashMap<FxCode, ValueData>>,
@@ -116,6 +269,17 @@ impl Engine {
         quanto_correlation_data: Arc<HashMap<(String, FxCode), ValueData>>,
         past_daily_value_data: Arc<HashMap<String, DailyValueData>>,
     ) -> Result<Engine> {        
+        let parameter_data = ParameterData {
+            fx_data: fx_data.clone(),
+            stock_data: stock_data.clone(),
+            curve_data: curve_data.clone(),
+            dividend_data: dividend_data.clone(),
+            equity_constant_volatility_data: equity_constant_volatility_data.clone(),
+            equity_volatility_surface_data: equity_volatility_surface_data.clone(),
+            fx_constant_volatility_data: fx_constant_volatility_data.clone(),
+            quanto_correlation_data: quanto_correlation_data.clone(),
+            past_daily_value_data: past_daily_value_data.clone(),
+        };
         let fx_codes = self.instruments.get_all_fxcodes_for_pricing();
         let mut fxs: HashMap<FxCode, Rc<RefCell<MarketPrice>>> = HashMap::new();
         for fx_code in fx_codes {
@@ -274,112 +438,14 @@ impl Engine {
         let mut volatilities = HashMap::new();
         let all_underlying_codes = self.instruments.get_all_unerlying_codes_requiring_volatility(None);
         for und_code in all_underlying_codes {
-            if equity_constant_volatility_data.contains_key(&und_code) {
-                let data = equity_constant_volatility_data.get(&und_code).unwrap();
-                let vega_matrix_spot_moneyness = self.calculation_configuration.get_vega_matrix_spot_moneyness();
-                let vega_structure_tenors = self.calculation_configuration.get_vega_structure_tenors();
-                let market_price = equities.get(&und_code)
-                    .with_context(|| anyhow!(
-                        "({}:{}) failed to get market price for {}", 
-                        file!(), line!(), und_code))?.clone();
-                let collateral_curve_map = self.match_parameter.get_collateral_curve_map()
-                    .get(&und_code)
-                    .with_context(|| anyhow!(
-                        "({}:{}) failed to get collateral curve map for {} from match_parameter in creating volatility surface",
-                        file!(), line!(), und_code))?;
-                let collateral_curve = zero_curves.get(collateral_curve_map)
-                    .with_context(|| anyhow!(
-                        "({}:{}) failed to get collateral curve for {} in creating volatility surface", 
-                        file!(), line!(), und_code))?.clone();
-                let borrowing_curve_map = self.match_parameter.get_borrowing_curve_map()
-                    .get(&und_code)
-                    .with_context(|| anyhow!(
-                        "({}:{}) failed to get borrowing curve map for {} from match_parameter in creating volatility surface",
-                        file!(), line!(), und_code))?;
-                let borrowing_curve = zero_curves.get(borrowing_curve_map)
-                    .with_context(|| anyhow!(
-                        "({}:{}) failed to get borrowing curve for {} in creating volatility surface\n\
-                        zero curves list:\n {:?}",
-                        file!(), line!(), und_code,
-                        zero_curves.keys().into_iter().map(|s| s.as_str()).collect::<Vec<&str>>().join(" | "),
-                    ))?.clone();
-                let stickyness = self.calculation_configuration.get_stickyness_type();
-                let lv_interpolator = self.calculation_configuration.get_lv_interpolator();
-                let mut lv = LocalVolatilitySurface::initialize(
-                    self.evaluation_date.clone(),
-                    market_price,
-                    collateral_curve,
-                    borrowing_curve,
-                    stickyness,
-                    lv_interpolator,
-                    und_code.clone(),
-                    und_code.clone(),
-                ).with_constant_volatility(
-                    data,
-                    vega_structure_tenors.clone(),
-                    vega_matrix_spot_moneyness.clone(),
-                )?;
-                lv.build()?;
-                let rc = Rc::new(RefCell::new(
-                    Volatility::LocalVolatilitySurface(lv)
-                ));
-                volatilities.insert(und_code.clone(), rc);
-            } else if equity_volatility_surface_data.contains_key(&und_code) {
-                let data = equity_volatility_surface_data.get(&und_code).unwrap();
-                let vega_matrix_spot_moneyness = self.calculation_configuration.get_vega_matrix_spot_moneyness();
-                let vega_structure_tenors = self.calculation_configuration.get_vega_structure_tenors();
-                let market_price = equities.get(&und_code)
-                    .with_context(|| anyhow!(
-                        "({}:{}) failed to get market price for {}", 
-                        file!(), line!(), und_code))?.clone();
-                let collateral_curve_map = self.match_parameter.get_collateral_curve_map()
-                    .get(&und_code)
-                    .with_context(|| anyhow!(
-                        "({}:{}) failed to get collateral curve map for {} from match_parameter in creating volatility surface",
-                        file!(), line!(), und_code))?;
-                let collateral_curve = zero_curves.get(collateral_curve_map)
-                    .with_context(|| anyhow!(
-                        "({}:{}) failed to get collateral curve for {} in creating volatility surface", 
-                        file!(), line!(), und_code))?.clone();
-                let borrowing_curve_map = self.match_parameter.get_borrowing_curve_map()
-                    .get(&und_code)
-                    .with_context(|| anyhow!(
-                        "({}:{}) failed to get borrowing curve map for {} from match_parameter in creating volatility surface",
-                        file!(), line!(), und_code))?;
-                let borrowing_curve = zero_curves.get(borrowing_curve_map)
-                    .with_context(|| anyhow!(
-                        "({}:{}) failed to get borrowing curve for {} in creating volatility surface\n\
-                        zero curves list:\n {:?}",
-                        file!(), line!(), und_code,
-                        zero_curves.keys().into_iter().map(|s| s.as_str()).collect::<Vec<&str>>().join(" | "), 
-                    ))?.clone();
-                let stickyness = self.calculation_configuration.get_stickyness_type();
-                let lv_interpolator = self.calculation_configuration.get_lv_interpolator();
-                let mut lv = LocalVolatilitySurface::initialize(
-                    self.evaluation_date.clone(),
-                    market_price,
-                    collateral_curve,
-                    borrowing_curve,
-                    stickyness,
-                    lv_interpolator,
-                    und_code.clone(),
-                    und_code.clone(),
-                ).with_market_surface(
-                    data,
-                    vega_structure_tenors.clone(),
-                    vega_matrix_spot_moneyness.clone(),
-                )?;
-                lv.build()?;
-                let rc = Rc::new(RefCell::new(
-                    Volatility::LocalVolatilitySurface(lv)
-                ));
-                volatilities.insert(und_code.clone(), rc);
-            } else {
-                bail!(
-                    "({}:{}) failed to get equity volatility data for {}", 
-                    file!(), line!(), und_code
-                );
-            }
+            let volatility = self.build_equity_volatility(
+                &und_code,
+                &equities,
+                &zero_curves,
+                &equity_constant_volatility_data,
+                &equity_volatility_surface_data,
+            )?;
+            volatilities.insert(und_code.clone(), Rc::new(RefCell::new(volatility)));
         }
         // 
         // fx volatility parameter
@@ -447,6 +513,7 @@ impl Engine {
         self.volatilities = volatilities;
         self.quantos = quantos;
         self.past_daily_close_prices = past_daily_close_prices;
+        self.parameter_data = Some(parameter_data);
 
         // add marketprice_observers
         for (_, fx) in self.fxs.iter() {
@@ -467,6 +534,156 @@ impl Engine {
 
         Ok(self)
     }
+
+    /// The volatility of und_code from the constant volatility data or, if not given, the surface data
+    /// as in with_parameter_data. The volatility is linked to the given equities and zero_curves.
+    fn build_equity_volatility(
+        &self,
+        und_code: &String,
+        equities: &HashMap<String, Rc<RefCell<MarketPrice>>>,
+        zero_curves: &HashMap<String, Rc<RefCell<ZeroCurve>>>,
+        equity_constant_volatility_data: &HashMap<String, ValueData>,
+        equity_volatility_surface_data: &HashMap<String, SurfaceData>,
+    ) -> Result<Volatility> {
+        if equity_constant_volatility_data.contains_key(und_code) {
+            let data = equity_constant_volatility_data.get(und_code).unwrap();
+            let vega_matrix_spot_moneyness = self.calculation_configuration.get_vega_matrix_spot_moneyness();
+            let vega_structure_tenors = self.calculation_configuration.get_vega_structure_tenors();
+            let market_price = equities.get(und_code)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to get market price for {}", 
+                    file!(), line!(), und_code))?.clone();
+            let collateral_curve_map = self.match_parameter.get_collateral_curve_map()
+                .get(und_code)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to get collateral curve map for {} from match_parameter in creating volatility surface",
+                    file!(), line!(), und_code))?;
+            let collateral_curve = zero_curves.get(collateral_curve_map)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to get collateral curve for {} in creating volatility surface", 
+                    file!(), line!(), und_code))?.clone();
+            let borrowing_curve_map = self.match_parameter.get_borrowing_curve_map()
+                .get(und_code)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to get borrowing curve map for {} from match_parameter in creating volatility surface",
+                    file!(), line!(), und_code))?;
+            let borrowing_curve = zero_curves.get(borrowing_curve_map)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to get borrowing curve for {} in creating volatility surface\n\
+                    zero curves list:\n {:?}",
+                    file!(), line!(), und_code,
+                    zero_curves.keys().into_iter().map(|s| s.as_str()).collect::<Vec<&str>>().join(" | "),
+                ))?.clone();
+            let stickyness = self.calculation_configuration.get_stickyness_type();
+            let lv_interpolator = self.calculation_configuration.get_lv_interpolator();
+            let mut lv = LocalVolatilitySurface::initialize(
+                self.evaluation_date.clone(),
+                market_price,
+                collateral_curve,
+                borrowing_curve,
+                stickyness,
+                lv_interpolator,
+                und_code.clone(),
+                und_code.clone(),
+            ).with_constant_volatility(
+                data,
+                vega_structure_tenors.clone(),
+                vega_matrix_spot_moneyness.clone(),
+            )?;
+            lv.build()?;
+            Ok(Volatility::LocalVolatilitySurface(lv))
+        } else if equity_volatility_surface_data.contains_key(und_code) {
+            let data = equity_volatility_surface_data.get(und_code).unwrap();
+            let vega_matrix_spot_moneyness = self.calculation_configuration.get_vega_matrix_spot_moneyness();
+            let vega_structure_tenors = self.calculation_configuration.get_vega_structure_tenors();
+            let market_price = equities.get(und_code)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to get market price for {}", 
+                    file!(), line!(), und_code))?.clone();
+            let collateral_curve_map = self.match_parameter.get_collateral_curve_map()
+                .get(und_code)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to get collateral curve map for {} from match_parameter in creating volatility surface",
+                    file!(), line!(), und_code))?;
+            let collateral_curve = zero_curves.get(collateral_curve_map)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to get collateral curve for {} in creating volatility surface", 
+                    file!(), line!(), und_code))?.clone();
+            let borrowing_curve_map = self.match_parameter.get_borrowing_curve_map()
+                .get(und_code)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to get borrowing curve map for {} from match_parameter in creating volatility surface",
+                    file!(), line!(), und_code))?;
+            let borrowing_curve = zero_curves.get(borrowing_curve_map)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to get borrowing curve for {} in creating volatility surface\n\
+                    zero curves list:\n {:?}",
+                    file!(), line!(), und_code,
+                    zero_curves.keys().into_iter().map(|s| s.as_str()).collect::<Vec<&str>>().join(" | "), 
+                ))?.clone();
+            let volatility = match self.calculation_configuration.get_volatility_surface_type() {
+                VolatilitySurfaceType::Interpolated => {
+                    let stickyness = self.calculation_configuration.get_stickyness_type();
+                    let lv_interpolator = self.calculation_configuration.get_lv_interpolator();
+                    let mut lv = LocalVolatilitySurface::initialize(
+                        self.evaluation_date.clone(),
+                        market_price,
+                        collateral_curve,
+                        borrowing_curve,
+                        stickyness,
+                        lv_interpolator,
+                        und_code.clone(),
+                        und_code.clone(),
+                    ).with_market_surface(
+                        data,
+                        vega_structure_tenors.clone(),
+                        vega_matrix_spot_moneyness.clone(),
+                    )?;
+                    lv.build()?;
+                    Volatility::LocalVolatilitySurface(lv)
+                },
+                surface_type @ (VolatilitySurfaceType::RawSvi | VolatilitySurfaceType::Ssvi) => {
+                    let svi = SviVolatilitySurface::initialize(
+                        self.evaluation_date.clone(),
+                        market_price,
+                        collateral_curve,
+                        borrowing_curve,
+                        surface_type,
+                        und_code.clone(),
+                        und_code.clone(),
+                    ).with_market_surface(
+                        data,
+                        vega_structure_tenors.clone(),
+                        vega_matrix_spot_moneyness.clone(),
+                    )?;
+                    Volatility::SviVolatilitySurface(svi)
+                },
+                VolatilitySurfaceType::Heston => {
+                    let heston = HestonVolatility::initialize(
+                        HestonParameters::default(),
+                        self.evaluation_date.clone(),
+                        market_price,
+                        collateral_curve,
+                        borrowing_curve,
+                        und_code.clone(),
+                        und_code.clone(),
+                    ).with_market_surface(
+                        data,
+                        vega_structure_tenors.clone(),
+                        vega_matrix_spot_moneyness.clone(),
+                    )?;
+                    Volatility::HestonVolatility(heston)
+                },
+            };
+            Ok(volatility)
+        } else {
+            bail!(
+                "({}:{}) failed to get equity volatility data for {}", 
+                file!(), line!(), und_code
+            );
+        }
+    }
+
     // initialize CalculationResult for each instrument
     pub fn with_instruments(
         mut self, 
@@ -718,6 +935,82 @@ impl Engine {
         Ok(())
     }
 
+    /// Bond analytics (yield, duration, convexity, spreads) for bonds and the underlying bonds of KTBF.
+    /// The measures are per unit face value, so they are not scaled by unit_notional
+    pub fn set_bond_analytics(&mut self) -> Result<()> {
+        let yield_convention = self.calculation_configuration.get_yield_convention();
+        let target_types = ["Bond", "KTBF"];
+        for inst in &self.instruments_in_action {
+            if !target_types.contains(&inst.get_type_name()) {
+                continue;
+            }
+            let inst_code = inst.get_code();
+            let pricer = self.pricers.get(inst_code)
+                .ok_or_else(|| anyhow!(
+                    "({}:{}) failed to get pricer for {} in getting bond analytics\n{}",
+                    file!(), line!(), inst_code, self.msg_tag,
+                ))?;
+
+            let bond_analytics = pricer.bond_analytics(inst, yield_convention)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to get bond analytics for {} ({})\n{}",
+                    file!(), line!(), inst_code, inst.get_type_name(), self.msg_tag,
+                ))?;
+
+            let mut result = self.calculation_results.get(inst_code)
+                .ok_or_else(|| anyhow!(
+                    "({}:{}) result is not set for {} in getting bond analytics\n{}",
+                    file!(), line!(), inst_code, self.msg_tag,
+                ))?
+                .borrow_mut();
+            for (bond_code, analytics) in bond_analytics.iter() {
+                result.set_single_bond_analytics(bond_code, *analytics);
+            }
+        }
+        Ok(())
+    }
+
+    /// Implied volatilities of the vanilla options whose prices are given in option_prices.
+    /// Options without a price are skipped.
+    pub fn set_implied_volatilities(&mut self) -> Result<()> {
+        for inst in &self.instruments_in_action {
+            if !matches!(inst.as_ref(), Instrument::VanillaOption(_)) {
+                continue;
+            }
+            let inst_code = inst.get_code();
+            let price = match self.option_prices.get(inst_code) {
+                Some(price) => *price,
+                None => {
+                    warn!(
+                        "({}:{}) no option price for {} ({}) in implied volatility calculation\n{}",
+                        file!(), line!(), inst.get_name(), inst_code, self.msg_tag,
+                    );
+                    continue;
+                },
+            };
+            let pricer = self.pricers.get(inst_code)
+                .ok_or_else(|| anyhow!(
+                    "({}:{}) failed to get pricer for {} in getting implied volatility\n{}",
+                    file!(), line!(), inst_code, self.msg_tag,
+                ))?;
+
+            let implied_volatility = pricer.implied_volatility(inst, price)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to get implied volatility for {} ({})\n{}",
+                    file!(), line!(), inst_code, inst.get_type_name(), self.msg_tag,
+                ))?;
+
+            self.calculation_results.get(inst_code)
+                .ok_or_else(|| anyhow!(
+                    "({}:{}) result is not set for {} in getting implied volatility\n{}",
+                    file!(), line!(), inst_code, self.msg_tag,
+                ))?
+                .borrow_mut()
+                .set_implied_volatility(implied_volatility);
+        }
+        Ok(())
+    }
+
     /// Set the value of the instruments which means npv * unit_notional
     pub fn set_values(&mut self) -> Result<()> {
         for (_code, result) in self.calculation_results.iter() {
@@ -770,629 +1063,785 @@ impl Engine {
     }
     
     pub fn set_delta_gamma(&mut self) -> Result<()> {
-        self.reset_instruments_in_action();
-
-        let all_underlying_codes = self.instruments.get_all_underlying_codes();
         let delta_bump_ratio = self.calculation_configuration.get_delta_bump_ratio();
-
-        let mut delta_up_map: HashMap::<String, Real>;
-        let mut delta_down_map: HashMap::<String, Real>;
-        
-        let mut delta_up: Real;
-        let mut delta_down: Real;
-        let mut delta: Real;
-        let mut gamma: Real;
-        let mut mid: Real;
-        let mut original_price: Real;
-
-        let up_bump = 1.0 + delta_bump_ratio;
-        let down_bump = 1.0 - delta_bump_ratio;
         let exclude_type = vec!["Stock", "Futures"];
-        let exclude_type_clone = exclude_type.clone();
-        for und_code in all_underlying_codes.iter() {
-            self.instruments_in_action = self.instruments
-                .instruments_with_underlying(
-                    und_code,
-                    Some(exclude_type_clone.clone()),
-                );
-            
-            if self.instruments_in_action.is_empty() {
+        // (underlying code, instruments) with the up and down bump jobs
+        let mut targets = Vec::new();
+        let mut jobs = Vec::new();
+        for und_code in self.instruments.get_all_underlying_codes() {
+            let insts = self.instruments.instruments_with_underlying(und_code, Some(exclude_type.clone()));
+            if insts.is_empty() {
                 continue;
             }
-
-            original_price = self.equities
-                .get(*und_code)
-                .ok_or_else(|| anyhow!("there is no equity {}", und_code))?
-                .borrow()
-                .get_value();
-
-            // set instruments that needs to be calculated
-            {
-                let mut equity = (*self.equities
-                    .get(*und_code)
-                    .ok_or_else(|| anyhow!(
-                        "({}:{}) there is no stock {}", 
-                        file!(), line!(),
-                        und_code
-                    ))?).as_ref().borrow_mut();
-
-                *equity *= up_bump;
+            let inst_codes = self.instruments.get_all_inst_code_clone(Some(&insts));
+            for ratio in [delta_bump_ratio, -delta_bump_ratio] {
+                jobs.push(BumpJob::new(
+                    inst_codes.clone(),
+                    vec![Bump::Equity { code: und_code.clone(), ratio }],
+                ));
             }
+            targets.push((und_code.clone(), insts));
+        }
 
-            delta_up_map = self.get_npvs().context("failed to get npvs")?;
-            {
-                let mut equity = (*self.equities
-                    .get(*und_code)
-                    .ok_or_else(|| anyhow!(
-                        "({}:{}) there is no stock {}", 
-                        file!(), line!(),
-                        und_code))?).as_ref().borrow_mut();
-
-                equity.set_price(original_price);
-                *equity *= down_bump;
-            }
-            
-            delta_down_map = self.get_npvs().context("failed to get npvs")?;
-            
-            for inst in &self.instruments_in_action {
+        let npvs = self.get_npvs_of_bump_jobs(&jobs).context("failed to get npvs")?;
+        for ((und_code, insts), bumped) in targets.iter().zip(npvs.chunks(2)) {
+            let (delta_up_map, delta_down_map) = (&bumped[0], &bumped[1]);
+            for inst in insts.iter() {
                 let inst_code = inst.get_code();
                 let unitamt = inst.get_unit_notional();
-                delta_up = delta_up_map
-                    .get(inst_code)
-                    .ok_or_else(|| anyhow!("delta_up is not set"))?
-                    .clone();
-                delta_down = delta_down_map
-                    .get(inst_code)
-                    .ok_or_else(|| anyhow!("delta_down is not set"))?
-                    .clone();
-
-                delta = (delta_up - delta_down) / (2.0 * delta_bump_ratio) * DELTA_PNL_UNIT;
-
-                (*self.calculation_results
-                    .get(inst_code)
-                    .ok_or_else(|| anyhow!(
-                        "({}:{}) result is not set for {}",
-                        file!(), line!(), inst_code,
-                    ))?)
-                    .borrow_mut()
-                    .set_single_delta(&und_code, delta * unitamt);
-
-                mid = self.calculation_results
-                        .get(inst.get_code())
-                        .ok_or_else(|| anyhow!("result is not set"))?
-                        .borrow()
-                        .get_npv_result()
-                        .ok_or_else(|| anyhow!("npv is not set"))?
-                        .get_npv();
-
-                gamma = delta_up - mid + delta_down - mid;
+                let delta_up = *delta_up_map.get(inst_code)
+                    .ok_or_else(|| anyhow!("({}:{}) delta_up is not set for {}", file!(), line!(), inst_code))?;
+                let delta_down = *delta_down_map.get(inst_code)
+                    .ok_or_else(|| anyhow!("({}:{}) delta_down is not set for {}", file!(), line!(), inst_code))?;
+
+                let delta = (delta_up - delta_down) / (2.0 * delta_bump_ratio) * DELTA_PNL_UNIT;
+                let mid = self.get_npv_of_result(inst_code)?;
+                let mut gamma = delta_up - mid + delta_down - mid;
                 gamma *= DELTA_PNL_UNIT / delta_bump_ratio;
                 gamma *= 0.5 * (DELTA_PNL_UNIT / delta_bump_ratio);
 
-                (*self.calculation_results
-                    .get(inst.get_code())
+                let mut result = self.calculation_results.get(inst_code)
                     .ok_or_else(|| anyhow!(
                         "({}:{}) result is not set for {}",
-                        file!(), line!(), inst.get_code(),
-                    ))?)
-                    .borrow_mut()
-                    .set_single_gamma(&und_code, gamma * unitamt);
-            }
-
-            {
-                (*self.equities
-                    .get(*und_code)
-                    .ok_or_else(|| anyhow!(
-                        "({}:{}) there is no stock {}", 
-                        file!(), line!(), und_code
-                    ))?)
-                    .as_ref()
-                    .borrow_mut()
-                    .set_price(original_price);
+                        file!(), line!(), inst_code,
+                    ))?
+                    .borrow_mut();
+                result.set_single_delta(und_code, delta * unitamt);
+                result.set_single_gamma(und_code, gamma * unitamt);
             }
         }
-        
         Ok(())
     }
 
     pub fn set_rho(&mut self) -> Result<()> {
-        let mut npvs_up: HashMap::<String, Real>;
-        let all_curve_names = self.instruments.get_all_curve_names(&self.match_parameter)?;
         let bump_val = self.calculation_configuration.get_rho_bump_value();
         let exclude_type = vec!["Stock"];
-        let exclude_type_clone = exclude_type.clone();
-        
-        for curve_name in all_curve_names {
-            self.instruments_in_action = self.instruments
+        let mut targets = Vec::new();
+        let mut jobs = Vec::new();
+        for curve_name in self.instruments.get_all_curve_names(&self.match_parameter)? {
+            let insts = self.instruments
                 .instruments_using_curve(
-                    curve_name, 
+                    curve_name,
                     &self.match_parameter,
-                    Some(exclude_type_clone.clone()),
+                    Some(exclude_type.clone()),
                 )?;
-            if self.instruments_in_action.is_empty() {
+            if insts.is_empty() {
                 continue;
             }
-            // bump the curve but limit the scope that the zero_curve ismutably borrowed
-            {
-                (*self.zero_curves.get(curve_name)
-                    .with_context(|| anyhow!(
-                        "({}:{}) no zero curve: {}\n{}", 
-                        file!(), line!(),
-                        curve_name, self.msg_tag,))?)
-                        .as_ref()
-                        .borrow_mut()
-                        .bump_time_interval(None, None, bump_val)?;
-            }
-
-            npvs_up = self.get_npvs().context("failed to get npvs")?;
+            jobs.push(BumpJob::new(
+                self.instruments.get_all_inst_code_clone(Some(&insts)),
+                vec![Bump::ZeroCurve { code: curve_name.clone(), start: None, end: None, value: bump_val }],
+            ));
+            targets.push((curve_name.clone(), insts));
+        }
 
-            for inst in &self.instruments_in_action {
+        let npvs = self.get_npvs_of_bump_jobs(&jobs).context("failed to get npvs")?;
+        for ((curve_name, insts), npvs_up) in targets.iter().zip(npvs.iter()) {
+            for inst in insts.iter() {
                 let inst_code = inst.get_code();
                 let unitamt = inst.get_unit_notional();
                 let npv_up = npvs_up.get(inst_code)
-                    .ok_or_else(|| anyhow!("npv_up is not set"))?;
-                let npv = self.calculation_results
-                    .get(inst_code)
-                    .ok_or_else(|| anyhow!("result is not set"))?
-                    .borrow()
-                    .get_npv_result()
-                    .ok_or_else(|| anyhow!("npv is not set"))?
-                    .get_npv();
+                    .ok_or_else(|| anyhow!("({}:{}) npv_up is not set for {}", file!(), line!(), inst_code))?;
+                let npv = self.get_npv_of_result(inst_code)?;
 
                 let rho = (npv_up - npv) / bump_val * RHO_PNL_UNIT * unitamt;
-                (*self.calculation_results
-                    .get(inst.get_code())
+                self.calculation_results.get(inst_code)
                     .ok_or_else(|| anyhow!(
                         "({}:{}) result is not set for {}",
-                        file!(), line!(), inst.get_code(),
-                    ))?)
+                        file!(), line!(), inst_code,
+                    ))?
                     .borrow_mut()
                     .set_single_rho(curve_name, rho);
             }
-            // put back the bump value
-            {
-                (*self.zero_curves.get(curve_name)
-                    .with_context(|| anyhow!(
-                        "({}:{}) no zero curve: {}\n{}", 
-                        file!(), line!(),
-                        curve_name, self.msg_tag,))?)
-                    .as_ref()
-                    .borrow_mut()
-                    .bump_time_interval(None, None, -bump_val)?;
-            }
         }
         Ok(())
     }
 
     pub fn set_vega(&mut self) -> Result<()> {
-        let mut npvs_up: HashMap::<String, Real>;
-        let all_underlying_codes = self.instruments.get_all_underlying_codes();
         let bump_val = self.calculation_configuration.get_vega_bump_value();
-        let mut npv: Real;
         let exclude_type = vec!["Futures", "Stock"];
-        let exclude_type_clone = exclude_type.clone();
-        for vol_code in all_underlying_codes {
-            self.instruments_in_action = self.instruments
-                .instruments_with_underlying(
-                    vol_code,
-                    Some(exclude_type_clone.clone()),
-                );
-            
-            if self.instruments_in_action.is_empty() {
+        let mut targets = Vec::new();
+        let mut jobs = Vec::new();
+        for vol_code in self.instruments.get_all_underlying_codes() {
+            let insts = self.instruments.instruments_with_underlying(vol_code, Some(exclude_type.clone()));
+            if insts.is_empty() {
                 continue;
             }
+            jobs.push(BumpJob::new(
+                self.instruments.get_all_inst_code_clone(Some(&insts)),
+                vec![Bump::Volatility {
+                    code: vol_code.clone(),
+                    start: None,
+                    end: None,
+                    left_moneyness: None,
+                    right_moneyness: None,
+                    value: bump_val,
+                }],
+            ));
+            targets.push((vol_code.clone(), insts));
+        }
 
-            // bump the volatility but limit the scope that is mutably borrowed
-            {
-                (*self.volatilities
-                    .get(vol_code)
-                    .ok_or_else(|| anyhow!(
-                        "({}:{}) volatility {} is not set\ntag:\n{}", 
-                        file!(), line!(),
-                        vol_code, self.msg_tag
-                    ))?)
-                    .as_ref()
-                    .borrow_mut()
-                    .bump_volatility(
-                        None, 
-                        None, 
-                        None,
-                        None,
-                        bump_val)?;
-            }
-
-            npvs_up = self.get_npvs().context("failed to get npvs")?; // instrument code (String) -> npv (Real
-
-            for inst in &self.instruments_in_action {
+        let npvs = self.get_npvs_of_bump_jobs(&jobs).context("failed to get npvs")?;
+        for ((vol_code, insts), npvs_up) in targets.iter().zip(npvs.iter()) {
+            for inst in insts.iter() {
                 let inst_code = inst.get_code();
                 let unitamt = inst.get_unit_notional();
                 let npv_up = npvs_up.get(inst_code)
                     .ok_or_else(|| anyhow!(
                         "({}:{}) npv_up is not set for {}", file!(), line!(), inst_code))?;
-
-                npv = self.calculation_results
-                    .get(inst.get_code())
-                    .ok_or_else(|| anyhow!(
-                        "({}:{}) result is not set for {}", file!(), line!(), inst_code))?
-                    .borrow()
-                    .get_npv_result()
-                    .ok_or_else(|| anyhow!(
-                        "({}:{}) npv is not set for {}", file!(), line!(), inst_code))?
-                    .get_npv();
+                let npv = self.get_npv_of_result(inst_code)?;
 
                 let vega = (npv_up - npv) / bump_val * VEGA_PNL_UNIT * unitamt;
-                (*self.calculation_results
-                    .get(inst.get_code())
+                self.calculation_results.get(inst_code)
                     .ok_or_else(|| anyhow!(
-                        "({}:{}) result is not set for {}", file!(), line!(), inst.get_code()))?
-                    )
+                        "({}:{}) result is not set for {}", file!(), line!(), inst_code))?
                     .borrow_mut()
                     .set_single_vega(vol_code, vega);
             }
-            // put back the bump
-            {
-                (*self.volatilities
-                    .get(vol_code)
-                    .ok_or_else(|| anyhow!(
-                        "({}:{}) volatility {} is not set\ntag:\n{}", 
-                        file!(), line!(),
-                        vol_code, self.msg_tag
-                    ))?).as_ref().borrow_mut()
-                    .bump_volatility(
-                        None, 
-                        None, 
-                        None,
-                        None,
-                        -bump_val
-                    )?;
-            }
         }
         Ok(())
     }
 
 
-    // set vega structure performs the bump from the tail
-    // this is for the arbitrage condition
-    // say the bumped vector is vega_structure_up, with the length N
-    // then vega_structure[i] = vege_structure_up[i] - vega_structure_up[i+1]
-    // ... for i = 0, 1, ..., N-2 and
-    // vega_structure[N-1] = vega_structure_up[N-1] - npv
-    pub fn set_vega_structure(&mut self) -> Result<()> {
-        let all_underlying_codes = self.instruments.get_all_underlying_codes();
-        let eval_dt = self.evaluation_date.borrow().get_date_clone();
-        let bump_val = self.calculation_configuration.get_vega_structure_bump_value();
-        let calc_tenors = self.calculation_configuration.get_vega_structure_tenors();
-        let tenor_length = calc_tenors.len();
-        let time_calculator = NullCalendar::default(); 
-        let calc_times = calc_tenors.iter()
-            .map(|tenor| add_period(&eval_dt, tenor.as_str()))
-            .map(|dt| time_calculator.get_time_difference(&eval_dt, &dt))
-            .collect::<Vec<Time>>();
+    /// the fx rates of the engine which are code (e.g., "USDKRW") or its reciprocal pair (inverted = true)
+    fn fx_prices(&self, code: &str) -> Result<Vec<(FxCode, bool)>> {
+        let mut prices = Vec::new();
+        for fx_code in self.fxs.keys() {
+            if fx_code.to_string() == code {
+                prices.push((*fx_code, false));
+            } else if fx_code.reciprocal().to_string() == code {
+                prices.push((*fx_code, true));
+            }
+        }
+        if prices.is_empty() {
+            bail!("({}:{}) there is no fx rate {}\n{}", file!(), line!(), code, self.msg_tag);
+        }
+        Ok(prices)
+    }
 
-        // instrument code (String) -> npv (Real)
-        let mut current_npvs_up: HashMap::<String, Real>;
-        let mut npv: Real;
-        let mut npv_up: Real;
-        // inst code (String) -> Vec<Real>
-        let mut single_vega_structure: HashMap::<String, Vec<Real>>;
-        let exclude_type = vec!["Cash", "Stock", "Futures"];
-        let exclude_type_clone = exclude_type.clone();
+    /// the fx volatility shared by the quantos of fx_code (e.g., "USDKRW")
+    fn fx_volatility(&self, fx_code: &str) -> Result<(FxCode, Rc<RefCell<Volatility>>)> {
+        self.quantos.iter()
+            .find(|((_, code), _)| code.to_string() == fx_code)
+            .map(|((_, code), quanto)| (*code, quanto.borrow().get_fx_volatility().clone()))
+            .ok_or_else(|| anyhow!(
+                "({}:{}) there is no quanto with fx code {}\ntag:\n{}",
+                file!(), line!(), fx_code, self.msg_tag
+            ))
+    }
 
-        for und_code in all_underlying_codes {
-            self.instruments_in_action = self.instruments
-                .instruments_with_underlying(
-                    und_code, 
-                    Some(exclude_type_clone.clone()));
-            let longest_maturity = self.instruments.get_longest_maturity(Some(&self.instruments_in_action.clone()));
-            let longest_mat_time = match longest_maturity {
-                Some(m) => time_calculator.get_time_difference(&eval_dt, &m),
-                None => 100_000_000.0
-            };
-            
-            if self.instruments_in_action.is_empty() { continue; }
-            let inst_codes_in_action = self.instruments.get_all_inst_code_clone(
-                Some(&self.instruments_in_action));
-            let init_vec: Vec<Vec<Real>> = vec![vec![0.0; tenor_length];inst_codes_in_action.len()];
-            single_vega_structure = inst_codes_in_action.clone()
-                .into_iter()
-                .zip(init_vec.into_iter())
-                .collect();
-            let mut prev_npvs_up: HashMap::<String, Real> = HashMap::new();
-            for inst_code in inst_codes_in_action.iter() {
-                prev_npvs_up.insert(
-                    inst_code.clone(),
-                    self.calculation_results
-                        .get(inst_code)
+    /// relative bump of code which is an equity or an fx rate, e.g., the codes of the cross gamma
+    fn spot_bump(&self, code: &str, ratio: Real) -> Bump {
+        if self.equities.contains_key(code) {
+            Bump::Equity { code: code.to_string(), ratio }
+        } else {
+            Bump::Fx { code: code.to_string(), ratio }
+        }
+    }
+
+    /// parallel shift of the volatility of code
+    fn volatility_bump(code: &str, value: Real) -> Bump {
+        Bump::Volatility {
+            code: code.to_string(),
+            start: None,
+            end: None,
+            left_moneyness: None,
+            right_moneyness: None,
+            value,
+        }
+    }
+
+    fn get_npv_of_result(&self, inst_code: &String) -> Result<Real> {
+        Ok(self.calculation_results.get(inst_code)
+            .ok_or_else(|| anyhow!("({}:{}) result is not set for {}", file!(), line!(), inst_code))?
+            .borrow()
+            .get_npv_result()
+            .ok_or_else(|| anyhow!("({}:{}) npv is not set for {}", file!(), line!(), inst_code))?
+            .get_npv())
+    }
+
+    /// The snapshot of the engine as it is now for the replicas.
+    /// The parameters must be given by with_parameter_data.
+    fn get_engine_snapshot(&self) -> Result<Arc<EngineSnapshot>> {
+        let parameter_data = self.parameter_data.clone()
+            .ok_or_else(|| anyhow!(
+                "({}:{}) parallel_bumps requires the parameters given by with_parameter_data\n{}",
+                file!(), line!(), self.msg_tag
+            ))?;
+        Ok(Arc::new(EngineSnapshot {
+            engine_id: self.engine_id,
+            calculation_configuration: self.calculation_configuration.as_ref().clone(),
+            evaluation_datetime: self.evaluation_date.borrow().get_date_clone(),
+            match_parameter: self.match_parameter.as_ref().clone(),
+            instruments: self.instruments.iter().map(|inst| inst.as_ref().clone()).collect(),
+            option_prices: self.option_prices.clone(),
+            parameter_data,
+            scenario: self.applied_scenario.clone(),
+        }))
+    }
+
+    /// apply the bumps where the bumped market data are backed up (only the first time for each code)
+    fn apply_bumps(&self, bumps: &[Bump], backup: &mut ScenarioBackup) -> Result<()> {
+        for bump in bumps.iter() {
+            match bump {
+                Bump::Equity { code, ratio } => {
+                    let equity = self.equities.get(code)
+                        .ok_or_else(|| anyhow!("({}:{}) there is no stock {}\n{}", file!(), line!(), code, self.msg_tag))?;
+                    let price = equity.borrow().get_value();
+                    backup.equities.entry(code.clone()).or_insert(price);
+                    *equity.borrow_mut() *= 1.0 + ratio;
+                },
+                Bump::Fx { code, ratio } => {
+                    for (fx_code, inverted) in self.fx_prices(code)? {
+                        let fx = &self.fxs[&fx_code];
+                        let rate = fx.borrow().get_value();
+                        backup.fxs.entry(fx_code).or_insert(rate);
+                        let bumped = if inverted { rate / (1.0 + ratio) } else { rate * (1.0 + ratio) };
+                        fx.borrow_mut().set_price(bumped);
+                    }
+                },
+                Bump::ZeroCurve { code, start, end, value } => {
+                    let curve = self.zero_curves.get(code)
+                        .ok_or_else(|| anyhow!("({}:{}) no zero curve: {}\n{}", file!(), line!(), code, self.msg_tag))?;
+                    backup.zero_curves.entry(code.clone())
+                        .or_insert_with(|| curve.borrow().clone());
+                    curve.borrow_mut().bump_time_interval(*start, *end, *value)?;
+                },
+                Bump::Volatility { code, start, end, left_moneyness, right_moneyness, value } => {
+                    let volatility = self.volatilities.get(code)
                         .ok_or_else(|| anyhow!(
-                            "({}:{}) result is not set for {}",
-                            file!(), line!(), inst_code,
-                        ))?
-                        .borrow()
-                        .get_npv_result()
+                            "({}:{}) volatility {} is not set\ntag:\n{}",
+                            file!(), line!(), code, self.msg_tag
+                        ))?;
+                    backup.volatilities.entry(code.clone())
+                        .or_insert_with(|| volatility.borrow().clone());
+                    volatility.borrow_mut().bump_volatility(*start, *end, *left_moneyness, *right_moneyness, *value)?;
+                },
+                Bump::Dividend { code, start, end, value } => {
+                    let dividend = match self.dividends.get(code) {
+                        Some(Some(dividend)) => dividend,
+                        _ => bail!("({}:{}) dividend {} is not set\n{}", file!(), line!(), code, self.msg_tag),
+                    };
+                    backup.dividends.entry(code.clone())
+                        .or_insert_with(|| dividend.borrow().clone());
+                    dividend.borrow_mut().bump_date_interval(start.as_ref(), end.as_ref(), *value)?;
+                },
+                Bump::FxVolatility { code, value } => {
+                    let (fx_code, volatility) = self.fx_volatility(code)?;
+                    backup.fx_volatilities.entry(fx_code)
+                        .or_insert_with(|| volatility.borrow().clone());
+                    volatility.borrow_mut().bump_volatility(None, None, None, None, *value)?;
+                },
+                Bump::QuantoCorrelation { und_code, fx_code, value } => {
+                    let (key, quanto) = self.quantos.iter()
+                        .find(|((code, fx), _)| code == und_code && fx.to_string() == *fx_code)
                         .ok_or_else(|| anyhow!(
-                            "({}:{}) npv is not set for {}",
-                            file!(), line!(), inst_code,
-                        ))?
-                        .get_npv()
-                );
+                            "({}:{}) there is no quanto for ({}, {})\ntag:\n{}",
+                            file!(), line!(), und_code, fx_code, self.msg_tag
+                        ))?;
+                    backup.quantos.entry(key.clone())
+                        .or_insert_with(|| quanto.borrow().clone());
+                    quanto.borrow_mut().bump_correlation(*value);
+                },
             }
+        }
+        Ok(())
+    }
 
-            for i in (0..calc_times.len()).rev() {
-                let bump_start = match i {
-                    0 => None,
-                    _ => Some(calc_times[i-1]),
-                };
-                let bump_end = Some(calc_times[i]);
-                {
-                    (*self.volatilities
-                        .get(und_code)
-                        .ok_or_else(|| anyhow!(
-                            "({}:{}) volatility {} is not set\ntag:\n{}", 
-                            file!(), line!(), und_code, self.msg_tag
-                        ))?).as_ref()
-                        .borrow_mut()
-                        .bump_volatility(
-                            bump_start, bump_end, 
-                            None, None,
-                            bump_val)?;
+    /// npvs of the instruments of the job under its bumps.
+    /// The market data and instruments_in_action are put back before returning.
+    fn get_npvs_of_bump_job(&mut self, job: &BumpJob) -> Result<HashMap<String, Real>> {
+        let inst_codes = job.inst_codes.iter().collect::<HashSet<&String>>();
+        let job_instruments = self.instruments.iter()
+            .filter(|inst| inst_codes.contains(inst.get_code()))
+            .cloned()
+            .collect();
+        let instruments_in_action = std::mem::replace(&mut self.instruments_in_action, job_instruments);
+        let mut backup = ScenarioBackup::new("bump");
+        let npvs = self.apply_bumps(&job.bumps, &mut backup)
+            .and_then(|_| self.get_npvs());
+        self.instruments_in_action = instruments_in_action;
+        self.restore_market_data(backup)?;
+        npvs
+    }
+
+    /// Npvs of the jobs in the order of the jobs.
+    /// If parallel_bumps is set in the configuration, the jobs are split over the rayon threads
+    /// where each share of the jobs is evaluated on a replica built from the snapshot of the engine (see EngineSnapshot).
+    fn get_npvs_of_bump_jobs(&mut self, jobs: &[BumpJob]) -> Result<Vec<HashMap<String, Real>>> {
+        if !self.calculation_configuration.get_parallel_bumps() || jobs.len() < 2 {
+            return jobs.iter().map(|job| self.get_npvs_of_bump_job(job)).collect();
+        }
+        let snapshot = self.get_engine_snapshot()?;
+        let replicas = rayon::current_num_threads().clamp(1, jobs.len());
+        let chunk_size = jobs.len().div_ceil(replicas);
+        let npvs = jobs.par_chunks(chunk_size)
+            .map(|chunk| {
+                let mut replica = snapshot.build()
+                    .with_context(|| anyhow!(
+                        "({}:{}) failed to build a replica of engine-{} for the bumps",
+                        file!(), line!(), snapshot.engine_id
+                    ))?;
+                chunk.iter()
+                    .map(|job| replica.get_npvs_of_bump_job(job))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(npvs.into_iter().flatten().collect())
+    }
+
+    /// Cross gamma between every pair of the underlyings and the quanto fx codes of each instrument:
+    /// (V(+,+) - V(+,-) - V(-,+) + V(-,-)) / (4 * delta_bump_ratio^2) * DELTA_PNL_UNIT^2 * unit_notional
+    pub fn set_cross_gamma(&mut self) -> Result<()> {
+        let bump = self.calculation_configuration.get_delta_bump_ratio();
+        let exclude_type = ["Stock", "Futures", "Cash"];
+        let mut pairs: BTreeMap<(String, String), Vec<Rc<Instrument>>> = BTreeMap::new();
+        for inst in self.instruments.get_instruments_clone() {
+            if exclude_type.contains(&inst.get_type_name()) {
+                continue;
+            }
+            let mut codes: Vec<String> = inst.get_underlying_codes().into_iter().cloned().collect();
+            for (_, fx_code) in inst.get_quanto_fxcode_und_pair() {
+                let fx_code = fx_code.to_string();
+                if !codes.contains(&fx_code) {
+                    codes.push(fx_code);
                 }
-                if let Some(start) = bump_start {
-                    if longest_mat_time < start {
-                        continue;
+            }
+            for i in 0..codes.len() {
+                for j in (i + 1)..codes.len() {
+                    pairs.entry((codes[i].clone(), codes[j].clone())).or_default().push(inst.clone());
+                }
+            }
+        }
+
+        let mut jobs = Vec::new();
+        for ((code1, code2), instruments) in pairs.iter() {
+            let inst_codes = self.instruments.get_all_inst_code_clone(Some(instruments));
+            for (bump1, bump2) in [(bump, bump), (bump, -bump), (-bump, bump), (-bump, -bump)] {
+                jobs.push(BumpJob::new(
+                    inst_codes.clone(),
+                    vec![self.spot_bump(code1, bump1), self.spot_bump(code2, bump2)],
+                ));
+            }
+        }
+
+        let npvs = self.get_npvs_of_bump_jobs(&jobs).context("failed to get npvs")?;
+        for (((code1, code2), instruments), bumped) in pairs.iter().zip(npvs.chunks(4)) {
+            let (npvs_uu, npvs_ud, npvs_du, npvs_dd) = (&bumped[0], &bumped[1], &bumped[2], &bumped[3]);
+            for inst in instruments.iter() {
+                let inst_code = inst.get_code();
+                let npv = |npvs: &HashMap<String, Real>| npvs.get(inst_code).copied()
+                    .ok_or_else(|| anyhow!("({}:{}) npv is not set for {}", file!(), line!(), inst_code));
+                let cross_gamma = (npv(npvs_uu)? - npv(npvs_ud)? - npv(npvs_du)? + npv(npvs_dd)?)
+                    / (4.0 * bump * bump) * DELTA_PNL_UNIT * DELTA_PNL_UNIT * inst.get_unit_notional();
+                self.calculation_results.get(inst_code)
+                    .ok_or_else(|| anyhow!("({}:{}) result is not set for {}", file!(), line!(), inst_code))?
+                    .borrow_mut()
+                    .set_single_cross_gamma(code1, code2, cross_gamma);
+            }
+        }
+        Ok(())
+    }
+
+    /// vanna = (V(S+, v+) - V(S-, v+) - V(S+, v-) + V(S-, v-)) / (4 * delta_bump_ratio * vega_bump_value)
+    ///         * DELTA_PNL_UNIT * VEGA_PNL_UNIT * unit_notional
+    /// volga = (V(v+) - 2 V + V(v-)) / vega_bump_value^2 * VEGA_PNL_UNIT^2 * unit_notional
+    /// where the volatility is shifted in parallel
+    pub fn set_vanna_volga(&mut self) -> Result<()> {
+        let spot_bump = self.calculation_configuration.get_delta_bump_ratio();
+        let vol_bump = self.calculation_configuration.get_vega_bump_value();
+        let exclude_type = ["Stock", "Futures", "Cash"];
+        let mut vanna_pairs: BTreeMap<(String, String), Vec<Rc<Instrument>>> = BTreeMap::new();
+        let mut volga_codes: BTreeMap<String, Vec<Rc<Instrument>>> = BTreeMap::new();
+        for inst in self.instruments.get_instruments_clone() {
+            if exclude_type.contains(&inst.get_type_name()) {
+                continue;
+            }
+            for vol_code in inst.get_underlying_codes_requiring_volatility() {
+                if self.calculation_configuration.get_vanna_calculation() {
+                    for und_code in inst.get_underlying_codes() {
+                        vanna_pairs.entry((und_code.clone(), vol_code.clone())).or_default().push(inst.clone());
                     }
                 }
-                current_npvs_up = self.get_npvs().with_context(|| anyhow!(
-                    "({}:{}) failed to get npvs in vega structure", file!(), line!()))?;
-                
-                for inst in self.instruments_in_action.iter() {
-                    let inst_code = inst.get_code();
-                    let unitamt = inst.get_unit_notional();
-                    npv_up = current_npvs_up.get(inst_code)
-                        .ok_or_else(|| anyhow!("npv_up is not set for {}", inst_code))?.clone();
-                    npv = prev_npvs_up.get(inst_code)
-                        .ok_or_else(|| anyhow!("npv is not set for {}", inst_code))?.clone();
-                    
-                    let vega_structure = (npv_up - npv) / bump_val * VEGA_PNL_UNIT * unitamt;
-                    single_vega_structure.get_mut(inst_code)
-                        .ok_or_else(|| anyhow!(
-                            "vega_structure is not set for {}", 
-                            inst_code))?[i] = vega_structure;
+                if self.calculation_configuration.get_volga_calculation() {
+                    volga_codes.entry(vol_code.clone()).or_default().push(inst.clone());
                 }
-                prev_npvs_up = current_npvs_up;
-                for (inst_code, vega_structure) in single_vega_structure.iter() {
-                    (*self.calculation_results
-                        .get(inst_code)
-                        .ok_or_else(|| anyhow!(
-                            "({}:{}) result is not set for {}",
-                            file!(), line!(), inst_code,
-                        ))?)
-                        .borrow_mut()
-                        .set_single_vega_structure(und_code, vega_structure.clone());
+            }
+        }
+
+        let mut jobs = Vec::new();
+        for ((und_code, vol_code), instruments) in vanna_pairs.iter() {
+            let inst_codes = self.instruments.get_all_inst_code_clone(Some(instruments));
+            for (spot, vol) in [(spot_bump, vol_bump), (-spot_bump, vol_bump), (spot_bump, -vol_bump), (-spot_bump, -vol_bump)] {
+                jobs.push(BumpJob::new(
+                    inst_codes.clone(),
+                    vec![self.spot_bump(und_code, spot), Engine::volatility_bump(vol_code, vol)],
+                ));
+            }
+        }
+        for (vol_code, instruments) in volga_codes.iter() {
+            let inst_codes = self.instruments.get_all_inst_code_clone(Some(instruments));
+            for vol in [vol_bump, -vol_bump] {
+                jobs.push(BumpJob::new(inst_codes.clone(), vec![Engine::volatility_bump(vol_code, vol)]));
+            }
+        }
+
+        let npvs = self.get_npvs_of_bump_jobs(&jobs).context("failed to get npvs")?;
+        let (vanna_npvs, volga_npvs) = npvs.split_at(4 * vanna_pairs.len());
+        for (((und_code, vol_code), instruments), bumped) in vanna_pairs.iter().zip(vanna_npvs.chunks(4)) {
+            let (npvs_uu, npvs_du, npvs_ud, npvs_dd) = (&bumped[0], &bumped[1], &bumped[2], &bumped[3]);
+            for inst in instruments.iter() {
+                let inst_code = inst.get_code();
+                let npv = |npvs: &HashMap<String, Real>| npvs.get(inst_code).copied()
+                    .ok_or_else(|| anyhow!("({}:{}) npv is not set for {}", file!(), line!(), inst_code));
+                let vanna = (npv(npvs_uu)? - npv(npvs_du)? - npv(npvs_ud)? + npv(npvs_dd)?)
+                    / (4.0 * spot_bump * vol_bump) * DELTA_PNL_UNIT * VEGA_PNL_UNIT * inst.get_unit_notional();
+                self.calculation_results.get(inst_code)
+                    .ok_or_else(|| anyhow!("({}:{}) result is not set for {}", file!(), line!(), inst_code))?
+                    .borrow_mut()
+                    .set_single_vanna(und_code, vol_code, vanna);
+            }
+        }
+
+        for ((vol_code, instruments), bumped) in volga_codes.iter().zip(volga_npvs.chunks(2)) {
+            let (npvs_up, npvs_down) = (&bumped[0], &bumped[1]);
+            for inst in instruments.iter() {
+                let inst_code = inst.get_code();
+                let npv = self.get_npv_of_result(inst_code)?;
+                let npv_up = npvs_up.get(inst_code).copied()
+                    .ok_or_else(|| anyhow!("({}:{}) npv_up is not set for {}", file!(), line!(), inst_code))?;
+                let npv_down = npvs_down.get(inst_code).copied()
+                    .ok_or_else(|| anyhow!("({}:{}) npv_down is not set for {}", file!(), line!(), inst_code))?;
+                let volga = (npv_up - 2.0 * npv + npv_down) / (vol_bump * vol_bump)
+                    * VEGA_PNL_UNIT * VEGA_PNL_UNIT * inst.get_unit_notional();
+                self.calculation_results.get(inst_code)
+                    .ok_or_else(|| anyhow!("({}:{}) result is not set for {}", file!(), line!(), inst_code))?
+                    .borrow_mut()
+                    .set_single_volga(vol_code, volga);
+            }
+        }
+        Ok(())
+    }
+
+    /// fx delta = (V(FX+) - V(FX-)) / (2 * delta_bump_ratio) * DELTA_PNL_UNIT * unit_notional
+    /// fx gamma = 0.5 * (V(FX+) - 2 V + V(FX-)) * (DELTA_PNL_UNIT / delta_bump_ratio)^2 * unit_notional
+    /// for the fx rates used in pricing, e.g., FxFutures and the floating_to_fixed_fx of CRS.
+    /// The reciprocal rate, if any, is moved together.
+    pub fn set_fx_delta_gamma(&mut self) -> Result<()> {
+        let bump = self.calculation_configuration.get_delta_bump_ratio();
+        let calc_delta = self.calculation_configuration.get_fx_delta_calculation();
+        let calc_gamma = self.calculation_configuration.get_fx_gamma_calculation();
+        let mut fx_instruments: BTreeMap<String, Vec<Rc<Instrument>>> = BTreeMap::new();
+        for inst in self.instruments.get_instruments_clone() {
+            for fx_code in inst.get_all_fxcodes_for_pricing() {
+                fx_instruments.entry(fx_code.to_string()).or_default().push(inst.clone());
+            }
+        }
+
+        let mut jobs = Vec::new();
+        for (fx_code, instruments) in fx_instruments.iter() {
+            let inst_codes = self.instruments.get_all_inst_code_clone(Some(instruments));
+            for ratio in [bump, -bump] {
+                jobs.push(BumpJob::new(inst_codes.clone(), vec![Bump::Fx { code: fx_code.clone(), ratio }]));
+            }
+        }
+
+        let npvs = self.get_npvs_of_bump_jobs(&jobs).context("failed to get npvs")?;
+        for ((fx_code, instruments), bumped) in fx_instruments.iter().zip(npvs.chunks(2)) {
+            let (npvs_up, npvs_down) = (&bumped[0], &bumped[1]);
+            for inst in instruments.iter() {
+                let inst_code = inst.get_code();
+                let unitamt = inst.get_unit_notional();
+                let npv = self.get_npv_of_result(inst_code)?;
+                let npv_up = npvs_up.get(inst_code).copied()
+                    .ok_or_else(|| anyhow!("({}:{}) npv_up is not set for {}", file!(), line!(), inst_code))?;
+                let npv_down = npvs_down.get(inst_code).copied()
+                    .ok_or_else(|| anyhow!("({}:{}) npv_down is not set for {}", file!(), line!(), inst_code))?;
+
+                let mut result = self.calculation_results.get(inst_code)
+                    .ok_or_else(|| anyhow!("({}:{}) result is not set for {}", file!(), line!(), inst_code))?
+                    .borrow_mut();
+                if calc_delta {
+                    let fx_delta = (npv_up - npv_down) / (2.0 * bump) * DELTA_PNL_UNIT * unitamt;
+                    result.set_single_fx_delta(fx_code, fx_delta);
+                }
+                if calc_gamma {
+                    let fx_gamma = 0.5 * (npv_up - 2.0 * npv + npv_down)
+                        * (DELTA_PNL_UNIT / bump) * (DELTA_PNL_UNIT / bump) * unitamt;
+                    result.set_single_fx_gamma(fx_code, fx_gamma);
                 }
             }
-            // put back
-            {
-                (*self.volatilities
-                    .get(und_code)
-                    .ok_or_else(|| anyhow!(
-                        "({}:{}) volatility {} is not set\ntag:\n{}", 
-                        file!(), line!(),
-                        und_code, self.msg_tag
-                    ))?).as_ref()
+        }
+        Ok(())
+    }
+
+    /// fx vega = (V(fx vol + vega_bump_value) - V) / vega_bump_value * VEGA_PNL_UNIT * unit_notional
+    /// quanto correlation = (V(rho + correlation_bump_value) - V) / correlation_bump_value * CORRELATION_PNL_UNIT * unit_notional
+    /// The fx volatility is shared by all quantos with the same fx code.
+    pub fn set_fx_vega_quanto_correlation(&mut self) -> Result<()> {
+        let vol_bump = self.calculation_configuration.get_vega_bump_value();
+        let corr_bump = self.calculation_configuration.get_correlation_bump_value();
+        let mut fx_vega_instruments: BTreeMap<String, Vec<Rc<Instrument>>> = BTreeMap::new();
+        let mut correlation_instruments: BTreeMap<(String, String), Vec<Rc<Instrument>>> = BTreeMap::new();
+        for inst in self.instruments.get_instruments_clone() {
+            for (und_code, fx_code) in inst.get_quanto_fxcode_und_pair() {
+                if self.calculation_configuration.get_fx_vega_calculation() {
+                    fx_vega_instruments.entry(fx_code.to_string()).or_default().push(inst.clone());
+                }
+                if self.calculation_configuration.get_quanto_correlation_calculation() {
+                    correlation_instruments.entry((und_code.clone(), fx_code.to_string()))
+                        .or_default()
+                        .push(inst.clone());
+                }
+            }
+        }
+
+        let mut jobs = Vec::new();
+        for (fx_str, instruments) in fx_vega_instruments.iter() {
+            jobs.push(BumpJob::new(
+                self.instruments.get_all_inst_code_clone(Some(instruments)),
+                vec![Bump::FxVolatility { code: fx_str.clone(), value: vol_bump }],
+            ));
+        }
+        for ((und_code, fx_str), instruments) in correlation_instruments.iter() {
+            jobs.push(BumpJob::new(
+                self.instruments.get_all_inst_code_clone(Some(instruments)),
+                vec![Bump::QuantoCorrelation { und_code: und_code.clone(), fx_code: fx_str.clone(), value: corr_bump }],
+            ));
+        }
+
+        let npvs = self.get_npvs_of_bump_jobs(&jobs).context("failed to get npvs")?;
+        let (fx_vega_npvs, correlation_npvs) = npvs.split_at(fx_vega_instruments.len());
+        for ((fx_str, instruments), npvs_up) in fx_vega_instruments.iter().zip(fx_vega_npvs.iter()) {
+            for inst in instruments.iter() {
+                let inst_code = inst.get_code();
+                let npv = self.get_npv_of_result(inst_code)?;
+                let npv_up = npvs_up.get(inst_code).copied()
+                    .ok_or_else(|| anyhow!("({}:{}) npv_up is not set for {}", file!(), line!(), inst_code))?;
+                let fx_vega = (npv_up - npv) / vol_bump * VEGA_PNL_UNIT * inst.get_unit_notional();
+                self.calculation_results.get(inst_code)
+                    .ok_or_else(|| anyhow!("({}:{}) result is not set for {}", file!(), line!(), inst_code))?
                     .borrow_mut()
-                    .bump_volatility(
-                        None, None,
-                        None, None,
-                        -bump_val)?;
+                    .set_single_fx_vega(fx_str, fx_vega);
+            }
+        }
+
+        for (((und_code, fx_str), instruments), npvs_up) in correlation_instruments.iter().zip(correlation_npvs.iter()) {
+            for inst in instruments.iter() {
+                let inst_code = inst.get_code();
+                let npv = self.get_npv_of_result(inst_code)?;
+                let npv_up = npvs_up.get(inst_code).copied()
+                    .ok_or_else(|| anyhow!("({}:{}) npv_up is not set for {}", file!(), line!(), inst_code))?;
+                let sensitivity = (npv_up - npv) / corr_bump * CORRELATION_PNL_UNIT * inst.get_unit_notional();
+                self.calculation_results.get(inst_code)
+                    .ok_or_else(|| anyhow!("({}:{}) result is not set for {}", file!(), line!(), inst_code))?
+                    .borrow_mut()
+                    .set_single_quanto_correlation(und_code, fx_str, sensitivity);
             }
         }
         Ok(())
     }
 
-    pub fn set_vega_matrix(&mut self) -> Result<()> {
-        let all_underlying_codes = self.instruments.get_all_underlying_codes();
+    // set vega structure performs the bump from the tail
+    // this is for the arbitrage condition
+    // say the bumped vector is vega_structure_up, with the length N
+    // then vega_structure[i] = vege_structure_up[i] - vega_structure_up[i+1]
+    // ... for i = 0, 1, ..., N-2 and
+    // vega_structure[N-1] = vega_structure_up[N-1] - npv
+    /// The volatilities are bumped on (t_(i-1), t_last] for each tenor t_i, which is the same as
+    /// bumping the tenors one by one backward from the last, and the i-th vega structure is the difference
+    /// from the npv where the next tenors are bumped. The tenors over the longest maturity are not calculated.
+    pub fn set_vega_structure(&mut self) -> Result<()> {
         let eval_dt = self.evaluation_date.borrow().get_date_clone();
         let bump_val = self.calculation_configuration.get_vega_structure_bump_value();
         let calc_tenors = self.calculation_configuration.get_vega_structure_tenors();
-        
         let time_calculator = NullCalendar::default(); 
         let calc_times = calc_tenors.iter()
             .map(|tenor| add_period(&eval_dt, tenor.as_str()))
             .map(|dt| time_calculator.get_time_difference(&eval_dt, &dt))
             .collect::<Vec<Time>>();
-
-        let spot_moneyness = self.calculation_configuration.get_vega_matrix_spot_moneyness().to_vec();
-        
-        // instrument code (String) -> npv (Real)
-        let mut current_npvs_up: HashMap::<String, Real>;
-        let mut npv: Real;
-        let mut npv_up: Real;
-        // inst code (String) -> Array2<Real>
-        let mut single_vega_matrix: HashMap::<String, Array2<Real>> = HashMap::new();
         let exclude_type = vec!["Cash", "Stock", "Futures"];
-        let exclude_type_clone = exclude_type.clone();
 
-        for und_code in all_underlying_codes {
-            self.instruments_in_action = self.instruments
-                .instruments_with_underlying(
-                    und_code, 
-                    Some(exclude_type_clone.clone()));
-            let longest_maturity = self.instruments.get_longest_maturity(Some(&self.instruments_in_action.clone()));
-            let longest_mat_time = match longest_maturity {
+        // (underlying code, instruments, indices of the tenors calculated)
+        let mut targets = Vec::new();
+        let mut jobs = Vec::new();
+        for und_code in self.instruments.get_all_underlying_codes() {
+            let insts = self.instruments.instruments_with_underlying(und_code, Some(exclude_type.clone()));
+            if insts.is_empty() { continue; }
+            let longest_mat_time = match self.instruments.get_longest_maturity(Some(&insts)) {
                 Some(m) => time_calculator.get_time_difference(&eval_dt, &m),
                 None => 100_000_000.0
             };
-            
-            if self.instruments_in_action.is_empty() { continue; }
-            let inst_codes_in_action = self.instruments.get_all_inst_code_clone(
-                Some(&self.instruments_in_action));
-
-            for inst_code in inst_codes_in_action.iter() {
-                let init = Array2::zeros((calc_times.len(), spot_moneyness.len()));
-                single_vega_matrix.insert(inst_code.clone(), init);
-            }
-
-            let mut prev_npvs_up: HashMap::<String, Real> = HashMap::new();
-
-            for inst_code in inst_codes_in_action.iter() {
-                prev_npvs_up.insert(
-                    inst_code.clone(),
-                    self.calculation_results
-                        .get(inst_code)
-                        .ok_or_else(|| anyhow!(
-                            "({}:{}) result is not set for {}",
-                            file!(), line!(), inst_code,
-                        ))?
-                        .borrow()
-                        .get_npv_result()
-                        .ok_or_else(|| anyhow!(
-                            "({}:{}) npv is not set for {}",
-                            file!(), line!(), inst_code,
-                        ))?
-                        .get_npv()
-                );
+            let tenors = (0..calc_times.len())
+                .filter(|&i| i == 0 || calc_times[i - 1] <= longest_mat_time)
+                .collect::<Vec<usize>>();
+            let inst_codes = self.instruments.get_all_inst_code_clone(Some(&insts));
+            for &i in tenors.iter() {
+                jobs.push(BumpJob::new(
+                    inst_codes.clone(),
+                    vec![Bump::Volatility {
+                        code: und_code.clone(),
+                        start: if i == 0 { None } else { Some(calc_times[i - 1]) },
+                        end: calc_times.last().copied(),
+                        left_moneyness: None,
+                        right_moneyness: None,
+                        value: bump_val,
+                    }],
+                ));
             }
+            targets.push((und_code.clone(), insts, tenors));
+        }
 
-            for i in (0..calc_times.len()).rev() {
-                let bump_tenor_start = match i {
-                    0 => None,
-                    _ => Some(calc_times[i-1]),
-                };
-                let bump_tenor_end = Some(calc_times[i]);
-                if let Some(start) = bump_tenor_start {
-                    if longest_mat_time < start {
-                        continue;
-                    }
+        let npvs = self.get_npvs_of_bump_jobs(&jobs)
+            .with_context(|| anyhow!("({}:{}) failed to get npvs in vega structure", file!(), line!()))?;
+        let mut npvs = npvs.into_iter();
+        for (und_code, insts, tenors) in targets.into_iter() {
+            let npvs_up = npvs.by_ref().take(tenors.len()).collect::<Vec<_>>();
+            for inst in insts.iter() {
+                let inst_code = inst.get_code();
+                let unitamt = inst.get_unit_notional();
+                let mut vega_structure = vec![0.0; calc_times.len()];
+                let mut prev_npv_up = self.get_npv_of_result(inst_code)?;
+                for (k, &i) in tenors.iter().enumerate().rev() {
+                    let npv_up = *npvs_up[k].get(inst_code)
+                        .ok_or_else(|| anyhow!("({}:{}) npv_up is not set for {}", file!(), line!(), inst_code))?;
+                    vega_structure[i] = (npv_up - prev_npv_up) / bump_val * VEGA_PNL_UNIT * unitamt;
+                    prev_npv_up = npv_up;
                 }
-                for j in 0..spot_moneyness.len() {
-                    let bump_moneyness_start = match j {
-                        0 => None,
-                        _ => Some(spot_moneyness[j-1]),
-                    };
+                self.calculation_results.get(inst_code)
+                    .ok_or_else(|| anyhow!(
+                        "({}:{}) result is not set for {}",
+                        file!(), line!(), inst_code,
+                    ))?
+                    .borrow_mut()
+                    .set_single_vega_structure(&und_code, vega_structure);
+            }
+        }
+        Ok(())
+    }
 
-                    let bump_moneyness_end = Some(spot_moneyness[j]);
-                    {
-                        (*self.volatilities
-                            .get(und_code)
-                            .ok_or_else(|| anyhow!(
-                                "({}:{}) volatility {} is not set\ntag:\n{}", 
-                                file!(), line!(), und_code, self.msg_tag
-                            ))?).as_ref()
-                            .borrow_mut()
-                            .bump_volatility(
-                                bump_tenor_start, bump_tenor_end, 
-                                bump_moneyness_start, bump_moneyness_end,
-                                bump_val)?;
-                    }
+    /// The cells are bumped one by one backward from the last tenor and forward from the first moneyness,
+    /// and each cell is the difference from the npv of the previous cell. Each job bumps the cells up to
+    /// the cell at once: the later tenors on all the moneyness and the tenor of the cell up to its moneyness.
+    pub fn set_vega_matrix(&mut self) -> Result<()> {
+        let eval_dt = self.evaluation_date.borrow().get_date_clone();
+        let bump_val = self.calculation_configuration.get_vega_structure_bump_value();
+        let calc_tenors = self.calculation_configuration.get_vega_structure_tenors();
+        let time_calculator = NullCalendar::default(); 
+        let calc_times = calc_tenors.iter()
+            .map(|tenor| add_period(&eval_dt, tenor.as_str()))
+            .map(|dt| time_calculator.get_time_difference(&eval_dt, &dt))
+            .collect::<Vec<Time>>();
+        let spot_moneyness = self.calculation_configuration.get_vega_matrix_spot_moneyness().to_vec();
+        let exclude_type = vec!["Cash", "Stock", "Futures"];
 
-                    current_npvs_up = self.get_npvs().with_context(|| anyhow!(
-                        "({}:{}) failed to get npvs in vega structure", file!(), line!()))?;
-                    
-                    //let code = String::from("165XXX3");
-                    //println!("i: {}, j: {} current_npvs_up: {:?}", i, j, current_npvs_up.get(&code).unwrap());
-
-                    for inst in self.instruments_in_action.iter() {
-                        let inst_code = inst.get_code();
-                        let unitamt = inst.get_unit_notional();
-                        npv_up = current_npvs_up.get(inst_code)
-                            .ok_or_else(|| anyhow!("npv_up is not set for {}", inst_code))?.clone();
-                        npv = prev_npvs_up.get(inst_code)
-                            .ok_or_else(|| anyhow!("npv is not set for {}", inst_code))?.clone();
-
-                        let vega_matrix = (npv_up - npv) / bump_val * VEGA_PNL_UNIT * unitamt;
-                        single_vega_matrix.get_mut(inst_code)
-                            .ok_or_else(|| anyhow!(
-                                "vega_matrix is not set for {}", 
-                                inst_code))?[[i, j]] = vega_matrix;
-                    }
-                    prev_npvs_up = current_npvs_up;
-                    for (inst_code, vega_matrix) in single_vega_matrix.iter() {
-                        (*self.calculation_results
-                            .get(inst_code)
-                            .ok_or_else(|| anyhow!(
-                                "({}:{}) result is not set for {}",
-                                file!(), line!(), inst_code,
-                            ))?)
-                            .borrow_mut()
-                            .set_single_vega_matrix(und_code, vega_matrix.clone());
+        // (underlying code, instruments, cells (tenor, moneyness) in the order of bumping)
+        let mut targets = Vec::new();
+        let mut jobs = Vec::new();
+        for und_code in self.instruments.get_all_underlying_codes() {
+            let insts = self.instruments.instruments_with_underlying(und_code, Some(exclude_type.clone()));
+            if insts.is_empty() { continue; }
+            let longest_mat_time = match self.instruments.get_longest_maturity(Some(&insts)) {
+                Some(m) => time_calculator.get_time_difference(&eval_dt, &m),
+                None => 100_000_000.0
+            };
+            let tenors = (0..calc_times.len())
+                .filter(|&i| i == 0 || calc_times[i - 1] <= longest_mat_time)
+                .collect::<Vec<usize>>();
+            let last_tenor = match tenors.last() {
+                Some(last) => *last,
+                None => continue,
+            };
+            let inst_codes = self.instruments.get_all_inst_code_clone(Some(&insts));
+            let mut cells = Vec::new();
+            for &i in tenors.iter().rev() {
+                for j in 0..spot_moneyness.len() {
+                    let mut bumps = Vec::new();
+                    if i < last_tenor {
+                        bumps.push(Bump::Volatility {
+                            code: und_code.clone(),
+                            start: Some(calc_times[i]),
+                            end: Some(calc_times[last_tenor]),
+                            left_moneyness: None,
+                            right_moneyness: spot_moneyness.last().copied(),
+                            value: bump_val,
+                        });
                     }
+                    bumps.push(Bump::Volatility {
+                        code: und_code.clone(),
+                        start: if i == 0 { None } else { Some(calc_times[i - 1]) },
+                        end: Some(calc_times[i]),
+                        left_moneyness: None,
+                        right_moneyness: Some(spot_moneyness[j]),
+                        value: bump_val,
+                    });
+                    jobs.push(BumpJob::new(inst_codes.clone(), bumps));
+                    cells.push((i, j));
                 }
             }
-            // put back
-            {
-                (*self.volatilities
-                    .get(und_code)
+            targets.push((und_code.clone(), insts, cells));
+        }
+
+        let npvs = self.get_npvs_of_bump_jobs(&jobs)
+            .with_context(|| anyhow!("({}:{}) failed to get npvs in vega matrix", file!(), line!()))?;
+        let mut npvs = npvs.into_iter();
+        for (und_code, insts, cells) in targets.into_iter() {
+            let npvs_up = npvs.by_ref().take(cells.len()).collect::<Vec<_>>();
+            for inst in insts.iter() {
+                let inst_code = inst.get_code();
+                let unitamt = inst.get_unit_notional();
+                let mut vega_matrix = Array2::zeros((calc_times.len(), spot_moneyness.len()));
+                let mut prev_npv_up = self.get_npv_of_result(inst_code)?;
+                for (npvs_up, (i, j)) in npvs_up.iter().zip(cells.iter()) {
+                    let npv_up = *npvs_up.get(inst_code)
+                        .ok_or_else(|| anyhow!("({}:{}) npv_up is not set for {}", file!(), line!(), inst_code))?;
+                    vega_matrix[[*i, *j]] = (npv_up - prev_npv_up) / bump_val * VEGA_PNL_UNIT * unitamt;
+                    prev_npv_up = npv_up;
+                }
+                self.calculation_results.get(inst_code)
                     .ok_or_else(|| anyhow!(
-                        "({}:{}) volatility {} is not set\ntag:\n{}", 
-                        file!(), line!(),
-                        und_code, self.msg_tag
-                    ))?).as_ref()
+                        "({}:{}) result is not set for {}",
+                        file!(), line!(), inst_code,
+                    ))?
                     .borrow_mut()
-                    .bump_volatility(
-                        None, None,
-                        None, None,
-                        -bump_val)?;
+                    .set_single_vega_matrix(&und_code, vega_matrix);
             }
         }
         Ok(())
     }
 
     pub fn set_div_delta(&mut self) -> Result<()> {
-        let mut npvs_up: HashMap::<String, Real>;
-        let all_dividend_codes = self.instruments.get_all_underlying_codes();
         let bump_val = self.calculation_configuration.get_div_bump_value();
-        let mut npv: Real;
         let exclude_type = vec!["Stock", "Cash"];
-        let exclude_type_clone = exclude_type.clone();
-        
-        for div_code in all_dividend_codes {
-            self.instruments_in_action = self.instruments
-                .instruments_with_underlying(div_code, Some(exclude_type_clone.clone()));
-
-            if self.instruments_in_action.is_empty() {
+        let mut targets = Vec::new();
+        let mut jobs = Vec::new();
+        for div_code in self.instruments.get_all_underlying_codes() {
+            let insts = self.instruments.instruments_with_underlying(div_code, Some(exclude_type.clone()));
+            if insts.is_empty() {
                 continue;
             }
-            // bump dividend but limit the scope that is mutably borrowed
-            {
-                self.dividends.get(div_code)
-                    .unwrap().clone().unwrap().borrow_mut()
-                    .bump_date_interval(None, None, bump_val)?;
-            }
-
-            npvs_up = self.get_npvs().context("failed to get npvs")?; // instrument code (String) -> npv (Real
+            jobs.push(BumpJob::new(
+                self.instruments.get_all_inst_code_clone(Some(&insts)),
+                vec![Bump::Dividend { code: div_code.clone(), start: None, end: None, value: bump_val }],
+            ));
+            targets.push((div_code.clone(), insts));
+        }
 
-            for inst in &self.instruments_in_action {
+        let npvs = self.get_npvs_of_bump_jobs(&jobs).context("failed to get npvs")?;
+        for ((div_code, insts), npvs_up) in targets.iter().zip(npvs.iter()) {
+            for inst in insts.iter() {
                 let inst_code = inst.get_code();
                 let unitamt = inst.get_unit_notional();
                 let npv_up = npvs_up.get(inst_code)
-                    .ok_or_else(|| anyhow!("npv_up is not set"))?;
-
-                npv = self.calculation_results
-                    .get(inst.get_code())
-                    .ok_or_else(|| anyhow!("result is not set"))?
-                    .borrow()
-                    .get_npv_result()
-                    .ok_or_else(|| anyhow!("npv is not set"))?
-                    .get_npv();
-
+                    .ok_or_else(|| anyhow!("({}:{}) npv_up is not set for {}", file!(), line!(), inst_code))?;
+                let npv = self.get_npv_of_result(inst_code)?;
 
                 let div_delta = (npv_up - npv) / bump_val * DIV_PNL_UNIT * unitamt;
-                (*self.calculation_results
-                    .get(inst.get_code())
-                    .ok_or_else(|| anyhow!("result is not set for {}", inst.get_code()))?)
+                self.calculation_results.get(inst_code)
+                    .ok_or_else(|| anyhow!("({}:{}) result is not set for {}", file!(), line!(), inst_code))?
                     .borrow_mut()
                     .set_single_div_delta(div_code, div_delta);
             }
-            // put back the bump
-            {
-                self.dividends
-                    .get(div_code)
-                    .unwrap()
-                    .clone()
-                    .unwrap()
-                    .borrow_mut()
-                    .bump_date_interval(None, None, -bump_val)?;
-            }
         }
         Ok(())
     }
@@ -1507,217 +1956,566 @@ impl Engine {
     }
 
     pub fn set_rho_structure(&mut self) -> Result<()> {
-        let all_curve_codes = self.instruments.get_all_curve_names(&self.match_parameter)?;
         let eval_dt = self.evaluation_date.borrow().get_date_clone();
         let bump_val = self.calculation_configuration.get_rho_bump_value();
         let calc_tenors = self.calculation_configuration.get_rho_structure_tenors();
-        let tenor_length = calc_tenors.len();
         let time_calculator = NullCalendar::default(); 
         let calc_dates = calc_tenors.iter()
             .map(|tenor| add_period(&eval_dt, tenor.as_str()))
             .collect::<Vec<_>>();
-        let mut calc_times =  Vec::<Time>::new();
-        for date in calc_dates.iter() {
-            calc_times.push(time_calculator.get_time_difference(&eval_dt, date));
-        }
-
-        // instrument code (String) -> npv (Real)
-        let mut npvs_up: HashMap::<String, Real>;
-        let mut npv_up: Real;
-        let mut npv: Real;
-        // inst code (String) -> Vec<Real>
-        let mut single_rho_structure: HashMap<String, Vec<Real>>;
-        let mut val: Real;
+        let calc_times = calc_dates.iter()
+            .map(|date| time_calculator.get_time_difference(&eval_dt, date))
+            .collect::<Vec<Time>>();
         let exclude_type = vec!["Stock", "Cash"];
-        let exclude_type_clone = exclude_type.clone();
 
-        for curve_code in all_curve_codes {
-            self.instruments_in_action = self.instruments
+        // (curve code, instruments, number of the tenors calculated)
+        let mut targets = Vec::new();
+        let mut jobs = Vec::new();
+        for curve_code in self.instruments.get_all_curve_names(&self.match_parameter)? {
+            let insts = self.instruments
                 .instruments_using_curve(
                     curve_code, 
                     &self.match_parameter,
-                    Some(exclude_type_clone.clone()),
+                    Some(exclude_type.clone()),
                 )?;
-            
-            if self.instruments_in_action.is_empty() {
+            if insts.is_empty() {
                 continue;
             }
-
-            let inst_codes_in_action = self.instruments.get_all_inst_code_clone(Some(&self.instruments_in_action));
-            let init_vec: Vec<Vec<Real>> = vec![vec![0.0; tenor_length];inst_codes_in_action.len()];
-            single_rho_structure = inst_codes_in_action.into_iter().zip(init_vec.into_iter()).collect();
-            // bump zero_curve by bump_date_interval where calc_dates[i] < date <= calc_dates[i+1]
-            for i in 0..calc_times.len() {
-                let bump_start = match i {
-                    0 => None,
-                    _ => Some(calc_times[i-1]),
-                };
-                let bump_end = Some(calc_times[i]);
-                // bump the curve with the limit of the scope of mutable borrow
-                {
-                    (*self.zero_curves.get(curve_code)
-                        .with_context(|| anyhow!(
-                            "({}:{}) no zero curve: {}\n{}", 
-                            file!(), line!(),
-                            curve_code, self.msg_tag,))?)
-                        .as_ref()
-                        .borrow_mut()
-                        .bump_time_interval(bump_start, bump_end, bump_val)?;
-                }
-                // 
-                npvs_up = self.get_npvs().context("failed to get npvs")?;
-                for inst in &self.instruments_in_action {
-                    let inst_code = inst.get_code();
-                    let unitamt = inst.get_unit_notional();
-                    npv_up = npvs_up.get(inst_code).context("failed to get npv_up in rho-structure calculation")?.clone();
-                    npv = self.calculation_results.get(inst_code)
-                        .context("failed to get npv in rho-structure calculation")?
-                        .borrow().get_npv_result()
-                        .context("failed to get npv_result in rho-structure calculation")?.get_npv();
-
-                    val = (npv_up - npv) / bump_val * RHO_PNL_UNIT * unitamt;
-                    single_rho_structure.get_mut(inst_code)
-                        .context("failed to get single_rho_structure")?[i] = val;
-                }
-                // put back
-                {
-                    (*self.zero_curves.get(curve_code)
-                        .with_context(|| anyhow!(
-                            "({}:{}) no zero curve: {}\n{}", 
-                            file!(), line!(),
-                            curve_code, self.msg_tag,))?)
-                        .as_ref()
-                        .borrow_mut()
-                        .bump_time_interval(bump_start, bump_end, -bump_val)?;
-                }
-               
-                // if there is no instrument over the calc_tenors, we do not need to calculate the next bump
-                let inst_over_bump_end = self.instruments
-                    .instruments_with_maturity_over(
-                        Some(&self.instruments_in_action), 
-                        &calc_dates[i],
-                        Some(exclude_type_clone.clone()),
-                    );
-                if inst_over_bump_end.is_empty() {
-                    break;
-                }
-
+            // if there is no instrument over a tenor, we do not need to calculate the next tenors
+            let tenor_count = calc_dates.iter()
+                .position(|date| self.instruments
+                    .instruments_with_maturity_over(Some(&insts), date, Some(exclude_type.clone()))
+                    .is_empty())
+                .map_or(calc_dates.len(), |i| i + 1);
+            let inst_codes = self.instruments.get_all_inst_code_clone(Some(&insts));
+            // bump zero_curve by bump_time_interval where calc_times[i-1] < time <= calc_times[i]
+            for i in 0..tenor_count {
+                jobs.push(BumpJob::new(
+                    inst_codes.clone(),
+                    vec![Bump::ZeroCurve {
+                        code: curve_code.clone(),
+                        start: if i == 0 { None } else { Some(calc_times[i - 1]) },
+                        end: Some(calc_times[i]),
+                        value: bump_val,
+                    }],
+                ));
             }
+            targets.push((curve_code.clone(), insts, tenor_count));
+        }
 
-            for (inst_code, rho_structure) in single_rho_structure.iter() {
-                (*self.calculation_results
+        let npvs = self.get_npvs_of_bump_jobs(&jobs).context("failed to get npvs")?;
+        let mut npvs = npvs.into_iter();
+        for (curve_code, insts, tenor_count) in targets.into_iter() {
+            let npvs_up = npvs.by_ref().take(tenor_count).collect::<Vec<_>>();
+            for inst in insts.iter() {
+                let inst_code = inst.get_code();
+                let unitamt = inst.get_unit_notional();
+                let npv = self.get_npv_of_result(inst_code)?;
+                let mut rho_structure = vec![0.0; calc_dates.len()];
+                for (i, npvs_up) in npvs_up.iter().enumerate() {
+                    let npv_up = npvs_up.get(inst_code)
+                        .context("failed to get npv_up in rho-structure calculation")?;
+                    rho_structure[i] = (npv_up - npv) / bump_val * RHO_PNL_UNIT * unitamt;
+                }
+                self.calculation_results
                     .get(inst_code)
                     .with_context(|| anyhow!(
                         "({}:{}) failed to get result of {}",
                         file!(), line!(), inst_code,
                     ))?
-                ).borrow_mut()
-                .set_single_rho_structure(curve_code, rho_structure.clone());
+                    .borrow_mut()
+                    .set_single_rho_structure(&curve_code, rho_structure);
             }
         }
         Ok(())
     }
 
     pub fn set_div_structure(&mut self) -> Result<()> {
-        //let all_dividend_codes = self.instruments.get_all_underlying_codes();
-        let all_dividend_codes = self.dividends.keys().collect::<Vec<&String>>();
+        let eval_dt = self.evaluation_date.borrow().get_date_clone();
         let bump_val = self.calculation_configuration.get_div_bump_value();
         let calc_tenors = self.calculation_configuration.get_div_structure_tenors();
-        let tenor_length = calc_tenors.len();
         let calc_dates = calc_tenors.iter()
-            .map(|tenor| add_period(
-                &self.evaluation_date.borrow().get_date_clone(), 
-                tenor.as_str(),
-            )).collect::<Vec<_>>();
-        
-        // instrument code (String) -> npv (Real)
-        let mut npvs_up: HashMap::<String, Real>;
-        let mut npv_up: Real;
-        let mut npv: Real;
-        // inst code (String) -> Vec<Real>
-        let mut single_div_structure: HashMap<String, Vec<Real>>;
-        let mut val: Real;
+            .map(|tenor| add_period(&eval_dt, tenor.as_str()))
+            .collect::<Vec<_>>();
         let exclude_type = vec!["Stock", "Cash"];
-        let exclude_type_clone = exclude_type.clone();
-
-        for div_code in all_dividend_codes {
-            // reset instruments
-            self.instruments_in_action = self.instruments
-                .instruments_with_underlying(div_code, Some(exclude_type_clone.clone()));
 
-            if self.instruments_in_action.is_empty() {
+        // (dividend code, instruments, number of the tenors calculated)
+        let mut targets = Vec::new();
+        let mut jobs = Vec::new();
+        for div_code in self.dividends.keys() {
+            let insts = self.instruments.instruments_with_underlying(div_code, Some(exclude_type.clone()));
+            if insts.is_empty() {
                 continue;
             }
-            // initialize the single_div_structure. insert the inst code and zero vector
-            let inst_codes_in_action = self.instruments.get_all_inst_code_clone(Some(&self.instruments_in_action));
+            // if there is no instrument over a tenor, we do not need to calculate the next tenors
+            let tenor_count = calc_dates.iter()
+                .position(|date| self.instruments
+                    .instruments_with_maturity_over(Some(&insts), date, Some(exclude_type.clone()))
+                    .is_empty())
+                .map_or(calc_dates.len(), |i| i + 1);
+            let inst_codes = self.instruments.get_all_inst_code_clone(Some(&insts));
+            // bump dividend by bump_date_interval where calc_dates[i-1] < date <= calc_dates[i]
+            for i in 0..tenor_count {
+                jobs.push(BumpJob::new(
+                    inst_codes.clone(),
+                    vec![Bump::Dividend {
+                        code: div_code.clone(),
+                        start: if i == 0 { None } else { Some(calc_dates[i - 1]) },
+                        end: Some(calc_dates[i]),
+                        value: bump_val,
+                    }],
+                ));
+            }
+            targets.push((div_code.clone(), insts, tenor_count));
+        }
+
+        let npvs = self.get_npvs_of_bump_jobs(&jobs)?;
+        let mut npvs = npvs.into_iter();
+        for (div_code, insts, tenor_count) in targets.into_iter() {
+            let npvs_up = npvs.by_ref().take(tenor_count).collect::<Vec<_>>();
+            for inst in insts.iter() {
+                let inst_code = inst.get_code();
+                let unitamt = inst.get_unit_notional();
+                let npv = self.get_npv_of_result(inst_code)?;
+                let mut div_structure = vec![0.0; calc_dates.len()];
+                for (i, npvs_up) in npvs_up.iter().enumerate() {
+                    let npv_up = npvs_up.get(inst_code)
+                        .context("failed to get npv_up in div-structure calculation")?;
+                    div_structure[i] = (npv_up - npv) / bump_val * DIV_PNL_UNIT * unitamt;
+                }
+                self.calculation_results.get(inst_code)
+                    .context("failed to get result")?
+                    .borrow_mut()
+                    .set_single_div_structure(&div_code, div_structure);
+            }
+        }
+        Ok(())
+    }
 
-            let init_vec: Vec<Vec<Real>> = vec![vec![0.0; tenor_length];inst_codes_in_action.len()];
-            single_div_structure = inst_codes_in_action.into_iter().zip(init_vec.into_iter()).collect();
+    /// Apply the shocks of the scenario to the market data held by the engine.
+    /// The shocked data are backed up so that Engine::restore_scenario puts them back exactly.
+    /// Shocks on data which the engine does not hold are ignored.
+    /// If a shock fails, the data are restored before returning the error.
+    pub fn apply_scenario(&mut self, scenario: &Scenario) -> Result<()> {
+        if let Some(backup) = self.scenario_backup.as_ref() {
+            bail!(
+                "({}:{}) scenario {} is already applied. restore it before applying {}\n{}",
+                file!(), line!(), backup.name, scenario.get_name(), self.msg_tag
+            );
+        }
+        scenario.validate()?;
+        self.scenario_backup = Some(ScenarioBackup::new(scenario.get_name()));
+        self.applied_scenario = Some(scenario.clone());
+
+        if let Err(error) = self.apply_scenario_shocks(scenario) {
+            self.restore_scenario()?;
+            return Err(error.context(anyhow!(
+                "({}:{}) failed to apply scenario {}\n{}",
+                file!(), line!(), scenario.get_name(), self.msg_tag
+            )));
+        }
+        Ok(())
+    }
+
+    fn apply_scenario_shocks(&mut self, scenario: &Scenario) -> Result<()> {
+        let eval_dt = self.evaluation_date.borrow().get_date_clone();
+        let time_calculator = NullCalendar::default();
+        let tenor_date = |tenor: &Option<String>| tenor.as_ref().map(|tenor| add_period(&eval_dt, tenor));
+        let backup = self.scenario_backup.as_mut()
+            .ok_or_else(|| anyhow!("({}:{}) scenario backup is not set", file!(), line!()))?;
+
+        for shock in scenario.equity_shocks.iter() {
+            let equity = match self.equities.get(&shock.code) {
+                Some(equity) => equity,
+                None => continue,
+            };
+            let price = equity.borrow().get_value();
+            backup.equities.entry(shock.code.clone()).or_insert(price);
+            let shocked = match shock.shock_type {
+                ShockType::Relative => price * (1.0 + shock.value),
+                ShockType::Absolute => price + shock.value,
+            };
+            if shocked <= 0.0 {
+                bail!(
+                    "({}:{}) shocked price of {} is not positive: {} -> {}",
+                    file!(), line!(), shock.code, price, shocked
+                );
+            }
+            equity.borrow_mut().set_price(shocked);
+        }
 
-            // bump dividend by bump_date_interval where calc_dates[i] < date <= calc_dates[i+1]
-            for i in 0..calc_dates.len() {
-                let bump_start = match i {
-                    0 => None,
-                    _ => Some(&calc_dates[i-1]),
+        for shock in scenario.fx_shocks.iter() {
+            let fx_code = FxCode::from(shock.fx_code.as_str());
+            for (code, reciprocal) in [(fx_code, false), (fx_code.reciprocal(), true)] {
+                let fx = match self.fxs.get(&code) {
+                    Some(fx) => fx,
+                    None => continue,
                 };
-                let bump_end = Some(&calc_dates[i]);
-                {            
-                    self.dividends
-                        .get(div_code)
-                        .unwrap()
-                        .clone()
-                        .unwrap()
-                        .borrow_mut()
-                        .bump_date_interval(bump_start, bump_end, bump_val)?;
-                }
-                
-                // 
-                npvs_up = self.get_npvs()?;
-                for inst in &self.instruments_in_action {
-                    let inst_code = inst.get_code();
-                    let unitamt = inst.get_unit_notional();
-                    npv_up = npvs_up.get(inst_code).context("failed to get npv_up in div-structure calculation")?.clone();
-                    npv = self.calculation_results.get(inst_code)
-                        .context("failed to get npv in div-structure calculation")?
-                        .borrow().get_npv_result()
-                        .context("failed to get npv_result in div-structure calculation")?.get_npv();
-
-                    val = (npv_up - npv) / bump_val * DIV_PNL_UNIT * unitamt;
-                    single_div_structure.get_mut(inst_code)
-                        .context("failed to get single_div_structure")?[i] = val;
+                let rate = fx.borrow().get_value();
+                backup.fxs.entry(code).or_insert(rate);
+                // the shock is given on fx_code, so the reciprocal pair is shocked as 1 / (shocked fx_code)
+                let quoted = if reciprocal { 1.0 / rate } else { rate };
+                let shocked = match shock.shock_type {
+                    ShockType::Relative => quoted * (1.0 + shock.value),
+                    ShockType::Absolute => quoted + shock.value,
+                };
+                if shocked <= 0.0 {
+                    bail!(
+                        "({}:{}) shocked fx rate of {} is not positive: {} -> {}",
+                        file!(), line!(), shock.fx_code, quoted, shocked
+                    );
                 }
+                fx.borrow_mut().set_price(if reciprocal { 1.0 / shocked } else { shocked });
+            }
+        }
 
-                {            
-                    self.dividends
-                        .get(div_code)
-                        .unwrap()
-                        .clone()
-                        .unwrap()
-                        .borrow_mut()
-                        .bump_date_interval(bump_start, bump_end, -bump_val)?;
-                }
+        for shock in scenario.curve_shocks.iter() {
+            let curve = match self.zero_curves.get(&shock.curve_name) {
+                Some(curve) => curve,
+                None => continue,
+            };
+            backup.zero_curves.entry(shock.curve_name.clone())
+                .or_insert_with(|| curve.borrow().clone());
+            let start = tenor_date(&shock.start_tenor);
+            let end = tenor_date(&shock.end_tenor);
+            curve.borrow_mut().bump_date_interval(start.as_ref(), end.as_ref(), shock.value)?;
+        }
 
-                // if there is no instrument over the calc_tenors, we do not need to calculate the next bump
-                let inst_over_bump_end = self.instruments
-                    .instruments_with_maturity_over(
-                        Some(&self.instruments_in_action), 
-                        &calc_dates[i],
-                        Some(exclude_type_clone.clone()));
+        for shock in scenario.volatility_shocks.iter() {
+            let volatility = match self.volatilities.get(&shock.code) {
+                Some(volatility) => volatility,
+                None => continue,
+            };
+            backup.volatilities.entry(shock.code.clone())
+                .or_insert_with(|| volatility.borrow().clone());
+            let start = tenor_date(&shock.start_tenor)
+                .map(|dt| time_calculator.get_time_difference(&eval_dt, &dt));
+            let end = tenor_date(&shock.end_tenor)
+                .map(|dt| time_calculator.get_time_difference(&eval_dt, &dt));
+            volatility.borrow_mut().bump_volatility(
+                start, end,
+                shock.left_spot_moneyness, shock.right_spot_moneyness,
+                shock.value,
+            )?;
+        }
 
-                if inst_over_bump_end.is_empty() {
+        for shock in scenario.dividend_shocks.iter() {
+            let dividend = match self.dividends.get(&shock.code) {
+                Some(Some(dividend)) => dividend,
+                _ => continue,
+            };
+            backup.dividends.entry(shock.code.clone())
+                .or_insert_with(|| dividend.borrow().clone());
+            match shock.shock_type {
+                ShockType::Relative => dividend.borrow_mut().scale_date_interval(None, None, shock.value)?,
+                ShockType::Absolute => dividend.borrow_mut().bump_date_interval(None, None, shock.value)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Put back the market data shocked by Engine::apply_scenario. It does nothing if no scenario is applied.
+    pub fn restore_scenario(&mut self) -> Result<()> {
+        self.applied_scenario = None;
+        match self.scenario_backup.take() {
+            Some(backup) => self.restore_market_data(backup),
+            None => Ok(()),
+        }
+    }
+
+    /// put back the market data in the backup
+    fn restore_market_data(&self, backup: ScenarioBackup) -> Result<()> {
+        for (code, price) in backup.equities {
+            self.equities.get(&code)
+                .ok_or_else(|| anyhow!("({}:{}) there is no equity {}", file!(), line!(), code))?
+                .borrow_mut()
+                .set_price(price);
+        }
+        for (code, rate) in backup.fxs {
+            self.fxs.get(&code)
+                .ok_or_else(|| anyhow!("({}:{}) there is no fx {}", file!(), line!(), code))?
+                .borrow_mut()
+                .set_price(rate);
+        }
+        for (name, curve) in backup.zero_curves {
+            *self.zero_curves.get(&name)
+                .ok_or_else(|| anyhow!("({}:{}) no zero curve: {}", file!(), line!(), name))?
+                .borrow_mut() = curve;
+        }
+        for (code, volatility) in backup.volatilities {
+            *self.volatilities.get(&code)
+                .ok_or_else(|| anyhow!("({}:{}) volatility {} is not set", file!(), line!(), code))?
+                .borrow_mut() = volatility;
+        }
+        for (code, dividend) in backup.dividends {
+            *self.dividends.get(&code)
+                .and_then(|dividend| dividend.as_ref())
+                .ok_or_else(|| anyhow!("({}:{}) dividend {} is not set", file!(), line!(), code))?
+                .borrow_mut() = dividend;
+        }
+        for (fx_code, volatility) in backup.fx_volatilities {
+            *self.fx_volatility(&fx_code.to_string())?.1.borrow_mut() = volatility;
+        }
+        for (key, quanto) in backup.quantos {
+            *self.quantos.get(&key)
+                .ok_or_else(|| anyhow!("({}:{}) there is no quanto for {:?}", file!(), line!(), key))?
+                .borrow_mut() = quanto;
+        }
+        Ok(())
+    }
+
+    /// Values (npv * unit_notional) of the instruments alive after date under each of the scenarios,
+    /// where the evaluation date is moved to date. The curves keep their rates on the tenors (rolled curves),
+    /// and the equities are deducted by the dividends in between as in the theta calculation.
+    /// The evaluation date and the market data are put back before returning.
+    pub fn get_values_on_date(
+        &mut self,
+        date: &OffsetDateTime,
+        scenarios: &[Scenario],
+    ) -> Result<Vec<HashMap<String, Real>>> {
+        let original_evaluation_date = self.evaluation_date.borrow().get_date_clone();
+        self.instruments_in_action = self.instruments.get_instruments_clone().into_iter()
+            .filter(|inst| inst.get_maturity().is_none_or(|maturity| maturity.date() > date.date()))
+            .collect();
+        { self.evaluation_date.borrow_mut().set_date(*date); }
+
+        let mut res = Vec::with_capacity(scenarios.len());
+        let mut error = None;
+        for scenario in scenarios.iter() {
+            if let Err(e) = self.apply_scenario(scenario) {
+                error = Some(e);
+                break;
+            }
+            let npvs = self.get_npvs();
+            self.restore_scenario()?;
+            match npvs {
+                Ok(npvs) => {
+                    let values = self.instruments_in_action.iter()
+                        .filter_map(|inst| npvs.get(inst.get_code())
+                            .map(|npv| (inst.get_code().clone(), npv * inst.get_unit_notional())))
+                        .collect();
+                    res.push(values);
+                },
+                Err(e) => {
+                    error = Some(e.context(anyhow!(
+                        "({}:{}) failed to get npvs in scenario {} on {}",
+                        file!(), line!(), scenario.get_name(), date
+                    )));
                     break;
-                }
+                },
             }
-            for (inst_code, div_structure) in single_div_structure.iter() {
+        }
+
+        { self.evaluation_date.borrow_mut().set_date(original_evaluation_date); }
+        self.reset_instruments_in_action();
+        match error {
+            Some(e) => Err(e),
+            None => Ok(res),
+        }
+    }
+
+    /// For each scenario in CalculationConfiguration, 
+    /// scenario pnl = (npv under the scenario - npv) * unit_notional for all instruments
+    pub fn set_scenario_pnls(&mut self) -> Result<()> {
+        self.reset_instruments_in_action();
+        let scenarios = self.calculation_configuration.get_scenarios().clone();
+        for scenario in scenarios.iter() {
+            self.apply_scenario(scenario)?;
+            let npvs = self.get_npvs();
+            self.restore_scenario()?;
+            let npvs = npvs.with_context(|| anyhow!(
+                "({}:{}) failed to get npvs in scenario {}", file!(), line!(), scenario.get_name()))?;
+
+            for inst in &self.instruments_in_action {
+                let inst_code = inst.get_code();
+                let unitamt = inst.get_unit_notional();
+                let npv_shocked = npvs.get(inst_code)
+                    .ok_or_else(|| anyhow!("npv is not set for {} in scenario {}", inst_code, scenario.get_name()))?;
+                let npv = self.calculation_results
+                    .get(inst_code)
+                    .ok_or_else(|| anyhow!("result is not set"))?
+                    .borrow()
+                    .get_npv_result()
+                    .ok_or_else(|| anyhow!("npv is not set"))?
+                    .get_npv();
+
+                (*self.calculation_results
+                    .get(inst_code)
+                    .ok_or_else(|| anyhow!(
+                        "({}:{}) result is not set for {}",
+                        file!(), line!(), inst_code,
+                    ))?)
+                    .borrow_mut()
+                    .set_single_scenario_pnl(scenario.get_name(), (npv_shocked - npv) * unitamt);
+            }
+        }
+        Ok(())
+    }
+
+    /// For each curvature scenario in CalculationConfiguration,
+    /// curvature pnl = (npv under the up (down) scenario - npv) * unit_notional for all instruments.
+    /// A risk factor which the instruments of the engine do not depend on has zero pnls.
+    pub fn set_curvature_pnls(&mut self) -> Result<()> {
+        self.reset_instruments_in_action();
+        let curvature_scenarios = self.calculation_configuration.get_curvature_scenarios().clone();
+        for curvature_scenario in curvature_scenarios.iter() {
+            curvature_scenario.validate()?;
+            let mut shocked_npvs = Vec::with_capacity(2);
+            for scenario in [&curvature_scenario.up, &curvature_scenario.down] {
+                self.apply_scenario(scenario)?;
+                let npvs = self.get_npvs();
+                self.restore_scenario()?;
+                shocked_npvs.push(npvs.with_context(|| anyhow!(
+                    "({}:{}) failed to get npvs in curvature scenario {} of {}",
+                    file!(), line!(), scenario.get_name(), curvature_scenario.get_risk_factor()
+                ))?);
+            }
+
+            for inst in self.instruments_in_action.iter() {
+                let inst_code = inst.get_code();
+                let unitamt = inst.get_unit_notional();
+                let npv = self.get_npv_of_result(inst_code)?;
+                let pnl = |npvs: &HashMap<String, Real>| -> Result<Real> {
+                    let shocked = npvs.get(inst_code).copied()
+                        .ok_or_else(|| anyhow!("({}:{}) shocked npv is not set for {}", file!(), line!(), inst_code))?;
+                    Ok((shocked - npv) * unitamt)
+                };
+                let (up, down) = (pnl(&shocked_npvs[0])?, pnl(&shocked_npvs[1])?);
                 self.calculation_results.get(inst_code)
-                    .context("failed to get result")?
+                    .ok_or_else(|| anyhow!("({}:{}) result is not set for {}", file!(), line!(), inst_code))?
                     .borrow_mut()
-                    .set_single_div_structure(div_code, div_structure.clone());
+                    .set_single_curvature_pnl(curvature_scenario.get_risk_factor(), up, down);
             }
         }
         Ok(())
     }
 
+    /// Apply the market data updates to the parameters in place, so that the engine is repriced
+    /// without being built again (see EngineGenerator::update_market_data).
+    /// A spot update also sets the dividend of the stock again as the dividend ratios are taken against the spot,
+    /// and the volatility of an underlying is built again once for all its volatility updates.
+    /// The updates of the data which the parameters do not use only change the data kept for the replicas.
+    /// If it fails, the parameters may be partially updated, so the engine must not be used anymore.
+    pub fn update_market_data(&mut self, updates: &[MarketDataUpdate]) -> Result<()> {
+        if self.scenario_backup.is_some() {
+            bail!(
+                "({}:{}) the market data can not be updated while a scenario is applied\n{}",
+                file!(), line!(), self.msg_tag
+            );
+        }
+        let mut data = self.parameter_data.clone()
+            .ok_or_else(|| anyhow!(
+                "({}:{}) the market data update requires the parameters given by with_parameter_data\n{}",
+                file!(), line!(), self.msg_tag
+            ))?;
+        for update in updates.iter() {
+            update.apply_to(
+                &mut data.fx_data,
+                &mut data.stock_data,
+                &mut data.curve_data,
+                &mut data.equity_constant_volatility_data,
+                &mut data.equity_volatility_surface_data,
+            )?;
+        }
+        // the parameters are set once per code however many ticks of the code are in updates
+        let mut spot_codes = HashSet::<&String>::new();
+        let mut fx_codes = HashSet::<&FxCode>::new();
+        let mut curve_codes = HashSet::<&String>::new();
+        let mut volatility_codes = HashSet::<&String>::new();
+        for update in updates.iter() {
+            match update {
+                MarketDataUpdate::Spot { code, .. } => { spot_codes.insert(code); },
+                MarketDataUpdate::Fx { fx_code, .. } => { fx_codes.insert(fx_code); },
+                MarketDataUpdate::CurvePoint { code, .. } => { curve_codes.insert(code); },
+                MarketDataUpdate::VolatilityPoint { code, .. }
+                | MarketDataUpdate::ConstantVolatility { code, .. } => { volatility_codes.insert(code); },
+            }
+        }
+        for code in spot_codes {
+            let Some(equity) = self.equities.get(code) else { continue };
+            let spot = data.stock_data[code].get_value();
+            equity.borrow_mut().set_price(spot);
+            if let (Some(Some(dividend)), Some(dividend_data)) = (self.dividends.get(code), data.dividend_data.get(code)) {
+                *dividend.borrow_mut() = DiscreteRatioDividend::new(
+                    self.evaluation_date.clone(),
+                    dividend_data,
+                    spot,
+                    code.clone(),
+                    code.clone(),
+                )?;
+            }
+        }
+        for (pricing_code, fx) in self.fxs.iter() {
+            if fx_codes.iter().any(|fx_code| fx_rate_depends_on(pricing_code, fx_code)) {
+                let rate = resolve_fx_rate(
+                    |code| data.fx_data.get(code).map(|data| data.get_value()),
+                    *pricing_code.get_currency1(),
+                    *pricing_code.get_currency2(),
+                )?;
+                fx.borrow_mut().set_price(rate);
+            }
+        }
+        for code in curve_codes {
+            let Some(zero_curve) = self.zero_curves.get(code) else { continue };
+            *zero_curve.borrow_mut() = ZeroCurve::new(
+                self.evaluation_date.clone(),
+                &data.curve_data[code],
+                code.clone(),
+                code.clone(),
+            )?;
+        }
+        // the volatilities are built last on the updated spots and curves
+        for code in volatility_codes {
+            let Some(volatility) = self.volatilities.get(code) else { continue };
+            let updated = self.build_equity_volatility(
+                code,
+                &self.equities,
+                &self.zero_curves,
+                &data.equity_constant_volatility_data,
+                &data.equity_volatility_surface_data,
+            )?;
+            *volatility.borrow_mut() = updated;
+        }
+        self.parameter_data = Some(data);
+        Ok(())
+    }
+
+    /// Calculate only the instruments of inst_codes in the same way as calculate, e.g.,
+    /// the instruments affected by update_market_data, and return their results. The other results are not changed.
+    pub fn calculate_instruments(&mut self, inst_codes: &[String]) -> Result<HashMap<String, CalculationResult>> {
+        if inst_codes.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let codes = inst_codes.iter().collect::<HashSet<&String>>();
+        let selected: Vec<Rc<Instrument>> = self.instruments.iter()
+            .filter(|inst| codes.contains(inst.get_code()))
+            .cloned()
+            .collect();
+        if selected.len() != codes.len() {
+            bail!(
+                "({}:{}) some of {:?} are not in the engine\n{}",
+                file!(), line!(), inst_codes, self.msg_tag
+            );
+        }
+        // the instruments and the results are narrowed to the selected ones during the calculation
+        let (selected_results, other_results): (HashMap<_, _>, HashMap<_, _>) = std::mem::take(&mut self.calculation_results)
+            .into_iter()
+            .partition(|(code, _)| codes.contains(code));
+        self.calculation_results = selected_results;
+        let instruments = std::mem::replace(&mut self.instruments, Instruments::new(selected));
+        self.reset_instruments_in_action();
+        let calculated = self.calculate();
+        self.instruments = instruments;
+        self.reset_instruments_in_action();
+        let selected_results = std::mem::replace(&mut self.calculation_results, other_results);
+        let results = selected_results.iter()
+            .map(|(code, result)| (code.clone(), result.borrow().clone()))
+            .collect();
+        self.calculation_results.extend(selected_results);
+        calculated?;
+        Ok(results)
+    }
+
     pub fn calculate(&mut self) -> Result<()>{
         // enter new span
         let span = tracing::span!(Level::INFO, "calculate", engine_id = self.engine_id.clone());
@@ -1753,6 +2551,28 @@ impl Engine {
                 format_duration(start_time.elapsed().as_secs_f64()),
             );
         }
+
+        if self.calculation_configuration.get_implied_volatility_calculation() {
+            timer = std::time::Instant::now();
+            self.set_implied_volatilities()?;
+            info!(
+                "* implied volatility calculation is done (engine id: {}, time = {} whole time elapsed: {})", 
+                self.engine_id, 
+                format_duration(timer.elapsed().as_secs_f64()),
+                format_duration(start_time.elapsed().as_secs_f64()),
+            );
+        }
+
+        if self.calculation_configuration.get_bond_analytics_calculation() {
+            timer = std::time::Instant::now();
+            self.set_bond_analytics()?;
+            info!(
+                "* bond analytics calculation is done (engine id: {}, time = {} whole time elapsed: {})", 
+                self.engine_id, 
+                format_duration(timer.elapsed().as_secs_f64()),
+                format_duration(start_time.elapsed().as_secs_f64()),
+            );
+        }
         
         if self.calculation_configuration.get_delta_calculation() { 
             timer = std::time::Instant::now();
@@ -1766,6 +2586,28 @@ impl Engine {
             );
         }
 
+        if self.calculation_configuration.get_cross_gamma_calculation() {
+            timer = std::time::Instant::now();
+            self.set_cross_gamma()?;
+            info!(
+                "* cross gamma calculation is done (engine id: {}, time = {} whole time elapsed: {})", 
+                self.engine_id, 
+                format_duration(timer.elapsed().as_secs_f64()),
+                format_duration(start_time.elapsed().as_secs_f64()),
+            );
+        }
+
+        if self.calculation_configuration.get_fx_delta_calculation() || self.calculation_configuration.get_fx_gamma_calculation() {
+            timer = std::time::Instant::now();
+            self.set_fx_delta_gamma()?;
+            info!(
+                "* fx delta and gamma calculation is done (engine id: {}, time = {} whole time elapsed: {})", 
+                self.engine_id, 
+                format_duration(timer.elapsed().as_secs_f64()),
+                format_duration(start_time.elapsed().as_secs_f64()),
+            );
+        }
+
         if self.calculation_configuration.get_theta_calculation() {
             timer = std::time::Instant::now();
             let exclude_type = vec!["Cash", "Stock"];
@@ -1838,6 +2680,28 @@ impl Engine {
             );
         }
 
+        if self.calculation_configuration.get_vanna_calculation() || self.calculation_configuration.get_volga_calculation() {
+            timer = std::time::Instant::now();
+            self.set_vanna_volga()?;
+            info!(
+                "* vanna and volga calculation is done (engine id: {}, time = {} whole time elapsed: {})", 
+                self.engine_id, 
+                format_duration(timer.elapsed().as_secs_f64()),
+                format_duration(start_time.elapsed().as_secs_f64()),
+            );
+        }
+
+        if self.calculation_configuration.get_fx_vega_calculation() || self.calculation_configuration.get_quanto_correlation_calculation() {
+            timer = std::time::Instant::now();
+            self.set_fx_vega_quanto_correlation()?;
+            info!(
+                "* fx vega and quanto correlation calculation is done (engine id: {}, time = {} whole time elapsed: {})", 
+                self.engine_id, 
+                format_duration(timer.elapsed().as_secs_f64()),
+                format_duration(start_time.elapsed().as_secs_f64()),
+            );
+        }
+
         if self.calculation_configuration.get_rho_calculation() {
             timer = std::time::Instant::now();
             self.set_rho()?;
@@ -1903,6 +2767,28 @@ impl Engine {
                 format_duration(start_time.elapsed().as_secs_f64()),
             );
         }
+
+        if self.calculation_configuration.get_scenario_calculation() {
+            timer = std::time::Instant::now();
+            self.set_scenario_pnls()?;
+            info!(
+                "* scenario calculation is done (engine id: {}, time = {} whole time elapsed: {})", 
+                self.engine_id, 
+                format_duration(timer.elapsed().as_secs_f64()),
+                format_duration(start_time.elapsed().as_secs_f64()),
+            );
+        }
+
+        if self.calculation_configuration.get_curvature_calculation() {
+            timer = std::time::Instant::now();
+            self.set_curvature_pnls()?;
+            info!(
+                "* curvature calculation is done (engine id: {}, time = {} whole time elapsed: {})", 
+                self.engine_id, 
+                format_duration(timer.elapsed().as_secs_f64()),
+                format_duration(start_time.elapsed().as_secs_f64()),
+            );
+        }
         Ok(())
     }
 
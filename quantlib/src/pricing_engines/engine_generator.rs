@@ -13,20 +13,32 @@ use crate::pricing_engines::{
     calculation_result::CalculationResult,
     match_parameter::MatchParameter,
     engine::Engine,
+    engine_worker::EngineWorker,
+    error_report::{ErrorReport, InstrumentError, InstrumentErrorKind},
+    market_data_manifest::MarketDataManifest,
+    market_data_update::{CalculationResultDelta, MarketDataUpdate},
+    implied_volatility::{ImpliedVolatilityQuote, build_implied_volatility_surface},
+    scenario::Scenario,
 };
+use crate::definitions::Real;
+use crate::enums::RiskClass;
+use crate::time::datetimegrid::DateTimeGrid;
 use crate::data::{
     value_data::ValueData,
     vector_data::VectorData,
     surface_data::SurfaceData,
     daily_value_data::DailyValueData,
+    market_data_snapshot::{MarketDataCategory, MarketDataSnapshot},
 };
 //
 use std::{
     sync::{
         Arc,
         Mutex,
+        mpsc::{channel, Receiver, Sender},
     },
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    rc::Rc,
     //thread,
 };
 use serde::{Deserialize, Serialize};
@@ -100,6 +112,79 @@ impl InstrumentCategory {
     }
 }
 
+/// fx rate of currency1 in currency2 by rate_of (the quoted rates): direct, reciprocal, or through KRW
+pub fn resolve_fx_rate<F>(rate_of: F, currency1: Currency, currency2: Currency) -> Result<Real>
+where F: Fn(&FxCode) -> Option<Real>
+{
+    if currency1 == currency2 {
+        return Ok(1.0);
+    }
+    let fx_code = FxCode::new(currency1, currency2);
+    if let Some(rate) = rate_of(&fx_code) {
+        return Ok(rate);
+    }
+    if let Some(rate) = rate_of(&fx_code.reciprocal()) {
+        return Ok(1.0 / rate);
+    }
+    if let (Some(rate1), Some(rate2)) = (
+        rate_of(&FxCode::new(currency1, Currency::KRW)),
+        rate_of(&FxCode::new(currency2, Currency::KRW)),
+    ) {
+        return Ok(rate1 / rate2);
+    }
+    bail!("({}:{}) failed to get fx rate for {}", file!(), line!(), fx_code)
+}
+
+/// The inputs of the engines of the instrument groups. The data are shared by the engines.
+#[derive(Clone)]
+struct EngineData {
+    configuration: CalculationConfiguration,
+    evaluation_datetime: OffsetDateTime,
+    match_parameter: MatchParameter,
+    option_prices: Arc<HashMap<String, Real>>,
+    fx_data: Arc<HashMap<FxCode, ValueData>>,
+    stock_data: Arc<HashMap<String, ValueData>>,
+    curve_data: Arc<HashMap<String, VectorData>>,
+    dividend_data: Arc<HashMap<String, VectorData>>,
+    equity_constant_volatility_data: Arc<HashMap<String, ValueData>>,
+    equity_volatility_surface_data: Arc<HashMap<String, SurfaceData>>,
+    fx_constant_volatility_data: Arc<HashMap<FxCode, ValueData>>,
+    quanto_correlation_data: Arc<HashMap<(String, FxCode), ValueData>>,
+    past_daily_value_data: Arc<HashMap<String, DailyValueData>>,
+}
+
+/// The engine of the instruments with the pricers initialized, or the failed step and the error
+fn build_engine(
+    engine_id: usize,
+    instruments: Vec<Instrument>,
+    data: &EngineData,
+) -> std::result::Result<Engine, (InstrumentErrorKind, anyhow::Error)> {
+    let engine = Engine::builder(
+        engine_id,
+        data.configuration.clone(),
+        data.evaluation_datetime,
+        data.match_parameter.clone(),
+    )
+        .with_instruments(instruments)
+        .map_err(|e| (InstrumentErrorKind::InvalidInstrument, e))?;
+    let mut engine = engine
+        .with_option_prices(data.option_prices.clone())
+        .with_parameter_data(
+            data.fx_data.clone(),
+            data.stock_data.clone(),
+            data.curve_data.clone(),
+            data.dividend_data.clone(),
+            data.equity_constant_volatility_data.clone(),
+            data.equity_volatility_surface_data.clone(),
+            data.fx_constant_volatility_data.clone(),
+            data.quanto_correlation_data.clone(),
+            data.past_daily_value_data.clone(),
+        )
+        .map_err(|e| (InstrumentErrorKind::MarketData, e))?;
+    engine.initialize_pricers().map_err(|e| (InstrumentErrorKind::Pricer, e))?;
+    Ok(engine)
+}
+
 pub struct EngineGenerator {
     instruments: Instruments,
     instrument_group_vec: Vec<Vec<Instrument>>,
@@ -109,6 +194,14 @@ pub struct EngineGenerator {
     match_parameter: MatchParameter,
     //
     calculation_results: HashMap<String, CalculationResult>,
+    // failures are collected in error_report instead of failing the whole calculation
+    continue_on_error: bool,
+    distribution_errors: Vec<InstrumentError>,
+    error_report: ErrorReport,
+    // the deltas of update_market_data are sent to the subscribers
+    result_subscribers: Vec<Sender<Vec<CalculationResultDelta>>>,
+    // the engines of the instrument groups kept for update_market_data (group id -> worker)
+    engine_workers: HashMap<usize, EngineWorker>,
     // evaluation date
     evaluation_date: EvaluationDate,
     // data
@@ -121,6 +214,7 @@ pub struct EngineGenerator {
     fx_constant_volatility_data: Arc<HashMap<FxCode, ValueData>>,
     quanto_correlation_data: Arc<HashMap<(String, FxCode), ValueData>>,
     past_daily_value_data: Arc<HashMap<String, DailyValueData>>,
+    option_prices: Arc<HashMap<String, Real>>,
 }
 
 impl Default for EngineGenerator {
@@ -134,6 +228,11 @@ impl Default for EngineGenerator {
             match_parameter: MatchParameter::default(),
             //
             calculation_results: HashMap::new(),
+            continue_on_error: false,
+            distribution_errors: vec![],
+            error_report: ErrorReport::default(),
+            result_subscribers: vec![],
+            engine_workers: HashMap::new(),
             //
             evaluation_date: EvaluationDate::default(),
             //
@@ -146,6 +245,7 @@ impl Default for EngineGenerator {
             fx_constant_volatility_data: Arc::new(HashMap::new()),
             quanto_correlation_data: Arc::new(HashMap::new()),
             past_daily_value_data: Arc::new(HashMap::new()),
+            option_prices: Arc::new(HashMap::new()),
         }   
     }
 }
@@ -155,6 +255,24 @@ impl EngineGenerator {
         EngineGenerator::default()
     }
 
+    fn get_engine_data(&self) -> EngineData {
+        EngineData {
+            configuration: self.calculation_configuration.clone(),
+            evaluation_datetime: self.evaluation_date.get_date_clone(),
+            match_parameter: self.match_parameter.clone(),
+            option_prices: self.option_prices.clone(),
+            fx_data: self.fx_data.clone(),
+            stock_data: self.stock_data.clone(),
+            curve_data: self.curve_data.clone(),
+            dividend_data: self.dividend_data.clone(),
+            equity_constant_volatility_data: self.equity_constant_volatility_data.clone(),
+            equity_volatility_surface_data: self.equity_volatility_surface_data.clone(),
+            fx_constant_volatility_data: self.fx_constant_volatility_data.clone(),
+            quanto_correlation_data: self.quanto_correlation_data.clone(),
+            past_daily_value_data: self.past_daily_value_data.clone(),
+        }
+    }
+
     pub fn with_configuration(
         &mut self,
         calculation_configuration: CalculationConfiguration,
@@ -164,6 +282,7 @@ impl EngineGenerator {
         self.calculation_configuration = calculation_configuration;
         self.evaluation_date = EvaluationDate::new(evalutation_datetime);
         self.match_parameter = match_parameter;
+        self.engine_workers.clear();
         Ok(self)
     }
 
@@ -172,6 +291,14 @@ impl EngineGenerator {
         Ok(self)
     }
 
+    /// If set, calculate does not fail on the instruments with missing market data or failing pricers,
+    /// but reports them in get_error_report and calculates the others.
+    /// The instruments in none of the categories are also reported instead of failing distribute_instruments.
+    pub fn with_continue_on_error(&mut self, continue_on_error: bool) -> Result<&mut Self> {
+        self.continue_on_error = continue_on_error;
+        Ok(self)
+    }
+
     pub fn with_instrument_categories(&mut self, instrument_categories: Vec<InstrumentCategory>) -> Result<&mut Self> {
         self.instrument_categories = instrument_categories;
         Ok(self)
@@ -198,6 +325,28 @@ impl EngineGenerator {
         self.fx_constant_volatility_data = Arc::new(fx_constant_volatility_data);
         self.quanto_correlation_data = Arc::new(quanto_correlation_data);
         self.past_daily_value_data = Arc::new(past_daily_value_data);
+        self.engine_workers.clear();
+        Ok(self)
+    }
+
+    pub fn with_market_data_snapshot(&mut self, snapshot: MarketDataSnapshot) -> Result<&mut Self> {
+        self.with_data(
+            snapshot.get_fx_data().clone(),
+            snapshot.get_stock_data().clone(),
+            snapshot.get_curve_data().clone(),
+            snapshot.get_dividend_data().clone(),
+            snapshot.get_equity_constant_volatility_data().clone(),
+            snapshot.get_equity_volatility_surface_data().clone(),
+            snapshot.get_fx_constant_volatility_data().clone(),
+            snapshot.get_quanto_correlation_data().clone(),
+            snapshot.get_past_daily_value_data().clone(),
+        )
+    }
+
+    /// option code -> market price used when implied volatility calculation is set in the configuration
+    pub fn with_option_prices(&mut self, option_prices: HashMap<String, Real>) -> Result<&mut Self> {
+        self.option_prices = Arc::new(option_prices);
+        self.engine_workers.clear();
         Ok(self)
     }
 
@@ -219,6 +368,7 @@ impl EngineGenerator {
         }
         
         let mut inst_name_code: Vec<String> = vec![];
+        let mut distribution_errors: Vec<InstrumentError> = vec![];
         for (inst_id, is_distributed) in distribution_checker.iter().enumerate() {
             if !is_distributed {
                 let msg = format!(
@@ -233,11 +383,16 @@ impl EngineGenerator {
                     self.instruments[inst_id].get_underlying_codes(),
                 );
 
+                distribution_errors.push(InstrumentError::new(
+                    &self.instruments[inst_id],
+                    InstrumentErrorKind::Undistributed,
+                    format!("({}:{}) not in any instrument category", file!(), line!()),
+                ));
                 inst_name_code.push(msg);
             }
         }
 
-        if !inst_name_code.is_empty() {
+        if !inst_name_code.is_empty() && !self.continue_on_error {
             return Err(anyhow!(
                 "The following instruments are not distributed:\n{}",
                 inst_name_code.join("\n"),
@@ -245,46 +400,189 @@ impl EngineGenerator {
         }
 
         self.instrument_group_vec = instrument_group_vec;
+        self.distribution_errors = distribution_errors;
+        self.engine_workers.clear();
 
         Ok(())
     }
 
+    /// market data required by the instruments (see MarketDataManifest::resolve)
+    pub fn get_market_data_manifest(&self) -> Result<MarketDataManifest> {
+        MarketDataManifest::resolve(&self.instruments, &self.match_parameter, self.evaluation_date.get_date_clone())
+    }
+
+    /// Market data which the instruments need but are not in the data of self, found before any pricing.
+    /// Dividends are optional as in Engine::with_parameter_data.
+    /// The instruments with missing data are reported as MissingMarketData and
+    /// those whose curves are not found in the match parameter as InvalidInstrument.
+    pub fn validate_market_data(&self) -> ErrorReport {
+        self.validate_market_data_of(self.instruments.iter().collect())
+    }
+
+    fn validate_market_data_of(&self, instruments: Vec<&Rc<Instrument>>) -> ErrorReport {
+        let mut report = ErrorReport::default();
+        for instrument in instruments {
+            let missing = match self.get_missing_market_data_of(instrument) {
+                Ok(missing) => missing,
+                Err(e) => {
+                    report.add_instrument_error(InstrumentError::new(
+                        instrument, InstrumentErrorKind::InvalidInstrument, format!("{:#}", e),
+                    ));
+                    continue;
+                },
+            };
+            if missing.is_empty() {
+                continue;
+            }
+            let msg = format!(
+                "({}:{}) missing market data: {}",
+                file!(), line!(),
+                missing.iter().map(|(category, code)| format!("{:?} {}", category, code)).collect::<Vec<_>>().join(", "),
+            );
+            for (category, code) in missing {
+                report.add_missing_market_data(category, code, instrument.get_code());
+            }
+            report.add_instrument_error(InstrumentError::new(instrument, InstrumentErrorKind::MissingMarketData, msg));
+        }
+        report.sort();
+        report
+    }
+
+    fn get_missing_market_data_of(&self, instrument: &Rc<Instrument>) -> Result<Vec<(MarketDataCategory, String)>> {
+        let mut missing = vec![];
+        for fx_code in instrument.get_all_fxcodes_for_pricing() {
+            if self.get_fx_rate(*fx_code.get_currency1(), *fx_code.get_currency2()).is_err() {
+                missing.push((MarketDataCategory::Fx, fx_code.to_string()));
+            }
+        }
+        let single = Instruments::new(vec![instrument.clone()]);
+        for curve_name in single.get_all_curve_names(&self.match_parameter)? {
+            if !self.curve_data.contains_key(curve_name) {
+                missing.push((MarketDataCategory::Curve, curve_name.clone()));
+            }
+        }
+        for und_code in instrument.get_underlying_codes() {
+            if !self.stock_data.contains_key(und_code) {
+                missing.push((MarketDataCategory::Stock, und_code.clone()));
+            }
+            // borrowing curve
+            if !self.curve_data.contains_key(und_code) {
+                missing.push((MarketDataCategory::Curve, und_code.clone()));
+            }
+        }
+        for und_code in instrument.get_underlying_codes_requiring_volatility() {
+            if !self.equity_constant_volatility_data.contains_key(und_code)
+                && !self.equity_volatility_surface_data.contains_key(und_code) {
+                missing.push((MarketDataCategory::EquityVolatilitySurface, und_code.clone()));
+            }
+        }
+        for (und_code, fx_code) in instrument.get_quanto_fxcode_und_pair() {
+            if !self.fx_constant_volatility_data.contains_key(fx_code) {
+                missing.push((MarketDataCategory::FxConstantVolatility, fx_code.to_string()));
+            }
+            if !self.quanto_correlation_data.contains_key(&(und_code.clone(), *fx_code)) {
+                missing.push((MarketDataCategory::QuantoCorrelation, format!("{}/{}", und_code, fx_code)));
+            }
+        }
+        missing.sort();
+        missing.dedup();
+        Ok(missing)
+    }
+
+    /// The continue-on-error mode of calculate. The instruments failing validate_market_data are excluded,
+    /// and if the engine of a group fails, each instrument of the group is calculated in its own engine
+    /// so that only the failing instruments are lost.
+    fn calculate_continuing_on_error(&mut self) -> Result<()> {
+        let undistributed: HashSet<&String> = self.distribution_errors.iter()
+            .map(|error| &error.instrument_code)
+            .collect();
+        let mut report = self.validate_market_data_of(self.instruments.iter()
+            .filter(|inst| !undistributed.contains(inst.get_code()))
+            .collect());
+        for error in self.distribution_errors.iter() {
+            report.add_instrument_error(error.clone());
+        }
+        let failed: HashSet<String> = report.get_failed_instrument_codes().into_iter().cloned().collect();
+        let instrument_groups: Vec<Vec<Instrument>> = self.instrument_group_vec.iter()
+            .map(|group| group.iter()
+                .filter(|inst| !failed.contains(inst.get_code()))
+                .cloned()
+                .collect::<Vec<Instrument>>())
+            .filter(|group| !group.is_empty())
+            .collect();
+
+        let data = self.get_engine_data();
+        // results of the instruments in an engine, or the failed step and the error
+        let calculate_group = |group_id: usize, instrument_group: &[Instrument]|
+            -> std::result::Result<HashMap<String, CalculationResult>, (InstrumentErrorKind, anyhow::Error)> {
+            let mut engine = build_engine(group_id, instrument_group.to_vec(), &data)?;
+            engine.calculate().map_err(|e| (InstrumentErrorKind::Calculation, e))?;
+            Ok(engine.get_calculation_result_clone())
+        };
+
+        let group_outcomes: Vec<(HashMap<String, CalculationResult>, Vec<InstrumentError>)> = instrument_groups
+            .par_iter()
+            .enumerate()
+            .map(|(group_id, instrument_group)| {
+                match calculate_group(group_id, instrument_group) {
+                    Ok(results) => (results, vec![]),
+                    Err((_, e)) => {
+                        info!(
+                            "({}:{}) engine {} failed and its instruments are calculated one by one: {:#}",
+                            file!(), line!(), group_id, e,
+                        );
+                        let outcomes: Vec<std::result::Result<HashMap<String, CalculationResult>, InstrumentError>> = instrument_group
+                            .par_iter()
+                            .map(|instrument| calculate_group(group_id, std::slice::from_ref(instrument))
+                                .map_err(|(kind, e)| InstrumentError::new(instrument, kind, format!("{:#}", e))))
+                            .collect();
+                        let mut results = HashMap::new();
+                        let mut errors = vec![];
+                        for outcome in outcomes {
+                            match outcome {
+                                Ok(res) => results.extend(res),
+                                Err(error) => errors.push(error),
+                            }
+                        }
+                        (results, errors)
+                    },
+                }
+            }).collect();
+
+        let mut calculation_results = HashMap::new();
+        for (results, errors) in group_outcomes {
+            calculation_results.extend(results);
+            for error in errors {
+                report.add_instrument_error(error);
+            }
+        }
+        report.sort();
+        if !report.is_empty() {
+            info!(
+                "({}:{}) {} instruments failed, {} market data are missing",
+                file!(), line!(), report.instrument_errors.len(), report.missing_market_data.len(),
+            );
+        }
+        self.calculation_results = calculation_results;
+        self.error_report = report;
+        Ok(())
+    }
+
     /// spawn threads to create engine and calculate
     pub fn calculate(&mut self) -> Result<()> {
+        // the engines of update_market_data are built again from the data and the instruments not failed
+        self.engine_workers.clear();
+        if self.continue_on_error {
+            return self.calculate_continuing_on_error();
+        }
         let mut shared_results = Arc::new(Mutex::new(HashMap::<String, CalculationResult>::new()));
-        let dt = self.evaluation_date.get_date_clone();
+        let data = self.get_engine_data();
         let calc_res: Result<()> = self.instrument_group_vec.par_iter().enumerate().map(
             |(group_id, instrument_group)| {
-                let mut engine = Engine::builder(
-                    group_id,
-                    self.calculation_configuration.clone(),
-                    dt.clone(),
-                    self.match_parameter.clone(),
-                );
-        
-                let mut engine = match engine.with_instruments(instrument_group.clone()) {
-                    Ok(engine) => engine,
-                    Err(e) => return Err(e),
-                };
-        
-                let mut engine = match engine.with_parameter_data(
-                    self.fx_data.clone(),
-                    self.stock_data.clone(),
-                    self.curve_data.clone(),
-                    self.dividend_data.clone(),
-                    self.equity_constant_volatility_data.clone(),
-                    self.equity_volatility_surface_data.clone(),
-                    self.fx_constant_volatility_data.clone(),
-                    self.quanto_correlation_data.clone(),
-                    self.past_daily_value_data.clone(),
-                ){
+                let mut engine = match build_engine(group_id, instrument_group.clone(), &data) {
                     Ok(engine) => engine,
-                    Err(e) => return Err(e),
+                    Err((_, e)) => return Err(e),
                 };
-                 
-                if let Err(e) = engine.initialize_pricers() {
-                    return Err(e.into());
-                }
         
                 if let Err(e) = engine.calculate() {
                     return Err(e.into());
@@ -309,7 +607,590 @@ impl EngineGenerator {
         }
     }
 
+    /// Reprice the instruments under the scenarios in parallel.
+    /// The scenarios are split into number_of_chunks and an engine is made for each (instrument group, chunk),
+    /// so that distribute_instruments must be called before.
+    /// Only npv and scenario pnl are calculated regardless of the configuration.
+    /// It returns instrument code -> CalculationResult where the value and scenario_pnl are set.
+    pub fn calculate_scenarios(
+        &self,
+        scenarios: &[Scenario],
+        number_of_chunks: usize,
+    ) -> Result<HashMap<String, CalculationResult>> {
+        if self.instrument_group_vec.is_empty() {
+            bail!("({}:{}) instruments are not distributed", file!(), line!());
+        }
+        let chunk_size = scenarios.len().div_ceil(number_of_chunks.max(1)).max(1);
+        let chunks: Vec<&[Scenario]> = scenarios.chunks(chunk_size).collect();
+        let jobs: Vec<(usize, &Vec<Instrument>, &[Scenario])> = self.instrument_group_vec.iter()
+            .flat_map(|group| chunks.iter().map(move |chunk| (group, *chunk)))
+            .enumerate()
+            .map(|(job_id, (group, chunk))| (job_id, group, chunk))
+            .collect();
+
+        let base_data = self.get_engine_data();
+        let job_results: Result<Vec<HashMap<String, CalculationResult>>> = jobs.par_iter().map(
+            |(job_id, instrument_group, chunk)| {
+                let data = EngineData {
+                    configuration: base_data.configuration.clone()
+                        .npv_only()
+                        .with_scenario_calculation(true)
+                        .with_scenarios(chunk.to_vec()),
+                    ..base_data.clone()
+                };
+                let mut engine = build_engine(*job_id, (*instrument_group).clone(), &data).map_err(|(_, e)| e)?;
+                engine.calculate()?;
+                Ok(engine.get_calculation_result_clone())
+            }
+        ).collect();
+
+        let mut res: HashMap<String, CalculationResult> = HashMap::new();
+        for job_result in job_results? {
+            for (code, result) in job_result {
+                match res.get_mut(&code) {
+                    None => {
+                        res.insert(code, result);
+                    },
+                    Some(merged) => {
+                        if let Some(scenario_pnl) = result.get_scenario_pnl() {
+                            for (name, pnl) in scenario_pnl.iter() {
+                                merged.set_single_scenario_pnl(name, *pnl);
+                            }
+                        }
+                    },
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Values (npv * unit_notional) of the instruments of self on the evaluation date of source.
+    /// The market data of the risk classes in market_data_from and the past daily values (fixings) are taken from source,
+    /// and the other market data from self. This is a step of the waterfall revaluation in PnlExplain.
+    /// It returns instrument code -> CalculationResult where only npv, value and cashflows are set.
+    pub fn calculate_with_market_data(
+        &self,
+        source: &EngineGenerator,
+        market_data_from: &[RiskClass],
+    ) -> Result<HashMap<String, CalculationResult>> {
+        if self.instrument_group_vec.is_empty() {
+            bail!("({}:{}) instruments are not distributed", file!(), line!());
+        }
+        let pick = |risk_class: RiskClass| if market_data_from.contains(&risk_class) { source } else { self };
+        let (equity, rates, fx, volatility, dividend) = (
+            pick(RiskClass::Equity),
+            pick(RiskClass::Rates),
+            pick(RiskClass::Fx),
+            pick(RiskClass::Volatility),
+            pick(RiskClass::Dividend),
+        );
+        let data = EngineData {
+            configuration: self.calculation_configuration.clone().npv_only(),
+            evaluation_datetime: source.evaluation_date.get_date_clone(),
+            fx_data: fx.fx_data.clone(),
+            stock_data: equity.stock_data.clone(),
+            curve_data: rates.curve_data.clone(),
+            dividend_data: dividend.dividend_data.clone(),
+            equity_constant_volatility_data: volatility.equity_constant_volatility_data.clone(),
+            equity_volatility_surface_data: volatility.equity_volatility_surface_data.clone(),
+            fx_constant_volatility_data: volatility.fx_constant_volatility_data.clone(),
+            quanto_correlation_data: volatility.quanto_correlation_data.clone(),
+            past_daily_value_data: source.past_daily_value_data.clone(),
+            ..self.get_engine_data()
+        };
+        let group_results: Result<Vec<HashMap<String, CalculationResult>>> = self.instrument_group_vec
+            .par_iter()
+            .enumerate()
+            .map(|(group_id, instrument_group)| {
+                let mut engine = build_engine(group_id, instrument_group.clone(), &data).map_err(|(_, e)| e)?;
+                engine.calculate()?;
+                Ok(engine.get_calculation_result_clone())
+            }).collect();
+
+        Ok(group_results?.into_iter().flatten().collect())
+    }
+
+    /// Values (npv * unit_notional) in the instrument currencies on the dates of grid under simulated market states,
+    /// where scenarios[k][p] is applied on grid.get_datetimes()[k] in path p (see Engine::get_values_on_date).
+    /// The paths are split into number_of_chunks and an engine is made for each (instrument group, chunk).
+    /// Only the instruments in instrument_codes are revalued.
+    /// It returns values[k][p]: instrument code -> value where the matured instruments are not included.
+    pub fn calculate_on_grid(
+        &self,
+        grid: &DateTimeGrid,
+        scenarios: &[Vec<Scenario>],
+        instrument_codes: &[String],
+        number_of_chunks: usize,
+    ) -> Result<Vec<Vec<HashMap<String, Real>>>> {
+        if self.instrument_group_vec.is_empty() {
+            bail!("({}:{}) instruments are not distributed", file!(), line!());
+        }
+        if scenarios.len() != grid.len() {
+            bail!(
+                "({}:{}) scenarios are given on {} dates, but the grid has {} dates",
+                file!(), line!(), scenarios.len(), grid.len()
+            );
+        }
+        let number_of_paths = scenarios.first().map(|paths| paths.len()).unwrap_or(0);
+        if scenarios.iter().any(|paths| paths.len() != number_of_paths) {
+            bail!("({}:{}) the number of paths must be the same on all dates", file!(), line!());
+        }
+        let chunk_size = number_of_paths.div_ceil(number_of_chunks.max(1)).max(1);
+        let path_ranges: Vec<(usize, usize)> = (0..number_of_paths).step_by(chunk_size)
+            .map(|start| (start, (start + chunk_size).min(number_of_paths)))
+            .collect();
+        let instrument_groups: Vec<Vec<Instrument>> = self.instrument_group_vec.iter()
+            .map(|group| group.iter()
+                .filter(|inst| instrument_codes.contains(inst.get_code()))
+                .cloned()
+                .collect::<Vec<Instrument>>())
+            .filter(|group| !group.is_empty())
+            .collect();
+        let jobs: Vec<(usize, &Vec<Instrument>, (usize, usize))> = instrument_groups.iter()
+            .flat_map(|group| path_ranges.iter().map(move |range| (group, *range)))
+            .enumerate()
+            .map(|(job_id, (group, range))| (job_id, group, range))
+            .collect();
+
+        let data = EngineData {
+            configuration: self.calculation_configuration.clone().npv_only(),
+            ..self.get_engine_data()
+        };
+        // (start path, values[k][p - start])
+        let job_results = jobs.par_iter().map(
+            |(job_id, instrument_group, (start, end))| {
+                let mut engine = build_engine(*job_id, (*instrument_group).clone(), &data).map_err(|(_, e)| e)?;
+                let mut values = Vec::with_capacity(grid.len());
+                for (date, paths) in grid.get_datetimes().iter().zip(scenarios.iter()) {
+                    values.push(engine.get_values_on_date(date, &paths[*start..*end])?);
+                }
+                Ok((*start, values))
+            }
+        ).collect::<Result<Vec<_>>>();
+
+        let mut res: Vec<Vec<HashMap<String, Real>>> = vec![vec![HashMap::new(); number_of_paths]; grid.len()];
+        for (start, values) in job_results? {
+            for (k, paths) in values.into_iter().enumerate() {
+                for (p, path_values) in paths.into_iter().enumerate() {
+                    res[k][start + p].extend(path_values);
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// The deltas of the following update_market_data calls are sent to the receiver.
+    /// The subscription ends when the receiver is dropped.
+    pub fn subscribe_result_deltas(&mut self) -> Receiver<Vec<CalculationResultDelta>> {
+        let (sender, receiver) = channel();
+        self.result_subscribers.push(sender);
+        receiver
+    }
+
+    /// Apply the intraday ticks to the market data and recalculate only the instruments affected by them
+    /// (see MarketDataUpdate::get_affected_instruments).
+    /// The engine of an instrument group is built on the first update affecting the group and kept in an EngineWorker,
+    /// so that the following updates are applied to its parameters in place (see Engine::update_market_data)
+    /// and the affected instruments are repriced without building the engine again.
+    /// The results are merged into get_calculation_results and the deltas from the previous results
+    /// are returned and sent to the result subscribers. distribute_instruments must be called before.
+    /// If any update or calculation fails, neither the data nor the results are changed
+    /// and the engines are built again on the next update.
+    pub fn update_market_data(&mut self, updates: &[MarketDataUpdate]) -> Result<Vec<CalculationResultDelta>> {
+        if self.instrument_group_vec.is_empty() {
+            bail!("({}:{}) instruments are not distributed", file!(), line!());
+        }
+        let mut fx_data = self.fx_data.clone();
+        let mut stock_data = self.stock_data.clone();
+        let mut curve_data = self.curve_data.clone();
+        let mut equity_constant_volatility_data = self.equity_constant_volatility_data.clone();
+        let mut equity_volatility_surface_data = self.equity_volatility_surface_data.clone();
+        let mut affected_codes = HashSet::<String>::new();
+        for update in updates.iter() {
+            update.apply_to(
+                &mut fx_data,
+                &mut stock_data,
+                &mut curve_data,
+                &mut equity_constant_volatility_data,
+                &mut equity_volatility_surface_data,
+            )?;
+            for instrument in update.get_affected_instruments(&self.instruments, &self.match_parameter)? {
+                affected_codes.insert(instrument.get_code().clone());
+            }
+        }
+        let deltas = self.reprice_in_engine_workers(updates, &affected_codes)
+            .and_then(|mut results| {
+                results.sort_by(|a, b| a.0.cmp(&b.0));
+                results.into_iter()
+                    .map(|(code, result)| CalculationResultDelta::new(
+                        &code,
+                        self.calculation_results.get(&code),
+                        result,
+                        &self.calculation_configuration,
+                    ))
+                    .collect::<Result<Vec<CalculationResultDelta>>>()
+            });
+        let deltas = match deltas {
+            Ok(deltas) => deltas,
+            Err(e) => {
+                // the engines may be partially updated, so they are built again from the data on the next update
+                self.engine_workers.clear();
+                return Err(e);
+            },
+        };
+
+        self.fx_data = fx_data;
+        self.stock_data = stock_data;
+        self.curve_data = curve_data;
+        self.equity_constant_volatility_data = equity_constant_volatility_data;
+        self.equity_volatility_surface_data = equity_volatility_surface_data;
+        for delta in deltas.iter() {
+            self.calculation_results.insert(delta.instrument_code.clone(), delta.result.clone());
+        }
+        self.result_subscribers.retain(|subscriber| subscriber.send(deltas.clone()).is_ok());
+        Ok(deltas)
+    }
+
+    /// Send the updates to the engine workers of the groups with affected instruments and of the groups
+    /// whose engines are already built (to keep their parameters up to date), and collect the results.
+    /// The instruments failed in the continue-on-error mode are neither in the engines nor recalculated.
+    fn reprice_in_engine_workers(
+        &mut self,
+        updates: &[MarketDataUpdate],
+        affected_codes: &HashSet<String>,
+    ) -> Result<Vec<(String, CalculationResult)>> {
+        let failed_codes = self.error_report.get_failed_instrument_codes();
+        let mut replies = Vec::new();
+        for (group_id, group) in self.instrument_group_vec.iter().enumerate() {
+            let inst_codes: Vec<String> = group.iter()
+                .map(|inst| inst.get_code().clone())
+                .filter(|code| affected_codes.contains(code) && !failed_codes.contains(code))
+                .collect();
+            if !self.engine_workers.contains_key(&group_id) {
+                if inst_codes.is_empty() {
+                    continue;
+                }
+                let worker = self.spawn_engine_worker(group_id, &failed_codes);
+                self.engine_workers.insert(group_id, worker);
+            }
+            replies.push(self.engine_workers[&group_id].request(updates, inst_codes)?);
+        }
+        let mut results = Vec::new();
+        for reply in replies {
+            let group_results = reply.recv()
+                .map_err(|_| anyhow!("({}:{}) the engine worker stopped without the result", file!(), line!()))??;
+            results.extend(group_results);
+        }
+        Ok(results)
+    }
+
+    /// The engine worker of the instrument group built from the current data
+    fn spawn_engine_worker(&self, group_id: usize, failed_codes: &HashSet<&String>) -> EngineWorker {
+        let instruments: Vec<Instrument> = self.instrument_group_vec[group_id].iter()
+            .filter(|inst| !failed_codes.contains(inst.get_code()))
+            .cloned()
+            .collect();
+        let data = self.get_engine_data();
+        EngineWorker::spawn(move || build_engine(group_id, instruments, &data).map_err(|(_, e)| e))
+    }
+
+    /// fx rate of currency1 in currency2 from the fx data: direct, reciprocal, or through KRW
+    pub fn get_fx_rate(&self, currency1: Currency, currency2: Currency) -> Result<Real> {
+        resolve_fx_rate(|fx_code| self.fx_data.get(fx_code).map(|data| data.get_value()), currency1, currency2)
+    }
+
+    pub fn get_calculation_configuration(&self) -> &CalculationConfiguration {
+        &self.calculation_configuration
+    }
+
+    pub fn get_evaluation_date(&self) -> &EvaluationDate {
+        &self.evaluation_date
+    }
+
+    pub fn get_instruments(&self) -> &Instruments {
+        &self.instruments
+    }
+
     pub fn get_calculation_results(&self) -> &HashMap<String, CalculationResult> {
         &self.calculation_results
     }
-}
\ No newline at end of file
+
+    /// failures of the last calculate in the continue-on-error mode
+    pub fn get_error_report(&self) -> &ErrorReport {
+        &self.error_report
+    }
+
+    pub fn get_fx_data(&self) -> &HashMap<FxCode, ValueData> {
+        &self.fx_data
+    }
+
+    pub fn get_stock_data(&self) -> &HashMap<String, ValueData> {
+        &self.stock_data
+    }
+
+    pub fn get_curve_data(&self) -> &HashMap<String, VectorData> {
+        &self.curve_data
+    }
+
+    pub fn get_dividend_data(&self) -> &HashMap<String, VectorData> {
+        &self.dividend_data
+    }
+
+    pub fn get_fx_constant_volatility_data(&self) -> &HashMap<FxCode, ValueData> {
+        &self.fx_constant_volatility_data
+    }
+
+    pub fn get_equity_constant_volatility_data(&self) -> &HashMap<String, ValueData> {
+        &self.equity_constant_volatility_data
+    }
+
+    pub fn get_equity_volatility_surface_data(&self) -> &HashMap<String, SurfaceData> {
+        &self.equity_volatility_surface_data
+    }
+
+    /// underlying code -> SurfaceData of the implied volatilities in the calculation results
+    pub fn get_implied_volatility_surfaces(&self) -> Result<HashMap<String, SurfaceData>> {
+        let mut quotes: HashMap<String, (Currency, Vec<ImpliedVolatilityQuote>)> = HashMap::new();
+        for inst in self.instruments.iter() {
+            if !matches!(inst.as_ref(), Instrument::VanillaOption(_)) {
+                continue;
+            }
+            let implied_volatility = self.calculation_results.get(inst.get_code())
+                .and_then(|result| result.get_implied_volatility());
+            if let (Some(volatility), Some(maturity), Some(und_code)) = (
+                implied_volatility, inst.get_maturity(), inst.get_underlying_codes().first()
+            ) {
+                let currency = *inst.get_underlying_currency()?;
+                quotes.entry((*und_code).clone())
+                    .or_insert_with(|| (currency, Vec::new()))
+                    .1
+                    .push(ImpliedVolatilityQuote {
+                        maturity: *maturity,
+                        strike: inst.get_strike()?,
+                        volatility,
+                    });
+            }
+        }
+
+        let mut res = HashMap::new();
+        for (und_code, (currency, und_quotes)) in quotes.iter() {
+            let spot = self.stock_data.get(und_code).map(|data| data.get_value());
+            let surface = build_implied_volatility_surface(
+                und_quotes,
+                spot,
+                Some(self.evaluation_date.get_date_clone()),
+                *currency,
+                format!("{} implied volatility", und_code),
+                und_code.clone(),
+            )?;
+            res.insert(und_code.clone(), surface);
+        }
+        Ok(res)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruments::futures::Futures;
+    use ndarray::Array1;
+    use time::macros::datetime;
+
+    fn futures(currency: Currency, und_code: &str, code: &str) -> Rc<Instrument> {
+        Rc::new(Instrument::Futures(Futures::new(
+            320.0,
+            datetime!(2023-09-15 09:00:00 +09:00),
+            datetime!(2024-03-14 15:20:00 +09:00),
+            datetime!(2024-03-14 15:20:00 +09:00),
+            datetime!(2024-03-14 15:20:00 +09:00),
+            250_000.0,
+            currency,
+            currency,
+            und_code.to_string(),
+            format!("{} Fut Mar24", und_code),
+            code.to_string(),
+        )))
+    }
+
+    fn build_engine_generator(continue_on_error: bool) -> Result<EngineGenerator> {
+        let market_datetime = datetime!(2024-01-02 16:30:00 +09:00);
+        let stock = ValueData::new(350.0, Some(market_datetime), Currency::KRW, "KOSPI2".to_string(), "KOSPI2".to_string())?;
+        let mut curve_data = HashMap::new();
+        for code in ["KSD", "KOSPI2"] {
+            let data = VectorData::new(
+                Array1::from(vec![0.0345, 0.0345]),
+                Some(vec![datetime!(2025-01-02 16:30:00 +09:00), datetime!(2026-01-02 16:30:00 +09:00)]),
+                None,
+                Some(market_datetime),
+                Currency::KRW,
+                code.to_string(),
+                code.to_string(),
+            )?;
+            curve_data.insert(code.to_string(), data);
+        }
+        let snapshot = MarketDataSnapshot::new(market_datetime)
+            .with_stock_data(HashMap::from([("KOSPI2".to_string(), stock)]))
+            .with_curve_data(curve_data);
+        // no collateral curve for NIKKEI225
+        let match_parameter: MatchParameter = serde_json::from_str(r#"{
+            "collateral_curve_map": { "KOSPI2": "KSD", "KOSDAQ150": "KSD" },
+            "borrowing_curve_map": { "KOSPI2": "KOSPI2", "KOSDAQ150": "KOSDAQ150" }
+        }"#)?;
+        let instruments = Instruments::new(vec![
+            futures(Currency::KRW, "KOSPI2", "165XXX"),
+            futures(Currency::KRW, "KOSDAQ150", "106XXX"),
+            futures(Currency::KRW, "NIKKEI225", "NKXXX"),
+            futures(Currency::USD, "SPX", "ESXXX"),
+        ]);
+        let configuration = CalculationConfiguration::default()
+            .npv_only()
+            .with_delta_calculation(true);
+
+        let mut engine_generator = EngineGenerator::builder();
+        engine_generator
+            .with_configuration(configuration, market_datetime, match_parameter)?
+            .with_instruments(instruments)?
+            .with_instrument_categories(vec![InstrumentCategory::new(None, Some(vec![Currency::KRW]), None)])?
+            .with_market_data_snapshot(snapshot)?
+            .with_continue_on_error(continue_on_error)?;
+        Ok(engine_generator)
+    }
+
+    #[test]
+    fn test_continue_on_error() -> Result<()> {
+        let mut engine_generator = build_engine_generator(false)?;
+        assert!(engine_generator.distribute_instruments().is_err());
+
+        let mut engine_generator = build_engine_generator(true)?;
+        let validation = engine_generator.validate_market_data();
+        let missing: Vec<(MarketDataCategory, &str)> = validation.missing_market_data.iter()
+            .map(|data| (data.category, data.code.as_str()))
+            .collect();
+        assert!(missing.contains(&(MarketDataCategory::Stock, "KOSDAQ150")));
+        assert!(missing.contains(&(MarketDataCategory::Curve, "KOSDAQ150")));
+        assert!(!missing.iter().any(|(_, code)| *code == "KOSPI2"));
+
+        engine_generator.distribute_instruments()?;
+        engine_generator.calculate()?;
+        let results = engine_generator.get_calculation_results();
+        assert_eq!(results.len(), 1);
+        let npv = results["165XXX"].get_npv_result().expect("npv").get_npv();
+        assert!(npv > 320.0 && npv < 360.0, "npv: {}", npv);
+
+        let report = engine_generator.get_error_report();
+        let kinds: Vec<(&str, InstrumentErrorKind)> = report.instrument_errors.iter()
+            .map(|error| (error.instrument_code.as_str(), error.kind))
+            .collect();
+        assert_eq!(kinds, vec![
+            ("106XXX", InstrumentErrorKind::MissingMarketData),
+            ("ESXXX", InstrumentErrorKind::Undistributed),
+            ("NKXXX", InstrumentErrorKind::InvalidInstrument),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_market_data() -> Result<()> {
+        let market_datetime = datetime!(2024-01-02 16:30:00 +09:00);
+        let curve_dates = vec![datetime!(2025-01-02 16:30:00 +09:00), datetime!(2026-01-02 16:30:00 +09:00)];
+        let mut stock_data = HashMap::new();
+        let mut curve_data = HashMap::new();
+        for (code, spot) in [("KOSPI2", 350.0), ("KOSDAQ150", 1300.0), ("KSD", 0.0)] {
+            if spot > 0.0 {
+                let data = ValueData::new(spot, Some(market_datetime), Currency::KRW, code.to_string(), code.to_string())?;
+                stock_data.insert(code.to_string(), data);
+            }
+            let data = VectorData::new(
+                Array1::from(vec![0.0345, 0.0345]),
+                Some(curve_dates.clone()),
+                None,
+                Some(market_datetime),
+                Currency::KRW,
+                code.to_string(),
+                code.to_string(),
+            )?;
+            curve_data.insert(code.to_string(), data);
+        }
+        let snapshot = MarketDataSnapshot::new(market_datetime)
+            .with_stock_data(stock_data)
+            .with_curve_data(curve_data);
+        let match_parameter: MatchParameter = serde_json::from_str(r#"{
+            "collateral_curve_map": { "KOSPI2": "KSD", "KOSDAQ150": "KSD" },
+            "borrowing_curve_map": { "KOSPI2": "KOSPI2", "KOSDAQ150": "KOSDAQ150" }
+        }"#)?;
+        let instruments = Instruments::new(vec![
+            futures(Currency::KRW, "KOSPI2", "165XXX"),
+            futures(Currency::KRW, "KOSDAQ150", "106XXX"),
+        ]);
+        let configuration = CalculationConfiguration::default()
+            .npv_only()
+            .with_delta_calculation(true);
+
+        let mut engine_generator = EngineGenerator::builder();
+        engine_generator
+            .with_configuration(configuration, market_datetime, match_parameter)?
+            .with_instruments(instruments)?
+            .with_instrument_categories(vec![InstrumentCategory::new(None, Some(vec![Currency::KRW]), None)])?
+            .with_market_data_snapshot(snapshot)?;
+        engine_generator.distribute_instruments()?;
+        engine_generator.calculate()?;
+        let kosdaq_result = engine_generator.get_calculation_results()["106XXX"].clone();
+
+        let receiver = engine_generator.subscribe_result_deltas();
+        let deltas = engine_generator.update_market_data(&[
+            MarketDataUpdate::Spot { code: "KOSPI2".to_string(), value: 351.0 },
+        ])?;
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].instrument_code, "165XXX");
+        let value_change = deltas[0].get_value_change().expect("value change");
+        assert!(value_change > 0.0, "value change: {}", value_change);
+        assert!(deltas[0].changes.iter().any(|change| change.risk_type == "npv"));
+        assert_eq!(receiver.try_recv()?, deltas);
+        assert_eq!(engine_generator.get_stock_data()["KOSPI2"].get_value(), 351.0);
+        assert!(engine_generator.get_calculation_results()["106XXX"] == kosdaq_result);
+
+        // the collateral curve is used by both
+        let deltas = engine_generator.update_market_data(&[MarketDataUpdate::CurvePoint {
+            code: "KSD".to_string(),
+            date: curve_dates[0],
+            value: 0.0355,
+        }])?;
+        let codes: Vec<&str> = deltas.iter().map(|delta| delta.instrument_code.as_str()).collect();
+        assert_eq!(codes, vec!["106XXX", "165XXX"]);
+        // the engine of the group is kept and repriced in place
+        assert_eq!(engine_generator.engine_workers.len(), 1);
+
+        // the same results as a new calculation on the updated data
+        let mut recalculated = EngineGenerator::builder();
+        recalculated
+            .with_configuration(
+                engine_generator.get_calculation_configuration().clone(),
+                market_datetime,
+                engine_generator.match_parameter.clone(),
+            )?
+            .with_instruments(engine_generator.get_instruments().clone())?
+            .with_instrument_categories(engine_generator.instrument_categories.clone())?
+            .with_data(
+                engine_generator.get_fx_data().clone(),
+                engine_generator.get_stock_data().clone(),
+                engine_generator.get_curve_data().clone(),
+                engine_generator.get_dividend_data().clone(),
+                engine_generator.get_equity_constant_volatility_data().clone(),
+                engine_generator.get_equity_volatility_surface_data().clone(),
+                engine_generator.get_fx_constant_volatility_data().clone(),
+                HashMap::new(),
+                HashMap::new(),
+            )?;
+        recalculated.distribute_instruments()?;
+        recalculated.calculate()?;
+        assert!(recalculated.get_calculation_results() == engine_generator.get_calculation_results());
+
+        // a failing update changes nothing
+        let failed = engine_generator.update_market_data(&[
+            MarketDataUpdate::Spot { code: "KOSPI2".to_string(), value: 360.0 },
+            MarketDataUpdate::Spot { code: "NIKKEI225".to_string(), value: 33000.0 },
+        ]);
+        assert!(failed.is_err());
+        assert_eq!(engine_generator.get_stock_data()["KOSPI2"].get_value(), 351.0);
+        Ok(())
+    }
+}
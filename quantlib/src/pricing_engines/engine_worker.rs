@@ -0,0 +1,79 @@
+use crate::pricing_engines::{
+    calculation_result::CalculationResult,
+    engine::Engine,
+    market_data_update::MarketDataUpdate,
+};
+//
+use anyhow::{anyhow, Result};
+use std::{
+    collections::HashMap,
+    sync::mpsc::{channel, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+/// market data updates to apply to the engine and the instruments to reprice after them
+struct UpdateRequest {
+    updates: Vec<MarketDataUpdate>,
+    inst_codes: Vec<String>,
+    reply: Sender<Result<HashMap<String, CalculationResult>>>,
+}
+
+/// An engine kept alive in its own thread, so that the market data updates reprice it in place
+/// instead of building a new engine on every tick (see EngineGenerator::update_market_data).
+/// Engine is not Send, hence the engine is built and used only in the thread of the worker.
+/// The thread ends when the worker is dropped.
+pub struct EngineWorker {
+    requests: Option<Sender<UpdateRequest>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EngineWorker {
+    /// The engine is made by build in the thread. If build fails, every request fails with the error.
+    pub fn spawn<F>(build: F) -> EngineWorker
+    where F: FnOnce() -> Result<Engine> + Send + 'static
+    {
+        let (sender, receiver) = channel::<UpdateRequest>();
+        let handle = thread::spawn(move || {
+            let mut engine = build();
+            for request in receiver {
+                let result = match engine.as_mut() {
+                    Ok(engine) => engine.update_market_data(&request.updates)
+                        .and_then(|_| engine.calculate_instruments(&request.inst_codes)),
+                    Err(e) => Err(anyhow!("({}:{}) failed to build the engine: {:?}", file!(), line!(), e)),
+                };
+                // the requester may have given up on the reply
+                let _ = request.reply.send(result);
+            }
+        });
+        EngineWorker {
+            requests: Some(sender),
+            handle: Some(handle),
+        }
+    }
+
+    /// Send the updates and the instruments to reprice (can be empty) to the engine.
+    /// The result is received from the returned receiver, so that the workers run in parallel.
+    pub fn request(
+        &self,
+        updates: &[MarketDataUpdate],
+        inst_codes: Vec<String>,
+    ) -> Result<Receiver<Result<HashMap<String, CalculationResult>>>> {
+        let (reply, receiver) = channel();
+        let request = UpdateRequest { updates: updates.to_vec(), inst_codes, reply };
+        self.requests.as_ref()
+            .ok_or_else(|| anyhow!("({}:{}) the engine worker is stopped", file!(), line!()))?
+            .send(request)
+            .map_err(|_| anyhow!("({}:{}) the engine worker is stopped", file!(), line!()))?;
+        Ok(receiver)
+    }
+}
+
+impl Drop for EngineWorker {
+    fn drop(&mut self) {
+        // closing the channel ends the loop of the thread
+        self.requests.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::data::market_data_snapshot::MarketDataCategory;
+use crate::instrument::{Instrument, InstrumentTrait};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// the step where an instrument failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum InstrumentErrorKind {
+    /// in none of the instrument categories
+    Undistributed,
+    /// the market data of the instrument are missing (see ErrorReport::missing_market_data)
+    MissingMarketData,
+    /// the instrument is not consistent, e.g., its curves are not found in the match parameter
+    InvalidInstrument,
+    /// the parameters (curves, volatilities, etc.) failed to be built from the market data
+    MarketData,
+    /// no pricer for the instrument or the pricer failed to be created
+    Pricer,
+    /// the pricing or the greeks failed
+    Calculation,
+}
+
+/// a market data which is needed but not given. The code of QuantoCorrelation is "underlying code/fx code".
+/// A missing equity volatility is reported as EquityVolatilitySurface while the constant volatility is also accepted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MissingMarketData {
+    pub category: MarketDataCategory,
+    pub code: String,
+    /// the instruments which need the data
+    pub instrument_codes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstrumentError {
+    pub instrument_code: String,
+    pub instrument_name: String,
+    pub type_name: String,
+    pub kind: InstrumentErrorKind,
+    pub message: String,
+}
+
+impl InstrumentError {
+    pub fn new(instrument: &Instrument, kind: InstrumentErrorKind, message: String) -> InstrumentError {
+        InstrumentError {
+            instrument_code: instrument.get_code().clone(),
+            instrument_name: instrument.get_name().clone(),
+            type_name: instrument.get_type_name().to_string(),
+            kind,
+            message,
+        }
+    }
+}
+
+/// The failures of a calculation in the continue-on-error mode of EngineGenerator,
+/// sorted by (category, code) and instrument code respectively.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ErrorReport {
+    pub missing_market_data: Vec<MissingMarketData>,
+    pub instrument_errors: Vec<InstrumentError>,
+}
+
+impl ErrorReport {
+    pub fn is_empty(&self) -> bool {
+        self.missing_market_data.is_empty() && self.instrument_errors.is_empty()
+    }
+
+    /// adds instrument_code to the instruments needing the data
+    pub fn add_missing_market_data(&mut self, category: MarketDataCategory, code: String, instrument_code: &String) {
+        match self.missing_market_data.iter_mut().find(|data| data.category == category && data.code == code) {
+            Some(data) => {
+                if !data.instrument_codes.contains(instrument_code) {
+                    data.instrument_codes.push(instrument_code.clone());
+                }
+            },
+            None => self.missing_market_data.push(MissingMarketData {
+                category,
+                code,
+                instrument_codes: vec![instrument_code.clone()],
+            }),
+        }
+    }
+
+    pub fn add_instrument_error(&mut self, error: InstrumentError) {
+        self.instrument_errors.push(error);
+    }
+
+    pub fn extend(&mut self, other: ErrorReport) {
+        for data in other.missing_market_data {
+            for instrument_code in data.instrument_codes.iter() {
+                self.add_missing_market_data(data.category, data.code.clone(), instrument_code);
+            }
+        }
+        self.instrument_errors.extend(other.instrument_errors);
+    }
+
+    pub fn sort(&mut self) {
+        for data in self.missing_market_data.iter_mut() {
+            data.instrument_codes.sort();
+        }
+        self.missing_market_data.sort_by(|a, b| (a.category, &a.code).cmp(&(b.category, &b.code)));
+        self.instrument_errors.sort_by(|a, b| a.instrument_code.cmp(&b.instrument_code));
+    }
+
+    pub fn get_failed_instrument_codes(&self) -> HashSet<&String> {
+        self.instrument_errors.iter().map(|error| &error.instrument_code).collect()
+    }
+
+    pub fn get_errors_of(&self, kind: InstrumentErrorKind) -> Vec<&InstrumentError> {
+        self.instrument_errors.iter().filter(|error| error.kind == kind).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_report_merge() {
+        let mut report = ErrorReport::default();
+        report.add_missing_market_data(MarketDataCategory::Stock, "KOSPI2".to_string(), &"B".to_string());
+        report.add_missing_market_data(MarketDataCategory::Curve, "KSD".to_string(), &"A".to_string());
+
+        let mut other = ErrorReport::default();
+        other.add_missing_market_data(MarketDataCategory::Stock, "KOSPI2".to_string(), &"A".to_string());
+        other.add_missing_market_data(MarketDataCategory::Stock, "KOSPI2".to_string(), &"A".to_string());
+        report.extend(other);
+        report.sort();
+
+        assert_eq!(report.missing_market_data.len(), 2);
+        assert_eq!(report.missing_market_data[0].category, MarketDataCategory::Stock);
+        assert_eq!(report.missing_market_data[0].instrument_codes, vec!["A".to_string(), "B".to_string()]);
+        assert_eq!(report.missing_market_data[1].code, "KSD");
+        assert!(!report.is_empty());
+        assert!(report.get_failed_instrument_codes().is_empty());
+    }
+}
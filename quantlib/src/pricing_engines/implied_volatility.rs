@@ -0,0 +1,248 @@
+use crate::definitions::{Real, Time};
+use crate::currency::Currency;
+use crate::data::surface_data::SurfaceData;
+use crate::enums::OptionType;
+use crate::math::{
+    interpolator::{InterpolatorReal1D, ExtraPolationType},
+    interpolators::linear_interpolator::LinearInterpolator1D,
+    solvers::{newton, SolverConfig},
+};
+//
+use anyhow::{anyhow, bail, Context, Result};
+use ndarray::{Array1, Array2};
+use statrs::distribution::{Normal, ContinuousCDF};
+use time::OffsetDateTime;
+
+const MIN_VOLATILITY: Real = 1.0e-4;
+const MAX_VOLATILITY: Real = 5.0;
+
+/// Black price used in OptionAnalyticPricer.
+/// quanto_factor is Quanto::quanto_adjust (fx volatility * correlation), so that
+/// the drift adjustment is volatility * t * quanto_factor. Use 0.0 for non-quanto options.
+pub fn black_price(
+    option_type: OptionType,
+    forward: Real,
+    strike: Real,
+    t: Time,
+    discount: Real,
+    volatility: Real,
+    quanto_factor: Real,
+) -> Real {
+    let total_deviation = volatility * t.sqrt();
+    let total_variance = total_deviation * total_deviation;
+    let quanto_adjustment = volatility * t * quanto_factor;
+    let y = (strike / forward).ln();
+
+    let d1 = (-y + total_variance / 2.0 - quanto_adjustment) / total_deviation;
+    let d2 = d1 - total_deviation;
+
+    let normal = Normal::new(0.0, 1.0).unwrap();
+    let nd1 = normal.cdf(d1 as f64) as Real;
+    let nd2 = normal.cdf(d2 as f64) as Real;
+
+    match option_type {
+        OptionType::Call => discount * (forward * nd1 - strike * nd2),
+        OptionType::Put => discount * (strike * (1.0 - nd2) - forward * (1.0 - nd1)),
+    }
+}
+
+/// Volatility such that black_price(volatility) = price.
+/// Newton's method (numerical vega) safeguarded by bisection on [1e-4, 5.0]
+/// starting from the Brenner-Subrahmanyam approximation.
+pub fn implied_black_volatility(
+    option_type: OptionType,
+    forward: Real,
+    strike: Real,
+    t: Time,
+    discount: Real,
+    price: Real,
+    quanto_factor: Real,
+) -> Result<Real> {
+    if t <= 0.0 {
+        bail!("({}:{}) implied volatility is not defined for t = {}", file!(), line!(), t);
+    }
+    if forward <= 0.0 || strike <= 0.0 || discount <= 0.0 {
+        bail!(
+            "({}:{}) forward ({}), strike ({}) and discount factor ({}) must be positive",
+            file!(), line!(), forward, strike, discount
+        );
+    }
+    // no-arbitrage bounds (without quanto adjustment)
+    if quanto_factor == 0.0 {
+        let (intrinsic, upper_bound) = match option_type {
+            OptionType::Call => (discount * (forward - strike).max(0.0), discount * forward),
+            OptionType::Put => (discount * (strike - forward).max(0.0), discount * strike),
+        };
+        if price <= intrinsic || price >= upper_bound {
+            bail!(
+                "({}:{}) price {} is out of the no-arbitrage bounds ({}, {}) \
+                (forward = {}, strike = {}, t = {})",
+                file!(), line!(), price, intrinsic, upper_bound, forward, strike, t
+            );
+        }
+    }
+
+    let objective = |volatility: Real| -> Real {
+        black_price(option_type, forward, strike, t, discount, volatility, quanto_factor) - price
+    };
+    let f_df = |volatility: Real| -> Result<(Real, Real)> {
+        let h = 1.0e-3 * volatility.max(MIN_VOLATILITY * 10.0);
+        let vega = (objective(volatility + h) - objective(volatility - h)) / (2.0 * h);
+        Ok((objective(volatility), vega))
+    };
+
+    let init_guess = ((2.0 * std::f32::consts::PI / t).sqrt() * price / (discount * forward))
+        .clamp(0.05, 1.0);
+    let config = SolverConfig::default()
+        .with_x_tolerance(1.0e-6)
+        .with_f_tolerance(1.0e-6 * forward.max(strike).max(1.0));
+
+    let report = newton(f_df, init_guess, MIN_VOLATILITY, MAX_VOLATILITY, &config)
+        .with_context(|| anyhow!(
+            "({}:{}) failed to find implied volatility (price = {}, forward = {}, strike = {}, t = {})",
+            file!(), line!(), price, forward, strike, t
+        ))?;
+    Ok(report.get_root())
+}
+
+/// An implied volatility observed at (maturity, strike)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImpliedVolatilityQuote {
+    pub maturity: OffsetDateTime,
+    pub strike: Real,
+    pub volatility: Real,
+}
+
+/// Build a SurfaceData (rows: maturities, columns: strikes) from scattered quotes.
+/// Quotes on the same (maturity date, strike) are averaged, and
+/// missing strikes on each maturity are linearly interpolated (flat extrapolation).
+pub fn build_implied_volatility_surface(
+    quotes: &[ImpliedVolatilityQuote],
+    spot: Option<Real>,
+    market_datetime: Option<OffsetDateTime>,
+    currency: Currency,
+    name: String,
+    code: String,
+) -> Result<SurfaceData> {
+    if quotes.is_empty() {
+        bail!("({}:{}) no implied volatility quotes to build {}", file!(), line!(), code);
+    }
+    if let Some(quote) = quotes.iter().find(|quote| !quote.strike.is_finite() || !quote.volatility.is_finite()) {
+        bail!("({}:{}) non-finite implied volatility quote of {}: {:?}", file!(), line!(), code, quote);
+    }
+
+    let mut maturities: Vec<OffsetDateTime> = Vec::new();
+    for quote in quotes.iter() {
+        if !maturities.iter().any(|m| m.date() == quote.maturity.date()) {
+            maturities.push(quote.maturity);
+        }
+    }
+    maturities.sort();
+
+    let mut strikes: Vec<Real> = quotes.iter().map(|quote| quote.strike).collect();
+    strikes.sort_by(|a, b| a.total_cmp(b));
+    strikes.dedup();
+
+    let mut value = Array2::<Real>::zeros((maturities.len(), strikes.len()));
+    for (i, maturity) in maturities.iter().enumerate() {
+        // (strike, sum of volatility, count)
+        let mut slice: Vec<(Real, Real, Real)> = Vec::new();
+        for quote in quotes.iter().filter(|quote| quote.maturity.date() == maturity.date()) {
+            match slice.iter_mut().find(|(strike, _, _)| *strike == quote.strike) {
+                Some(point) => {
+                    point.1 += quote.volatility;
+                    point.2 += 1.0;
+                },
+                None => slice.push((quote.strike, quote.volatility, 1.0)),
+            }
+        }
+        slice.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        if slice.len() == 1 {
+            value.row_mut(i).fill(slice[0].1 / slice[0].2);
+            continue;
+        }
+
+        let interpolator = LinearInterpolator1D::new(
+            slice.iter().map(|(strike, _, _)| *strike).collect::<Array1<Real>>(),
+            slice.iter().map(|(_, sum, count)| sum / count).collect::<Array1<Real>>(),
+            ExtraPolationType::Flat,
+            true,
+        ).with_context(|| anyhow!(
+            "({}:{}) failed to interpolate implied volatilities of {} at {}",
+            file!(), line!(), code, maturity
+        ))?;
+        for (j, strike) in strikes.iter().enumerate() {
+            value[[i, j]] = interpolator.interpolate(*strike)?;
+        }
+    }
+
+    Ok(SurfaceData::new(
+        spot,
+        value,
+        maturities,
+        Array1::from_vec(strikes),
+        market_datetime,
+        currency,
+        name,
+        code,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_implied_black_volatility() -> Result<()> {
+        let forward = 350.0;
+        let t = 0.7;
+        let discount = 0.98;
+        for option_type in [OptionType::Call, OptionType::Put] {
+            for strike in [250.0, 320.0, 350.0, 380.0, 450.0] {
+                for (volatility, quanto_factor) in [(0.15, 0.0), (0.35, 0.0), (0.2, -0.03)] {
+                    let price = black_price(option_type, forward, strike, t, discount, volatility, quanto_factor);
+                    let implied = implied_black_volatility(
+                        option_type, forward, strike, t, discount, price, quanto_factor)?;
+                    assert!(
+                        (implied - volatility).abs() < 1.0e-4,
+                        "{:?} strike: {}, vol: {}, implied: {}", option_type, strike, volatility, implied
+                    );
+                }
+            }
+        }
+        // below the intrinsic value
+        assert!(implied_black_volatility(OptionType::Call, forward, 300.0, t, discount, 40.0, 0.0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_implied_volatility_surface() -> Result<()> {
+        let m1 = datetime!(2024-03-14 15:45:00 +09:00);
+        let m2 = datetime!(2024-06-13 15:45:00 +09:00);
+        let quotes = vec![
+            ImpliedVolatilityQuote { maturity: m1, strike: 300.0, volatility: 0.20 },
+            ImpliedVolatilityQuote { maturity: m1, strike: 350.0, volatility: 0.16 },
+            ImpliedVolatilityQuote { maturity: m1, strike: 350.0, volatility: 0.18 },
+            ImpliedVolatilityQuote { maturity: m2, strike: 325.0, volatility: 0.19 },
+        ];
+        let surface = build_implied_volatility_surface(
+            &quotes, Some(340.0), None, Currency::KRW, "test".to_string(), "test".to_string())?;
+
+        assert_eq!(surface.get_dates(), &vec![m1, m2]);
+        assert_eq!(surface.get_strike().to_vec(), vec![300.0, 325.0, 350.0]);
+        let value = surface.get_value();
+        assert!((value[[0, 0]] - 0.20).abs() < 1.0e-6);
+        assert!((value[[0, 1]] - 0.185).abs() < 1.0e-6);
+        assert!((value[[0, 2]] - 0.17).abs() < 1.0e-6);
+        assert!(value.row(1).iter().all(|v| (v - 0.19).abs() < 1.0e-6));
+
+        // a NaN quote is rejected instead of breaking the sort
+        let mut nan_quotes = quotes.clone();
+        nan_quotes.push(ImpliedVolatilityQuote { maturity: m2, strike: Real::NAN, volatility: 0.19 });
+        assert!(build_implied_volatility_surface(
+            &nan_quotes, Some(340.0), None, Currency::KRW, "test".to_string(), "test".to_string()).is_err());
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use crate::definitions::Real;
+use crate::math::solvers::{brent, find_bracket, SolverConfig};
 use crate::instruments::bond::Bond;
 use crate::pricing_engines::pricer::PricerTrait;
 use crate::pricing_engines::npv_result::NpvResult;
@@ -18,9 +19,6 @@ use std::{
     rc::Rc,
     cell::RefCell,
 };
-use argmin::core::{Error, Executor, CostFunction, Gradient};
-use argmin::solver::gradientdescent::SteepestDescent;
-use argmin::solver::linesearch::MoreThuenteLineSearch;
 
 /// 금융투자회사의 영업 및 업무에 관한 규정 별표 14
 /// https://law.kofia.or.kr/service/law/lawFullScreenContent.do?seq=136&historySeq=263
@@ -57,69 +55,39 @@ impl KrxYieldPricer {
         self.bond_yield = bond_yield;
     }
 
+    /// Find the yield such that the npv of the bond under 별표 14 matches the given npv.
+    /// The root is bracketed around init_guess (default 0.02) and found by Brent's method.
     pub fn find_bond_yield(
         &self, 
         bond: Bond, 
         npv: Real,
         init_guess: Option<Real>,
     ) -> Result<Real> {
-        let pricer = self.clone();
-        let problem = KrxYieldPricerCostFunction::new(bond, npv, pricer);
-        let linesearch = MoreThuenteLineSearch::new();        
-        
-        let solver = SteepestDescent::new(linesearch);
-        let init_param = init_guess.unwrap_or(0.02);
-        let executor = Executor::new(problem, solver)
-            .configure(|state|
-                state
-                    .param(init_param)
-                    .max_iters(30)
-                    .target_cost(1.0e-9)
-            );
-        
-        let res = executor.run()?;
-        match res.state.best_param {
-            Some(param) => Ok(param),
-            None => Err(anyhow!("Failed to find bond yield")),
-        }
-    }
-}
-
-pub struct KrxYieldPricerCostFunction {
-    bond: Instrument,
-    npv: Real,
-    pricer: RefCell<KrxYieldPricer>,
-}
+        let mut pricer = self.clone();
+        let inst = Instrument::Bond(bond);
+        let mut objective = |bond_yield: Real| -> Result<Real> {
+            pricer.set_bond_yield(bond_yield);
+            Ok(pricer.npv(&inst)? - npv)
+        };
 
-impl KrxYieldPricerCostFunction {
-    pub fn new(bond: Bond, npv: Real, pricer: KrxYieldPricer) -> KrxYieldPricerCostFunction {
-        KrxYieldPricerCostFunction {
-            bond: Instrument::Bond(bond),
-            npv,
-            pricer: RefCell::new(pricer),
-        }
-    }
-}
-
-impl CostFunction for KrxYieldPricerCostFunction {
-    type Param = Real;
-    type Output = Real;
-    
-    fn cost(&self, param: &Self::Param) -> Result<Self::Output, Error> {
-        {self.pricer.borrow_mut().set_bond_yield(*param);}
-        let npv = self.pricer.borrow().npv(&self.bond)?;
-        Ok((npv - self.npv).powf(2.0))
-    }
-}
+        let init_param = init_guess.unwrap_or(0.02);
+        let (lower, upper, _, _) = find_bracket(
+            &mut objective,
+            init_param - 0.01,
+            init_param + 0.01,
+            20,
+        ).with_context(|| anyhow!(
+            "({}:{}) failed to bracket the yield of {} ({}) for npv = {}",
+            file!(), line!(), inst.get_name(), inst.get_code(), npv
+        ))?;
 
-impl Gradient for KrxYieldPricerCostFunction {
-    type Param = Real;
-    type Gradient = Real;
-  
-    fn gradient(&self, param: &Self::Param) -> Result<Self::Gradient, Error> {
-        let h = 5.0e-5;
-        let grad = (self.cost(&(param + h))? - self.cost(&(param - h))?)/(2.0*h);
-        Ok(grad)
+        let config = SolverConfig::default().with_f_tolerance(1.0e-7);
+        let report = brent(&mut objective, lower, upper, &config)
+            .with_context(|| anyhow!(
+                "({}:{}) failed to find the yield of {} ({}) for npv = {}",
+                file!(), line!(), inst.get_name(), inst.get_code(), npv
+            ))?;
+        Ok(report.get_root())
     }
 }
 
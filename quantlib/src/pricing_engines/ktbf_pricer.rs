@@ -5,18 +5,21 @@ use crate::pricing_engines::{
     pricer::PricerTrait,
     krx_yield_pricer::KrxYieldPricer,
     bond_pricer::BondPricer,
+    bond_analytics::BondAnalytics,
 };
 use crate::instrument::{
     Instrument,
     InstrumentTrait,
 };
 use crate::definitions::Real;
-use crate::enums::Compounding;
+use crate::enums::{Compounding, YieldConvention};
+use crate::math::solvers::{brent, find_bracket, SolverConfig};
 //
-use anyhow::Result;
+use anyhow::{Result, Context, anyhow};
 use std::{
     rc::Rc, 
     cell::RefCell,
+    collections::HashMap,
 };
 
 pub struct KtbfPricer {
@@ -37,6 +40,35 @@ impl KtbfPricer {
             borrowing_curve,
         }
     }
+
+    /// The yield of the virtual bond implied by the futures price,
+    /// i.e., y such that virtual_bond_npv(y) * (borrowing discount factor to maturity) = price
+    pub fn implied_yield(&self, instrument: &Instrument, price: Real) -> Result<Real> {
+        let maturity = instrument.get_maturity()
+            .ok_or_else(|| anyhow!(
+                "({}:{}) no maturity in {} ({})",
+                file!(), line!(), instrument.get_name(), instrument.get_code()
+            ))?;
+        let borrowing_cost = self.borrowing_curve.borrow().get_discount_factor_at_date(maturity)?;
+
+        let mut objective = |bond_yield: Real| -> Result<Real> {
+            Ok(instrument.get_virtual_bond_npv(bond_yield)? * borrowing_cost - price)
+        };
+        let (lower, upper, _, _) = find_bracket(&mut objective, 0.0, 0.1, 20)
+            .with_context(|| anyhow!(
+                "({}:{}) failed to bracket the implied yield of {} ({}) for price = {}",
+                file!(), line!(), instrument.get_name(), instrument.get_code(), price
+            ))?;
+
+        let config = SolverConfig::default()
+            .with_f_tolerance(Real::EPSILON * price.abs().max(1.0));
+        let report = brent(&mut objective, lower, upper, &config)
+            .with_context(|| anyhow!(
+                "({}:{}) failed to find the implied yield of {} ({}) for price = {}",
+                file!(), line!(), instrument.get_name(), instrument.get_code(), price
+            ))?;
+        Ok(report.get_root())
+    }
 }
 
 impl PricerTrait for KtbfPricer {
@@ -94,11 +126,34 @@ impl PricerTrait for KtbfPricer {
         Ok(NpvResult::new_from_npv(npv))
     }
 
+    /// analytics of the underlying bonds (bond code -> BondAnalytics)
+    fn bond_analytics(
+        &self,
+        instrument: &Instrument,
+        yield_convention: YieldConvention,
+    ) -> Result<HashMap<String, BondAnalytics>> {
+        let bond_pricer = BondPricer::new(
+            self.evaluation_date.clone(),
+            self.discount_curve.clone(),
+            None,
+            None,
+        );
+
+        let mut res = HashMap::new();
+        for bond in instrument.get_underlying_bonds()?.iter() {
+            let analytics = bond_pricer.bond_analytics(
+                &Instrument::Bond(bond.clone()),
+                yield_convention,
+            )?;
+            res.extend(analytics);
+        }
+        Ok(res)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::instrument::Instrument;
+    use crate::instrument::{Instrument, InstrumentTrait};
     use crate::instruments::bond::Bond;
     use crate::instruments::ktbf::{KtbfVirtualBond, KTBF};
     use crate::evaluation_date::EvaluationDate;
@@ -258,14 +313,24 @@ mod tests {
             "KTBF".to_string(),
         )?;
 
+        let borrowing_curve_rc = Rc::new(RefCell::new(borrowing_curve));
         let ktbf_pricer = KtbfPricer::new(
             evaluation_date.clone(),
             Rc::new(RefCell::new(discount_curve)),
-            Rc::new(RefCell::new(borrowing_curve)),
+            borrowing_curve_rc.clone(),
+        );
+
+        let inst = Instrument::KTBF(ktbf);
+        let implied_yield = ktbf_pricer.implied_yield(&inst, 104.0)?;
+        let implied_price = inst.get_virtual_bond_npv(implied_yield)?
+            * borrowing_curve_rc.borrow().get_discount_factor_at_date(&ktbf_maturity)?;
+        assert!(
+            (implied_price - 104.0).abs() < 1.0e-4,
+            "implied yield: {}, implied price: {}", implied_yield, implied_price
         );
 
         let pricer = Pricer::KtbfPricer(ktbf_pricer);
-        let npv = pricer.npv(&Instrument::KTBF(ktbf))?;
+        let npv = pricer.npv(&inst)?;
         println!("KTBF NPV: {}", npv);
 
 
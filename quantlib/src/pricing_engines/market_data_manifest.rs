@@ -0,0 +1,382 @@
+use crate::currency::{Currency, FxCode};
+use crate::data::{
+    market_data_snapshot::{MarketDataCategory, MarketDataSnapshot},
+    value_data::ValueData,
+};
+use crate::instrument::{Instrument, InstrumentTrait, Instruments};
+use crate::pricing_engines::match_parameter::MatchParameter;
+//
+use anyhow::{anyhow, Context, Result};
+use serde::{de::{value::StrDeserializer, IntoDeserializer}, Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use time::{Date, OffsetDateTime};
+
+/// A market data needed to price the instruments. The codes are the keys of the data in MarketDataSnapshot,
+/// where QuantoCorrelation is "underlying code/fx code" and PastDailyValue is the rate index name.
+/// An equity volatility is required as EquityVolatilitySurface, but EquityConstantVolatility is also accepted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketDataRequirement {
+    pub category: MarketDataCategory,
+    pub code: String,
+    pub instrument_codes: Vec<String>,
+    /// dividends are optional
+    pub optional: bool,
+    /// (first, last) dates of the fixings before the evaluation date in the current coupon periods,
+    /// None if no fixing is needed while the series is still required by the pricers
+    pub fixing_period: Option<(Date, Date)>,
+}
+
+/// the market data required by a portfolio, sorted by (category, code)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketDataManifest {
+    pub evaluation_date: OffsetDateTime,
+    pub requirements: Vec<MarketDataRequirement>,
+}
+
+/// a manifest against a data set
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MarketDataManifestDiff {
+    /// requirements which are not in the data
+    pub missing: Vec<MarketDataRequirement>,
+    /// fixings in the data which do not cover the fixing period
+    pub incomplete_fixings: Vec<MarketDataRequirement>,
+    /// (category, code) of the data which none of the instruments needs
+    pub unused: Vec<(MarketDataCategory, String)>,
+}
+
+impl MarketDataManifestDiff {
+    /// true if all the data but the optional ones are given
+    pub fn is_complete(&self) -> bool {
+        self.missing.iter().all(|requirement| requirement.optional) && self.incomplete_fixings.is_empty()
+    }
+}
+
+/// the fx data giving the rate of fx_code: itself, its reciprocal, or both Curr1/KRW and Curr2/KRW
+fn fx_sources(fx_data: &HashMap<FxCode, ValueData>, fx_code: &FxCode) -> Option<Vec<FxCode>> {
+    if fx_data.contains_key(fx_code) {
+        return Some(vec![*fx_code]);
+    }
+    if fx_data.contains_key(&fx_code.reciprocal()) {
+        return Some(vec![fx_code.reciprocal()]);
+    }
+    let legs = vec![
+        FxCode::new(*fx_code.get_currency1(), Currency::KRW),
+        FxCode::new(*fx_code.get_currency2(), Currency::KRW),
+    ];
+    match legs.iter().all(|leg| fx_data.contains_key(leg)) {
+        true => Some(legs),
+        false => None,
+    }
+}
+
+/// first and last fixing dates before the evaluation date of the coupons not paid yet
+fn fixing_period(instrument: &Instrument, evaluation_date: &OffsetDateTime) -> Result<Option<(Date, Date)>> {
+    let schedule = instrument.get_floating_schedule()
+        .with_context(|| anyhow!(
+            "({}:{}) failed to get the floating schedule of {} ({})",
+            file!(), line!(), instrument.get_name(), instrument.get_code()))?;
+    let first_fixing_date = schedule.iter()
+        .filter(|base_schedule| {
+            base_schedule.get_fixing_date() < evaluation_date && base_schedule.get_payment_date() >= evaluation_date
+        })
+        .map(|base_schedule| base_schedule.get_fixing_date().date())
+        .min();
+    Ok(first_fixing_date.map(|date| (date, evaluation_date.date().previous_day().unwrap_or(date).max(date))))
+}
+
+// (category, code) -> (instrument codes, fixing period)
+type Required = BTreeMap<(MarketDataCategory, String), (Vec<String>, Option<(Date, Date)>)>;
+
+fn add_requirement<'a>(
+    required: &'a mut Required,
+    category: MarketDataCategory,
+    code: String,
+    inst_code: &String,
+) -> &'a mut (Vec<String>, Option<(Date, Date)>) {
+    let entry = required.entry((category, code)).or_default();
+    if !entry.0.contains(inst_code) {
+        entry.0.push(inst_code.clone());
+    }
+    entry
+}
+
+impl MarketDataManifest {
+    /// The market data needed by Engine::with_parameter_data and the pricers for the instruments:
+    /// fx, curves (the curves of the match parameter and the borrowing curves by underlying codes),
+    /// spots, dividends (optional), equity and fx volatilities, quanto correlations and rate index fixings.
+    pub fn resolve(
+        instruments: &Instruments,
+        match_parameter: &MatchParameter,
+        evaluation_date: OffsetDateTime,
+    ) -> Result<MarketDataManifest> {
+        let mut required: Required = BTreeMap::new();
+        for instrument in instruments.iter() {
+            let inst_code = instrument.get_code();
+            for fx_code in instrument.get_all_fxcodes_for_pricing() {
+                add_requirement(&mut required, MarketDataCategory::Fx, fx_code.to_string(), inst_code);
+            }
+            let single = Instruments::new(vec![instrument.clone()]);
+            let curve_names = single.get_all_curve_names(match_parameter)
+                .with_context(|| anyhow!(
+                    "({}:{}) failed to get the curve names of {} ({})",
+                    file!(), line!(), instrument.get_name(), inst_code))?;
+            for curve_name in curve_names {
+                add_requirement(&mut required, MarketDataCategory::Curve, curve_name.clone(), inst_code);
+            }
+            for und_code in instrument.get_underlying_codes() {
+                add_requirement(&mut required, MarketDataCategory::Stock, und_code.clone(), inst_code);
+                // borrowing curve
+                add_requirement(&mut required, MarketDataCategory::Curve, und_code.clone(), inst_code);
+                add_requirement(&mut required, MarketDataCategory::Dividend, und_code.clone(), inst_code);
+            }
+            for und_code in instrument.get_underlying_codes_requiring_volatility() {
+                add_requirement(&mut required, MarketDataCategory::EquityVolatilitySurface, und_code.clone(), inst_code);
+            }
+            for (und_code, fx_code) in instrument.get_quanto_fxcode_und_pair() {
+                add_requirement(&mut required, MarketDataCategory::FxConstantVolatility, fx_code.to_string(), inst_code);
+                add_requirement(&mut required, MarketDataCategory::QuantoCorrelation, format!("{}/{}", und_code, fx_code), inst_code);
+            }
+            if let Ok(Some(rate_index)) = instrument.get_rate_index() {
+                let period = fixing_period(instrument, &evaluation_date)?;
+                let entry = add_requirement(&mut required, MarketDataCategory::PastDailyValue, rate_index.get_name().clone(), inst_code);
+                entry.1 = match (entry.1, period) {
+                    (Some((start1, end1)), Some((start2, end2))) => Some((start1.min(start2), end1.max(end2))),
+                    (current, new) => current.or(new),
+                };
+            }
+        }
+        let requirements = required.into_iter()
+            .map(|((category, code), (mut instrument_codes, fixing_period))| {
+                instrument_codes.sort();
+                MarketDataRequirement {
+                    category,
+                    code,
+                    instrument_codes,
+                    optional: category == MarketDataCategory::Dividend,
+                    fixing_period,
+                }
+            })
+            .collect();
+        Ok(MarketDataManifest { evaluation_date, requirements })
+    }
+
+    pub fn get_requirements_of(&self, category: MarketDataCategory) -> Vec<&MarketDataRequirement> {
+        self.requirements.iter().filter(|requirement| requirement.category == category).collect()
+    }
+
+    pub fn get_requirement(&self, category: MarketDataCategory, code: &str) -> Option<&MarketDataRequirement> {
+        self.requirements.iter().find(|requirement| requirement.category == category && requirement.code == code)
+    }
+
+    /// The requirements which the snapshot does not satisfy and the data of the snapshot which are not required.
+    /// The fx rates may be given by the reciprocal or the KRW crosses as in Engine::with_parameter_data.
+    pub fn diff(&self, snapshot: &MarketDataSnapshot) -> MarketDataManifestDiff {
+        let fx_data = snapshot.get_fx_data();
+        let mut used: BTreeSet<(MarketDataCategory, String)> = BTreeSet::new();
+        let mut res = MarketDataManifestDiff::default();
+        for requirement in self.requirements.iter() {
+            let code = &requirement.code;
+            let found = match requirement.category {
+                MarketDataCategory::Fx => {
+                    let deserializer: StrDeserializer<'_, serde::de::value::Error> = code.as_str().into_deserializer();
+                    let fx_code = FxCode::deserialize(deserializer).ok();
+                    match fx_code.and_then(|fx_code| fx_sources(fx_data, &fx_code)) {
+                        Some(sources) => {
+                            used.extend(sources.iter().map(|source| (MarketDataCategory::Fx, source.to_string())));
+                            true
+                        },
+                        None => false,
+                    }
+                },
+                MarketDataCategory::EquityVolatilitySurface | MarketDataCategory::EquityConstantVolatility => {
+                    let surface = snapshot.get_equity_volatility_surface_data().contains_key(code);
+                    let constant = snapshot.get_equity_constant_volatility_data().contains_key(code);
+                    if surface {
+                        used.insert((MarketDataCategory::EquityVolatilitySurface, code.clone()));
+                    }
+                    if constant {
+                        used.insert((MarketDataCategory::EquityConstantVolatility, code.clone()));
+                    }
+                    surface || constant
+                },
+                category => {
+                    used.insert((category, code.clone()));
+                    snapshot_keys(snapshot, category).contains(code)
+                },
+            };
+            if !found {
+                res.missing.push(requirement.clone());
+                continue;
+            }
+            if let (MarketDataCategory::PastDailyValue, Some((start, _))) = (requirement.category, requirement.fixing_period) {
+                let first_date = snapshot.get_past_daily_value_data().get(code)
+                    .and_then(|data| data.get_value().keys().min().copied());
+                if first_date.map(|first_date| first_date > start).unwrap_or(true) {
+                    res.incomplete_fixings.push(requirement.clone());
+                }
+            }
+        }
+        for category in [
+            MarketDataCategory::Fx,
+            MarketDataCategory::Stock,
+            MarketDataCategory::Curve,
+            MarketDataCategory::Dividend,
+            MarketDataCategory::EquityConstantVolatility,
+            MarketDataCategory::EquityVolatilitySurface,
+            MarketDataCategory::FxConstantVolatility,
+            MarketDataCategory::QuantoCorrelation,
+            MarketDataCategory::PastDailyValue,
+        ] {
+            for code in snapshot_keys(snapshot, category) {
+                if !used.contains(&(category, code.clone())) {
+                    res.unused.push((category, code));
+                }
+            }
+        }
+        res
+    }
+}
+
+/// keys of the data of the category in the snapshot, sorted
+fn snapshot_keys(snapshot: &MarketDataSnapshot, category: MarketDataCategory) -> BTreeSet<String> {
+    match category {
+        MarketDataCategory::Fx => snapshot.get_fx_data().keys().map(|fx_code| fx_code.to_string()).collect(),
+        MarketDataCategory::Stock => snapshot.get_stock_data().keys().cloned().collect(),
+        MarketDataCategory::Curve => snapshot.get_curve_data().keys().cloned().collect(),
+        MarketDataCategory::Dividend => snapshot.get_dividend_data().keys().cloned().collect(),
+        MarketDataCategory::EquityConstantVolatility => snapshot.get_equity_constant_volatility_data().keys().cloned().collect(),
+        MarketDataCategory::EquityVolatilitySurface => snapshot.get_equity_volatility_surface_data().keys().cloned().collect(),
+        MarketDataCategory::FxConstantVolatility => snapshot.get_fx_constant_volatility_data().keys()
+            .map(|fx_code| fx_code.to_string())
+            .collect(),
+        MarketDataCategory::QuantoCorrelation => snapshot.get_quanto_correlation_data().keys()
+            .map(|(und_code, fx_code)| format!("{}/{}", und_code, fx_code))
+            .collect(),
+        MarketDataCategory::PastDailyValue => snapshot.get_past_daily_value_data().keys().cloned().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{daily_value_data::DailyValueData, vector_data::VectorData};
+    use crate::instruments::{futures::Futures, plain_swap::PlainSwap};
+    use crate::parameters::rate_index::RateIndex;
+    use crate::time::calendar::Calendar;
+    use crate::time::calendars::southkorea::{SouthKorea, SouthKoreaType};
+    use crate::time::conventions::{BusinessDayConvention, DayCountConvention, PaymentFrequency};
+    use crate::time::jointcalendar::JointCalendar;
+    use ndarray::Array1;
+    use std::rc::Rc;
+    use time::macros::{date, datetime};
+    use time::{Time, UtcOffset};
+
+    fn curve(code: &str) -> Result<VectorData> {
+        VectorData::new(
+            Array1::from(vec![0.03, 0.03]),
+            Some(vec![datetime!(2022-01-01 00:00:00 +09:00), datetime!(2023-01-01 00:00:00 +09:00)]),
+            None,
+            Some(datetime!(2021-05-14 16:00:00 +09:00)),
+            Currency::KRW,
+            code.to_string(),
+            code.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_market_data_manifest() -> Result<()> {
+        let evaluation_date = datetime!(2021-05-14 16:00:00 +09:00);
+        let futures = Futures::new(
+            100.0,
+            datetime!(2021-01-01 00:00:00 +09:00),
+            datetime!(2021-12-31 00:00:00 +09:00),
+            datetime!(2021-12-31 00:00:00 +09:00),
+            datetime!(2021-12-31 00:00:00 +09:00),
+            100.0,
+            Currency::USD,
+            Currency::USD,
+            "AAPL".to_string(),
+            "AAPL Fut Dec21".to_string(),
+            "AAPLZ1".to_string(),
+        );
+        let calendar = JointCalendar::new(vec![Calendar::SouthKorea(SouthKorea::new(SouthKoreaType::Settlement))])?;
+        let cd = RateIndex::new(String::from("91D"), Currency::KRW, "CD 91D".to_string(), "CD 91D".to_string())?;
+        let irs = PlainSwap::new_from_conventions(
+            Currency::KRW,
+            Currency::KRW,
+            None, None, None, None,
+            10_000_000_000.0,
+            datetime!(2021-01-04 00:00:00 +09:00),
+            datetime!(2021-01-04 00:00:00 +09:00),
+            datetime!(2022-01-04 00:00:00 +09:00),
+            Some(0.02),
+            Some(cd),
+            None,
+            true,
+            DayCountConvention::Actual365Fixed,
+            DayCountConvention::Actual365Fixed,
+            BusinessDayConvention::ModifiedFollowing,
+            BusinessDayConvention::ModifiedFollowing,
+            PaymentFrequency::Quarterly,
+            PaymentFrequency::Quarterly,
+            1,
+            0,
+            calendar,
+            "KRW IRS 1Y".to_string(),
+            "IRS1".to_string(),
+        )?;
+        let instruments = Instruments::new(vec![
+            Rc::new(Instrument::Futures(futures)),
+            Rc::new(Instrument::PlainSwap(irs)),
+        ]);
+        let match_parameter: MatchParameter = serde_json::from_str(r#"{
+            "collateral_curve_map": { "AAPL": "USDGOV" },
+            "borrowing_curve_map": { "AAPL": "AAPL" },
+            "rate_index_forward_curve_map": { "CD 91D": "KRWIRS" }
+        }"#)?;
+
+        let manifest = MarketDataManifest::resolve(&instruments, &match_parameter, evaluation_date)?;
+        let curves: Vec<&str> = manifest.get_requirements_of(MarketDataCategory::Curve).iter()
+            .map(|requirement| requirement.code.as_str())
+            .collect();
+        assert_eq!(curves, vec!["AAPL", "KRWIRS", "USDGOV"]);
+        assert_eq!(
+            manifest.get_requirement(MarketDataCategory::Stock, "AAPL").map(|r| r.instrument_codes.clone()),
+            Some(vec!["AAPLZ1".to_string()]),
+        );
+        assert!(manifest.get_requirement(MarketDataCategory::Dividend, "AAPL").expect("dividend").optional);
+        // the coupon from April is fixed before the evaluation date
+        let fixings = manifest.get_requirement(MarketDataCategory::PastDailyValue, "CD 91D").expect("fixings");
+        let (start, end) = fixings.fixing_period.expect("fixing period");
+        assert!(start >= date!(2021-03-25) && start < date!(2021-04-10), "start: {}", start);
+        assert_eq!(end, date!(2021-05-13));
+
+        let fixing_data = DailyValueData::new(
+            HashMap::from([(date!(2021-04-15), 0.0072)]),
+            Time::from_hms(15, 30, 0)?,
+            UtcOffset::from_hms(9, 0, 0)?,
+            Calendar::SouthKorea(SouthKorea::new(SouthKoreaType::Settlement)),
+            "CD 91D".to_string(),
+            "CD 91D".to_string(),
+        );
+        let stock = ValueData::new(130.0, Some(evaluation_date), Currency::USD, "AAPL".to_string(), "AAPL".to_string())?;
+        let msft = ValueData::new(250.0, Some(evaluation_date), Currency::USD, "MSFT".to_string(), "MSFT".to_string())?;
+        let snapshot = MarketDataSnapshot::new(evaluation_date)
+            .with_stock_data(HashMap::from([("AAPL".to_string(), stock), ("MSFT".to_string(), msft)]))
+            .with_curve_data(HashMap::from([
+                ("USDGOV".to_string(), curve("USDGOV")?),
+                ("KRWIRS".to_string(), curve("KRWIRS")?),
+            ]))
+            .with_past_daily_value_data(HashMap::from([("CD 91D".to_string(), fixing_data)]));
+
+        let diff = manifest.diff(&snapshot);
+        let missing: Vec<(MarketDataCategory, &str)> = diff.missing.iter()
+            .map(|requirement| (requirement.category, requirement.code.as_str()))
+            .collect();
+        assert_eq!(missing, vec![(MarketDataCategory::Curve, "AAPL"), (MarketDataCategory::Dividend, "AAPL")]);
+        assert_eq!(diff.incomplete_fixings.len(), 1);
+        assert_eq!(diff.unused, vec![(MarketDataCategory::Stock, "MSFT".to_string())]);
+        assert!(!diff.is_complete());
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+use crate::currency::{Currency, FxCode};
+use crate::data::{
+    market_data_snapshot::MarketDataCategory,
+    surface_data::SurfaceData,
+    value_data::ValueData,
+    vector_data::VectorData,
+};
+use crate::definitions::Real;
+use crate::instrument::{Instrument, InstrumentTrait, Instruments};
+use crate::pricing_engines::{
+    calculation_configuration::CalculationConfiguration,
+    calculation_result::CalculationResult,
+    engine_generator::EngineGenerator,
+    match_parameter::MatchParameter,
+    result_export::flatten_results,
+};
+//
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+    sync::Arc,
+};
+use time::OffsetDateTime;
+
+/// An intraday tick of the market data of EngineGenerator.
+/// The point updates change an existing point of the data, i.e., date must be a date of the curve
+/// and (date, strike) a point of the surface.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum MarketDataUpdate {
+    /// the price of a stock or an index
+    Spot { code: String, value: Real },
+    /// the rate of fx_code or, if not given, of its reciprocal
+    Fx { fx_code: FxCode, value: Real },
+    CurvePoint { code: String, date: OffsetDateTime, value: Real },
+    VolatilityPoint { code: String, date: OffsetDateTime, strike: Real, value: Real },
+    ConstantVolatility { code: String, value: Real },
+}
+
+impl MarketDataUpdate {
+    pub fn get_category(&self) -> MarketDataCategory {
+        match self {
+            MarketDataUpdate::Spot { .. } => MarketDataCategory::Stock,
+            MarketDataUpdate::Fx { .. } => MarketDataCategory::Fx,
+            MarketDataUpdate::CurvePoint { .. } => MarketDataCategory::Curve,
+            MarketDataUpdate::VolatilityPoint { .. } => MarketDataCategory::EquityVolatilitySurface,
+            MarketDataUpdate::ConstantVolatility { .. } => MarketDataCategory::EquityConstantVolatility,
+        }
+    }
+
+    pub fn get_code(&self) -> String {
+        match self {
+            MarketDataUpdate::Fx { fx_code, .. } => fx_code.to_string(),
+            MarketDataUpdate::Spot { code, .. }
+            | MarketDataUpdate::CurvePoint { code, .. }
+            | MarketDataUpdate::VolatilityPoint { code, .. }
+            | MarketDataUpdate::ConstantVolatility { code, .. } => code.clone(),
+        }
+    }
+
+    /// Set the updated value in the data where the data shared by Arc are copied on write.
+    /// It fails if the updated data (point) does not exist.
+    pub fn apply_to(
+        &self,
+        fx_data: &mut Arc<HashMap<FxCode, ValueData>>,
+        stock_data: &mut Arc<HashMap<String, ValueData>>,
+        curve_data: &mut Arc<HashMap<String, VectorData>>,
+        equity_constant_volatility_data: &mut Arc<HashMap<String, ValueData>>,
+        equity_volatility_surface_data: &mut Arc<HashMap<String, SurfaceData>>,
+    ) -> Result<()> {
+        let not_found = || anyhow!(
+            "({}:{}) no {:?} data of {} to update",
+            file!(), line!(), self.get_category(), self.get_code()
+        );
+        match self {
+            MarketDataUpdate::Spot { code, value } => {
+                Arc::make_mut(stock_data).get_mut(code).ok_or_else(not_found)?.set_value(*value);
+            },
+            MarketDataUpdate::Fx { fx_code, value } => {
+                let fx_data = Arc::make_mut(fx_data);
+                if let Some(data) = fx_data.get_mut(fx_code) {
+                    data.set_value(*value);
+                } else {
+                    fx_data.get_mut(&fx_code.reciprocal()).ok_or_else(not_found)?.set_value(1.0 / value);
+                }
+            },
+            MarketDataUpdate::CurvePoint { code, date, value } => {
+                Arc::make_mut(curve_data).get_mut(code).ok_or_else(not_found)?
+                    .set_value_on_date(date, *value)?;
+            },
+            MarketDataUpdate::VolatilityPoint { code, date, strike, value } => {
+                Arc::make_mut(equity_volatility_surface_data).get_mut(code).ok_or_else(not_found)?
+                    .set_value_on(date, *strike, *value)?;
+            },
+            MarketDataUpdate::ConstantVolatility { code, value } => {
+                Arc::make_mut(equity_constant_volatility_data).get_mut(code).ok_or_else(not_found)?
+                    .set_value(*value);
+            },
+        }
+        Ok(())
+    }
+
+    /// The instruments whose npv or greeks depend on the updated data:
+    /// - spot: the instruments with the underlying
+    /// - fx: the instruments pricing with the fx code, its reciprocal, or the KRW cross of one of its legs
+    /// - curve: the instruments using the curve and the instruments borrowing it (by the underlying code)
+    /// - volatility: the instruments requiring the volatility of the underlying
+    pub fn get_affected_instruments(
+        &self,
+        instruments: &Instruments,
+        match_parameter: &MatchParameter,
+    ) -> Result<Vec<Rc<Instrument>>> {
+        let mut res = match self {
+            MarketDataUpdate::Spot { code, .. } => instruments.instruments_with_underlying(code, None),
+            MarketDataUpdate::Fx { fx_code, .. } => instruments.iter()
+                .filter(|instrument| instrument.get_all_fxcodes_for_pricing().iter()
+                    .any(|pricing_code| fx_rate_depends_on(pricing_code, fx_code)))
+                .cloned()
+                .collect(),
+            MarketDataUpdate::CurvePoint { code, .. } => {
+                let mut res = instruments.instruments_using_curve(code, match_parameter, None)
+                    .with_context(|| anyhow!("({}:{}) failed to find the instruments using {}", file!(), line!(), code))?;
+                res.extend(instruments.instruments_with_underlying(code, None));
+                res
+            },
+            MarketDataUpdate::VolatilityPoint { code, .. }
+            | MarketDataUpdate::ConstantVolatility { code, .. } => instruments.instruments_with_underlying(code, None)
+                .into_iter()
+                .filter(|instrument| instrument.get_underlying_codes_requiring_volatility().contains(&code))
+                .collect(),
+        };
+        let mut codes = Vec::<String>::new();
+        res.retain(|instrument| {
+            let new = !codes.contains(instrument.get_code());
+            if new {
+                codes.push(instrument.get_code().clone());
+            }
+            new
+        });
+        Ok(res)
+    }
+}
+
+/// whether the rate of pricing_code (direct, reciprocal or through KRW as in EngineGenerator::get_fx_rate) uses updated
+pub(crate) fn fx_rate_depends_on(pricing_code: &FxCode, updated: &FxCode) -> bool {
+    if pricing_code == updated || pricing_code.reciprocal() == *updated {
+        return true;
+    }
+    let krw_leg = match (updated.get_currency1(), updated.get_currency2()) {
+        (currency, Currency::KRW) | (Currency::KRW, currency) => currency,
+        _ => return false,
+    };
+    krw_leg == pricing_code.get_currency1() || krw_leg == pricing_code.get_currency2()
+}
+
+/// a row of flatten_results which changed. previous (current) is None if the row is new (removed).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResultChange {
+    pub risk_type: String,
+    pub risk_factor: String,
+    pub bucket: String,
+    pub previous: Option<f64>,
+    pub current: Option<f64>,
+}
+
+/// The recalculated result of an instrument after market data updates with the changes from the previous result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CalculationResultDelta {
+    pub instrument_code: String,
+    /// value (npv * unit_notional) before and after the updates
+    pub previous_value: Option<Real>,
+    pub current_value: Option<Real>,
+    pub changes: Vec<ResultChange>,
+    pub result: CalculationResult,
+}
+
+impl CalculationResultDelta {
+    pub fn new(
+        instrument_code: &str,
+        previous: Option<&CalculationResult>,
+        current: CalculationResult,
+        configuration: &CalculationConfiguration,
+    ) -> Result<CalculationResultDelta> {
+        let rows = |result: &CalculationResult| -> Result<BTreeMap<(String, String, String), f64>> {
+            let results = HashMap::from([(instrument_code.to_string(), result.clone())]);
+            Ok(flatten_results(&results, configuration)?
+                .into_iter()
+                .map(|row| ((row.risk_type, row.risk_factor, row.bucket), row.value))
+                .collect())
+        };
+        let previous_rows = match previous {
+            Some(previous) => rows(previous)?,
+            None => BTreeMap::new(),
+        };
+        let mut current_rows = rows(&current)?;
+
+        let mut changes = Vec::new();
+        for (key, previous_value) in previous_rows {
+            let current_value = current_rows.remove(&key);
+            if current_value != Some(previous_value) {
+                changes.push(ResultChange {
+                    risk_type: key.0,
+                    risk_factor: key.1,
+                    bucket: key.2,
+                    previous: Some(previous_value),
+                    current: current_value,
+                });
+            }
+        }
+        for (key, current_value) in current_rows {
+            changes.push(ResultChange {
+                risk_type: key.0,
+                risk_factor: key.1,
+                bucket: key.2,
+                previous: None,
+                current: Some(current_value),
+            });
+        }
+
+        Ok(CalculationResultDelta {
+            instrument_code: instrument_code.to_string(),
+            previous_value: previous.and_then(|result| result.get_value()),
+            current_value: current.get_value(),
+            changes,
+            result: current,
+        })
+    }
+
+    /// current_value - previous_value, None if either is missing
+    pub fn get_value_change(&self) -> Option<Real> {
+        Some(self.current_value? - self.previous_value?)
+    }
+}
+
+/// Publishes the market data updates to the subscribed engine generators
+/// which recalculate the affected instruments and publish the deltas to their result subscribers.
+#[derive(Default)]
+pub struct MarketDataFeed {
+    engine_generator_observers: Vec<Rc<RefCell<EngineGenerator>>>,
+}
+
+impl MarketDataFeed {
+    pub fn new() -> MarketDataFeed {
+        MarketDataFeed::default()
+    }
+
+    pub fn add_engine_generator_observer(&mut self, observer: Rc<RefCell<EngineGenerator>>) {
+        self.engine_generator_observers.push(observer);
+    }
+
+    /// Every observer is updated even if some of them fail (a failed observer keeps its data and results).
+    /// The error lists the failed observers by the order in which they are added.
+    pub fn publish(&self, updates: &[MarketDataUpdate]) -> Result<()> {
+        let mut errors = Vec::new();
+        for (index, observer) in self.engine_generator_observers.iter().enumerate() {
+            if let Err(e) = observer.borrow_mut().update_market_data(updates) {
+                errors.push(format!("observer {}: {:?}", index, e));
+            }
+        }
+        if !errors.is_empty() {
+            bail!(
+                "({}:{}) failed to update the market data of {} of {} observers\n{}",
+                file!(), line!(), errors.len(), self.engine_generator_observers.len(), errors.join("\n")
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fx_rate_depends_on() {
+        let usdkrw = FxCode::new(Currency::USD, Currency::KRW);
+        let eurusd = FxCode::new(Currency::EUR, Currency::USD);
+        assert!(fx_rate_depends_on(&usdkrw, &usdkrw));
+        assert!(fx_rate_depends_on(&usdkrw.reciprocal(), &usdkrw));
+        // EURUSD through EURKRW and USDKRW
+        assert!(fx_rate_depends_on(&eurusd, &usdkrw));
+        assert!(!fx_rate_depends_on(&usdkrw, &FxCode::new(Currency::EUR, Currency::KRW)));
+        assert!(!fx_rate_depends_on(&usdkrw, &eurusd));
+    }
+
+    #[test]
+    fn test_publish_to_every_observer() {
+        // the observers fail as their instruments are not distributed
+        let mut feed = MarketDataFeed::new();
+        for _ in 0..2 {
+            feed.add_engine_generator_observer(Rc::new(RefCell::new(EngineGenerator::builder())));
+        }
+        let error = feed.publish(&[MarketDataUpdate::Spot { code: "KOSPI2".to_string(), value: 351.0 }])
+            .expect_err("observers without instruments");
+        let message = error.to_string();
+        assert!(message.contains("2 of 2 observers"), "{}", message);
+        assert!(message.contains("observer 0:") && message.contains("observer 1:"), "{}", message);
+    }
+
+    #[test]
+    fn test_market_data_update_serde() -> Result<()> {
+        let update: MarketDataUpdate = serde_json::from_str(r#"{"type": "Fx", "fx_code": "USDKRW", "value": 1300.0}"#)?;
+        assert_eq!(update.get_category(), MarketDataCategory::Fx);
+        assert_eq!(update.get_code(), "USDKRW");
+        Ok(())
+    }
+}
@@ -8,22 +8,69 @@ use crate::enums::{
 };
 use crate::instruments::plain_swap::PlainSwapType;
 //
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
 use std::collections::HashMap;
 use anyhow::{Result, Context, anyhow};
-//
+
+type BondDiscountCurveMap = HashMap<(String, IssuerType, CreditRating, Currency), String>;
+
+/// an item of bond_discount_curve_map in files, since the keys of JSON objects must be strings
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct BondDiscountCurveEntry {
+    issuer: String,
+    issuer_type: IssuerType,
+    credit_rating: CreditRating,
+    currency: Currency,
+    curve_name: String,
+}
+
+fn serialize_bond_discount_curve_map<S: Serializer>(map: &BondDiscountCurveMap, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut entries: Vec<BondDiscountCurveEntry> = map.iter()
+        .map(|((issuer, issuer_type, credit_rating, currency), curve_name)| BondDiscountCurveEntry {
+            issuer: issuer.clone(),
+            issuer_type: *issuer_type,
+            credit_rating: *credit_rating,
+            currency: *currency,
+            curve_name: curve_name.clone(),
+        })
+        .collect();
+    entries.sort();
+    entries.serialize(serializer)
+}
+
+fn deserialize_bond_discount_curve_map<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BondDiscountCurveMap, D::Error> {
+    let entries = Vec::<BondDiscountCurveEntry>::deserialize(deserializer)?;
+    Ok(entries.into_iter()
+        .map(|entry| ((entry.issuer, entry.issuer_type, entry.credit_rating, entry.currency), entry.curve_name))
+        .collect())
+}
+
+fn default_dummy_string() -> String {
+    String::from("Dummy")
+}
+
+/// In files, bond_discount_curve_map is a list of
+/// {"issuer": .., "issuer_type": .., "credit_rating": .., "currency": .., "curve_name": ..}
+/// and the missing maps are empty.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MatchParameter {
     // Underlying asset code: String -> curve_name: String
     // Underlying code examples are stock, bond, commodity, etc.
+    #[serde(default)]
     collateral_curve_map: HashMap<String, String>,
     // Underlying asset code: String -> curve_name: String
     // Underlying code examples are stock, bond, commodity, etc.
+    #[serde(default)]
     borrowing_curve_map: HashMap<String, String>,
     // (issuer: String, 
     //  issuer_type: IssuerType, 
     //  credit_rating: CreditRating, 
     //  currency: Currency) -> String
+    #[serde(
+        default,
+        serialize_with = "serialize_bond_discount_curve_map",
+        deserialize_with = "deserialize_bond_discount_curve_map"
+    )]
     bond_discount_curve_map: HashMap<(
         String, 
         IssuerType, 
@@ -31,13 +78,17 @@ pub struct MatchParameter {
         Currency
     ), String>,
     // index code: RateIndexCode -> String
+    #[serde(default)]
     rate_index_forward_curve_map: HashMap<String, String>,
     // Currency::XXX -> String::from("XXXCRS")
     // But if XXX == USD, then it is String::from("USDOIS")
+    #[serde(default)]
     crs_curve_map: HashMap<Currency, String>,
     //
+    #[serde(default)]
     funding_cost_map: HashMap<Currency, String>,
     //
+    #[serde(skip, default = "default_dummy_string")]
     dummy_string: String,
 }
 
@@ -465,5 +516,24 @@ mod tests {
         );
         Ok(())
     }
-}
 
+    #[test]
+    fn test_match_parameter_serde() -> Result<()> {
+        let json = r#"{
+            "collateral_curve_map": {"KOSPI2": "KSD"},
+            "bond_discount_curve_map": [
+                {"issuer": "Korea Gov", "issuer_type": "Government", "credit_rating": "None", "currency": "KRW", "curve_name": "KRWGOV"}
+            ]
+        }"#;
+        let match_parameter: MatchParameter = serde_json::from_str(json)?;
+        let key = ("Korea Gov".to_string(), IssuerType::Government, CreditRating::None, Currency::KRW);
+        assert_eq!(match_parameter.bond_discount_curve_map[&key], "KRWGOV");
+        assert_eq!(match_parameter.dummy_string, "Dummy");
+        assert!(match_parameter.crs_curve_map.is_empty());
+
+        let deserialized: MatchParameter = serde_json::from_str(&serde_json::to_string(&match_parameter)?)?;
+        assert_eq!(deserialized.bond_discount_curve_map, match_parameter.bond_discount_curve_map);
+        assert_eq!(deserialized.collateral_curve_map, match_parameter.collateral_curve_map);
+        Ok(())
+    }
+}
@@ -1,21 +1,31 @@
 pub mod option_analytic_pricer;
+pub mod option_monte_carlo_pricer;
 pub mod engine;
 pub mod calculation_result;
 pub mod pricer;
 pub mod calculation_configuration;
 pub mod montecarlo {
     pub mod rand_generator;
+    pub mod heston_path_generator;
 }
 pub mod match_parameter;
 pub mod npv_result;
 pub mod bond_pricer;
 pub mod krx_yield_pricer;
+pub mod bond_analytics;
+pub mod implied_volatility;
+pub mod scenario;
 pub mod pricer_factory;
 pub mod ktbf_pricer;
 pub mod plain_swap_pricer;
 pub mod fx_futures_pricer;
 pub mod engine_generator;
+pub mod engine_worker;
+pub mod run_configuration;
+pub mod result_export;
 pub mod futures_pricer;
 pub mod cash_pricer;
 pub mod identity_pricer;
-pub mod unit_pricer;
\ No newline at end of file
+pub mod unit_pricer;pub mod error_report;
+pub mod market_data_manifest;
+pub mod market_data_update;
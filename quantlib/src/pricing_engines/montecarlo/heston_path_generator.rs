@@ -0,0 +1,231 @@
+use crate::definitions::{Real, Time};
+use crate::enums::MonteCarloScheme;
+use crate::parameters::volatilities::heston_volatility::HestonParameters;
+//
+use anyhow::{bail, Result};
+use ndarray::Array2;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::StandardNormal;
+
+/// critical value of psi switching the quadratic and exponential schemes (Andersen 2008)
+const PSI_CRITICAL: f64 = 1.5;
+
+/// Heston path generator with the Quadratic-Exponential (QE) scheme of Andersen (2008)
+/// and its martingale correction (gamma1 = gamma2 = 0.5), or the full truncation Euler scheme.
+/// Paths are generated for ln(S / F) and multiplied by the forwards, so that E[S(t)] = F(t) without quanto.
+/// A quanto option has the drift -quanto_factor * sqrt(v) in ln(S) (Quanto::quanto_adjust = fx vol * correlation).
+/// The seed is fixed per generator, so that bumped revaluations use the same random numbers.
+#[derive(Debug, Clone)]
+pub struct HestonPathGenerator {
+    parameters: HestonParameters,
+    max_time_step: Time,
+    seed: u64,
+    scheme: MonteCarloScheme,
+    quanto_factor: Real,
+}
+
+impl HestonPathGenerator {
+    pub fn new(parameters: HestonParameters, seed: u64) -> HestonPathGenerator {
+        HestonPathGenerator {
+            parameters,
+            max_time_step: 1.0 / 52.0,
+            seed,
+            scheme: MonteCarloScheme::default(),
+            quanto_factor: 0.0,
+        }
+    }
+
+    pub fn with_scheme(mut self, scheme: MonteCarloScheme) -> HestonPathGenerator {
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn with_quanto_factor(mut self, quanto_factor: Real) -> HestonPathGenerator {
+        self.quanto_factor = quanto_factor;
+        self
+    }
+
+    pub fn with_max_time_step(mut self, max_time_step: Time) -> Result<HestonPathGenerator> {
+        if max_time_step <= 0.0 {
+            bail!("({}:{}) max_time_step must be positive, got {}", file!(), line!(), max_time_step);
+        }
+        self.max_time_step = max_time_step;
+        Ok(self)
+    }
+
+    /// underlying levels of shape (number_of_paths, times.len()) where forwards[i] is the forward at times[i]
+    pub fn generate(&self, forwards: &[Real], times: &[Time], number_of_paths: usize) -> Result<Array2<Real>> {
+        if forwards.len() != times.len() || times.is_empty() {
+            bail!(
+                "({}:{}) forwards ({}) and times ({}) must have the same positive length",
+                file!(), line!(), forwards.len(), times.len()
+            );
+        }
+        if times[0] <= 0.0 || times.windows(2).any(|w| w[0] >= w[1]) {
+            bail!("({}:{}) times must be positive and strictly increasing: {:?}", file!(), line!(), times);
+        }
+
+        let (v0, kappa, theta, xi, rho) = (
+            self.parameters.get_v0() as f64,
+            self.parameters.get_kappa() as f64,
+            self.parameters.get_theta() as f64,
+            self.parameters.get_xi() as f64,
+            self.parameters.get_rho() as f64,
+        );
+        let quanto_factor = self.quanto_factor as f64;
+
+        // time steps between the observation times
+        let mut steps: Vec<(f64, Option<usize>)> = Vec::new(); // (dt, observation index at the end)
+        let mut previous = 0.0;
+        for (i, t) in times.iter().enumerate() {
+            let interval = (*t - previous) as f64;
+            let n = (interval / self.max_time_step as f64).ceil().max(1.0) as usize;
+            for s in 0..n {
+                steps.push((interval / n as f64, if s == n - 1 { Some(i) } else { None }));
+            }
+            previous = *t;
+        }
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut paths = Array2::<Real>::zeros((number_of_paths, times.len()));
+        for p in 0..number_of_paths {
+            let mut v = v0;
+            let mut x = 0.0; // ln(S / F)
+            for (dt, observation) in steps.iter() {
+                let dt = *dt;
+                let (v_next, dx) = match self.scheme {
+                    MonteCarloScheme::QuadraticExponential => qe_step(&mut rng, v, dt, kappa, theta, xi, rho),
+                    MonteCarloScheme::FullTruncationEuler => euler_step(&mut rng, v, dt, kappa, theta, xi, rho),
+                };
+                // trapezoidal integral of sqrt(v) for the quanto drift
+                let quanto_drift = quanto_factor * 0.5 * (v.max(0.0).sqrt() + v_next.max(0.0).sqrt()) * dt;
+                x += dx - quanto_drift;
+                v = v_next;
+                if let Some(i) = observation {
+                    paths[[p, *i]] = forwards[*i] * x.exp() as Real;
+                }
+            }
+        }
+        Ok(paths)
+    }
+}
+
+/// (v(t + dt), ln(S(t + dt) / S(t)) - ln(F(t + dt) / F(t))) by the QE scheme with the martingale correction
+fn qe_step(rng: &mut StdRng, v: f64, dt: f64, kappa: f64, theta: f64, xi: f64, rho: f64) -> (f64, f64) {
+    let (gamma1, gamma2) = (0.5, 0.5);
+    let ekdt = (-kappa * dt).exp();
+    let m = theta + (v - theta) * ekdt;
+    let s2 = v * xi * xi * ekdt / kappa * (1.0 - ekdt)
+        + theta * xi * xi / (2.0 * kappa) * (1.0 - ekdt) * (1.0 - ekdt);
+    let psi = s2 / (m * m);
+
+    let k1 = gamma1 * dt * (kappa * rho / xi - 0.5) - rho / xi;
+    let k2 = gamma2 * dt * (kappa * rho / xi - 0.5) + rho / xi;
+    let k3 = gamma1 * dt * (1.0 - rho * rho);
+    let k4 = gamma2 * dt * (1.0 - rho * rho);
+    let a_coef = k2 + 0.5 * k4;
+
+    let (v_next, k0) = if psi <= PSI_CRITICAL {
+        let b2 = 2.0 / psi - 1.0 + (2.0 / psi).sqrt() * (2.0 / psi - 1.0).sqrt();
+        let a = m / (1.0 + b2);
+        let z: f64 = rng.sample(StandardNormal);
+        let v_next = a * (b2.sqrt() + z).powi(2);
+        let k0 = if a_coef < 1.0 / (2.0 * a) {
+            -a_coef * b2 * a / (1.0 - 2.0 * a_coef * a) + 0.5 * (1.0 - 2.0 * a_coef * a).ln()
+                - (k1 + 0.5 * k3) * v
+        } else {
+            -rho * kappa * theta / xi * dt
+        };
+        (v_next, k0)
+    } else {
+        let prob = (psi - 1.0) / (psi + 1.0);
+        let beta = (1.0 - prob) / m;
+        let u: f64 = rng.gen();
+        let v_next = if u <= prob { 0.0 } else { ((1.0 - prob) / (1.0 - u)).ln() / beta };
+        let k0 = if a_coef < beta {
+            -(prob + beta * (1.0 - prob) / (beta - a_coef)).ln() - (k1 + 0.5 * k3) * v
+        } else {
+            -rho * kappa * theta / xi * dt
+        };
+        (v_next, k0)
+    };
+
+    let z: f64 = rng.sample(StandardNormal);
+    let dx = k0 + k1 * v + k2 * v_next + (k3 * v + k4 * v_next).max(0.0).sqrt() * z;
+    (v_next, dx)
+}
+
+/// the same as qe_step by the full truncation Euler scheme where v may be negative
+fn euler_step(rng: &mut StdRng, v: f64, dt: f64, kappa: f64, theta: f64, xi: f64, rho: f64) -> (f64, f64) {
+    let v_plus = v.max(0.0);
+    let z1: f64 = rng.sample(StandardNormal);
+    let z2: f64 = rng.sample(StandardNormal);
+    let zx = rho * z1 + (1.0 - rho * rho).sqrt() * z2;
+    let v_next = v + kappa * (theta - v_plus) * dt + xi * (v_plus * dt).sqrt() * z1;
+    let dx = -0.5 * v_plus * dt + (v_plus * dt).sqrt() * zx;
+    (v_next, dx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::OptionType;
+    use ndarray::Axis;
+
+    #[test]
+    fn test_heston_qe_paths() -> Result<()> {
+        let parameters = HestonParameters::new(0.03, 1.5, 0.05, 0.8, -0.7)?;
+        let forwards = [101.0, 103.0];
+        let times = [0.5, 1.0];
+        let number_of_paths = 20000;
+        let paths = HestonPathGenerator::new(parameters, 42).generate(&forwards, &times, number_of_paths)?;
+
+        let means = paths.mean_axis(Axis(0)).unwrap();
+        for (i, forward) in forwards.iter().enumerate() {
+            assert!((means[i] / forward - 1.0).abs() < 5.0e-3, "mean: {}, forward: {}", means[i], forward);
+        }
+
+        // Monte Carlo vs semi-analytic prices at the last time
+        for strike in [80.0, 103.0, 125.0] {
+            let payoffs: Vec<f64> = paths.column(1).iter().map(|s| (*s as f64 - strike).max(0.0)).collect();
+            let mean = payoffs.iter().sum::<f64>() / number_of_paths as f64;
+            let variance = payoffs.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / (number_of_paths - 1) as f64;
+            let standard_error = (variance / number_of_paths as f64).sqrt();
+            let analytic = parameters.price(OptionType::Call, 103.0, strike as Real, 1.0, 1.0) as f64;
+            assert!(
+                (mean - analytic).abs() < 4.0 * standard_error + 0.02,
+                "strike: {}, mc: {} (se: {}), analytic: {}", strike, mean, standard_error, analytic
+            );
+        }
+
+        // the same seed gives the same paths
+        let again = HestonPathGenerator::new(parameters, 42).generate(&forwards, &times, 10)?;
+        assert_eq!(again.row(3), paths.row(3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_heston_euler_and_quanto() -> Result<()> {
+        let number_of_paths = 20000;
+        let parameters = HestonParameters::new(0.03, 1.5, 0.05, 0.8, -0.7)?;
+        let euler = HestonPathGenerator::new(parameters, 7)
+            .with_max_time_step(1.0 / 100.0)?
+            .with_scheme(MonteCarloScheme::FullTruncationEuler)
+            .generate(&[100.0], &[1.0], number_of_paths)?;
+        let mean = euler.column(0).iter().map(|s| (*s as f64 - 100.0).max(0.0)).sum::<f64>() / number_of_paths as f64;
+        let analytic = parameters.price(OptionType::Call, 100.0, 100.0, 1.0, 1.0) as f64;
+        assert!((mean - analytic).abs() < 0.15, "euler: {}, analytic: {}", mean, analytic);
+
+        // with (almost) no vol of vol, the quanto forward is F * exp(-quanto_factor * sqrt(v0) * t)
+        let flat = HestonParameters::new(0.04, 1.0, 0.04, 1.0e-2, 0.0)?;
+        let quanto_factor = 0.1 * -0.5;
+        let generator = HestonPathGenerator::new(flat, 11);
+        let paths = generator.clone().generate(&[100.0], &[1.0], number_of_paths)?;
+        let quanto_paths = generator.with_quanto_factor(quanto_factor).generate(&[100.0], &[1.0], number_of_paths)?;
+        let expected_ratio = (-quanto_factor * 0.2).exp();
+        for (s, quanto_s) in paths.column(0).iter().zip(quanto_paths.column(0).iter()).take(100) {
+            assert!((quanto_s / s / expected_ratio - 1.0).abs() < 1.0e-3, "{} vs {}", quanto_s / s, expected_ratio);
+        }
+        Ok(())
+    }
+}
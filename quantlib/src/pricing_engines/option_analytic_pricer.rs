@@ -15,9 +15,9 @@ use crate::parameters::{
 use crate::pricing_engines::{
     npv_result::NpvResult,
     futures_pricer::FuturesPricer,
+    implied_volatility::{black_price, implied_black_volatility},
 };
 use crate::instrument::InstrumentTrait;
-use crate::enums::OptionType;
 //
 use anyhow::{anyhow, Context, Result};
 
@@ -25,7 +25,6 @@ use std::{
     rc::Rc,
     cell::RefCell,
 };
-use statrs::distribution::{Normal, ContinuousCDF};
 
 pub struct OptionAnalyticPricer {
     evaluation_date: Rc<RefCell<EvaluationDate>>,
@@ -78,13 +77,6 @@ impl PricerTrait for OptionAnalyticPricer {
             &maturity,
         );
         
-        let total_variance = self.volatility
-            .borrow()
-            .total_variance(t, forward_moneyness)?;
-        let total_deviation = self.volatility
-            .borrow()
-            .total_deviation(t, forward_moneyness)?;
-
         if instrument.get_currency() != instrument.get_underlying_currency()? &&
         self.quanto.is_none() 
         {
@@ -95,41 +87,65 @@ impl PricerTrait for OptionAnalyticPricer {
             ));
         }
 
-        let vol = self.volatility.borrow()
-            .get_value(t, forward_moneyness);
-        let quanto_adjustment = match &self.quanto {
-            Some(quanto) => {
-                vol * t * quanto.borrow().quanto_adjust(t, forward_moneyness)
-            }
+        // total_deviation fails where get_value panics
+        self.volatility.borrow().total_deviation(t, forward_moneyness)?;
+        let vol = self.volatility.borrow().get_value(t, forward_moneyness);
+        let quanto_factor = match &self.quanto {
+            Some(quanto) => quanto.borrow().quanto_adjust(t, forward_moneyness),
             None => 0.0,
         };
-
-        let y = forward_moneyness.ln();
-        let option_type = instrument.get_option_type()?;
-
         let dsc = self.discount_curve.borrow().get_discount_factor(t)?;
 
-        let d1 = (-y + total_variance / 2.0 - quanto_adjustment) / total_deviation;
-        let d2 = d1 - total_deviation;
-
-        let normal = Normal::new(0.0, 1.0).unwrap(); 
-        let nd1 = normal.cdf(d1 as f64) as Real;
-        let nd2 = normal.cdf(d2 as f64) as Real;
-
-        match option_type {
-            OptionType::Call => {
-                Ok(dsc * (fwd * nd1 - strike * nd2))
-            }
-            OptionType::Put => {
-                Ok(dsc * (strike * (1.0 - nd2) - fwd * (1.0 - nd1)))
-            }
-        }
+        Ok(black_price(instrument.get_option_type()?, fwd, strike, t, dsc, vol, quanto_factor))
     }
 
     fn npv_result(&self, instrument: &Instrument) -> Result<NpvResult> {
         let npv = self.npv(instrument)?;
         Ok(NpvResult::new_from_npv(npv))
     }
+
+    /// Black volatility reproducing the price with the same forward, discounting and quanto adjustment as npv.
+    /// The quanto adjustment (fx volatility * correlation) is taken at the forward moneyness of the strike.
+    fn implied_volatility(&self, instrument: &Instrument, price: Real) -> Result<Real> {
+        let maturity = instrument.get_maturity()
+            .context("(OptionAnalyticPricer:implied_volatility) Failed to get maturity")?;
+        let fwd = self.futures_helper.fair_forward(maturity)?;
+        let strike = instrument.get_strike()?;
+        let forward_moneyness = strike / fwd;
+        let t = self.time_calculator.get_time_difference(
+            self.evaluation_date.borrow().get_date(),
+            maturity,
+        );
+
+        if instrument.get_currency() != instrument.get_underlying_currency()? &&
+        self.quanto.is_none() 
+        {
+            return Err(anyhow!(
+                "({}:{}) {} ({}) has different currency from underlying market_price ({}) but no quanto is provided",
+                file!(), line!(),
+                instrument.get_name(), instrument.get_code(), self.market_price.borrow().get_name(),
+            ));
+        }
+
+        let quanto_factor = match &self.quanto {
+            Some(quanto) => quanto.borrow().quanto_adjust(t, forward_moneyness),
+            None => 0.0,
+        };
+        let dsc = self.discount_curve.borrow().get_discount_factor(t)?;
+
+        implied_black_volatility(
+            instrument.get_option_type()?,
+            fwd,
+            strike,
+            t,
+            dsc,
+            price,
+            quanto_factor,
+        ).with_context(|| anyhow!(
+            "({}:{}) failed to get implied volatility of {} ({})",
+            file!(), line!(), instrument.get_name(), instrument.get_code(),
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -260,6 +276,17 @@ pub mod test {
         let expected_npv = 6.41674;
 
         assert!((npv - expected_npv).abs() < 1.0e-5, "npv: {}, expected_npv: {}", npv, expected_npv);
+
+        // the implied volatility reproduces the npv
+        let implied_vol = pricer.implied_volatility(&inst, npv)?;
+        let maturity_t = NullCalendar::new().get_time_difference(&eval_date, &maturity);
+        let fwd = FuturesPricer::new(market_price.clone(), discount_curve.clone(), discount_curve.clone())
+            .fair_forward(&maturity)?;
+        let vol = volatility.borrow().get_value(maturity_t, spot * 0.85 / fwd);
+        assert!(
+            (implied_vol - vol).abs() < 1.0e-4,
+            "implied volatility: {}, volatility: {}", implied_vol, vol
+        );
         
         Ok(())
     }
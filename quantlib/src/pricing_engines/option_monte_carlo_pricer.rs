@@ -0,0 +1,134 @@
+use crate::time::{
+    calendars::nullcalendar::NullCalendar,
+    calendar_trait::CalendarTrait,
+};
+use crate::evaluation_date::EvaluationDate;
+use crate::parameters::market_price::MarketPrice;
+use crate::definitions::Real;
+use crate::instrument::{Instrument, InstrumentTrait};
+use crate::parameters::{
+    zero_curve::ZeroCurve,
+    volatility::Volatility,
+    quanto::Quanto,
+};
+use crate::pricing_engines::{
+    pricer::PricerTrait,
+    npv_result::NpvResult,
+    calculation_configuration::MonteCarloConfiguration,
+    futures_pricer::FuturesPricer,
+    montecarlo::heston_path_generator::HestonPathGenerator,
+};
+use crate::enums::OptionType;
+//
+use anyhow::{anyhow, bail, Context, Result};
+use std::{
+    rc::Rc,
+    cell::RefCell,
+};
+
+/// Monte Carlo pricer of vanilla options under the Heston model (see HestonPathGenerator).
+/// The volatility must be Volatility::HestonVolatility whose volatility bumps at the maturity and strike
+/// are applied as a shift of the model (HestonVolatility::get_bumped_parameters).
+/// Quanto options are simulated with the quanto drift of the fx volatility and correlation at the strike.
+pub struct OptionMonteCarloPricer {
+    evaluation_date: Rc<RefCell<EvaluationDate>>,
+    market_price: Rc<RefCell<MarketPrice>>,
+    futures_helper: FuturesPricer,
+    discount_curve: Rc<RefCell<ZeroCurve>>,
+    volatility: Rc<RefCell<Volatility>>,
+    quanto: Option<Rc<RefCell<Quanto>>>,
+    monte_carlo: MonteCarloConfiguration,
+    time_calculator: NullCalendar,
+}
+
+impl OptionMonteCarloPricer {
+    pub fn new(
+        evaluation_date: Rc<RefCell<EvaluationDate>>,
+        market_price: Rc<RefCell<MarketPrice>>,
+        collateral_curve: Rc<RefCell<ZeroCurve>>,
+        borrowing_curve: Rc<RefCell<ZeroCurve>>,
+        discount_curve: Rc<RefCell<ZeroCurve>>,
+        volatility: Rc<RefCell<Volatility>>,
+        quanto: Option<Rc<RefCell<Quanto>>>,
+    ) -> OptionMonteCarloPricer {
+        let futures_helper = FuturesPricer::new(
+            market_price.clone(),
+            collateral_curve,
+            borrowing_curve,
+        );
+
+        OptionMonteCarloPricer {
+            evaluation_date,
+            market_price,
+            futures_helper,
+            discount_curve,
+            volatility,
+            quanto,
+            monte_carlo: MonteCarloConfiguration::default(),
+            time_calculator: NullCalendar::new(),
+        }
+    }
+
+    pub fn with_monte_carlo_configuration(mut self, monte_carlo: MonteCarloConfiguration) -> OptionMonteCarloPricer {
+        self.monte_carlo = monte_carlo;
+        self
+    }
+}
+
+impl PricerTrait for OptionMonteCarloPricer {
+    fn npv(&self, instrument: &Instrument) -> Result<Real> {
+        if instrument.get_currency() != instrument.get_underlying_currency()? && self.quanto.is_none() {
+            return Err(anyhow!(
+                "({}:{}) {} ({}) has different currency from underlying market_price ({}) but no quanto is provided",
+                file!(), line!(),
+                instrument.get_name(), instrument.get_code(), self.market_price.borrow().get_name(),
+            ));
+        }
+        let maturity = instrument.get_maturity()
+            .context("(OptionMonteCarloPricer:npv) Failed to get maturity")?;
+        let fwd = self.futures_helper.fair_forward(maturity)?;
+        let strike = instrument.get_strike()?;
+        let option_type = instrument.get_option_type()?;
+        let t = self.time_calculator.get_time_difference(
+            self.evaluation_date.borrow().get_date(),
+            maturity,
+        );
+        let dsc = self.discount_curve.borrow().get_discount_factor(t)?;
+        let payoff = |underlying: Real| match option_type {
+            OptionType::Call => (underlying - strike).max(0.0),
+            OptionType::Put => (strike - underlying).max(0.0),
+        };
+        if t <= 0.0 {
+            return Ok(dsc * payoff(fwd));
+        }
+
+        let spot_moneyness = strike / self.market_price.borrow().get_value();
+        let parameters = match &*self.volatility.borrow() {
+            Volatility::HestonVolatility(heston) => heston.get_bumped_parameters(t, spot_moneyness),
+            volatility => bail!(
+                "({}:{}) OptionMonteCarloPricer needs a Heston volatility but {} is {:?}",
+                file!(), line!(), volatility.get_code(), volatility.get_volatility_type(),
+            ),
+        };
+
+        let quanto_factor = match &self.quanto {
+            Some(quanto) => quanto.borrow().quanto_adjust(t, strike / fwd),
+            None => 0.0,
+        };
+        let number_of_paths = self.monte_carlo.get_number_of_paths();
+        let paths = HestonPathGenerator::new(parameters, self.monte_carlo.get_seed())
+            .with_max_time_step(self.monte_carlo.get_max_time_step())?
+            .with_scheme(self.monte_carlo.get_scheme())
+            .with_quanto_factor(quanto_factor)
+            .generate(&[fwd], &[t], number_of_paths)?;
+        let mean = paths.column(0).iter()
+            .map(|underlying| payoff(*underlying) as f64)
+            .sum::<f64>() / number_of_paths as f64;
+        Ok(dsc * mean as Real)
+    }
+
+    fn npv_result(&self, instrument: &Instrument) -> Result<NpvResult> {
+        let npv = self.npv(instrument)?;
+        Ok(NpvResult::new_from_npv(npv))
+    }
+}
@@ -4,11 +4,14 @@ use crate::instrument::{
     InstrumentTrait,
 };
 use crate::definitions::Real;
+use crate::enums::YieldConvention;
 use crate::pricing_engines::npv_result::NpvResult;
+use crate::pricing_engines::bond_analytics::BondAnalytics;
 use crate::pricing_engines::{
     bond_pricer::BondPricer,
     futures_pricer::FuturesPricer,
     option_analytic_pricer::OptionAnalyticPricer,
+    option_monte_carlo_pricer::OptionMonteCarloPricer,
     ktbf_pricer::KtbfPricer,
     krx_yield_pricer::KrxYieldPricer,
     plain_swap_pricer::PlainSwapPricer,
@@ -17,7 +20,7 @@ use crate::pricing_engines::{
     unit_pricer::UnitPricer,
 };
 //
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use enum_dispatch::enum_dispatch;
 use std::collections::HashMap;
 
@@ -34,12 +37,31 @@ pub trait PricerTrait {
         Ok(map)
     
     }
+    /// volatility implied by the given price (npv of the instrument)
+    fn implied_volatility(&self, instrument: &Instrument, _price: Real) -> Result<Real> {
+        Err(anyhow!(
+            "({}:{}) implied volatility is not supported for {} ({})",
+            file!(), line!(), instrument.get_name(), instrument.get_code()
+        ))
+    }
+    /// bond code -> BondAnalytics (the instrument itself for bonds, the underlying bonds for KTBF)
+    fn bond_analytics(
+        &self,
+        instrument: &Instrument,
+        _yield_convention: YieldConvention,
+    ) -> Result<HashMap<String, BondAnalytics>> {
+        Err(anyhow!(
+            "({}:{}) bond analytics is not supported for {} ({})",
+            file!(), line!(), instrument.get_name(), instrument.get_code()
+        ))
+    }
 }
 
 #[enum_dispatch(PricerTrait)]
 pub enum Pricer {
     FuturesPricer(FuturesPricer),
     OptionAnalyticPricer(OptionAnalyticPricer),
+    OptionMonteCarloPricer(OptionMonteCarloPricer),
     BondPricer(BondPricer),
     KtbfPricer(KtbfPricer),
     KrxYieldPricer(KrxYieldPricer),
@@ -17,6 +17,7 @@ use crate::pricing_engines::{
     pricer::Pricer,
     futures_pricer::FuturesPricer,
     option_analytic_pricer::OptionAnalyticPricer,
+    option_monte_carlo_pricer::OptionMonteCarloPricer,
     bond_pricer::BondPricer,
     ktbf_pricer::KtbfPricer,
     fx_futures_pricer::FxFuturesPricer,
@@ -223,9 +224,9 @@ impl PricerFactory {
             },
             true => None,
         };
-        let core = match self.calculation_configuration.get_vanilla_option_calculation_method() {
+        let pricer = match self.calculation_configuration.get_vanilla_option_calculation_method() {
             VanillaOptionCalculationMethod::Analytic => {
-                OptionAnalyticPricer::new(
+                Pricer::OptionAnalyticPricer(OptionAnalyticPricer::new(
                     self.evaluation_date.clone(),
                     equity,
                     collatral_curve,
@@ -233,11 +234,22 @@ impl PricerFactory {
                     discount_curve,
                     volatility,
                     quanto,
-                )
+                ))
+            },
+            VanillaOptionCalculationMethod::MonteCarlo => {
+                Pricer::OptionMonteCarloPricer(OptionMonteCarloPricer::new(
+                    self.evaluation_date.clone(),
+                    equity,
+                    collatral_curve,
+                    borrowing_curve,
+                    discount_curve,
+                    volatility,
+                    quanto,
+                ).with_monte_carlo_configuration(self.calculation_configuration.get_monte_carlo_configuration()))
             },
             _ => return Err(anyhow::Error::msg("Unsupported calculation method")),        
         };
-        Ok(Pricer::OptionAnalyticPricer(core))
+        Ok(pricer)
     }
 
     fn get_ktbf_pricer(&self, instrument: &Rc<Instrument>) -> Result<Pricer> {
@@ -0,0 +1,426 @@
+use crate::pricing_engines::{
+    calculation_configuration::CalculationConfiguration,
+    calculation_result::CalculationResult,
+};
+use crate::definitions::Real;
+//
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// The columns of the exported tables in order. The schema is the same for all runs
+/// whatever measures are calculated: a measure which is not calculated has no rows.
+pub const RESULT_COLUMNS: [&str; 9] = [
+    "evaluation_date",
+    "instrument_code",
+    "instrument_name",
+    "instrument_type",
+    "currency",
+    "risk_type",
+    "risk_factor",
+    "bucket",
+    "value",
+];
+
+/// A row of the long format table of CalculationResults.
+/// - evaluation_date: ISO-8601 (RFC3339), e.g., 2024-01-02T16:30:00+09:00
+/// - risk_type: npv, value, delta, rho_structure, vega_matrix, cashflow, bond_analytics, etc.
+/// - risk_factor: the underlying code, curve name, currency or fx code of the risk (empty for npv, value, theta, etc.)
+/// - bucket: the tenor of the structures, "tenor/moneyness" of vega_matrix, the payment datetime of cashflow,
+///   the field of bond_analytics and the second risk factor of cross_gamma, vanna and quanto_correlation
+/// - currency: the currency of the instrument in which the value is
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResultRow {
+    pub evaluation_date: String,
+    pub instrument_code: String,
+    pub instrument_name: String,
+    pub instrument_type: String,
+    pub currency: String,
+    pub risk_type: String,
+    pub risk_factor: String,
+    pub bucket: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultExportFormat {
+    Csv,
+    ArrowIpc,
+    Parquet,
+}
+
+impl ResultExportFormat {
+    /// .csv, .arrow (or .ipc, .feather) and .parquet
+    pub fn from_path(path: &str) -> Result<ResultExportFormat> {
+        let extension = std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "csv" => Ok(ResultExportFormat::Csv),
+            "arrow" | "ipc" | "feather" => Ok(ResultExportFormat::ArrowIpc),
+            "parquet" => Ok(ResultExportFormat::Parquet),
+            _ => bail!("({}:{}) unknown result file extension of {} (csv, arrow or parquet)", file!(), line!(), path),
+        }
+    }
+}
+
+pub fn format_datetime(datetime: &OffsetDateTime) -> Result<String> {
+    datetime.format(&Rfc3339)
+        .with_context(|| anyhow!("({}:{}) failed to format {}", file!(), line!(), datetime))
+}
+
+/// (risk_type, risk_factor, bucket, value)
+type Entry = (&'static str, String, String, Real);
+
+/// the order of the risk types in the exported tables
+const RISK_TYPES: [&str; 25] = [
+    "npv", "value", "theta", "implied_volatility", "fx_exposure",
+    "delta", "gamma", "vega", "rho", "div_delta", "volga", "fx_delta", "fx_gamma", "fx_vega",
+    "scenario_pnl", "curvature_up_pnl", "curvature_down_pnl",
+    "vega_structure", "rho_structure", "div_structure",
+    "cross_gamma", "vanna", "quanto_correlation", "vega_matrix", "cashflow",
+];
+
+fn risk_type_order(risk_type: &str) -> usize {
+    // bond_analytics is last
+    RISK_TYPES.iter().position(|r| *r == risk_type).unwrap_or(RISK_TYPES.len())
+}
+
+fn map_entries<K: ToString>(risk_type: &'static str, map: Option<&HashMap<K, Real>>) -> Vec<Entry> {
+    map.into_iter().flatten()
+        .map(|(key, value)| (risk_type, key.to_string(), String::new(), *value))
+        .collect()
+}
+
+fn nested_entries(risk_type: &'static str, map: Option<&HashMap<String, HashMap<String, Real>>>) -> Vec<Entry> {
+    map.into_iter().flatten()
+        .flat_map(|(key, inner)| {
+            let mut inner = inner.iter().collect::<Vec<_>>();
+            inner.sort_by(|a, b| a.0.cmp(b.0));
+            inner.into_iter().map(move |(second, value)| (risk_type, key.clone(), second.clone(), *value))
+        })
+        .collect()
+}
+
+fn structure_entries(risk_type: &'static str, map: Option<&HashMap<String, Vec<Real>>>, tenors: &[String]) -> Vec<Entry> {
+    map.into_iter().flatten()
+        .flat_map(|(key, values)| values.iter().enumerate().map(move |(i, value)| {
+            let tenor = tenors.get(i).cloned().unwrap_or_else(|| i.to_string());
+            (risk_type, key.clone(), tenor, *value)
+        }))
+        .collect()
+}
+
+fn result_entries(result: &CalculationResult, configuration: &CalculationConfiguration) -> Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let scalars = [
+        ("npv", result.get_npv_result().map(|npv| npv.get_npv())),
+        ("value", result.get_value()),
+        ("theta", result.get_theta()),
+        ("implied_volatility", result.get_implied_volatility()),
+    ];
+    for (risk_type, value) in scalars {
+        if let Some(value) = value {
+            entries.push((risk_type, String::new(), String::new(), value));
+        }
+    }
+    entries.extend(map_entries("fx_exposure", result.get_fx_exposure()));
+    for (risk_type, map) in [
+        ("delta", result.get_delta()),
+        ("gamma", result.get_gamma()),
+        ("vega", result.get_vega()),
+        ("rho", result.get_rho()),
+        ("div_delta", result.get_div_delta()),
+        ("volga", result.get_volga()),
+        ("fx_delta", result.get_fx_delta()),
+        ("fx_gamma", result.get_fx_gamma()),
+        ("fx_vega", result.get_fx_vega()),
+        ("scenario_pnl", result.get_scenario_pnl()),
+        ("curvature_up_pnl", result.get_curvature_up_pnl()),
+        ("curvature_down_pnl", result.get_curvature_down_pnl()),
+    ] {
+        entries.extend(map_entries(risk_type, map));
+    }
+    entries.extend(structure_entries("vega_structure", result.get_vega_structure(), configuration.get_vega_structure_tenors()));
+    entries.extend(structure_entries("rho_structure", result.get_rho_structure(), configuration.get_rho_structure_tenors()));
+    entries.extend(structure_entries("div_structure", result.get_div_structure(), configuration.get_div_structure_tenors()));
+    entries.extend(nested_entries("cross_gamma", result.get_cross_gamma()));
+    entries.extend(nested_entries("vanna", result.get_vanna()));
+    entries.extend(nested_entries("quanto_correlation", result.get_quanto_correlation()));
+
+    let tenors = configuration.get_vega_structure_tenors();
+    let moneyness = configuration.get_vega_matrix_spot_moneyness();
+    for (und_code, matrix) in result.get_vega_matrix().into_iter().flatten() {
+        for ((i, j), value) in matrix.indexed_iter() {
+            let tenor = tenors.get(i).cloned().unwrap_or_else(|| i.to_string());
+            let bucket = match moneyness.get(j) {
+                Some(m) => format!("{}/{}", tenor, m),
+                None => format!("{}/{}", tenor, j),
+            };
+            entries.push(("vega_matrix", und_code.clone(), bucket, *value));
+        }
+    }
+    let mut cashflows = result.get_cashflows().into_iter().flatten().collect::<Vec<_>>();
+    cashflows.sort_by(|a, b| a.0.cmp(b.0));
+    for (payment_date, amount) in cashflows {
+        entries.push(("cashflow", String::new(), format_datetime(payment_date)?, *amount));
+    }
+    for (bond_code, analytics) in result.get_bond_analytics().into_iter().flatten() {
+        for (field, value) in [
+            ("dirty_price", analytics.get_dirty_price()),
+            ("clean_price", analytics.get_clean_price()),
+            ("accrued_interest", analytics.get_accrued_interest()),
+            ("yield_to_maturity", analytics.get_yield_to_maturity()),
+            ("macaulay_duration", analytics.get_macaulay_duration()),
+            ("modified_duration", analytics.get_modified_duration()),
+            ("convexity", analytics.get_convexity()),
+            ("dv01", analytics.get_dv01()),
+            ("z_spread", analytics.get_z_spread()),
+            ("i_spread", analytics.get_i_spread()),
+        ] {
+            entries.push(("bond_analytics", bond_code.clone(), field.to_string(), value));
+        }
+    }
+    Ok(entries)
+}
+
+/// Rows of all the results ordered by instrument code, risk type (in the order above), risk factor and bucket.
+/// Rows of the same input are the same, so the outputs of runs can be compared line by line.
+pub fn flatten_results(
+    results: &HashMap<String, CalculationResult>,
+    configuration: &CalculationConfiguration,
+) -> Result<Vec<ResultRow>> {
+    let mut codes = results.keys().collect::<Vec<_>>();
+    codes.sort();
+    let mut rows = Vec::new();
+    for code in codes {
+        let result = &results[code];
+        let evaluation_date = match result.get_evaluation_date() {
+            Some(datetime) => format_datetime(datetime)?,
+            None => String::new(),
+        };
+        let (instrument_name, instrument_type, currency) = match result.get_instrument_info() {
+            Some(info) => (info.get_name().clone(), info.type_name().clone(), info.get_currency().as_str().to_string()),
+            None => (String::new(), String::new(), String::new()),
+        };
+        let mut entries = result_entries(result, configuration)
+            .with_context(|| anyhow!("({}:{}) failed to flatten the result of {}", file!(), line!(), code))?;
+        // buckets are already in order (e.g., tenors in the order of the configuration), so the sort is stable
+        entries.sort_by(|a, b| (risk_type_order(a.0), &a.1).cmp(&(risk_type_order(b.0), &b.1)));
+        for (risk_type, risk_factor, bucket, value) in entries {
+            rows.push(ResultRow {
+                evaluation_date: evaluation_date.clone(),
+                instrument_code: code.clone(),
+                instrument_name: instrument_name.clone(),
+                instrument_type: instrument_type.clone(),
+                currency: currency.clone(),
+                risk_type: risk_type.to_string(),
+                risk_factor,
+                bucket,
+                value: value as f64,
+            });
+        }
+    }
+    Ok(rows)
+}
+
+pub fn write_csv<W: Write>(rows: &[ResultRow], writer: W) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    // the header is written even if there is no row
+    writer.write_record(RESULT_COLUMNS)?;
+    for row in rows {
+        writer.write_record([
+            row.evaluation_date.as_str(),
+            row.instrument_code.as_str(),
+            row.instrument_name.as_str(),
+            row.instrument_type.as_str(),
+            row.currency.as_str(),
+            row.risk_type.as_str(),
+            row.risk_factor.as_str(),
+            row.bucket.as_str(),
+            row.value.to_string().as_str(),
+        ])?;
+    }
+    writer.flush()
+        .with_context(|| anyhow!("({}:{}) failed to write csv", file!(), line!()))
+}
+
+#[cfg(feature = "arrow")]
+pub mod columnar {
+    use super::{ResultRow, RESULT_COLUMNS};
+    use anyhow::{anyhow, Context, Result};
+    use arrow::array::{ArrayRef, Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use std::io::Write;
+    use std::sync::Arc;
+
+    /// all columns are non-null Utf8 except value (Float64)
+    pub fn result_schema() -> Schema {
+        let fields = RESULT_COLUMNS.iter().map(|name| {
+            let data_type = match *name {
+                "value" => DataType::Float64,
+                _ => DataType::Utf8,
+            };
+            Field::new(*name, data_type, false)
+        }).collect::<Vec<_>>();
+        Schema::new(fields)
+    }
+
+    pub fn to_record_batch(rows: &[ResultRow]) -> Result<RecordBatch> {
+        let strings = |f: fn(&ResultRow) -> &str| -> ArrayRef {
+            Arc::new(StringArray::from_iter_values(rows.iter().map(f)))
+        };
+        let columns: Vec<ArrayRef> = vec![
+            strings(|row| &row.evaluation_date),
+            strings(|row| &row.instrument_code),
+            strings(|row| &row.instrument_name),
+            strings(|row| &row.instrument_type),
+            strings(|row| &row.currency),
+            strings(|row| &row.risk_type),
+            strings(|row| &row.risk_factor),
+            strings(|row| &row.bucket),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|row| row.value))),
+        ];
+        RecordBatch::try_new(Arc::new(result_schema()), columns)
+            .with_context(|| anyhow!("({}:{}) failed to make a record batch of the results", file!(), line!()))
+    }
+
+    pub fn write_arrow_ipc<W: Write>(rows: &[ResultRow], writer: W) -> Result<()> {
+        let batch = to_record_batch(rows)?;
+        let mut writer = arrow::ipc::writer::FileWriter::try_new(writer, &batch.schema())
+            .with_context(|| anyhow!("({}:{}) failed to make an arrow ipc writer", file!(), line!()))?;
+        writer.write(&batch)?;
+        writer.finish()
+            .with_context(|| anyhow!("({}:{}) failed to write arrow ipc", file!(), line!()))
+    }
+
+    pub fn write_parquet<W: Write + Send>(rows: &[ResultRow], writer: W) -> Result<()> {
+        let batch = to_record_batch(rows)?;
+        let mut writer = parquet::arrow::ArrowWriter::try_new(writer, batch.schema(), None)
+            .with_context(|| anyhow!("({}:{}) failed to make a parquet writer", file!(), line!()))?;
+        writer.write(&batch)?;
+        writer.close()
+            .with_context(|| anyhow!("({}:{}) failed to write parquet", file!(), line!()))?;
+        Ok(())
+    }
+}
+
+/// Arrow IPC and Parquet need the arrow feature. ResultExportFormat::from_path gives the format from the extension.
+pub fn export_results(rows: &[ResultRow], path: &str, format: ResultExportFormat) -> Result<()> {
+    let file = std::fs::File::create(path)
+        .with_context(|| anyhow!("({}:{}) failed to create {}", file!(), line!(), path))?;
+    let writer = std::io::BufWriter::new(file);
+    let res = match format {
+        ResultExportFormat::Csv => write_csv(rows, writer),
+        #[cfg(feature = "arrow")]
+        ResultExportFormat::ArrowIpc => columnar::write_arrow_ipc(rows, writer),
+        #[cfg(feature = "arrow")]
+        ResultExportFormat::Parquet => columnar::write_parquet(rows, writer),
+        #[cfg(not(feature = "arrow"))]
+        ResultExportFormat::ArrowIpc | ResultExportFormat::Parquet => {
+            drop(writer);
+            let _ = std::fs::remove_file(path);
+            bail!("({}:{}) {:?} export of {} needs the arrow feature of quantlib", file!(), line!(), format, path)
+        },
+    };
+    res.with_context(|| anyhow!("({}:{}) failed to export the results to {}", file!(), line!(), path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::Currency;
+    use crate::instruments::instrument_info::InstrumentInfo;
+    use crate::pricing_engines::npv_result::NpvResult;
+    use time::macros::datetime;
+
+    fn results() -> HashMap<String, CalculationResult> {
+        let info = InstrumentInfo::new(
+            "KTB 3Y".to_string(),
+            "KR103502GE97".to_string(),
+            "Bond",
+            Currency::KRW,
+            10_000.0,
+            None,
+        );
+        let mut result = CalculationResult::new(info, datetime!(2024-01-02 16:30:00 +09:00));
+        result.set_npv(NpvResult::new_from_npv(101.5));
+        result.set_single_rho(&"KRWGOV".to_string(), -2.0);
+        result.set_single_rho_structure(&"KRWGOV".to_string(), vec![-0.5, -1.5]);
+        result.set_cashflows(HashMap::from([
+            (datetime!(2025-01-02 00:00:00 +09:00), 103.0),
+            (datetime!(2024-07-02 00:00:00 +09:00), 1.5),
+        ]));
+        HashMap::from([("KR103502GE97".to_string(), result)])
+    }
+
+    #[test]
+    fn test_flatten_results() -> Result<()> {
+        let configuration = CalculationConfiguration::default()
+            .with_rho_structure_tenors(vec!["6M".to_string(), "10Y".to_string()]);
+        let rows = flatten_results(&results(), &configuration)?;
+        let keys = rows.iter()
+            .map(|row| (row.risk_type.as_str(), row.risk_factor.as_str(), row.bucket.as_str(), row.value))
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec![
+            ("npv", "", "", 101.5),
+            ("rho", "KRWGOV", "", -2.0),
+            // tenors in the order of the configuration
+            ("rho_structure", "KRWGOV", "6M", -0.5),
+            ("rho_structure", "KRWGOV", "10Y", -1.5),
+            ("cashflow", "", "2024-07-02T00:00:00+09:00", 1.5),
+            ("cashflow", "", "2025-01-02T00:00:00+09:00", 103.0),
+        ]);
+        assert!(rows.iter().all(|row| row.evaluation_date == "2024-01-02T16:30:00+09:00"));
+        assert!(rows.iter().all(|row| row.currency == "KRW" && row.instrument_type == "Bond"));
+
+        let mut csv = Vec::new();
+        write_csv(&rows, &mut csv)?;
+        let csv = String::from_utf8(csv)?;
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(RESULT_COLUMNS.join(",").as_str()));
+        assert_eq!(lines.next(), Some("2024-01-02T16:30:00+09:00,KR103502GE97,KTB 3Y,Bond,KRW,npv,,,101.5"));
+
+        // the header only, even for no result
+        let mut empty = Vec::new();
+        write_csv(&[], &mut empty)?;
+        assert_eq!(String::from_utf8(empty)?.lines().count(), 1);
+        Ok(())
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn test_columnar_export() -> Result<()> {
+        use arrow::array::{Array, Float64Array, StringArray};
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let rows = flatten_results(&results(), &CalculationConfiguration::default())?;
+        let batch = columnar::to_record_batch(&rows)?;
+        assert_eq!(batch.num_rows(), rows.len());
+        assert_eq!(batch.schema().as_ref(), &columnar::result_schema());
+
+        let mut ipc = Vec::new();
+        columnar::write_arrow_ipc(&rows, &mut ipc)?;
+        let reader = arrow::ipc::reader::FileReader::try_new(std::io::Cursor::new(ipc), None)?;
+        assert_eq!(reader.schema().as_ref(), &columnar::result_schema());
+        let read = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(read, vec![batch.clone()]);
+
+        let path = std::env::temp_dir().join(format!("quantlib-results-{}.parquet", std::process::id()));
+        export_results(&rows, &path.to_string_lossy(), ResultExportFormat::from_path(&path.to_string_lossy())?)?;
+        let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path)?)?.build()?;
+        let read = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+        let risk_types = read[0].column(5).as_any().downcast_ref::<StringArray>().expect("risk_type");
+        let values = read[0].column(8).as_any().downcast_ref::<Float64Array>().expect("value");
+        assert_eq!(risk_types.len(), rows.len());
+        assert_eq!(risk_types.value(0), "npv");
+        assert_eq!(values.value(0), 101.5);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
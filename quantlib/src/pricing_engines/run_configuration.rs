@@ -0,0 +1,407 @@
+use crate::currency::FxCode;
+use crate::data::{
+    market_data_snapshot::MarketDataSnapshot,
+    market_data_store::MarketDataStore,
+    surface_data::SurfaceData,
+    value_data::ValueData,
+    vector_data::VectorData,
+};
+use crate::instrument::{InstrumentTrait, Instruments};
+use crate::instruments::trade_loader::load_trades;
+use crate::pricing_engines::{
+    calculation_configuration::CalculationConfiguration,
+    calculation_result::CalculationResult,
+    engine_generator::{EngineGenerator, InstrumentCategory},
+    error_report::ErrorReport,
+    match_parameter::MatchParameter,
+    result_export::{export_results, flatten_results, ResultExportFormat},
+};
+//
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::info;
+
+/// "2024-01-02T16:30:00+09:00" or the array form of OffsetDateTime as in the data files
+pub(crate) fn deserialize_datetime<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OffsetDateTime, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum DateTimeRepr {
+        Text(String),
+        Fields(OffsetDateTime),
+    }
+    match DateTimeRepr::deserialize(deserializer)? {
+        DateTimeRepr::Text(text) => OffsetDateTime::parse(&text, &Rfc3339)
+            .map_err(|e| serde::de::Error::custom(format!("invalid RFC3339 datetime {}: {}", text, e))),
+        DateTimeRepr::Fields(datetime) => Ok(datetime),
+    }
+}
+
+pub(crate) fn serialize_datetime<S: Serializer>(datetime: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error> {
+    let text = datetime.format(&Rfc3339).map_err(serde::ser::Error::custom)?;
+    serializer.serialize_str(&text)
+}
+
+/// Market data files in the formats of json_data (lists of ValueData, VectorData and SurfaceData).
+/// The data are keyed by their codes, i.e., fx codes (e.g., USDKRW), underlying codes and curve names.
+/// If snapshot_store (a MarketDataStore directory) is given, the snapshot as of the evaluation datetime
+/// is loaded first and the data in the files replace those of the same keys.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MarketDataFiles {
+    pub snapshot_store: Option<String>,
+    pub fx: Vec<String>,
+    pub stock: Vec<String>,
+    pub curve: Vec<String>,
+    pub dividend: Vec<String>,
+    pub equity_volatility: Vec<String>,
+    pub equity_volatility_surface: Vec<String>,
+    pub fx_volatility: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunOutput {
+    /// CalculationResults keyed by instrument code
+    pub json: Option<String>,
+    /// long format tables of result_export::flatten_results
+    pub csv: Option<String>,
+    /// Arrow IPC file (needs the arrow feature)
+    pub arrow: Option<String>,
+    /// needs the arrow feature
+    pub parquet: Option<String>,
+}
+
+/// A valuation run read from a JSON file: the evaluation datetime, CalculationConfiguration, MatchParameter,
+/// InstrumentCategory list, trade files (see instruments::trade_loader), market data files and outputs.
+/// Relative paths are relative to the directory of the run file.
+/// Missing instrument_categories means one category for all the instruments.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunConfiguration {
+    #[serde(serialize_with = "serialize_datetime", deserialize_with = "deserialize_datetime")]
+    pub evaluation_datetime: OffsetDateTime,
+    #[serde(default)]
+    pub calculation_configuration: CalculationConfiguration,
+    #[serde(default)]
+    pub match_parameter: MatchParameter,
+    #[serde(default)]
+    pub instrument_categories: Vec<InstrumentCategory>,
+    pub trade_files: Vec<String>,
+    #[serde(default)]
+    pub market_data: MarketDataFiles,
+    #[serde(default)]
+    pub output: RunOutput,
+    /// see EngineGenerator::with_continue_on_error
+    #[serde(default)]
+    pub continue_on_error: bool,
+    #[serde(skip)]
+    base_directory: PathBuf,
+}
+
+/// what a run did, for the summary of the command line
+#[derive(Debug, Clone, Default)]
+pub struct RunSummary {
+    pub trades: usize,
+    pub results: HashMap<String, CalculationResult>,
+    /// instruments without results
+    pub missing: Vec<String>,
+    pub outputs: Vec<String>,
+    /// failures with continue_on_error
+    pub error_report: ErrorReport,
+}
+
+impl RunSummary {
+    pub fn is_success(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+fn read_list<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| anyhow!("({}:{}) failed to read {}", file!(), line!(), path.display()))?;
+    serde_json::from_str(&json)
+        .with_context(|| anyhow!("({}:{}) failed to deserialize {}", file!(), line!(), path.display()))
+}
+
+fn fx_code(code: &str, path: &Path) -> Result<FxCode> {
+    if code.len() != 6 || !code.is_ascii() {
+        bail!("({}:{}) fx code must be six letters (e.g., USDKRW), got {} in {}", file!(), line!(), code, path.display());
+    }
+    Ok(FxCode::from(code))
+}
+
+impl RunConfiguration {
+    pub fn from_json_str(json: &str) -> Result<RunConfiguration> {
+        let configuration: RunConfiguration = serde_json::from_str(json)
+            .with_context(|| anyhow!("({}:{}) failed to parse the run configuration", file!(), line!()))?;
+        if configuration.trade_files.is_empty() {
+            bail!("({}:{}) trade_files of the run configuration must not be empty", file!(), line!());
+        }
+        Ok(configuration)
+    }
+
+    pub fn from_json_file(path: &str) -> Result<RunConfiguration> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| anyhow!("({}:{}) failed to read the run configuration {}", file!(), line!(), path))?;
+        let configuration = RunConfiguration::from_json_str(&json)
+            .with_context(|| anyhow!("({}:{}) invalid run configuration {}", file!(), line!(), path))?;
+        let base_directory = Path::new(path).parent().map(|p| p.to_path_buf()).unwrap_or_default();
+        Ok(configuration.with_base_directory(base_directory))
+    }
+
+    /// directory of the relative paths
+    pub fn with_base_directory(mut self, base_directory: PathBuf) -> RunConfiguration {
+        self.base_directory = base_directory;
+        self
+    }
+
+    pub fn resolve_path(&self, path: &str) -> PathBuf {
+        let path = Path::new(path);
+        match path.is_absolute() {
+            true => path.to_path_buf(),
+            false => self.base_directory.join(path),
+        }
+    }
+
+    /// trades of all the trade files. The codes must be unique over the files.
+    pub fn load_instruments(&self) -> Result<Instruments> {
+        let mut codes = HashSet::new();
+        let mut instruments = Vec::new();
+        for file in self.trade_files.iter() {
+            let path = self.resolve_path(file);
+            let loaded = load_trades(&path.to_string_lossy())?;
+            for instrument in loaded.iter() {
+                if !codes.insert(instrument.get_code().clone()) {
+                    bail!(
+                        "({}:{}) code {} in {} is duplicated in the trade files",
+                        file!(), line!(), instrument.get_code(), path.display()
+                    );
+                }
+                instruments.push(instrument.clone());
+            }
+        }
+        Ok(Instruments::new(instruments))
+    }
+
+    pub fn load_market_data(&self) -> Result<MarketDataSnapshot> {
+        let files = &self.market_data;
+        let base = match &files.snapshot_store {
+            Some(root) => MarketDataStore::new(self.resolve_path(root)).load_as_of(self.evaluation_datetime)?,
+            None => MarketDataSnapshot::new(self.evaluation_datetime),
+        };
+        let mut fx = base.get_fx_data().clone();
+        let mut fx_volatility = base.get_fx_constant_volatility_data().clone();
+        let mut stock = base.get_stock_data().clone();
+        let mut equity_volatility = base.get_equity_constant_volatility_data().clone();
+        let mut curve = base.get_curve_data().clone();
+        let mut dividend = base.get_dividend_data().clone();
+        let mut equity_volatility_surface = base.get_equity_volatility_surface_data().clone();
+        for file in files.fx.iter() {
+            let path = self.resolve_path(file);
+            for data in read_list::<ValueData>(&path)? {
+                fx.insert(fx_code(data.get_code(), &path)?, data);
+            }
+        }
+        for file in files.fx_volatility.iter() {
+            let path = self.resolve_path(file);
+            for data in read_list::<ValueData>(&path)? {
+                fx_volatility.insert(fx_code(data.get_code(), &path)?, data);
+            }
+        }
+        let value_files = [(&files.stock, &mut stock), (&files.equity_volatility, &mut equity_volatility)];
+        for (files, map) in value_files {
+            for file in files.iter() {
+                for data in read_list::<ValueData>(&self.resolve_path(file))? {
+                    map.insert(data.get_code().clone(), data);
+                }
+            }
+        }
+        let vector_files = [(&files.curve, &mut curve), (&files.dividend, &mut dividend)];
+        for (files, map) in vector_files {
+            for file in files.iter() {
+                for data in read_list::<VectorData>(&self.resolve_path(file))? {
+                    map.insert(data.get_code().clone(), data);
+                }
+            }
+        }
+        for file in files.equity_volatility_surface.iter() {
+            for data in read_list::<SurfaceData>(&self.resolve_path(file))? {
+                equity_volatility_surface.insert(data.get_code().to_string(), data);
+            }
+        }
+        Ok(MarketDataSnapshot::new(*base.get_as_of())
+            .with_fx_data(fx)
+            .with_stock_data(stock)
+            .with_curve_data(curve)
+            .with_dividend_data(dividend)
+            .with_equity_constant_volatility_data(equity_volatility)
+            .with_equity_volatility_surface_data(equity_volatility_surface)
+            .with_fx_constant_volatility_data(fx_volatility)
+            .with_quanto_correlation_data(base.get_quanto_correlation_data().clone())
+            .with_past_daily_value_data(base.get_past_daily_value_data().clone()))
+    }
+
+    /// EngineGenerator with the instruments distributed to the categories, ready to calculate
+    pub fn build_engine_generator(&self) -> Result<EngineGenerator> {
+        let instruments = self.load_instruments()?;
+        let data = self.load_market_data()?;
+        let instrument_categories = match self.instrument_categories.is_empty() {
+            true => vec![InstrumentCategory::default()],
+            false => self.instrument_categories.clone(),
+        };
+        let mut engine_generator = EngineGenerator::builder();
+        engine_generator
+            .with_configuration(
+                self.calculation_configuration.clone(),
+                self.evaluation_datetime,
+                self.match_parameter.clone(),
+            )?
+            .with_instruments(instruments)?
+            .with_instrument_categories(instrument_categories)?
+            .with_market_data_snapshot(data)?
+            .with_continue_on_error(self.continue_on_error)?;
+        engine_generator.distribute_instruments()
+            .with_context(|| anyhow!("({}:{}) failed to distribute the instruments", file!(), line!()))?;
+        Ok(engine_generator)
+    }
+
+    /// calculate and write the outputs
+    pub fn run(&self) -> Result<RunSummary> {
+        let mut engine_generator = self.build_engine_generator()?;
+        let trades = engine_generator.get_instruments().len();
+        info!("run: {} trades are loaded", trades);
+        engine_generator.calculate()
+            .with_context(|| anyhow!("({}:{}) failed to calculate", file!(), line!()))?;
+
+        let results = engine_generator.get_calculation_results().clone();
+        let mut missing: Vec<String> = engine_generator.get_instruments().iter()
+            .map(|inst| inst.get_code().clone())
+            .filter(|code| !results.contains_key(code))
+            .collect();
+        missing.sort();
+
+        let mut outputs = Vec::new();
+        if let Some(path) = &self.output.json {
+            let path = self.resolve_path(path);
+            let json = serde_json::to_string_pretty(&results)
+                .with_context(|| anyhow!("({}:{}) failed to serialize the results", file!(), line!()))?;
+            std::fs::write(&path, json)
+                .with_context(|| anyhow!("({}:{}) failed to write {}", file!(), line!(), path.display()))?;
+            outputs.push(path.display().to_string());
+        }
+        let tables = [
+            (&self.output.csv, ResultExportFormat::Csv),
+            (&self.output.arrow, ResultExportFormat::ArrowIpc),
+            (&self.output.parquet, ResultExportFormat::Parquet),
+        ];
+        if tables.iter().any(|(path, _)| path.is_some()) {
+            let rows = flatten_results(&results, &self.calculation_configuration)?;
+            for (path, format) in tables {
+                if let Some(path) = path {
+                    let path = self.resolve_path(path).display().to_string();
+                    export_results(&rows, &path, format)?;
+                    outputs.push(path);
+                }
+            }
+        }
+        let error_report = engine_generator.get_error_report().clone();
+        Ok(RunSummary { trades, results, missing, outputs, error_report })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::Currency;
+    use crate::instrument::Instrument;
+    use crate::instruments::{futures::Futures, trade_loader::{write_trades_to_string, TradeFileFormat}};
+    use ndarray::Array1;
+    use std::rc::Rc;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_run_configuration() -> Result<()> {
+        let directory = std::env::temp_dir().join(format!("quantlib-run-{}", std::process::id()));
+        std::fs::create_dir_all(&directory)?;
+        let market_datetime = datetime!(2024-01-02 16:30:00 +09:00);
+
+        let futures = Futures::new(
+            320.0,
+            datetime!(2023-09-15 09:00:00 +09:00),
+            datetime!(2024-03-14 15:20:00 +09:00),
+            datetime!(2024-03-14 15:20:00 +09:00),
+            datetime!(2024-03-14 15:20:00 +09:00),
+            250_000.0,
+            Currency::KRW,
+            Currency::KRW,
+            "KOSPI2".to_string(),
+            "KOSPI2 Fut Mar24".to_string(),
+            "165XXX".to_string(),
+        );
+        let instruments = Instruments::new(vec![Rc::new(Instrument::Futures(futures))]);
+        std::fs::write(directory.join("trades.json"), write_trades_to_string(&instruments, TradeFileFormat::Json)?)?;
+
+        let stock = vec![ValueData::new(350.0, Some(market_datetime), Currency::KRW, "KOSPI2".to_string(), "KOSPI2".to_string())?];
+        std::fs::write(directory.join("stock.json"), serde_json::to_string(&stock)?)?;
+        let curve = ["KSD", "KOSPI2"].iter().map(|code| VectorData::new(
+            Array1::from(vec![0.0345, 0.0345]),
+            Some(vec![datetime!(2025-01-02 16:30:00 +09:00), datetime!(2026-01-02 16:30:00 +09:00)]),
+            None,
+            Some(market_datetime),
+            Currency::KRW,
+            code.to_string(),
+            code.to_string(),
+        )).collect::<Result<Vec<_>>>()?;
+        // curves from the snapshot store
+        let snapshot = MarketDataSnapshot::new(datetime!(2024-01-02 15:40:00 +09:00))
+            .with_curve_data(curve.into_iter().map(|data| (data.get_code().clone(), data)).collect());
+        MarketDataStore::new(directory.join("store")).save(&snapshot)?;
+
+        let run_json = r#"{
+            "evaluation_datetime": "2024-01-02T16:30:00+09:00",
+            "calculation_configuration": { "npv": true, "delta": true },
+            "match_parameter": {
+                "collateral_curve_map": { "KOSPI2": "KSD" },
+                "borrowing_curve_map": { "KOSPI2": "KOSPI2" }
+            },
+            "trade_files": ["trades.json"],
+            "market_data": { "snapshot_store": "store", "stock": ["stock.json"] },
+            "output": { "json": "results.json", "csv": "results.csv" }
+        }"#;
+        let run_path = directory.join("run.json");
+        std::fs::write(&run_path, run_json)?;
+
+        let configuration = RunConfiguration::from_json_file(&run_path.to_string_lossy())?;
+        assert_eq!(configuration.evaluation_datetime, market_datetime);
+        let summary = configuration.run()?;
+        assert_eq!(summary.trades, 1);
+        assert!(summary.is_success(), "missing results: {:?}", summary.missing);
+
+        let npv = summary.results["165XXX"].get_npv_result().expect("npv").get_npv();
+        assert!(npv > 320.0 && npv < 360.0, "npv: {}", npv);
+
+        let written: HashMap<String, CalculationResult> = serde_json::from_str(
+            &std::fs::read_to_string(directory.join("results.json"))?
+        )?;
+        let written_npv = written["165XXX"].get_npv_result().expect("npv in results.json").get_npv();
+        assert!((written_npv - npv).abs() < 1e-4);
+
+        let csv = std::fs::read_to_string(directory.join("results.csv"))?;
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("evaluation_date,instrument_code,instrument_name,instrument_type,currency,risk_type,risk_factor,bucket,value"));
+        assert!(lines.next().expect("npv row").starts_with("2024-01-02T16:30:00+09:00,165XXX,KOSPI2 Fut Mar24,Futures,KRW,npv,,,"));
+        assert!(csv.lines().any(|line| line.contains(",KRW,delta,")));
+
+        // a trade code must be unique over the trade files
+        let mut duplicated = configuration.clone();
+        duplicated.trade_files.push("trades.json".to_string());
+        assert!(duplicated.load_instruments().is_err());
+
+        std::fs::remove_dir_all(&directory)?;
+        Ok(())
+    }
+}
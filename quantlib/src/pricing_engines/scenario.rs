@@ -0,0 +1,292 @@
+use crate::definitions::Real;
+use crate::enums::ShockType;
+//
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+static TENOR_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"^(\d+(Y|M|W|D))+$").unwrap());
+
+/// shock on an equity price (underlying code)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EquityShock {
+    pub code: String,
+    #[serde(default)]
+    pub shock_type: ShockType,
+    pub value: Real,
+}
+
+/// shock on an fx rate, e.g., fx_code = "USDKRW".
+/// The reciprocal pair in the engine (KRWUSD) is moved consistently.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FxShock {
+    pub fx_code: String,
+    #[serde(default)]
+    pub shock_type: ShockType,
+    pub value: Real,
+}
+
+/// parallel shift (absolute, e.g., 0.01 = 100bp) of the zero rates in the bucket start_tenor < tenor <= end_tenor.
+/// None means unbounded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CurveShock {
+    pub curve_name: String,
+    #[serde(default)]
+    pub start_tenor: Option<String>,
+    #[serde(default)]
+    pub end_tenor: Option<String>,
+    pub value: Real,
+}
+
+/// shift (absolute, e.g., 0.05 = 5 vol points) of the volatility in the region
+/// start_tenor < tenor <= end_tenor and left_spot_moneyness < K/S <= right_spot_moneyness.
+/// None means unbounded. The shift follows Volatility::bump_volatility.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VolatilityShock {
+    pub code: String,
+    #[serde(default)]
+    pub start_tenor: Option<String>,
+    #[serde(default)]
+    pub end_tenor: Option<String>,
+    #[serde(default)]
+    pub left_spot_moneyness: Option<Real>,
+    #[serde(default)]
+    pub right_spot_moneyness: Option<Real>,
+    pub value: Real,
+}
+
+/// shock on the dividend amounts of an underlying.
+/// Absolute adds the value to every dividend amount, relative multiplies them by (1 + value).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DividendShock {
+    pub code: String,
+    #[serde(default)]
+    pub shock_type: ShockType,
+    pub value: Real,
+}
+
+/// Scenario is a user-defined set of market shocks applied together by Engine::apply_scenario.
+/// The shocks on data which the engine does not hold (e.g., a curve not used by its instruments) are ignored,
+/// so that the same scenario set can be given to all engines.
+///
+/// Example (JSON):
+/// ```json
+/// {
+///     "name": "equity crash",
+///     "equity_shocks": [{ "code": "KOSPI2", "shock_type": "Relative", "value": -0.3 }],
+///     "volatility_shocks": [{ "code": "KOSPI2", "value": 0.1 }],
+///     "curve_shocks": [{ "curve_name": "KRWGOV", "end_tenor": "1Y", "value": 0.005 }]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub equity_shocks: Vec<EquityShock>,
+    #[serde(default)]
+    pub fx_shocks: Vec<FxShock>,
+    #[serde(default)]
+    pub curve_shocks: Vec<CurveShock>,
+    #[serde(default)]
+    pub volatility_shocks: Vec<VolatilityShock>,
+    #[serde(default)]
+    pub dividend_shocks: Vec<DividendShock>,
+}
+
+fn check_tenor(tenor: &Option<String>, scenario_name: &str) -> Result<()> {
+    if let Some(tenor) = tenor {
+        if !TENOR_REGEX.is_match(tenor) {
+            bail!(
+                "({}:{}) invalid tenor {} in scenario {} (e.g., 3M, 1Y6M)",
+                file!(), line!(), tenor, scenario_name
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_relative_shock(shock_type: ShockType, value: Real, code: &str, scenario_name: &str) -> Result<()> {
+    if shock_type == ShockType::Relative && value <= -1.0 {
+        bail!(
+            "({}:{}) relative shock on {} must be > -1.0, got {} in scenario {}",
+            file!(), line!(), code, value, scenario_name
+        );
+    }
+    Ok(())
+}
+
+impl Scenario {
+    pub fn new(name: String) -> Scenario {
+        Scenario {
+            name,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_description(mut self, description: String) -> Scenario {
+        self.description = description;
+        self
+    }
+
+    pub fn with_equity_shock(mut self, code: String, shock_type: ShockType, value: Real) -> Scenario {
+        self.equity_shocks.push(EquityShock { code, shock_type, value });
+        self
+    }
+
+    pub fn with_fx_shock(mut self, fx_code: String, shock_type: ShockType, value: Real) -> Scenario {
+        self.fx_shocks.push(FxShock { fx_code, shock_type, value });
+        self
+    }
+
+    pub fn with_curve_shock(
+        mut self,
+        curve_name: String,
+        start_tenor: Option<String>,
+        end_tenor: Option<String>,
+        value: Real,
+    ) -> Scenario {
+        self.curve_shocks.push(CurveShock { curve_name, start_tenor, end_tenor, value });
+        self
+    }
+
+    pub fn with_volatility_shock(mut self, volatility_shock: VolatilityShock) -> Scenario {
+        self.volatility_shocks.push(volatility_shock);
+        self
+    }
+
+    pub fn with_dividend_shock(mut self, code: String, shock_type: ShockType, value: Real) -> Scenario {
+        self.dividend_shocks.push(DividendShock { code, shock_type, value });
+        self
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            bail!("({}:{}) scenario name must not be empty", file!(), line!());
+        }
+        for shock in self.equity_shocks.iter() {
+            check_relative_shock(shock.shock_type, shock.value, &shock.code, &self.name)?;
+        }
+        for shock in self.fx_shocks.iter() {
+            if shock.fx_code.len() != 6 {
+                bail!(
+                    "({}:{}) fx code must be six letters (e.g., USDKRW), got {} in scenario {}",
+                    file!(), line!(), shock.fx_code, self.name
+                );
+            }
+            check_relative_shock(shock.shock_type, shock.value, &shock.fx_code, &self.name)?;
+        }
+        for shock in self.curve_shocks.iter() {
+            check_tenor(&shock.start_tenor, &self.name)?;
+            check_tenor(&shock.end_tenor, &self.name)?;
+        }
+        for shock in self.volatility_shocks.iter() {
+            check_tenor(&shock.start_tenor, &self.name)?;
+            check_tenor(&shock.end_tenor, &self.name)?;
+        }
+        for shock in self.dividend_shocks.iter() {
+            check_relative_shock(shock.shock_type, shock.value, &shock.code, &self.name)?;
+        }
+        Ok(())
+    }
+
+    /// load a JSON array of scenarios (or a single scenario) and validate them
+    pub fn from_json_str(json: &str) -> Result<Vec<Scenario>> {
+        let value: serde_json::Value = serde_json::from_str(json)
+            .with_context(|| anyhow!("({}:{}) failed to parse scenario json", file!(), line!()))?;
+        let scenarios: Vec<Scenario> = match value {
+            serde_json::Value::Array(_) => serde_json::from_value(value),
+            _ => serde_json::from_value(value).map(|scenario| vec![scenario]),
+        }.with_context(|| anyhow!("({}:{}) failed to deserialize scenarios", file!(), line!()))?;
+
+        let mut names = std::collections::HashSet::new();
+        for scenario in scenarios.iter() {
+            scenario.validate()?;
+            if !names.insert(scenario.name.clone()) {
+                bail!("({}:{}) duplicated scenario name: {}", file!(), line!(), scenario.name);
+            }
+        }
+        Ok(scenarios)
+    }
+
+    pub fn from_json_file(path: &str) -> Result<Vec<Scenario>> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| anyhow!("({}:{}) failed to read scenario file {}", file!(), line!(), path))?;
+        Scenario::from_json_str(&json)
+    }
+}
+
+/// A pair of up and down scenarios on a risk factor (e.g., a parallel shift of all the curves of a currency)
+/// revalued by Engine::set_curvature_pnls, as in the curvature risk of FRTB.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CurvatureScenario {
+    pub risk_factor: String,
+    pub up: Scenario,
+    pub down: Scenario,
+}
+
+impl CurvatureScenario {
+    pub fn new(risk_factor: String, up: Scenario, down: Scenario) -> CurvatureScenario {
+        CurvatureScenario { risk_factor, up, down }
+    }
+
+    pub fn get_risk_factor(&self) -> &String {
+        &self.risk_factor
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.risk_factor.is_empty() {
+            bail!("({}:{}) risk factor of a curvature scenario must not be empty", file!(), line!());
+        }
+        self.up.validate()?;
+        self.down.validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scenario_from_json() -> Result<()> {
+        let json = r#"[
+            {
+                "name": "equity crash",
+                "equity_shocks": [{ "code": "KOSPI2", "shock_type": "Relative", "value": -0.3 }],
+                "volatility_shocks": [{ "code": "KOSPI2", "end_tenor": "1Y", "value": 0.1 }]
+            },
+            {
+                "name": "rate up",
+                "curve_shocks": [{ "curve_name": "KRWGOV", "start_tenor": "1Y", "value": 0.01 }],
+                "fx_shocks": [{ "fx_code": "USDKRW", "shock_type": "Absolute", "value": 100.0 }]
+            }
+        ]"#;
+        let scenarios = Scenario::from_json_str(json)?;
+        assert_eq!(scenarios.len(), 2);
+        let expected = Scenario::new("equity crash".to_string())
+            .with_equity_shock("KOSPI2".to_string(), ShockType::Relative, -0.3)
+            .with_volatility_shock(VolatilityShock {
+                code: "KOSPI2".to_string(),
+                start_tenor: None,
+                end_tenor: Some("1Y".to_string()),
+                left_spot_moneyness: None,
+                right_spot_moneyness: None,
+                value: 0.1,
+            });
+        assert_eq!(scenarios[0], expected);
+        assert_eq!(scenarios[1].curve_shocks[0].start_tenor, Some("1Y".to_string()));
+
+        let single = Scenario::from_json_str(r#"{ "name": "div cut", "dividend_shocks": [{ "code": "KOSPI2", "value": -0.5 }] }"#)?;
+        assert_eq!(single[0].dividend_shocks[0].shock_type, ShockType::Relative);
+
+        assert!(Scenario::from_json_str(r#"{ "name": "bad", "curve_shocks": [{ "curve_name": "KRWGOV", "end_tenor": "1X", "value": 0.01 }] }"#).is_err());
+        assert!(Scenario::from_json_str(r#"{ "name": "bad", "equity_shocks": [{ "code": "KOSPI2", "value": -1.0 }] }"#).is_err());
+        assert!(Scenario::from_json_str(r#"[{ "name": "a" }, { "name": "a" }]"#).is_err());
+        Ok(())
+    }
+}
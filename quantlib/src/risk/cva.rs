@@ -0,0 +1,196 @@
+use crate::currency::Currency;
+use crate::definitions::{Real, Time};
+use crate::risk::exposure::ExposureReport;
+//
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Credit curve with piecewise constant hazard rates:
+/// hazard_rates[i] on (times[i-1], times[i]] (times[-1] = 0) and hazard_rates[last] after times[last]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreditCurve {
+    name: String,
+    times: Vec<Time>,
+    hazard_rates: Vec<Real>,
+    recovery_rate: Real,
+}
+
+impl CreditCurve {
+    pub fn new(
+        name: String,
+        times: Vec<Time>,
+        hazard_rates: Vec<Real>,
+        recovery_rate: Real,
+    ) -> Result<CreditCurve> {
+        if times.is_empty() || times.len() != hazard_rates.len() {
+            bail!(
+                "({}:{}) credit curve {} needs the same number of times and hazard rates, got {:?} and {:?}",
+                file!(), line!(), name, times, hazard_rates
+            );
+        }
+        if times[0] <= 0.0 || times.windows(2).any(|w| w[0] >= w[1]) {
+            bail!("({}:{}) times of credit curve {} must be positive and increasing: {:?}", file!(), line!(), name, times);
+        }
+        if hazard_rates.iter().any(|h| *h < 0.0) {
+            bail!("({}:{}) hazard rates of credit curve {} must not be negative: {:?}", file!(), line!(), name, hazard_rates);
+        }
+        if !(0.0..1.0).contains(&recovery_rate) {
+            bail!("({}:{}) recovery rate of credit curve {} must be in [0, 1), got {}", file!(), line!(), name, recovery_rate);
+        }
+        Ok(CreditCurve {
+            name,
+            times,
+            hazard_rates,
+            recovery_rate,
+        })
+    }
+
+    /// flat hazard rate from a credit spread by the credit triangle: spread / (1 - recovery_rate)
+    pub fn from_spread(name: String, spread: Real, recovery_rate: Real) -> Result<CreditCurve> {
+        let hazard_rate = spread / (1.0 - recovery_rate);
+        CreditCurve::new(name, vec![1.0], vec![hazard_rate], recovery_rate)
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_recovery_rate(&self) -> Real {
+        self.recovery_rate
+    }
+
+    /// exp(- integral of the hazard rate from 0 to t)
+    pub fn survival_probability(&self, t: Time) -> Real {
+        if t <= 0.0 {
+            return 1.0;
+        }
+        let mut integral = 0.0;
+        let mut previous = 0.0;
+        for (time, hazard_rate) in self.times.iter().zip(self.hazard_rates.iter()) {
+            if t <= *time {
+                return (-(integral + hazard_rate * (t - previous))).exp();
+            }
+            integral += hazard_rate * (time - previous);
+            previous = *time;
+        }
+        let last = self.hazard_rates[self.hazard_rates.len() - 1];
+        (-(integral + last * (t - previous))).exp()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CvaReport {
+    currency: Currency,
+    cva: BTreeMap<String, Real>,
+    dva: BTreeMap<String, Real>,
+}
+
+impl CvaReport {
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    /// netting set name -> CVA (a positive number is a cost)
+    pub fn get_cva(&self) -> &BTreeMap<String, Real> {
+        &self.cva
+    }
+
+    /// netting set name -> DVA (a positive number is a benefit). Empty without the own credit curve.
+    pub fn get_dva(&self) -> &BTreeMap<String, Real> {
+        &self.dva
+    }
+
+    pub fn get_total_cva(&self) -> Real {
+        self.cva.values().sum()
+    }
+
+    pub fn get_total_dva(&self) -> Real {
+        self.dva.values().sum()
+    }
+}
+
+/// Unilateral CVA and DVA on the exposure profiles:
+/// CVA = (1 - R) * sum_k DF(t_k) * EE(t_k) * (S(t_{k-1}) - S(t_k)) with the counterparty curve,
+/// DVA = (1 - R_own) * sum_k DF(t_k) * |ENE(t_k)| * (S_own(t_{k-1}) - S_own(t_k)) with the own curve.
+/// The exposures and the defaults are independent (no wrong way risk).
+pub struct CvaCalculator {
+    counterparty_curves: HashMap<String, CreditCurve>,
+    own_curve: Option<CreditCurve>,
+}
+
+impl CvaCalculator {
+    /// counterparty name -> credit curve
+    pub fn new(counterparty_curves: HashMap<String, CreditCurve>) -> CvaCalculator {
+        CvaCalculator {
+            counterparty_curves,
+            own_curve: None,
+        }
+    }
+
+    pub fn with_own_curve(mut self, own_curve: CreditCurve) -> CvaCalculator {
+        self.own_curve = Some(own_curve);
+        self
+    }
+
+    fn adjustment(curve: &CreditCurve, times: &[Time], discount_factors: &[Real], exposures: &[Real]) -> Real {
+        let mut previous_survival = 1.0;
+        let mut res = 0.0;
+        for ((t, df), exposure) in times.iter().zip(discount_factors.iter()).zip(exposures.iter()) {
+            let survival = curve.survival_probability(*t);
+            res += df * exposure.abs() * (previous_survival - survival);
+            previous_survival = survival;
+        }
+        (1.0 - curve.recovery_rate) * res
+    }
+
+    pub fn calculate(&self, exposure_report: &ExposureReport) -> Result<CvaReport> {
+        let times = exposure_report.get_times();
+        let discount_factors = exposure_report.get_discount_factors();
+        let mut cva = BTreeMap::new();
+        let mut dva = BTreeMap::new();
+        for (netting_set, profile) in exposure_report.get_profiles().iter() {
+            let curve = self.counterparty_curves.get(&profile.counterparty)
+                .ok_or_else(|| anyhow!(
+                    "({}:{}) no credit curve for {} of netting set {}",
+                    file!(), line!(), profile.counterparty, netting_set
+                ))?;
+            cva.insert(
+                netting_set.clone(),
+                CvaCalculator::adjustment(curve, times, discount_factors, &profile.expected_exposure),
+            );
+            if let Some(own_curve) = self.own_curve.as_ref() {
+                dva.insert(
+                    netting_set.clone(),
+                    CvaCalculator::adjustment(own_curve, times, discount_factors, &profile.expected_negative_exposure),
+                );
+            }
+        }
+        Ok(CvaReport {
+            currency: exposure_report.get_currency(),
+            cva,
+            dva,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credit_curve_survival_probability() -> Result<()> {
+        let curve = CreditCurve::new("A".to_string(), vec![1.0, 3.0], vec![0.01, 0.02], 0.4)?;
+        assert!((curve.survival_probability(0.5) - (-0.005_f32).exp()).abs() < 1.0e-6);
+        assert!((curve.survival_probability(2.0) - (-0.03_f32).exp()).abs() < 1.0e-6);
+        // flat after the last time
+        assert!((curve.survival_probability(5.0) - (-0.09_f32).exp()).abs() < 1.0e-6);
+
+        let flat = CreditCurve::from_spread("B".to_string(), 0.006, 0.4)?;
+        assert!((flat.survival_probability(2.0) - (-0.02_f32).exp()).abs() < 1.0e-6);
+
+        assert!(CreditCurve::new("C".to_string(), vec![2.0, 1.0], vec![0.01, 0.01], 0.4).is_err());
+        assert!(CreditCurve::from_spread("D".to_string(), 0.01, 1.0).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,804 @@
+use crate::currency::{Currency, FxCode};
+use crate::definitions::{Real, Time};
+use crate::enums::ShockType;
+use crate::evaluation_date::EvaluationDate;
+use crate::instrument::InstrumentTrait;
+use crate::math::cholescky_factorization::cholesky_decomposition;
+use crate::parameters::zero_curve::ZeroCurve;
+use crate::pricing_engines::{
+    engine_generator::EngineGenerator,
+    scenario::Scenario,
+};
+use crate::time::{
+    calendar_trait::CalendarTrait,
+    calendars::nullcalendar::NullCalendar,
+    datetimegrid::DateTimeGrid,
+};
+use crate::utils::string_arithmetic::add_period;
+//
+use anyhow::{anyhow, bail, Result};
+use ndarray::{Array1, Array2};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::StandardNormal;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+use time::OffsetDateTime;
+
+/// Hull-White one factor model dr = (theta(t) - a r) dt + sigma dW fitted to the curve of the currency
+/// in ExposureConfiguration::with_currency_curves. The simulated zero rate shift is applied
+/// to all the curves in curve_names (e.g., the government and the swap curves of the currency).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HullWhiteParameters {
+    pub currency: Currency,
+    pub curve_names: Vec<String>,
+    pub mean_reversion: Real,
+    pub volatility: Real,
+}
+
+impl HullWhiteParameters {
+    pub fn new(
+        currency: Currency,
+        curve_names: Vec<String>,
+        mean_reversion: Real,
+        volatility: Real,
+    ) -> HullWhiteParameters {
+        HullWhiteParameters {
+            currency,
+            curve_names,
+            mean_reversion,
+            volatility,
+        }
+    }
+
+    /// B(tau) = (1 - exp(-a tau)) / a
+    fn b(&self, tau: f64) -> f64 {
+        let a = self.mean_reversion as f64;
+        if a.abs() < 1.0e-8 {
+            return tau;
+        }
+        (1.0 - (-a * tau).exp()) / a
+    }
+
+    /// V(tau) = sigma^2 / a^2 * (tau + 2/a exp(-a tau) - 1/(2a) exp(-2a tau) - 3/(2a)),
+    /// the variance of the integral of x over a period tau
+    fn v(&self, tau: f64) -> f64 {
+        let a = self.mean_reversion as f64;
+        let sigma = self.volatility as f64;
+        if a.abs() < 1.0e-8 {
+            return sigma * sigma * tau * tau * tau / 3.0;
+        }
+        sigma * sigma / (a * a)
+            * (tau + 2.0 / a * (-a * tau).exp() - 0.5 / a * (-2.0 * a * tau).exp() - 1.5 / a)
+    }
+
+    /// standard deviation of x(t + dt) given x(t)
+    fn step_stddev(&self, dt: f64) -> f64 {
+        let a = self.mean_reversion as f64;
+        let sigma = self.volatility as f64;
+        if a.abs() < 1.0e-8 {
+            return sigma * dt.sqrt();
+        }
+        sigma * ((1.0 - (-2.0 * a * dt).exp()) / (2.0 * a)).sqrt()
+    }
+
+    /// z(t, tau) - z(0, tau) where z(t, tau) = -ln P(t, t + tau) / tau is the simulated zero rate
+    /// for the state x of the short rate deviation, and
+    /// ln P(t, T) = ln P(0, T) - ln P(0, t) + (V(T - t) - V(T) + V(t)) / 2 - B(T - t) x.
+    /// It is the shift of the rolled curve held by the engine on the simulation date.
+    /// ln_discount is ln P(0, t) of the initial curve.
+    pub fn zero_rate_shift(&self, ln_discount: &dyn Fn(f64) -> f64, t: f64, tau: f64, x: f64) -> f64 {
+        let ln_p = ln_discount(t + tau) - ln_discount(t)
+            + 0.5 * (self.v(tau) - self.v(t + tau) + self.v(t))
+            - self.b(tau) * x;
+        (-ln_p + ln_discount(tau)) / tau
+    }
+}
+
+/// Credit support annex of a netting set in the reporting currency.
+/// The counterparty posts the value above threshold, and we post the value below -own_threshold,
+/// only if the amount exceeds minimum_transfer_amount. The margin period of risk is not modeled.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollateralAgreement {
+    pub threshold: Real,
+    pub own_threshold: Real,
+    pub minimum_transfer_amount: Real,
+}
+
+impl CollateralAgreement {
+    pub fn new(threshold: Real, own_threshold: Real, minimum_transfer_amount: Real) -> CollateralAgreement {
+        CollateralAgreement {
+            threshold,
+            own_threshold,
+            minimum_transfer_amount,
+        }
+    }
+
+    /// collateral held (positive) or posted (negative) for the netting set value
+    pub fn collateral(&self, value: Real) -> Real {
+        let received = value - self.threshold;
+        let posted = value + self.own_threshold;
+        if received > 0.0 && received > self.minimum_transfer_amount {
+            received
+        } else if posted < 0.0 && -posted > self.minimum_transfer_amount {
+            posted
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NettingSet {
+    pub name: String,
+    pub counterparty: String,
+    pub instrument_codes: Vec<String>,
+    #[serde(default)]
+    pub collateral_agreement: Option<CollateralAgreement>,
+}
+
+impl NettingSet {
+    pub fn new(name: String, counterparty: String, instrument_codes: Vec<String>) -> NettingSet {
+        NettingSet {
+            name,
+            counterparty,
+            instrument_codes,
+            collateral_agreement: None,
+        }
+    }
+
+    pub fn with_collateral_agreement(mut self, collateral_agreement: CollateralAgreement) -> NettingSet {
+        self.collateral_agreement = Some(collateral_agreement);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExposureConfiguration {
+    currency: Currency,
+    tenors: Vec<String>,
+    number_of_paths: usize,
+    seed: u64,
+    pfe_quantiles: Vec<Real>,
+    hull_white_parameters: Vec<HullWhiteParameters>,
+    curve_shock_tenors: Vec<String>,
+    currency_curves: HashMap<Currency, String>,
+    fx_volatilities: HashMap<String, Real>,
+    equity_volatilities: HashMap<String, Real>,
+    correlations: Vec<(String, String, Real)>,
+    number_of_chunks: usize,
+}
+
+impl Default for ExposureConfiguration {
+    fn default() -> ExposureConfiguration {
+        ExposureConfiguration {
+            currency: Currency::KRW,
+            tenors: vec!["1M", "3M", "6M", "1Y"].into_iter().map(String::from).collect(),
+            number_of_paths: 1_000,
+            seed: 0,
+            pfe_quantiles: vec![0.95, 0.99],
+            hull_white_parameters: vec![],
+            curve_shock_tenors: vec!["3M", "6M", "1Y", "2Y", "3Y", "5Y", "7Y", "10Y", "20Y"]
+                .into_iter().map(String::from).collect(),
+            currency_curves: HashMap::new(),
+            fx_volatilities: HashMap::new(),
+            equity_volatilities: HashMap::new(),
+            correlations: vec![],
+            number_of_chunks: 1,
+        }
+    }
+}
+
+impl ExposureConfiguration {
+    /// reporting currency of the netting set values. Its curve must be in currency_curves for discounting.
+    pub fn with_currency(mut self, currency: Currency) -> ExposureConfiguration {
+        self.currency = currency;
+        self
+    }
+
+    /// simulation dates from the evaluation date, e.g., ["1M", "3M", "6M", "1Y"]
+    pub fn with_tenors(mut self, tenors: Vec<String>) -> ExposureConfiguration {
+        self.tenors = tenors;
+        self
+    }
+
+    pub fn with_number_of_paths(mut self, number_of_paths: usize) -> ExposureConfiguration {
+        self.number_of_paths = number_of_paths;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> ExposureConfiguration {
+        self.seed = seed;
+        self
+    }
+
+    /// quantiles of the exposure distribution for PFE, e.g., [0.95, 0.99]
+    pub fn with_pfe_quantiles(mut self, pfe_quantiles: Vec<Real>) -> ExposureConfiguration {
+        self.pfe_quantiles = pfe_quantiles;
+        self
+    }
+
+    /// curves not driven by a Hull-White model only roll down to the simulation dates
+    pub fn with_hull_white_parameters(mut self, hull_white_parameters: Vec<HullWhiteParameters>) -> ExposureConfiguration {
+        self.hull_white_parameters = hull_white_parameters;
+        self
+    }
+
+    /// The simulated curves are shifted in the buckets (previous tenor, tenor] by the shift at the tenor.
+    /// The rates beyond the last tenor are shifted by the shift at the last tenor.
+    pub fn with_curve_shock_tenors(mut self, curve_shock_tenors: Vec<String>) -> ExposureConfiguration {
+        self.curve_shock_tenors = curve_shock_tenors;
+        self
+    }
+
+    /// currency -> curve name used for the drifts of the equities and fx rates,
+    /// the Hull-White initial curve, and the discount factors in the reporting currency
+    pub fn with_currency_curves(mut self, currency_curves: HashMap<Currency, String>) -> ExposureConfiguration {
+        self.currency_curves = currency_curves;
+        self
+    }
+
+    /// fx code (e.g., "USDKRW") -> volatility. The fx rates without a volatility here or in
+    /// the fx constant volatility data of the EngineGenerator are not simulated.
+    pub fn with_fx_volatilities(mut self, fx_volatilities: HashMap<String, Real>) -> ExposureConfiguration {
+        self.fx_volatilities = fx_volatilities;
+        self
+    }
+
+    /// equity code -> volatility. The equities without a volatility here or in
+    /// the equity constant volatility data of the EngineGenerator are not simulated.
+    pub fn with_equity_volatilities(mut self, equity_volatilities: HashMap<String, Real>) -> ExposureConfiguration {
+        self.equity_volatilities = equity_volatilities;
+        self
+    }
+
+    /// correlation between two risk factors named by the currency (Hull-White factor, e.g., "KRW"),
+    /// the fx code (e.g., "USDKRW") or the equity code. The others are uncorrelated.
+    pub fn with_correlation(mut self, factor1: String, factor2: String, correlation: Real) -> ExposureConfiguration {
+        self.correlations.push((factor1, factor2, correlation));
+        self
+    }
+
+    /// paths are split into number_of_chunks for the parallel revaluation
+    pub fn with_number_of_chunks(mut self, number_of_chunks: usize) -> ExposureConfiguration {
+        self.number_of_chunks = number_of_chunks;
+        self
+    }
+
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn get_tenors(&self) -> &Vec<String> {
+        &self.tenors
+    }
+
+    pub fn get_number_of_paths(&self) -> usize {
+        self.number_of_paths
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_pfe_quantiles(&self) -> &Vec<Real> {
+        &self.pfe_quantiles
+    }
+
+    pub fn get_hull_white_parameters(&self) -> &Vec<HullWhiteParameters> {
+        &self.hull_white_parameters
+    }
+
+    pub fn get_curve_shock_tenors(&self) -> &Vec<String> {
+        &self.curve_shock_tenors
+    }
+
+    pub fn get_currency_curves(&self) -> &HashMap<Currency, String> {
+        &self.currency_curves
+    }
+
+    pub fn get_fx_volatilities(&self) -> &HashMap<String, Real> {
+        &self.fx_volatilities
+    }
+
+    pub fn get_equity_volatilities(&self) -> &HashMap<String, Real> {
+        &self.equity_volatilities
+    }
+
+    pub fn get_correlations(&self) -> &Vec<(String, String, Real)> {
+        &self.correlations
+    }
+
+    pub fn get_number_of_chunks(&self) -> usize {
+        self.number_of_chunks
+    }
+}
+
+/// Exposure profile of a netting set on the simulation dates in the reporting currency after collateral.
+/// pfe[i] is the profile at pfe_quantiles[i] of the configuration, and
+/// expected_positive_exposure is the time weighted average of expected_exposure.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExposureProfile {
+    pub counterparty: String,
+    pub expected_value: Vec<Real>,
+    pub expected_exposure: Vec<Real>,
+    pub expected_negative_exposure: Vec<Real>,
+    pub pfe: Vec<Vec<Real>>,
+    pub expected_positive_exposure: Real,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExposureReport {
+    evaluation_date: OffsetDateTime,
+    currency: Currency,
+    number_of_paths: usize,
+    dates: Vec<OffsetDateTime>,
+    times: Vec<Time>,
+    discount_factors: Vec<Real>,
+    pfe_quantiles: Vec<Real>,
+    profiles: BTreeMap<String, ExposureProfile>,
+}
+
+impl ExposureReport {
+    pub fn get_evaluation_date(&self) -> &OffsetDateTime {
+        &self.evaluation_date
+    }
+
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn get_number_of_paths(&self) -> usize {
+        self.number_of_paths
+    }
+
+    pub fn get_dates(&self) -> &Vec<OffsetDateTime> {
+        &self.dates
+    }
+
+    pub fn get_times(&self) -> &Vec<Time> {
+        &self.times
+    }
+
+    /// discount factors of the reporting currency curve on the simulation dates
+    pub fn get_discount_factors(&self) -> &Vec<Real> {
+        &self.discount_factors
+    }
+
+    pub fn get_pfe_quantiles(&self) -> &Vec<Real> {
+        &self.pfe_quantiles
+    }
+
+    /// netting set name -> profile
+    pub fn get_profiles(&self) -> &BTreeMap<String, ExposureProfile> {
+        &self.profiles
+    }
+
+    pub fn get_profile(&self, netting_set: &str) -> Option<&ExposureProfile> {
+        self.profiles.get(netting_set)
+    }
+
+    pub fn get_pfe(&self, netting_set: &str, quantile: Real) -> Option<&Vec<Real>> {
+        let index = self.pfe_quantiles.iter().position(|q| (q - quantile).abs() < 1.0e-6)?;
+        self.profiles.get(netting_set).map(|profile| &profile.pfe[index])
+    }
+}
+
+enum RiskFactorModel {
+    HullWhite(HullWhiteParameters),
+    Fx { fx_code: String, volatility: Real, drift_currencies: (Currency, Currency) },
+    Equity { code: String, volatility: Real, currency: Currency },
+}
+
+impl RiskFactorModel {
+    fn name(&self) -> String {
+        match self {
+            RiskFactorModel::HullWhite(parameters) => parameters.currency.as_str().to_string(),
+            RiskFactorModel::Fx { fx_code, .. } => fx_code.clone(),
+            RiskFactorModel::Equity { code, .. } => code.clone(),
+        }
+    }
+}
+
+/// scenarios[k][p] on the k-th date of the grid in path p, and fx code -> factors[k][p] of the fx rates
+struct SimulatedMarket {
+    scenarios: Vec<Vec<Scenario>>,
+    fx_factors: HashMap<String, Vec<Vec<Real>>>,
+}
+
+/// fx rate into the reporting currency moved by the simulated factors of the pair (or its reciprocal).
+/// The rate is constant if the pair is not simulated.
+struct Conversion<'a> {
+    rate: Real,
+    factors: Option<&'a Vec<Vec<Real>>>,
+    reciprocal: bool,
+}
+
+impl Conversion<'_> {
+    fn rate_on_path(&self, k: usize, p: usize) -> Real {
+        match (self.factors, self.reciprocal) {
+            (Some(factors), false) => self.rate * factors[k][p],
+            (Some(factors), true) => self.rate / factors[k][p],
+            (None, _) => self.rate,
+        }
+    }
+}
+
+/// Monte Carlo simulation of the market on a DateTimeGrid and the revaluation of the netting sets.
+/// The curves follow Hull-White models, and the equities and fx rates follow geometric Brownian motions
+/// with the drifts of the currency curves (r_d - r_f for fx rates). Each simulated state is given to
+/// the engines as a Scenario on the (rolled) market of the simulation date.
+pub struct ExposureSimulation {
+    configuration: ExposureConfiguration,
+    netting_sets: Vec<NettingSet>,
+}
+
+impl ExposureSimulation {
+    pub fn new(configuration: ExposureConfiguration, netting_sets: Vec<NettingSet>) -> ExposureSimulation {
+        ExposureSimulation {
+            configuration,
+            netting_sets,
+        }
+    }
+
+    pub fn get_configuration(&self) -> &ExposureConfiguration {
+        &self.configuration
+    }
+
+    pub fn get_netting_sets(&self) -> &Vec<NettingSet> {
+        &self.netting_sets
+    }
+
+    /// currency -> initial curve of currency_curves
+    fn initial_curves(&self, engine_generator: &EngineGenerator) -> Result<HashMap<Currency, ZeroCurve>> {
+        let evaluation_date = Rc::new(RefCell::new(
+            EvaluationDate::new(engine_generator.get_evaluation_date().get_date_clone())
+        ));
+        let mut res = HashMap::new();
+        for (currency, curve_name) in self.configuration.currency_curves.iter() {
+            let data = engine_generator.get_curve_data().get(curve_name)
+                .ok_or_else(|| anyhow!("({}:{}) no curve data for {} of {}", file!(), line!(), curve_name, currency))?;
+            let curve = ZeroCurve::new(evaluation_date.clone(), data, curve_name.clone(), curve_name.clone())?;
+            res.insert(*currency, curve);
+        }
+        Ok(res)
+    }
+
+    fn risk_factor_models(&self, engine_generator: &EngineGenerator) -> Result<Vec<RiskFactorModel>> {
+        let mut res = Vec::new();
+        for parameters in self.configuration.hull_white_parameters.iter() {
+            if !self.configuration.currency_curves.contains_key(&parameters.currency) {
+                bail!(
+                    "({}:{}) no curve is given for the Hull-White model of {}",
+                    file!(), line!(), parameters.currency
+                );
+            }
+            res.push(RiskFactorModel::HullWhite(parameters.clone()));
+        }
+
+        let fx_volatility_data = engine_generator.get_fx_constant_volatility_data();
+        let mut fx_codes: Vec<&FxCode> = engine_generator.get_fx_data().keys().collect();
+        fx_codes.sort_by_key(|fx_code| fx_code.to_string());
+        for fx_code in fx_codes {
+            let name = fx_code.to_string();
+            let volatility = match self.configuration.fx_volatilities.get(&name) {
+                Some(volatility) => *volatility,
+                None => match fx_volatility_data.get(fx_code) {
+                    Some(data) => data.get_value(),
+                    None => continue,
+                },
+            };
+            res.push(RiskFactorModel::Fx {
+                fx_code: name,
+                volatility,
+                drift_currencies: (*fx_code.get_currency1(), *fx_code.get_currency2()),
+            });
+        }
+
+        let equity_volatility_data = engine_generator.get_equity_constant_volatility_data();
+        let mut stock_codes: Vec<&String> = engine_generator.get_stock_data().keys().collect();
+        stock_codes.sort();
+        for code in stock_codes {
+            let volatility = match self.configuration.equity_volatilities.get(code) {
+                Some(volatility) => *volatility,
+                None => match equity_volatility_data.get(code) {
+                    Some(data) => data.get_value(),
+                    None => continue,
+                },
+            };
+            res.push(RiskFactorModel::Equity {
+                code: code.clone(),
+                volatility,
+                currency: *engine_generator.get_stock_data()[code].get_currency(),
+            });
+        }
+        Ok(res)
+    }
+
+    fn correlation_cholesky(&self, models: &[RiskFactorModel]) -> Result<Array2<Real>> {
+        let names: Vec<String> = models.iter().map(|model| model.name()).collect();
+        let mut correlation = Array2::<Real>::eye(names.len());
+        for (factor1, factor2, rho) in self.configuration.correlations.iter() {
+            let i = names.iter().position(|name| name == factor1);
+            let j = names.iter().position(|name| name == factor2);
+            if let (Some(i), Some(j)) = (i, j) {
+                if i != j {
+                    correlation[[i, j]] = *rho;
+                    correlation[[j, i]] = *rho;
+                }
+            }
+        }
+        cholesky_decomposition(&correlation)
+            .map_err(|e| anyhow!("({}:{}) correlation matrix of {:?}: {}", file!(), line!(), names, e))
+    }
+
+    fn simulate(&self, engine_generator: &EngineGenerator, grid: &DateTimeGrid) -> Result<SimulatedMarket> {
+        let models = self.risk_factor_models(engine_generator)?;
+        let lower = self.correlation_cholesky(&models)?;
+        let curves = self.initial_curves(engine_generator)?;
+        let ln_discount = |currency: &Currency, t: f64| -> Result<f64> {
+            match curves.get(currency) {
+                Some(curve) => Ok((curve.get_discount_factor(t as Time)? as f64).ln()),
+                None => Ok(0.0),
+            }
+        };
+
+        let times: Vec<f64> = grid.get_times().iter().map(|t| *t as f64).collect();
+        let steps: Vec<f64> = grid.get_time_steps().iter().map(|dt| *dt as f64).collect();
+        // (tau of the bucket end from each simulation date, start tenor, end tenor)
+        let time_calculator = NullCalendar::default();
+        let shock_tenors = &self.configuration.curve_shock_tenors;
+        let mut buckets: Vec<(Vec<f64>, Option<String>, Option<String>)> = Vec::new();
+        for (i, tenor) in shock_tenors.iter().enumerate() {
+            let taus = grid.get_datetimes().iter()
+                .map(|dt| time_calculator.get_time_difference(dt, &add_period(dt, tenor)) as f64)
+                .collect::<Vec<f64>>();
+            let start = if i == 0 { None } else { Some(shock_tenors[i - 1].clone()) };
+            buckets.push((taus.clone(), start, Some(tenor.clone())));
+            if i == shock_tenors.len() - 1 {
+                buckets.push((taus, Some(tenor.clone()), None));
+            }
+        }
+
+        // the zero rate shift is linear in x: shift[k][bucket] at x = 0 and its slope B(tau) / tau
+        let mut hw_shifts: Vec<Vec<Vec<(f64, f64)>>> = Vec::new();
+        for model in models.iter() {
+            let mut shifts = Vec::new();
+            if let RiskFactorModel::HullWhite(parameters) = model {
+                let curve = &curves[&parameters.currency];
+                let ln_p0 = |t: f64| curve.get_discount_factor(t as Time)
+                    .map(|df| (df as f64).ln())
+                    .unwrap_or(0.0);
+                for (k, t) in times.iter().enumerate() {
+                    shifts.push(buckets.iter()
+                        .map(|(taus, _, _)| (
+                            parameters.zero_rate_shift(&ln_p0, *t, taus[k], 0.0),
+                            parameters.b(taus[k]) / taus[k],
+                        ))
+                        .collect());
+                }
+            }
+            hw_shifts.push(shifts);
+        }
+        // deterministic drift parts of ln(factor) for fx rates and equities: r t
+        let mut drifts: Vec<Vec<f64>> = Vec::new();
+        for model in models.iter() {
+            let drift = match model {
+                RiskFactorModel::HullWhite(_) => vec![0.0; times.len()],
+                RiskFactorModel::Fx { drift_currencies: (foreign, domestic), .. } => times.iter()
+                    .map(|t| Ok(ln_discount(foreign, *t)? - ln_discount(domestic, *t)?))
+                    .collect::<Result<Vec<f64>>>()?,
+                RiskFactorModel::Equity { currency, .. } => times.iter()
+                    .map(|t| Ok(-ln_discount(currency, *t)?))
+                    .collect::<Result<Vec<f64>>>()?,
+            };
+            drifts.push(drift);
+        }
+
+        let number_of_paths = self.configuration.number_of_paths;
+        let lower = lower.mapv(|x| x as f64);
+        let mut rng = StdRng::seed_from_u64(self.configuration.seed);
+        let mut scenarios: Vec<Vec<Scenario>> = vec![Vec::with_capacity(number_of_paths); times.len()];
+        let mut fx_factors: HashMap<String, Vec<Vec<Real>>> = HashMap::new();
+        for model in models.iter() {
+            if let RiskFactorModel::Fx { fx_code, .. } = model {
+                fx_factors.insert(fx_code.clone(), vec![vec![1.0; number_of_paths]; times.len()]);
+            }
+        }
+
+        for p in 0..number_of_paths {
+            // x for Hull-White factors and the Brownian motion W for the others
+            let mut states = vec![0.0_f64; models.len()];
+            for k in 0..times.len() {
+                let z = Array1::from_iter((0..models.len()).map(|_| rng.sample::<f64, _>(StandardNormal)));
+                let e = lower.dot(&z);
+                let t = times[k];
+                let dt = steps[k];
+                let mut scenario = Scenario::new(format!("path{}_{}", p, k));
+                for (i, model) in models.iter().enumerate() {
+                    match model {
+                        RiskFactorModel::HullWhite(parameters) => {
+                            let a = parameters.mean_reversion as f64;
+                            states[i] = states[i] * (-a * dt).exp() + parameters.step_stddev(dt) * e[i];
+                            for ((_, start, end), (shift0, slope)) in buckets.iter().zip(hw_shifts[i][k].iter()) {
+                                let shift = (shift0 + slope * states[i]) as Real;
+                                for curve_name in parameters.curve_names.iter() {
+                                    scenario = scenario.with_curve_shock(
+                                        curve_name.clone(), start.clone(), end.clone(), shift
+                                    );
+                                }
+                            }
+                        },
+                        RiskFactorModel::Fx { fx_code, volatility, .. } => {
+                            let sigma = *volatility as f64;
+                            states[i] += dt.sqrt() * e[i];
+                            let factor = (drifts[i][k] - 0.5 * sigma * sigma * t + sigma * states[i]).exp() as Real;
+                            if let Some(factors) = fx_factors.get_mut(fx_code) {
+                                factors[k][p] = factor;
+                            }
+                            scenario = scenario.with_fx_shock(fx_code.clone(), ShockType::Relative, factor - 1.0);
+                        },
+                        RiskFactorModel::Equity { code, volatility, .. } => {
+                            let sigma = *volatility as f64;
+                            states[i] += dt.sqrt() * e[i];
+                            let factor = (drifts[i][k] - 0.5 * sigma * sigma * t + sigma * states[i]).exp() as Real;
+                            scenario = scenario.with_equity_shock(code.clone(), ShockType::Relative, factor - 1.0);
+                        },
+                    }
+                }
+                scenarios[k].push(scenario);
+            }
+        }
+        Ok(SimulatedMarket { scenarios, fx_factors })
+    }
+
+    pub fn calculate(&self, engine_generator: &EngineGenerator) -> Result<ExposureReport> {
+        let configuration = &self.configuration;
+        let currency = configuration.currency;
+        if configuration.number_of_paths == 0 {
+            bail!("({}:{}) number of paths must be positive", file!(), line!());
+        }
+        if configuration.pfe_quantiles.iter().any(|q| *q <= 0.0 || *q >= 1.0) {
+            bail!("({}:{}) pfe quantiles must be in (0, 1), got {:?}", file!(), line!(), configuration.pfe_quantiles);
+        }
+        if !configuration.currency_curves.contains_key(&currency) {
+            bail!("({}:{}) no curve is given for the reporting currency {}", file!(), line!(), currency);
+        }
+
+        let evaluation_date = engine_generator.get_evaluation_date().get_date_clone();
+        let grid = DateTimeGrid::from_tenors(evaluation_date, &configuration.tenors)?;
+        let SimulatedMarket { scenarios, fx_factors } = self.simulate(engine_generator, &grid)?;
+        let mut instrument_codes: Vec<String> = self.netting_sets.iter()
+            .flat_map(|netting_set| netting_set.instrument_codes.iter().cloned())
+            .collect();
+        instrument_codes.sort();
+        instrument_codes.dedup();
+        let values = engine_generator.calculate_on_grid(
+            &grid, &scenarios, &instrument_codes, configuration.number_of_chunks
+        )?;
+
+        // instrument code -> conversion into the reporting currency
+        let mut conversions: HashMap<String, Conversion> = HashMap::new();
+        for inst in engine_generator.get_instruments().iter() {
+            if !self.netting_sets.iter().any(|netting_set| netting_set.instrument_codes.contains(inst.get_code())) {
+                continue;
+            }
+            let inst_currency = *inst.get_currency();
+            let rate = engine_generator.get_fx_rate(inst_currency, currency)?;
+            let direct = FxCode::new(inst_currency, currency).to_string();
+            let reciprocal = FxCode::new(currency, inst_currency).to_string();
+            let (factors, reciprocal) = match (fx_factors.get(&direct), fx_factors.get(&reciprocal)) {
+                (Some(factors), _) => (Some(factors), false),
+                (None, Some(factors)) => (Some(factors), true),
+                _ => (None, false),
+            };
+            conversions.insert(inst.get_code().clone(), Conversion { rate, factors, reciprocal });
+        }
+
+        let curves = self.initial_curves(engine_generator)?;
+        let discount_factors = grid.get_times().iter()
+            .map(|t| curves[&currency].get_discount_factor(*t))
+            .collect::<Result<Vec<Real>>>()?;
+        let maturity = *grid.get_times().last().unwrap_or(&1.0);
+
+        let number_of_paths = configuration.number_of_paths;
+        let mut profiles = BTreeMap::new();
+        for netting_set in self.netting_sets.iter() {
+            if let Some(code) = netting_set.instrument_codes.iter().find(|code| !conversions.contains_key(*code)) {
+                bail!("({}:{}) {} in netting set {} is not given to the EngineGenerator", file!(), line!(), code, netting_set.name);
+            }
+            let mut profile = ExposureProfile {
+                counterparty: netting_set.counterparty.clone(),
+                ..Default::default()
+            };
+            let mut pfe = vec![Vec::with_capacity(grid.len()); configuration.pfe_quantiles.len()];
+            for (k, date_values) in values.iter().enumerate() {
+                let mut set_values = Vec::with_capacity(number_of_paths);
+                let mut exposures = Vec::with_capacity(number_of_paths);
+                let mut negative_sum = 0.0;
+                for (p, path_values) in date_values.iter().enumerate() {
+                    let mut value = 0.0;
+                    for code in netting_set.instrument_codes.iter() {
+                        let inst_value = match path_values.get(code) {
+                            Some(v) => *v,
+                            None => continue, // matured
+                        };
+                        value += inst_value * conversions[code].rate_on_path(k, p);
+                    }
+                    let collateral = netting_set.collateral_agreement.as_ref()
+                        .map(|csa| csa.collateral(value))
+                        .unwrap_or(0.0);
+                    set_values.push(value);
+                    exposures.push((value - collateral).max(0.0));
+                    negative_sum += (value - collateral).min(0.0);
+                }
+                let n = number_of_paths as Real;
+                profile.expected_value.push(set_values.iter().sum::<Real>() / n);
+                profile.expected_exposure.push(exposures.iter().sum::<Real>() / n);
+                profile.expected_negative_exposure.push(negative_sum / n);
+                exposures.sort_by(|a, b| a.total_cmp(b));
+                for (i, q) in configuration.pfe_quantiles.iter().enumerate() {
+                    pfe[i].push(quantile(&exposures, *q));
+                }
+            }
+            profile.pfe = pfe;
+            profile.expected_positive_exposure = profile.expected_exposure.iter()
+                .zip(grid.get_time_steps().iter())
+                .map(|(ee, dt)| ee * dt)
+                .sum::<Real>() / maturity;
+            profiles.insert(netting_set.name.clone(), profile);
+        }
+
+        Ok(ExposureReport {
+            evaluation_date,
+            currency,
+            number_of_paths,
+            dates: grid.get_datetimes().clone(),
+            times: grid.get_times().clone(),
+            discount_factors,
+            pfe_quantiles: configuration.pfe_quantiles.clone(),
+            profiles,
+        })
+    }
+}
+
+/// empirical quantile of sorted values
+fn quantile(sorted: &[Real], q: Real) -> Real {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let index = ((q * sorted.len() as Real).ceil() as usize).clamp(1, sorted.len()) - 1;
+    sorted[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hull_white_zero_rate_shift() {
+        let flat = |t: f64| -0.03 * t;
+        let no_vol = HullWhiteParameters::new(Currency::KRW, vec![], 0.05, 0.0);
+        // rolled flat curve is not moved without volatility
+        assert!(no_vol.zero_rate_shift(&flat, 1.0, 5.0, 0.0).abs() < 1.0e-12);
+
+        let hw = HullWhiteParameters::new(Currency::KRW, vec![], 0.05, 0.01);
+        let up = hw.zero_rate_shift(&flat, 1.0, 5.0, 0.01);
+        let down = hw.zero_rate_shift(&flat, 1.0, 5.0, -0.01);
+        assert!(up > 0.0 && down < 0.0);
+        // the shift of the short end follows x
+        assert!((hw.zero_rate_shift(&flat, 1.0, 1.0e-4, 0.01) - 0.01).abs() < 1.0e-4);
+        // mean reversion damps the long end
+        assert!(hw.zero_rate_shift(&flat, 1.0, 10.0, 0.01) < hw.zero_rate_shift(&flat, 1.0, 1.0, 0.01));
+    }
+
+    #[test]
+    fn test_collateral_agreement() {
+        let csa = CollateralAgreement::new(100.0, 50.0, 10.0);
+        assert_eq!(csa.collateral(105.0), 0.0);
+        assert_eq!(csa.collateral(150.0), 50.0);
+        assert_eq!(csa.collateral(-55.0), 0.0);
+        assert_eq!(csa.collateral(-80.0), -30.0);
+    }
+}
@@ -0,0 +1,1385 @@
+use crate::currency::{Currency, FxCode};
+use crate::data::{value_data::ValueData, vector_data::VectorData};
+use crate::definitions::{Real, DELTA_PNL_UNIT, RHO_PNL_UNIT, VEGA_PNL_UNIT};
+use crate::enums::{
+    CreditRating,
+    FrtbCorrelationScenario,
+    FrtbDrcBucket,
+    FrtbRiskClass,
+    FrtbRiskMeasure,
+    IssuerType,
+    RankType,
+    ShockType,
+};
+use crate::instrument::{Instrument, InstrumentTrait};
+use crate::pricing_engines::{
+    calculation_result::CalculationResult,
+    engine_generator::EngineGenerator,
+    scenario::{CurvatureScenario, Scenario},
+};
+use crate::risk::util::{allocate, correlated_sum, tenor_days, tenor_years};
+use crate::time::{calendar_trait::CalendarTrait, calendars::nullcalendar::NullCalendar};
+//
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// basis of the credit spread sensitivities of bonds (Label2 of CSR delta)
+pub const BOND_BASIS: &str = "Bond";
+
+const BUNDLED_PARAMETERS: &str = include_str!("frtb_parameters.json");
+const CORRELATION_SCENARIOS: [FrtbCorrelationScenario; 3] = [
+    FrtbCorrelationScenario::Low,
+    FrtbCorrelationScenario::Medium,
+    FrtbCorrelationScenario::High,
+];
+const RATING_CATEGORIES: [&str; 9] = ["AAA", "AA", "A", "BBB", "BB", "B", "CCC", "Unrated", "Defaulted"];
+
+/// Each currency is a bucket. The risk weights on the tenors are scaled by specified_currency_scaling
+/// for the specified currencies and the reporting currency. The correlation between the tenors of a curve is
+/// max(exp(-tenor_correlation_decay |T1 - T2| / min(T1, T2)), tenor_correlation_floor),
+/// multiplied by curve_correlation between different curves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrtbGirrParameters {
+    pub tenors: Vec<String>,
+    pub risk_weights: Vec<Real>,
+    pub specified_currencies: Vec<String>,
+    pub specified_currency_scaling: Real,
+    pub tenor_correlation_decay: Real,
+    pub tenor_correlation_floor: Real,
+    pub curve_correlation: Real,
+    pub inter_currency_correlation: Real,
+    pub vega_risk_weight: Real,
+    pub option_maturities: Vec<String>,
+    pub option_maturity_decay: Real,
+}
+
+/// credit spread risk of non-securitisations. The sensitivities in other_bucket are added up without diversification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrtbCsrParameters {
+    pub tenors: Vec<String>,
+    pub buckets: Vec<String>,
+    pub other_bucket: String,
+    pub risk_weights: HashMap<String, Real>,
+    pub name_correlation: Real,
+    pub tenor_correlation: Real,
+    pub basis_correlation: Real,
+    pub bucket_correlations: Vec<Vec<Real>>,
+    pub vega_risk_weight: Real,
+    pub option_maturities: Vec<String>,
+    pub option_maturity_decay: Real,
+}
+
+/// equity spot risk. The sensitivities in other_bucket are added up without diversification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrtbEquityParameters {
+    pub buckets: Vec<String>,
+    pub other_bucket: String,
+    pub risk_weights: HashMap<String, Real>,
+    pub name_correlations: HashMap<String, Real>,
+    pub bucket_correlations: Vec<Vec<Real>>,
+    pub vega_risk_weights: HashMap<String, Real>,
+    pub option_maturities: Vec<String>,
+    pub option_maturity_decay: Real,
+}
+
+/// Each currency against the reporting currency is a bucket. The risk weight is scaled by specified_pair_scaling
+/// if both currencies are in specified_currencies.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrtbFxParameters {
+    pub risk_weight: Real,
+    pub specified_currencies: Vec<String>,
+    pub specified_pair_scaling: Real,
+    pub correlation: Real,
+    pub vega_risk_weight: Real,
+    pub option_maturities: Vec<String>,
+    pub option_maturity_decay: Real,
+}
+
+/// default risk charge: risk weights by rating category (AAA, AA, A, BBB, BB, B, CCC, Unrated, Defaulted),
+/// LGD by seniority, and the maturity floor (in years) of the scaling of the jump-to-default
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrtbDrcParameters {
+    pub risk_weights: HashMap<String, Real>,
+    pub senior_lgd: Real,
+    pub non_senior_lgd: Real,
+    pub maturity_floor: Real,
+}
+
+/// Parameters of the FRTB standardised approach.
+/// FrtbParameters::default() is the bundled file (frtb_parameters.json) and others are loaded by from_json_str.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrtbParameters {
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    pub girr: FrtbGirrParameters,
+    pub csr: FrtbCsrParameters,
+    pub equity: FrtbEquityParameters,
+    pub fx: FrtbFxParameters,
+    pub drc: FrtbDrcParameters,
+}
+
+impl Default for FrtbParameters {
+    fn default() -> FrtbParameters {
+        FrtbParameters::from_json_str(BUNDLED_PARAMETERS).expect("bundled FRTB parameters are invalid")
+    }
+}
+
+fn check_buckets(
+    name: &str,
+    buckets: &[String],
+    bucket_correlations: &[Vec<Real>],
+    maps: &[(&str, &HashMap<String, Real>)],
+) -> Result<()> {
+    if bucket_correlations.len() != buckets.len() || bucket_correlations.iter().any(|row| row.len() != buckets.len()) {
+        bail!("({}:{}) {} bucket correlations must be a {} x {} matrix", file!(), line!(), name, buckets.len(), buckets.len());
+    }
+    for bucket in buckets.iter() {
+        if let Some((map_name, _)) = maps.iter().find(|(_, map)| !map.contains_key(bucket)) {
+            bail!("({}:{}) {} {} has no value for bucket {}", file!(), line!(), name, map_name, bucket);
+        }
+    }
+    Ok(())
+}
+
+impl FrtbParameters {
+    pub fn from_json_str(json: &str) -> Result<FrtbParameters> {
+        let parameters: FrtbParameters = serde_json::from_str(json)
+            .with_context(|| anyhow!("({}:{}) failed to parse FRTB parameters", file!(), line!()))?;
+        parameters.validate()?;
+        Ok(parameters)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.girr.risk_weights.len() != self.girr.tenors.len() {
+            bail!("({}:{}) GIRR risk weights do not match the tenors", file!(), line!());
+        }
+        let csr = &self.csr;
+        check_buckets("CSR", &csr.buckets, &csr.bucket_correlations, &[("risk weights", &csr.risk_weights)])?;
+        let eq = &self.equity;
+        check_buckets("equity", &eq.buckets, &eq.bucket_correlations, &[
+            ("risk weights", &eq.risk_weights),
+            ("name correlations", &eq.name_correlations),
+            ("vega risk weights", &eq.vega_risk_weights),
+        ])?;
+        if !csr.buckets.contains(&csr.other_bucket) || !eq.buckets.contains(&eq.other_bucket) {
+            bail!("({}:{}) the other buckets must be in the buckets", file!(), line!());
+        }
+        if let Some(category) = RATING_CATEGORIES.iter().find(|c| !self.drc.risk_weights.contains_key(**c)) {
+            bail!("({}:{}) DRC has no risk weight for {}", file!(), line!(), category);
+        }
+        for tenor in self.girr.tenors.iter()
+            .chain(self.girr.option_maturities.iter())
+            .chain(csr.tenors.iter())
+            .chain(csr.option_maturities.iter())
+            .chain(eq.option_maturities.iter())
+            .chain(self.fx.option_maturities.iter()) {
+            tenor_days(tenor)?;
+        }
+        Ok(())
+    }
+
+    fn girr_risk_weight(&self, tenor: usize, currency: &str, reporting_currency: &str) -> f64 {
+        let girr = &self.girr;
+        let scaling = match currency == reporting_currency || girr.specified_currencies.iter().any(|c| c == currency) {
+            true => girr.specified_currency_scaling,
+            false => 1.0,
+        };
+        (girr.risk_weights[tenor] * scaling) as f64
+    }
+
+    fn fx_risk_weight(&self, currency: &str, reporting_currency: &str) -> f64 {
+        let fx = &self.fx;
+        let specified = |c: &str| fx.specified_currencies.iter().any(|s| s == c);
+        match specified(currency) && specified(reporting_currency) {
+            true => (fx.risk_weight * fx.specified_pair_scaling) as f64,
+            false => fx.risk_weight as f64,
+        }
+    }
+
+    /// (bucket, risk weight) of a curvature risk factor: the parallel shift by the largest GIRR risk weight,
+    /// the credit spread shift and the relative equity and fx shocks by the delta risk weights
+    fn curvature_risk_weight(
+        &self,
+        risk_class: FrtbRiskClass,
+        bucket: &str,
+        qualifier: &str,
+        reporting_currency: &str,
+    ) -> Result<f64> {
+        let risk_weight = match risk_class {
+            FrtbRiskClass::Girr => self.girr.risk_weights.iter().fold(0.0, |max: Real, rw| max.max(*rw)) as f64,
+            FrtbRiskClass::CsrNonSec => self.csr.risk_weights.get(bucket).copied()
+                .ok_or_else(|| anyhow!("({}:{}) unknown CSR bucket {}", file!(), line!(), bucket))? as f64,
+            FrtbRiskClass::Equity => self.equity.risk_weights.get(bucket).copied()
+                .ok_or_else(|| anyhow!("({}:{}) unknown equity bucket {}", file!(), line!(), bucket))? as f64,
+            FrtbRiskClass::Fx => self.fx_risk_weight(qualifier, reporting_currency),
+        };
+        Ok(risk_weight)
+    }
+}
+
+fn label_years(label: &str) -> Result<f64> {
+    Ok(tenor_days(label)? / 365.0)
+}
+
+fn label_index(labels: &[String], label: &str) -> Result<usize> {
+    labels.iter().position(|l| l.eq_ignore_ascii_case(label.trim()))
+        .ok_or_else(|| anyhow!("({}:{}) {} is not in the FRTB tenors {:?}", file!(), line!(), label, labels))
+}
+
+/// exp(-decay |t1 - t2| / min(t1, t2))
+fn maturity_correlation(t1: f64, t2: f64, decay: Real) -> f64 {
+    (-(decay as f64) * (t1 - t2).abs() / t1.min(t2)).exp()
+}
+
+/// High: min(1.25 rho, 1), Low: max(2 rho - 1, 0.75 rho)
+fn scenario_correlation(rho: f64, scenario: FrtbCorrelationScenario) -> f64 {
+    match scenario {
+        FrtbCorrelationScenario::Medium => rho,
+        FrtbCorrelationScenario::High => (1.25 * rho).min(1.0),
+        FrtbCorrelationScenario::Low => (2.0 * rho - 1.0).max(0.75 * rho),
+    }
+}
+
+fn rating_category(credit_rating: CreditRating) -> &'static str {
+    match credit_rating {
+        CreditRating::AAA => "AAA",
+        CreditRating::AAp | CreditRating::AA | CreditRating::AAm => "AA",
+        CreditRating::Ap | CreditRating::A | CreditRating::Am => "A",
+        CreditRating::BBBp | CreditRating::BBB | CreditRating::BBBm => "BBB",
+        CreditRating::BBp | CreditRating::BB | CreditRating::BBm => "BB",
+        CreditRating::Bp | CreditRating::B => "B",
+        CreditRating::C => "CCC",
+        CreditRating::D => "Defaulted",
+        CreditRating::None | CreditRating::Undefined => "Unrated",
+    }
+}
+
+fn curvature_risk_factor(risk_class: FrtbRiskClass, qualifier: &str) -> String {
+    let name = serde_json::to_value(risk_class).ok()
+        .and_then(|value| value.as_str().map(|s| s.to_string()))
+        .unwrap_or_default();
+    format!("{}/{}", name, qualifier)
+}
+
+fn parse_curvature_risk_factor(risk_factor: &str) -> Result<(FrtbRiskClass, String)> {
+    let (risk_class, qualifier) = risk_factor.split_once('/')
+        .ok_or_else(|| anyhow!("({}:{}) invalid curvature risk factor {}", file!(), line!(), risk_factor))?;
+    let risk_class: FrtbRiskClass = serde_json::from_value(serde_json::Value::String(risk_class.to_string()))
+        .map_err(|_| anyhow!("({}:{}) unknown risk class of curvature risk factor {}", file!(), line!(), risk_factor))?;
+    Ok((risk_class, qualifier.to_string()))
+}
+
+/// Delta or vega sensitivity in the reporting currency:
+/// dV / 0.0001 for rates and credit spreads, dV / 0.01 for the relative moves of equities and fx rates,
+/// and dV / 0.01 * implied volatility for vega.
+/// bucket is the currency (GIRR, FX delta), the currency pair (FX vega) or the bucket number (CSR, EQ).
+/// qualifier is the curve (GIRR), the issuer (CSR), the equity (EQ) or the currency (FX).
+/// label1 is the tenor or the option maturity, and label2 the underlying maturity (GIRR vega) or the basis (CSR).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrtbSensitivity {
+    pub trade_id: String,
+    pub risk_class: FrtbRiskClass,
+    pub risk_measure: FrtbRiskMeasure,
+    pub bucket: String,
+    pub qualifier: String,
+    #[serde(default)]
+    pub label1: String,
+    #[serde(default)]
+    pub label2: String,
+    pub amount: Real,
+}
+
+/// curvature risk of a risk factor in the reporting currency:
+/// cvr_up = -(V(up) - V - RW * s) and cvr_down = -(V(down) - V + RW * s), where s is the delta sensitivity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrtbCurvature {
+    pub trade_id: String,
+    pub risk_class: FrtbRiskClass,
+    pub bucket: String,
+    pub qualifier: String,
+    pub cvr_up: Real,
+    pub cvr_down: Real,
+}
+
+/// position for the default risk charge. notional is signed (negative for a short position),
+/// market_value is in the reporting currency and maturity in years.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrtbDefaultExposure {
+    pub trade_id: String,
+    pub obligor: String,
+    pub bucket: FrtbDrcBucket,
+    pub credit_rating: CreditRating,
+    pub rank: RankType,
+    pub notional: Real,
+    pub market_value: Real,
+    pub maturity: Real,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrtbPositions {
+    pub currency: Currency,
+    pub sensitivities: Vec<FrtbSensitivity>,
+    pub curvatures: Vec<FrtbCurvature>,
+    pub default_exposures: Vec<FrtbDefaultExposure>,
+}
+
+impl FrtbPositions {
+    pub fn new(currency: Currency) -> FrtbPositions {
+        FrtbPositions {
+            currency,
+            sensitivities: vec![],
+            curvatures: vec![],
+            default_exposures: vec![],
+        }
+    }
+}
+
+/// capital of a risk measure under each correlation scenario, and the capital K_b of each bucket
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrtbMeasureCapital {
+    pub capital: BTreeMap<FrtbCorrelationScenario, Real>,
+    pub buckets: BTreeMap<FrtbCorrelationScenario, BTreeMap<String, Real>>,
+}
+
+/// capital of a risk class: the sum of delta, vega and curvature under each correlation scenario
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrtbRiskClassCapital {
+    pub measures: BTreeMap<FrtbRiskMeasure, FrtbMeasureCapital>,
+    pub capital: BTreeMap<FrtbCorrelationScenario, Real>,
+}
+
+/// default risk charge with the net jump-to-default of each obligor (bucket -> obligor -> net JTD)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrtbDrcCapital {
+    pub net_jtd: BTreeMap<FrtbDrcBucket, BTreeMap<String, Real>>,
+    pub buckets: BTreeMap<FrtbDrcBucket, Real>,
+    pub total: Real,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrtbReport {
+    version: String,
+    currency: Currency,
+    risk_classes: BTreeMap<FrtbRiskClass, FrtbRiskClassCapital>,
+    scenario_capitals: BTreeMap<FrtbCorrelationScenario, Real>,
+    binding_scenario: FrtbCorrelationScenario,
+    drc: FrtbDrcCapital,
+}
+
+impl FrtbReport {
+    pub fn get_version(&self) -> &String {
+        &self.version
+    }
+
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn get_risk_classes(&self) -> &BTreeMap<FrtbRiskClass, FrtbRiskClassCapital> {
+        &self.risk_classes
+    }
+
+    pub fn get_risk_class(&self, risk_class: FrtbRiskClass) -> Option<&FrtbRiskClassCapital> {
+        self.risk_classes.get(&risk_class)
+    }
+
+    /// sum of the risk classes under each correlation scenario
+    pub fn get_scenario_capitals(&self) -> &BTreeMap<FrtbCorrelationScenario, Real> {
+        &self.scenario_capitals
+    }
+
+    /// the correlation scenario with the largest capital
+    pub fn get_binding_scenario(&self) -> FrtbCorrelationScenario {
+        self.binding_scenario
+    }
+
+    /// sensitivities-based method capital, i.e., the capital of the binding scenario
+    pub fn get_sbm_capital(&self) -> Real {
+        self.scenario_capitals.get(&self.binding_scenario).copied().unwrap_or(0.0)
+    }
+
+    pub fn get_drc(&self) -> &FrtbDrcCapital {
+        &self.drc
+    }
+
+    pub fn get_total(&self) -> Real {
+        self.get_sbm_capital() + self.drc.total
+    }
+}
+
+/// risk factor of a bucket: (qualifier, label1, label2) of the sensitivities
+type FactorKey = (String, String, String);
+
+struct BucketCapital {
+    bucket: String,
+    k: f64,
+    s: f64,
+}
+
+/// sqrt(sum_b K_b^2 + sum_{b != c} gamma_bc S_b S_c), where S_b is replaced by max(min(S_b, K_b), -K_b)
+/// if the sum is negative. For curvature, the terms of two negative S are dropped.
+fn aggregate_buckets(buckets: &[BucketCapital], gamma: impl Fn(&str, &str) -> f64, curvature: bool) -> f64 {
+    let sum = |s: &dyn Fn(&BucketCapital) -> f64| -> f64 {
+        let mut total: f64 = buckets.iter().map(|b| b.k * b.k).sum();
+        for b in buckets.iter() {
+            for c in buckets.iter() {
+                let (sb, sc) = (s(b), s(c));
+                if b.bucket == c.bucket || (curvature && sb < 0.0 && sc < 0.0) {
+                    continue;
+                }
+                total += gamma(&b.bucket, &c.bucket) * sb * sc;
+            }
+        }
+        total
+    };
+    let mut total = sum(&|b| b.s);
+    if total < 0.0 {
+        total = sum(&|b: &BucketCapital| b.s.clamp(-b.k, b.k));
+    }
+    total.max(0.0).sqrt()
+}
+
+/// K_b = sqrt(max(sum max(CVR, 0)^2 + sum_{k != l} rho CVR_k CVR_l, 0)) without the terms of two negative CVRs,
+/// for the up and the down shocks. The larger one is taken (the one with the larger sum if they are equal).
+fn curvature_bucket(bucket: &str, cvrs: &[(f64, f64)], rho: impl Fn(usize, usize) -> f64) -> BucketCapital {
+    let k = |values: &[f64]| -> f64 {
+        let mut sum = 0.0;
+        for (i, x) in values.iter().enumerate() {
+            for (j, y) in values.iter().enumerate() {
+                if i == j {
+                    sum += x.max(0.0).powi(2);
+                } else if *x >= 0.0 || *y >= 0.0 {
+                    sum += rho(i, j) * x * y;
+                }
+            }
+        }
+        sum.max(0.0).sqrt()
+    };
+    let up: Vec<f64> = cvrs.iter().map(|(up, _)| *up).collect();
+    let down: Vec<f64> = cvrs.iter().map(|(_, down)| *down).collect();
+    let (k_up, k_down) = (k(&up), k(&down));
+    let (s_up, s_down): (f64, f64) = (up.iter().sum(), down.iter().sum());
+    let (k, s) = if k_up > k_down || (k_up == k_down && s_up >= s_down) { (k_up, s_up) } else { (k_down, s_down) };
+    BucketCapital { bucket: bucket.to_string(), k, s }
+}
+
+/// FRTB standardised approach: the sensitivities-based method (delta, vega and curvature of GIRR, CSR non-securitisation,
+/// equity and FX under the low, medium and high correlation scenarios) and the default risk charge.
+pub struct FrtbCalculator {
+    parameters: FrtbParameters,
+}
+
+impl FrtbCalculator {
+    pub fn new(parameters: FrtbParameters) -> FrtbCalculator {
+        FrtbCalculator { parameters }
+    }
+
+    pub fn get_parameters(&self) -> &FrtbParameters {
+        &self.parameters
+    }
+
+    pub fn calculate(&self, positions: &FrtbPositions) -> Result<FrtbReport> {
+        let mut sensitivities: BTreeMap<(FrtbRiskClass, FrtbRiskMeasure), Vec<&FrtbSensitivity>> = BTreeMap::new();
+        for sensitivity in positions.sensitivities.iter() {
+            if sensitivity.risk_measure == FrtbRiskMeasure::Curvature {
+                bail!(
+                    "({}:{}) curvature of {} must be given as FrtbCurvature, not as FrtbSensitivity",
+                    file!(), line!(), sensitivity.trade_id
+                );
+            }
+            sensitivities.entry((sensitivity.risk_class, sensitivity.risk_measure)).or_default().push(sensitivity);
+        }
+        let mut curvatures: BTreeMap<FrtbRiskClass, Vec<&FrtbCurvature>> = BTreeMap::new();
+        for curvature in positions.curvatures.iter() {
+            curvatures.entry(curvature.risk_class).or_default().push(curvature);
+        }
+
+        let reporting_currency = positions.currency.as_str();
+        let mut risk_classes: BTreeMap<FrtbRiskClass, FrtbRiskClassCapital> = BTreeMap::new();
+        for ((risk_class, risk_measure), records) in sensitivities.iter() {
+            let measure = self.sensitivity_capital(*risk_class, *risk_measure, records, reporting_currency)
+                .with_context(|| anyhow!("({}:{}) failed to calculate {:?} {:?}", file!(), line!(), risk_class, risk_measure))?;
+            risk_classes.entry(*risk_class).or_default().measures.insert(*risk_measure, measure);
+        }
+        for (risk_class, records) in curvatures.iter() {
+            let measure = self.curvature_capital(*risk_class, records)
+                .with_context(|| anyhow!("({}:{}) failed to calculate {:?} curvature", file!(), line!(), risk_class))?;
+            risk_classes.entry(*risk_class).or_default().measures.insert(FrtbRiskMeasure::Curvature, measure);
+        }
+
+        let mut scenario_capitals = BTreeMap::new();
+        for scenario in CORRELATION_SCENARIOS {
+            let mut total = 0.0;
+            for capital in risk_classes.values_mut() {
+                let sum: Real = capital.measures.values().map(|m| m.capital.get(&scenario).copied().unwrap_or(0.0)).sum();
+                capital.capital.insert(scenario, sum);
+                total += sum;
+            }
+            scenario_capitals.insert(scenario, total);
+        }
+        let binding_scenario = scenario_capitals.iter()
+            .fold((FrtbCorrelationScenario::Medium, Real::MIN), |(best, max), (scenario, capital)| {
+                if *capital > max { (*scenario, *capital) } else { (best, max) }
+            }).0;
+
+        Ok(FrtbReport {
+            version: self.parameters.version.clone(),
+            currency: positions.currency,
+            risk_classes,
+            scenario_capitals,
+            binding_scenario,
+            drc: self.drc_capital(&positions.default_exposures)?,
+        })
+    }
+
+    /// delta or vega capital where the sensitivities on the same risk factor are netted
+    fn sensitivity_capital(
+        &self,
+        risk_class: FrtbRiskClass,
+        risk_measure: FrtbRiskMeasure,
+        records: &[&FrtbSensitivity],
+        reporting_currency: &str,
+    ) -> Result<FrtbMeasureCapital> {
+        let p = &self.parameters;
+        let is_vega = risk_measure == FrtbRiskMeasure::Vega;
+        // bucket -> risk factor -> weighted sensitivity
+        let mut buckets: BTreeMap<String, BTreeMap<FactorKey, f64>> = BTreeMap::new();
+        for record in records.iter() {
+            let amount = record.amount as f64;
+            let (label1, label2) = (record.label1.trim().to_string(), record.label2.trim().to_string());
+            let risk_weight = match (risk_class, is_vega) {
+                (FrtbRiskClass::Girr, false) => {
+                    let tenor = label_index(&p.girr.tenors, &label1)?;
+                    p.girr_risk_weight(tenor, &record.bucket, reporting_currency)
+                },
+                (FrtbRiskClass::Girr, true) => p.girr.vega_risk_weight as f64,
+                (FrtbRiskClass::CsrNonSec, false) => {
+                    label_index(&p.csr.tenors, &label1)?;
+                    p.csr.risk_weights.get(&record.bucket).copied()
+                        .ok_or_else(|| anyhow!("({}:{}) unknown CSR bucket {}", file!(), line!(), record.bucket))? as f64
+                },
+                (FrtbRiskClass::CsrNonSec, true) => p.csr.vega_risk_weight as f64,
+                (FrtbRiskClass::Equity, false) => p.equity.risk_weights.get(&record.bucket).copied()
+                    .ok_or_else(|| anyhow!("({}:{}) unknown equity bucket {}", file!(), line!(), record.bucket))? as f64,
+                (FrtbRiskClass::Equity, true) => p.equity.vega_risk_weights.get(&record.bucket).copied()
+                    .ok_or_else(|| anyhow!("({}:{}) unknown equity bucket {}", file!(), line!(), record.bucket))? as f64,
+                (FrtbRiskClass::Fx, false) => p.fx_risk_weight(&record.bucket, reporting_currency),
+                (FrtbRiskClass::Fx, true) => p.fx.vega_risk_weight as f64,
+            };
+            if is_vega {
+                let option_maturities = match risk_class {
+                    FrtbRiskClass::Girr => &p.girr.option_maturities,
+                    FrtbRiskClass::CsrNonSec => &p.csr.option_maturities,
+                    FrtbRiskClass::Equity => &p.equity.option_maturities,
+                    FrtbRiskClass::Fx => &p.fx.option_maturities,
+                };
+                label_index(option_maturities, &label1)?;
+            }
+            let key = (record.qualifier.clone(), label1, label2);
+            *buckets.entry(record.bucket.clone()).or_default().entry(key).or_insert(0.0) += risk_weight * amount;
+        }
+
+        // correlation between the risk factors in a bucket
+        let rho = |bucket: &str, a: &FactorKey, b: &FactorKey| -> Result<f64> {
+            let option_correlation = |decay: Real| -> Result<f64> {
+                Ok(maturity_correlation(label_years(&a.1)?, label_years(&b.1)?, decay))
+            };
+            let correlation = match (risk_class, is_vega) {
+                (FrtbRiskClass::Girr, false) => {
+                    let girr = &p.girr;
+                    let tenor = maturity_correlation(label_years(&a.1)?, label_years(&b.1)?, girr.tenor_correlation_decay)
+                        .max(girr.tenor_correlation_floor as f64);
+                    if a.0 == b.0 { tenor } else { tenor * girr.curve_correlation as f64 }
+                },
+                (FrtbRiskClass::Girr, true) => {
+                    let underlying = match a.2.is_empty() || b.2.is_empty() {
+                        true => 1.0,
+                        false => maturity_correlation(label_years(&a.2)?, label_years(&b.2)?, p.girr.option_maturity_decay),
+                    };
+                    (option_correlation(p.girr.option_maturity_decay)? * underlying).min(1.0)
+                },
+                (FrtbRiskClass::CsrNonSec, false) => {
+                    let csr = &p.csr;
+                    let name = if a.0 == b.0 { 1.0 } else { csr.name_correlation };
+                    let tenor = if a.1 == b.1 { 1.0 } else { csr.tenor_correlation };
+                    let basis = if a.2 == b.2 { 1.0 } else { csr.basis_correlation };
+                    (name * tenor * basis) as f64
+                },
+                (FrtbRiskClass::CsrNonSec, true) => {
+                    let name = if a.0 == b.0 { 1.0 } else { p.csr.name_correlation as f64 };
+                    (name * option_correlation(p.csr.option_maturity_decay)?).min(1.0)
+                },
+                (FrtbRiskClass::Equity, _) => {
+                    let name = match a.0 == b.0 {
+                        true => 1.0,
+                        false => p.equity.name_correlations.get(bucket).copied().unwrap_or(0.0) as f64,
+                    };
+                    match is_vega {
+                        true => (name * option_correlation(p.equity.option_maturity_decay)?).min(1.0),
+                        false => name,
+                    }
+                },
+                (FrtbRiskClass::Fx, false) => 1.0,
+                (FrtbRiskClass::Fx, true) => option_correlation(p.fx.option_maturity_decay)?.min(1.0),
+            };
+            Ok(correlation)
+        };
+        let mut correlations: HashMap<String, Vec<Vec<f64>>> = HashMap::new();
+        for (bucket, factors) in buckets.iter() {
+            let keys: Vec<&FactorKey> = factors.keys().collect();
+            let matrix = keys.iter()
+                .map(|a| keys.iter().map(|b| rho(bucket, a, b)).collect::<Result<Vec<f64>>>())
+                .collect::<Result<Vec<Vec<f64>>>>()?;
+            correlations.insert(bucket.clone(), matrix);
+        }
+
+        let other_bucket = self.other_bucket(risk_class);
+        let mut res = FrtbMeasureCapital::default();
+        for scenario in CORRELATION_SCENARIOS {
+            let mut capitals = Vec::new();
+            let mut other = 0.0;
+            let mut bucket_capitals = BTreeMap::new();
+            for (bucket, factors) in buckets.iter() {
+                let ws: Vec<f64> = factors.values().copied().collect();
+                let k = match Some(bucket.as_str()) == other_bucket {
+                    true => {
+                        let k: f64 = ws.iter().map(|x| x.abs()).sum();
+                        other += k;
+                        k
+                    },
+                    false => {
+                        let matrix = &correlations[bucket];
+                        let k = correlated_sum(&ws, |i, j| scenario_correlation(matrix[i][j], scenario));
+                        capitals.push(BucketCapital { bucket: bucket.clone(), k, s: ws.iter().sum() });
+                        k
+                    },
+                };
+                bucket_capitals.insert(bucket.clone(), k as Real);
+            }
+            let capital = aggregate_buckets(
+                &capitals,
+                |b, c| scenario_correlation(self.bucket_correlation(risk_class, b, c), scenario),
+                false,
+            ) + other;
+            res.capital.insert(scenario, capital as Real);
+            res.buckets.insert(scenario, bucket_capitals);
+        }
+        Ok(res)
+    }
+
+    /// curvature capital where the correlations are the squares of the delta correlations
+    fn curvature_capital(&self, risk_class: FrtbRiskClass, records: &[&FrtbCurvature]) -> Result<FrtbMeasureCapital> {
+        // bucket -> qualifier -> (cvr_up, cvr_down)
+        let mut buckets: BTreeMap<String, BTreeMap<String, (f64, f64)>> = BTreeMap::new();
+        for record in records.iter() {
+            let cvr = buckets.entry(record.bucket.clone()).or_default()
+                .entry(record.qualifier.clone()).or_insert((0.0, 0.0));
+            cvr.0 += record.cvr_up as f64;
+            cvr.1 += record.cvr_down as f64;
+        }
+        let p = &self.parameters;
+        let rho = |bucket: &str| -> Result<f64> {
+            let rho = match risk_class {
+                FrtbRiskClass::Girr => p.girr.curve_correlation,
+                FrtbRiskClass::CsrNonSec => p.csr.name_correlation,
+                FrtbRiskClass::Equity => p.equity.name_correlations.get(bucket).copied()
+                    .ok_or_else(|| anyhow!("({}:{}) unknown equity bucket {}", file!(), line!(), bucket))?,
+                FrtbRiskClass::Fx => 1.0,
+            };
+            Ok((rho * rho) as f64)
+        };
+
+        let other_bucket = self.other_bucket(risk_class);
+        let mut res = FrtbMeasureCapital::default();
+        for scenario in CORRELATION_SCENARIOS {
+            let mut capitals = Vec::new();
+            let mut other = 0.0;
+            let mut bucket_capitals = BTreeMap::new();
+            for (bucket, factors) in buckets.iter() {
+                let cvrs: Vec<(f64, f64)> = factors.values().copied().collect();
+                let k = match Some(bucket.as_str()) == other_bucket {
+                    true => {
+                        let up: f64 = cvrs.iter().map(|(up, _)| up.max(0.0)).sum();
+                        let down: f64 = cvrs.iter().map(|(_, down)| down.max(0.0)).sum();
+                        other += up.max(down);
+                        up.max(down)
+                    },
+                    false => {
+                        let rho = scenario_correlation(rho(bucket)?, scenario);
+                        let capital = curvature_bucket(bucket, &cvrs, |_, _| rho);
+                        let k = capital.k;
+                        capitals.push(capital);
+                        k
+                    },
+                };
+                bucket_capitals.insert(bucket.clone(), k as Real);
+            }
+            let capital = aggregate_buckets(
+                &capitals,
+                |b, c| scenario_correlation(self.bucket_correlation(risk_class, b, c).powi(2), scenario),
+                true,
+            ) + other;
+            res.capital.insert(scenario, capital as Real);
+            res.buckets.insert(scenario, bucket_capitals);
+        }
+        Ok(res)
+    }
+
+    fn other_bucket(&self, risk_class: FrtbRiskClass) -> Option<&str> {
+        match risk_class {
+            FrtbRiskClass::CsrNonSec => Some(self.parameters.csr.other_bucket.as_str()),
+            FrtbRiskClass::Equity => Some(self.parameters.equity.other_bucket.as_str()),
+            _ => None,
+        }
+    }
+
+    /// correlation between the buckets b and c of the risk class
+    fn bucket_correlation(&self, risk_class: FrtbRiskClass, b: &str, c: &str) -> f64 {
+        let p = &self.parameters;
+        let from_matrix = |buckets: &[String], matrix: &[Vec<Real>]| -> f64 {
+            match (buckets.iter().position(|x| x == b), buckets.iter().position(|x| x == c)) {
+                (Some(i), Some(j)) => matrix[i][j] as f64,
+                _ => 0.0,
+            }
+        };
+        match risk_class {
+            FrtbRiskClass::Girr => p.girr.inter_currency_correlation as f64,
+            FrtbRiskClass::CsrNonSec => from_matrix(&p.csr.buckets, &p.csr.bucket_correlations),
+            FrtbRiskClass::Equity => from_matrix(&p.equity.buckets, &p.equity.bucket_correlations),
+            FrtbRiskClass::Fx => p.fx.correlation as f64,
+        }
+    }
+
+    /// JTD = max(LGD * notional + (market value - notional), 0) for long and min(.., 0) for short positions,
+    /// scaled by max(min(maturity, 1), maturity_floor) and netted by obligor.
+    /// DRC_b = max(sum RW * net long JTD - HBR * sum RW * |net short JTD|, 0) with HBR = long / (long + |short|)
+    fn drc_capital(&self, exposures: &[FrtbDefaultExposure]) -> Result<FrtbDrcCapital> {
+        let drc = &self.parameters.drc;
+        // bucket -> obligor -> (net jtd, risk weight)
+        let mut net: BTreeMap<FrtbDrcBucket, BTreeMap<String, (f64, f64)>> = BTreeMap::new();
+        for exposure in exposures.iter() {
+            let lgd = match exposure.rank {
+                RankType::Senior => drc.senior_lgd,
+                _ => drc.non_senior_lgd,
+            } as f64;
+            let notional = exposure.notional as f64;
+            let jtd = lgd * notional + (exposure.market_value as f64 - notional);
+            let jtd = if notional >= 0.0 { jtd.max(0.0) } else { jtd.min(0.0) };
+            let scaling = (exposure.maturity as f64).min(1.0).max(drc.maturity_floor as f64);
+            let risk_weight = drc.risk_weights.get(rating_category(exposure.credit_rating)).copied()
+                .ok_or_else(|| anyhow!("({}:{}) no DRC risk weight for {:?}", file!(), line!(), exposure.credit_rating))? as f64;
+            let entry = net.entry(exposure.bucket).or_default().entry(exposure.obligor.clone()).or_insert((0.0, 0.0));
+            entry.0 += jtd * scaling;
+            // the worst rating of the obligor
+            entry.1 = entry.1.max(risk_weight);
+        }
+
+        let mut res = FrtbDrcCapital::default();
+        for (bucket, obligors) in net.iter() {
+            let long: f64 = obligors.values().map(|(jtd, _)| jtd.max(0.0)).sum();
+            let short: f64 = obligors.values().map(|(jtd, _)| (-jtd).max(0.0)).sum();
+            let hbr = if long + short > 0.0 { long / (long + short) } else { 0.0 };
+            let weighted_long: f64 = obligors.values().map(|(jtd, rw)| rw * jtd.max(0.0)).sum();
+            let weighted_short: f64 = obligors.values().map(|(jtd, rw)| rw * (-jtd).max(0.0)).sum();
+            let capital = (weighted_long - hbr * weighted_short).max(0.0);
+            res.buckets.insert(*bucket, capital as Real);
+            res.net_jtd.insert(*bucket, obligors.iter().map(|(name, (jtd, _))| (name.clone(), *jtd as Real)).collect());
+            res.total += capital as Real;
+        }
+        Ok(res)
+    }
+}
+
+/// FRTB issuer and bucket of a credit curve
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrtbCreditMapping {
+    pub issuer: String,
+    pub bucket: String,
+}
+
+/// Mapping of the results of an EngineGenerator to FRTB positions in the reporting currency.
+/// rho_structure goes to GIRR (bucket is the currency of the curve) or to CSR for the curves in credit_curves,
+/// delta and vega_structure of equities to EQ (bucket from equity_buckets, the other bucket otherwise),
+/// the value in a foreign currency and fx_delta to FX delta, and fx_vega to FX vega.
+/// The implied volatility of vega is taken from volatilities, the constant volatility data or
+/// the at-the-money volatility of the nearest expiry in the surface data in order.
+/// The borrowing curves of equities (equity repo rates) are not shifted in the GIRR curvature scenarios.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrtbMappingConfiguration {
+    currency: Currency,
+    credit_curves: HashMap<String, FrtbCreditMapping>,
+    borrowing_curves: Vec<String>,
+    equity_buckets: HashMap<String, String>,
+    volatilities: HashMap<String, Real>,
+}
+
+impl Default for FrtbMappingConfiguration {
+    fn default() -> FrtbMappingConfiguration {
+        FrtbMappingConfiguration {
+            currency: Currency::KRW,
+            credit_curves: HashMap::new(),
+            borrowing_curves: vec![],
+            equity_buckets: HashMap::new(),
+            volatilities: HashMap::new(),
+        }
+    }
+}
+
+impl FrtbMappingConfiguration {
+    /// reporting currency
+    pub fn with_currency(mut self, currency: Currency) -> FrtbMappingConfiguration {
+        self.currency = currency;
+        self
+    }
+
+    /// curve name -> issuer and CSR bucket. The rho of these curves is credit spread risk
+    pub fn with_credit_curves(mut self, credit_curves: HashMap<String, FrtbCreditMapping>) -> FrtbMappingConfiguration {
+        self.credit_curves = credit_curves;
+        self
+    }
+
+    /// borrowing curves in MatchParameter, i.e., the curves which are not in rho
+    pub fn with_borrowing_curves(mut self, borrowing_curves: Vec<String>) -> FrtbMappingConfiguration {
+        self.borrowing_curves = borrowing_curves;
+        self
+    }
+
+    /// equity code -> FRTB bucket
+    pub fn with_equity_buckets(mut self, equity_buckets: HashMap<String, String>) -> FrtbMappingConfiguration {
+        self.equity_buckets = equity_buckets;
+        self
+    }
+
+    /// underlying code or fx code -> implied volatility used for the vega sensitivity
+    pub fn with_volatilities(mut self, volatilities: HashMap<String, Real>) -> FrtbMappingConfiguration {
+        self.volatilities = volatilities;
+        self
+    }
+
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn get_credit_curves(&self) -> &HashMap<String, FrtbCreditMapping> {
+        &self.credit_curves
+    }
+
+    pub fn get_borrowing_curves(&self) -> &Vec<String> {
+        &self.borrowing_curves
+    }
+
+    pub fn get_equity_buckets(&self) -> &HashMap<String, String> {
+        &self.equity_buckets
+    }
+
+    pub fn get_volatilities(&self) -> &HashMap<String, Real> {
+        &self.volatilities
+    }
+}
+
+fn sensitivity(
+    trade_id: &str,
+    risk_class: FrtbRiskClass,
+    risk_measure: FrtbRiskMeasure,
+    labels: [&str; 4], // bucket, qualifier, label1, label2
+    amount: f64,
+) -> FrtbSensitivity {
+    FrtbSensitivity {
+        trade_id: trade_id.to_string(),
+        risk_class,
+        risk_measure,
+        bucket: labels[0].to_string(),
+        qualifier: labels[1].to_string(),
+        label1: labels[2].to_string(),
+        label2: labels[3].to_string(),
+        amount: amount as Real,
+    }
+}
+
+/// points (years, value) allocated linearly to the adjacent labels
+fn allocate_points(points: &[(f64, f64)], labels: &[String]) -> Result<Vec<f64>> {
+    let mut allocated = vec![0.0; labels.len()];
+    for (t, value) in points.iter() {
+        for (index, weight) in allocate(*t, labels)? {
+            allocated[index] += weight * value;
+        }
+    }
+    Ok(allocated)
+}
+
+pub struct FrtbSensitivityMapper {
+    configuration: FrtbMappingConfiguration,
+    parameters: FrtbParameters,
+}
+
+impl FrtbSensitivityMapper {
+    pub fn new(configuration: FrtbMappingConfiguration, parameters: FrtbParameters) -> FrtbSensitivityMapper {
+        FrtbSensitivityMapper {
+            configuration,
+            parameters,
+        }
+    }
+
+    fn equity_bucket(&self, code: &str) -> String {
+        self.configuration.equity_buckets.get(code).cloned()
+            .unwrap_or_else(|| self.parameters.equity.other_bucket.clone())
+    }
+
+    fn issuer_bucket(&self, issuer: &str) -> Option<&String> {
+        self.configuration.credit_curves.values()
+            .find(|mapping| mapping.issuer == issuer)
+            .map(|mapping| &mapping.bucket)
+    }
+
+    /// Curvature scenarios to be given to CalculationConfiguration::with_curvature_scenarios:
+    /// a parallel shift of the curves of each currency (GIRR) and of each issuer (CSR),
+    /// and relative shocks of each equity and of each currency against the reporting currency (FX).
+    pub fn get_curvature_scenarios(
+        &self,
+        curve_data: &HashMap<String, VectorData>,
+        stock_data: &HashMap<String, ValueData>,
+        fx_data: &HashMap<FxCode, ValueData>,
+    ) -> Result<Vec<CurvatureScenario>> {
+        let reporting_currency = self.configuration.currency;
+        let curvature_scenario = |risk_class: FrtbRiskClass, bucket: &str, qualifier: &str, shock: &dyn Fn(Scenario, Real) -> Scenario| -> Result<CurvatureScenario> {
+            let risk_factor = curvature_risk_factor(risk_class, qualifier);
+            let risk_weight = self.parameters.curvature_risk_weight(risk_class, bucket, qualifier, reporting_currency.as_str())? as Real;
+            Ok(CurvatureScenario::new(
+                risk_factor.clone(),
+                shock(Scenario::new(format!("{} up", risk_factor)), risk_weight),
+                shock(Scenario::new(format!("{} down", risk_factor)), -risk_weight),
+            ))
+        };
+        let curve_shocks = |curves: &[String]| {
+            let curves = curves.to_vec();
+            move |scenario: Scenario, shift: Real| curves.iter()
+                .fold(scenario, |scenario, curve| scenario.with_curve_shock(curve.clone(), None, None, shift))
+        };
+
+        let mut res = Vec::new();
+        let mut girr_curves: BTreeMap<Currency, Vec<String>> = BTreeMap::new();
+        let mut csr_curves: BTreeMap<&String, Vec<String>> = BTreeMap::new();
+        for (curve_name, data) in curve_data.iter() {
+            if self.configuration.borrowing_curves.contains(curve_name) {
+                continue;
+            }
+            match self.configuration.credit_curves.get(curve_name) {
+                Some(mapping) => csr_curves.entry(&mapping.issuer).or_default().push(curve_name.clone()),
+                None => girr_curves.entry(*data.get_currency()).or_default().push(curve_name.clone()),
+            }
+        }
+        for (currency, mut curves) in girr_curves {
+            curves.sort();
+            res.push(curvature_scenario(FrtbRiskClass::Girr, currency.as_str(), currency.as_str(), &curve_shocks(&curves))?);
+        }
+        for (issuer, mut curves) in csr_curves {
+            curves.sort();
+            let bucket = self.issuer_bucket(issuer).cloned().unwrap_or_default();
+            res.push(curvature_scenario(FrtbRiskClass::CsrNonSec, &bucket, issuer, &curve_shocks(&curves))?);
+        }
+
+        let mut equities: Vec<&String> = stock_data.keys().collect();
+        equities.sort();
+        for code in equities {
+            let shock = |scenario: Scenario, shift: Real| scenario.with_equity_shock(code.clone(), ShockType::Relative, shift);
+            res.push(curvature_scenario(FrtbRiskClass::Equity, &self.equity_bucket(code), code, &shock)?);
+        }
+
+        let mut currencies: Vec<Currency> = fx_data.keys()
+            .flat_map(|fx_code| [*fx_code.get_currency1(), *fx_code.get_currency2()])
+            .filter(|currency| *currency != reporting_currency)
+            .collect();
+        currencies.sort();
+        currencies.dedup();
+        for currency in currencies {
+            let fx_code = FxCode::new(currency, reporting_currency);
+            if !fx_data.contains_key(&fx_code) && !fx_data.contains_key(&fx_code.reciprocal()) {
+                continue;
+            }
+            let shock = |scenario: Scenario, shift: Real| scenario.with_fx_shock(fx_code.to_string(), ShockType::Relative, shift);
+            res.push(curvature_scenario(FrtbRiskClass::Fx, currency.as_str(), currency.as_str(), &shock)?);
+        }
+        Ok(res)
+    }
+
+    /// FRTB positions of all the instruments of the EngineGenerator.
+    /// The curvature needs the curvature pnls of the scenarios made by get_curvature_scenarios.
+    pub fn get_positions(&self, engine_generator: &EngineGenerator) -> Result<FrtbPositions> {
+        let results = engine_generator.get_calculation_results();
+        let configuration = engine_generator.get_calculation_configuration();
+        let rho_tenors = configuration.get_rho_structure_tenors()
+            .iter().map(|tenor| tenor_years(tenor)).collect::<Result<Vec<f64>>>()?;
+        let vega_tenors = configuration.get_vega_structure_tenors()
+            .iter().map(|tenor| tenor_years(tenor)).collect::<Result<Vec<f64>>>()?;
+        let evaluation_date = engine_generator.get_evaluation_date().get_date_clone();
+
+        let mut positions = FrtbPositions::new(self.configuration.currency);
+        for inst in engine_generator.get_instruments().iter() {
+            let code = inst.get_code();
+            let result = results.get(code)
+                .ok_or_else(|| anyhow!("({}:{}) no result for {}", file!(), line!(), code))?;
+            let maturity_years = inst.get_maturity()
+                .map(|maturity| NullCalendar::default().get_time_difference(&evaluation_date, maturity) as f64)
+                .unwrap_or(0.0);
+            self.map_result(engine_generator, inst, result, (&rho_tenors, &vega_tenors, maturity_years), &mut positions)
+                .with_context(|| anyhow!("({}:{}) failed to map the result of {} to FRTB", file!(), line!(), code))?;
+        }
+        Ok(positions)
+    }
+
+    fn volatility(&self, engine_generator: &EngineGenerator, code: &str) -> Result<f64> {
+        if let Some(volatility) = self.configuration.volatilities.get(code) {
+            return Ok(*volatility as f64);
+        }
+        if let Some(data) = engine_generator.get_equity_constant_volatility_data().get(code) {
+            return Ok(data.get_value() as f64);
+        }
+        if code.len() == 6 {
+            if let Some(data) = engine_generator.get_fx_constant_volatility_data().get(&FxCode::from(code)) {
+                return Ok(data.get_value() as f64);
+            }
+        }
+        if let Some(surface) = engine_generator.get_equity_volatility_surface_data().get(code) {
+            let spot = surface.get_spot()
+                .or_else(|| engine_generator.get_stock_data().get(code).map(|data| data.get_value()));
+            let strikes = surface.get_strike();
+            let values = surface.get_value();
+            if let (Some(spot), false) = (spot, strikes.is_empty() || values.is_empty()) {
+                let atm = strikes.iter().enumerate()
+                    .fold((0, Real::MAX), |(best, distance), (i, strike)| {
+                        if (strike - spot).abs() < distance { (i, (strike - spot).abs()) } else { (best, distance) }
+                    }).0;
+                return Ok(values[[0, atm]] as f64);
+            }
+        }
+        bail!("({}:{}) no volatility of {} for the FRTB vega", file!(), line!(), code)
+    }
+
+    fn map_result(
+        &self,
+        engine_generator: &EngineGenerator,
+        inst: &Instrument,
+        result: &CalculationResult,
+        (rho_tenors, vega_tenors, maturity_years): (&[f64], &[f64], f64),
+        positions: &mut FrtbPositions,
+    ) -> Result<()> {
+        let p = &self.parameters;
+        let code = inst.get_code();
+        let reporting_currency = self.configuration.currency;
+        let currency = *inst.get_currency();
+        let fx_rate = engine_generator.get_fx_rate(currency, reporting_currency)? as f64;
+        let stock_data = engine_generator.get_stock_data();
+        let equity_code = |key: &String| -> Option<String> {
+            let key = match inst.get_underlying_codes().first() {
+                Some(underlying) if key == code => (*underlying).clone(),
+                _ => key.clone(),
+            };
+            stock_data.contains_key(&key).then_some(key)
+        };
+        let mut records = Vec::new();
+        // curvature risk factor -> delta sensitivity, for RW * s of the curvature
+        let mut curvature_deltas: HashMap<String, f64> = HashMap::new();
+
+        // rates and credit spreads
+        let mut rho_structure: Vec<(String, Vec<(f64, f64)>)> = Vec::new();
+        match (result.get_rho_structure(), result.get_rho()) {
+            (Some(structure), _) => for (curve_name, rhos) in structure.iter() {
+                let points = rho_tenors.iter().zip(rhos.iter()).map(|(t, rho)| (*t, *rho as f64)).collect();
+                rho_structure.push((curve_name.clone(), points));
+            },
+            (None, Some(rho)) => for (curve_name, value) in rho.iter() {
+                rho_structure.push((curve_name.clone(), vec![(maturity_years, *value as f64)]));
+            },
+            _ => {},
+        }
+        rho_structure.sort_by(|a, b| a.0.cmp(&b.0));
+        for (curve_name, points) in rho_structure.iter() {
+            let points: Vec<(f64, f64)> = points.iter().map(|(t, rho)| (*t, rho / RHO_PNL_UNIT as f64 * fx_rate)).collect();
+            match self.configuration.credit_curves.get(curve_name) {
+                Some(mapping) => {
+                    let allocated = allocate_points(&points, &p.csr.tenors)?;
+                    for (tenor, amount) in p.csr.tenors.iter().zip(allocated.iter()).filter(|(_, a)| **a != 0.0) {
+                        records.push(sensitivity(
+                            code, FrtbRiskClass::CsrNonSec, FrtbRiskMeasure::Delta,
+                            [&mapping.bucket, &mapping.issuer, tenor, BOND_BASIS], *amount,
+                        ));
+                    }
+                    *curvature_deltas.entry(curvature_risk_factor(FrtbRiskClass::CsrNonSec, &mapping.issuer)).or_insert(0.0)
+                        += allocated.iter().sum::<f64>();
+                },
+                None => {
+                    let curve_currency = engine_generator.get_curve_data().get(curve_name)
+                        .map(|data| *data.get_currency())
+                        .unwrap_or(currency);
+                    let allocated = allocate_points(&points, &p.girr.tenors)?;
+                    for (tenor, amount) in p.girr.tenors.iter().zip(allocated.iter()).filter(|(_, a)| **a != 0.0) {
+                        records.push(sensitivity(
+                            code, FrtbRiskClass::Girr, FrtbRiskMeasure::Delta,
+                            [curve_currency.as_str(), curve_name, tenor, ""], *amount,
+                        ));
+                    }
+                    *curvature_deltas.entry(curvature_risk_factor(FrtbRiskClass::Girr, curve_currency.as_str())).or_insert(0.0)
+                        += allocated.iter().sum::<f64>();
+                },
+            }
+        }
+
+        // equities
+        let mut deltas: BTreeMap<String, f64> = BTreeMap::new();
+        for (key, value) in result.get_delta().into_iter().flatten() {
+            if let Some(equity) = equity_code(key) {
+                *deltas.entry(equity).or_insert(0.0) += *value as f64 / DELTA_PNL_UNIT as f64 * fx_rate;
+            }
+        }
+        for (equity, amount) in deltas.iter() {
+            records.push(sensitivity(
+                code, FrtbRiskClass::Equity, FrtbRiskMeasure::Delta, [&self.equity_bucket(equity), equity, "", ""], *amount,
+            ));
+            *curvature_deltas.entry(curvature_risk_factor(FrtbRiskClass::Equity, equity)).or_insert(0.0) += amount;
+        }
+        let mut vegas: Vec<(String, Vec<(f64, f64)>)> = Vec::new();
+        match (result.get_vega_structure(), result.get_vega()) {
+            (Some(structure), _) => for (und_code, values) in structure.iter() {
+                let points = vega_tenors.iter().zip(values.iter()).map(|(t, v)| (*t, *v as f64)).collect();
+                vegas.push((und_code.clone(), points));
+            },
+            (None, Some(vega)) => for (und_code, value) in vega.iter() {
+                vegas.push((und_code.clone(), vec![(maturity_years, *value as f64)]));
+            },
+            _ => {},
+        }
+        vegas.sort_by(|a, b| a.0.cmp(&b.0));
+        for (und_code, points) in vegas.iter() {
+            if !stock_data.contains_key(und_code) || points.iter().all(|(_, v)| *v == 0.0) {
+                continue;
+            }
+            let scaling = self.volatility(engine_generator, und_code)? / VEGA_PNL_UNIT as f64 * fx_rate;
+            let points: Vec<(f64, f64)> = points.iter().map(|(t, v)| (*t, v * scaling)).collect();
+            let bucket = self.equity_bucket(und_code);
+            let allocated = allocate_points(&points, &p.equity.option_maturities)?;
+            for (maturity, amount) in p.equity.option_maturities.iter().zip(allocated.iter()).filter(|(_, a)| **a != 0.0) {
+                records.push(sensitivity(
+                    code, FrtbRiskClass::Equity, FrtbRiskMeasure::Vega, [&bucket, und_code, maturity, ""], *amount,
+                ));
+            }
+        }
+
+        // fx: the value in a foreign currency moves linearly, so that it has no curvature
+        let mut fx_deltas: BTreeMap<Currency, f64> = BTreeMap::new();
+        if currency != reporting_currency {
+            *fx_deltas.entry(currency).or_insert(0.0) += result.get_value().unwrap_or(0.0) as f64 * fx_rate;
+        }
+        for (fx_code, value) in result.get_fx_delta().into_iter().flatten() {
+            let fx_code = FxCode::from(fx_code.as_str());
+            let amount = *value as f64 / DELTA_PNL_UNIT as f64 * fx_rate;
+            for (fx_currency, signed) in [(*fx_code.get_currency1(), amount), (*fx_code.get_currency2(), -amount)] {
+                *fx_deltas.entry(fx_currency).or_insert(0.0) += signed;
+                *curvature_deltas.entry(curvature_risk_factor(FrtbRiskClass::Fx, fx_currency.as_str())).or_insert(0.0) += signed;
+            }
+        }
+        for (fx_currency, amount) in fx_deltas.iter() {
+            if *fx_currency != reporting_currency && *amount != 0.0 {
+                let fx_currency = fx_currency.as_str();
+                records.push(sensitivity(
+                    code, FrtbRiskClass::Fx, FrtbRiskMeasure::Delta, [fx_currency, fx_currency, "", ""], *amount,
+                ));
+            }
+        }
+        for (fx_code, value) in result.get_fx_vega().into_iter().flatten() {
+            if *value == 0.0 {
+                continue;
+            }
+            let amount = *value as f64 / VEGA_PNL_UNIT as f64 * self.volatility(engine_generator, fx_code)? * fx_rate;
+            let allocated = allocate_points(&[(maturity_years, amount)], &p.fx.option_maturities)?;
+            for (maturity, amount) in p.fx.option_maturities.iter().zip(allocated.iter()).filter(|(_, a)| **a != 0.0) {
+                records.push(sensitivity(
+                    code, FrtbRiskClass::Fx, FrtbRiskMeasure::Vega, [fx_code, fx_code, maturity, ""], *amount,
+                ));
+            }
+        }
+
+        // curvature: cvr = -(V(shocked) - V -/+ RW * s)
+        let ups = result.get_curvature_up_pnl();
+        let downs = result.get_curvature_down_pnl();
+        if let (Some(ups), Some(downs)) = (ups, downs) {
+            let mut risk_factors: Vec<&String> = ups.keys().collect();
+            risk_factors.sort();
+            for risk_factor in risk_factors {
+                let (risk_class, qualifier) = parse_curvature_risk_factor(risk_factor)?;
+                let bucket = match risk_class {
+                    FrtbRiskClass::Girr | FrtbRiskClass::Fx => qualifier.clone(),
+                    FrtbRiskClass::CsrNonSec => self.issuer_bucket(&qualifier).cloned()
+                        .ok_or_else(|| anyhow!("({}:{}) issuer {} is not in the credit curves", file!(), line!(), qualifier))?,
+                    FrtbRiskClass::Equity => self.equity_bucket(&qualifier),
+                };
+                let risk_weight = p.curvature_risk_weight(risk_class, &bucket, &qualifier, reporting_currency.as_str())?;
+                let delta = risk_weight * curvature_deltas.get(risk_factor).copied().unwrap_or(0.0);
+                let up = ups[risk_factor] as f64 * fx_rate;
+                let down = downs.get(risk_factor).copied()
+                    .ok_or_else(|| anyhow!("({}:{}) no down pnl of {}", file!(), line!(), risk_factor))? as f64 * fx_rate;
+                if up == 0.0 && down == 0.0 {
+                    continue;
+                }
+                positions.curvatures.push(FrtbCurvature {
+                    trade_id: code.clone(),
+                    risk_class,
+                    bucket,
+                    qualifier,
+                    cvr_up: -(up - delta) as Real,
+                    cvr_down: -(down + delta) as Real,
+                });
+            }
+        }
+        positions.sensitivities.extend(records);
+
+        // default risk of bonds
+        if let (Ok(credit_rating), Ok(rank), Ok(issuer_type), Ok(issuer)) = (
+            inst.get_credit_rating(), inst.get_rank_type(), inst.get_issuer_type(), inst.get_issuer_name(),
+        ) {
+            let value = result.get_value()
+                .ok_or_else(|| anyhow!("({}:{}) value of {} is not calculated", file!(), line!(), code))?;
+            let bucket = match issuer_type {
+                IssuerType::Government => FrtbDrcBucket::Sovereigns,
+                IssuerType::Public => FrtbDrcBucket::LocalGovernments,
+                _ => FrtbDrcBucket::Corporates,
+            };
+            positions.default_exposures.push(FrtbDefaultExposure {
+                trade_id: code.clone(),
+                obligor: issuer.clone(),
+                bucket,
+                credit_rating: *credit_rating,
+                rank: *rank,
+                notional: (inst.get_unit_notional() as f64 * fx_rate) as Real,
+                market_value: (value as f64 * fx_rate) as Real,
+                maturity: maturity_years as Real,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(risk_class: FrtbRiskClass, bucket: &str, qualifier: &str, label1: &str, amount: Real) -> FrtbSensitivity {
+        sensitivity("T1", risk_class, FrtbRiskMeasure::Delta, [bucket, qualifier, label1, ""], amount as f64)
+    }
+
+    fn capital(report: &FrtbReport, risk_class: FrtbRiskClass, measure: FrtbRiskMeasure, scenario: FrtbCorrelationScenario) -> f64 {
+        report.get_risk_class(risk_class).unwrap().measures[&measure].capital[&scenario] as f64
+    }
+
+    #[test]
+    fn test_frtb_delta_capital() -> Result<()> {
+        let parameters = FrtbParameters::default();
+        let mut positions = FrtbPositions::new(Currency::KRW);
+        positions.sensitivities = vec![
+            delta(FrtbRiskClass::Girr, "KRW", "KRWGOV", "1y", 1.0e6),
+            delta(FrtbRiskClass::Girr, "KRW", "KRWGOV", "5y", -2.0e6),
+            delta(FrtbRiskClass::Equity, "3", "A", "", 1.0e6),
+            delta(FrtbRiskClass::Equity, "3", "B", "", 1.0e6),
+            delta(FrtbRiskClass::Fx, "USD", "USD", "", 1.0e6),
+        ];
+        let report = FrtbCalculator::new(parameters.clone()).calculate(&positions)?;
+
+        // GIRR: the reporting currency is scaled by 1 / sqrt(2)
+        let girr = &parameters.girr;
+        let scaling = girr.specified_currency_scaling as f64;
+        let (ws1, ws5) = (girr.risk_weights[2] as f64 * scaling * 1.0e6, -(girr.risk_weights[5] as f64) * scaling * 2.0e6);
+        let rho = (-(girr.tenor_correlation_decay as f64) * 4.0).exp().max(girr.tenor_correlation_floor as f64);
+        for scenario in CORRELATION_SCENARIOS {
+            let rho = scenario_correlation(rho, scenario);
+            let expected = (ws1 * ws1 + ws5 * ws5 + 2.0 * rho * ws1 * ws5).sqrt();
+            let calculated = capital(&report, FrtbRiskClass::Girr, FrtbRiskMeasure::Delta, scenario);
+            assert!((calculated - expected).abs() < 1e-4 * expected, "{:?}: {} vs {}", scenario, calculated, expected);
+        }
+
+        // equity: two names in a bucket
+        let ws = parameters.equity.risk_weights["3"] as f64 * 1.0e6;
+        let rho = parameters.equity.name_correlations["3"] as f64;
+        let expected = (2.0 * ws * ws * (1.0 + rho)).sqrt();
+        let calculated = capital(&report, FrtbRiskClass::Equity, FrtbRiskMeasure::Delta, FrtbCorrelationScenario::Medium);
+        assert!((calculated - expected).abs() < 1e-4 * expected);
+
+        // fx: USDKRW is a specified pair
+        let expected = (parameters.fx.risk_weight * parameters.fx.specified_pair_scaling) as f64 * 1.0e6;
+        let calculated = capital(&report, FrtbRiskClass::Fx, FrtbRiskMeasure::Delta, FrtbCorrelationScenario::High);
+        assert!((calculated - expected).abs() < 1e-4 * expected);
+
+        let binding = report.get_binding_scenario();
+        for capital in report.get_scenario_capitals().values() {
+            assert!(*capital <= report.get_scenario_capitals()[&binding]);
+        }
+        assert_eq!(report.get_sbm_capital(), report.get_scenario_capitals()[&binding]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_frtb_curvature_capital() -> Result<()> {
+        let calculator = FrtbCalculator::new(FrtbParameters::default());
+        let curvature = |qualifier: &str, cvr_up: Real, cvr_down: Real| FrtbCurvature {
+            trade_id: "T1".to_string(),
+            risk_class: FrtbRiskClass::Equity,
+            bucket: "3".to_string(),
+            qualifier: qualifier.to_string(),
+            cvr_up,
+            cvr_down,
+        };
+        // a short option loses in both directions
+        let mut positions = FrtbPositions::new(Currency::KRW);
+        positions.curvatures = vec![curvature("A", 100.0, 60.0)];
+        let report = calculator.calculate(&positions)?;
+        assert!((capital(&report, FrtbRiskClass::Equity, FrtbRiskMeasure::Curvature, FrtbCorrelationScenario::Medium) - 100.0).abs() < 1e-3);
+
+        // a long option has no curvature capital, and two negative CVRs do not offset each other
+        positions.curvatures = vec![curvature("A", -100.0, -60.0), curvature("B", -50.0, -50.0)];
+        let report = calculator.calculate(&positions)?;
+        assert_eq!(capital(&report, FrtbRiskClass::Equity, FrtbRiskMeasure::Curvature, FrtbCorrelationScenario::Low), 0.0);
+
+        // the positive CVR is offset by the negative CVR with rho^2
+        positions.curvatures = vec![curvature("A", 100.0, 100.0), curvature("B", -50.0, -50.0)];
+        let report = calculator.calculate(&positions)?;
+        let rho = (FrtbParameters::default().equity.name_correlations["3"] as f64).powi(2);
+        let expected = (100.0_f64.powi(2) - 2.0 * rho * 100.0 * 50.0).sqrt();
+        let calculated = capital(&report, FrtbRiskClass::Equity, FrtbRiskMeasure::Curvature, FrtbCorrelationScenario::Medium);
+        assert!((calculated - expected).abs() < 1e-3, "{} vs {}", calculated, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_frtb_default_risk_charge() -> Result<()> {
+        let parameters = FrtbParameters::default();
+        let exposure = |obligor: &str, credit_rating: CreditRating, rank: RankType, notional: Real, market_value: Real| FrtbDefaultExposure {
+            trade_id: obligor.to_string(),
+            obligor: obligor.to_string(),
+            bucket: FrtbDrcBucket::Corporates,
+            credit_rating,
+            rank,
+            notional,
+            market_value,
+            maturity: 2.0,
+        };
+        let mut positions = FrtbPositions::new(Currency::KRW);
+        positions.default_exposures = vec![
+            exposure("A", CreditRating::A, RankType::Senior, 100.0, 100.0),
+            exposure("B", CreditRating::BB, RankType::Subordinated, -10.0, -10.0),
+        ];
+        let drc = FrtbCalculator::new(parameters.clone()).calculate(&positions)?.get_drc().clone();
+        let drc_parameters = &parameters.drc;
+        let long = drc_parameters.senior_lgd as f64 * 100.0;
+        let short = drc_parameters.non_senior_lgd as f64 * 10.0;
+        let hbr = long / (long + short);
+        let expected = drc_parameters.risk_weights["A"] as f64 * long - hbr * drc_parameters.risk_weights["BB"] as f64 * short;
+        assert!(expected > 0.0);
+        assert!((drc.total as f64 - expected).abs() < 1e-4);
+        assert!((drc.net_jtd[&FrtbDrcBucket::Corporates]["B"] as f64 + short).abs() < 1e-4);
+
+        assert_eq!(rating_category(CreditRating::BBBm), "BBB");
+        assert_eq!(parse_curvature_risk_factor(&curvature_risk_factor(FrtbRiskClass::CsrNonSec, "Issuer/A"))?,
+            (FrtbRiskClass::CsrNonSec, "Issuer/A".to_string()));
+        Ok(())
+    }
+}
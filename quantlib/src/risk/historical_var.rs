@@ -0,0 +1,558 @@
+use crate::currency::{Currency, FxCode};
+use crate::data::daily_value_data::DailyValueData;
+use crate::definitions::Real;
+use crate::enums::{RiskClass, ShockType};
+use crate::instrument::InstrumentTrait;
+use crate::pricing_engines::{
+    engine_generator::{resolve_fx_rate, EngineGenerator},
+    scenario::{Scenario, VolatilityShock},
+};
+//
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use ndarray::Array2;
+use time::Date;
+
+/// market data whose history generates the historical scenarios
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RiskFactor {
+    Equity { code: String },
+    /// e.g., fx_code = "USDKRW"
+    Fx { fx_code: String },
+    /// zero rate history of the bucket start_tenor < tenor <= end_tenor (None is unbounded)
+    Curve { curve_name: String, start_tenor: Option<String>, end_tenor: Option<String> },
+    /// (e.g., ATM) volatility history applied as a parallel shift of the volatility
+    Volatility { code: String },
+    /// dividend amount history of an underlying applied to all its dividends
+    Dividend { code: String },
+}
+
+impl RiskFactor {
+    pub fn get_risk_class(&self) -> RiskClass {
+        match self {
+            RiskFactor::Equity { .. } => RiskClass::Equity,
+            RiskFactor::Fx { .. } => RiskClass::Fx,
+            RiskFactor::Curve { .. } => RiskClass::Rates,
+            RiskFactor::Volatility { .. } => RiskClass::Volatility,
+            RiskFactor::Dividend { .. } => RiskClass::Dividend,
+        }
+    }
+}
+
+/// history of a risk factor and the type of the returns taken from it.
+/// Curve and Volatility returns are applied as additive shifts, so they must be Absolute.
+#[derive(Debug, Clone)]
+pub struct RiskFactorHistory {
+    risk_factor: RiskFactor,
+    return_type: ShockType,
+    history: DailyValueData,
+}
+
+impl RiskFactorHistory {
+    pub fn new(risk_factor: RiskFactor, return_type: ShockType, history: DailyValueData) -> Result<RiskFactorHistory> {
+        match risk_factor {
+            RiskFactor::Curve { .. } | RiskFactor::Volatility { .. } if return_type == ShockType::Relative => {
+                bail!(
+                    "({}:{}) {:?} is shifted additively, so its return type must be Absolute",
+                    file!(), line!(), risk_factor
+                );
+            },
+            _ => {},
+        }
+        Ok(RiskFactorHistory { risk_factor, return_type, history })
+    }
+
+    pub fn get_risk_factor(&self) -> &RiskFactor {
+        &self.risk_factor
+    }
+
+    pub fn get_return_type(&self) -> ShockType {
+        self.return_type
+    }
+
+    pub fn get_history(&self) -> &DailyValueData {
+        &self.history
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoricalVarConfiguration {
+    confidence_levels: Vec<Real>,
+    number_of_scenarios: usize,
+    horizon_days: usize,
+    currency: Currency,
+    number_of_chunks: usize,
+}
+
+impl Default for HistoricalVarConfiguration {
+    fn default() -> HistoricalVarConfiguration {
+        HistoricalVarConfiguration {
+            confidence_levels: vec![0.99, 0.975],
+            number_of_scenarios: 250,
+            horizon_days: 1,
+            currency: Currency::KRW,
+            number_of_chunks: rayon::current_num_threads(),
+        }
+    }
+}
+
+impl HistoricalVarConfiguration {
+    pub fn with_confidence_levels(mut self, confidence_levels: Vec<Real>) -> HistoricalVarConfiguration {
+        self.confidence_levels = confidence_levels;
+        self
+    }
+
+    /// the last number_of_scenarios returns in the histories are used
+    pub fn with_number_of_scenarios(mut self, number_of_scenarios: usize) -> HistoricalVarConfiguration {
+        self.number_of_scenarios = number_of_scenarios;
+        self
+    }
+
+    /// returns are taken over horizon_days observations (overlapping if horizon_days > 1)
+    pub fn with_horizon_days(mut self, horizon_days: usize) -> HistoricalVarConfiguration {
+        self.horizon_days = horizon_days;
+        self
+    }
+
+    /// currency in which the pnl is aggregated
+    pub fn with_currency(mut self, currency: Currency) -> HistoricalVarConfiguration {
+        self.currency = currency;
+        self
+    }
+
+    /// the scenarios are split into number_of_chunks repriced in parallel
+    pub fn with_number_of_chunks(mut self, number_of_chunks: usize) -> HistoricalVarConfiguration {
+        self.number_of_chunks = number_of_chunks;
+        self
+    }
+
+    pub fn get_confidence_levels(&self) -> &Vec<Real> {
+        &self.confidence_levels
+    }
+
+    pub fn get_number_of_scenarios(&self) -> usize {
+        self.number_of_scenarios
+    }
+
+    pub fn get_horizon_days(&self) -> usize {
+        self.horizon_days
+    }
+
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn get_number_of_chunks(&self) -> usize {
+        self.number_of_chunks
+    }
+}
+
+/// (start date, end date) of a return
+pub type ReturnPeriod = (Date, Date);
+
+/// Returns of the risk factors over horizon_days on the dates where all the risk factors are observed.
+/// The last number_of_returns returns are taken. It returns ((start date, end date) of the returns, returns)
+/// where the returns are of shape (number of returns, risk_factors.len()).
+pub fn risk_factor_returns(
+    risk_factors: &[RiskFactorHistory],
+    horizon_days: usize,
+    number_of_returns: usize,
+) -> Result<(Vec<ReturnPeriod>, Array2<Real>)> {
+    if risk_factors.is_empty() || horizon_days == 0 {
+        bail!(
+            "({}:{}) risk factors ({}) and horizon_days ({}) must not be empty",
+            file!(), line!(), risk_factors.len(), horizon_days
+        );
+    }
+    let mut dates: BTreeSet<Date> = risk_factors[0].history.get_value().keys().cloned().collect();
+    for risk_factor in risk_factors.iter().skip(1) {
+        dates.retain(|date| risk_factor.history.get_value().contains_key(date));
+    }
+    let dates: Vec<Date> = dates.into_iter().collect();
+    if dates.len() <= horizon_days {
+        bail!(
+            "({}:{}) {} common dates of the risk factor histories are not enough for {} day returns",
+            file!(), line!(), dates.len(), horizon_days
+        );
+    }
+    let first = (dates.len() - horizon_days).saturating_sub(number_of_returns) + horizon_days;
+
+    let mut periods = Vec::with_capacity(dates.len() - first);
+    let mut returns = Array2::<Real>::zeros((dates.len() - first, risk_factors.len()));
+    for (row, i) in (first..dates.len()).enumerate() {
+        let (start_date, end_date) = (dates[i - horizon_days], dates[i]);
+        periods.push((start_date, end_date));
+        for (col, risk_factor) in risk_factors.iter().enumerate() {
+            let values = risk_factor.history.get_value();
+            let (start, end) = (values[&start_date], values[&end_date]);
+            returns[[row, col]] = match risk_factor.return_type {
+                ShockType::Relative => {
+                    if start <= 0.0 {
+                        bail!(
+                            "({}:{}) relative return of {:?} on {} from a non-positive value {}",
+                            file!(), line!(), risk_factor.risk_factor, start_date, start
+                        );
+                    }
+                    end / start - 1.0
+                },
+                ShockType::Absolute => end - start,
+            };
+        }
+    }
+    Ok((periods, returns))
+}
+
+/// VaR and ES of a pnl vector at a confidence level.
+/// With k = ceil(n * (1 - confidence)), VaR is the loss of the k-th worst scenario and
+/// ES is the average loss of the k worst scenarios. Losses are positive.
+/// It returns (VaR, ES, index of the VaR scenario, indices of the tail scenarios).
+pub fn var_and_expected_shortfall(pnl: &[Real], confidence: Real) -> Result<(Real, Real, usize, Vec<usize>)> {
+    if pnl.is_empty() {
+        bail!("({}:{}) pnl is empty", file!(), line!());
+    }
+    if !(0.0 < confidence && confidence < 1.0) {
+        bail!("({}:{}) confidence level must be in (0, 1), got {}", file!(), line!(), confidence);
+    }
+    let mut order: Vec<usize> = (0..pnl.len()).collect();
+    order.sort_by(|&i, &j| pnl[i].partial_cmp(&pnl[j]).unwrap_or(std::cmp::Ordering::Equal));
+    // the tolerance keeps, e.g., 100 * (1 - 0.95) = 5 in single precision
+    let k = ((pnl.len() as Real * (1.0 - confidence) - 1.0e-4).ceil() as usize).clamp(1, pnl.len());
+    let tail = order[..k].to_vec();
+    let var = -pnl[tail[k - 1]];
+    let es = -tail.iter().map(|&i| pnl[i] as f64).sum::<f64>() as Real / k as Real;
+    Ok((var, es, tail[k - 1], tail))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoricalVarReport {
+    currency: Currency,
+    confidence_levels: Vec<Real>,
+    var: Vec<Real>,
+    expected_shortfall: Vec<Real>,
+    scenario_names: Vec<String>,
+    portfolio_pnl: Vec<Real>,
+    instrument_pnl: HashMap<String, Vec<Real>>, // instrument code -> pnl on scenarios
+    // (instrument code or underlying code) -> values on confidence levels
+    component_var: HashMap<String, Vec<Real>>,
+    incremental_var: HashMap<String, Vec<Real>>,
+    underlying_component_var: HashMap<String, Vec<Real>>,
+    underlying_incremental_var: HashMap<String, Vec<Real>>,
+}
+
+impl HistoricalVarReport {
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn get_confidence_levels(&self) -> &Vec<Real> {
+        &self.confidence_levels
+    }
+
+    pub fn get_var(&self) -> &Vec<Real> {
+        &self.var
+    }
+
+    pub fn get_expected_shortfall(&self) -> &Vec<Real> {
+        &self.expected_shortfall
+    }
+
+    pub fn get_scenario_names(&self) -> &Vec<String> {
+        &self.scenario_names
+    }
+
+    pub fn get_portfolio_pnl(&self) -> &Vec<Real> {
+        &self.portfolio_pnl
+    }
+
+    pub fn get_instrument_pnl(&self) -> &HashMap<String, Vec<Real>> {
+        &self.instrument_pnl
+    }
+
+    /// pnl of the instrument in the VaR scenario with the sign of a loss, so that they sum up to the VaR
+    pub fn get_component_var(&self) -> &HashMap<String, Vec<Real>> {
+        &self.component_var
+    }
+
+    /// VaR - VaR of the portfolio without the instrument
+    pub fn get_incremental_var(&self) -> &HashMap<String, Vec<Real>> {
+        &self.incremental_var
+    }
+
+    pub fn get_underlying_component_var(&self) -> &HashMap<String, Vec<Real>> {
+        &self.underlying_component_var
+    }
+
+    pub fn get_underlying_incremental_var(&self) -> &HashMap<String, Vec<Real>> {
+        &self.underlying_incremental_var
+    }
+}
+
+/// Historical simulation VaR and Expected Shortfall.
+/// The returns of the risk factor histories are applied to today's market as scenarios (see Scenario)
+/// and the whole portfolio in EngineGenerator is fully revalued in parallel (EngineGenerator::calculate_scenarios).
+/// The pnl of the instruments in other currencies are converted by the fx rates shocked in the scenario.
+pub struct HistoricalVar {
+    configuration: HistoricalVarConfiguration,
+    risk_factors: Vec<RiskFactorHistory>,
+}
+
+impl HistoricalVar {
+    pub fn new(configuration: HistoricalVarConfiguration, risk_factors: Vec<RiskFactorHistory>) -> Result<HistoricalVar> {
+        if configuration.confidence_levels.is_empty() {
+            bail!("({}:{}) confidence levels are empty", file!(), line!());
+        }
+        if configuration.horizon_days == 0 || configuration.number_of_scenarios == 0 {
+            bail!(
+                "({}:{}) horizon_days ({}) and number_of_scenarios ({}) must be positive",
+                file!(), line!(), configuration.horizon_days, configuration.number_of_scenarios
+            );
+        }
+        if risk_factors.is_empty() {
+            bail!("({}:{}) no risk factor history is given", file!(), line!());
+        }
+        Ok(HistoricalVar { configuration, risk_factors })
+    }
+
+    /// scenarios named by the end date of the returns, in the order of the dates
+    pub fn generate_scenarios(&self) -> Result<Vec<Scenario>> {
+        let (periods, returns) = risk_factor_returns(
+            &self.risk_factors,
+            self.configuration.horizon_days,
+            self.configuration.number_of_scenarios,
+        )?;
+
+        let mut scenarios = Vec::with_capacity(periods.len());
+        for ((start, end), row) in periods.iter().zip(returns.rows()) {
+            let mut scenario = Scenario::new(end.to_string())
+                .with_description(format!("historical returns from {} to {}", start, end));
+            for (risk_factor, shock) in self.risk_factors.iter().zip(row.iter()) {
+                let shock = *shock;
+                scenario = match &risk_factor.risk_factor {
+                    RiskFactor::Equity { code } => scenario.with_equity_shock(code.clone(), risk_factor.return_type, shock),
+                    RiskFactor::Fx { fx_code } => scenario.with_fx_shock(fx_code.clone(), risk_factor.return_type, shock),
+                    RiskFactor::Curve { curve_name, start_tenor, end_tenor } => scenario.with_curve_shock(
+                        curve_name.clone(), start_tenor.clone(), end_tenor.clone(), shock,
+                    ),
+                    RiskFactor::Volatility { code } => scenario.with_volatility_shock(VolatilityShock {
+                        code: code.clone(),
+                        start_tenor: None,
+                        end_tenor: None,
+                        left_spot_moneyness: None,
+                        right_spot_moneyness: None,
+                        value: shock,
+                    }),
+                    RiskFactor::Dividend { code } => scenario.with_dividend_shock(code.clone(), risk_factor.return_type, shock),
+                };
+            }
+            scenarios.push(scenario);
+        }
+        Ok(scenarios)
+    }
+
+    /// The quoted fx rates shocked by the scenario as in Engine::apply_scenario:
+    /// a shock on fx_code moves the data of fx_code and of its reciprocal.
+    fn shocked_fx_rates(scenario: &Scenario, fx_rates: &HashMap<FxCode, Real>) -> HashMap<FxCode, Real> {
+        let mut res = fx_rates.clone();
+        for shock in scenario.fx_shocks.iter() {
+            let fx_code = FxCode::from(shock.fx_code.as_str());
+            for (code, reciprocal) in [(fx_code, false), (fx_code.reciprocal(), true)] {
+                if let Some(rate) = res.get_mut(&code) {
+                    let quoted = if reciprocal { 1.0 / *rate } else { *rate };
+                    let shocked = match shock.shock_type {
+                        ShockType::Relative => quoted * (1.0 + shock.value),
+                        ShockType::Absolute => quoted + shock.value,
+                    };
+                    *rate = if reciprocal { 1.0 / shocked } else { shocked };
+                }
+            }
+        }
+        res
+    }
+
+    pub fn calculate(&self, engine_generator: &EngineGenerator) -> Result<HistoricalVarReport> {
+        let scenarios = self.generate_scenarios()?;
+        let results = engine_generator.calculate_scenarios(&scenarios, self.configuration.number_of_chunks)?;
+        let n = scenarios.len();
+        // the fx rates are resolved from the shocked data in the same way as EngineGenerator::get_fx_rate
+        let fx_rates: HashMap<FxCode, Real> = engine_generator.get_fx_data().iter()
+            .map(|(fx_code, data)| (*fx_code, data.get_value()))
+            .collect();
+        let shocked_fx_rates: Vec<HashMap<FxCode, Real>> = scenarios.iter()
+            .map(|scenario| HistoricalVar::shocked_fx_rates(scenario, &fx_rates))
+            .collect();
+
+        let mut instrument_pnl: HashMap<String, Vec<Real>> = HashMap::new();
+        let mut underlying_pnl: HashMap<String, Vec<Real>> = HashMap::new();
+        for inst in engine_generator.get_instruments().iter() {
+            let code = inst.get_code();
+            let result = results.get(code)
+                .ok_or_else(|| anyhow!("({}:{}) no result for {}", file!(), line!(), code))?;
+            let value = result.get_value()
+                .ok_or_else(|| anyhow!("({}:{}) value is not set for {}", file!(), line!(), code))?;
+            let scenario_pnl = result.get_scenario_pnl()
+                .ok_or_else(|| anyhow!("({}:{}) scenario pnl is not set for {}", file!(), line!(), code))?;
+            let currency = *inst.get_currency();
+            let fx_rate = engine_generator.get_fx_rate(currency, self.configuration.currency)?;
+
+            let mut pnl = Vec::with_capacity(n);
+            for (scenario, shocked_rates) in scenarios.iter().zip(shocked_fx_rates.iter()) {
+                let local_pnl = scenario_pnl.get(scenario.get_name())
+                    .ok_or_else(|| anyhow!(
+                        "({}:{}) pnl of {} is not set in scenario {}",
+                        file!(), line!(), code, scenario.get_name()
+                    ))?;
+                let shocked_fx_rate = resolve_fx_rate(
+                    |fx_code| shocked_rates.get(fx_code).copied(),
+                    currency,
+                    self.configuration.currency,
+                )?;
+                pnl.push((value + local_pnl) * shocked_fx_rate - value * fx_rate);
+            }
+
+            // instruments without underlying are grouped by their type
+            let underlying = inst.get_underlying_codes().first()
+                .map(|code| (*code).clone())
+                .unwrap_or_else(|| inst.get_type_name().to_string());
+            let group = underlying_pnl.entry(underlying).or_insert_with(|| vec![0.0; n]);
+            for (g, p) in group.iter_mut().zip(pnl.iter()) {
+                *g += p;
+            }
+            instrument_pnl.insert(code.clone(), pnl);
+        }
+
+        let mut portfolio_pnl = vec![0.0; n];
+        for pnl in instrument_pnl.values() {
+            for (total, p) in portfolio_pnl.iter_mut().zip(pnl.iter()) {
+                *total += p;
+            }
+        }
+
+        let mut var = Vec::new();
+        let mut expected_shortfall = Vec::new();
+        let mut component_var: HashMap<String, Vec<Real>> = HashMap::new();
+        let mut incremental_var: HashMap<String, Vec<Real>> = HashMap::new();
+        let mut underlying_component_var: HashMap<String, Vec<Real>> = HashMap::new();
+        let mut underlying_incremental_var: HashMap<String, Vec<Real>> = HashMap::new();
+        for confidence in self.configuration.confidence_levels.iter() {
+            let (portfolio_var, portfolio_es, var_scenario, _) = var_and_expected_shortfall(&portfolio_pnl, *confidence)?;
+            var.push(portfolio_var);
+            expected_shortfall.push(portfolio_es);
+
+            for (pnls, component, incremental) in [
+                (&instrument_pnl, &mut component_var, &mut incremental_var),
+                (&underlying_pnl, &mut underlying_component_var, &mut underlying_incremental_var),
+            ] {
+                for (key, pnl) in pnls.iter() {
+                    component.entry(key.clone()).or_default().push(-pnl[var_scenario]);
+                    let without: Vec<Real> = portfolio_pnl.iter().zip(pnl.iter()).map(|(t, p)| t - p).collect();
+                    let (var_without, _, _, _) = var_and_expected_shortfall(&without, *confidence)?;
+                    incremental.entry(key.clone()).or_default().push(portfolio_var - var_without);
+                }
+            }
+        }
+
+        Ok(HistoricalVarReport {
+            currency: self.configuration.currency,
+            confidence_levels: self.configuration.confidence_levels.clone(),
+            var,
+            expected_shortfall,
+            scenario_names: scenarios.iter().map(|scenario| scenario.get_name().clone()).collect(),
+            portfolio_pnl,
+            instrument_pnl,
+            component_var,
+            incremental_var,
+            underlying_component_var,
+            underlying_incremental_var,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::calendar::Calendar;
+    use crate::time::calendars::nullcalendar::NullCalendar;
+    use time::{macros::date, Duration, UtcOffset};
+
+    fn history(values: &[Real], code: &str) -> DailyValueData {
+        let start = date!(2024-01-01);
+        DailyValueData::new(
+            values.iter().enumerate().map(|(i, v)| (start + Duration::days(i as i64), *v)).collect(),
+            time::Time::from_hms(15, 40, 0).unwrap(),
+            UtcOffset::from_hms(9, 0, 0).unwrap(),
+            Calendar::NullCalendar(NullCalendar::new()),
+            code.to_string(),
+            code.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_var_and_expected_shortfall() -> Result<()> {
+        let pnl: Vec<Real> = (1..=100).map(|i| i as Real - 50.0).collect(); // -49, ..., 50
+        let (var, es, var_scenario, tail) = var_and_expected_shortfall(&pnl, 0.95)?;
+        assert_eq!(tail.len(), 5);
+        assert_eq!(var, 45.0);
+        assert_eq!(var_scenario, 4);
+        assert!((es - 47.0).abs() < 1.0e-5);
+        assert!(var_and_expected_shortfall(&pnl, 1.0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_shocked_fx_rates_through_krw_cross() -> Result<()> {
+        let usdkrw = FxCode::new(Currency::USD, Currency::KRW);
+        let eurkrw = FxCode::new(Currency::EUR, Currency::KRW);
+        let fx_rates = HashMap::from([(usdkrw, 1300.0), (eurkrw, 1400.0)]);
+        let scenario = Scenario::new("usd up".to_string())
+            .with_fx_shock("KRWUSD".to_string(), ShockType::Relative, -0.1);
+        let shocked = HistoricalVar::shocked_fx_rates(&scenario, &fx_rates);
+        // the shock on the reciprocal moves USDKRW to 1300 / 0.9
+        assert!((shocked[&usdkrw] - 1300.0 / 0.9).abs() < 1.0e-8);
+        assert_eq!(shocked[&eurkrw], 1400.0);
+
+        // USD in EUR has no quote and is resolved through KRW
+        let rate = resolve_fx_rate(|fx_code| fx_rates.get(fx_code).copied(), Currency::USD, Currency::EUR)?;
+        let shocked_rate = resolve_fx_rate(|fx_code| shocked.get(fx_code).copied(), Currency::USD, Currency::EUR)?;
+        assert!((rate - 1300.0 / 1400.0).abs() < 1.0e-12);
+        assert!((shocked_rate - rate / 0.9).abs() < 1.0e-12);
+        assert!(resolve_fx_rate(|fx_code| shocked.get(fx_code).copied(), Currency::USD, Currency::JPY).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_scenarios() -> Result<()> {
+        let equity = RiskFactorHistory::new(
+            RiskFactor::Equity { code: "KOSPI2".to_string() },
+            ShockType::Relative,
+            history(&[100.0, 110.0, 99.0, 99.0], "KOSPI2"),
+        )?;
+        let curve = RiskFactorHistory::new(
+            RiskFactor::Curve { curve_name: "KRWGOV".to_string(), start_tenor: None, end_tenor: None },
+            ShockType::Absolute,
+            history(&[0.030, 0.031, 0.029], "KRWGOV"),
+        )?;
+        assert!(RiskFactorHistory::new(
+            RiskFactor::Volatility { code: "KOSPI2".to_string() },
+            ShockType::Relative,
+            history(&[0.2, 0.21], "KOSPI2"),
+        ).is_err());
+
+        let configuration = HistoricalVarConfiguration::default().with_number_of_scenarios(250);
+        let scenarios = HistoricalVar::new(configuration.clone(), vec![equity.clone(), curve.clone()])?
+            .generate_scenarios()?;
+        // the curve has no data on the last date
+        assert_eq!(scenarios.len(), 2);
+        assert_eq!(scenarios[0].get_name(), "2024-01-02");
+        assert!((scenarios[0].equity_shocks[0].value - 0.1).abs() < 1.0e-6);
+        assert!((scenarios[1].equity_shocks[0].value + 0.1).abs() < 1.0e-6);
+        assert!((scenarios[1].curve_shocks[0].value + 0.002).abs() < 1.0e-6);
+
+        let scenarios = HistoricalVar::new(configuration.with_horizon_days(2).with_number_of_scenarios(1), vec![equity])?
+            .generate_scenarios()?;
+        assert_eq!(scenarios.len(), 1);
+        assert_eq!(scenarios[0].get_name(), "2024-01-04");
+        assert!((scenarios[0].equity_shocks[0].value + 0.1).abs() < 1.0e-6);
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+pub mod cva;
+pub mod exposure;
+pub mod frtb;
+pub mod historical_var;
+pub mod parametric_var;
+pub mod pnl_explain;
+pub mod portfolio;
+pub mod simm;
+pub mod util;
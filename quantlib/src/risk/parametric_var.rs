@@ -0,0 +1,735 @@
+use crate::currency::{Currency, FxCode};
+use crate::definitions::{Real, DELTA_PNL_UNIT, DIV_PNL_UNIT, RHO_PNL_UNIT, VEGA_PNL_UNIT};
+use crate::enums::{DeltaGammaMethod, RiskClass, ShockType};
+use crate::instrument::InstrumentTrait;
+use crate::pricing_engines::engine_generator::EngineGenerator;
+use crate::risk::historical_var::{
+    risk_factor_returns, var_and_expected_shortfall,
+    HistoricalVarReport, RiskFactor, RiskFactorHistory,
+};
+use crate::time::{calendar_trait::CalendarTrait, calendars::nullcalendar::NullCalendar};
+use crate::utils::string_arithmetic::add_period;
+//
+use anyhow::{anyhow, bail, Result};
+use ndarray::{Array1, Array2};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::StandardNormal;
+use serde::{Deserialize, Serialize};
+use statrs::distribution::{Continuous, ContinuousCDF, Normal};
+use std::collections::HashMap;
+
+/// number of quantiles averaged for the Cornish-Fisher expected shortfall
+const CORNISH_FISHER_ES_POINTS: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParametricVarConfiguration {
+    confidence_levels: Vec<Real>,
+    number_of_observations: usize,
+    horizon_days: usize,
+    ewma_lambda: Option<Real>,
+    currency: Currency,
+    delta_gamma_method: DeltaGammaMethod,
+    number_of_simulations: usize,
+    seed: u64,
+}
+
+impl Default for ParametricVarConfiguration {
+    fn default() -> ParametricVarConfiguration {
+        ParametricVarConfiguration {
+            confidence_levels: vec![0.99, 0.975],
+            number_of_observations: 250,
+            horizon_days: 1,
+            ewma_lambda: None,
+            currency: Currency::KRW,
+            delta_gamma_method: DeltaGammaMethod::CornishFisher,
+            number_of_simulations: 10_000,
+            seed: 0,
+        }
+    }
+}
+
+impl ParametricVarConfiguration {
+    pub fn with_confidence_levels(mut self, confidence_levels: Vec<Real>) -> ParametricVarConfiguration {
+        self.confidence_levels = confidence_levels;
+        self
+    }
+
+    /// the last number_of_observations returns in the histories are used for the covariance
+    pub fn with_number_of_observations(mut self, number_of_observations: usize) -> ParametricVarConfiguration {
+        self.number_of_observations = number_of_observations;
+        self
+    }
+
+    /// returns are taken over horizon_days observations (overlapping if horizon_days > 1)
+    pub fn with_horizon_days(mut self, horizon_days: usize) -> ParametricVarConfiguration {
+        self.horizon_days = horizon_days;
+        self
+    }
+
+    /// exponentially weighted covariance with decay lambda (e.g., 0.94). None is equally weighted.
+    pub fn with_ewma_lambda(mut self, ewma_lambda: Option<Real>) -> ParametricVarConfiguration {
+        self.ewma_lambda = ewma_lambda;
+        self
+    }
+
+    /// currency in which the sensitivities are aggregated
+    pub fn with_currency(mut self, currency: Currency) -> ParametricVarConfiguration {
+        self.currency = currency;
+        self
+    }
+
+    pub fn with_delta_gamma_method(mut self, delta_gamma_method: DeltaGammaMethod) -> ParametricVarConfiguration {
+        self.delta_gamma_method = delta_gamma_method;
+        self
+    }
+
+    /// number of simulations and the seed for DeltaGammaMethod::MonteCarlo
+    pub fn with_monte_carlo(mut self, number_of_simulations: usize, seed: u64) -> ParametricVarConfiguration {
+        self.number_of_simulations = number_of_simulations;
+        self.seed = seed;
+        self
+    }
+
+    pub fn get_confidence_levels(&self) -> &Vec<Real> {
+        &self.confidence_levels
+    }
+
+    pub fn get_number_of_observations(&self) -> usize {
+        self.number_of_observations
+    }
+
+    pub fn get_horizon_days(&self) -> usize {
+        self.horizon_days
+    }
+
+    pub fn get_ewma_lambda(&self) -> Option<Real> {
+        self.ewma_lambda
+    }
+
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn get_delta_gamma_method(&self) -> DeltaGammaMethod {
+        self.delta_gamma_method
+    }
+
+    pub fn get_number_of_simulations(&self) -> usize {
+        self.number_of_simulations
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParametricVarReport {
+    currency: Currency,
+    confidence_levels: Vec<Real>,
+    risk_factors: Vec<RiskFactor>,
+    delta: Vec<Real>,
+    gamma: Array2<Real>,
+    covariance: Array2<Real>,
+    delta_normal_var: Vec<Real>,
+    delta_normal_expected_shortfall: Vec<Real>,
+    delta_gamma_var: Vec<Real>,
+    delta_gamma_expected_shortfall: Vec<Real>,
+    // risk class -> values on confidence levels
+    risk_class_delta_normal_var: HashMap<RiskClass, Vec<Real>>,
+    risk_class_delta_gamma_var: HashMap<RiskClass, Vec<Real>>,
+    scenario_names: Vec<String>,
+    sensitivity_pnl: Vec<Real>,
+    risk_class_sensitivity_pnl: HashMap<RiskClass, Vec<Real>>,
+}
+
+impl ParametricVarReport {
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn get_confidence_levels(&self) -> &Vec<Real> {
+        &self.confidence_levels
+    }
+
+    pub fn get_risk_factors(&self) -> &Vec<RiskFactor> {
+        &self.risk_factors
+    }
+
+    /// pnl in the report currency per unit return of the risk factors
+    pub fn get_delta(&self) -> &Vec<Real> {
+        &self.delta
+    }
+
+    /// second derivative of the pnl in the report currency w.r.t. the returns of the risk factors
+    pub fn get_gamma(&self) -> &Array2<Real> {
+        &self.gamma
+    }
+
+    pub fn get_covariance(&self) -> &Array2<Real> {
+        &self.covariance
+    }
+
+    pub fn get_delta_normal_var(&self) -> &Vec<Real> {
+        &self.delta_normal_var
+    }
+
+    pub fn get_delta_normal_expected_shortfall(&self) -> &Vec<Real> {
+        &self.delta_normal_expected_shortfall
+    }
+
+    pub fn get_delta_gamma_var(&self) -> &Vec<Real> {
+        &self.delta_gamma_var
+    }
+
+    pub fn get_delta_gamma_expected_shortfall(&self) -> &Vec<Real> {
+        &self.delta_gamma_expected_shortfall
+    }
+
+    /// standalone VaR of the risk factors in each risk class
+    pub fn get_risk_class_delta_normal_var(&self) -> &HashMap<RiskClass, Vec<Real>> {
+        &self.risk_class_delta_normal_var
+    }
+
+    pub fn get_risk_class_delta_gamma_var(&self) -> &HashMap<RiskClass, Vec<Real>> {
+        &self.risk_class_delta_gamma_var
+    }
+
+    /// names of the historical returns (end date) as in HistoricalVar::generate_scenarios
+    pub fn get_scenario_names(&self) -> &Vec<String> {
+        &self.scenario_names
+    }
+
+    /// delta-gamma pnl on the historical returns used for the covariance
+    pub fn get_sensitivity_pnl(&self) -> &Vec<Real> {
+        &self.sensitivity_pnl
+    }
+
+    pub fn get_risk_class_sensitivity_pnl(&self) -> &HashMap<RiskClass, Vec<Real>> {
+        &self.risk_class_sensitivity_pnl
+    }
+
+    /// Compare the full revaluation pnl of HistoricalVar with the sensitivity pnl on the same returns.
+    /// The historical report must be made on the same risk factors, horizon, currency and confidence levels.
+    pub fn reconcile(&self, historical: &HistoricalVarReport) -> Result<VarReconciliation> {
+        if historical.get_currency() != self.currency || historical.get_confidence_levels() != &self.confidence_levels {
+            bail!(
+                "({}:{}) currencies ({:?}, {:?}) or confidence levels ({:?}, {:?}) are different",
+                file!(), line!(), historical.get_currency(), self.currency,
+                historical.get_confidence_levels(), self.confidence_levels
+            );
+        }
+        let index: HashMap<&String, usize> = self.scenario_names.iter().enumerate()
+            .map(|(i, name)| (name, i))
+            .collect();
+        let mut sensitivity_pnl = Vec::with_capacity(historical.get_scenario_names().len());
+        let mut risk_class_sensitivity_pnl: HashMap<RiskClass, Vec<Real>> = HashMap::new();
+        for name in historical.get_scenario_names().iter() {
+            let i = *index.get(name).ok_or_else(|| anyhow!(
+                "({}:{}) scenario {} of the historical VaR is not in the parametric VaR",
+                file!(), line!(), name
+            ))?;
+            sensitivity_pnl.push(self.sensitivity_pnl[i]);
+            for (risk_class, pnl) in self.risk_class_sensitivity_pnl.iter() {
+                risk_class_sensitivity_pnl.entry(*risk_class).or_default().push(pnl[i]);
+            }
+        }
+        let full_revaluation_pnl = historical.get_portfolio_pnl().clone();
+        let unexplained_pnl = full_revaluation_pnl.iter().zip(sensitivity_pnl.iter())
+            .map(|(full, sensitivity)| full - sensitivity)
+            .collect();
+
+        let mut sensitivity_historical_var = Vec::new();
+        let mut risk_class_sensitivity_historical_var: HashMap<RiskClass, Vec<Real>> = HashMap::new();
+        for confidence in self.confidence_levels.iter() {
+            sensitivity_historical_var.push(var_and_expected_shortfall(&sensitivity_pnl, *confidence)?.0);
+            for (risk_class, pnl) in risk_class_sensitivity_pnl.iter() {
+                risk_class_sensitivity_historical_var.entry(*risk_class).or_default()
+                    .push(var_and_expected_shortfall(pnl, *confidence)?.0);
+            }
+        }
+
+        Ok(VarReconciliation {
+            currency: self.currency,
+            confidence_levels: self.confidence_levels.clone(),
+            scenario_names: historical.get_scenario_names().clone(),
+            full_revaluation_pnl,
+            sensitivity_pnl,
+            unexplained_pnl,
+            historical_var: historical.get_var().clone(),
+            sensitivity_historical_var,
+            parametric_var: self.delta_gamma_var.clone(),
+            risk_class_sensitivity_historical_var,
+            risk_class_parametric_var: self.risk_class_delta_gamma_var.clone(),
+        })
+    }
+}
+
+/// full revaluation (HistoricalVar) against sensitivities (ParametricVar) on the same historical returns
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VarReconciliation {
+    currency: Currency,
+    confidence_levels: Vec<Real>,
+    scenario_names: Vec<String>,
+    full_revaluation_pnl: Vec<Real>,
+    sensitivity_pnl: Vec<Real>,
+    unexplained_pnl: Vec<Real>,
+    historical_var: Vec<Real>,
+    sensitivity_historical_var: Vec<Real>,
+    parametric_var: Vec<Real>,
+    risk_class_sensitivity_historical_var: HashMap<RiskClass, Vec<Real>>,
+    risk_class_parametric_var: HashMap<RiskClass, Vec<Real>>,
+}
+
+impl VarReconciliation {
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn get_confidence_levels(&self) -> &Vec<Real> {
+        &self.confidence_levels
+    }
+
+    pub fn get_scenario_names(&self) -> &Vec<String> {
+        &self.scenario_names
+    }
+
+    pub fn get_full_revaluation_pnl(&self) -> &Vec<Real> {
+        &self.full_revaluation_pnl
+    }
+
+    pub fn get_sensitivity_pnl(&self) -> &Vec<Real> {
+        &self.sensitivity_pnl
+    }
+
+    /// full revaluation pnl - sensitivity pnl
+    pub fn get_unexplained_pnl(&self) -> &Vec<Real> {
+        &self.unexplained_pnl
+    }
+
+    pub fn get_historical_var(&self) -> &Vec<Real> {
+        &self.historical_var
+    }
+
+    /// historical VaR of the sensitivity pnl
+    pub fn get_sensitivity_historical_var(&self) -> &Vec<Real> {
+        &self.sensitivity_historical_var
+    }
+
+    /// delta-gamma VaR
+    pub fn get_parametric_var(&self) -> &Vec<Real> {
+        &self.parametric_var
+    }
+
+    pub fn get_risk_class_sensitivity_historical_var(&self) -> &HashMap<RiskClass, Vec<Real>> {
+        &self.risk_class_sensitivity_historical_var
+    }
+
+    pub fn get_risk_class_parametric_var(&self) -> &HashMap<RiskClass, Vec<Real>> {
+        &self.risk_class_parametric_var
+    }
+}
+
+/// Parametric (delta-normal and delta-gamma) VaR from the sensitivities in CalculationResult.
+/// The covariance of the risk factor returns is estimated from the same histories as HistoricalVar
+/// (zero mean, equally or exponentially weighted), and the pnl is approximated by
+/// delta' x + 0.5 x' gamma x for the returns x of the risk factors.
+///
+/// The sensitivities are mapped to the risk factors as
+/// - Equity (Relative): delta and gamma of the underlying
+/// - Fx (Relative or Absolute): fx_exposure of the currency quoted against the report currency.
+///   Pairs without the report currency are not mapped
+/// - Curve (Absolute): rho, or the rho_structure buckets whose tenor is in (start_tenor, end_tenor]
+/// - Volatility (Absolute): vega
+/// - Dividend (Absolute): div_delta
+///
+/// so the EngineGenerator must have been calculated with these greeks. Missing greeks are taken as zero.
+pub struct ParametricVar {
+    configuration: ParametricVarConfiguration,
+    risk_factors: Vec<RiskFactorHistory>,
+}
+
+impl ParametricVar {
+    pub fn new(configuration: ParametricVarConfiguration, risk_factors: Vec<RiskFactorHistory>) -> Result<ParametricVar> {
+        if configuration.confidence_levels.is_empty() {
+            bail!("({}:{}) confidence levels are empty", file!(), line!());
+        }
+        if configuration.horizon_days == 0 || configuration.number_of_observations < 2 {
+            bail!(
+                "({}:{}) horizon_days ({}) must be positive and number_of_observations ({}) must be > 1",
+                file!(), line!(), configuration.horizon_days, configuration.number_of_observations
+            );
+        }
+        if let Some(lambda) = configuration.ewma_lambda {
+            if !(0.0 < lambda && lambda < 1.0) {
+                bail!("({}:{}) ewma lambda must be in (0, 1), got {}", file!(), line!(), lambda);
+            }
+        }
+        if configuration.delta_gamma_method == DeltaGammaMethod::MonteCarlo && configuration.number_of_simulations == 0 {
+            bail!("({}:{}) number_of_simulations must be positive", file!(), line!());
+        }
+        if risk_factors.is_empty() {
+            bail!("({}:{}) no risk factor history is given", file!(), line!());
+        }
+        for risk_factor in risk_factors.iter() {
+            match (risk_factor.get_risk_factor(), risk_factor.get_return_type()) {
+                (RiskFactor::Equity { .. }, ShockType::Absolute) | (RiskFactor::Dividend { .. }, ShockType::Relative) => {
+                    bail!(
+                        "({}:{}) {:?} with {:?} returns can not be mapped to the sensitivities",
+                        file!(), line!(), risk_factor.get_risk_factor(), risk_factor.get_return_type()
+                    );
+                },
+                _ => {},
+            }
+        }
+        Ok(ParametricVar { configuration, risk_factors })
+    }
+
+    /// Returns of the risk factors and their covariance. It returns (names of the returns, returns, covariance).
+    pub fn covariance(&self) -> Result<(Vec<String>, Array2<f64>, Array2<f64>)> {
+        let (periods, returns) = risk_factor_returns(
+            &self.risk_factors,
+            self.configuration.horizon_days,
+            self.configuration.number_of_observations,
+        )?;
+        let returns = returns.mapv(|x| x as f64);
+        let n = returns.nrows();
+        let weights: Array1<f64> = match self.configuration.ewma_lambda {
+            // the latest return has the largest weight
+            Some(lambda) => {
+                let lambda = lambda as f64;
+                let weights = Array1::from_iter((0..n).map(|t| lambda.powi((n - 1 - t) as i32)));
+                let sum = weights.sum();
+                weights / sum
+            },
+            None => Array1::from_elem(n, 1.0 / n as f64),
+        };
+        let weighted = &returns * &weights.insert_axis(ndarray::Axis(1));
+        let covariance = weighted.t().dot(&returns);
+        let names = periods.iter().map(|(_, end)| end.to_string()).collect();
+        Ok((names, returns, covariance))
+    }
+
+    /// delta and gamma of the portfolio in the report currency per unit return of the risk factors
+    pub fn sensitivities(&self, engine_generator: &EngineGenerator) -> Result<(Array1<f64>, Array2<f64>)> {
+        let report_currency = self.configuration.currency;
+        let configuration = engine_generator.get_calculation_configuration();
+        let eval_dt = engine_generator.get_evaluation_date().get_date_clone();
+        let time_calculator = NullCalendar::default();
+        let tenor_time = |tenor: &String| time_calculator.get_time_difference(&eval_dt, &add_period(&eval_dt, tenor));
+        let rho_times: Vec<Real> = configuration.get_rho_structure_tenors().iter().map(tenor_time).collect();
+
+        let m = self.risk_factors.len();
+        let mut delta = Array1::<f64>::zeros(m);
+        let mut gamma = Array2::<f64>::zeros((m, m));
+        let results = engine_generator.get_calculation_results();
+        for inst in engine_generator.get_instruments().iter() {
+            let code = inst.get_code();
+            let result = results.get(code)
+                .ok_or_else(|| anyhow!("({}:{}) no result for {}", file!(), line!(), code))?;
+            let fx_rate = engine_generator.get_fx_rate(*inst.get_currency(), report_currency)? as f64;
+            let greek = |map: Option<&HashMap<String, Real>>, key: &String| {
+                map.and_then(|map| map.get(key)).map(|v| *v as f64).unwrap_or(0.0)
+            };
+
+            for (k, risk_factor) in self.risk_factors.iter().enumerate() {
+                match risk_factor.get_risk_factor() {
+                    RiskFactor::Equity { code } => {
+                        // delta-one instruments (Stock, Futures) keep their delta on the instrument code
+                        let key = match result.get_delta() {
+                            Some(map) if !map.contains_key(code) && inst.get_underlying_codes().contains(&code) => inst.get_code(),
+                            _ => code,
+                        };
+                        let unit = DELTA_PNL_UNIT as f64;
+                        delta[k] += greek(result.get_delta(), key) * fx_rate / unit;
+                        gamma[[k, k]] += 2.0 * greek(result.get_gamma(), key) * fx_rate / (unit * unit);
+                    },
+                    RiskFactor::Fx { fx_code } => {
+                        let fx_code = FxCode::from(fx_code.as_str());
+                        let (currency1, currency2) = (*fx_code.get_currency1(), *fx_code.get_currency2());
+                        let (foreign, direct) = if currency2 == report_currency {
+                            (currency1, true)
+                        } else if currency1 == report_currency {
+                            (currency2, false)
+                        } else {
+                            continue;
+                        };
+                        // rate = foreign / report currency and the exposure is converted in the report currency
+                        let rate = engine_generator.get_fx_rate(foreign, report_currency)? as f64;
+                        let exposure = match result.get_fx_exposure().and_then(|map| map.get(&foreign)) {
+                            Some(exposure) => *exposure as f64 * rate,
+                            None => continue,
+                        };
+                        let (d, g) = match (direct, risk_factor.get_return_type()) {
+                            (true, ShockType::Relative) => (exposure, 0.0),
+                            (true, ShockType::Absolute) => (exposure / rate, 0.0),
+                            // the quoted rate is 1 / rate
+                            (false, ShockType::Relative) => (-exposure, 2.0 * exposure),
+                            (false, ShockType::Absolute) => (-exposure * rate, 2.0 * exposure * rate * rate),
+                        };
+                        delta[k] += d;
+                        gamma[[k, k]] += g;
+                    },
+                    RiskFactor::Curve { curve_name, start_tenor, end_tenor } => {
+                        let unit = RHO_PNL_UNIT as f64;
+                        if start_tenor.is_none() && end_tenor.is_none() {
+                            delta[k] += greek(result.get_rho(), curve_name) * fx_rate / unit;
+                            continue;
+                        }
+                        let structure = match result.get_rho_structure().and_then(|map| map.get(curve_name)) {
+                            Some(structure) => structure,
+                            None => continue,
+                        };
+                        let start = start_tenor.as_ref().map(tenor_time);
+                        let end = end_tenor.as_ref().map(tenor_time);
+                        for (time, rho) in rho_times.iter().zip(structure.iter()) {
+                            if start.is_none_or(|start| start < *time) && end.is_none_or(|end| *time <= end) {
+                                delta[k] += *rho as f64 * fx_rate / unit;
+                            }
+                        }
+                    },
+                    RiskFactor::Volatility { code } => {
+                        delta[k] += greek(result.get_vega(), code) * fx_rate / VEGA_PNL_UNIT as f64;
+                    },
+                    RiskFactor::Dividend { code } => {
+                        delta[k] += greek(result.get_div_delta(), code) * fx_rate / DIV_PNL_UNIT as f64;
+                    },
+                }
+            }
+        }
+        Ok((delta, gamma))
+    }
+
+    /// (VaR, ES) of the delta-gamma pnl by the configured method
+    fn delta_gamma_var(
+        &self,
+        delta: &Array1<f64>,
+        gamma: &Array2<f64>,
+        covariance: &Array2<f64>,
+        confidence: Real,
+    ) -> Result<(f64, f64)> {
+        match self.configuration.delta_gamma_method {
+            DeltaGammaMethod::CornishFisher => cornish_fisher_var(delta, gamma, covariance, confidence as f64),
+            DeltaGammaMethod::MonteCarlo => {
+                let lower = cholesky(covariance);
+                let mut rng = StdRng::seed_from_u64(self.configuration.seed);
+                let mut pnl = Vec::with_capacity(self.configuration.number_of_simulations);
+                for _ in 0..self.configuration.number_of_simulations {
+                    let z = Array1::from_iter((0..delta.len()).map(|_| rng.sample::<f64, _>(StandardNormal)));
+                    let x = lower.dot(&z);
+                    pnl.push((delta.dot(&x) + 0.5 * x.dot(&gamma.dot(&x))) as Real);
+                }
+                let (var, es, _, _) = var_and_expected_shortfall(&pnl, confidence)?;
+                Ok((var as f64, es as f64))
+            },
+        }
+    }
+
+    pub fn calculate(&self, engine_generator: &EngineGenerator) -> Result<ParametricVarReport> {
+        let (scenario_names, returns, covariance) = self.covariance()?;
+        let (delta, gamma) = self.sensitivities(engine_generator)?;
+
+        let mut risk_classes: Vec<RiskClass> = self.risk_factors.iter()
+            .map(|risk_factor| risk_factor.get_risk_factor().get_risk_class())
+            .collect();
+        risk_classes.sort();
+        risk_classes.dedup();
+        let class_mask = |risk_class: RiskClass| -> Array1<f64> {
+            Array1::from_iter(self.risk_factors.iter().map(|risk_factor| {
+                if risk_factor.get_risk_factor().get_risk_class() == risk_class { 1.0 } else { 0.0 }
+            }))
+        };
+
+        let mut delta_normal_var = Vec::new();
+        let mut delta_normal_expected_shortfall = Vec::new();
+        let mut delta_gamma_var = Vec::new();
+        let mut delta_gamma_expected_shortfall = Vec::new();
+        let mut risk_class_delta_normal_var: HashMap<RiskClass, Vec<Real>> = HashMap::new();
+        let mut risk_class_delta_gamma_var: HashMap<RiskClass, Vec<Real>> = HashMap::new();
+        for confidence in self.configuration.confidence_levels.iter() {
+            if !(0.0 < *confidence && *confidence < 1.0) {
+                bail!("({}:{}) confidence level must be in (0, 1), got {}", file!(), line!(), confidence);
+            }
+            let (var, es) = delta_normal_var_and_es(&delta, &covariance, *confidence as f64);
+            delta_normal_var.push(var as Real);
+            delta_normal_expected_shortfall.push(es as Real);
+            let (var, es) = self.delta_gamma_var(&delta, &gamma, &covariance, *confidence)?;
+            delta_gamma_var.push(var as Real);
+            delta_gamma_expected_shortfall.push(es as Real);
+
+            // standalone VaR on the risk factors of the class
+            for risk_class in risk_classes.iter() {
+                let mask = class_mask(*risk_class);
+                let class_delta = &delta * &mask;
+                let class_gamma = &gamma * &mask.view().insert_axis(ndarray::Axis(1)) * &mask;
+                let (var, _) = delta_normal_var_and_es(&class_delta, &covariance, *confidence as f64);
+                risk_class_delta_normal_var.entry(*risk_class).or_default().push(var as Real);
+                let (var, _) = self.delta_gamma_var(&class_delta, &class_gamma, &covariance, *confidence)?;
+                risk_class_delta_gamma_var.entry(*risk_class).or_default().push(var as Real);
+            }
+        }
+
+        let sensitivity_pnl_of = |delta: &Array1<f64>, gamma: &Array2<f64>| -> Vec<Real> {
+            returns.rows().into_iter()
+                .map(|x| (delta.dot(&x) + 0.5 * x.dot(&gamma.dot(&x))) as Real)
+                .collect()
+        };
+        let sensitivity_pnl = sensitivity_pnl_of(&delta, &gamma);
+        let mut risk_class_sensitivity_pnl = HashMap::new();
+        for risk_class in risk_classes.iter() {
+            let mask = class_mask(*risk_class);
+            let class_gamma = &gamma * &mask.view().insert_axis(ndarray::Axis(1)) * &mask;
+            risk_class_sensitivity_pnl.insert(*risk_class, sensitivity_pnl_of(&(&delta * &mask), &class_gamma));
+        }
+
+        Ok(ParametricVarReport {
+            currency: self.configuration.currency,
+            confidence_levels: self.configuration.confidence_levels.clone(),
+            risk_factors: self.risk_factors.iter().map(|risk_factor| risk_factor.get_risk_factor().clone()).collect(),
+            delta: delta.iter().map(|v| *v as Real).collect(),
+            gamma: gamma.mapv(|v| v as Real),
+            covariance: covariance.mapv(|v| v as Real),
+            delta_normal_var,
+            delta_normal_expected_shortfall,
+            delta_gamma_var,
+            delta_gamma_expected_shortfall,
+            risk_class_delta_normal_var,
+            risk_class_delta_gamma_var,
+            scenario_names,
+            sensitivity_pnl,
+            risk_class_sensitivity_pnl,
+        })
+    }
+}
+
+/// VaR = z sigma and ES = sigma phi(z) / (1 - confidence) of the zero mean normal pnl delta' x
+pub fn delta_normal_var_and_es(delta: &Array1<f64>, covariance: &Array2<f64>, confidence: f64) -> (f64, f64) {
+    let normal = Normal::new(0.0, 1.0).unwrap();
+    let sigma = delta.dot(&covariance.dot(delta)).max(0.0).sqrt();
+    let z = normal.inverse_cdf(confidence);
+    (z * sigma, sigma * normal.pdf(z) / (1.0 - confidence))
+}
+
+/// Cornish-Fisher expansion on the first four cumulants of the delta-gamma pnl
+/// with x ~ N(0, covariance). ES is the average of the expanded quantiles in the tail.
+pub fn cornish_fisher_var(
+    delta: &Array1<f64>,
+    gamma: &Array2<f64>,
+    covariance: &Array2<f64>,
+    confidence: f64,
+) -> Result<(f64, f64)> {
+    let gs = gamma.dot(covariance); // Gamma Sigma
+    let gs2 = gs.dot(&gs);
+    let gs3 = gs2.dot(&gs);
+    let gs4 = gs3.dot(&gs);
+    let sd = covariance.dot(delta); // Sigma delta
+
+    let mean = 0.5 * gs.diag().sum();
+    let variance = delta.dot(&sd) + 0.5 * gs2.diag().sum();
+    if variance <= 0.0 {
+        return Ok((-mean, -mean));
+    }
+    let sigma = variance.sqrt();
+    let skewness = (3.0 * sd.dot(&gamma.dot(&sd)) + gs3.diag().sum()) / sigma.powi(3);
+    let kurtosis = (12.0 * sd.dot(&gamma.dot(&gs.dot(&sd))) + 3.0 * gs4.diag().sum()) / sigma.powi(4);
+
+    let normal = Normal::new(0.0, 1.0).unwrap();
+    let loss_at = |alpha: f64| {
+        let z = normal.inverse_cdf(alpha);
+        let w = z
+            + (z * z - 1.0) * skewness / 6.0
+            + (z.powi(3) - 3.0 * z) * kurtosis / 24.0
+            - (2.0 * z.powi(3) - 5.0 * z) * skewness * skewness / 36.0;
+        -(mean + w * sigma)
+    };
+    let tail = 1.0 - confidence;
+    let var = loss_at(tail);
+    let es = (0..CORNISH_FISHER_ES_POINTS)
+        .map(|i| loss_at(tail * (i as f64 + 0.5) / CORNISH_FISHER_ES_POINTS as f64))
+        .sum::<f64>() / CORNISH_FISHER_ES_POINTS as f64;
+    Ok((var, es))
+}
+
+/// lower triangular L with L L' = matrix. Non-positive pivots (degenerate directions) are set to zero.
+fn cholesky(matrix: &Array2<f64>) -> Array2<f64> {
+    let n = matrix.nrows();
+    let mut lower = Array2::<f64>::zeros((n, n));
+    for j in 0..n {
+        let pivot = matrix[[j, j]] - (0..j).map(|k| lower[[j, k]] * lower[[j, k]]).sum::<f64>();
+        if pivot <= 1.0e-14 {
+            continue;
+        }
+        let pivot = pivot.sqrt();
+        lower[[j, j]] = pivot;
+        for i in (j + 1)..n {
+            let s = matrix[[i, j]] - (0..j).map(|k| lower[[i, k]] * lower[[j, k]]).sum::<f64>();
+            lower[[i, j]] = s / pivot;
+        }
+    }
+    lower
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::daily_value_data::DailyValueData;
+    use crate::time::calendar::Calendar;
+    use ndarray::array;
+    use time::{macros::date, Duration, UtcOffset};
+
+    fn history(values: &[Real], code: &str) -> DailyValueData {
+        let start = date!(2024-01-01);
+        DailyValueData::new(
+            values.iter().enumerate().map(|(i, v)| (start + Duration::days(i as i64), *v)).collect(),
+            time::Time::from_hms(15, 40, 0).unwrap(),
+            UtcOffset::from_hms(9, 0, 0).unwrap(),
+            Calendar::NullCalendar(NullCalendar::new()),
+            code.to_string(),
+            code.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_delta_gamma_var() -> Result<()> {
+        let delta = array![100.0, -50.0];
+        let covariance = array![[0.0004, 0.0001], [0.0001, 0.0009]];
+        let zero_gamma = Array2::<f64>::zeros((2, 2));
+        // Cornish-Fisher is the delta-normal VaR without gamma
+        let (normal_var, normal_es) = delta_normal_var_and_es(&delta, &covariance, 0.99);
+        let (cf_var, cf_es) = cornish_fisher_var(&delta, &zero_gamma, &covariance, 0.99)?;
+        let sigma: f64 = (100.0 * 100.0 * 0.0004 - 2.0 * 100.0 * 50.0 * 0.0001 + 50.0 * 50.0 * 0.0009_f64).sqrt();
+        assert!((normal_var - 2.326348 * sigma).abs() < 1.0e-4);
+        assert!((cf_var - normal_var).abs() < 1.0e-8);
+        assert!((cf_es - normal_es).abs() / normal_es < 1.0e-3);
+
+        // a short gamma position adds to the loss
+        let gamma = array![[-20_000.0, 0.0], [0.0, 0.0]];
+        let (cf_var, _) = cornish_fisher_var(&delta, &gamma, &covariance, 0.99)?;
+        assert!(cf_var > normal_var);
+        let lower = cholesky(&covariance);
+        assert!((lower.dot(&lower.t()) - &covariance).iter().all(|v| v.abs() < 1.0e-12));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ewma_covariance() -> Result<()> {
+        let returns_history = history(&[100.0, 101.0, 100.0, 110.0], "KOSPI2");
+        let equity = RiskFactorHistory::new(
+            RiskFactor::Equity { code: "KOSPI2".to_string() },
+            ShockType::Absolute,
+            returns_history.clone(),
+        )?;
+        assert!(ParametricVar::new(ParametricVarConfiguration::default(), vec![equity]).is_err());
+
+        let fx = RiskFactorHistory::new(RiskFactor::Fx { fx_code: "USDKRW".to_string() }, ShockType::Absolute, returns_history)?;
+        let configuration = ParametricVarConfiguration::default();
+        let (names, _, covariance) = ParametricVar::new(configuration.clone(), vec![fx.clone()])?.covariance()?;
+        assert_eq!(names, vec!["2024-01-02", "2024-01-03", "2024-01-04"]);
+        assert!((covariance[[0, 0]] - 102.0 / 3.0).abs() < 1.0e-6);
+
+        let (_, _, covariance) = ParametricVar::new(configuration.with_ewma_lambda(Some(0.5)), vec![fx])?.covariance()?;
+        // weights 1/7, 2/7, 4/7
+        assert!((covariance[[0, 0]] - (1.0 + 2.0 + 400.0) / 7.0).abs() < 1.0e-6);
+        Ok(())
+    }
+}
@@ -0,0 +1,387 @@
+use crate::currency::{Currency, FxCode};
+use crate::data::vector_data::VectorData;
+use crate::definitions::{
+    Real, Time,
+    DELTA_PNL_UNIT, DIV_PNL_UNIT, RHO_PNL_UNIT, THETA_PNL_UNIT, VEGA_PNL_UNIT,
+};
+use crate::enums::{PnlExplainMethod, RiskClass};
+use crate::instrument::{Instrument, InstrumentTrait};
+use crate::math::interpolator::{ExtraPolationType, InterpolatorReal1D};
+use crate::math::interpolators::linear_interpolator::LinearInterpolator1D;
+use crate::pricing_engines::{
+    calculation_result::CalculationResult,
+    engine_generator::EngineGenerator,
+};
+use crate::time::{calendar_trait::CalendarTrait, calendars::nullcalendar::NullCalendar};
+use crate::utils::string_arithmetic::add_period;
+//
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+/// Attribution of the pnl of an instrument between two evaluation dates in the currency of the instrument.
+/// total = value(t) - value(t-1) + cashflow where cashflow is paid in (t-1, t],
+/// and residual = total - (theta + delta + gamma + cross_gamma + vega + vanna + volga + rho + fx + dividend).
+/// theta includes the cashflow as in Engine::set_theta, so that a coupon payment is not shown as a loss.
+/// cross_gamma, vanna and volga are used if they are calculated in the previous run,
+/// and fx includes fx_delta and fx_gamma on the fx rates used in pricing on top of fx_exposure.
+/// In the waterfall method, the second order terms are zero because each step is a full revaluation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PnlAttribution {
+    pub currency: Currency,
+    pub previous_value: Real,
+    pub current_value: Real,
+    pub cashflow: Real,
+    pub total: Real,
+    pub theta: Real,
+    pub delta: Real,
+    pub gamma: Real,
+    pub cross_gamma: Real,
+    pub vega: Real,
+    pub vanna: Real,
+    pub volga: Real,
+    pub rho: Real,
+    pub fx: Real,
+    pub dividend: Real,
+    pub residual: Real,
+}
+
+impl PnlAttribution {
+    fn set_residual(&mut self) {
+        self.residual = self.total
+            - (self.theta + self.delta + self.gamma + self.cross_gamma + self.vega + self.vanna + self.volga
+                + self.rho + self.fx + self.dividend);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PnlExplainReport {
+    method: PnlExplainMethod,
+    previous_date: OffsetDateTime,
+    current_date: OffsetDateTime,
+    attributions: HashMap<String, PnlAttribution>, // instrument code -> attribution
+}
+
+impl PnlExplainReport {
+    pub fn get_method(&self) -> PnlExplainMethod {
+        self.method
+    }
+
+    pub fn get_previous_date(&self) -> &OffsetDateTime {
+        &self.previous_date
+    }
+
+    pub fn get_current_date(&self) -> &OffsetDateTime {
+        &self.current_date
+    }
+
+    pub fn get_attributions(&self) -> &HashMap<String, PnlAttribution> {
+        &self.attributions
+    }
+
+    pub fn get_attribution(&self, code: &str) -> Option<&PnlAttribution> {
+        self.attributions.get(code)
+    }
+}
+
+/// Explain the pnl between two EngineGenerator runs on t-1 (previous) and t (current).
+/// Both must hold the same instruments and be calculated.
+/// - Sensitivity: the greeks of the previous run (theta, delta, gamma, vega, rho or rho_structure, div_delta, fx_exposure)
+///   are multiplied by the moves of the market data. The curve moves are taken on the rho structure tenors
+///   and the volatility surface moves are averaged over the surface.
+/// - Waterfall: the instruments are revalued on t with the market data of t-1, then the market data are replaced
+///   by those of t in the order of equity, volatility, rates, fx and dividend. Each step is attributed to the factor.
+pub struct PnlExplain {
+    method: PnlExplainMethod,
+}
+
+const WATERFALL_STEPS: [RiskClass; 5] = [
+    RiskClass::Equity,
+    RiskClass::Volatility,
+    RiskClass::Rates,
+    RiskClass::Fx,
+    RiskClass::Dividend,
+];
+
+impl PnlExplain {
+    pub fn new(method: PnlExplainMethod) -> PnlExplain {
+        PnlExplain { method }
+    }
+
+    pub fn calculate(&self, previous: &EngineGenerator, current: &EngineGenerator) -> Result<PnlExplainReport> {
+        let previous_date = previous.get_evaluation_date().get_date_clone();
+        let current_date = current.get_evaluation_date().get_date_clone();
+        if current_date <= previous_date {
+            bail!(
+                "({}:{}) current date {} must be after the previous date {}",
+                file!(), line!(), current_date, previous_date
+            );
+        }
+
+        let mut attributions = HashMap::new();
+        for inst in previous.get_instruments().iter() {
+            let code = inst.get_code();
+            let previous_result = get_result(previous.get_calculation_results(), code, "previous")?;
+            let current_result = get_result(current.get_calculation_results(), code, "current")?;
+            let previous_value = get_value(previous_result, code)?;
+            let current_value = get_value(current_result, code)?;
+            let cashflow = cashflow_inbetween(previous_result, inst.get_unit_notional(), &previous_date, &current_date);
+            let mut attribution = PnlAttribution {
+                currency: *inst.get_currency(),
+                previous_value,
+                current_value,
+                cashflow,
+                total: current_value - previous_value + cashflow,
+                ..Default::default()
+            };
+            if self.method == PnlExplainMethod::Sensitivity {
+                self.sensitivity_attribution(&mut attribution, inst, previous_result, previous, current)?;
+                attribution.set_residual();
+            }
+            attributions.insert(code.clone(), attribution);
+        }
+
+        if self.method == PnlExplainMethod::Waterfall {
+            // t-1 market on t: theta and carry
+            let mut steps = vec![previous.calculate_with_market_data(current, &[])?];
+            for i in 0..WATERFALL_STEPS.len() {
+                steps.push(previous.calculate_with_market_data(current, &WATERFALL_STEPS[..=i])?);
+            }
+            for (code, attribution) in attributions.iter_mut() {
+                let mut values = vec![attribution.previous_value];
+                for step in steps.iter() {
+                    values.push(get_value(get_result(step, code, "waterfall")?, code)?);
+                }
+                attribution.theta = values[1] - values[0] + attribution.cashflow;
+                attribution.delta = values[2] - values[1];
+                attribution.vega = values[3] - values[2];
+                attribution.rho = values[4] - values[3];
+                attribution.fx = values[5] - values[4];
+                attribution.dividend = values[6] - values[5];
+                attribution.set_residual();
+            }
+        }
+
+        Ok(PnlExplainReport {
+            method: self.method,
+            previous_date,
+            current_date,
+            attributions,
+        })
+    }
+
+    fn sensitivity_attribution(
+        &self,
+        attribution: &mut PnlAttribution,
+        inst: &Instrument,
+        result: &CalculationResult,
+        previous: &EngineGenerator,
+        current: &EngineGenerator,
+    ) -> Result<()> {
+        let previous_date = previous.get_evaluation_date().get_date_clone();
+        let current_date = current.get_evaluation_date().get_date_clone();
+        let days = (current_date.date() - previous_date.date()).whole_days() as Real;
+        attribution.theta = result.get_theta().unwrap_or(0.0) * days / THETA_PNL_UNIT;
+
+        if let Some(delta) = result.get_delta() {
+            for (key, value) in delta.iter() {
+                // delta-one instruments (Stock, Futures) keep their delta on the instrument code
+                let underlying = match previous.get_stock_data().contains_key(key) {
+                    true => key,
+                    false => match inst.get_underlying_codes().first() {
+                        Some(code) => *code,
+                        None => continue,
+                    },
+                };
+                let Some(spot_move) = spot_move(previous, current, underlying) else {
+                    continue;
+                };
+                attribution.delta += value * spot_move;
+                let gamma = result.get_gamma().and_then(|gamma| gamma.get(key)).copied().unwrap_or(0.0);
+                attribution.gamma += gamma * spot_move * spot_move;
+            }
+        }
+
+        // the matrix is symmetric, so each pair is counted twice
+        for (code1, row) in result.get_cross_gamma().into_iter().flatten() {
+            for (code2, value) in row.iter() {
+                if let (Some(move1), Some(move2)) = (spot_move(previous, current, code1), spot_move(previous, current, code2)) {
+                    attribution.cross_gamma += 0.5 * value * move1 * move2;
+                }
+            }
+        }
+
+        if let Some(vega) = result.get_vega() {
+            for (code, value) in vega.iter() {
+                attribution.vega += value * volatility_move(previous, current, code) / VEGA_PNL_UNIT;
+            }
+        }
+
+        for (und_code, row) in result.get_vanna().into_iter().flatten() {
+            let Some(spot_move) = spot_move(previous, current, und_code) else {
+                continue;
+            };
+            for (vol_code, value) in row.iter() {
+                attribution.vanna += value * spot_move * volatility_move(previous, current, vol_code) / VEGA_PNL_UNIT;
+            }
+        }
+
+        for (vol_code, value) in result.get_volga().into_iter().flatten() {
+            let vol_move = volatility_move(previous, current, vol_code) / VEGA_PNL_UNIT;
+            attribution.volga += 0.5 * value * vol_move * vol_move;
+        }
+
+        let tenors = previous.get_calculation_configuration().get_rho_structure_tenors();
+        let time_calculator = NullCalendar::default();
+        let tenor_times: Vec<Time> = tenors.iter()
+            .map(|tenor| time_calculator.get_time_difference(&previous_date, &add_period(&previous_date, tenor)))
+            .collect();
+        let curve_moves = |curve_name: &String| -> Result<Option<Vec<Real>>> {
+            match (previous.get_curve_data().get(curve_name), current.get_curve_data().get(curve_name)) {
+                (Some(curve0), Some(curve1)) => {
+                    let (rates0, rates1) = (curve_rates(curve0, &tenor_times)?, curve_rates(curve1, &tenor_times)?);
+                    Ok(Some(rates1.iter().zip(rates0.iter()).map(|(r1, r0)| r1 - r0).collect()))
+                },
+                _ => Ok(None),
+            }
+        };
+        match result.get_rho_structure() {
+            Some(rho_structure) => {
+                for (curve_name, rhos) in rho_structure.iter() {
+                    if let Some(moves) = curve_moves(curve_name)? {
+                        attribution.rho += rhos.iter().zip(moves.iter()).map(|(rho, m)| rho * m).sum::<Real>() / RHO_PNL_UNIT;
+                    }
+                }
+            },
+            None => {
+                for (curve_name, rho) in result.get_rho().into_iter().flatten() {
+                    if let Some(moves) = curve_moves(curve_name)? {
+                        let parallel = moves.iter().sum::<Real>() / moves.len().max(1) as Real;
+                        attribution.rho += rho * parallel / RHO_PNL_UNIT;
+                    }
+                }
+            },
+        }
+
+        if let Some(div_delta) = result.get_div_delta() {
+            for (code, value) in div_delta.iter() {
+                attribution.dividend += value * dividend_move(previous, current, code) / DIV_PNL_UNIT;
+            }
+        }
+
+        // exposures on the other currencies than the instrument currency
+        let currency = *inst.get_currency();
+        for (exposure_currency, exposure) in result.get_fx_exposure().into_iter().flatten() {
+            if *exposure_currency == currency {
+                continue;
+            }
+            let rate0 = previous.get_fx_rate(*exposure_currency, currency)?;
+            let rate1 = current.get_fx_rate(*exposure_currency, currency)?;
+            attribution.fx += exposure * (rate1 - rate0);
+        }
+        // fx rates used in pricing (FxFutures, CRS)
+        for (fx_code, value) in result.get_fx_delta().into_iter().flatten() {
+            let Some(fx_move) = spot_move(previous, current, fx_code) else {
+                continue;
+            };
+            let fx_gamma = result.get_fx_gamma().and_then(|gamma| gamma.get(fx_code)).copied().unwrap_or(0.0);
+            attribution.fx += value * fx_move + fx_gamma * fx_move * fx_move;
+        }
+        Ok(())
+    }
+}
+
+fn get_result<'a>(
+    results: &'a HashMap<String, CalculationResult>,
+    code: &str,
+    run: &str,
+) -> Result<&'a CalculationResult> {
+    results.get(code)
+        .ok_or_else(|| anyhow!("({}:{}) no {} result for {}", file!(), line!(), run, code))
+}
+
+fn get_value(result: &CalculationResult, code: &str) -> Result<Real> {
+    result.get_value()
+        .ok_or_else(|| anyhow!("({}:{}) value is not set for {}", file!(), line!(), code))
+}
+
+/// cashflows paid in (previous_date, current_date] considering unit_notional
+fn cashflow_inbetween(
+    result: &CalculationResult,
+    unit_notional: Real,
+    previous_date: &OffsetDateTime,
+    current_date: &OffsetDateTime,
+) -> Real {
+    result.get_cashflows().into_iter().flatten()
+        .filter(|(date, _)| previous_date.date() < date.date() && date.date() <= current_date.date())
+        .map(|(_, amount)| amount * unit_notional)
+        .sum()
+}
+
+/// relative move of an equity or an fx rate (e.g., "USDKRW") in DELTA_PNL_UNIT
+fn spot_move(previous: &EngineGenerator, current: &EngineGenerator, code: &String) -> Option<Real> {
+    if let (Some(price0), Some(price1)) = (previous.get_stock_data().get(code), current.get_stock_data().get(code)) {
+        return Some((price1.get_value() / price0.get_value() - 1.0) / DELTA_PNL_UNIT);
+    }
+    if code.len() != 6 {
+        return None;
+    }
+    let fx_code = FxCode::from(code.as_str());
+    let (currency1, currency2) = (*fx_code.get_currency1(), *fx_code.get_currency2());
+    if currency1 == Currency::NIL || currency2 == Currency::NIL {
+        return None;
+    }
+    let rate0 = previous.get_fx_rate(currency1, currency2).ok()?;
+    let rate1 = current.get_fx_rate(currency1, currency2).ok()?;
+    Some((rate1 / rate0 - 1.0) / DELTA_PNL_UNIT)
+}
+
+/// change of the constant volatility, or the average change of the volatility surface
+fn volatility_move(previous: &EngineGenerator, current: &EngineGenerator, code: &String) -> Real {
+    if let (Some(vol0), Some(vol1)) = (
+        previous.get_equity_constant_volatility_data().get(code),
+        current.get_equity_constant_volatility_data().get(code),
+    ) {
+        return vol1.get_value() - vol0.get_value();
+    }
+    match (
+        previous.get_equity_volatility_surface_data().get(code),
+        current.get_equity_volatility_surface_data().get(code),
+    ) {
+        (Some(surface0), Some(surface1)) => {
+            surface1.get_value().mean().unwrap_or(0.0) - surface0.get_value().mean().unwrap_or(0.0)
+        },
+        _ => 0.0,
+    }
+}
+
+/// average change of the dividends on the dates in both data
+fn dividend_move(previous: &EngineGenerator, current: &EngineGenerator, code: &String) -> Real {
+    let (Some(dividend0), Some(dividend1)) = (previous.get_dividend_data().get(code), current.get_dividend_data().get(code)) else {
+        return 0.0;
+    };
+    let (Some(dates0), Some(dates1)) = (dividend0.get_dates_clone(), dividend1.get_dates_clone()) else {
+        return 0.0;
+    };
+    let (values0, values1) = (dividend0.get_value_clone(), dividend1.get_value_clone());
+    let moves: Vec<Real> = dates0.iter().zip(values0.iter())
+        .filter_map(|(date, value0)| {
+            dates1.iter().position(|d| d == date).map(|i| values1[i] - value0)
+        })
+        .collect();
+    match moves.is_empty() {
+        true => 0.0,
+        false => moves.iter().sum::<Real>() / moves.len() as Real,
+    }
+}
+
+/// rates of the curve data on the times (linear, flat extrapolation)
+fn curve_rates(curve: &VectorData, times: &[Time]) -> Result<Vec<Real>> {
+    let (domain, rates) = (curve.get_times_clone(), curve.get_value_clone());
+    if domain.len() == 1 {
+        return Ok(vec![rates[0]; times.len()]);
+    }
+    let interpolator = LinearInterpolator1D::new(domain, rates, ExtraPolationType::Flat, true)?;
+    times.iter().map(|t| interpolator.interpolate(*t)).collect()
+}
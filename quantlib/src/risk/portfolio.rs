@@ -0,0 +1,224 @@
+use crate::currency::Currency;
+use crate::definitions::Real;
+use crate::enums::PortfolioGrouping;
+use crate::instrument::{Instrument, InstrumentTrait};
+use crate::pricing_engines::{
+    calculation_result::CalculationResult,
+    engine_generator::EngineGenerator,
+};
+//
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use time::OffsetDateTime;
+
+/// group name used when an instrument has no book, issuer or underlying
+pub const UNASSIGNED_GROUP: &str = "N/A";
+/// group name of the whole portfolio
+pub const TOTAL_GROUP: &str = "Total";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioAggregationConfiguration {
+    currency: Currency,
+    groupings: Vec<PortfolioGrouping>,
+    books: HashMap<String, String>, // instrument code -> book
+}
+
+impl Default for PortfolioAggregationConfiguration {
+    fn default() -> PortfolioAggregationConfiguration {
+        PortfolioAggregationConfiguration {
+            currency: Currency::KRW,
+            groupings: vec![PortfolioGrouping::Total],
+            books: HashMap::new(),
+        }
+    }
+}
+
+impl PortfolioAggregationConfiguration {
+    /// reporting currency into which every result is converted
+    pub fn with_currency(mut self, currency: Currency) -> PortfolioAggregationConfiguration {
+        self.currency = currency;
+        self
+    }
+
+    pub fn with_groupings(mut self, groupings: Vec<PortfolioGrouping>) -> PortfolioAggregationConfiguration {
+        self.groupings = groupings;
+        self
+    }
+
+    /// instrument code -> book. The instruments not in the map are in UNASSIGNED_GROUP
+    pub fn with_books(mut self, books: HashMap<String, String>) -> PortfolioAggregationConfiguration {
+        self.books = books;
+        self
+    }
+
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn get_groupings(&self) -> &Vec<PortfolioGrouping> {
+        &self.groupings
+    }
+
+    pub fn get_books(&self) -> &HashMap<String, String> {
+        &self.books
+    }
+}
+
+/// Sum of the results of the instruments in a group in the reporting currency.
+/// delta is keyed by the underlying code (the delta of Stock and Futures kept on the instrument code is
+/// moved to its underlying), rho_structure by the curve name and vega_structure by the underlying code
+/// on the tenors of the CalculationConfiguration.
+/// fx_exposure is the exposure in each currency converted into the reporting currency.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioAggregate {
+    pub instrument_codes: Vec<String>,
+    pub value: Real,
+    pub delta: HashMap<String, Real>,
+    pub rho_structure: HashMap<String, Vec<Real>>,
+    pub vega_structure: HashMap<String, Vec<Real>>,
+    pub fx_exposure: HashMap<Currency, Real>,
+}
+
+impl PortfolioAggregate {
+    fn add(&mut self, inst: &Instrument, result: &CalculationResult, fx_exposure: &HashMap<Currency, Real>) {
+        self.instrument_codes.push(inst.get_code().clone());
+        self.value += result.get_value().unwrap_or(0.0);
+        for (key, value) in result.get_delta().into_iter().flatten() {
+            let underlying = match inst.get_underlying_codes().first() {
+                Some(code) if key == inst.get_code() => (*code).clone(),
+                _ => key.clone(),
+            };
+            *self.delta.entry(underlying).or_insert(0.0) += value;
+        }
+        for (curve_name, rhos) in result.get_rho_structure().into_iter().flatten() {
+            add_structure(self.rho_structure.entry(curve_name.clone()).or_default(), rhos);
+        }
+        for (und_code, vegas) in result.get_vega_structure().into_iter().flatten() {
+            add_structure(self.vega_structure.entry(und_code.clone()).or_default(), vegas);
+        }
+        for (currency, exposure) in fx_exposure.iter() {
+            *self.fx_exposure.entry(*currency).or_insert(0.0) += exposure;
+        }
+    }
+}
+
+fn add_structure(sum: &mut Vec<Real>, values: &[Real]) {
+    if sum.len() < values.len() {
+        sum.resize(values.len(), 0.0);
+    }
+    for (s, v) in sum.iter_mut().zip(values.iter()) {
+        *s += v;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioReport {
+    evaluation_date: OffsetDateTime,
+    currency: Currency,
+    rho_structure_tenors: Vec<String>,
+    vega_structure_tenors: Vec<String>,
+    total: PortfolioAggregate,
+    groups: HashMap<PortfolioGrouping, BTreeMap<String, PortfolioAggregate>>,
+}
+
+impl PortfolioReport {
+    pub fn get_evaluation_date(&self) -> &OffsetDateTime {
+        &self.evaluation_date
+    }
+
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn get_rho_structure_tenors(&self) -> &Vec<String> {
+        &self.rho_structure_tenors
+    }
+
+    pub fn get_vega_structure_tenors(&self) -> &Vec<String> {
+        &self.vega_structure_tenors
+    }
+
+    pub fn get_total(&self) -> &PortfolioAggregate {
+        &self.total
+    }
+
+    /// group name -> aggregate for the grouping given in the configuration
+    pub fn get_groups(&self, grouping: PortfolioGrouping) -> Option<&BTreeMap<String, PortfolioAggregate>> {
+        self.groups.get(&grouping)
+    }
+
+    pub fn get_group(&self, grouping: PortfolioGrouping, name: &str) -> Option<&PortfolioAggregate> {
+        self.groups.get(&grouping).and_then(|groups| groups.get(name))
+    }
+}
+
+/// Aggregation of the results of an EngineGenerator into a reporting currency
+/// using the fx data given to EngineGenerator::with_data.
+pub struct PortfolioAggregator {
+    configuration: PortfolioAggregationConfiguration,
+}
+
+impl PortfolioAggregator {
+    pub fn new(configuration: PortfolioAggregationConfiguration) -> PortfolioAggregator {
+        PortfolioAggregator { configuration }
+    }
+
+    /// group names of the instrument. An instrument with several underlyings belongs to
+    /// the group joining the underlying codes by "/", so that the value is not counted twice.
+    pub fn group_name(&self, inst: &Instrument, grouping: PortfolioGrouping) -> String {
+        match grouping {
+            PortfolioGrouping::Total => TOTAL_GROUP.to_string(),
+            PortfolioGrouping::Book => self.configuration.books.get(inst.get_code())
+                .cloned()
+                .unwrap_or_else(|| UNASSIGNED_GROUP.to_string()),
+            PortfolioGrouping::InstrumentType => inst.get_type_name().to_string(),
+            PortfolioGrouping::Issuer => inst.get_issuer_name()
+                .cloned()
+                .unwrap_or_else(|_| UNASSIGNED_GROUP.to_string()),
+            PortfolioGrouping::Underlying => {
+                let codes = inst.get_underlying_codes();
+                match codes.is_empty() {
+                    true => UNASSIGNED_GROUP.to_string(),
+                    false => codes.iter().map(|code| code.as_str()).collect::<Vec<&str>>().join("/"),
+                }
+            },
+        }
+    }
+
+    pub fn aggregate(&self, engine_generator: &EngineGenerator) -> Result<PortfolioReport> {
+        let currency = self.configuration.currency;
+        let results = engine_generator.get_calculation_results();
+        let mut total = PortfolioAggregate::default();
+        let mut groups: HashMap<PortfolioGrouping, BTreeMap<String, PortfolioAggregate>> = HashMap::new();
+        for inst in engine_generator.get_instruments().iter() {
+            let code = inst.get_code();
+            let result = results.get(code)
+                .ok_or_else(|| anyhow!("({}:{}) no result for {}", file!(), line!(), code))?;
+            let fx_rate = engine_generator.get_fx_rate(*inst.get_currency(), currency)?;
+            let converted = result.representation_currency_conversion(currency, fx_rate)?;
+            let mut fx_exposure = HashMap::new();
+            for (exposure_currency, exposure) in result.get_fx_exposure().into_iter().flatten() {
+                let rate = engine_generator.get_fx_rate(*exposure_currency, currency)?;
+                fx_exposure.insert(*exposure_currency, exposure * rate);
+            }
+
+            total.add(inst, &converted, &fx_exposure);
+            for grouping in self.configuration.groupings.iter() {
+                groups.entry(*grouping).or_default()
+                    .entry(self.group_name(inst, *grouping)).or_default()
+                    .add(inst, &converted, &fx_exposure);
+            }
+        }
+
+        let configuration = engine_generator.get_calculation_configuration();
+        Ok(PortfolioReport {
+            evaluation_date: engine_generator.get_evaluation_date().get_date_clone(),
+            currency,
+            rho_structure_tenors: configuration.get_rho_structure_tenors().clone(),
+            vega_structure_tenors: configuration.get_vega_structure_tenors().clone(),
+            total,
+            groups,
+        })
+    }
+}
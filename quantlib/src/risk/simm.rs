@@ -0,0 +1,1117 @@
+use crate::currency::{Currency, FxCode};
+use crate::definitions::Real;
+use crate::enums::{SimmProductClass, SimmRiskClass, SimmRiskType};
+use crate::instrument::{Instrument, InstrumentTrait};
+use crate::pricing_engines::{
+    calculation_result::CalculationResult,
+    engine_generator::EngineGenerator,
+};
+use crate::risk::exposure::NettingSet;
+use crate::risk::util::{allocate, correlated_sum, tenor_days, tenor_years};
+use crate::time::{calendar_trait::CalendarTrait, calendars::nullcalendar::NullCalendar};
+//
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use statrs::distribution::{ContinuousCDF, Normal};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// bucket of the qualifiers which are not classified
+pub const RESIDUAL_BUCKET: &str = "Residual";
+/// sub-curve (Label2 of Risk_IRCurve) used when the curve is not in SimmMappingConfiguration
+pub const DEFAULT_SUB_CURVE: &str = "OIS";
+
+const BUNDLED_PARAMETERS: &str = include_str!("simm_parameters.json");
+
+/// risk weights, correlations and concentration thresholds of the interest rate risk class.
+/// The currencies are grouped by volatility (risk weights) and by liquidity (concentration thresholds).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimmInterestRateParameters {
+    pub tenors: Vec<String>,
+    pub risk_weights: HashMap<String, Vec<Real>>, // volatility group -> risk weights on tenors
+    pub volatility_groups: HashMap<String, String>, // currency -> volatility group
+    pub default_volatility_group: String,
+    pub tenor_correlations: Vec<Vec<Real>>,
+    pub sub_curve_correlation: Real,
+    pub inflation_risk_weight: Real,
+    pub inflation_correlation: Real,
+    pub xccy_basis_risk_weight: Real,
+    pub xccy_basis_correlation: Real,
+    pub inter_currency_correlation: Real,
+    pub threshold_groups: HashMap<String, String>, // currency -> concentration threshold group
+    pub default_threshold_group: String,
+    pub delta_concentration_thresholds: HashMap<String, Real>,
+    pub vega_concentration_thresholds: HashMap<String, Real>,
+    pub vega_risk_weight: Real,
+    pub historical_volatility_ratio: Real,
+    pub curvature_scaling: Real,
+}
+
+/// parameters of the risk classes whose qualifiers are grouped in buckets (equity, qualifying credit).
+/// The maps are keyed by the bucket including RESIDUAL_BUCKET, and bucket_correlations are
+/// in the order of buckets. If vega_with_implied_sigma, the vega is multiplied by
+/// sigma = RW sqrt(365 / 14) / Phi^-1(0.99) of the bucket (equity), otherwise the vega in CRIF
+/// already contains the volatility (credit).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimmBucketedParameters {
+    pub tenors: Vec<String>,
+    pub buckets: Vec<String>,
+    pub risk_weights: HashMap<String, Real>,
+    pub same_qualifier_correlations: HashMap<String, Real>,
+    pub different_qualifier_correlations: HashMap<String, Real>,
+    pub bucket_correlations: Vec<Vec<Real>>,
+    pub delta_concentration_thresholds: HashMap<String, Real>,
+    pub vega_concentration_thresholds: HashMap<String, Real>,
+    pub vega_risk_weights: HashMap<String, Real>,
+    pub vega_with_implied_sigma: bool,
+    pub historical_volatility_ratio: Real,
+    pub curvature_scaling: Real,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimmFxParameters {
+    pub risk_weight: Real,
+    pub high_volatility_risk_weight: Real,
+    pub high_volatility_currencies: Vec<String>,
+    pub correlation: Real,
+    pub threshold_groups: HashMap<String, String>, // currency -> concentration threshold group
+    pub default_threshold_group: String,
+    pub delta_concentration_thresholds: HashMap<String, Real>,
+    pub vega_concentration_threshold: Real,
+    pub vega_risk_weight: Real,
+    pub vega_tenors: Vec<String>,
+    pub historical_volatility_ratio: Real,
+    pub curvature_scaling: Real,
+}
+
+/// SIMM parameters of a version. The amounts (AmountUSD in CRIF) and the concentration thresholds
+/// are in calculation_currency. risk_class_correlations are in the order of SimmRiskClass.
+///
+/// SimmParameters::default() is the bundled file (simm_parameters.json), whose values are indicative.
+/// The calibrated ISDA parameters of a version are loaded by from_json_str in the same layout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimmParameters {
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    pub calculation_currency: Currency,
+    pub risk_class_correlations: Vec<Vec<Real>>,
+    pub interest_rate: SimmInterestRateParameters,
+    pub credit_qualifying: SimmBucketedParameters,
+    pub equity: SimmBucketedParameters,
+    pub fx: SimmFxParameters,
+}
+
+impl Default for SimmParameters {
+    fn default() -> SimmParameters {
+        SimmParameters::from_json_str(BUNDLED_PARAMETERS).expect("bundled SIMM parameters are invalid")
+    }
+}
+
+fn check_matrix(matrix: &[Vec<Real>], n: usize, name: &str) -> Result<()> {
+    if matrix.len() != n || matrix.iter().any(|row| row.len() != n) {
+        bail!("({}:{}) {} must be a {} x {} matrix", file!(), line!(), name, n, n);
+    }
+    Ok(())
+}
+
+impl SimmBucketedParameters {
+    fn validate(&self, name: &str) -> Result<()> {
+        check_matrix(&self.bucket_correlations, self.buckets.len(), &format!("{} bucket correlations", name))?;
+        let maps = [
+            ("risk weights", &self.risk_weights),
+            ("same qualifier correlations", &self.same_qualifier_correlations),
+            ("different qualifier correlations", &self.different_qualifier_correlations),
+            ("delta concentration thresholds", &self.delta_concentration_thresholds),
+            ("vega concentration thresholds", &self.vega_concentration_thresholds),
+            ("vega risk weights", &self.vega_risk_weights),
+        ];
+        for bucket in self.buckets.iter().map(|b| b.as_str()).chain([RESIDUAL_BUCKET]) {
+            if let Some((map_name, _)) = maps.iter().find(|(_, map)| !map.contains_key(bucket)) {
+                bail!("({}:{}) {} {} has no value for bucket {}", file!(), line!(), name, map_name, bucket);
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&self, map: &HashMap<String, Real>, bucket: &str) -> f64 {
+        // validated to have all the buckets
+        map.get(bucket).copied().unwrap_or(0.0) as f64
+    }
+}
+
+impl SimmParameters {
+    pub fn from_json_str(json: &str) -> Result<SimmParameters> {
+        let parameters: SimmParameters = serde_json::from_str(json)
+            .with_context(|| anyhow!("({}:{}) failed to parse SIMM parameters", file!(), line!()))?;
+        parameters.validate()?;
+        Ok(parameters)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        check_matrix(&self.risk_class_correlations, 6, "risk class correlations")?;
+        let ir = &self.interest_rate;
+        check_matrix(&ir.tenor_correlations, ir.tenors.len(), "interest rate tenor correlations")?;
+        if let Some((group, _)) = ir.risk_weights.iter().find(|(_, weights)| weights.len() != ir.tenors.len()) {
+            bail!("({}:{}) interest rate risk weights of {} do not match the tenors", file!(), line!(), group);
+        }
+        self.credit_qualifying.validate("credit qualifying")?;
+        self.equity.validate("equity")?;
+        for tenor in ir.tenors.iter()
+            .chain(self.credit_qualifying.tenors.iter())
+            .chain(self.equity.tenors.iter())
+            .chain(self.fx.vega_tenors.iter()) {
+            tenor_days(tenor)?;
+        }
+        Ok(())
+    }
+}
+
+fn tenor_index(tenors: &[String], label: &str) -> Result<usize> {
+    tenors.iter().position(|tenor| tenor.eq_ignore_ascii_case(label.trim()))
+        .ok_or_else(|| anyhow!("({}:{}) tenor {} is not in the SIMM tenors {:?}", file!(), line!(), label, tenors))
+}
+
+/// curvature scaling function SF(t) = 0.5 * min(1, 14 days / t)
+fn scaling_function(label: &str) -> Result<f64> {
+    Ok(0.5 * (14.0 / tenor_days(label)?).min(1.0))
+}
+
+/// sqrt(sum_b K_b^2 + sum_{b != c} gamma_bc S_b S_c) where S_b = max(min(sum_b, K_b), -K_b)
+fn aggregate_buckets(k: &[f64], sums: &[f64], gamma: impl Fn(usize, usize) -> f64) -> f64 {
+    let s: Vec<f64> = k.iter().zip(sums.iter()).map(|(k, sum)| sum.clamp(-k, *k)).collect();
+    let mut total: f64 = k.iter().map(|k| k * k).sum();
+    for b in 0..k.len() {
+        for c in 0..k.len() {
+            if b != c {
+                total += gamma(b, c) * s[b] * s[c];
+            }
+        }
+    }
+    total.max(0.0).sqrt()
+}
+
+/// max(sum CVR + lambda K, 0) where theta = min(sum CVR / sum |CVR|, 0) and
+/// lambda = (Phi^-1(0.995)^2 - 1)(1 + theta) - theta
+fn curvature_margin(cvrs: &[f64], k: f64) -> f64 {
+    let sum: f64 = cvrs.iter().sum();
+    let abs_sum: f64 = cvrs.iter().map(|x| x.abs()).sum();
+    if abs_sum == 0.0 {
+        return 0.0;
+    }
+    let theta = (sum / abs_sum).min(0.0);
+    let z = Normal::new(0.0, 1.0).unwrap().inverse_cdf(0.995);
+    let lambda = (z * z - 1.0) * (1.0 + theta) - theta;
+    (sum + lambda * k).max(0.0)
+}
+
+/// RW sqrt(365 / 14) / Phi^-1(0.99), the volatility implied by the delta risk weight
+fn implied_sigma(risk_weight: f64) -> f64 {
+    risk_weight * (365.0_f64 / 14.0).sqrt() / Normal::new(0.0, 1.0).unwrap().inverse_cdf(0.99)
+}
+
+/// concentration risk factor max(1, sqrt(|sum| / threshold))
+fn concentration(sum: f64, threshold: f64) -> f64 {
+    if threshold <= 0.0 {
+        return 1.0;
+    }
+    (sum.abs() / threshold).sqrt().max(1.0)
+}
+
+fn ratio(a: f64, b: f64) -> f64 {
+    a.min(b) / a.max(b)
+}
+
+fn normalize_bucket(bucket: &str) -> String {
+    let bucket = bucket.trim();
+    if bucket.is_empty() || bucket.eq_ignore_ascii_case(RESIDUAL_BUCKET) {
+        RESIDUAL_BUCKET.to_string()
+    } else {
+        bucket.to_string()
+    }
+}
+
+/// A row of the Common Risk Interchange Format. amount_usd is the amount in the calculation currency.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrifRecord {
+    pub trade_id: String,
+    pub portfolio_id: String,
+    pub product_class: SimmProductClass,
+    pub risk_type: SimmRiskType,
+    pub qualifier: String,
+    pub bucket: String,
+    pub label1: String,
+    pub label2: String,
+    pub amount: Real,
+    pub amount_currency: String,
+    pub amount_usd: Real,
+}
+
+impl CrifRecord {
+    /// Read CRIF text delimited by tabs or commas with the header
+    /// TradeID, PortfolioID, ProductClass, RiskType, Qualifier, Bucket, Label1, Label2, Amount, AmountCurrency, AmountUSD.
+    /// ProductClass, RiskType and AmountUSD are required and the other columns are optional.
+    pub fn read_crif(text: &str) -> Result<Vec<CrifRecord>> {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let header = lines.next()
+            .ok_or_else(|| anyhow!("({}:{}) CRIF has no header", file!(), line!()))?;
+        let delimiter = if header.contains('\t') { '\t' } else { ',' };
+        let columns: Vec<String> = header.split(delimiter).map(|c| c.trim().to_lowercase()).collect();
+        let column = |name: &str| columns.iter().position(|c| c == name);
+        let required = |name: &str| column(&name.to_lowercase())
+            .ok_or_else(|| anyhow!("({}:{}) CRIF has no column {}", file!(), line!(), name));
+        let product_class_index = required("ProductClass")?;
+        let risk_type_index = required("RiskType")?;
+        let amount_usd_index = required("AmountUSD")?;
+        let optional = ["tradeid", "portfolioid", "qualifier", "bucket", "label1", "label2", "amount", "amountcurrency"]
+            .map(column);
+
+        let mut res = Vec::new();
+        for (row, line) in lines.enumerate() {
+            let fields: Vec<&str> = line.split(delimiter).map(|f| f.trim()).collect();
+            let field = |index: Option<usize>| index.and_then(|i| fields.get(i)).copied().unwrap_or("");
+            let parse_enum = |value: &str| serde_json::Value::String(value.to_string());
+            let product_class: SimmProductClass = serde_json::from_value(parse_enum(field(Some(product_class_index))))
+                .map_err(|_| anyhow!(
+                    "({}:{}) unknown ProductClass {} in CRIF row {}",
+                    file!(), line!(), field(Some(product_class_index)), row + 1
+                ))?;
+            let risk_type: SimmRiskType = serde_json::from_value(parse_enum(field(Some(risk_type_index))))
+                .map_err(|_| anyhow!(
+                    "({}:{}) unknown RiskType {} in CRIF row {}",
+                    file!(), line!(), field(Some(risk_type_index)), row + 1
+                ))?;
+            let parse_amount = |value: &str| -> Result<Real> {
+                if value.is_empty() {
+                    return Ok(0.0);
+                }
+                value.parse::<Real>()
+                    .map_err(|_| anyhow!("({}:{}) invalid amount {} in CRIF row {}", file!(), line!(), value, row + 1))
+            };
+            res.push(CrifRecord {
+                trade_id: field(optional[0]).to_string(),
+                portfolio_id: field(optional[1]).to_string(),
+                product_class,
+                risk_type,
+                qualifier: field(optional[2]).to_string(),
+                bucket: field(optional[3]).to_string(),
+                label1: field(optional[4]).to_string(),
+                label2: field(optional[5]).to_string(),
+                amount: parse_amount(field(optional[6]))?,
+                amount_currency: field(optional[7]).to_string(),
+                amount_usd: parse_amount(field(Some(amount_usd_index)))?,
+            });
+        }
+        Ok(res)
+    }
+}
+
+/// margins of a risk class in a product class: total = delta + vega + curvature
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SimmRiskClassMargin {
+    pub delta: Real,
+    pub vega: Real,
+    pub curvature: Real,
+    pub total: Real,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SimmProductClassMargin {
+    pub total: Real,
+    pub risk_classes: BTreeMap<SimmRiskClass, SimmRiskClassMargin>,
+}
+
+/// SIMM of a portfolio (netting set) is the sum of the margins of the product classes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SimmPortfolioMargin {
+    pub total: Real,
+    pub product_classes: BTreeMap<SimmProductClass, SimmProductClassMargin>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimmReport {
+    version: String,
+    calculation_currency: Currency,
+    portfolios: BTreeMap<String, SimmPortfolioMargin>,
+}
+
+impl SimmReport {
+    pub fn get_version(&self) -> &String {
+        &self.version
+    }
+
+    pub fn get_calculation_currency(&self) -> Currency {
+        self.calculation_currency
+    }
+
+    /// portfolio id (netting set name) -> margin
+    pub fn get_portfolios(&self) -> &BTreeMap<String, SimmPortfolioMargin> {
+        &self.portfolios
+    }
+
+    pub fn get_portfolio(&self, portfolio_id: &str) -> Option<&SimmPortfolioMargin> {
+        self.portfolios.get(portfolio_id)
+    }
+
+    pub fn get_total(&self) -> Real {
+        self.portfolios.values().map(|portfolio| portfolio.total).sum()
+    }
+}
+
+/// ISDA SIMM (delta, vega and curvature margins with concentration thresholds) on CRIF sensitivities
+/// for the interest rate, qualifying credit, equity and fx risk classes.
+/// Risk_CreditNonQ, Risk_Commodity, Risk_BaseCorr and the add-ons are not supported.
+pub struct SimmCalculator {
+    parameters: SimmParameters,
+}
+
+impl SimmCalculator {
+    pub fn new(parameters: SimmParameters) -> SimmCalculator {
+        SimmCalculator { parameters }
+    }
+
+    pub fn get_parameters(&self) -> &SimmParameters {
+        &self.parameters
+    }
+
+    pub fn calculate(&self, crif: &[CrifRecord]) -> Result<SimmReport> {
+        // portfolio -> product class -> risk class -> records
+        let mut grouped: BTreeMap<&str, BTreeMap<SimmProductClass, BTreeMap<SimmRiskClass, Vec<&CrifRecord>>>> = BTreeMap::new();
+        for record in crif.iter() {
+            grouped.entry(record.portfolio_id.as_str()).or_default()
+                .entry(record.product_class).or_default()
+                .entry(record.risk_type.get_risk_class()).or_default()
+                .push(record);
+        }
+
+        let psi = &self.parameters.risk_class_correlations;
+        let mut portfolios = BTreeMap::new();
+        for (portfolio_id, product_classes) in grouped.iter() {
+            let mut portfolio = SimmPortfolioMargin::default();
+            for (product_class, risk_classes) in product_classes.iter() {
+                let mut product = SimmProductClassMargin::default();
+                for (risk_class, records) in risk_classes.iter() {
+                    let margin = self.risk_class_margin(*risk_class, records)
+                        .with_context(|| anyhow!(
+                            "({}:{}) failed to calculate {:?} margin of {:?} in portfolio {}",
+                            file!(), line!(), risk_class, product_class, portfolio_id
+                        ))?;
+                    product.risk_classes.insert(*risk_class, margin);
+                }
+                let classes: Vec<(usize, f64)> = product.risk_classes.iter()
+                    .map(|(risk_class, margin)| (*risk_class as usize, margin.total as f64))
+                    .collect();
+                let values: Vec<f64> = classes.iter().map(|(_, im)| *im).collect();
+                product.total = correlated_sum(&values, |i, j| psi[classes[i].0][classes[j].0] as f64) as Real;
+                portfolio.total += product.total;
+                portfolio.product_classes.insert(*product_class, product);
+            }
+            portfolios.insert(portfolio_id.to_string(), portfolio);
+        }
+
+        Ok(SimmReport {
+            version: self.parameters.version.clone(),
+            calculation_currency: self.parameters.calculation_currency,
+            portfolios,
+        })
+    }
+
+    fn risk_class_margin(&self, risk_class: SimmRiskClass, records: &[&CrifRecord]) -> Result<SimmRiskClassMargin> {
+        let (delta, vega, curvature) = match risk_class {
+            SimmRiskClass::InterestRate => self.interest_rate_margins(records)?,
+            SimmRiskClass::CreditQualifying => self.bucketed_margins(
+                &self.parameters.credit_qualifying, SimmRiskType::CreditQ, SimmRiskType::CreditVol, records,
+            )?,
+            SimmRiskClass::Equity => self.bucketed_margins(
+                &self.parameters.equity, SimmRiskType::Equity, SimmRiskType::EquityVol, records,
+            )?,
+            SimmRiskClass::Fx => self.fx_margins(records)?,
+            _ => bail!("({}:{}) SIMM risk class {:?} is not supported", file!(), line!(), risk_class),
+        };
+        Ok(SimmRiskClassMargin {
+            delta: delta as Real,
+            vega: vega as Real,
+            curvature: curvature as Real,
+            total: (delta + vega + curvature) as Real,
+        })
+    }
+
+    /// (delta, vega, curvature) margins of the interest rate risk class where each currency is a bucket
+    fn interest_rate_margins(&self, records: &[&CrifRecord]) -> Result<(f64, f64, f64)> {
+        #[derive(PartialEq)]
+        enum Factor {
+            Curve(usize, String),
+            Inflation,
+            XccyBasis,
+        }
+        let p = &self.parameters.interest_rate;
+        // currency -> (tenor, sub-curve) -> amount, and currency -> amount for inflation and cross currency basis
+        let mut curves: BTreeMap<&str, BTreeMap<(usize, String), f64>> = BTreeMap::new();
+        let mut inflations: BTreeMap<&str, f64> = BTreeMap::new();
+        let mut xccy_bases: BTreeMap<&str, f64> = BTreeMap::new();
+        // currency -> (inflation, tenor) -> vega
+        let mut vegas: BTreeMap<&str, BTreeMap<(bool, usize), f64>> = BTreeMap::new();
+        for record in records.iter() {
+            let currency = record.qualifier.as_str();
+            let amount = record.amount_usd as f64;
+            match record.risk_type {
+                SimmRiskType::IrCurve => {
+                    let key = (tenor_index(&p.tenors, &record.label1)?, record.label2.clone());
+                    *curves.entry(currency).or_default().entry(key).or_insert(0.0) += amount;
+                },
+                SimmRiskType::Inflation => *inflations.entry(currency).or_insert(0.0) += amount,
+                SimmRiskType::XccyBasis => *xccy_bases.entry(currency).or_insert(0.0) += amount,
+                SimmRiskType::IrVol | SimmRiskType::InflationVol => {
+                    let key = (record.risk_type == SimmRiskType::InflationVol, tenor_index(&p.tenors, &record.label1)?);
+                    *vegas.entry(currency).or_default().entry(key).or_insert(0.0) += amount;
+                },
+                _ => bail!("({}:{}) {:?} is not an interest rate risk type", file!(), line!(), record.risk_type),
+            }
+        }
+        let threshold = |currency: &str, thresholds: &HashMap<String, Real>| -> Result<f64> {
+            let group = p.threshold_groups.get(currency).unwrap_or(&p.default_threshold_group);
+            thresholds.get(group).map(|t| *t as f64)
+                .ok_or_else(|| anyhow!("({}:{}) no concentration threshold for {} ({})", file!(), line!(), currency, group))
+        };
+
+        // delta
+        let currencies: BTreeSet<&str> = curves.keys().chain(inflations.keys()).chain(xccy_bases.keys()).copied().collect();
+        let (mut k, mut sums, mut crs) = (Vec::new(), Vec::new(), Vec::new());
+        for currency in currencies.iter() {
+            let group = p.volatility_groups.get(*currency).unwrap_or(&p.default_volatility_group);
+            let risk_weights = p.risk_weights.get(group)
+                .ok_or_else(|| anyhow!("({}:{}) no risk weights for the volatility group {}", file!(), line!(), group))?;
+            let curve = curves.get(currency);
+            let inflation = inflations.get(currency).copied();
+            let net = curve.map(|c| c.values().sum::<f64>()).unwrap_or(0.0) + inflation.unwrap_or(0.0);
+            let cr = concentration(net, threshold(currency, &p.delta_concentration_thresholds)?);
+
+            let mut factors: Vec<(Factor, f64)> = Vec::new();
+            for ((tenor, sub_curve), s) in curve.into_iter().flatten() {
+                factors.push((Factor::Curve(*tenor, sub_curve.clone()), risk_weights[*tenor] as f64 * s * cr));
+            }
+            if let Some(s) = inflation {
+                factors.push((Factor::Inflation, p.inflation_risk_weight as f64 * s * cr));
+            }
+            if let Some(s) = xccy_bases.get(currency) {
+                factors.push((Factor::XccyBasis, p.xccy_basis_risk_weight as f64 * s));
+            }
+            let ws: Vec<f64> = factors.iter().map(|(_, ws)| *ws).collect();
+            k.push(correlated_sum(&ws, |i, j| match (&factors[i].0, &factors[j].0) {
+                (Factor::Curve(t1, c1), Factor::Curve(t2, c2)) => {
+                    let phi = if c1 == c2 { 1.0 } else { p.sub_curve_correlation };
+                    (p.tenor_correlations[*t1][*t2] * phi) as f64
+                },
+                (Factor::XccyBasis, _) | (_, Factor::XccyBasis) => p.xccy_basis_correlation as f64,
+                _ => p.inflation_correlation as f64,
+            }));
+            sums.push(ws.iter().sum());
+            crs.push(cr);
+        }
+        let gamma = p.inter_currency_correlation as f64;
+        let delta = aggregate_buckets(&k, &sums, |b, c| gamma * ratio(crs[b], crs[c]));
+
+        // vega and curvature
+        let (mut vega_k, mut vega_sums, mut curvature_k, mut curvature_sums) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let mut cvrs_all = Vec::new();
+        for (currency, factors) in vegas.iter() {
+            let net: f64 = factors.values().sum();
+            let vcr = concentration(net, threshold(currency, &p.vega_concentration_thresholds)?);
+            let keys: Vec<&(bool, usize)> = factors.keys().collect();
+            let correlation = |i: usize, j: usize| {
+                let ((inf1, t1), (inf2, t2)) = (keys[i], keys[j]);
+                if inf1 == inf2 { p.tenor_correlations[*t1][*t2] as f64 } else { p.inflation_correlation as f64 }
+            };
+            let vr: Vec<f64> = factors.values()
+                .map(|v| (p.historical_volatility_ratio * p.vega_risk_weight) as f64 * v * vcr)
+                .collect();
+            vega_k.push(correlated_sum(&vr, correlation));
+            vega_sums.push(vr.iter().sum());
+
+            let cvr = factors.iter()
+                .map(|((_, tenor), v)| Ok(scaling_function(&p.tenors[*tenor])? * v))
+                .collect::<Result<Vec<f64>>>()?;
+            curvature_k.push(correlated_sum(&cvr, |i, j| correlation(i, j).powi(2)));
+            curvature_sums.push(cvr.iter().sum());
+            cvrs_all.extend(cvr);
+        }
+        let vega = aggregate_buckets(&vega_k, &vega_sums, |_, _| gamma);
+        let curvature_k = aggregate_buckets(&curvature_k, &curvature_sums, |_, _| gamma * gamma);
+        let curvature = curvature_margin(&cvrs_all, curvature_k) * p.curvature_scaling as f64;
+        Ok((delta, vega, curvature))
+    }
+
+    /// (delta, vega, curvature) margins of equity or qualifying credit where the residual bucket
+    /// is added to the aggregation of the other buckets
+    fn bucketed_margins(
+        &self,
+        p: &SimmBucketedParameters,
+        delta_type: SimmRiskType,
+        vega_type: SimmRiskType,
+        records: &[&CrifRecord],
+    ) -> Result<(f64, f64, f64)> {
+        // bucket -> (qualifier, risk factor) -> amount where the risk factor is the tenor and label2
+        let mut deltas: BTreeMap<String, BTreeMap<(String, String), f64>> = BTreeMap::new();
+        // bucket -> (qualifier, tenor) -> vega
+        let mut vegas: BTreeMap<String, BTreeMap<(String, usize), f64>> = BTreeMap::new();
+        for record in records.iter() {
+            let bucket = normalize_bucket(&record.bucket);
+            if bucket != RESIDUAL_BUCKET && !p.buckets.contains(&bucket) {
+                bail!("({}:{}) unknown bucket {} of {}", file!(), line!(), bucket, record.qualifier);
+            }
+            let amount = record.amount_usd as f64;
+            if record.risk_type == delta_type {
+                let tenor = match record.label1.trim().is_empty() {
+                    true => String::new(),
+                    false => p.tenors[tenor_index(&p.tenors, &record.label1)?].clone(),
+                };
+                let key = (record.qualifier.clone(), format!("{}/{}", tenor, record.label2.trim()));
+                *deltas.entry(bucket).or_default().entry(key).or_insert(0.0) += amount;
+            } else if record.risk_type == vega_type {
+                let key = (record.qualifier.clone(), tenor_index(&p.tenors, &record.label1)?);
+                *vegas.entry(bucket).or_default().entry(key).or_insert(0.0) += amount;
+            } else {
+                bail!("({}:{}) unexpected risk type {:?}", file!(), line!(), record.risk_type);
+            }
+        }
+        let bucket_index = |bucket: &str| p.buckets.iter().position(|b| b == bucket);
+        // aggregation of the non-residual buckets plus the residual bucket
+        let aggregate = |buckets: &[&String], k: &[f64], sums: &[f64], square: bool| -> f64 {
+            let (mut k_non, mut s_non, mut index_non, mut k_res) = (Vec::new(), Vec::new(), Vec::new(), 0.0);
+            for ((bucket, k), sum) in buckets.iter().zip(k.iter()).zip(sums.iter()) {
+                match bucket_index(bucket) {
+                    Some(index) => {
+                        k_non.push(*k);
+                        s_non.push(*sum);
+                        index_non.push(index);
+                    },
+                    None => k_res = *k,
+                }
+            }
+            let gamma = |b: usize, c: usize| {
+                let g = p.bucket_correlations[index_non[b]][index_non[c]] as f64;
+                if square { g * g } else { g }
+            };
+            aggregate_buckets(&k_non, &s_non, gamma) + k_res
+        };
+
+        // delta
+        let (mut k, mut sums) = (Vec::new(), Vec::new());
+        let delta_buckets: Vec<&String> = deltas.keys().collect();
+        for (bucket, factors) in deltas.iter() {
+            let threshold = p.get(&p.delta_concentration_thresholds, bucket);
+            let mut nets: HashMap<&String, f64> = HashMap::new();
+            for ((qualifier, _), s) in factors.iter() {
+                *nets.entry(qualifier).or_insert(0.0) += s;
+            }
+            let crs: HashMap<&String, f64> = nets.iter().map(|(q, net)| (*q, concentration(*net, threshold))).collect();
+            let risk_weight = p.get(&p.risk_weights, bucket);
+            let keys: Vec<&String> = factors.keys().map(|(qualifier, _)| qualifier).collect();
+            let ws: Vec<f64> = factors.iter().map(|((q, _), s)| risk_weight * s * crs[q]).collect();
+            let same = p.get(&p.same_qualifier_correlations, bucket);
+            let different = p.get(&p.different_qualifier_correlations, bucket);
+            k.push(correlated_sum(&ws, |i, j| match keys[i] == keys[j] {
+                true => same,
+                false => different * ratio(crs[keys[i]], crs[keys[j]]),
+            }));
+            sums.push(ws.iter().sum());
+        }
+        let delta = aggregate(&delta_buckets, &k, &sums, false);
+
+        // vega and curvature
+        let vega_buckets: Vec<&String> = vegas.keys().collect();
+        let (mut vega_k, mut vega_sums, mut curvature_k, mut curvature_sums) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let (mut cvrs_non, mut cvrs_res) = (Vec::new(), Vec::new());
+        for (bucket, factors) in vegas.iter() {
+            let sigma = match p.vega_with_implied_sigma {
+                true => implied_sigma(p.get(&p.risk_weights, bucket)),
+                false => 1.0,
+            };
+            let threshold = p.get(&p.vega_concentration_thresholds, bucket);
+            let mut nets: HashMap<&String, f64> = HashMap::new();
+            for ((qualifier, _), v) in factors.iter() {
+                *nets.entry(qualifier).or_insert(0.0) += sigma * v;
+            }
+            let vcrs: HashMap<&String, f64> = nets.iter().map(|(q, net)| (*q, concentration(*net, threshold))).collect();
+            let vega_risk_weight = p.historical_volatility_ratio as f64 * p.get(&p.vega_risk_weights, bucket);
+            let keys: Vec<&String> = factors.keys().map(|(qualifier, _)| qualifier).collect();
+            let same = p.get(&p.same_qualifier_correlations, bucket);
+            let different = p.get(&p.different_qualifier_correlations, bucket);
+            let correlation = |i: usize, j: usize| if keys[i] == keys[j] { same } else { different };
+
+            let vr: Vec<f64> = factors.iter().map(|((q, _), v)| vega_risk_weight * sigma * v * vcrs[q]).collect();
+            vega_k.push(correlated_sum(&vr, correlation));
+            vega_sums.push(vr.iter().sum());
+
+            let cvr = factors.iter()
+                .map(|((_, tenor), v)| Ok(scaling_function(&p.tenors[*tenor])? * sigma * v))
+                .collect::<Result<Vec<f64>>>()?;
+            curvature_k.push(correlated_sum(&cvr, |i, j| correlation(i, j).powi(2)));
+            curvature_sums.push(cvr.iter().sum());
+            match bucket.as_str() {
+                RESIDUAL_BUCKET => cvrs_res.extend(cvr),
+                _ => cvrs_non.extend(cvr),
+            }
+        }
+        let vega = aggregate(&vega_buckets, &vega_k, &vega_sums, false);
+        // the residual bucket has its own curvature margin
+        let (mut k_non, mut s_non, mut b_non, mut k_res) = (Vec::new(), Vec::new(), Vec::new(), 0.0);
+        for ((bucket, k), sum) in vega_buckets.iter().zip(curvature_k.iter()).zip(curvature_sums.iter()) {
+            if bucket.as_str() == RESIDUAL_BUCKET {
+                k_res = *k;
+            } else {
+                k_non.push(*k);
+                s_non.push(*sum);
+                b_non.push(*bucket);
+            }
+        }
+        let k_curvature = aggregate(&b_non, &k_non, &s_non, true);
+        let curvature = (curvature_margin(&cvrs_non, k_curvature) + curvature_margin(&cvrs_res, k_res))
+            * p.curvature_scaling as f64;
+        Ok((delta, vega, curvature))
+    }
+
+    /// (delta, vega, curvature) margins of the fx risk class with a single bucket.
+    /// The delta on the calculation currency is not a risk.
+    fn fx_margins(&self, records: &[&CrifRecord]) -> Result<(f64, f64, f64)> {
+        let p = &self.parameters.fx;
+        let calculation_currency = self.parameters.calculation_currency.as_str();
+        let mut deltas: BTreeMap<String, f64> = BTreeMap::new();
+        let mut vegas: BTreeMap<(String, usize), f64> = BTreeMap::new();
+        for record in records.iter() {
+            let qualifier = record.qualifier.trim().to_uppercase();
+            let amount = record.amount_usd as f64;
+            match record.risk_type {
+                SimmRiskType::Fx => {
+                    if qualifier != calculation_currency {
+                        *deltas.entry(qualifier).or_insert(0.0) += amount;
+                    }
+                },
+                SimmRiskType::FxVol => {
+                    let tenor = tenor_index(&p.vega_tenors, &record.label1)?;
+                    *vegas.entry((qualifier, tenor)).or_insert(0.0) += amount;
+                },
+                _ => bail!("({}:{}) {:?} is not an fx risk type", file!(), line!(), record.risk_type),
+            }
+        }
+        let risk_weight = |currency: &str| -> f64 {
+            match p.high_volatility_currencies.iter().any(|c| c == currency) {
+                true => p.high_volatility_risk_weight as f64,
+                false => p.risk_weight as f64,
+            }
+        };
+
+        // delta
+        let mut crs = Vec::new();
+        let mut ws = Vec::new();
+        for (currency, s) in deltas.iter() {
+            let group = p.threshold_groups.get(currency).unwrap_or(&p.default_threshold_group);
+            let threshold = p.delta_concentration_thresholds.get(group)
+                .ok_or_else(|| anyhow!("({}:{}) no fx concentration threshold for {} ({})", file!(), line!(), currency, group))?;
+            let cr = concentration(*s, *threshold as f64);
+            ws.push(risk_weight(currency) * s * cr);
+            crs.push(cr);
+        }
+        let rho = p.correlation as f64;
+        let delta = correlated_sum(&ws, |i, j| rho * ratio(crs[i], crs[j]));
+
+        // vega and curvature on the currency pairs
+        let sigma = |pair: &str| -> f64 {
+            let (c1, c2) = pair.split_at(pair.len().min(3));
+            implied_sigma(risk_weight(c1).max(risk_weight(c2)))
+        };
+        let mut nets: HashMap<&String, f64> = HashMap::new();
+        for ((pair, _), v) in vegas.iter() {
+            *nets.entry(pair).or_insert(0.0) += sigma(pair) * v;
+        }
+        let keys: Vec<&String> = vegas.keys().map(|(pair, _)| pair).collect();
+        let correlation = |i: usize, j: usize| if keys[i] == keys[j] { 1.0 } else { rho };
+        let vega_risk_weight = (p.historical_volatility_ratio * p.vega_risk_weight) as f64;
+        let threshold = p.vega_concentration_threshold as f64;
+        let vr: Vec<f64> = vegas.iter()
+            .map(|((pair, _), v)| vega_risk_weight * sigma(pair) * v * concentration(nets[pair], threshold))
+            .collect();
+        let vega = correlated_sum(&vr, correlation);
+        let cvr = vegas.iter()
+            .map(|((pair, tenor), v)| Ok(scaling_function(&p.vega_tenors[*tenor])? * sigma(pair) * v))
+            .collect::<Result<Vec<f64>>>()?;
+        let k = correlated_sum(&cvr, |i, j| correlation(i, j).powi(2));
+        let curvature = curvature_margin(&cvr, k) * p.curvature_scaling as f64;
+        Ok((delta, vega, curvature))
+    }
+}
+
+/// SIMM qualifier and bucket of a credit curve
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimmCreditMapping {
+    pub qualifier: String,
+    pub bucket: String,
+}
+
+/// Mapping of the results of an EngineGenerator to CRIF.
+/// rho_structure goes to Risk_IRCurve (currency of the curve data, sub-curve from sub_curves) or
+/// to Risk_CreditQ for the curves in credit_curves, delta and vega_structure of equities to
+/// Risk_Equity and Risk_EquityVol (bucket from equity_buckets), the value in a foreign currency and
+/// fx_delta to Risk_FX, and fx_vega to Risk_FXVol. The tenors of the CalculationConfiguration are
+/// allocated linearly to the adjacent SIMM tenors. The dividend sensitivities are not SIMM risks.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SimmMappingConfiguration {
+    sub_curves: HashMap<String, String>,
+    credit_curves: HashMap<String, SimmCreditMapping>,
+    equity_buckets: HashMap<String, String>,
+    product_classes: HashMap<String, SimmProductClass>,
+}
+
+impl SimmMappingConfiguration {
+    /// curve name -> SIMM sub-curve (Label2), e.g., "Libor3m". DEFAULT_SUB_CURVE otherwise
+    pub fn with_sub_curves(mut self, sub_curves: HashMap<String, String>) -> SimmMappingConfiguration {
+        self.sub_curves = sub_curves;
+        self
+    }
+
+    /// curve name -> credit qualifier and bucket. The rho of these curves is credit spread risk
+    pub fn with_credit_curves(mut self, credit_curves: HashMap<String, SimmCreditMapping>) -> SimmMappingConfiguration {
+        self.credit_curves = credit_curves;
+        self
+    }
+
+    /// equity code -> SIMM bucket. RESIDUAL_BUCKET otherwise
+    pub fn with_equity_buckets(mut self, equity_buckets: HashMap<String, String>) -> SimmMappingConfiguration {
+        self.equity_buckets = equity_buckets;
+        self
+    }
+
+    /// instrument code -> product class. Otherwise Equity for the instruments on equities and RatesFX for the others
+    pub fn with_product_classes(mut self, product_classes: HashMap<String, SimmProductClass>) -> SimmMappingConfiguration {
+        self.product_classes = product_classes;
+        self
+    }
+
+    pub fn get_sub_curves(&self) -> &HashMap<String, String> {
+        &self.sub_curves
+    }
+
+    pub fn get_credit_curves(&self) -> &HashMap<String, SimmCreditMapping> {
+        &self.credit_curves
+    }
+
+    pub fn get_equity_buckets(&self) -> &HashMap<String, String> {
+        &self.equity_buckets
+    }
+
+    pub fn get_product_classes(&self) -> &HashMap<String, SimmProductClass> {
+        &self.product_classes
+    }
+}
+
+pub struct SimmCrifMapper {
+    configuration: SimmMappingConfiguration,
+    parameters: SimmParameters,
+}
+
+impl SimmCrifMapper {
+    pub fn new(configuration: SimmMappingConfiguration, parameters: SimmParameters) -> SimmCrifMapper {
+        SimmCrifMapper {
+            configuration,
+            parameters,
+        }
+    }
+
+    fn record(
+        trade_id: &str,
+        portfolio_id: &str,
+        product_class: SimmProductClass,
+        risk_type: SimmRiskType,
+        labels: [&str; 4], // qualifier, bucket, label1, label2
+        amount: f64,
+        (currency, rate): (Currency, Real),
+    ) -> CrifRecord {
+        CrifRecord {
+            trade_id: trade_id.to_string(),
+            portfolio_id: portfolio_id.to_string(),
+            product_class,
+            risk_type,
+            qualifier: labels[0].to_string(),
+            bucket: labels[1].to_string(),
+            label1: labels[2].to_string(),
+            label2: labels[3].to_string(),
+            amount: amount as Real,
+            amount_currency: currency.as_str().to_string(),
+            amount_usd: amount as Real * rate,
+        }
+    }
+
+    /// CRIF of the instruments in the netting sets (PortfolioID = netting set name)
+    pub fn get_crif(&self, engine_generator: &EngineGenerator, netting_sets: &[NettingSet]) -> Result<Vec<CrifRecord>> {
+        let results = engine_generator.get_calculation_results();
+        let configuration = engine_generator.get_calculation_configuration();
+        let rho_tenors = configuration.get_rho_structure_tenors()
+            .iter().map(|tenor| tenor_years(tenor)).collect::<Result<Vec<f64>>>()?;
+        let vega_tenors = configuration.get_vega_structure_tenors()
+            .iter().map(|tenor| tenor_years(tenor)).collect::<Result<Vec<f64>>>()?;
+        let instruments: HashMap<&String, &Instrument> = engine_generator.get_instruments().iter()
+            .map(|inst| (inst.get_code(), inst.as_ref()))
+            .collect();
+        let evaluation_date = engine_generator.get_evaluation_date().get_date_clone();
+
+        let mut res = Vec::new();
+        for netting_set in netting_sets.iter() {
+            for code in netting_set.instrument_codes.iter() {
+                let inst = instruments.get(code)
+                    .ok_or_else(|| anyhow!("({}:{}) {} in netting set {} is not given to the EngineGenerator", file!(), line!(), code, netting_set.name))?;
+                let result = results.get(code)
+                    .ok_or_else(|| anyhow!("({}:{}) no result for {}", file!(), line!(), code))?;
+                let maturity_years = inst.get_maturity()
+                    .map(|maturity| NullCalendar::default().get_time_difference(&evaluation_date, maturity) as f64)
+                    .unwrap_or(0.0);
+                self.map_result(
+                    engine_generator, inst, result, &netting_set.name,
+                    (&rho_tenors, &vega_tenors, maturity_years), &mut res,
+                )?;
+            }
+        }
+        Ok(res)
+    }
+
+    fn map_result(
+        &self,
+        engine_generator: &EngineGenerator,
+        inst: &Instrument,
+        result: &CalculationResult,
+        portfolio_id: &str,
+        (rho_tenors, vega_tenors, maturity_years): (&[f64], &[f64], f64),
+        res: &mut Vec<CrifRecord>,
+    ) -> Result<()> {
+        let code = inst.get_code();
+        let calculation_currency = self.parameters.calculation_currency;
+        let currency = *inst.get_currency();
+        let conversion = (currency, engine_generator.get_fx_rate(currency, calculation_currency)?);
+        let stock_data = engine_generator.get_stock_data();
+        let equity_code = |key: &String| -> Option<String> {
+            let key = match inst.get_underlying_codes().first() {
+                Some(underlying) if key == code => (*underlying).clone(),
+                _ => key.clone(),
+            };
+            stock_data.contains_key(&key).then_some(key)
+        };
+        let is_equity = result.get_delta().into_iter().flatten().any(|(key, _)| equity_code(key).is_some());
+        let product_class = match self.configuration.product_classes.get(code) {
+            Some(product_class) => *product_class,
+            None if is_equity => SimmProductClass::Equity,
+            None => SimmProductClass::RatesFx,
+        };
+        let record = |risk_type: SimmRiskType, labels: [&str; 4], amount: f64| SimmCrifMapper::record(
+            code, portfolio_id, product_class, risk_type, labels, amount, conversion,
+        );
+
+        // rates and credit spreads
+        let ir = &self.parameters.interest_rate;
+        let credit = &self.parameters.credit_qualifying;
+        let mut rho_structure: Vec<(String, Vec<(f64, f64)>)> = Vec::new(); // curve -> (years, rho)
+        match (result.get_rho_structure(), result.get_rho()) {
+            (Some(structure), _) => for (curve_name, rhos) in structure.iter() {
+                let points = rho_tenors.iter().zip(rhos.iter()).map(|(t, rho)| (*t, *rho as f64)).collect();
+                rho_structure.push((curve_name.clone(), points));
+            },
+            (None, Some(rho)) => for (curve_name, value) in rho.iter() {
+                rho_structure.push((curve_name.clone(), vec![(maturity_years, *value as f64)]));
+            },
+            _ => {},
+        }
+        rho_structure.sort_by(|a, b| a.0.cmp(&b.0));
+        for (curve_name, points) in rho_structure.iter() {
+            let credit_mapping = self.configuration.credit_curves.get(curve_name);
+            let simm_tenors = if credit_mapping.is_some() { &credit.tenors } else { &ir.tenors };
+            let mut allocated = vec![0.0; simm_tenors.len()];
+            for (t, rho) in points.iter() {
+                for (index, weight) in allocate(*t, simm_tenors)? {
+                    allocated[index] += weight * rho;
+                }
+            }
+            for (tenor, amount) in simm_tenors.iter().zip(allocated.iter()) {
+                if *amount == 0.0 {
+                    continue;
+                }
+                match credit_mapping {
+                    Some(mapping) => res.push(record(
+                        SimmRiskType::CreditQ, [&mapping.qualifier, &mapping.bucket, tenor, ""], *amount,
+                    )),
+                    None => {
+                        let curve_currency = engine_generator.get_curve_data().get(curve_name)
+                            .map(|data| *data.get_currency())
+                            .unwrap_or(currency);
+                        let sub_curve = self.configuration.sub_curves.get(curve_name)
+                            .map(|s| s.as_str())
+                            .unwrap_or(DEFAULT_SUB_CURVE);
+                        res.push(record(SimmRiskType::IrCurve, [curve_currency.as_str(), "", tenor, sub_curve], *amount));
+                    },
+                }
+            }
+        }
+
+        // equities
+        let mut deltas: BTreeMap<String, f64> = BTreeMap::new();
+        for (key, value) in result.get_delta().into_iter().flatten() {
+            if let Some(equity) = equity_code(key) {
+                *deltas.entry(equity).or_insert(0.0) += *value as f64;
+            }
+        }
+        let bucket = |equity: &String| self.configuration.equity_buckets.get(equity)
+            .cloned()
+            .unwrap_or_else(|| RESIDUAL_BUCKET.to_string());
+        for (equity, amount) in deltas.iter() {
+            res.push(record(SimmRiskType::Equity, [equity, &bucket(equity), "", ""], *amount));
+        }
+        let mut vegas: Vec<(String, Vec<(f64, f64)>)> = Vec::new();
+        match (result.get_vega_structure(), result.get_vega()) {
+            (Some(structure), _) => for (und_code, values) in structure.iter() {
+                let points = vega_tenors.iter().zip(values.iter()).map(|(t, v)| (*t, *v as f64)).collect();
+                vegas.push((und_code.clone(), points));
+            },
+            (None, Some(vega)) => for (und_code, value) in vega.iter() {
+                vegas.push((und_code.clone(), vec![(maturity_years, *value as f64)]));
+            },
+            _ => {},
+        }
+        vegas.sort_by(|a, b| a.0.cmp(&b.0));
+        for (und_code, points) in vegas.iter() {
+            if !stock_data.contains_key(und_code) {
+                continue;
+            }
+            let simm_tenors = &self.parameters.equity.tenors;
+            let mut allocated = vec![0.0; simm_tenors.len()];
+            for (t, v) in points.iter() {
+                for (index, weight) in allocate(*t, simm_tenors)? {
+                    allocated[index] += weight * v;
+                }
+            }
+            for (tenor, amount) in simm_tenors.iter().zip(allocated.iter()) {
+                if *amount != 0.0 {
+                    res.push(record(SimmRiskType::EquityVol, [und_code, &bucket(und_code), tenor, ""], *amount));
+                }
+            }
+        }
+
+        // fx: 1% move of each currency against the calculation currency
+        let mut fx_deltas: BTreeMap<Currency, f64> = BTreeMap::new();
+        if currency != calculation_currency {
+            *fx_deltas.entry(currency).or_insert(0.0) += result.get_value().unwrap_or(0.0) as f64 * 0.01;
+        }
+        for (fx_code, value) in result.get_fx_delta().into_iter().flatten() {
+            let fx_code = FxCode::from(fx_code.as_str());
+            *fx_deltas.entry(*fx_code.get_currency1()).or_insert(0.0) += *value as f64;
+            *fx_deltas.entry(*fx_code.get_currency2()).or_insert(0.0) -= *value as f64;
+        }
+        for (fx_currency, amount) in fx_deltas.iter() {
+            if *fx_currency != calculation_currency && *amount != 0.0 {
+                res.push(record(SimmRiskType::Fx, [fx_currency.as_str(), "", "", ""], *amount));
+            }
+        }
+        let fx_tenors = &self.parameters.fx.vega_tenors;
+        for (fx_code, value) in result.get_fx_vega().into_iter().flatten() {
+            for (index, weight) in allocate(maturity_years, fx_tenors)? {
+                res.push(record(SimmRiskType::FxVol, [fx_code, "", &fx_tenors[index], ""], weight * *value as f64));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crif_line(portfolio: &str, product: &str, risk_type: &str, qualifier: &str, bucket: &str, label1: &str, amount: Real) -> String {
+        format!("T1\t{}\t{}\t{}\t{}\t{}\t{}\t\t{}\tUSD\t{}\n", portfolio, product, risk_type, qualifier, bucket, label1, amount, amount)
+    }
+
+    #[test]
+    fn test_simm_delta_margins() -> Result<()> {
+        let parameters = SimmParameters::default();
+        let header = "TradeID\tPortfolioID\tProductClass\tRiskType\tQualifier\tBucket\tLabel1\tLabel2\tAmount\tAmountCurrency\tAmountUSD\n";
+        let mut text = header.to_string();
+        text.push_str(&crif_line("P1", "RatesFX", "Risk_IRCurve", "USD", "1", "2y", 1000.0));
+        text.push_str(&crif_line("P1", "RatesFX", "Risk_IRCurve", "USD", "1", "5y", -500.0));
+        text.push_str(&crif_line("P1", "RatesFX", "Risk_FX", "EUR", "", "", 1.0e6));
+        text.push_str(&crif_line("P1", "RatesFX", "Risk_FX", "USD", "", "", 5.0e6));
+        text.push_str(&crif_line("P2", "Equity", "Risk_Equity", "KOSPI2", "3", "", 2.0e5));
+        let crif = CrifRecord::read_crif(&text)?;
+        assert_eq!(crif.len(), 5);
+        assert_eq!(crif[0].risk_type, SimmRiskType::IrCurve);
+        assert_eq!(crif[0].label1, "2y");
+
+        let report = SimmCalculator::new(parameters.clone()).calculate(&crif)?;
+        let ir = &parameters.interest_rate;
+        let (rw2, rw5) = (ir.risk_weights["Regular"][5] as f64, ir.risk_weights["Regular"][7] as f64);
+        let rho = ir.tenor_correlations[5][7] as f64;
+        let (ws2, ws5) = (rw2 * 1000.0, -rw5 * 500.0);
+        let expected_ir = (ws2 * ws2 + ws5 * ws5 + 2.0 * rho * ws2 * ws5).sqrt();
+        // the delta on USD (calculation currency) is not a risk
+        let expected_fx = parameters.fx.risk_weight as f64 * 1.0e6;
+        let psi = parameters.risk_class_correlations[0][5] as f64;
+        let expected_rates_fx = (expected_ir.powi(2) + expected_fx.powi(2) + 2.0 * psi * expected_ir * expected_fx).sqrt();
+
+        let p1 = report.get_portfolio("P1").ok_or_else(|| anyhow!("no P1"))?;
+        let rates_fx = &p1.product_classes[&SimmProductClass::RatesFx];
+        let ir_margin = rates_fx.risk_classes[&SimmRiskClass::InterestRate].delta as f64;
+        assert!((ir_margin - expected_ir).abs() < 1e-4 * expected_ir, "{} vs {}", ir_margin, expected_ir);
+        assert!((rates_fx.total as f64 - expected_rates_fx).abs() < 1e-4 * expected_rates_fx);
+
+        let p2 = report.get_portfolio("P2").ok_or_else(|| anyhow!("no P2"))?;
+        let expected_equity = parameters.equity.risk_weights["3"] as f64 * 2.0e5;
+        assert!((p2.total as f64 - expected_equity).abs() < 1e-4 * expected_equity);
+        assert!((report.get_total() - p1.total - p2.total).abs() < 1e-3 * report.get_total());
+        Ok(())
+    }
+
+    #[test]
+    fn test_simm_vega_curvature_and_concentration() -> Result<()> {
+        let parameters = SimmParameters::default();
+        let calculator = SimmCalculator::new(parameters.clone());
+        let record = |risk_type: SimmRiskType, label1: &str, amount: Real| CrifRecord {
+            trade_id: "T1".to_string(),
+            portfolio_id: "P".to_string(),
+            product_class: SimmProductClass::Equity,
+            risk_type,
+            qualifier: "KOSPI2".to_string(),
+            bucket: "3".to_string(),
+            label1: label1.to_string(),
+            label2: String::new(),
+            amount,
+            amount_currency: "USD".to_string(),
+            amount_usd: amount,
+        };
+        let long_vega = calculator.calculate(&[record(SimmRiskType::EquityVol, "1y", 1.0e4)])?;
+        let margin = &long_vega.get_portfolio("P").unwrap().product_classes[&SimmProductClass::Equity]
+            .risk_classes[&SimmRiskClass::Equity];
+        let eq = &parameters.equity;
+        let sigma = implied_sigma(eq.risk_weights["3"] as f64);
+        let expected_vega = (eq.historical_volatility_ratio * eq.vega_risk_weights["3"]) as f64 * sigma * 1.0e4;
+        assert!((margin.vega as f64 - expected_vega).abs() < 1e-4 * expected_vega);
+        // long vega (positive curvature) has a curvature margin, short vega has none
+        assert!(margin.curvature > 0.0);
+        let short_vega = calculator.calculate(&[record(SimmRiskType::EquityVol, "1y", -1.0e4)])?;
+        let short_margin = &short_vega.get_portfolio("P").unwrap().product_classes[&SimmProductClass::Equity]
+            .risk_classes[&SimmRiskClass::Equity];
+        assert_eq!(short_margin.curvature, 0.0);
+        assert!((short_margin.vega - margin.vega).abs() < 1e-3 * margin.vega);
+
+        // the delta margin grows faster than linear above the concentration threshold
+        let threshold = eq.delta_concentration_thresholds["3"];
+        let small = calculator.calculate(&[record(SimmRiskType::Equity, "", threshold)])?.get_total();
+        let large = calculator.calculate(&[record(SimmRiskType::Equity, "", 4.0 * threshold)])?.get_total();
+        assert!((large / small - 8.0).abs() < 1e-3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_simm_tenor_allocation() -> Result<()> {
+        let tenors = SimmParameters::default().interest_rate.tenors;
+        let allocated = allocate(tenor_years("1Y6M")?, &tenors)?;
+        assert_eq!(allocated.len(), 2);
+        assert_eq!(tenors[allocated[0].0], "1y");
+        assert!((allocated[0].1 - 0.5).abs() < 1e-10);
+        assert_eq!(allocate(50.0, &tenors)?, vec![(tenors.len() - 1, 1.0)]);
+        assert!(CrifRecord::read_crif("ProductClass,RiskType,AmountUSD\nRatesFX,Risk_Unknown,1.0").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use anyhow::{anyhow, bail, Result};
+use std::sync::LazyLock;
+
+static TENOR_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"(\d+)(Y|M|W|D)").unwrap());
+
+/// days of a tenor label of the regulatory models, e.g., 2w, 3m, 10y
+pub(crate) fn tenor_days(label: &str) -> Result<f64> {
+    let label = label.trim().to_lowercase();
+    let (number, unit) = label.split_at(label.len().saturating_sub(1));
+    let number: f64 = number.parse()
+        .map_err(|_| anyhow!("({}:{}) invalid tenor {}", file!(), line!(), label))?;
+    match unit {
+        "d" => Ok(number),
+        "w" => Ok(number * 7.0),
+        "m" => Ok(number * 365.0 / 12.0),
+        "y" => Ok(number * 365.0),
+        _ => bail!("({}:{}) invalid tenor {}", file!(), line!(), label),
+    }
+}
+
+/// years of a tenor of the CalculationConfiguration, e.g., "1Y6M" = 1.5
+pub(crate) fn tenor_years(tenor: &str) -> Result<f64> {
+    let mut years = 0.0;
+    for cap in TENOR_REGEX.captures_iter(tenor) {
+        let number: f64 = cap[1].parse()?;
+        years += match &cap[2] {
+            "Y" => number,
+            "M" => number / 12.0,
+            "W" => number * 7.0 / 365.0,
+            _ => number / 365.0,
+        };
+    }
+    if years <= 0.0 {
+        bail!("({}:{}) invalid tenor {}", file!(), line!(), tenor);
+    }
+    Ok(years)
+}
+
+/// sqrt(sum_i sum_j rho_ij x_i x_j) with rho_ii = 1
+pub(crate) fn correlated_sum(values: &[f64], correlation: impl Fn(usize, usize) -> f64) -> f64 {
+    let mut sum = 0.0;
+    for (i, x) in values.iter().enumerate() {
+        for (j, y) in values.iter().enumerate() {
+            let rho = if i == j { 1.0 } else { correlation(i, j) };
+            sum += rho * x * y;
+        }
+    }
+    sum.max(0.0).sqrt()
+}
+
+/// (index, weight) of the tenor labels (e.g., 2w, 1y) adjacent to t, with the weights summing to one
+pub(crate) fn allocate(t: f64, tenors: &[String]) -> Result<Vec<(usize, f64)>> {
+    if tenors.is_empty() || !t.is_finite() {
+        bail!("({}:{}) can not allocate {} to the tenors {:?}", file!(), line!(), t, tenors);
+    }
+    let vertices = tenors.iter().map(|tenor| Ok(tenor_days(tenor)? / 365.0)).collect::<Result<Vec<f64>>>()?;
+    let last = vertices.len() - 1;
+    if t <= vertices[0] {
+        return Ok(vec![(0, 1.0)]);
+    }
+    if t >= vertices[last] {
+        return Ok(vec![(last, 1.0)]);
+    }
+    let upper = vertices.iter().position(|v| *v >= t).unwrap_or(last);
+    let weight = (vertices[upper] - t) / (vertices[upper] - vertices[upper - 1]);
+    Ok(vec![(upper - 1, weight), (upper, 1.0 - weight)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenors() -> Result<()> {
+        assert_eq!(tenor_days("2w")?, 14.0);
+        assert!((tenor_years("1Y6M")? - 1.5).abs() < 1e-12);
+        assert!(tenor_years("abc").is_err());
+        assert!(tenor_days("1x").is_err());
+        assert!(allocate(1.0, &[]).is_err());
+        assert!(allocate(f64::NAN, &["1y".to_string()]).is_err());
+        Ok(())
+    }
+}
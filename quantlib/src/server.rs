@@ -0,0 +1,422 @@
+use crate::data::{
+    market_data_snapshot::MarketDataSnapshot,
+    market_data_store::MarketDataStore,
+};
+use crate::instrument::InstrumentTrait;
+use crate::instruments::trade_loader::load_trades_from_json_value;
+use crate::pricing_engines::{
+    calculation_configuration::CalculationConfiguration,
+    calculation_result::CalculationResult,
+    engine_generator::{EngineGenerator, InstrumentCategory},
+    error_report::ErrorReport,
+    match_parameter::MatchParameter,
+    run_configuration::{deserialize_datetime, serialize_datetime},
+};
+//
+use anyhow::{anyhow, Context, Result};
+use axum::{
+    extract::{DefaultBodyLimit, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use time::OffsetDateTime;
+use tracing::info;
+
+/// Snapshots loaded to the server by name. The snapshots are shared by the pricing requests.
+/// The stores are configured at start, and the snapshots are loaded only from them by name.
+#[derive(Debug, Default)]
+pub struct PricingServerState {
+    snapshots: RwLock<HashMap<String, Arc<MarketDataSnapshot>>>,
+    stores: HashMap<String, MarketDataStore>,
+}
+
+impl PricingServerState {
+    pub fn new() -> PricingServerState {
+        PricingServerState::default()
+    }
+
+    /// adds a store that POST /snapshots/{name}/load can read by the store name
+    pub fn with_store(mut self, name: &str, store: MarketDataStore) -> PricingServerState {
+        self.stores.insert(name.to_string(), store);
+        self
+    }
+
+    pub fn get_store(&self, name: &str) -> Option<&MarketDataStore> {
+        self.stores.get(name)
+    }
+
+    pub fn insert_snapshot(&self, name: &str, snapshot: MarketDataSnapshot) {
+        let mut snapshots = self.snapshots.write().unwrap_or_else(|e| e.into_inner());
+        snapshots.insert(name.to_string(), Arc::new(snapshot));
+    }
+
+    pub fn get_snapshot(&self, name: &str) -> Option<Arc<MarketDataSnapshot>> {
+        let snapshots = self.snapshots.read().unwrap_or_else(|e| e.into_inner());
+        snapshots.get(name).cloned()
+    }
+
+    pub fn remove_snapshot(&self, name: &str) -> Option<Arc<MarketDataSnapshot>> {
+        let mut snapshots = self.snapshots.write().unwrap_or_else(|e| e.into_inner());
+        snapshots.remove(name)
+    }
+
+    /// (name, as_of) sorted by name
+    pub fn get_snapshot_list(&self) -> Vec<SnapshotInfo> {
+        let snapshots = self.snapshots.read().unwrap_or_else(|e| e.into_inner());
+        let mut res = snapshots.iter()
+            .map(|(name, snapshot)| SnapshotInfo { name: name.clone(), as_of: *snapshot.get_as_of() })
+            .collect::<Vec<_>>();
+        res.sort_by(|a, b| a.name.cmp(&b.name));
+        res
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub name: String,
+    #[serde(serialize_with = "serialize_datetime", deserialize_with = "deserialize_datetime")]
+    pub as_of: OffsetDateTime,
+}
+
+/// Body of POST /snapshots/{name}/load: the snapshot as of as_of in the store of the name configured at start.
+/// Missing store means DEFAULT_STORE.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadSnapshotRequest {
+    #[serde(default)]
+    pub store: Option<String>,
+    #[serde(serialize_with = "serialize_datetime", deserialize_with = "deserialize_datetime")]
+    pub as_of: OffsetDateTime,
+}
+
+/// Body of POST /price. The instruments are trades as in the trade files (see instruments::trade_loader),
+/// priced with the snapshot of the name. Missing instrument_categories means one category for all the instruments.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PricingRequest {
+    pub snapshot: String,
+    #[serde(serialize_with = "serialize_datetime", deserialize_with = "deserialize_datetime")]
+    pub evaluation_datetime: OffsetDateTime,
+    #[serde(default)]
+    pub calculation_configuration: CalculationConfiguration,
+    #[serde(default)]
+    pub match_parameter: MatchParameter,
+    #[serde(default)]
+    pub instrument_categories: Vec<InstrumentCategory>,
+    pub instruments: serde_json::Value,
+    /// see EngineGenerator::with_continue_on_error
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PricingResponse {
+    pub results: HashMap<String, CalculationResult>,
+    /// instruments without results
+    pub missing: Vec<String>,
+    /// failures with continue_on_error
+    #[serde(default)]
+    pub errors: ErrorReport,
+}
+
+/// an error response: {"error": "..."} with the status
+#[derive(Debug)]
+pub struct ServerError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ServerError {
+    fn new(status: StatusCode, message: String) -> ServerError {
+        ServerError { status, message }
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        let body = Json(serde_json::json!({ "error": self.message }));
+        (self.status, body).into_response()
+    }
+}
+
+/// Prices the instruments with the snapshot. This is synchronous and runs in the rayon pool,
+/// where EngineGenerator::calculate distributes the instrument groups.
+pub fn price(request: PricingRequest, snapshot: &MarketDataSnapshot) -> Result<PricingResponse> {
+    let instruments = load_trades_from_json_value(request.instruments)
+        .with_context(|| anyhow!("({}:{}) invalid instruments", file!(), line!()))?;
+    let instrument_categories = match request.instrument_categories.is_empty() {
+        true => vec![InstrumentCategory::default()],
+        false => request.instrument_categories,
+    };
+    let mut engine_generator = EngineGenerator::builder();
+    engine_generator
+        .with_configuration(request.calculation_configuration, request.evaluation_datetime, request.match_parameter)?
+        .with_instruments(instruments)?
+        .with_instrument_categories(instrument_categories)?
+        .with_market_data_snapshot(snapshot.clone())?
+        .with_continue_on_error(request.continue_on_error)?;
+    engine_generator.distribute_instruments()
+        .with_context(|| anyhow!("({}:{}) failed to distribute the instruments", file!(), line!()))?;
+    engine_generator.calculate()
+        .with_context(|| anyhow!("({}:{}) failed to calculate", file!(), line!()))?;
+
+    let results = engine_generator.get_calculation_results().clone();
+    let mut missing = engine_generator.get_instruments().iter()
+        .map(|inst| inst.get_code().clone())
+        .filter(|code| !results.contains_key(code))
+        .collect::<Vec<_>>();
+    missing.sort();
+    let errors = engine_generator.get_error_report().clone();
+    Ok(PricingResponse { results, missing, errors })
+}
+
+/// runs f in the rayon pool and waits for it without blocking the tokio workers
+async fn spawn_rayon<T, F>(f: F) -> std::result::Result<T, ServerError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    rayon::spawn(move || {
+        // the receiver is gone only if the request was dropped
+        let _ = sender.send(f());
+    });
+    receiver.await.map_err(|e| ServerError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("pricing task failed: {}", e)))
+}
+
+async fn health() -> &'static str {
+    "ok"
+}
+
+async fn list_snapshots(State(state): State<Arc<PricingServerState>>) -> Json<Vec<SnapshotInfo>> {
+    Json(state.get_snapshot_list())
+}
+
+async fn put_snapshot(
+    State(state): State<Arc<PricingServerState>>,
+    Path(name): Path<String>,
+    body: String,
+) -> std::result::Result<Json<SnapshotInfo>, ServerError> {
+    // large snapshots are parsed off the tokio workers
+    let snapshot = spawn_rayon(move || serde_json::from_str::<MarketDataSnapshot>(&body)).await?
+        .map_err(|e| ServerError::new(StatusCode::BAD_REQUEST, format!("invalid snapshot: {}", e)))?;
+    let info = SnapshotInfo { name: name.clone(), as_of: *snapshot.get_as_of() };
+    state.insert_snapshot(&name, snapshot);
+    info!("snapshot {} as of {} is loaded", name, info.as_of);
+    Ok(Json(info))
+}
+
+async fn load_snapshot(
+    State(state): State<Arc<PricingServerState>>,
+    Path(name): Path<String>,
+    Json(request): Json<LoadSnapshotRequest>,
+) -> std::result::Result<Json<SnapshotInfo>, ServerError> {
+    let store_name = request.store.as_deref().unwrap_or(DEFAULT_STORE);
+    let store = state.get_store(store_name)
+        .ok_or_else(|| ServerError::new(StatusCode::NOT_FOUND, format!("no store {}", store_name)))?
+        .clone();
+    let snapshot = spawn_rayon(move || store.load_as_of(request.as_of)).await?
+        .map_err(|e| ServerError::new(StatusCode::NOT_FOUND, format!("{:#}", e)))?;
+    let info = SnapshotInfo { name: name.clone(), as_of: *snapshot.get_as_of() };
+    state.insert_snapshot(&name, snapshot);
+    info!("snapshot {} as of {} is loaded from a store", name, info.as_of);
+    Ok(Json(info))
+}
+
+async fn delete_snapshot(
+    State(state): State<Arc<PricingServerState>>,
+    Path(name): Path<String>,
+) -> std::result::Result<StatusCode, ServerError> {
+    match state.remove_snapshot(&name) {
+        Some(_) => Ok(StatusCode::NO_CONTENT),
+        None => Err(ServerError::new(StatusCode::NOT_FOUND, format!("no snapshot {}", name))),
+    }
+}
+
+async fn price_instruments(
+    State(state): State<Arc<PricingServerState>>,
+    body: String,
+) -> std::result::Result<Response, ServerError> {
+    let request: PricingRequest = serde_json::from_str(&body)
+        .map_err(|e| ServerError::new(StatusCode::BAD_REQUEST, format!("invalid pricing request: {}", e)))?;
+    let snapshot = state.get_snapshot(&request.snapshot)
+        .ok_or_else(|| ServerError::new(StatusCode::NOT_FOUND, format!("no snapshot {}", request.snapshot)))?;
+    // the instruments (Rc) live only in the rayon task, and the response goes back as json
+    let response = spawn_rayon(move || -> Result<String> {
+        let response = price(request, &snapshot)?;
+        serde_json::to_string(&response)
+            .with_context(|| anyhow!("({}:{}) failed to serialize the results", file!(), line!()))
+    }).await?
+        .map_err(|e| ServerError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", e)))?;
+    Ok(([(axum::http::header::CONTENT_TYPE, "application/json")], response).into_response())
+}
+
+pub const DEFAULT_STORE: &str = "default";
+
+pub const MAX_BODY_BYTES: usize = 256 * 1024 * 1024;
+
+/// - GET /health
+/// - GET /snapshots: the loaded snapshots
+/// - PUT /snapshots/{name}: a MarketDataSnapshot in the body
+/// - POST /snapshots/{name}/load: LoadSnapshotRequest
+/// - DELETE /snapshots/{name}
+/// - POST /price: PricingRequest to PricingResponse
+pub fn router(state: Arc<PricingServerState>) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/snapshots", get(list_snapshots))
+        .route("/snapshots/{name}", put(put_snapshot).delete(delete_snapshot))
+        .route("/snapshots/{name}/load", post(load_snapshot))
+        .route("/price", post(price_instruments))
+        // snapshots with surfaces and curves are larger than the default limit (2MB)
+        .layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
+        .with_state(state)
+}
+
+pub async fn serve(listener: tokio::net::TcpListener, state: Arc<PricingServerState>) -> Result<()> {
+    info!("pricing server listening on {}", listener.local_addr()?);
+    axum::serve(listener, router(state)).await
+        .with_context(|| anyhow!("({}:{}) pricing server failed", file!(), line!()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::Currency;
+    use crate::data::{value_data::ValueData, vector_data::VectorData};
+    use crate::instrument::{Instrument, Instruments};
+    use crate::instruments::{futures::Futures, trade_loader::{write_trades_to_string, TradeFileFormat}};
+    use ndarray::Array1;
+    use std::rc::Rc;
+    use time::macros::datetime;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// (status, body) of a request over a new connection
+    async fn request(address: std::net::SocketAddr, method: &str, path: &str, body: &str) -> Result<(u16, String)> {
+        let mut stream = tokio::net::TcpStream::connect(address).await?;
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method, path, body.len(), body
+        );
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        let (head, body) = response.split_once("\r\n\r\n").ok_or_else(|| anyhow!("invalid response {}", response))?;
+        let status = head.split_whitespace().nth(1).ok_or_else(|| anyhow!("no status in {}", head))?.parse()?;
+        Ok((status, body.to_string()))
+    }
+
+    fn snapshot() -> Result<MarketDataSnapshot> {
+        let as_of = datetime!(2024-01-02 15:40:00 +09:00);
+        let stock = ValueData::new(350.0, Some(as_of), Currency::KRW, "KOSPI2".to_string(), "KOSPI2".to_string())?;
+        let mut curves = HashMap::new();
+        for code in ["KSD", "KOSPI2"] {
+            let data = VectorData::new(
+                Array1::from(vec![0.0345, 0.0345]),
+                Some(vec![datetime!(2025-01-02 16:30:00 +09:00), datetime!(2026-01-02 16:30:00 +09:00)]),
+                None,
+                Some(as_of),
+                Currency::KRW,
+                code.to_string(),
+                code.to_string(),
+            )?;
+            curves.insert(code.to_string(), data);
+        }
+        Ok(MarketDataSnapshot::new(as_of)
+            .with_stock_data(HashMap::from([("KOSPI2".to_string(), stock)]))
+            .with_curve_data(curves))
+    }
+
+    fn instruments_json(code: &str) -> Result<String> {
+        let futures = Futures::new(
+            320.0,
+            datetime!(2023-09-15 09:00:00 +09:00),
+            datetime!(2024-03-14 15:20:00 +09:00),
+            datetime!(2024-03-14 15:20:00 +09:00),
+            datetime!(2024-03-14 15:20:00 +09:00),
+            250_000.0,
+            Currency::KRW,
+            Currency::KRW,
+            "KOSPI2".to_string(),
+            "KOSPI2 Fut Mar24".to_string(),
+            code.to_string(),
+        );
+        let instruments = Instruments::new(vec![Rc::new(Instrument::Futures(futures))]);
+        write_trades_to_string(&instruments, TradeFileFormat::Json)
+    }
+
+    fn pricing_request(snapshot: &str, code: &str) -> Result<String> {
+        Ok(format!(r#"{{
+            "snapshot": "{}",
+            "evaluation_datetime": "2024-01-02T16:30:00+09:00",
+            "calculation_configuration": {{ "npv": true, "delta": true }},
+            "match_parameter": {{
+                "collateral_curve_map": {{ "KOSPI2": "KSD" }},
+                "borrowing_curve_map": {{ "KOSPI2": "KOSPI2" }}
+            }},
+            "instruments": {}
+        }}"#, snapshot, instruments_json(code)?))
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_pricing_server() -> Result<()> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let root = std::env::temp_dir().join(format!("quantlib-server-store-{}", std::process::id()));
+        MarketDataStore::new(&root).save(&snapshot()?)?;
+        let state = PricingServerState::new().with_store(DEFAULT_STORE, MarketDataStore::new(&root));
+        let server = tokio::spawn(serve(listener, Arc::new(state)));
+
+        assert_eq!(request(address, "GET", "/health", "").await?, (200, "ok".to_string()));
+
+        let (status, body) = request(address, "PUT", "/snapshots/eod", &serde_json::to_string(&snapshot()?)?).await?;
+        assert_eq!(status, 200, "{}", body);
+        let info: SnapshotInfo = serde_json::from_str(&body)?;
+        assert_eq!(info.as_of, datetime!(2024-01-02 15:40:00 +09:00));
+        let (_, body) = request(address, "GET", "/snapshots", "").await?;
+        assert_eq!(serde_json::from_str::<Vec<SnapshotInfo>>(&body)?, vec![info]);
+
+        // requests are priced concurrently
+        let first = pricing_request("eod", "165XXX")?;
+        let second = pricing_request("eod", "165YYY")?;
+        let (first, second) = tokio::join!(
+            request(address, "POST", "/price", &first),
+            request(address, "POST", "/price", &second),
+        );
+        for ((status, body), code) in [(first?, "165XXX"), (second?, "165YYY")] {
+            assert_eq!(status, 200, "{}", body);
+            let response: PricingResponse = serde_json::from_str(&body)?;
+            assert!(response.missing.is_empty());
+            let npv = response.results[code].get_npv_result().expect("npv").get_npv();
+            assert!(npv > 320.0 && npv < 360.0, "npv: {}", npv);
+        }
+
+        let (status, body) = request(address, "POST", "/price", &pricing_request("intraday", "165XXX")?).await?;
+        assert_eq!(status, 404);
+        assert!(body.contains("no snapshot intraday"));
+        let (status, body) = request(address, "POST", "/price", r#"{"snapshot": "eod"}"#).await?;
+        assert_eq!(status, 400, "{}", body);
+
+        let load = r#"{"as_of": "2024-01-03T09:00:00+09:00"}"#;
+        let (status, body) = request(address, "POST", "/snapshots/stored/load", load).await?;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(serde_json::from_str::<SnapshotInfo>(&body)?.as_of, datetime!(2024-01-02 15:40:00 +09:00));
+        // only the configured stores are read, never a path from the client
+        let load = serde_json::to_string(&LoadSnapshotRequest {
+            store: Some(std::env::temp_dir().display().to_string()),
+            as_of: datetime!(2024-01-03 09:00:00 +09:00),
+        })?;
+        let (status, body) = request(address, "POST", "/snapshots/other/load", &load).await?;
+        assert_eq!(status, 404, "{}", body);
+        assert!(body.contains("no store"));
+        std::fs::remove_dir_all(&root)?;
+
+        assert_eq!(request(address, "DELETE", "/snapshots/eod", "").await?.0, 204);
+        assert_eq!(request(address, "DELETE", "/snapshots/eod", "").await?.0, 404);
+        server.abort();
+        Ok(())
+    }
+}
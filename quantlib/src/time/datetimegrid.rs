@@ -0,0 +1,112 @@
+use crate::definitions::Time;
+use crate::time::{
+    calendar_trait::CalendarTrait,
+    calendars::nullcalendar::NullCalendar,
+};
+use crate::utils::string_arithmetic::add_period;
+//
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// Sorted datetimes after a base datetime (e.g., the evaluation date) with the times from the base datetime.
+/// The times are measured by NullCalendar as the other parameters (ZeroCurve, Volatility).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DateTimeGrid {
+    base_datetime: OffsetDateTime,
+    datetimes: Vec<OffsetDateTime>,
+    times: Vec<Time>,
+}
+
+impl DateTimeGrid {
+    /// datetimes are sorted and deduplicated. They must be after base_datetime
+    pub fn new(base_datetime: OffsetDateTime, mut datetimes: Vec<OffsetDateTime>) -> Result<DateTimeGrid> {
+        if datetimes.is_empty() {
+            bail!("({}:{}) datetimes of DateTimeGrid must not be empty", file!(), line!());
+        }
+        datetimes.sort();
+        datetimes.dedup();
+        if datetimes[0] <= base_datetime {
+            bail!(
+                "({}:{}) datetimes of DateTimeGrid must be after the base datetime {}, got {}",
+                file!(), line!(), base_datetime, datetimes[0]
+            );
+        }
+        let time_calculator = NullCalendar::default();
+        let times = datetimes.iter()
+            .map(|dt| time_calculator.get_time_difference(&base_datetime, dt))
+            .collect();
+        Ok(DateTimeGrid {
+            base_datetime,
+            datetimes,
+            times,
+        })
+    }
+
+    /// base_datetime + tenor for each tenor, e.g., ["1M", "3M", "6M", "1Y"]
+    pub fn from_tenors(base_datetime: OffsetDateTime, tenors: &[String]) -> Result<DateTimeGrid> {
+        let re = regex::Regex::new(r"^(\d+(Y|M|W|D))+$").unwrap();
+        if let Some(tenor) = tenors.iter().find(|tenor| !re.is_match(tenor)) {
+            bail!("({}:{}) invalid tenor {} in DateTimeGrid (e.g., 3M, 1Y6M)", file!(), line!(), tenor);
+        }
+        let datetimes = tenors.iter().map(|tenor| add_period(&base_datetime, tenor)).collect();
+        DateTimeGrid::new(base_datetime, datetimes)
+    }
+
+    pub fn get_base_datetime(&self) -> &OffsetDateTime {
+        &self.base_datetime
+    }
+
+    pub fn get_datetimes(&self) -> &Vec<OffsetDateTime> {
+        &self.datetimes
+    }
+
+    pub fn get_times(&self) -> &Vec<Time> {
+        &self.times
+    }
+
+    /// times[i] - times[i-1] where times[-1] = 0.0
+    pub fn get_time_steps(&self) -> Vec<Time> {
+        let mut previous = 0.0;
+        self.times.iter()
+            .map(|t| {
+                let step = t - previous;
+                previous = *t;
+                step
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.datetimes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.datetimes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_datetime_grid_from_tenors() -> Result<()> {
+        let base = datetime!(2024-01-02 16:30:00 +09:00);
+        let tenors = vec!["6M".to_string(), "1M".to_string(), "1Y".to_string(), "1M".to_string()];
+        let grid = DateTimeGrid::from_tenors(base, &tenors)?;
+        assert_eq!(grid.len(), 3);
+        assert_eq!(grid.get_datetimes()[0], datetime!(2024-02-02 16:30:00 +09:00));
+        assert_eq!(grid.get_datetimes()[2], datetime!(2025-01-02 16:30:00 +09:00));
+
+        let steps = grid.get_time_steps();
+        let sum: Time = steps.iter().sum();
+        assert!((sum - grid.get_times()[2]).abs() < 1e-5);
+        assert!(steps.iter().all(|step| *step > 0.0));
+
+        assert!(DateTimeGrid::new(base, vec![base]).is_err());
+        assert!(DateTimeGrid::from_tenors(base, &["1X".to_string()]).is_err());
+        Ok(())
+    }
+}
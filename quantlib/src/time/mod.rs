@@ -3,6 +3,7 @@ pub mod conventions;
 pub mod jointcalendar;
 pub mod calendar;
 pub mod calendar_trait;
+pub mod datetimegrid;
 pub mod calendars {
     pub mod southkorea;
     pub mod unitedstates;
@@ -12,9 +12,12 @@ mod tests {
         vanilla_option::VanillaOption,
         stock::Stock,
         cash::Cash,
+        fx_futures::FxFutures,
+        trade_loader::{load_trades_from_str, write_trades_to_string, TradeFileFormat},
     };
     use quantlib::instrument::{
         Instrument,
+        InstrumentTrait,
         Instruments,
     };
     use quantlib::definitions::Real;
@@ -23,6 +26,48 @@ mod tests {
         calculation_result::CalculationResult,
     };
     use quantlib::pricing_engines::match_parameter::MatchParameter;
+    use quantlib::pricing_engines::scenario::Scenario;
+    use quantlib::pricing_engines::engine::Engine;
+    use quantlib::pricing_engines::market_data_update::MarketDataUpdate;
+    use quantlib::risk::cva::{CreditCurve, CvaCalculator};
+    use quantlib::risk::exposure::{
+        CollateralAgreement,
+        ExposureConfiguration,
+        ExposureSimulation,
+        HullWhiteParameters,
+        NettingSet,
+    };
+    use quantlib::risk::historical_var::{
+        HistoricalVar,
+        HistoricalVarConfiguration,
+        HistoricalVarReport,
+        RiskFactor,
+        RiskFactorHistory,
+    };
+    use quantlib::risk::pnl_explain::PnlExplain;
+    use quantlib::risk::portfolio::{
+        PortfolioAggregationConfiguration,
+        PortfolioAggregator,
+        UNASSIGNED_GROUP,
+    };
+    use quantlib::risk::parametric_var::{
+        ParametricVar,
+        ParametricVarConfiguration,
+    };
+    use quantlib::risk::frtb::{
+        FrtbCalculator,
+        FrtbMappingConfiguration,
+        FrtbParameters,
+        FrtbSensitivityMapper,
+    };
+    use quantlib::risk::simm::{
+        SimmCalculator,
+        SimmCrifMapper,
+        SimmMappingConfiguration,
+        SimmParameters,
+    };
+    use quantlib::data::daily_value_data::DailyValueData;
+    use quantlib::enums::{PnlExplainMethod, PortfolioGrouping, ShockType};
     use std::collections::HashMap;
     use quantlib::pricing_engines::{
         engine_generator::{
@@ -47,336 +92,285 @@ mod tests {
     use tracing_subscriber::layer::SubscriberExt;
     use tracing_appender::rolling;
     use tracing_appender::non_blocking;
-    use time::{macros::datetime, Duration};
+    use time::{macros::datetime, Duration, OffsetDateTime};
     use ndarray::array;
-    use ndarray::Array1;
     use std::time::Instant;
     use std::rc::Rc;
-    use std::fs::write;
-
-    #[test]
-    fn test_engine()-> Result<()> {
-        let theta_day = 100;
-        let start_time = Instant::now();
-        // Set up rolling file appender
-        let file_appender = rolling::daily("test_logs", "engine-test.log");
-        let (non_blocking_appender, _guard) = non_blocking(file_appender);
+    use std::sync::Arc;
 
-        // Set up console layer
-        let custom_time = CustomOffsetTime::new(9, 0, 0);
+    const SPOT: Real = 350.0;
 
-        let err_layer = fmt::layer()
-            .with_writer(std::io::stderr.with_max_level(Level::ERROR))
-            .with_timer(custom_time.clone());
-
-        let console_layer = fmt::layer()
-            .with_writer(
-                std::io::stdout.with_max_level(Level::DEBUG))
-            .with_timer(custom_time.clone());
-
-        let file_layer = fmt::layer()
-            .with_writer(non_blocking_appender.with_max_level(Level::DEBUG).with_min_level(Level::INFO))
-            .with_timer(custom_time);
-
-        // Combine console and file layers into a subscriber
-        let subscriber = tracing_subscriber::registry()
-            .with(file_layer)
-            .with(err_layer)
-            .with(console_layer);
-
-        tracing::subscriber::set_global_default(subscriber)
-            .expect("Setting default subscriber failed");
-
-        // Create a new span with an `info` level
-        let main_span = span!(Level::INFO, "main (engine)");
-        let _enter = main_span.enter();
+    fn evaluation_datetime() -> OffsetDateTime {
+        datetime!(2024-03-13 16:30:00 +09:00)
+    }
 
-        let spot: Real = 350.0;
-        // evaluation date = 2021-01-01 00:00:00 +09:00
-        let dt = datetime!(2024-03-13 16:30:00 +09:00);
+    /// the market data of the tests at evaluation_datetime
+    #[derive(Clone)]
+    struct MarketData {
+        fx_data_map: HashMap<FxCode, ValueData>,
+        stock_data_map: HashMap<String, ValueData>,
+        zero_curve_map: HashMap<String, VectorData>,
+        dividend_data_map: HashMap<String, VectorData>,
+        equity_vol_map: HashMap<String, ValueData>,
+        fx_vol_map: HashMap<FxCode, ValueData>,
+        quanto_correlation_map: HashMap<(String, FxCode), ValueData>,
+    }
 
-        // make zero curve named "KSD". First make vector data whose values are 0.03 and 0.04
-        // then make it as hash map whose key is "KSD"
-        let value = array![0.03358, 0.03358];
-        let dates = vec![
-            datetime!(2025-03-13 00:00:00 +09:00),
-            datetime!(2026-03-13 00:00:00 +09:00),
-        ];
+    /// the instruments of a test with their curve mapping and market data
+    struct Fixture {
+        instruments: Vec<Rc<Instrument>>,
+        match_parameter: MatchParameter,
+        instrument_categories: Vec<InstrumentCategory>,
+        market_data: MarketData,
+    }
 
-        let times = None;
-        let market_datetime = dt.clone();
-        let zero_curve1 = "KSD".to_string();
-        let zero_curve_data1 = VectorData::new(
-            &value - 0.0005, 
-            Some(dates.clone()), 
-            times.clone(), 
-            Some(market_datetime), 
-            Currency::KRW,
-            zero_curve1.clone(),
-            zero_curve1.clone(),
-        ).expect("Failed to create VectorData for KSD");
-
-        let zero_curve2 = "KRWGOV".to_string();
-        let zero_curve_data2 = VectorData::new(
-            value,
-            Some(dates.clone()), 
-            times, 
-            Some(market_datetime), 
-            Currency::KRW,
-            zero_curve2.clone(),
-            zero_curve2.clone(),
-        ).expect("Failed to create VectorData for KRWGOV");
-
-        let funding_curve1 = "Discount(KRW)".to_string();
-        let funding_curve_data1 = VectorData::new(
-            array![0.04, 0.04],
-            Some(dates.clone()),
-            None,
-            Some(market_datetime),
-            Currency::KRW,
-            funding_curve1.clone(),
-            funding_curve1.clone(),
-        ).expect("failed to make a vector data for funding curve");
-
-        // the borrowing fee curve which amounts to 0.005
-        let bor_curve_name = "KOSPI2".to_string();
-        let borrowing_curve_data = VectorData::new(
-            array![0.005, 0.005],
-            Some(dates.clone()),
-            None,
-            Some(market_datetime),
-            Currency::KRW,
-            bor_curve_name.clone(),
-            bor_curve_name.clone(),
-        ).expect("failed to make a vector data for borrowing fee");
-
-        //
-        // mapping construction
-        let mut zero_curve_map = HashMap::new();
-        zero_curve_map.insert(zero_curve1, zero_curve_data1);
-        zero_curve_map.insert(zero_curve2, zero_curve_data2);
-        zero_curve_map.insert("KOSPI2".to_string(), borrowing_curve_data);
-        zero_curve_map.insert(funding_curve1.clone(), funding_curve_data1);
+    impl Fixture {
+        /// only the instruments of the codes
+        fn with_instruments(mut self, codes: &[&str]) -> Fixture {
+            self.instruments.retain(|inst| codes.contains(&inst.get_code().as_str()));
+            self
+        }
 
-        
-        let mut equity_vol_map = HashMap::new();
-        let equity_surface_map = HashMap::new();
+        /// the engine generator before distribute_instruments
+        fn engine_generator(&self, calculation_configuration: CalculationConfiguration, dt: OffsetDateTime) -> Result<EngineGenerator> {
+            let market_data = self.market_data.clone();
+            let mut engine_generator = EngineGenerator::builder();
+            engine_generator
+                .with_configuration(calculation_configuration, dt, self.match_parameter.clone())?
+                .with_instruments(Instruments::new(self.instruments.clone()))?
+                .with_instrument_categories(self.instrument_categories.clone())?
+                .with_data(
+                    market_data.fx_data_map,
+                    market_data.stock_data_map,
+                    market_data.zero_curve_map,
+                    market_data.dividend_data_map,
+                    market_data.equity_vol_map,
+                    HashMap::new(),
+                    market_data.fx_vol_map,
+                    market_data.quanto_correlation_map,
+                    HashMap::new(),
+                )?;
+            Ok(engine_generator)
+        }
 
-        //let _equity_surface_data = surfacedatasample!(&market_datetime, spot);
-        let equity_constant_vol1 = ValueData::new(
-            0.2,
-            Some(market_datetime),
-            Currency::KRW,
-            "KOSPI2".to_string(),
-            "KOSPI2".to_string(),
-        ).expect("failed to make a value data for equity volatility");
+        /// the engine generator calculated on evaluation_datetime
+        fn calculate(&self, calculation_configuration: CalculationConfiguration) -> Result<EngineGenerator> {
+            let mut engine_generator = self.engine_generator(calculation_configuration, evaluation_datetime())?;
+            engine_generator.distribute_instruments()?;
+            engine_generator.calculate()?;
+            Ok(engine_generator)
+        }
+    }
 
-        
-        //equity_surface_map.insert("KOSPI2".to_string(), equity_surface_data);
-        equity_vol_map.insert("KOSPI2".to_string(), equity_constant_vol1);
-
-        let fx_str1 = "USDKRW";
-        let fx_code1 = FxCode::from(fx_str1);
-        let fx1 = ValueData::new(
-            1300.0,
-            Some(market_datetime),
-            Currency::KRW,
-            fx_str1.to_string(),
-            fx_str1.to_string(),
-        ).expect("failed to make a value data for fx rate");
-        let mut fx_data_map = HashMap::new();
-        
-        fx_data_map.insert(fx_code1, fx1);
+    fn value_data(value: Real, currency: Currency, code: &str) -> Result<ValueData> {
+        ValueData::new(value, Some(evaluation_datetime()), currency, code.to_string(), code.to_string())
+    }
 
-        
-        // make a vector data for dividend ratio
-        let div_name = "KOSPI2".to_string();
-        let dividend_data = VectorData::new(
-            Array1::from(vec![3.0, 3.0]),
-            Some(vec![datetime!(2024-06-01 00:00:00 +09:00), datetime!(2025-01-01 00:00:00 +09:00)]),
+    fn flat_curve(rate: Real, currency: Currency, code: &str) -> Result<VectorData> {
+        VectorData::new(
+            array![rate, rate],
+            Some(vec![datetime!(2025-03-13 00:00:00 +09:00), datetime!(2026-03-13 00:00:00 +09:00)]),
             None,
-            Some(market_datetime),
-            Currency::KRW,
-            div_name.clone(),
-            div_name.clone(),
-        ).expect("failed to make a vector data for dividend ratio");
-
-        let mut dividend_data_map = HashMap::new();
-        dividend_data_map.insert("KOSPI2".to_string(), dividend_data.clone());
-        
-        // make a stock data
-        let stock_name = "KOSPI2".to_string();
-        let stock_data = ValueData::new(
-            spot,
-            Some(market_datetime),
-            Currency::KRW,
-            stock_name.clone(),
-            stock_name.clone(),
-        ).expect("failed to make a stock data");
+            Some(evaluation_datetime()),
+            currency,
+            code.to_string(),
+            code.to_string(),
+        )
+    }
 
-        let mut stock_data_map = HashMap::new();
-        stock_data_map.insert("KOSPI2".to_string(), stock_data.clone());
-        
-        // make two stock futures of two maturities with the same other specs
-        // then make a Instruments object with the two stock futures
-        let stock_futures1 = Futures::new(
+    fn kospi2_futures(code: &str, maturity: OffsetDateTime) -> Instrument {
+        Instrument::Futures(Futures::new(
             350.0,
             datetime!(2021-01-01 00:00:00 +09:00),
-            datetime!(2021-01-11 00:00:00 +09:00),
-            datetime!(2024-06-14 00:00:00 +09:00),
-            datetime!(2024-06-14 00:00:00 +09:00),
+            datetime!(2021-01-01 00:00:00 +09:00),
+            maturity,
+            maturity,
             250_000.0,
             Currency::KRW,
             Currency::KRW,
             "KOSPI2".to_string(),
-            "KOSPI2 Fut Mar21".to_string(),
-            "165XXX1".to_string(),
-        );
+            code.to_string(),
+            code.to_string(),
+        ))
+    }
 
-        let stock_futures2 = Futures::new(
-            350.0,
-            datetime!(2021-01-01 00:00:00 +09:00),
-            datetime!(2021-01-01 00:00:00 +09:00),
-            datetime!(2025-06-14 00:00:00 +09:00),
-            datetime!(2025-06-14 00:00:00 +09:00),
+    /// KOSPI2 put of Sep24, which is a quanto if currency is not KRW
+    fn kospi2_put(code: &str, strike: Real, currency: Currency) -> Instrument {
+        Instrument::VanillaOption(VanillaOption::new(
+            strike,
             250_000.0,
+            datetime!(2021-01-01 00:00:00 +09:00),
+            datetime!(2024-09-13 00:00:00 +09:00),
+            datetime!(2024-09-13 00:00:00 +09:00),
+            datetime!(2024-09-13 00:00:00 +09:00),
+            vec![String::from("KOSPI2")],
             Currency::KRW,
-            Currency::KRW,
-            "KOSPI2".to_string(),
-            "KOSPI2 Fut Jun21".to_string(),
-            "165XXX2".to_string(),
-        );
-
-        let issuedate = datetime!(2020-01-01 16:30:00 +09:00);
-        let maturity = issuedate + Duration::days(365 * 6);
-        let issuer_name = "Korea Gov";
-        let bond_name = "Virtual KTB";
-        let bond_code = "KRxxxxxxxxxx";
-        let sk = Calendar::SouthKorea(SouthKorea::new(SouthKoreaType::Settlement));
-        let calendar = JointCalendar::new(vec![sk])?;
-
-        let bond_currency = Currency::KRW;
-        let issuer_type = IssuerType::Government;
-        let credit_rating = CreditRating::None;
+            currency,
+            OptionType::Put,
+            OptionExerciseType::European,
+            OptionDailySettlementType::NotSettled,
+            code.to_string(),
+            code.to_string(),
+        ))
+    }
 
+    /// KTB paying semiannual coupons
+    fn ktb(code: &str, issue_date: OffsetDateTime, maturity: OffsetDateTime, coupon: Real) -> Result<Instrument> {
+        let calendar = JointCalendar::new(vec![Calendar::SouthKorea(SouthKorea::new(SouthKoreaType::Settlement))])?;
         let bond = Bond::new_from_conventions(
-            issuer_type,
-            credit_rating,     
-            issuer_name.to_string(), 
-            RankType::Senior, 
-            bond_currency,
+            IssuerType::Government,
+            CreditRating::None,
+            "Korea Gov".to_string(),
+            RankType::Senior,
+            Currency::KRW,
             10_000.0,
-            false, 
-            issuedate.clone(), 
-            issuedate.clone(),
+            false,
+            issue_date,
+            issue_date,
             None,
             maturity,
-            Some(0.03),
-            None, 
+            Some(coupon),
             None,
             None,
-            calendar,
-            true,
-            DayCountConvention::StreetConvention,
-            BusinessDayConvention::Unadjusted, 
-            PaymentFrequency::SemiAnnually, 
-            0, 
-            0,
-            bond_name.to_string(), 
-            bond_code.to_string(),
-        )?;
-
-        let issuedate2 = datetime!(2022-12-10 16:30:00 +09:00);
-        let maturity2 = datetime!(2025-12-10 16:30:00 +09:00);
-        let issuer_name2 = "Korea Gov";
-        let bond_name2 = "국고채권 04250-2512(22-13)";
-        let bond_code2 = "KR103501GCC0";
-        let sk = Calendar::SouthKorea(SouthKorea::new(SouthKoreaType::Settlement));
-        let calendar = JointCalendar::new(vec![sk])?;
-
-        let bond_currency2 = Currency::KRW;
-        let issuer_type2 = IssuerType::Government;
-        let credit_rating2 = CreditRating::None;
-
-        let bond2 = Bond::new_from_conventions(
-            issuer_type2,
-            credit_rating2,     
-            issuer_name2.to_string(), 
-            RankType::Senior, 
-            bond_currency2,
-            10_000.0, 
-            false, 
-            issuedate2.clone(), 
-            issuedate2.clone(),
-            None,
-            maturity2,
-            Some(0.0425),
-            None,
-            None, 
             None,
             calendar,
             true,
             DayCountConvention::StreetConvention,
-            BusinessDayConvention::Unadjusted, 
-            PaymentFrequency::SemiAnnually, 
-            0, 
+            BusinessDayConvention::Unadjusted,
+            PaymentFrequency::SemiAnnually,
+            0,
             0,
-            bond_name2.to_string(), 
-            bond_code2.to_string(),
+            code.to_string(),
+            code.to_string(),
         )?;
+        Ok(Instrument::Bond(bond))
+    }
 
-        // option
-        let option1 = VanillaOption::new(
-            285.0,
-            250_000.0,
-            datetime!(2021-01-01 00:00:00 +09:00),
+    fn match_parameter(funding_cost_map: HashMap<Currency, String>) -> MatchParameter {
+        MatchParameter::new(
+            HashMap::from([("KOSPI2".to_string(), "KSD".to_string())]),
+            HashMap::from([("KOSPI2".to_string(), "KOSPI2".to_string())]),
+            HashMap::from([(
+                ("Korea Gov".to_string(), IssuerType::Government, CreditRating::None, Currency::KRW),
+                "KRWGOV".to_string(),
+            )]),
+            HashMap::from([(Currency::KRW, "KRWCRS".to_string()), (Currency::USD, "USDOIS".to_string())]),
+            HashMap::new(),
+            funding_cost_map,
+        )
+    }
+
+    /// the instruments and the market data of test_engine
+    fn fixture() -> Result<Fixture> {
+        let market_data = MarketData {
+            fx_data_map: HashMap::from([(FxCode::from("USDKRW"), value_data(1300.0, Currency::KRW, "USDKRW")?)]),
+            stock_data_map: HashMap::from([("KOSPI2".to_string(), value_data(SPOT, Currency::KRW, "KOSPI2")?)]),
+            zero_curve_map: HashMap::from([
+                ("KSD".to_string(), flat_curve(0.03358 - 0.0005, Currency::KRW, "KSD")?),
+                ("KRWGOV".to_string(), flat_curve(0.03358, Currency::KRW, "KRWGOV")?),
+                // borrowing fee
+                ("KOSPI2".to_string(), flat_curve(0.005, Currency::KRW, "KOSPI2")?),
+                ("Discount(KRW)".to_string(), flat_curve(0.04, Currency::KRW, "Discount(KRW)")?),
+            ]),
+            dividend_data_map: HashMap::from([(
+                "KOSPI2".to_string(),
+                VectorData::new(
+                    array![3.0, 3.0],
+                    Some(vec![datetime!(2024-06-01 00:00:00 +09:00), datetime!(2025-01-01 00:00:00 +09:00)]),
+                    None,
+                    Some(evaluation_datetime()),
+                    Currency::KRW,
+                    "KOSPI2".to_string(),
+                    "KOSPI2".to_string(),
+                )?,
+            )]),
+            equity_vol_map: HashMap::from([("KOSPI2".to_string(), value_data(0.2, Currency::KRW, "KOSPI2")?)]),
+            fx_vol_map: HashMap::new(),
+            quanto_correlation_map: HashMap::new(),
+        };
+        let instruments = vec![
+            Rc::new(kospi2_futures("165XXX1", datetime!(2024-06-14 00:00:00 +09:00))),
+            Rc::new(kospi2_futures("165XXX2", datetime!(2025-06-14 00:00:00 +09:00))),
+            Rc::new(ktb(
+                "KRxxxxxxxxxx",
+                datetime!(2020-01-01 16:30:00 +09:00),
+                datetime!(2020-01-01 16:30:00 +09:00) + Duration::days(365 * 6),
+                0.03,
+            )?),
+            Rc::new(ktb(
+                "KR103501GCC0",
+                datetime!(2022-12-10 16:30:00 +09:00),
+                datetime!(2025-12-10 16:30:00 +09:00),
+                0.0425,
+            )?),
+            Rc::new(kospi2_put("165XXX3", 285.0, Currency::KRW)),
+            Rc::new(Instrument::Cash(Cash::new(Currency::USD, "USD Cash".to_string(), "USD Cash".to_string()))),
+            Rc::new(Instrument::Stock(Stock::new(
+                "KOSPI2".to_string(),
+                "KOSPI2".to_string(),
+                vec!["KOSPI2".to_string()],
+                Currency::KRW,
+                None,
+            ))),
+        ];
+        let instrument_categories = vec![
+            InstrumentCategory::new(
+                Some(vec!["Futures".to_string(), "VanillaCall".to_string(), "VanillaPut".to_string()]),
+                Some(vec![Currency::KRW]),
+                Some(vec!["KOSPI2".to_string()]),
+            ),
+            InstrumentCategory::new(
+                Some(vec!["Bond".to_string(), "Cash".to_string(), "Stock".to_string()]),
+                Some(vec![Currency::KRW, Currency::USD]),
+                Some(vec!["KOSPI2".to_string()]),
+            ),
+        ];
+        Ok(Fixture {
+            instruments,
+            match_parameter: match_parameter(HashMap::from([(Currency::KRW, "Discount(KRW)".to_string())])),
+            instrument_categories,
+            market_data,
+        })
+    }
+
+    /// USDKRW futures and an at-the-money KOSPI2 put paid in USD, with the curves of the crs curve map
+    /// and the USD funding curve which the other tests do not need
+    fn fx_fixture() -> Result<Fixture> {
+        let mut market_data = fixture()?.market_data;
+        market_data.zero_curve_map.insert("KRWCRS".to_string(), flat_curve(0.034, Currency::KRW, "KRWCRS")?);
+        market_data.zero_curve_map.insert("USDOIS".to_string(), flat_curve(0.053, Currency::USD, "USDOIS")?);
+        let krwusd = FxCode::new(Currency::KRW, Currency::USD);
+        market_data.fx_vol_map.insert(krwusd.clone(), value_data(0.2, Currency::USD, "KRWUSD")?);
+        market_data.quanto_correlation_map.insert(("KOSPI2".to_string(), krwusd), value_data(0.2, Currency::USD, "KOSPI2-KRWUSD")?);
+        let fx_futures = FxFutures::new(
+            1_310.0,
+            datetime!(2024-01-02 00:00:00 +09:00),
             datetime!(2024-09-13 00:00:00 +09:00),
             datetime!(2024-09-13 00:00:00 +09:00),
             datetime!(2024-09-13 00:00:00 +09:00),
-            vec![String::from("KOSPI2")],
-            Currency::KRW,
-            Currency::KRW,
-            OptionType::Put,
-            OptionExerciseType::European,
-            OptionDailySettlementType::NotSettled,
-            "KOSPI2 Call Sep21".to_string(),
-            "165XXX3".to_string(),
-        );
-
-        let cash = Cash::new(
-            Currency::USD, 
-            "USD Cash".to_string(),
-            "USD Cash".to_string(),
-        );
-
-        let stock = Stock::new(
-            "KOSPI2".to_string(),
-            "KOSPI2".to_string(),
-            vec!["KOSPI2".to_string()],
+            10_000.0,
             Currency::KRW,
-            None,
+            Currency::USD,
+            "USDKRW Sep24".to_string(),
+            "USDKRW Sep24".to_string(),
         );
+        Ok(Fixture {
+            instruments: vec![
+                Rc::new(Instrument::FxFutures(fx_futures)),
+                Rc::new(kospi2_put("165QXX3", 350.0, Currency::USD)),
+            ],
+            match_parameter: match_parameter(HashMap::from([
+                (Currency::KRW, "Discount(KRW)".to_string()),
+                (Currency::USD, "USDOIS".to_string()),
+            ])),
+            instrument_categories: vec![InstrumentCategory::default()],
+            market_data,
+        })
+    }
 
-        let inst1 = Instrument::Futures(stock_futures1);
-        let inst2 = Instrument::Futures(stock_futures2);
-        let inst3: Instrument = Instrument::Bond(bond);
-        let inst4: Instrument = Instrument::Bond(bond2);
-        let inst5 = Instrument::VanillaOption(option1);
-        let inst6 = Instrument::Cash(cash);
-        let inst7 = Instrument::Stock(stock);
-
-        let inst_vec = vec![
-            Rc::new(inst1),
-            Rc::new(inst2),
-            Rc::new(inst3),
-            Rc::new(inst4),
-            Rc::new(inst5),
-            Rc::new(inst6),
-            Rc::new(inst7),
-        ];
-        
-        // make a calculation configuration
-        let calculation_configuration = CalculationConfiguration::default()
+    /// npv and the greeks of the instruments without the scenarios
+    fn greeks_configuration() -> CalculationConfiguration {
+        CalculationConfiguration::default()
             .with_delta_calculation(true)
             .with_gamma_calculation(true)
             .with_theta_calculation(true)
@@ -387,87 +381,213 @@ mod tests {
             .with_rho_structure_calculation(true)
             .with_div_structure_calculation(true)
             .with_vega_matrix_calculation(true)
-            .with_theta_day(theta_day);
-            
-        // make a match parameter
-        let mut collateral_curve_map = HashMap::new();
-        collateral_curve_map.insert(String::from("KOSPI2"), String::from("KSD"));
+            .with_cross_gamma_calculation(true)
+            .with_vanna_calculation(true)
+            .with_volga_calculation(true)
+            .with_fx_delta_calculation(true)
+            .with_fx_gamma_calculation(true)
+            .with_theta_day(100)
+    }
 
-        let mut borrowing_curve_map = HashMap::new();
-        borrowing_curve_map.insert(String::from("KOSPI2"), String::from("KOSPI2"));
+    /// the same trades uncollateralized and collateralized, where the option matures before 6M
+    fn netting_sets() -> Vec<NettingSet> {
+        let netting_codes = vec![
+            "165XXX1".to_string(),
+            "165XXX3".to_string(),
+        ];
+        vec![
+            NettingSet::new("Uncollateralized".to_string(), "Bank A".to_string(), netting_codes.clone()),
+            NettingSet::new("Collateralized".to_string(), "Bank A".to_string(), netting_codes)
+                .with_collateral_agreement(CollateralAgreement::new(10_000_000.0, 10_000_000.0, 1_000_000.0)),
+        ]
+    }
 
-        let mut bond_discount_curve_map = HashMap::new();
-        bond_discount_curve_map.insert(
-            (issuer_name.to_string(), issuer_type, credit_rating, bond_currency), "KRWGOV".to_string()
-        );
+    /// daily values up to the day before evaluation_datetime
+    fn history(values: &[Real], code: &str) -> DailyValueData {
+        let dt = evaluation_datetime();
+        DailyValueData::new(
+            values.iter().enumerate()
+                .map(|(i, v)| (dt.date() - Duration::days((values.len() - i) as i64), *v))
+                .collect(),
+            time::Time::from_hms(15, 40, 0).unwrap(),
+            time::UtcOffset::from_hms(9, 0, 0).unwrap(),
+            Calendar::SouthKorea(SouthKorea::new(SouthKoreaType::Krx)),
+            code.to_string(),
+            code.to_string(),
+        )
+    }
 
-        let rate_index_curve_map = HashMap::new();
+    fn risk_factor_histories() -> Result<Vec<RiskFactorHistory>> {
+        Ok(vec![
+            RiskFactorHistory::new(
+                RiskFactor::Equity { code: "KOSPI2".to_string() },
+                ShockType::Relative,
+                history(&[350.0, 357.0, 346.29, 339.36, 349.54, 342.55], "KOSPI2"),
+            )?,
+            RiskFactorHistory::new(
+                RiskFactor::Curve { curve_name: "KRWGOV".to_string(), start_tenor: None, end_tenor: None },
+                ShockType::Absolute,
+                history(&[0.0330, 0.0335, 0.0331, 0.0340, 0.0338, 0.0332], "KRWGOV"),
+            )?,
+        ])
+    }
 
-        let mut crs_curve_map = HashMap::new();
-        crs_curve_map.insert(Currency::KRW, "KRWCRS".to_string());
-        crs_curve_map.insert(Currency::USD, "USDOIS".to_string());
+    fn historical_var_report(engine_generator: &EngineGenerator) -> Result<HistoricalVarReport> {
+        HistoricalVar::new(
+            HistoricalVarConfiguration::default()
+                .with_confidence_levels(vec![0.8])
+                .with_number_of_chunks(2),
+            risk_factor_histories()?,
+        )?.calculate(engine_generator)
+    }
 
-        let mut funding_cost_map = HashMap::new();
-        funding_cost_map.insert(Currency::KRW, funding_curve1.clone());
+    /// the greeks calculated by the bump jobs, in parallel if parallel_bumps
+    fn bump_configuration(parallel_bumps: bool) -> CalculationConfiguration {
+        greeks_configuration()
+            .npv_only()
+            .with_delta_calculation(true)
+            .with_gamma_calculation(true)
+            .with_vega_calculation(true)
+            .with_rho_calculation(true)
+            .with_div_delta_calculation(true)
+            .with_vega_structure_calculation(true)
+            .with_rho_structure_calculation(true)
+            .with_div_structure_calculation(true)
+            .with_vega_matrix_calculation(true)
+            .with_parallel_bumps(parallel_bumps)
+    }
 
-        let match_parameter = MatchParameter::new(
-            collateral_curve_map,
-            borrowing_curve_map,
-            bond_discount_curve_map,
-            crs_curve_map,
-            rate_index_curve_map,
-            funding_cost_map,        
-        );
+    fn assert_same_greeks(
+        serial_results: &HashMap<String, CalculationResult>,
+        parallel_results: &HashMap<String, CalculationResult>,
+    ) -> Result<()> {
+        // npvs (f32) of different engines may differ in the last digit by the orders of the hash maps,
+        // which is amplified by 1 / bump in the greeks
+        let close = |a: &Real, b: &Real| (a - b).abs() <= 5e-3 * a.abs().max(b.abs()).max(1.0);
+        for (code, result) in serial_results.iter() {
+            let parallel = parallel_results.get(code)
+                .ok_or_else(|| anyhow::anyhow!("No parallel result for {}", code))?;
+            for (name, serial_map, parallel_map) in [
+                ("delta", result.get_delta(), parallel.get_delta()),
+                ("gamma", result.get_gamma(), parallel.get_gamma()),
+                ("vega", result.get_vega(), parallel.get_vega()),
+                ("rho", result.get_rho(), parallel.get_rho()),
+                ("div_delta", result.get_div_delta(), parallel.get_div_delta()),
+                ("volga", result.get_volga(), parallel.get_volga()),
+                ("fx_delta", result.get_fx_delta(), parallel.get_fx_delta()),
+                ("fx_gamma", result.get_fx_gamma(), parallel.get_fx_gamma()),
+                ("fx_vega", result.get_fx_vega(), parallel.get_fx_vega()),
+            ] {
+                let (serial_map, parallel_map) = (serial_map.cloned().unwrap_or_default(), parallel_map.cloned().unwrap_or_default());
+                assert_eq!(serial_map.len(), parallel_map.len(), "{} {}", code, name);
+                for (key, value) in serial_map.iter() {
+                    assert!(
+                        parallel_map.get(key).is_some_and(|parallel| close(value, parallel)),
+                        "{} {} of {}: {} vs {:?}", code, name, key, value, parallel_map.get(key),
+                    );
+                }
+            }
+            for (name, serial_map, parallel_map) in [
+                ("vega_structure", result.get_vega_structure(), parallel.get_vega_structure()),
+                ("rho_structure", result.get_rho_structure(), parallel.get_rho_structure()),
+                ("div_structure", result.get_div_structure(), parallel.get_div_structure()),
+            ] {
+                let (serial_map, parallel_map) = (serial_map.cloned().unwrap_or_default(), parallel_map.cloned().unwrap_or_default());
+                assert_eq!(serial_map.len(), parallel_map.len(), "{} {}", code, name);
+                for (key, values) in serial_map.iter() {
+                    let parallel_values = parallel_map.get(key)
+                        .ok_or_else(|| anyhow::anyhow!("No parallel {} of {} for {}", name, key, code))?;
+                    assert!(
+                        values.len() == parallel_values.len() && values.iter().zip(parallel_values.iter()).all(|(a, b)| close(a, b)),
+                        "{} {} of {}: {:?} vs {:?}", code, name, key, values, parallel_values,
+                    );
+                }
+            }
+            for (name, serial_map, parallel_map) in [
+                ("cross_gamma", result.get_cross_gamma(), parallel.get_cross_gamma()),
+                ("vanna", result.get_vanna(), parallel.get_vanna()),
+                ("quanto_correlation", result.get_quanto_correlation(), parallel.get_quanto_correlation()),
+            ] {
+                let (serial_map, parallel_map) = (serial_map.cloned().unwrap_or_default(), parallel_map.cloned().unwrap_or_default());
+                assert_eq!(serial_map.len(), parallel_map.len(), "{} {}", code, name);
+                for (key, values) in serial_map.iter() {
+                    let parallel_values = parallel_map.get(key)
+                        .ok_or_else(|| anyhow::anyhow!("No parallel {} of {} for {}", name, key, code))?;
+                    for (other, value) in values.iter() {
+                        assert!(
+                            parallel_values.get(other).is_some_and(|parallel| close(value, parallel)),
+                            "{} {} of ({}, {}): {} vs {:?}", code, name, key, other, value, parallel_values.get(other),
+                        );
+                    }
+                }
+            }
+            for (key, matrix) in result.get_vega_matrix().cloned().unwrap_or_default().iter() {
+                let parallel_matrix = parallel.get_vega_matrix()
+                    .and_then(|matrices| matrices.get(key))
+                    .ok_or_else(|| anyhow::anyhow!("No parallel vega matrix of {} for {}", key, code))?;
+                assert!(
+                    matrix.shape() == parallel_matrix.shape() && matrix.iter().zip(parallel_matrix.iter()).all(|(a, b)| close(a, b)),
+                    "{} vega matrix of {}", code, key,
+                );
+            }
+        }
+        Ok(())
+    }
 
-        let category1 = InstrumentCategory::new(
-            Some(vec![
-                "Futures".to_string(),
-                "VanillaCall".to_string(),
-                "VanillaPut".to_string(),
-                "IRS".to_string(),
-                "CRS".to_string(),
-                "FxFutures".to_string(),
-                ]),
-            Some(vec![Currency::KRW]),
-            Some(vec![
-                "KOSPI2".to_string(),
-            ])
-        );
+    #[test]
+    fn test_engine()-> Result<()> {
+        let start_time = Instant::now();
+        // Set up rolling file appender
+        let file_appender = rolling::daily("test_logs", "engine-test.log");
+        let (non_blocking_appender, _guard) = non_blocking(file_appender);
 
-        let category2 = InstrumentCategory::new(
-            Some(vec![
-                "Bond".to_string(),
-                "Cash".to_string(),
-                "Stock".to_string(),
-                ]),
-            Some(vec![Currency::KRW, Currency::USD]),
-            Some(vec![
-                "KOSPI2".to_string(),
-            ])
-        );
+        // Set up console layer
+        let custom_time = CustomOffsetTime::new(9, 0, 0);
 
-        let instrument_categories = vec![category1, category2];
-
-        let mut engine_builder = EngineGenerator::builder();
-        let engine_generator = engine_builder
-            .with_configuration(
-                calculation_configuration, 
-                dt.clone(),
-                match_parameter)?
-            .with_instruments(Instruments::new(inst_vec))?
-            .with_instrument_categories(instrument_categories)?
-            .with_data(
-                fx_data_map,
-                stock_data_map,
-                zero_curve_map,
-                dividend_data_map,
-                equity_vol_map,
-                equity_surface_map,
-                HashMap::new(),
-                HashMap::new(),
-                HashMap::new(),
-            )?;
-            
+        let err_layer = fmt::layer()
+            .with_writer(std::io::stderr.with_max_level(Level::ERROR))
+            .with_timer(custom_time.clone());
+
+        let console_layer = fmt::layer()
+            .with_writer(
+                std::io::stdout.with_max_level(Level::DEBUG))
+            .with_timer(custom_time.clone());
+
+        let file_layer = fmt::layer()
+            .with_writer(non_blocking_appender.with_max_level(Level::DEBUG).with_min_level(Level::INFO))
+            .with_timer(custom_time);
+
+        // Combine console and file layers into a subscriber
+        let subscriber = tracing_subscriber::registry()
+            .with(file_layer)
+            .with(err_layer)
+            .with(console_layer);
+
+        tracing::subscriber::set_global_default(subscriber)
+            .expect("Setting default subscriber failed");
+
+        // Create a new span with an `info` level
+        let main_span = span!(Level::INFO, "main (engine)");
+        let _enter = main_span.enter();
+
+        let theta_day = 100;
+        let dt = evaluation_datetime();
+
+        // make a calculation configuration
+        let calculation_configuration = CalculationConfiguration::default()
+            .with_delta_calculation(true)
+            .with_gamma_calculation(true)
+            .with_theta_calculation(true)
+            .with_rho_calculation(true)
+            .with_vega_calculation(true)
+            .with_vega_structure_calculation(true)
+            .with_div_delta_calculation(true)
+            .with_rho_structure_calculation(true)
+            .with_div_structure_calculation(true)
+            .with_vega_matrix_calculation(true)
+            .with_theta_day(theta_day);
+
+        let mut engine_generator = fixture()?.engine_generator(calculation_configuration, dt)?;
         engine_generator.distribute_instruments().context("Failed to distribute instruments")?;
         engine_generator.calculate().context("Failed to calculate")?;
 
@@ -506,4 +626,566 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_bond_analytics() -> Result<()> {
+        let engine_generator = fixture()?
+            .with_instruments(&["KRxxxxxxxxxx", "KR103501GCC0"])
+            .calculate(CalculationConfiguration::default().with_bond_analytics_calculation(true))?;
+        let calculation_results = engine_generator.get_calculation_results();
+
+        // the dirty price is the npv and the model price has no z-spread
+        for key in ["KRxxxxxxxxxx", "KR103501GCC0"].iter() {
+            let result = calculation_results.get(*key)
+                .ok_or_else(|| anyhow::anyhow!("No result found for key {}", key))?;
+            let npv = result.get_npv_result()
+                .ok_or_else(|| anyhow::anyhow!("No npv result found for key {}", key))?
+                .get_npv();
+            let analytics = result.get_bond_analytics()
+                .ok_or_else(|| anyhow::anyhow!("No bond analytics found for key {}", key))?
+                .get(*key)
+                .ok_or_else(|| anyhow::anyhow!("No bond analytics of {} found", key))?;
+            assert!((analytics.get_dirty_price() - npv).abs() < 1e-6);
+            assert!(
+                analytics.get_z_spread().abs() < 1e-5,
+                "z-spread of {} is not zero: {}", key, analytics.get_z_spread(),
+            );
+            assert!(analytics.get_modified_duration() > 0.0);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_implied_volatility() -> Result<()> {
+        let mut engine_generator = fixture()?
+            .with_instruments(&["165XXX3"])
+            .engine_generator(
+                CalculationConfiguration::default().with_implied_volatility_calculation(true),
+                evaluation_datetime(),
+            )?;
+        // the npv of the option at the volatility 0.2
+        engine_generator.with_option_prices(HashMap::from([("165XXX3".to_string(), 1.3148708)]))?;
+        engine_generator.distribute_instruments()?;
+        engine_generator.calculate()?;
+        let calculation_results = engine_generator.get_calculation_results();
+
+        // implied volatility from the option price and the surface built from it
+        let implied_vol = calculation_results.get("165XXX3")
+            .ok_or_else(|| anyhow::anyhow!("No result found for key 165XXX3"))?
+            .get_implied_volatility()
+            .ok_or_else(|| anyhow::anyhow!("No implied volatility found for key 165XXX3"))?;
+        assert!((implied_vol - 0.2).abs() < 1e-3, "implied volatility: {}", implied_vol);
+
+        let surfaces = engine_generator.get_implied_volatility_surfaces()?;
+        let surface = surfaces.get("KOSPI2")
+            .ok_or_else(|| anyhow::anyhow!("No implied volatility surface found for KOSPI2"))?;
+        assert_eq!(surface.get_value().shape(), &[1, 1]);
+        assert!((surface.get_value()[[0, 0]] - implied_vol).abs() < 1e-6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scenario_pnl() -> Result<()> {
+        let calculation_configuration = CalculationConfiguration::default()
+            .with_scenario_calculation(true)
+            .with_scenarios(Scenario::from_json_str(r#"[
+                {
+                    "name": "equity crash",
+                    "equity_shocks": [{ "code": "KOSPI2", "shock_type": "Relative", "value": -0.3 }],
+                    "volatility_shocks": [{ "code": "KOSPI2", "value": 0.1 }]
+                },
+                {
+                    "name": "rate up",
+                    "curve_shocks": [{ "curve_name": "KRWGOV", "value": 0.01 }]
+                }
+            ]"#)?);
+        let engine_generator = fixture()?
+            .with_instruments(&["165XXX1", "KOSPI2", "KRxxxxxxxxxx"])
+            .calculate(calculation_configuration)?;
+        let calculation_results = engine_generator.get_calculation_results();
+
+        // scenario pnl: the scenarios are put back before the next one is applied
+        let scenario_pnl = |key: &str, scenario: &str| -> Result<Real> {
+            calculation_results.get(key)
+                .ok_or_else(|| anyhow::anyhow!("No result found for key {}", key))?
+                .get_scenario_pnl()
+                .ok_or_else(|| anyhow::anyhow!("No scenario pnl found for key {}", key))?
+                .get(scenario)
+                .copied()
+                .ok_or_else(|| anyhow::anyhow!("No scenario pnl of {} found for key {}", scenario, key))
+        };
+        let stock_value = calculation_results.get("KOSPI2")
+            .ok_or_else(|| anyhow::anyhow!("No result found for key KOSPI2"))?
+            .get_value()
+            .ok_or_else(|| anyhow::anyhow!("No value found for key KOSPI2"))?;
+        let stock_crash_pnl = scenario_pnl("KOSPI2", "equity crash")?;
+        assert!(
+            (stock_crash_pnl + 0.3 * stock_value).abs() < 1e-3 * stock_value.abs(),
+            "stock pnl in equity crash: {}, value: {}", stock_crash_pnl, stock_value,
+        );
+        assert!(scenario_pnl("165XXX1", "equity crash")? < 0.0);
+        assert_eq!(scenario_pnl("KOSPI2", "rate up")?, 0.0);
+        assert!(scenario_pnl("KRxxxxxxxxxx", "rate up")? < 0.0);
+        assert_eq!(scenario_pnl("KRxxxxxxxxxx", "equity crash")?, 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_portfolio_aggregation() -> Result<()> {
+        let engine_generator = fixture()?.calculate(greeks_configuration())?;
+        let calculation_results = engine_generator.get_calculation_results();
+
+        // portfolio aggregation in KRW where the USD cash is converted by USDKRW
+        let books = HashMap::from([
+            ("165XXX3".to_string(), "Options".to_string()),
+            ("165XXX1".to_string(), "Delta One".to_string()),
+            ("165XXX2".to_string(), "Delta One".to_string()),
+        ]);
+        let groupings = vec![
+            PortfolioGrouping::Book,
+            PortfolioGrouping::InstrumentType,
+            PortfolioGrouping::Issuer,
+            PortfolioGrouping::Underlying,
+        ];
+        let portfolio_report = PortfolioAggregator::new(
+            PortfolioAggregationConfiguration::default()
+                .with_currency(Currency::KRW)
+                .with_groupings(groupings.clone())
+                .with_books(books),
+        ).aggregate(&engine_generator)?;
+        let mut expected_value = 0.0;
+        let mut expected_delta = 0.0;
+        for (code, result) in calculation_results.iter() {
+            let rate = match result.get_instrument_info().map(|info| info.get_currency()) {
+                Some(Currency::USD) => 1300.0,
+                _ => 1.0,
+            };
+            expected_value += result.get_value().unwrap_or(0.0) * rate;
+            expected_delta += result.get_delta()
+                .and_then(|delta| delta.get("KOSPI2").or_else(|| delta.get(code)))
+                .copied()
+                .unwrap_or(0.0);
+        }
+        let total = portfolio_report.get_total();
+        assert_eq!(total.instrument_codes.len(), calculation_results.len());
+        assert!((total.value - expected_value).abs() < 1e-4 * expected_value.abs(), "{} vs {}", total.value, expected_value);
+        assert!((total.delta["KOSPI2"] - expected_delta).abs() < 1e-4 * expected_delta.abs().max(1.0));
+        for grouping in groupings.iter().copied() {
+            let groups = portfolio_report.get_groups(grouping)
+                .ok_or_else(|| anyhow::anyhow!("No groups for {:?}", grouping))?;
+            let value: Real = groups.values().map(|aggregate| aggregate.value).sum();
+            assert!((value - total.value).abs() < 1e-4 * total.value.abs(), "{:?}: {} vs {}", grouping, value, total.value);
+        }
+        let options = portfolio_report.get_group(PortfolioGrouping::Book, "Options")
+            .ok_or_else(|| anyhow::anyhow!("No Options book"))?;
+        assert_eq!(options.instrument_codes, vec!["165XXX3".to_string()]);
+        assert!(options.vega_structure.contains_key("KOSPI2"));
+        assert!(portfolio_report.get_group(PortfolioGrouping::Book, UNASSIGNED_GROUP).is_some());
+        assert!(portfolio_report.get_group(PortfolioGrouping::Issuer, "Korea Gov").is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_exposure_and_cva() -> Result<()> {
+        let engine_generator = fixture()?.calculate(greeks_configuration())?;
+
+        let exposure_configuration = ExposureConfiguration::default()
+            .with_currency(Currency::KRW)
+            .with_tenors(vec!["1M".to_string(), "3M".to_string(), "6M".to_string()])
+            .with_number_of_paths(200)
+            .with_seed(1)
+            .with_curve_shock_tenors(vec!["1Y".to_string(), "3Y".to_string()])
+            .with_currency_curves(HashMap::from([(Currency::KRW, "KRWGOV".to_string())]))
+            .with_hull_white_parameters(vec![HullWhiteParameters::new(
+                Currency::KRW,
+                vec!["KRWGOV".to_string(), "KSD".to_string()],
+                0.05,
+                0.01,
+            )])
+            .with_fx_volatilities(HashMap::from([("USDKRW".to_string(), 0.1)]))
+            .with_correlation("KOSPI2".to_string(), "USDKRW".to_string(), -0.3)
+            .with_number_of_chunks(2);
+        let exposure_report = ExposureSimulation::new(exposure_configuration, netting_sets())
+            .calculate(&engine_generator)?;
+        let uncollateralized = exposure_report.get_profile("Uncollateralized")
+            .ok_or_else(|| anyhow::anyhow!("No exposure profile"))?;
+        let collateralized = exposure_report.get_profile("Collateralized")
+            .ok_or_else(|| anyhow::anyhow!("No exposure profile"))?;
+        let pfe = exposure_report.get_pfe("Uncollateralized", 0.95)
+            .ok_or_else(|| anyhow::anyhow!("No PFE"))?;
+        assert_eq!(uncollateralized.expected_exposure.len(), 3);
+        for k in 0..3 {
+            assert!(uncollateralized.expected_exposure[k] >= 0.0);
+            assert!(uncollateralized.expected_negative_exposure[k] <= 0.0);
+            assert!(pfe[k] >= uncollateralized.expected_exposure[k]);
+            assert!(collateralized.expected_exposure[k] <= uncollateralized.expected_exposure[k] + 1e-3);
+        }
+        assert!(uncollateralized.expected_positive_exposure > 0.0);
+        assert!(collateralized.expected_positive_exposure < uncollateralized.expected_positive_exposure);
+
+        let cva_report = CvaCalculator::new(HashMap::from([
+            ("Bank A".to_string(), CreditCurve::from_spread("Bank A".to_string(), 0.01, 0.4)?),
+        ]))
+            .with_own_curve(CreditCurve::from_spread("Own".to_string(), 0.005, 0.4)?)
+            .calculate(&exposure_report)?;
+        assert!(cva_report.get_cva()["Uncollateralized"] > 0.0);
+        assert!(cva_report.get_cva()["Collateralized"] < cva_report.get_cva()["Uncollateralized"]);
+        assert!(cva_report.get_total_dva() >= 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_simm() -> Result<()> {
+        let engine_generator = fixture()?.calculate(greeks_configuration())?;
+
+        let simm_parameters = SimmParameters::default();
+        let crif = SimmCrifMapper::new(
+            SimmMappingConfiguration::default()
+                .with_equity_buckets(HashMap::from([("KOSPI2".to_string(), "3".to_string())])),
+            simm_parameters.clone(),
+        ).get_crif(&engine_generator, &netting_sets())?;
+        assert!(!crif.is_empty());
+        let simm_report = SimmCalculator::new(simm_parameters).calculate(&crif)?;
+        let simm = simm_report.get_portfolio("Uncollateralized")
+            .ok_or_else(|| anyhow::anyhow!("No SIMM"))?;
+        assert!(simm.total > 0.0);
+        assert_eq!(simm.total, simm_report.get_portfolio("Collateralized").unwrap().total);
+        Ok(())
+    }
+
+    #[test]
+    fn test_frtb() -> Result<()> {
+        let fixture = fixture()?;
+        let market_data = &fixture.market_data;
+        // curvature scenarios of the FRTB risk factors
+        let frtb_mapper = FrtbSensitivityMapper::new(
+            FrtbMappingConfiguration::default()
+                .with_borrowing_curves(vec!["KOSPI2".to_string()])
+                .with_equity_buckets(HashMap::from([("KOSPI2".to_string(), "13".to_string())])),
+            FrtbParameters::default(),
+        );
+        let curvature_scenarios = frtb_mapper.get_curvature_scenarios(
+            &market_data.zero_curve_map,
+            &market_data.stock_data_map,
+            &market_data.fx_data_map,
+        )?;
+
+        let calculation_configuration = greeks_configuration()
+            .with_curvature_calculation(true)
+            .with_curvature_scenarios(curvature_scenarios);
+        let engine_generator = fixture.calculate(calculation_configuration)?;
+
+        let frtb_positions = frtb_mapper.get_positions(&engine_generator)?;
+        assert!(!frtb_positions.curvatures.is_empty());
+        let frtb_report = FrtbCalculator::new(FrtbParameters::default()).calculate(&frtb_positions)?;
+        info!("FRTB: {:?}", frtb_report);
+        assert!(frtb_report.get_sbm_capital() > 0.0);
+        assert!(frtb_report.get_drc().total > 0.0);
+        assert!(frtb_report.get_total() > frtb_report.get_sbm_capital());
+        Ok(())
+    }
+
+    #[test]
+    fn test_historical_var() -> Result<()> {
+        let engine_generator = fixture()?.calculate(CalculationConfiguration::default())?;
+        let stock_value = engine_generator.get_calculation_results().get("KOSPI2")
+            .ok_or_else(|| anyhow::anyhow!("No result found for key KOSPI2"))?
+            .get_value()
+            .ok_or_else(|| anyhow::anyhow!("No value found for key KOSPI2"))?;
+
+        // the returns of the histories are applied to today's market
+        let report = historical_var_report(&engine_generator)?;
+        assert_eq!(report.get_portfolio_pnl().len(), 5);
+        let stock_pnl = &report.get_instrument_pnl()["KOSPI2"];
+        assert!((stock_pnl[0] - 0.02 * stock_value).abs() < 1e-3 * stock_value.abs(), "stock pnl: {:?}", stock_pnl);
+        let var = report.get_var()[0];
+        assert!(var >= -report.get_portfolio_pnl().iter().cloned().fold(Real::MIN, Real::max));
+        let component_sum: Real = report.get_component_var().values().map(|v| v[0]).sum();
+        assert!((component_sum - var).abs() < 1e-3 * var.abs().max(1.0), "component sum: {}, var: {}", component_sum, var);
+        let underlying_sum: Real = report.get_underlying_component_var().values().map(|v| v[0]).sum();
+        assert!((underlying_sum - var).abs() < 1e-3 * var.abs().max(1.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parametric_var() -> Result<()> {
+        let engine_generator = fixture()?.calculate(greeks_configuration())?;
+
+        // parametric var on the returns of the historical var reconciles with the full revaluation
+        let report = historical_var_report(&engine_generator)?;
+        let parametric_var = ParametricVar::new(
+            ParametricVarConfiguration::default().with_confidence_levels(vec![0.8]),
+            risk_factor_histories()?,
+        )?;
+        let parametric_report = parametric_var.calculate(&engine_generator)?;
+        let reconciliation = parametric_report.reconcile(&report)?;
+        let max_pnl = report.get_portfolio_pnl().iter().fold(0.0 as Real, |m, p| m.max(p.abs()));
+        for (full, unexplained) in reconciliation.get_full_revaluation_pnl().iter()
+            .zip(reconciliation.get_unexplained_pnl().iter()) {
+            assert!(
+                unexplained.abs() < 0.01 * max_pnl,
+                "full revaluation pnl: {}, unexplained pnl: {}", full, unexplained,
+            );
+        }
+        assert!(parametric_report.get_delta_gamma_var()[0] > 0.0);
+        assert_eq!(parametric_report.get_risk_class_sensitivity_pnl().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pnl_explain() -> Result<()> {
+        let dt = evaluation_datetime();
+        let engine_generator = fixture()?.calculate(greeks_configuration())?;
+
+        // pnl explain from dt to the next day where KOSPI2 is up 1% and its volatility is up 1%
+        let next_dt = dt + Duration::days(1);
+        let mut next_stock_data_map = HashMap::new();
+        next_stock_data_map.insert(
+            "KOSPI2".to_string(),
+            ValueData::new(SPOT * 1.01, Some(next_dt), Currency::KRW, "KOSPI2".to_string(), "KOSPI2".to_string())?,
+        );
+        let mut next_equity_vol_map = HashMap::new();
+        next_equity_vol_map.insert(
+            "KOSPI2".to_string(),
+            ValueData::new(0.21, Some(next_dt), Currency::KRW, "KOSPI2".to_string(), "KOSPI2".to_string())?,
+        );
+        let mut next_fixture = fixture()?;
+        next_fixture.market_data.stock_data_map = next_stock_data_map;
+        next_fixture.market_data.equity_vol_map = next_equity_vol_map;
+        let mut next_engine_generator = next_fixture.engine_generator(greeks_configuration().npv_only(), next_dt)?;
+        next_engine_generator.distribute_instruments()?;
+        next_engine_generator.calculate()?;
+
+        let sensitivity_explain = PnlExplain::new(PnlExplainMethod::Sensitivity)
+            .calculate(&engine_generator, &next_engine_generator)?;
+        let waterfall_explain = PnlExplain::new(PnlExplainMethod::Waterfall)
+            .calculate(&engine_generator, &next_engine_generator)?;
+        for code in ["165XXX1", "165XXX3", "KOSPI2", "KRxxxxxxxxxx"] {
+            let sensitivity = sensitivity_explain.get_attribution(code)
+                .ok_or_else(|| anyhow::anyhow!("No pnl explain for {}", code))?;
+            let waterfall = waterfall_explain.get_attribution(code)
+                .ok_or_else(|| anyhow::anyhow!("No pnl explain for {}", code))?;
+            // theta is averaged over theta_day, so a part of the one day carry is left in the residual
+            let gross = sensitivity.theta.abs() + sensitivity.delta.abs() + sensitivity.gamma.abs()
+                + sensitivity.vega.abs() + sensitivity.vanna.abs() + sensitivity.volga.abs() + sensitivity.rho.abs();
+            assert!(
+                sensitivity.residual.abs() < 0.05 * gross.max(1.0),
+                "{}: sensitivity explain {:?}", code, sensitivity,
+            );
+            assert!(
+                waterfall.residual.abs() < 1e-3 * waterfall.total.abs().max(1.0),
+                "{}: waterfall explain {:?}", code, waterfall,
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_second_order_greeks() -> Result<()> {
+        let engine_generator = fixture()?.calculate(greeks_configuration())?;
+        let calculation_results = engine_generator.get_calculation_results();
+
+        // second order greeks of the vanilla option on a single underlying
+        let option_result = calculation_results.get("165XXX3")
+            .ok_or_else(|| anyhow::anyhow!("No result found for key 165XXX3"))?;
+        let vanna = option_result.get_vanna()
+            .and_then(|vanna| vanna.get("KOSPI2"))
+            .and_then(|row| row.get("KOSPI2"))
+            .ok_or_else(|| anyhow::anyhow!("No vanna found for 165XXX3"))?;
+        let volga = option_result.get_volga()
+            .and_then(|volga| volga.get("KOSPI2"))
+            .ok_or_else(|| anyhow::anyhow!("No volga found for 165XXX3"))?;
+        assert!(vanna.is_finite() && *volga > 0.0, "vanna: {}, volga: {}", vanna, volga);
+        assert!(option_result.get_cross_gamma().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fx_sensitivities() -> Result<()> {
+        let engine_generator = fx_fixture()?
+            .with_instruments(&["USDKRW Sep24"])
+            .calculate(greeks_configuration())?;
+        let calculation_results = engine_generator.get_calculation_results();
+
+        // fx futures is linear in the fx rate: fx delta = npv * DELTA_PNL_UNIT * unit_notional and fx gamma = 0
+        let fx_futures_result = calculation_results.get("USDKRW Sep24")
+            .ok_or_else(|| anyhow::anyhow!("No result found for key USDKRW Sep24"))?;
+        let fx_futures_npv = fx_futures_result.get_npv_result()
+            .ok_or_else(|| anyhow::anyhow!("No npv found for USDKRW Sep24"))?
+            .get_npv();
+        let fx_delta = fx_futures_result.get_fx_delta()
+            .and_then(|fx_delta| fx_delta.get("USDKRW"))
+            .ok_or_else(|| anyhow::anyhow!("No fx delta found for USDKRW Sep24"))?;
+        let fx_gamma = fx_futures_result.get_fx_gamma()
+            .and_then(|fx_gamma| fx_gamma.get("USDKRW"))
+            .ok_or_else(|| anyhow::anyhow!("No fx gamma found for USDKRW Sep24"))?;
+        let expected_fx_delta = fx_futures_npv * 0.01 * 10_000.0;
+        assert!(
+            (fx_delta - expected_fx_delta).abs() < 1.0e-3 * expected_fx_delta.abs(),
+            "fx delta: {}, expected: {}", fx_delta, expected_fx_delta,
+        );
+        assert!(fx_gamma.abs() < 1.0e-3 * expected_fx_delta.abs(), "fx gamma: {}", fx_gamma);
+        assert!(fx_futures_result.get_delta().is_none_or(|delta| delta.is_empty()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fx_vega_quanto_correlation() -> Result<()> {
+        let configuration = CalculationConfiguration::default()
+            .with_fx_vega_calculation(true)
+            .with_quanto_correlation_calculation(true);
+        let engine_generator = fx_fixture()?
+            .with_instruments(&["165QXX3"])
+            .calculate(configuration)?;
+        let result = engine_generator.get_calculation_results().get("165QXX3")
+            .ok_or_else(|| anyhow::anyhow!("No result found for key 165QXX3"))?;
+        let fx_vega = result.get_fx_vega()
+            .and_then(|fx_vega| fx_vega.get("KRWUSD"))
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("No fx vega found for 165QXX3"))?;
+        let quanto_correlation = result.get_quanto_correlation()
+            .and_then(|correlation| correlation.get("KOSPI2"))
+            .and_then(|row| row.get("KRWUSD"))
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("No quanto correlation found for 165QXX3"))?;
+        assert!(fx_vega.abs() > 0.0, "fx vega: {}", fx_vega);
+        // the quanto adjustment is a function of fx volatility * correlation where both are 0.2,
+        // so that the same bumps of the two (0.01 by default) give the same pnl
+        assert!(
+            (fx_vega - quanto_correlation).abs() < 1e-2 * fx_vega.abs(),
+            "fx vega: {}, quanto correlation: {}", fx_vega, quanto_correlation,
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_trade_loader() -> Result<()> {
+        let inst_vec = [fixture()?.instruments, fx_fixture()?.instruments].concat();
+
+        // the trades are written and loaded back in json and yaml
+        for format in [TradeFileFormat::Json, TradeFileFormat::Yaml] {
+            let text = write_trades_to_string(&Instruments::new(inst_vec.clone()), format)?;
+            let loaded = load_trades_from_str(&text, format)?;
+            assert_eq!(loaded.len(), inst_vec.len());
+            for (inst, loaded) in inst_vec.iter().zip(loaded.iter()) {
+                assert_eq!(inst.get_code(), loaded.get_code());
+                assert_eq!(inst.get_type_name(), loaded.get_type_name());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_bumps() -> Result<()> {
+        // the bump revaluations in parallel on the replicas of the engines give the same greeks
+        let mut bump_results = Vec::new();
+        for parallel_bumps in [false, true] {
+            let engine_generator = fixture()?.calculate(bump_configuration(parallel_bumps))?;
+            bump_results.push(engine_generator.get_calculation_results().clone());
+        }
+        assert_same_greeks(&bump_results[0], &bump_results[1])?;
+
+        // the cross, fx and quanto greeks are bumped on the replicas in the same way
+        let mut fx_results = Vec::new();
+        for parallel_bumps in [false, true] {
+            let configuration = greeks_configuration()
+                .with_fx_vega_calculation(true)
+                .with_quanto_correlation_calculation(true)
+                .with_parallel_bumps(parallel_bumps);
+            let engine_generator = fx_fixture()?.calculate(configuration)?;
+            fx_results.push(engine_generator.get_calculation_results().clone());
+        }
+        assert_same_greeks(&fx_results[0], &fx_results[1])?;
+        let quanto_result = fx_results[1].get("165QXX3")
+            .ok_or_else(|| anyhow::anyhow!("No result found for key 165QXX3"))?;
+        assert!(quanto_result.get_cross_gamma().is_some_and(|cross_gamma| !cross_gamma.is_empty()));
+        assert!(quanto_result.get_fx_vega().is_some_and(|fx_vega| !fx_vega.is_empty()));
+
+        // the replicas are bumped under the scenario applied to the engine
+        let scenario = Scenario::new("equity down".to_string())
+            .with_equity_shock("KOSPI2".to_string(), ShockType::Relative, -0.1)
+            .with_curve_shock("KRWGOV".to_string(), None, None, 0.01);
+        let fixture = fixture()?;
+        let market_data = &fixture.market_data;
+        let mut scenario_results = Vec::new();
+        for parallel_bumps in [false, true] {
+            let mut engine = Engine::builder(
+                0,
+                bump_configuration(parallel_bumps),
+                evaluation_datetime(),
+                fixture.match_parameter.clone(),
+            )
+                .with_instruments(fixture.instruments.iter().map(|inst| inst.as_ref().clone()).collect())?
+                .with_parameter_data(
+                    Arc::new(market_data.fx_data_map.clone()),
+                    Arc::new(market_data.stock_data_map.clone()),
+                    Arc::new(market_data.zero_curve_map.clone()),
+                    Arc::new(market_data.dividend_data_map.clone()),
+                    Arc::new(market_data.equity_vol_map.clone()),
+                    Arc::new(HashMap::new()),
+                    Arc::new(HashMap::new()),
+                    Arc::new(HashMap::new()),
+                    Arc::new(HashMap::new()),
+                )?;
+            engine.initialize_pricers()?;
+            engine.apply_scenario(&scenario)?;
+            engine.calculate()?;
+            scenario_results.push(engine.get_calculation_result_clone());
+        }
+        assert_same_greeks(&scenario_results[0], &scenario_results[1])?;
+
+        // and the greeks differ from the ones without the scenario
+        let option_delta = |results: &HashMap<String, CalculationResult>| -> Result<Real> {
+            results.get("165XXX3")
+                .and_then(|result| result.get_delta())
+                .and_then(|delta| delta.get("KOSPI2"))
+                .copied()
+                .ok_or_else(|| anyhow::anyhow!("No delta found for 165XXX3"))
+        };
+        let (delta, shocked_delta) = (option_delta(&bump_results[1])?, option_delta(&scenario_results[1])?);
+        assert!((delta - shocked_delta).abs() > 0.1 * delta.abs(), "delta: {}, shocked delta: {}", delta, shocked_delta);
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_market_data() -> Result<()> {
+        // the engines repriced in place by the ticks give the results of a new calculation on the updated data
+        let mut engine_generator = fixture()?.calculate(greeks_configuration())?;
+        for updates in [
+            vec![
+                MarketDataUpdate::Spot { code: "KOSPI2".to_string(), value: SPOT * 1.01 },
+                MarketDataUpdate::Fx { fx_code: FxCode::from("USDKRW"), value: 1310.0 },
+            ],
+            vec![
+                MarketDataUpdate::CurvePoint {
+                    code: "KRWGOV".to_string(),
+                    date: datetime!(2025-03-13 00:00:00 +09:00),
+                    value: 0.035,
+                },
+                MarketDataUpdate::ConstantVolatility { code: "KOSPI2".to_string(), value: 0.22 },
+            ],
+        ] {
+            let deltas = engine_generator.update_market_data(&updates)?;
+            assert!(!deltas.is_empty());
+        }
+
+        let mut fixture = fixture()?;
+        fixture.market_data.fx_data_map = engine_generator.get_fx_data().clone();
+        fixture.market_data.stock_data_map = engine_generator.get_stock_data().clone();
+        fixture.market_data.zero_curve_map = engine_generator.get_curve_data().clone();
+        fixture.market_data.equity_vol_map = engine_generator.get_equity_constant_volatility_data().clone();
+        let recalculated = fixture.calculate(greeks_configuration())?;
+
+        let (results, recalculated_results) = (engine_generator.get_calculation_results(), recalculated.get_calculation_results());
+        assert_eq!(results.len(), recalculated_results.len());
+        for (code, result) in recalculated_results.iter() {
+            let (value, recalculated_value) = (results[code].get_value(), result.get_value());
+            assert!(
+                value.zip(recalculated_value).is_some_and(|(a, b)| (a - b).abs() <= 1e-6 * a.abs().max(1.0)),
+                "{}: {:?} vs {:?}", code, value, recalculated_value,
+            );
+        }
+        assert_same_greeks(recalculated_results, results)?;
+        Ok(())
+    }
 }